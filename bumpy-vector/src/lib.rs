@@ -55,7 +55,8 @@
 //! }
 //! ```
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::collections::btree_map;
 use std::ops::Range;
 
 use simple_error::{SimpleResult, bail};
@@ -179,9 +180,10 @@ where
 /// Represents an instance of a Bumpy Vector
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BumpyVector<T> {
-    /// The data is represented by a HashMap, where the index is the key and
-    /// a BumpyEntry is the object.
-    data: HashMap<usize, MetaBumpyEntry<T>>,
+    /// The data is represented by a BTreeMap, where the index is the key and
+    /// a BumpyEntry is the object. It's ordered so iterating and serializing
+    /// always go in offset order.
+    data: BTreeMap<usize, MetaBumpyEntry<T>>,
 
     /// The maximum size.
     max_size: usize,
@@ -195,7 +197,7 @@ impl<'a, T> BumpyVector<T> {
     /// elements beyond the end are accessed, an error will be returned.
     pub fn new(max_size: usize) -> Self {
         BumpyVector {
-            data: HashMap::new(),
+            data: BTreeMap::new(),
             max_size: max_size,
         }
     }
@@ -612,12 +614,30 @@ impl<'a, T> BumpyVector<T> {
         result
     }
 
-    /// Returns the number of entries.
+    /// Iterate over every entry, in order of their starting index.
+    ///
+    /// Unlike [`BumpyVector::get_range`], this doesn't build a `Vec` first.
+    ///
+    /// ```
+    /// use bumpy_vector::BumpyVector;
+    ///
+    /// let mut v: BumpyVector<&str> = BumpyVector::new(10);
+    /// v.insert(("c", 6..9).into()).unwrap();
+    /// v.insert(("a", 1..3).into()).unwrap();
+    /// v.insert(("b", 3..4).into()).unwrap();
     ///
-    /// This isn't the most efficient operation, unfortunately; we can probably
-    /// improve if we need to
+    /// let entries: Vec<&str> = v.iter().map(|e| e.entry).collect();
+    /// assert_eq!(vec!["a", "b", "c"], entries);
+    /// ```
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            values: self.data.values(),
+        }
+    }
+
+    /// Returns the number of entries.
     pub fn len(&self) -> usize {
-        self.get_range(0..self.max_size()).len()
+        self.iter().count()
     }
 
     pub fn max_size(&self) -> usize {
@@ -625,17 +645,31 @@ impl<'a, T> BumpyVector<T> {
     }
 }
 
-/// Convert into an iterator.
-///
-/// Naively iterate across all entries, move them into a `Vec<_>`, and convert
-/// that vector into an iterator.
-///
+/// An iterator over the entries in a [`BumpyVector`], in order of their
+/// starting index - see [`BumpyVector::iter`].
+pub struct Iter<'a, T> {
+    values: btree_map::Values<'a, usize, MetaBumpyEntry<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a BumpyEntry<T>;
+
+    fn next(&mut self) -> Option<&'a BumpyEntry<T>> {
+        // Skip over the "middle" entries, they just point back to the start
+        self.values.find_map(|value| match value {
+            MetaBumpyEntry::Something(e) => Some(e),
+            MetaBumpyEntry::NearlySomething(_) => None,
+        })
+    }
+}
+
+/// Convert into an iterator - see [`BumpyVector::iter`].
 impl<'a, T> IntoIterator for &'a BumpyVector<T> {
     type Item = &'a BumpyEntry<T>;
-    type IntoIter = std::vec::IntoIter<&'a BumpyEntry<T>>;
+    type IntoIter = Iter<'a, T>;
 
-    fn into_iter(self) -> std::vec::IntoIter<&'a BumpyEntry<T>> {
-        return self.get_range(0..self.max_size).into_iter();
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
     }
 }
 
@@ -1010,6 +1044,30 @@ mod tests {
         assert!(iter.next().is_none());
     }
 
+    #[test]
+    fn test_iterator_order() {
+        // Insert out of order, and remove from the middle
+        let mut h: BumpyVector<&str> = BumpyVector::new(100);
+        h.insert(("d", 50..60).into()).unwrap();
+        h.insert(("a", 0..5).into()).unwrap();
+        h.insert(("x", 30..40).into()).unwrap();
+        h.insert(("c", 20..22).into()).unwrap();
+        h.insert(("b", 10..11).into()).unwrap();
+        h.remove(35);
+
+        let entries: Vec<(&str, Range<usize>)> = h.iter().map(|e| (e.entry, e.range.clone())).collect();
+        assert_eq!(vec![
+            ("a", 0..5),
+            ("b", 10..11),
+            ("c", 20..22),
+            ("d", 50..60),
+        ], entries);
+
+        // The iterator and get_range always agree
+        assert_eq!(h.get_range(0..100).len(), h.iter().count());
+        assert_eq!(4, h.len());
+    }
+
     #[test]
     fn test_serialize() {
         let mut h: BumpyVector<String> = BumpyVector::new(10);
@@ -1,5 +1,6 @@
 use simple_error::SimpleResult;
 use serde::{Serialize, Deserialize};
+use std::fmt;
 
 use crate::{Context, Endian, Character};
 
@@ -7,7 +8,7 @@ use crate::{Context, Endian, Character};
 ///
 /// Importantly, this can be serialized, which means it can be stored and
 /// re-used in the future.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CharacterReader {
     /// 8-bit ASCII Character
     ASCII,
@@ -64,4 +65,31 @@ impl CharacterReader {
             Self::UTF32(_) => Some(4),
         }
     }
+
+    /// The size - in bytes - of one code unit.
+    ///
+    /// Variable-length encodings are made up of one or more code units per
+    /// character: a UTF-8 character is 1 - 4 single-byte units, and a UTF-16
+    /// character is 1 or 2 two-byte units. Formats that store a string's
+    /// length often count these rather than characters.
+    pub fn code_unit_size(self) -> usize {
+        match self {
+            Self::ASCII    => 1,
+            Self::UTF8     => 1,
+            Self::UTF16(_) => 2,
+            Self::UTF32(_) => 4,
+        }
+    }
+}
+
+impl fmt::Display for CharacterReader {
+    /// Display the reader in a short form - `ascii`, `utf8`, `utf16le`, etc.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ASCII         => write!(f, "ascii"),
+            Self::UTF8          => write!(f, "utf8"),
+            Self::UTF16(endian) => write!(f, "utf16{}", endian),
+            Self::UTF32(endian) => write!(f, "utf32{}", endian),
+        }
+    }
 }
@@ -0,0 +1,148 @@
+use simple_error::{SimpleResult, bail};
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+use crate::{Endian, Character, CharacterReader};
+
+/// Defines how a [`Character`] is written back out as bytes.
+///
+/// This is the counterpart to [`CharacterReader`], with the same encodings
+/// and [`Endian`] options - a reader can be converted into the matching
+/// writer with `From` / `Into`.
+///
+/// Like the readers, this can be serialized, which means it can be stored and
+/// re-used in the future.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CharacterWriter {
+    /// 8-bit ASCII Character
+    ASCII,
+
+    /// 8 - 32-bit UTF8 character
+    UTF8,
+
+    /// 16 or 32-bit UTF16 character
+    UTF16(Endian),
+
+    /// 32-bit UTF32 character
+    UTF32(Endian),
+}
+
+impl From<CharacterReader> for CharacterWriter {
+    fn from(o: CharacterReader) -> Self {
+        match o {
+            CharacterReader::ASCII         => Self::ASCII,
+            CharacterReader::UTF8          => Self::UTF8,
+            CharacterReader::UTF16(endian) => Self::UTF16(endian),
+            CharacterReader::UTF32(endian) => Self::UTF32(endian),
+        }
+    }
+}
+
+impl CharacterWriter {
+    /// Write `value` as bytes.
+    ///
+    /// The character is encoded fresh, so its size is whatever this encoding
+    /// needs rather than [`Character::size`]. Characters that the encoding
+    /// can't represent (anything past `0x7f`, for ASCII) are an error.
+    pub fn write(self, value: Character) -> SimpleResult<Vec<u8>> {
+        let c = value.as_char();
+
+        match self {
+            Self::ASCII => {
+                if !c.is_ascii() {
+                    bail!("Character {:?} can't be written as ASCII", c);
+                }
+
+                Ok(vec![c as u8])
+            },
+
+            Self::UTF8 => {
+                let mut buffer = [0; 4];
+                Ok(c.encode_utf8(&mut buffer).as_bytes().to_vec())
+            },
+
+            // One or two code units, each with the endian applied
+            Self::UTF16(endian) => {
+                let mut buffer = [0; 2];
+                Ok(c.encode_utf16(&mut buffer).iter().flat_map(|unit| match endian {
+                    Endian::Big    => unit.to_be_bytes(),
+                    Endian::Little => unit.to_le_bytes(),
+                }).collect())
+            },
+
+            Self::UTF32(Endian::Big)    => Ok((c as u32).to_be_bytes().to_vec()),
+            Self::UTF32(Endian::Little) => Ok((c as u32).to_le_bytes().to_vec()),
+        }
+    }
+
+    /// The size - in bytes - that will be written by [`Self::write`].
+    ///
+    /// Note that not all types have a pre-defined size; those return [`None`].
+    pub fn size(self) -> Option<usize> {
+        match self {
+            Self::ASCII    => Some(1),
+            Self::UTF8     => None,
+            Self::UTF16(_) => None,
+            Self::UTF32(_) => Some(4),
+        }
+    }
+}
+
+impl fmt::Display for CharacterWriter {
+    /// Display the writer in a short form - `ascii`, `utf8`, `utf16le`, etc.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ASCII         => write!(f, "ascii"),
+            Self::UTF8          => write!(f, "utf8"),
+            Self::UTF16(endian) => write!(f, "utf16{}", endian),
+            Self::UTF32(endian) => write!(f, "utf32{}", endian),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+
+    use crate::Context;
+
+    #[test]
+    fn test_write() -> SimpleResult<()> {
+        let snowflake = Character::from(('❄', 3));
+
+        assert_eq!(b"A".to_vec(),                CharacterWriter::ASCII.write(Character::from(('A', 1)))?);
+        assert_eq!(b"\xe2\x9d\x84".to_vec(),     CharacterWriter::UTF8.write(snowflake)?);
+        assert_eq!(b"\x27\x44".to_vec(),         CharacterWriter::UTF16(Endian::Big).write(snowflake)?);
+        assert_eq!(b"\x44\x27\x00\x00".to_vec(), CharacterWriter::UTF32(Endian::Little).write(snowflake)?);
+
+        // Surrogate pairs
+        assert_eq!(b"\x3d\xd8\x00\xde".to_vec(), CharacterWriter::UTF16(Endian::Little).write(Character::from(('😀', 4)))?);
+
+        // Not ASCII
+        assert!(CharacterWriter::ASCII.write(snowflake).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> SimpleResult<()> {
+        let readers = vec![
+            (CharacterReader::ASCII,                 b"A".to_vec()),
+            (CharacterReader::UTF8,                  b"\xf0\x9f\x98\x80".to_vec()),
+            (CharacterReader::UTF16(Endian::Big),    b"\xd8\x3d\xde\x00".to_vec()),
+            (CharacterReader::UTF32(Endian::Little), b"\x00\xf6\x01\x00".to_vec()),
+        ];
+
+        for (reader, data) in readers {
+            let writer = CharacterWriter::from(reader);
+            let value = reader.read(Context::new(&data))?;
+
+            assert_eq!(reader.size(), writer.size());
+            assert_eq!(reader.to_string(), writer.to_string());
+            assert_eq!(data, writer.write(value)?);
+        }
+
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 use simple_error::SimpleResult;
 use serde::{Serialize, Deserialize};
-use std::mem;
+use std::{fmt, mem};
 
 use crate::{Context, Endian, Float};
 
@@ -10,6 +10,10 @@ use crate::{Context, Endian, Float};
 /// re-used in the future.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum FloatReader {
+    /// 16-bit (half-precision) float, read into an [`f32`] - every half
+    /// value fits exactly
+    F16(Endian),
+
     /// 32-bit float
     F32(Endian),
 
@@ -27,6 +31,7 @@ impl FloatReader {
     /// match the type that we chose in this struct.
     pub fn read(self, context: Context) -> SimpleResult<Float> {
         match self {
+            Self::F16(endian) => Ok(Float::from(context.read_f16(endian)?)),
             Self::F32(endian) => Ok(Float::from(context.read_f32(endian)?)),
             Self::F64(endian) => Ok(Float::from(context.read_f64(endian)?)),
         }
@@ -35,12 +40,53 @@ impl FloatReader {
     /// The size - in bytes - that will be read by [`Self::read`].
     pub fn size(self) -> usize {
         match self {
+            Self::F16(_)  => mem::size_of::<u16>(),
             Self::F32(_)  => mem::size_of::<f32>(),
             Self::F64(_)  => mem::size_of::<f64>(),
         }
     }
 }
 
+impl fmt::Display for FloatReader {
+    /// Display the reader in a short, C-like form - `f32le`, `f64be`, etc.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::F16(endian) => write!(f, "f16{}", endian),
+            Self::F32(endian) => write!(f, "f32{}", endian),
+            Self::F64(endian) => write!(f, "f64{}", endian),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+
+    #[test]
+    fn test_display() -> SimpleResult<()> {
+        assert_eq!("f16le", FloatReader::F16(Endian::Little).to_string());
+        assert_eq!("f32be", FloatReader::F32(Endian::Big).to_string());
+        assert_eq!("f64le", FloatReader::F64(Endian::Little).to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_f16() -> SimpleResult<()> {
+        let data = b"\x3e\x00\xc4\x00\x7b\xff\x00\x01\x7c\x00".to_vec();
+        let reader = FloatReader::F16(Endian::Big);
+        assert_eq!(2, reader.size());
+
+        assert_eq!("1.5",               reader.read(Context::new_at(&data, 0))?.to_string());
+        assert_eq!("-4",                reader.read(Context::new_at(&data, 2))?.to_string());
+        assert_eq!("65504",             reader.read(Context::new_at(&data, 4))?.to_string());
+        assert_eq!("0.000000059604645", reader.read(Context::new_at(&data, 6))?.to_string());
+        assert_eq!("inf",               reader.read(Context::new_at(&data, 8))?.to_string());
+
+        let data = b"\x00\x3e".to_vec();
+        assert_eq!("1.5", FloatReader::F16(Endian::Little).read(Context::new(&data))?.to_string());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,181 @@
+use simple_error::SimpleResult;
+use serde::{Serialize, Deserialize};
+use std::{fmt, mem};
+
+use crate::{Endian, Float, FloatReader};
+
+/// Defines how a [`Float`] is written back out as bytes.
+///
+/// This is the counterpart to [`FloatReader`], with the same widths and
+/// [`Endian`] options - a reader can be converted into the matching writer
+/// with `From` / `Into`.
+///
+/// Like the readers, this can be serialized, which means it can be stored and
+/// re-used in the future.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FloatWriter {
+    /// 16-bit (half-precision) float, rounded to the nearest half value
+    F16(Endian),
+
+    /// 32-bit float
+    F32(Endian),
+
+    /// 64-bit float
+    F64(Endian),
+}
+
+impl From<FloatReader> for FloatWriter {
+    fn from(o: FloatReader) -> Self {
+        match o {
+            FloatReader::F16(endian) => Self::F16(endian),
+            FloatReader::F32(endian) => Self::F32(endian),
+            FloatReader::F64(endian) => Self::F64(endian),
+        }
+    }
+}
+
+/// Convert an [`f32`] to the bits of an IEEE 754 half-precision float.
+///
+/// Values round to the nearest half (ties to even); anything too big becomes
+/// infinity, and anything too small becomes zero, same as a cast would.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7fffff;
+
+    // Infinity and NaN (keep NaNs as NaNs)
+    if exponent == 0xff {
+        return match mantissa {
+            0 => sign | 0x7c00,
+            _ => sign | 0x7e00,
+        };
+    }
+
+    // Round off the low `shift` bits of `mantissa`, ties to even
+    let round = |mantissa: u32, shift: u32| -> u32 {
+        let kept = mantissa >> shift;
+        let remainder = mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+
+        if remainder > halfway || (remainder == halfway && kept & 1 == 1) {
+            kept + 1
+        } else {
+            kept
+        }
+    };
+
+    let exponent = exponent - 127 + 15;
+    match exponent {
+        // Too big
+        31..=i32::MAX => sign | 0x7c00,
+
+        // Subnormal (the implicit 1 becomes explicit), or too small
+        -10..=0       => sign | round(mantissa | 0x800000, (14 - exponent) as u32) as u16,
+        i32::MIN..=-11 => sign,
+
+        // Normal - rounding can carry into the exponent, which is what we want
+        // (up to and including infinity)
+        _             => sign | (((exponent as u32) << 10) + round(mantissa, 13)) as u16,
+    }
+}
+
+impl FloatWriter {
+    /// Write `value` as bytes.
+    ///
+    /// The value is converted to the writer's width first, so precision can
+    /// be lost when writing an [`f64`] as a smaller type.
+    pub fn write(self, value: Float) -> SimpleResult<Vec<u8>> {
+        let value = match value {
+            Float::F32(f) => f as f64,
+            Float::F64(f) => f,
+        };
+
+        match self {
+            Self::F16(Endian::Big)    => Ok(f32_to_f16(value as f32).to_be_bytes().to_vec()),
+            Self::F16(Endian::Little) => Ok(f32_to_f16(value as f32).to_le_bytes().to_vec()),
+            Self::F32(Endian::Big)    => Ok((value as f32).to_be_bytes().to_vec()),
+            Self::F32(Endian::Little) => Ok((value as f32).to_le_bytes().to_vec()),
+            Self::F64(Endian::Big)    => Ok(value.to_be_bytes().to_vec()),
+            Self::F64(Endian::Little) => Ok(value.to_le_bytes().to_vec()),
+        }
+    }
+
+    /// The size - in bytes - that will be written by [`Self::write`].
+    pub fn size(self) -> usize {
+        match self {
+            Self::F16(_)  => mem::size_of::<u16>(),
+            Self::F32(_)  => mem::size_of::<f32>(),
+            Self::F64(_)  => mem::size_of::<f64>(),
+        }
+    }
+}
+
+impl fmt::Display for FloatWriter {
+    /// Display the writer in a short, C-like form - `f32le`, `f64be`, etc.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::F16(endian) => write!(f, "f16{}", endian),
+            Self::F32(endian) => write!(f, "f32{}", endian),
+            Self::F64(endian) => write!(f, "f64{}", endian),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+
+    use crate::Context;
+
+    #[test]
+    fn test_write() -> SimpleResult<()> {
+        assert_eq!(b"\x40\x20\x00\x00".to_vec(),                 FloatWriter::F32(Endian::Big).write(Float::from(2.5f32))?);
+        assert_eq!(b"\x00\x00\x20\x40".to_vec(),                 FloatWriter::F32(Endian::Little).write(Float::from(2.5f32))?);
+        assert_eq!(b"\x3f\xf8\x00\x00\x00\x00\x00\x00".to_vec(), FloatWriter::F64(Endian::Big).write(Float::from(1.5f32))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_f16() -> SimpleResult<()> {
+        let writer = FloatWriter::F16(Endian::Big);
+
+        // The same values FloatReader's test reads
+        assert_eq!(b"\x3e\x00".to_vec(), writer.write(Float::from(1.5f32))?);
+        assert_eq!(b"\xc4\x00".to_vec(), writer.write(Float::from(-4f32))?);
+        assert_eq!(b"\x7b\xff".to_vec(), writer.write(Float::from(65504f32))?);
+        assert_eq!(b"\x00\x01".to_vec(), writer.write(Float::from(0.000000059604645f32))?);
+        assert_eq!(b"\x7c\x00".to_vec(), writer.write(Float::from(f32::INFINITY))?);
+        assert_eq!(b"\x00\x3e".to_vec(), FloatWriter::F16(Endian::Little).write(Float::from(1.5f32))?);
+
+        // Out of range, and rounding
+        assert_eq!(b"\x7c\x00".to_vec(), writer.write(Float::from(1e10f64))?);
+        assert_eq!(b"\x80\x00".to_vec(), writer.write(Float::from(-1e-10f32))?);
+        assert_eq!(b"\x3c\x00".to_vec(), writer.write(Float::from(1.0001f32))?);
+        assert_eq!(b"\x7c\x00".to_vec(), writer.write(Float::from(65520f32))?);
+
+        let nan = writer.write(Float::from(f32::NAN))?;
+        assert!(FloatReader::F16(Endian::Big).read(Context::new(&nan))?.to_string() == "NaN");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> SimpleResult<()> {
+        let data = b"\x40\x09\x21\xfb\x54\x44\x2d\x18".to_vec();
+
+        for reader in [FloatReader::F16(Endian::Big), FloatReader::F32(Endian::Little), FloatReader::F64(Endian::Big)] {
+            let writer = FloatWriter::from(reader);
+            let value = reader.read(Context::new(&data))?;
+
+            assert_eq!(reader.size(), writer.size());
+            assert_eq!(reader.to_string(), writer.to_string());
+            assert_eq!(data[0..reader.size()].to_vec(), writer.write(value)?);
+        }
+
+        Ok(())
+    }
+}
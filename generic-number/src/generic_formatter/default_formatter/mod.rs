@@ -23,27 +23,82 @@ use crate::{Integer, IntegerRenderer, IntegerRendererTrait, Float, FloatRenderer
 /// let number = Float::from(314.159f32);
 /// assert_eq!("314.159", DefaultFormatter::new_float().render(number));
 /// ```
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+///
+/// Integers can also switch to hex on their own - when they're big, or when
+/// they're offsets or sizes (see [`IntegerRenderer::render_offset`]) - so
+/// small counters stay readable while addresses and lengths look like
+/// addresses and lengths:
+///
+/// ```
+/// use generic_number::*;
+///
+/// let renderer = DefaultFormatter::new_integer_auto(Some(0xffff), true);
+///
+/// assert_eq!("1234",    renderer.render(Integer::from(1234u32)));
+/// assert_eq!("0x10000", renderer.render(Integer::from(0x10000u32)));
+/// assert_eq!("0x4d2",   renderer.render_offset(Integer::from(1234u32)));
+///
+/// // Negative numbers are never offsets, and stay decimal
+/// assert_eq!("-100000", renderer.render(Integer::from(-100000i32)));
+/// ```
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
 pub struct DefaultFormatter {
+    /// Render integers above this value in hex (`None` to always use decimal)
+    #[serde(default)]
+    pub hex_above: Option<u64>,
+
+    /// Render offsets and sizes in hex, whatever their value
+    #[serde(default)]
+    pub hex_offsets: bool,
 }
 
 impl DefaultFormatter {
     pub fn new_integer() -> IntegerRenderer {
-        IntegerRenderer::Default(Self { })
+        IntegerRenderer::Default(Self::default())
+    }
+
+    /// Render integers in decimal, unless they're above `hex_above` or (if
+    /// `hex_offsets` is set) they're offsets or sizes.
+    pub fn new_integer_auto(hex_above: Option<u64>, hex_offsets: bool) -> IntegerRenderer {
+        IntegerRenderer::Default(Self {
+            hex_above: hex_above,
+            hex_offsets: hex_offsets,
+        })
+    }
+
+    /// Render integers in decimal, but offsets, sizes, and anything over
+    /// `0xffff` in hex.
+    pub fn pretty_integer_auto() -> IntegerRenderer {
+        Self::new_integer_auto(Some(0xffff), true)
     }
 
     pub fn new_float() -> FloatRenderer {
-        FloatRenderer::Default(Self { })
+        FloatRenderer::Default(Self::default())
     }
 
     pub fn new_character() -> CharacterRenderer {
-        CharacterRenderer::Default(Self { })
+        CharacterRenderer::Default(Self::default())
+    }
+
+    fn render_hex(number: Integer) -> String {
+        format!("{:#x}", number)
     }
 }
 
 impl IntegerRendererTrait for DefaultFormatter {
     fn render_integer(&self, number: Integer) -> String {
-        format!("{}", number)
+        match (self.hex_above, number.to_u64()) {
+            (Some(threshold), Ok(value)) if value > threshold => Self::render_hex(number),
+            (Some(_), Err(_)) if !number.is_negative() => Self::render_hex(number),
+            _ => format!("{}", number),
+        }
+    }
+
+    fn render_offset(&self, number: Integer) -> String {
+        match self.hex_offsets && !number.is_negative() {
+            true  => Self::render_hex(number),
+            false => self.render_integer(number),
+        }
     }
 }
 
@@ -264,6 +319,33 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_default_auto() -> SimpleResult<()> {
+        let tests = vec![
+            // value                       hex_above      hex_offsets  expected   expected_offset
+            (Integer::from(100u32),        None,          false,       "100",     "100"),
+            (Integer::from(100u32),        None,          true,        "100",     "0x64"),
+            (Integer::from(0xffffu32),     Some(0xffff),  false,       "65535",   "65535"),
+            (Integer::from(0x10000u32),    Some(0xffff),  false,       "0x10000", "0x10000"),
+            (Integer::from(0x10000i64),    Some(0xffff),  true,        "0x10000", "0x10000"),
+            (Integer::from(-0x10000i64),   Some(0xffff),  true,        "-65536",  "-65536"),
+            (Integer::from(0u8),           Some(0),       true,        "0",       "0x0"),
+            (Integer::from(u128::MAX),     Some(0xffff),  false,       "0xffffffffffffffffffffffffffffffff", "0xffffffffffffffffffffffffffffffff"),
+        ];
+
+        for (value, hex_above, hex_offsets, expected, expected_offset) in tests {
+            let renderer = DefaultFormatter::new_integer_auto(hex_above, hex_offsets);
+
+            assert_eq!(expected,        renderer.render(value));
+            assert_eq!(expected_offset, renderer.render_offset(value));
+        }
+
+        // Other renderers treat offsets like anything else
+        assert_eq!("0x0064", crate::HexFormatter::pretty_integer().render_offset(Integer::from(100u16)));
+
+        Ok(())
+    }
+
     #[test]
     fn test_default_f32() -> SimpleResult<()> {
         let data = b"\x00\x00\x00\x00\xff\xff\xff\xff\x41\xc8\x00\x00\x40\x48\xf5\xc3".to_vec();
@@ -0,0 +1,104 @@
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, bail};
+use std::fmt;
+
+use crate::{Integer, Float, Character};
+
+/// Represents any value that can be read by a [`crate::GenericReader`].
+///
+/// This is a tagged wrapper around [`Integer`], [`Float`], and [`Character`].
+/// The tag is retained, so the caller can recover the original value with
+/// [`GenericNumber::as_integer`] and friends (or by matching on it).
+///
+/// # Example
+///
+/// ```
+/// use generic_number::*;
+///
+/// // Create a buffer
+/// let buffer = b"\x01\x02".to_vec();
+///
+/// // Create a context that points to the start of the buffer
+/// let context = Context::new_at(&buffer, 0);
+///
+/// // Create a reader - note that this is just an IntegerReader wrapped in a
+/// // GenericReader
+/// let reader = GenericReader::from(IntegerReader::U16(Endian::Big));
+///
+/// // Read a tagged value
+/// let n = reader.read(context).unwrap();
+///
+/// // Get the integer back out
+/// assert_eq!(Integer::from(0x0102u16), n.as_integer().unwrap());
+/// assert!(n.as_float().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum GenericNumber {
+    Integer(Integer),
+    Float(Float),
+    Character(Character),
+}
+
+impl From<Integer>   for GenericNumber { fn from(o: Integer)   -> Self { Self::Integer(o)   } }
+impl From<Float>     for GenericNumber { fn from(o: Float)     -> Self { Self::Float(o)     } }
+impl From<Character> for GenericNumber { fn from(o: Character) -> Self { Self::Character(o) } }
+
+impl GenericNumber {
+    /// The size - in bytes - of the value that was read.
+    pub fn size(self) -> usize {
+        match self {
+            Self::Integer(i)   => i.size(),
+            Self::Float(f)     => f.size(),
+            Self::Character(c) => c.size(),
+        }
+    }
+
+    /// Is this an [`Integer`]?
+    pub fn is_integer(self) -> bool {
+        matches!(self, Self::Integer(_))
+    }
+
+    /// Is this a [`Float`]?
+    pub fn is_float(self) -> bool {
+        matches!(self, Self::Float(_))
+    }
+
+    /// Is this a [`Character`]?
+    pub fn is_character(self) -> bool {
+        matches!(self, Self::Character(_))
+    }
+
+    /// Get the value as an [`Integer`], if possible.
+    pub fn as_integer(self) -> SimpleResult<Integer> {
+        match self {
+            Self::Integer(i) => Ok(i),
+            _                => bail!("Value is not an integer: {}", self),
+        }
+    }
+
+    /// Get the value as a [`Float`], if possible.
+    pub fn as_float(self) -> SimpleResult<Float> {
+        match self {
+            Self::Float(f) => Ok(f),
+            _              => bail!("Value is not a float: {}", self),
+        }
+    }
+
+    /// Get the value as a [`Character`], if possible.
+    pub fn as_character(self) -> SimpleResult<Character> {
+        match self {
+            Self::Character(c) => Ok(c),
+            _                  => bail!("Value is not a character: {}", self),
+        }
+    }
+}
+
+impl fmt::Display for GenericNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Integer(v)   => fmt::Display::fmt(v, f),
+            Self::Float(v)     => fmt::Display::fmt(v, f),
+            Self::Character(v) => fmt::Display::fmt(v, f),
+        }
+    }
+}
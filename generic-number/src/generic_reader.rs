@@ -0,0 +1,127 @@
+use simple_error::SimpleResult;
+use serde::{Serialize, Deserialize};
+
+use crate::{Context, GenericNumber, IntegerReader, FloatReader, CharacterReader};
+
+/// Defines how data is read from a [`Context`] to produce any kind of
+/// [`GenericNumber`].
+///
+/// This wraps an [`IntegerReader`], [`FloatReader`], or [`CharacterReader`],
+/// so code that needs to store "some reader" - composite types, data-driven
+/// loaders, etc. - doesn't need three separate code paths. Each of the
+/// specific readers can be converted with `From` / `Into`.
+///
+/// Like the other readers, this can be serialized, which means it can be
+/// stored and re-used in the future.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum GenericReader {
+    /// Read an [`crate::Integer`]
+    Integer(IntegerReader),
+
+    /// Read a [`crate::Float`]
+    Float(FloatReader),
+
+    /// Read a [`crate::Character`]
+    Character(CharacterReader),
+}
+
+impl From<IntegerReader>   for GenericReader { fn from(o: IntegerReader)   -> Self { Self::Integer(o)   } }
+impl From<FloatReader>     for GenericReader { fn from(o: FloatReader)     -> Self { Self::Float(o)     } }
+impl From<CharacterReader> for GenericReader { fn from(o: CharacterReader) -> Self { Self::Character(o) } }
+
+impl GenericReader {
+    /// Read the chosen value at the given [`Context`].
+    ///
+    /// This defers to the wrapped reader, and tags the result so the caller
+    /// can tell which type was read.
+    pub fn read(self, context: Context) -> SimpleResult<GenericNumber> {
+        match self {
+            Self::Integer(r)   => Ok(GenericNumber::from(r.read(context)?)),
+            Self::Float(r)     => Ok(GenericNumber::from(r.read(context)?)),
+            Self::Character(r) => Ok(GenericNumber::from(r.read(context)?)),
+        }
+    }
+
+    /// The size - in bytes - that will be read by [`Self::read`].
+    ///
+    /// Note that not all types have a pre-defined size (variable-length
+    /// characters, for example); those return [`None`].
+    pub fn size(self) -> Option<usize> {
+        match self {
+            Self::Integer(r)   => Some(r.size()),
+            Self::Float(r)     => Some(r.size()),
+            Self::Character(r) => r.size(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+
+    use crate::{Endian, Integer, Float, Character};
+
+    #[test]
+    fn test_read_tagged() -> SimpleResult<()> {
+        let data = b"\x41\x42\x40\x48\xf5\xc3".to_vec();
+        let context = Context::new(&data);
+
+        let n = GenericReader::from(IntegerReader::U16(Endian::Big)).read(context)?;
+        assert_eq!(GenericNumber::Integer(Integer::from(0x4142u16)), n);
+        assert_eq!(2, n.size());
+
+        let n = GenericReader::from(FloatReader::F32(Endian::Big)).read(context.at(2))?;
+        assert_eq!(GenericNumber::Float(Float::from(3.14f32)), n);
+        assert_eq!(4, n.size());
+
+        let n = GenericReader::from(CharacterReader::ASCII).read(context)?;
+        assert_eq!(GenericNumber::Character(Character::from(('A', 1))), n);
+        assert_eq!(1, n.size());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_as_type() -> SimpleResult<()> {
+        let data = b"\x41\x42".to_vec();
+        let context = Context::new(&data);
+
+        let n = GenericReader::from(IntegerReader::U8).read(context)?;
+        assert!(n.is_integer());
+        assert_eq!(Integer::from(0x41u8), n.as_integer()?);
+        assert!(n.as_float().is_err());
+        assert!(n.as_character().is_err());
+
+        let n = GenericReader::from(CharacterReader::UTF8).read(context)?;
+        assert!(n.is_character());
+        assert_eq!('A', n.as_character()?.as_char());
+        assert!(n.as_integer().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_size() -> SimpleResult<()> {
+        assert_eq!(Some(1),  GenericReader::from(IntegerReader::I8).size());
+        assert_eq!(Some(16), GenericReader::from(IntegerReader::U128(Endian::Little)).size());
+        assert_eq!(Some(8),  GenericReader::from(FloatReader::F64(Endian::Little)).size());
+        assert_eq!(Some(4),  GenericReader::from(CharacterReader::UTF32(Endian::Big)).size());
+        assert_eq!(None,     GenericReader::from(CharacterReader::UTF8).size());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_buffer_too_short() -> SimpleResult<()> {
+        let data = b"A".to_vec();
+
+        assert!(GenericReader::from(IntegerReader::U8).read(Context::new(&data)).is_ok());
+        assert!(GenericReader::from(IntegerReader::U16(Endian::Big)).read(Context::new(&data)).is_err());
+        assert!(GenericReader::from(FloatReader::F32(Endian::Big)).read(Context::new(&data)).is_err());
+        assert!(GenericReader::from(CharacterReader::UTF32(Endian::Big)).read(Context::new(&data)).is_err());
+
+        Ok(())
+    }
+}
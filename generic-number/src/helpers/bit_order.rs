@@ -0,0 +1,47 @@
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+/// Define which bit is "bit 0", for anything that numbers individual bits.
+///
+/// Software formats almost always count from the least significant bit, but
+/// hardware datasheets and network RFCs often count from the most
+/// significant one; mixing them up flips every annotation. Anything that
+/// numbers bits - bitmasks, [`crate::Context::read_bit`] - takes one of these
+/// so they all agree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BitOrder {
+    /// Bit 0 is the least significant bit (eg, bit 0 of `0x01` is set)
+    Lsb0,
+
+    /// Bit 0 is the most significant bit (eg, bit 0 of `0x80` is set)
+    Msb0,
+}
+
+impl BitOrder {
+    /// How far right to shift a `width`-bit value to bring bit number `bit`
+    /// down to the least significant bit.
+    ///
+    /// `bit` must be less than `width`.
+    pub fn shift(self, bit: usize, width: usize) -> usize {
+        match self {
+            Self::Lsb0 => bit,
+            Self::Msb0 => width - 1 - bit,
+        }
+    }
+}
+
+impl Default for BitOrder {
+    fn default() -> Self {
+        Self::Lsb0
+    }
+}
+
+impl fmt::Display for BitOrder {
+    /// Display the bit order in short form (`lsb0` / `msb0`).
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Lsb0 => write!(f, "lsb0"),
+            Self::Msb0 => write!(f, "msb0"),
+        }
+    }
+}
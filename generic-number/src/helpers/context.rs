@@ -1,9 +1,9 @@
-use std::io::{Cursor, Read};
+use std::io::Read;
 
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
 use simple_error::{SimpleError, SimpleResult, bail};
 
-use crate::Endian;
+use crate::{BitOrder, ContextSource, Endian};
 
 /// The maximum size of a UTF8 character
 pub const MAX_UTF8_BYTES: usize = 4;
@@ -11,15 +11,33 @@ pub const MAX_UTF8_BYTES: usize = 4;
 /// The maximum number of 2-byte words in a UTF16 character
 pub const MAX_UTF16_WORDS: usize = 2;
 
-/// A structure to hold a data structure and a position while reading the data.
+/// A structure to hold a data source and a position while reading the data.
 ///
-/// This is essentially a [`Cursor`], but with some convenience functions to
-/// clone and set the position more quickly.
+/// This is essentially a [`std::io::Cursor`], but with some convenience
+/// functions to clone and set the position more quickly. The data comes from
+/// a [`ContextSource`], which is usually an in-memory [`Vec<u8>`] but can
+/// also stream from a file that's too big to load.
 #[derive(Debug, Clone, Copy)]
 pub struct Context<'a> {
-    v: &'a Vec<u8>,
+    v: &'a dyn ContextSource,
     position: u64,
-    //c: Cursor<&'a Vec<u8>>,
+}
+
+/// A [`Read`]er over a [`ContextSource`], starting at some position.
+///
+/// This is what lets us use [`ReadBytesExt`] on any source.
+struct SourceReader<'a> {
+    source: &'a dyn ContextSource,
+    position: u64,
+}
+
+impl<'a> Read for SourceReader<'a> {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let size = self.source.read_at(self.position, buffer)?;
+        self.position += size as u64;
+
+        Ok(size)
+    }
 }
 
 impl<'a> Context<'a> {
@@ -27,11 +45,7 @@ impl<'a> Context<'a> {
     ///
     /// Cannot fail, even if the Vec is empty.
     pub fn new(v: &'a Vec<u8>) -> Self {
-        Self {
-            v: v,
-            position: 0,
-            //c: Cursor::new(v)
-        }
+        Self::from_source(v)
     }
 
     /// Create a new [`Context`] at a given position.
@@ -39,26 +53,34 @@ impl<'a> Context<'a> {
     /// Cannot fail, even if the Vec is empty or if the position is crazy. Those
     /// are checked when using the cursor, not while creating it.
     pub fn new_at(v: &'a Vec<u8>, position: u64) -> Self {
-        //let mut c = Cursor::new(v);
-        //c.set_position(position);
+        Self::from_source_at(v, position)
+    }
 
+    /// Create a new [`Context`] at position 0 on any [`ContextSource`].
+    pub fn from_source(source: &'a dyn ContextSource) -> Self {
+        Self::from_source_at(source, 0)
+    }
+
+    /// Create a new [`Context`] at a given position on any [`ContextSource`].
+    ///
+    /// Like [`Context::new_at`], the position isn't checked until we read.
+    pub fn from_source_at(source: &'a dyn ContextSource, position: u64) -> Self {
         Self {
-            v: v,
+            v: source,
             position: position,
-            //c: c
         }
     }
 
-    /// Return a clone of the Cursor.
+    /// Return a reader at the current position.
     ///
     /// This is for internal use only. We clone a lot while reading values, but
     /// this operation is reasonably inexpensive since we don't actually clone
     /// the data - just a reference.
-    fn cursor(self) -> Cursor<&'a Vec<u8>> {
-        let mut cursor = Cursor::new(self.v);
-        cursor.set_position(self.position);
-
-        cursor
+    fn cursor(self) -> SourceReader<'a> {
+        SourceReader {
+            source: self.v,
+            position: self.position,
+        }
     }
 
     /// Clone the [`Context`] and change the position at the same time.
@@ -196,6 +218,35 @@ impl<'a> Context<'a> {
         }
     }
 
+    /// Read an IEEE 754 half-precision float.
+    ///
+    /// Rust doesn't have an `f16` type, but every half value can be
+    /// represented exactly as an [`f32`].
+    pub fn read_f16(self, endian: Endian) -> SimpleResult<f32> {
+        let half = self.read_u16(endian)?;
+
+        let exponent = (half >> 10) & 0x1f;
+        let mantissa = (half & 0x3ff) as f32;
+
+        let value = match exponent {
+            // Zero and subnormals
+            0  => mantissa * 2f32.powi(-24),
+
+            // Infinity and NaN
+            31 => match mantissa == 0.0 {
+                true  => f32::INFINITY,
+                false => f32::NAN,
+            },
+
+            _  => (mantissa + 1024.0) * 2f32.powi(exponent as i32 - 25),
+        };
+
+        match half & 0x8000 {
+            0 => Ok(value),
+            _ => Ok(-value),
+        }
+    }
+
     pub fn read_f32(self, endian: Endian) -> SimpleResult<f32> {
         match endian {
             Endian::Big => match self.cursor().read_f32::<BigEndian>() {
@@ -237,6 +288,17 @@ impl<'a> Context<'a> {
         }
     }
 
+    /// Read a single bit, counting from the start of the byte at the current
+    /// `position`.
+    ///
+    /// Bits are numbered across bytes in order - bit 8 is in the second
+    /// byte - and `order` decides which end of each byte is bit 0.
+    pub fn read_bit(self, bit: u64, order: BitOrder) -> SimpleResult<bool> {
+        let byte = self.at(self.position + (bit / 8)).read_u8()?;
+
+        Ok((byte >> order.shift((bit % 8) as usize, 8)) & 1 == 1)
+    }
+
     /// Read an 8-bit ASCII character.
     pub fn read_ascii(self) -> SimpleResult<char> {
         self.cursor().read_u8()
@@ -311,9 +373,26 @@ impl<'a> Context<'a> {
         }
     }
 
-    /// Get a [`u8`] slice starting at the current `position`
-    pub fn as_slice(self) -> &'a [u8] {
-        &self.v[(self.position as usize)..]
+    /// The number of bytes between the current `position` and the end of the
+    /// source.
+    pub fn remaining(self) -> u64 {
+        self.v.len().saturating_sub(self.position)
+    }
+
+    /// Get a [`u8`] slice starting at the current `position`.
+    ///
+    /// Fails if the source isn't in memory (see [`ContextSource::as_slice`])
+    /// or if the position is past the end.
+    pub fn as_slice(self) -> SimpleResult<&'a [u8]> {
+        let slice = match self.v.as_slice() {
+            Some(s) => s,
+            None    => bail!("This context's source can't be read as a slice"),
+        };
+
+        match slice.get((self.position as usize)..) {
+            Some(s) => Ok(s),
+            None    => bail!("Position {} is past the end of the buffer", self.position),
+        }
     }
 }
 
@@ -348,13 +427,38 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_read_bit() -> SimpleResult<()> {
+        let data = b"\x01\x80".to_vec();
+        let c = Context::new(&data);
+
+        assert_eq!(true,  c.read_bit(0, BitOrder::Lsb0)?);
+        assert_eq!(false, c.read_bit(7, BitOrder::Lsb0)?);
+        assert_eq!(true,  c.read_bit(15, BitOrder::Lsb0)?);
+
+        assert_eq!(false, c.read_bit(0, BitOrder::Msb0)?);
+        assert_eq!(true,  c.read_bit(7, BitOrder::Msb0)?);
+        assert_eq!(true,  c.read_bit(8, BitOrder::Msb0)?);
+
+        // Relative to the position
+        assert_eq!(true,  c.at(1).read_bit(0, BitOrder::Msb0)?);
+
+        assert!(c.read_bit(16, BitOrder::Lsb0).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_get_slice() -> SimpleResult<()> {
         let data = b"ABCDEF".to_vec();
 
         let c = Context::new_at(&data, 2);
-        let slice = c.as_slice();
+        let slice = c.as_slice()?;
         assert_eq!(b"CDEF".to_vec(), slice);
+        assert_eq!(4, c.remaining());
+
+        assert!(c.at(7).as_slice().is_err());
+        assert_eq!(0, c.at(7).remaining());
 
         Ok(())
     }
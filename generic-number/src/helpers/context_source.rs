@@ -0,0 +1,213 @@
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Mutex;
+
+/// The default chunk size for [`SeekSource`], in bytes.
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Something a [`crate::Context`] can read bytes from.
+///
+/// The simple case is a [`Vec<u8>`] that's fully in memory, but this lets a
+/// [`crate::Context`] sit on top of a file that's far too big to load (see
+/// [`SeekSource`]), or a memory-mapped file (anything that derefs to `[u8]`
+/// can be wrapped in a `&[u8]`).
+pub trait ContextSource: fmt::Debug + Sync {
+    /// The total size of the source, in bytes.
+    fn len(&self) -> u64;
+
+    /// Read as many bytes as will fit into `buffer`, starting at `offset`.
+    ///
+    /// Returns the number of bytes read, which is only smaller than
+    /// `buffer` when we run into the end of the source.
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> std::io::Result<usize>;
+
+    /// Get the source as a slice, if it's in memory.
+    ///
+    /// Streaming sources return `None`.
+    fn as_slice(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Copy out of an in-memory slice.
+fn read_slice_at(slice: &[u8], offset: u64, buffer: &mut [u8]) -> std::io::Result<usize> {
+    if offset >= slice.len() as u64 {
+        return Ok(0);
+    }
+
+    let available = &slice[(offset as usize)..];
+    let size = std::cmp::min(available.len(), buffer.len());
+    buffer[..size].copy_from_slice(&available[..size]);
+
+    Ok(size)
+}
+
+impl ContextSource for Vec<u8> {
+    fn len(&self) -> u64 {
+        Vec::len(self) as u64
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> std::io::Result<usize> {
+        read_slice_at(self, offset, buffer)
+    }
+
+    fn as_slice(&self) -> Option<&[u8]> {
+        Some(&self[..])
+    }
+}
+
+impl ContextSource for &[u8] {
+    fn len(&self) -> u64 {
+        <[u8]>::len(*self) as u64
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> std::io::Result<usize> {
+        read_slice_at(*self, offset, buffer)
+    }
+
+    fn as_slice(&self) -> Option<&[u8]> {
+        Some(*self)
+    }
+}
+
+/// The chunk that's currently cached by a [`SeekSource`].
+struct Chunk<R> {
+    reader: R,
+    start: u64,
+    data: Vec<u8>,
+}
+
+/// A [`ContextSource`] that reads from anything that implements
+/// [`Read`] + [`Seek`] - typically a [`std::fs::File`].
+///
+/// Reads are done one chunk at a time, and the most recent chunk is kept
+/// around. Types tend to read a bunch of small values that are close
+/// together, so most reads never touch the reader.
+pub struct SeekSource<R: Read + Seek + Send> {
+    chunk: Mutex<Chunk<R>>,
+    chunk_size: usize,
+    len: u64,
+}
+
+impl<R: Read + Seek + Send> SeekSource<R> {
+    /// Wrap a reader, using [`DEFAULT_CHUNK_SIZE`].
+    pub fn new(reader: R) -> std::io::Result<Self> {
+        Self::new_with_chunk_size(reader, DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Wrap a reader, reading `chunk_size` bytes at a time.
+    pub fn new_with_chunk_size(mut reader: R, chunk_size: usize) -> std::io::Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))?;
+
+        Ok(Self {
+            chunk: Mutex::new(Chunk {
+                reader: reader,
+                start: 0,
+                data: Vec::new(),
+            }),
+            chunk_size: std::cmp::max(chunk_size, 1),
+            len: len,
+        })
+    }
+}
+
+impl<R: Read + Seek + Send> fmt::Debug for SeekSource<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SeekSource")
+            .field("len", &self.len)
+            .field("chunk_size", &self.chunk_size)
+            .finish()
+    }
+}
+
+impl<R: Read + Seek + Send> ContextSource for SeekSource<R> {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let mut chunk = match self.chunk.lock() {
+            Ok(c) => c,
+            Err(e) => e.into_inner(),
+        };
+
+        let mut read = 0;
+        while read < buffer.len() {
+            let position = offset + read as u64;
+            if position >= self.len {
+                break;
+            }
+
+            // Load the chunk that contains `position`, if it's not the one
+            // we already have
+            let end = chunk.start + chunk.data.len() as u64;
+            if position < chunk.start || position >= end {
+                let start = position - (position % self.chunk_size as u64);
+                let size = std::cmp::min(self.chunk_size as u64, self.len - start) as usize;
+
+                let mut data = vec![0; size];
+                chunk.reader.seek(SeekFrom::Start(start))?;
+                chunk.reader.read_exact(&mut data)?;
+
+                chunk.start = start;
+                chunk.data = data;
+            }
+
+            read += read_slice_at(&chunk.data, position - chunk.start, &mut buffer[read..])?;
+        }
+
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+    use std::io::Cursor;
+
+    use crate::{Context, Endian};
+
+    #[test]
+    fn test_seek_source() -> SimpleResult<()> {
+        let data = b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09".to_vec();
+
+        // A tiny chunk size, so reads have to span chunks
+        let source = SeekSource::new_with_chunk_size(Cursor::new(data.clone()), 3).map_err(simple_error::SimpleError::from)?;
+        assert_eq!(10, source.len());
+
+        let c = Context::from_source(&source);
+        assert_eq!(0x00010203, c.read_u32(Endian::Big)?);
+        assert_eq!(0x05040302, c.at(2).read_u32(Endian::Little)?);
+        assert_eq!(data, c.read_bytes(10)?);
+        assert_eq!(b"\x08\x09".to_vec(), c.at(8).read_bytes(2)?);
+        assert_eq!(0x04, c.at(4).read_u8()?);
+
+        assert!(c.at(8).read_u32(Endian::Big).is_err());
+        assert!(c.at(10).read_u8().is_err());
+        assert!(c.read_bytes(11).is_err());
+
+        // Streaming sources can't be sliced
+        assert!(c.as_slice().is_err());
+        assert_eq!(6, c.at(4).remaining());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_slice_source() -> SimpleResult<()> {
+        let data: &[u8] = b"ABCD";
+        let c = Context::from_source(&data);
+
+        assert_eq!(b"BCD".to_vec(), c.at(1).read_bytes(3)?);
+        assert_eq!(b"CD", c.at(2).as_slice()?);
+
+        Ok(())
+    }
+}
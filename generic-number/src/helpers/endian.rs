@@ -1,7 +1,8 @@
 use serde::{Serialize, Deserialize};
+use std::fmt;
 
 /// Define the endianness for reading multi-byte integers
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Endian {
     /// Most significant byte is first (eg, `0x1234` -> `12 34`)
     Big,
@@ -9,3 +10,13 @@ pub enum Endian {
     /// Most significant byte is last (eg, `0x1234` -> `34 12`)
     Little,
 }
+
+impl fmt::Display for Endian {
+    /// Display the endian in short form (`be` / `le`).
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Big    => write!(f, "be"),
+            Self::Little => write!(f, "le"),
+        }
+    }
+}
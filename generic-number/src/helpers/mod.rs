@@ -1,5 +1,11 @@
 mod context;
 pub use context::*;
 
+mod context_source;
+pub use context_source::*;
+
 mod endian;
 pub use endian::*;
+
+mod bit_order;
+pub use bit_order::*;
@@ -1,7 +1,9 @@
 use serde::{Serialize, Deserialize};
-use simple_error::{SimpleResult, bail};
+use simple_error::{SimpleError, SimpleResult, bail};
 use std::{fmt, mem};
 use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::str::FromStr;
 
 /// A number that can be any of the primitive integer types.
 ///
@@ -76,6 +78,12 @@ impl Integer {
         }
     }
 
+    /// Is the value below zero? Unlike [`Integer::is_signed`], this goes by
+    /// the value.
+    pub fn is_negative(self) -> bool {
+        self.as_i128().map(|v| v < 0).unwrap_or(false)
+    }
+
     /// Is the type compatible with [`usize`]?
     ///
     /// Dynamically determine this based on [`mem::size_of`]
@@ -182,6 +190,195 @@ impl Integer {
     }
 }
 
+impl FromStr for Integer {
+    type Err = SimpleError;
+
+    /// See [`Integer::parse`].
+    fn from_str(s: &str) -> SimpleResult<Self> {
+        Self::parse(s)
+    }
+}
+
+/// The suffixes [`Integer::parse`] accepts, longest first so `u128` isn't
+/// mistaken for `u12` + `8`.
+const SUFFIXES: [&str; 10] = ["u128", "i128", "u16", "u32", "u64", "i16", "i32", "i64", "u8", "i8"];
+
+/// Parse a character literal like `'A'` or `'\n'`, if `s` is one.
+fn parse_character(s: &str) -> SimpleResult<Option<char>> {
+    let inner = match s.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+        Some(inner) if s.len() >= 2 => inner,
+        _                           => return Ok(None),
+    };
+
+    let c = match inner.strip_prefix('\\') {
+        Some(escape) => match escape {
+            "n"  => '\n',
+            "r"  => '\r',
+            "t"  => '\t',
+            "0"  => '\0',
+            "\\" => '\\',
+            "'"  => '\'',
+            "\"" => '"',
+            _ => {
+                // \x7f and \u{e9}
+                let code = if let Some(hex) = escape.strip_prefix('x').filter(|hex| hex.len() == 2) {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(hex) = escape.strip_prefix("u{").and_then(|hex| hex.strip_suffix('}')) {
+                    u32::from_str_radix(hex, 16).ok()
+                } else {
+                    None
+                };
+
+                match code.and_then(std::char::from_u32) {
+                    Some(c) => c,
+                    None    => bail!("Unknown escape in character literal: {}", s),
+                }
+            },
+        },
+        None => {
+            let mut chars = inner.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => bail!("Character literals must be exactly one character: {}", s),
+            }
+        },
+    };
+
+    Ok(Some(c))
+}
+
+impl Integer {
+    /// Parse an integer the way a user would type it.
+    ///
+    /// This accepts:
+    ///
+    /// * Decimal (`123`), hex (`0x7b`), octal (`0o173`), or binary
+    ///   (`0b1111011`); prefixes are case-insensitive
+    /// * A leading `-` or `+`
+    /// * `_` as a group separator anywhere after the prefix (`1_000_000`,
+    ///   `0xffff_ffff`)
+    /// * A type suffix, like Rust's (`255u8`, `-1i32`, `0x10_u16`) - the value
+    ///   must fit, and becomes that variant
+    /// * A character literal, which is its code point (`'A'`, `'\n'`,
+    ///   `'\x7f'`, `'\u{e9}'`, `'é'`)
+    ///
+    /// Surrounding whitespace is ignored. Without a suffix, negative values
+    /// become an [`Integer::I128`], and everything else an
+    /// [`Integer::U128`]; use a [`crate::IntegerWriter`] to get it back to a
+    /// specific size.
+    pub fn parse(s: &str) -> SimpleResult<Self> {
+        let trimmed = s.trim();
+
+        if let Some(c) = parse_character(trimmed)? {
+            return Ok(Self::U128(c as u128));
+        }
+
+        let (negative, unsigned) = match trimmed.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None       => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+        };
+
+        let lower = unsigned.to_ascii_lowercase();
+        let (radix, rest) = match lower.get(0..2) {
+            Some("0x") => (16, &lower[2..]),
+            Some("0o") => (8,  &lower[2..]),
+            Some("0b") => (2,  &lower[2..]),
+            _          => (10, &lower[..]),
+        };
+
+        // Hex digits never include 'u' or 'i', so a suffix can't be confused
+        // with the number
+        let (rest, suffix) = match SUFFIXES.iter().find(|suffix| rest.ends_with(*suffix)) {
+            Some(suffix) => (&rest[..(rest.len() - suffix.len())], Some(*suffix)),
+            None         => (rest, None),
+        };
+
+        let digits: String = rest.chars().filter(|c| *c != '_').collect();
+        if digits.is_empty() {
+            bail!("Couldn't parse {:?} as an integer: no digits", s);
+        }
+
+        // from_str_radix would allow a second sign
+        if digits.starts_with('+') || digits.starts_with('-') {
+            bail!("Couldn't parse {:?} as an integer", s);
+        }
+
+        let magnitude = u128::from_str_radix(&digits, radix).map_err(|e| {
+            SimpleError::new(format!("Couldn't parse {:?} as an integer: {}", s, e))
+        })?;
+
+        // i128::MIN's magnitude doesn't fit in an i128, so subtract from zero
+        // as a u128 and let it wrap
+        if negative && magnitude > i128::MIN.unsigned_abs() {
+            bail!("Couldn't parse {:?} as an integer: too small", s);
+        }
+
+        let signed = match negative {
+            true  => Some(0u128.wrapping_sub(magnitude) as i128),
+            false => i128::try_from(magnitude).ok(),
+        };
+        let unsigned = match negative && magnitude != 0 {
+            true  => None,
+            false => Some(magnitude),
+        };
+
+        let result = match suffix {
+            None if negative => signed.map(Self::I128),
+            None             => unsigned.map(Self::U128),
+
+            Some("u8")   => unsigned.and_then(|v| u8::try_from(v).ok()).map(Self::U8),
+            Some("u16")  => unsigned.and_then(|v| u16::try_from(v).ok()).map(Self::U16),
+            Some("u32")  => unsigned.and_then(|v| u32::try_from(v).ok()).map(Self::U32),
+            Some("u64")  => unsigned.and_then(|v| u64::try_from(v).ok()).map(Self::U64),
+            Some("u128") => unsigned.map(Self::U128),
+
+            Some("i8")   => signed.and_then(|v| i8::try_from(v).ok()).map(Self::I8),
+            Some("i16")  => signed.and_then(|v| i16::try_from(v).ok()).map(Self::I16),
+            Some("i32")  => signed.and_then(|v| i32::try_from(v).ok()).map(Self::I32),
+            Some("i64")  => signed.and_then(|v| i64::try_from(v).ok()).map(Self::I64),
+            Some(_)      => signed.map(Self::I128),
+        };
+
+        result.ok_or_else(|| {
+            SimpleError::new(format!("Couldn't parse {:?} as an integer: out of range for {}", s, suffix.unwrap_or("u128")))
+        })
+    }
+
+    /// The value as a [`u128`], if it isn't negative.
+    fn to_u128(self) -> Option<u128> {
+        self.as_u128().or_else(|| self.as_i128().and_then(|v| u128::try_from(v).ok()))
+    }
+
+    /// Attempt to convert to a [`u64`].
+    ///
+    /// Unlike [`Integer::as_usize`], this goes by the value rather than the
+    /// type - an [`Integer::U128`] or [`Integer::I8`] works if the value fits.
+    pub fn to_u64(self) -> SimpleResult<u64> {
+        self.to_u128().and_then(|v| u64::try_from(v).ok()).ok_or_else(|| {
+            SimpleError::new(format!("Can't convert {} to a u64 value: out of range", self))
+        })
+    }
+
+    /// Attempt to convert to a [`usize`], by value - see [`Integer::to_u64`].
+    pub fn to_usize(self) -> SimpleResult<usize> {
+        self.to_u128().and_then(|v| usize::try_from(v).ok()).ok_or_else(|| {
+            SimpleError::new(format!("Can't convert {} to a usize value: out of range", self))
+        })
+    }
+
+    /// Attempt to convert to an [`i128`] - every value fits, except unsigned
+    /// ones above [`i128::MAX`].
+    pub fn to_i128(self) -> SimpleResult<i128> {
+        match (self.as_i128(), self.as_u128()) {
+            (Some(v), _) => Ok(v),
+            (_, Some(v)) => i128::try_from(v).map_err(|_| {
+                SimpleError::new(format!("Can't convert {} to an i128 value: too large", v))
+            }),
+            _ => bail!("Serious signed/unsigned problem in GenericNumber"),
+        }
+    }
+}
+
 impl fmt::Display for Integer {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -375,6 +572,94 @@ mod tests {
 
     use crate::{Context, Integer, IntegerReader, Endian, DefaultFormatter};
 
+    #[test]
+    fn test_from_str() -> SimpleResult<()> {
+        // Compare the debug output, since it includes the type
+        let parse = |s: &str| -> SimpleResult<String> { Ok(format!("{:?}", s.parse::<Integer>()?)) };
+
+        assert_eq!("U128(500)",  parse("500")?);
+        assert_eq!("U128(31)",   parse("0x1F")?);
+        assert_eq!("U128(8)",    parse("0o10")?);
+        assert_eq!("U128(5)",    parse(" 0b101 ")?);
+        assert_eq!("I128(-16)",  parse("-0x10")?);
+        assert_eq!(format!("I128({})", i128::MIN), parse("-170141183460469231731687303715884105728")?);
+
+        assert!("".parse::<Integer>().is_err());
+        assert!("0x".parse::<Integer>().is_err());
+        assert!("--1".parse::<Integer>().is_err());
+        assert!("1.5".parse::<Integer>().is_err());
+        assert!("-170141183460469231731687303715884105729".parse::<Integer>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse() -> SimpleResult<()> {
+        let parse = |s: &str| -> SimpleResult<String> { Ok(format!("{:?}", Integer::parse(s)?)) };
+
+        // Signs, prefixes, and separators
+        assert_eq!("U128(500)",        parse("+500")?);
+        assert_eq!("U128(255)",        parse("0XfF")?);
+        assert_eq!("U128(1000000)",    parse("1_000_000")?);
+        assert_eq!("U128(4294967295)", parse("0xffff_ffff")?);
+        assert_eq!("I128(0)",          parse("-0")?);
+
+        // Suffixes
+        assert_eq!("U8(255)",          parse("255u8")?);
+        assert_eq!("I32(-1)",          parse("-1i32")?);
+        assert_eq!("U16(16)",          parse("0x10_u16")?);
+        assert_eq!("I8(-128)",         parse("-0x80i8")?);
+        assert_eq!("U128(128)",        parse("128u128")?);
+        assert_eq!("U64(5)",           parse("0b101u64")?);
+        assert_eq!("U8(0)",            parse("-0u8")?);
+
+        // Characters
+        assert_eq!("U128(65)",         parse("'A'")?);
+        assert_eq!("U128(10)",         parse(r"'\n'")?);
+        assert_eq!("U128(39)",         parse(r"'\''")?);
+        assert_eq!("U128(127)",        parse(r"'\x7f'")?);
+        assert_eq!("U128(233)",        parse(r"'\u{e9}'")?);
+        assert_eq!("U128(233)",        parse("'é'")?);
+
+        let tests = vec![
+            ("",        "Couldn't parse \"\" as an integer: no digits"),
+            ("u8",      "Couldn't parse \"u8\" as an integer: no digits"),
+            ("256u8",   "Couldn't parse \"256u8\" as an integer: out of range for u8"),
+            ("-1u32",   "Couldn't parse \"-1u32\" as an integer: out of range for u32"),
+            ("128i8",   "Couldn't parse \"128i8\" as an integer: out of range for i8"),
+            ("+-1",     "Couldn't parse \"+-1\" as an integer"),
+            ("12u7",    "Couldn't parse \"12u7\" as an integer: invalid digit found in string"),
+            ("'ab'",    "Character literals must be exactly one character: 'ab'"),
+            (r"'\q'",   r"Unknown escape in character literal: '\q'"),
+        ];
+
+        for (s, error) in tests {
+            assert_eq!(error, Integer::parse(s).unwrap_err().to_string(), "parsing {}", s);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_by_value() -> SimpleResult<()> {
+        assert_eq!(-1, Integer::from(-1i8).to_i128()?);
+        assert_eq!(255, Integer::from(255u8).to_i128()?);
+        assert_eq!(i128::MAX, Integer::from(i128::MAX as u128).to_i128()?);
+        assert!(Integer::from(u128::MAX).to_i128().is_err());
+
+        assert_eq!(5, Integer::from(5u128).to_u64()?);
+        assert_eq!(5, Integer::from(5i8).to_usize()?);
+        assert_eq!(u64::MAX, Integer::from(u64::MAX as i128).to_u64()?);
+        assert!(Integer::from(-1i8).to_u64().is_err());
+        assert!(Integer::from(u64::MAX as u128 + 1).to_u64().is_err());
+
+        assert!(Integer::from(-1i8).is_negative());
+        assert!(!Integer::from(0i32).is_negative());
+        assert!(!Integer::from(u128::MAX).is_negative());
+
+        Ok(())
+    }
+
     #[test]
     fn test_display() -> SimpleResult<()> {
         let data = b"\x00\x7F\x80\xFF\x00\x01\x02\x03\x80\x00\x00\x00\x00\x00\x00\x00".to_vec();
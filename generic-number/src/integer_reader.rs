@@ -1,6 +1,6 @@
 use simple_error::SimpleResult;
 use serde::{Serialize, Deserialize};
-use std::mem;
+use std::{fmt, mem};
 
 use crate::{Context, Endian, Integer};
 
@@ -102,6 +102,26 @@ impl IntegerReader {
     }
 }
 
+impl fmt::Display for IntegerReader {
+    /// Display the reader in a short, C-like form - `u8`, `u32le`, `i64be`,
+    /// etc.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::U8             => write!(f, "u8"),
+            Self::U16(endian)    => write!(f, "u16{}", endian),
+            Self::U32(endian)    => write!(f, "u32{}", endian),
+            Self::U64(endian)    => write!(f, "u64{}", endian),
+            Self::U128(endian)   => write!(f, "u128{}", endian),
+
+            Self::I8             => write!(f, "i8"),
+            Self::I16(endian)    => write!(f, "i16{}", endian),
+            Self::I32(endian)    => write!(f, "i32{}", endian),
+            Self::I64(endian)    => write!(f, "i64{}", endian),
+            Self::I128(endian)   => write!(f, "i128{}", endian),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -130,4 +150,13 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_display() -> SimpleResult<()> {
+        assert_eq!("u8",     IntegerReader::U8.to_string());
+        assert_eq!("u32le",  IntegerReader::U32(Endian::Little).to_string());
+        assert_eq!("i128be", IntegerReader::I128(Endian::Big).to_string());
+
+        Ok(())
+    }
 }
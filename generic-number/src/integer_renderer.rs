@@ -5,6 +5,12 @@ use crate::{Integer, BinaryFormatter, BooleanFormatter, DefaultFormatter, HexFor
 /// Define the interface for rendering an integer
 pub trait IntegerRendererTrait {
     fn render_integer(&self, number: Integer) -> String;
+
+    /// Render an integer that's an offset or a size, which some renderers
+    /// display differently (see [`DefaultFormatter::hex_offsets`]).
+    fn render_offset(&self, number: Integer) -> String {
+        self.render_integer(number)
+    }
 }
 
 /// Configure how an [`Integer`] is rendered.
@@ -32,6 +38,19 @@ impl IntegerRenderer {
             Self::Scientific(f) => f.render_integer(v),
         }
     }
+
+    /// Render an integer that's an offset or a size - most renderers treat
+    /// these like any other integer.
+    pub fn render_offset(self, v: Integer) -> String {
+        match self {
+            Self::Binary(f)     => f.render_offset(v),
+            Self::Boolean(f)    => f.render_offset(v),
+            Self::Default(f)    => f.render_offset(v),
+            Self::Hex(f)        => f.render_offset(v),
+            Self::Octal(f)      => f.render_offset(v),
+            Self::Scientific(f) => f.render_offset(v),
+        }
+    }
 }
 
 #[cfg(test)]
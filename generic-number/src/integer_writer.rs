@@ -0,0 +1,220 @@
+use simple_error::{SimpleResult, bail};
+use serde::{Serialize, Deserialize};
+use std::convert::TryFrom;
+use std::{fmt, mem};
+
+use crate::{Endian, Integer, IntegerReader};
+
+/// Defines how an [`Integer`] is written back out as bytes.
+///
+/// This is the counterpart to [`IntegerReader`], with the same widths and
+/// [`Endian`] options - a reader can be converted into the matching writer
+/// with `From` / `Into`, so a value can be written back exactly how it was
+/// read.
+///
+/// Like the readers, this can be serialized, which means it can be stored and
+/// re-used in the future.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IntegerWriter {
+    /// Unsigned 8-bit integer
+    U8,
+
+    /// Unsigned 16-bit integer
+    U16(Endian),
+
+    /// Unsigned 32-bit integer
+    U32(Endian),
+
+    /// Unsigned 64-bit integer
+    U64(Endian),
+
+    /// Unsigned 128-bit integer
+    U128(Endian),
+
+    /// Signed 8-bit integer
+    I8,
+
+    /// Signed 16-bit integer
+    I16(Endian),
+
+    /// Signed 32-bit integer
+    I32(Endian),
+
+    /// Signed 64-bit integer
+    I64(Endian),
+
+    /// Signed 128-bit integer
+    I128(Endian),
+}
+
+impl From<IntegerReader> for IntegerWriter {
+    fn from(o: IntegerReader) -> Self {
+        match o {
+            IntegerReader::U8           => Self::U8,
+            IntegerReader::U16(endian)  => Self::U16(endian),
+            IntegerReader::U32(endian)  => Self::U32(endian),
+            IntegerReader::U64(endian)  => Self::U64(endian),
+            IntegerReader::U128(endian) => Self::U128(endian),
+
+            IntegerReader::I8           => Self::I8,
+            IntegerReader::I16(endian)  => Self::I16(endian),
+            IntegerReader::I32(endian)  => Self::I32(endian),
+            IntegerReader::I64(endian)  => Self::I64(endian),
+            IntegerReader::I128(endian) => Self::I128(endian),
+        }
+    }
+}
+
+/// Convert any [`Integer`] to a specific primitive type, if it fits.
+macro_rules! convert {
+    ($value:expr, $t:ty) => {
+        match $value {
+            Integer::U8(n)   => <$t>::try_from(n).ok(),
+            Integer::U16(n)  => <$t>::try_from(n).ok(),
+            Integer::U32(n)  => <$t>::try_from(n).ok(),
+            Integer::U64(n)  => <$t>::try_from(n).ok(),
+            Integer::U128(n) => <$t>::try_from(n).ok(),
+            Integer::I8(n)   => <$t>::try_from(n).ok(),
+            Integer::I16(n)  => <$t>::try_from(n).ok(),
+            Integer::I32(n)  => <$t>::try_from(n).ok(),
+            Integer::I64(n)  => <$t>::try_from(n).ok(),
+            Integer::I128(n) => <$t>::try_from(n).ok(),
+        }
+    }
+}
+
+/// Convert, then serialize with the given endian.
+macro_rules! write_as {
+    ($self:expr, $value:expr, $t:ty, $endian:expr) => {
+        match convert!($value, $t) {
+            Some(n) => match $endian {
+                Endian::Big    => Ok(n.to_be_bytes().to_vec()),
+                Endian::Little => Ok(n.to_le_bytes().to_vec()),
+            },
+            None    => bail!("Value {} doesn't fit in a {}", $value, $self),
+        }
+    }
+}
+
+impl IntegerWriter {
+    /// Write `value` as bytes.
+    ///
+    /// The value doesn't need to be the same type as the writer - an
+    /// [`Integer::U8`] can be written as a `u32`, for example - but it does
+    /// need to fit. Values that don't fit (negative numbers into unsigned
+    /// types, or anything too large) are an error rather than being
+    /// truncated.
+    pub fn write(self, value: Integer) -> SimpleResult<Vec<u8>> {
+        match self {
+            // Endian doesn't matter for a single byte
+            Self::U8           => write_as!(self, value, u8,   Endian::Big),
+            Self::U16(endian)  => write_as!(self, value, u16,  endian),
+            Self::U32(endian)  => write_as!(self, value, u32,  endian),
+            Self::U64(endian)  => write_as!(self, value, u64,  endian),
+            Self::U128(endian) => write_as!(self, value, u128, endian),
+
+            Self::I8           => write_as!(self, value, i8,   Endian::Big),
+            Self::I16(endian)  => write_as!(self, value, i16,  endian),
+            Self::I32(endian)  => write_as!(self, value, i32,  endian),
+            Self::I64(endian)  => write_as!(self, value, i64,  endian),
+            Self::I128(endian) => write_as!(self, value, i128, endian),
+        }
+    }
+
+    /// The size - in bytes - that will be written by [`Self::write`].
+    pub fn size(self) -> usize {
+        match self {
+            Self::U8      => mem::size_of::<u8>(),
+            Self::U16(_)  => mem::size_of::<u16>(),
+            Self::U32(_)  => mem::size_of::<u32>(),
+            Self::U64(_)  => mem::size_of::<u64>(),
+            Self::U128(_) => mem::size_of::<u128>(),
+
+            Self::I8      => mem::size_of::<i8>(),
+            Self::I16(_)  => mem::size_of::<i16>(),
+            Self::I32(_)  => mem::size_of::<i32>(),
+            Self::I64(_)  => mem::size_of::<i64>(),
+            Self::I128(_) => mem::size_of::<i128>(),
+        }
+    }
+}
+
+impl fmt::Display for IntegerWriter {
+    /// Display the writer in a short, C-like form - `u8`, `u32le`, `i64be`,
+    /// etc.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::U8             => write!(f, "u8"),
+            Self::U16(endian)    => write!(f, "u16{}", endian),
+            Self::U32(endian)    => write!(f, "u32{}", endian),
+            Self::U64(endian)    => write!(f, "u64{}", endian),
+            Self::U128(endian)   => write!(f, "u128{}", endian),
+
+            Self::I8             => write!(f, "i8"),
+            Self::I16(endian)    => write!(f, "i16{}", endian),
+            Self::I32(endian)    => write!(f, "i32{}", endian),
+            Self::I64(endian)    => write!(f, "i64{}", endian),
+            Self::I128(endian)   => write!(f, "i128{}", endian),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+
+    use crate::Context;
+
+    #[test]
+    fn test_write() -> SimpleResult<()> {
+        assert_eq!(b"\x12\x34".to_vec(),         IntegerWriter::U16(Endian::Big).write(Integer::from(0x1234u16))?);
+        assert_eq!(b"\x34\x12".to_vec(),         IntegerWriter::U16(Endian::Little).write(Integer::from(0x1234u16))?);
+        assert_eq!(b"\xff".to_vec(),             IntegerWriter::I8.write(Integer::from(-1i8))?);
+        assert_eq!(b"\xfe\xff\xff\xff".to_vec(), IntegerWriter::I32(Endian::Little).write(Integer::from(-2i64))?);
+
+        // Widening is fine
+        assert_eq!(b"\x00\x00\x00\x00\x00\x00\x00\x01".to_vec(), IntegerWriter::U64(Endian::Big).write(Integer::from(1u8))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_doesnt_fit() -> SimpleResult<()> {
+        assert!(IntegerWriter::U8.write(Integer::from(0x100u16)).is_err());
+        assert!(IntegerWriter::U32(Endian::Big).write(Integer::from(-1i8)).is_err());
+        assert!(IntegerWriter::I8.write(Integer::from(0x80u8)).is_err());
+        assert!(IntegerWriter::I8.write(Integer::from(0x7fu8)).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> SimpleResult<()> {
+        let data = b"\x01\x23\x45\x67\x89\xab\xcd\xef\x01\x23\x45\x67\x89\xab\xcd\xef".to_vec();
+
+        let readers = vec![
+            IntegerReader::U8,
+            IntegerReader::U16(Endian::Big),
+            IntegerReader::U32(Endian::Little),
+            IntegerReader::U64(Endian::Big),
+            IntegerReader::U128(Endian::Little),
+            IntegerReader::I8,
+            IntegerReader::I16(Endian::Little),
+            IntegerReader::I32(Endian::Big),
+            IntegerReader::I64(Endian::Little),
+            IntegerReader::I128(Endian::Big),
+        ];
+
+        for reader in readers {
+            let writer = IntegerWriter::from(reader);
+            let value = reader.read(Context::new(&data))?;
+
+            assert_eq!(reader.size(), writer.size());
+            assert_eq!(reader.to_string(), writer.to_string());
+            assert_eq!(data[0..reader.size()].to_vec(), writer.write(value)?);
+        }
+
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
-//! A library for reading and formatting differently-sized integers and floats.
+//! A library for reading, writing, and formatting differently-sized integers
+//! and floats.
 //!
-//! The bulk of functionality is split into three parts:
+//! The bulk of functionality is split into five parts:
 //!
 //! * Datatypes - [`Integer`], [`Float`], and [`Character`], which represent
 //!   datatypes and implement traits similar to the datatypes they represent
@@ -8,6 +9,14 @@
 //! * Readers - [`IntegerReader`], [`FloatReader`], and [`CharacterReader`],
 //!   which make it easy to read any of the native types out of a [`Context`]
 //!
+//! * Writers - [`IntegerWriter`], [`FloatWriter`], and [`CharacterWriter`],
+//!   which do the opposite, turning a datatype back into bytes with the same
+//!   width and [`Endian`] options (each can be made from its matching reader)
+//!
+//! * Generic wrappers - [`GenericReader`] and [`GenericNumber`], which wrap
+//!   any of the readers / datatypes so they can be stored without knowing
+//!   which one is in use
+//!
 //! * Renderers - [`IntegerRenderer`], [`FloatRenderer`], and
 //!   [`CharacterRenderer`], which define how something is rendered. They are
 //!   not instantiated directly, but through the variety of
@@ -17,7 +26,9 @@
 //!
 //! To use, you typically want to:
 //!
-//! * Create a [`Context`]
+//! * Create a [`Context`] - usually on a [`Vec<u8>`], but any
+//!   [`ContextSource`] works, including [`SeekSource`] for files that are too
+//!   big to load into memory
 //! * Read a datatype (Integer / Float / Character) using one of the readers
 //! * Render it using one of the renderers
 //!
@@ -61,6 +72,9 @@ pub use integer::*;
 mod integer_reader;
 pub use integer_reader::*;
 
+mod integer_writer;
+pub use integer_writer::*;
+
 mod integer_renderer;
 pub use integer_renderer::*;
 
@@ -70,6 +84,9 @@ pub use float::*;
 mod float_reader;
 pub use float_reader::*;
 
+mod float_writer;
+pub use float_writer::*;
+
 mod float_renderer;
 pub use float_renderer::*;
 
@@ -79,5 +96,14 @@ pub use character::*;
 mod character_reader;
 pub use character_reader::*;
 
+mod character_writer;
+pub use character_writer::*;
+
 mod character_renderer;
 pub use character_renderer::*;
+
+mod generic_number;
+pub use generic_number::*;
+
+mod generic_reader;
+pub use generic_reader::*;
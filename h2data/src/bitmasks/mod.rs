@@ -2,15 +2,21 @@ use std::collections::HashMap;
 use simple_error::{SimpleResult, SimpleError, bail};
 use lazy_static::lazy_static;
 
+use crate::{parse_unsigned, string_size};
+
+/// The widest bitmask that can be loaded, in bits. Bitmasks wider than a
+/// single integer are read as several words - see [`BitmaskHandle::bits_wide`].
+pub const MAX_BITMASK_BITS: usize = 4096;
+
 /// Load a Bitmask from a .csv file.
 ///
 /// This requires the CSV to be a string file containing exactly two columns:
-/// a numeric column (unique, unsigned values no larger than 63) and a string
-/// column representing the "name" of the field.
+/// a numeric column (unique bit numbers, below [`MAX_BITMASK_BITS`]) and a
+/// string column representing the "name" of the field.
 ///
-/// The numeric column must be unique, but not every bit between 0 and 63 need
-/// be represented.
-fn load_from_csv(data: &str) -> SimpleResult<HashMap<usize, String>> {
+/// The numeric column must be unique, but not every bit need be represented. See [`crate::parse_unsigned`] for the formats it can be
+/// written in. Errors include the `filename` and line.
+fn load_from_csv(filename: &str, data: &str) -> SimpleResult<HashMap<usize, String>> {
     let mut out = HashMap::new();
 
     let mut rdr = csv::ReaderBuilder::new()
@@ -19,21 +25,23 @@ fn load_from_csv(data: &str) -> SimpleResult<HashMap<usize, String>> {
 
     for result in rdr.records() {
         let record = result.map_err(|e| {
-            SimpleError::new(format!("Couldn't read bitmask CSV: {}", e))
+            SimpleError::new(format!("Couldn't read bitmask CSV {}: {}", filename, e))
         })?;
 
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+
         if record.len() != 2 {
-            bail!("Bad bitmask CSV: must be 2 records per line, this line was {}", record.len());
+            bail!("Bad bitmask CSV {} line {}: must be 2 records per line, this line was {}", filename, line, record.len());
         }
 
-        let number: usize = record.get(0).ok_or(
+        let number = parse_unsigned(record.get(0).ok_or(
             SimpleError::new("Error reading the CSV file")
-        )?.parse().map_err(|e| {
-            SimpleError::new(format!("Couldn't parse first CSV field as integer: {}", e))
+        )?).map_err(|e| {
+            SimpleError::new(format!("Bad bitmask CSV {} line {}: {}", filename, line, e))
         })?;
 
-        if number > 63 {
-            bail!("Bitmask value is impossibly high: {} (mask is 63)", number);
+        if number >= MAX_BITMASK_BITS {
+            bail!("Bad bitmask CSV {} line {}: value is impossibly high: {} (max is {})", filename, line, number, MAX_BITMASK_BITS - 1);
         }
 
         out.insert(number, record.get(1).ok_or(
@@ -48,7 +56,10 @@ lazy_static! {
     /// Pre-load the BITMASKS structure
     pub static ref BITMASKS: HashMap<String, HashMap<usize, String>> = {
         let mut h = HashMap::new();
-        h.insert("TerrariaVisibility".to_string(), load_from_csv(include_str!("./terraria_visibility.csv")).unwrap());
+        h.insert("ElfProgramFlags".to_string(),    load_from_csv("elf_program_flags.csv", include_str!("./elf_program_flags.csv")).unwrap());
+        h.insert("ElfSectionFlags".to_string(),    load_from_csv("elf_section_flags.csv", include_str!("./elf_section_flags.csv")).unwrap());
+
+        h.insert("TerrariaVisibility".to_string(), load_from_csv("terraria_visibility.csv", include_str!("./terraria_visibility.csv")).unwrap());
 
         h
     };
@@ -59,6 +70,95 @@ pub fn bitmask_exists(name: &str) -> bool {
     BITMASKS.contains_key(name)
 }
 
+/// A bitmask that's already been looked up by name - see
+/// [`crate::EnumHandle`], which does the same thing for enums.
+#[derive(Debug, Clone, Copy)]
+pub struct BitmaskHandle {
+    name: &'static str,
+    bits: &'static HashMap<usize, String>,
+}
+
+impl BitmaskHandle {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Convert from a 64-bit value to a list of booleans and bits - see
+    /// [`from_bitmask`].
+    pub fn bits(&self, value: usize) -> Vec<(bool, usize, String)> {
+        self.bits_wide(64, |bit| (value >> bit) & 1 == 1).into_iter().map(|(is_on, bit, name)| {
+            (is_on, 1 << bit, name)
+        }).collect()
+    }
+
+    /// Convert a bitmask of any width to a list of booleans and bits.
+    ///
+    /// Unlike [`BitmaskHandle::bits`], the middle value is the bit number
+    /// rather than the mask, since a mask wider than 64 bits doesn't fit in a
+    /// `usize`. `is_set` is called for every bit number below `width`; how
+    /// bits are numbered (and which word they live in) is up to the caller.
+    /// Bits past `width` can't be set, so they're left out even if they're
+    /// defined.
+    pub fn bits_wide(&self, width: usize, is_set: impl Fn(usize) -> bool) -> Vec<(bool, usize, String)> {
+        let mut out = Vec::new();
+
+        for bit in 0..width {
+            let is_on = is_set(bit);
+
+            // Include it if it's "on" and/or defined
+            if is_on || self.bits.contains_key(&bit) {
+                let name = match self.bits.get(&bit) {
+                    Some(name) => name.to_string(),
+                    None if bit < 64 => format!("Unknown_0x{:x}", 1u64 << bit),
+                    None => format!("Unknown_bit{}", bit),
+                };
+
+                out.push((is_on, bit, name));
+            }
+        }
+
+        out
+    }
+
+    /// Convert from a 64-bit value to a list of strings - see
+    /// [`from_bitmask_str`].
+    pub fn strings(&self, value: usize, include_negatives: bool) -> Vec<String> {
+        self.strings_wide(64, |bit| (value >> bit) & 1 == 1, include_negatives)
+    }
+
+    /// Convert a bitmask of any width to a list of strings - see
+    /// [`BitmaskHandle::bits_wide`].
+    pub fn strings_wide(&self, width: usize, is_set: impl Fn(usize) -> bool, include_negatives: bool) -> Vec<String> {
+        let mut out: Vec<_> = self.bits_wide(width, is_set).iter()
+            .filter(|(is_set, _, _)| include_negatives || *is_set)
+            .map(|(is_set, _, name)| {
+                match *is_set {
+                    true  => name.to_string(),
+                    false => format!("~{}", name),
+                }
+            })
+            .collect();
+
+        if out.len() == 0 {
+            out.push("(n/a)".to_string());
+        }
+
+        out
+    }
+}
+
+/// Look up a bitmask once, to convert values quickly later.
+pub fn bitmask_handle(name: &str) -> SimpleResult<BitmaskHandle> {
+    let (name, bits) = BITMASKS.get_key_value(name).ok_or(
+        SimpleError::new(format!("No such bitmask: {}", name))
+    )?;
+
+    Ok(BitmaskHandle {
+        name: &name[..],
+        bits: bits,
+    })
+}
+
 /// Convert from a 64-bit value to a list of booleans and bits.
 ///
 /// The return type is a list of tuples. The tuples are composed of three
@@ -68,44 +168,21 @@ pub fn bitmask_exists(name: &str) -> bool {
 /// * `bit_mask` - the shifted integer value - bit `3` would be `(1<<3)` or `0x08`, for example
 /// * `name` - the name of the field, directly from the original CSV file
 pub fn from_bitmask(bitmask: &str, value: usize) -> SimpleResult<Vec<(bool, usize, String)>> {
-    let bitmask = BITMASKS.get(bitmask).ok_or(
-        SimpleError::new(format!("No such bitmask: {}", bitmask))
-    )?;
-
-    let mut out = Vec::new();
-
-    for bit in 0..64 {
-        // If it doesn't exist...
-        let shifted_bit = 1 << bit;
-        let is_on = (shifted_bit & value) == shifted_bit;
-
-        // Include it if it's "on" and/or defined
-        if is_on || bitmask.contains_key(&bit) {
-            //let default_name = format!("Unknown_0x{:x}", 1u64 << bit);
-            out.push((is_on, shifted_bit, bitmask.get(&bit).unwrap_or(&format!("Unknown_0x{:x}", shifted_bit)).to_string()));
-        }
-    }
-
-    Ok(out)
+    Ok(bitmask_handle(bitmask)?.bits(value))
 }
 
 /// Convert from a 64-bit value to a list of strings.
 ///
 /// "Negative" strings are only included if `include_negatives` is set.
 pub fn from_bitmask_str(bitmask: &str, value: usize, include_negatives: bool) -> SimpleResult<Vec<String>> {
-    let mut out: Vec<_> = from_bitmask(bitmask, value)?.iter()
-        .filter(|(is_set, _, _)| include_negatives || *is_set)
-        .map(|(is_set, _, name)| {
-            match *is_set {
-                true  => name.to_string(),
-                false => format!("~{}", name),
-            }
-        })
-        .collect();
-
-    if out.len() == 0 {
-        out.push("(n/a)".to_string());
-    }
+    Ok(bitmask_handle(bitmask)?.strings(value, include_negatives))
+}
 
-    Ok(out)
+/// Approximately how many bytes the loaded bitmasks are using.
+pub(crate) fn memory_usage() -> usize {
+    BITMASKS.iter().map(|(name, values)| {
+        string_size(name) + values.iter().map(|(_, value)| {
+            std::mem::size_of::<usize>() + string_size(value)
+        }).sum::<usize>()
+    }).sum()
 }
@@ -2,14 +2,17 @@ use std::collections::HashMap;
 use simple_error::{SimpleResult, SimpleError, bail};
 use lazy_static::lazy_static;
 
+use crate::{parse_unsigned, string_size};
+
 /// Load an Enum from a .csv file.
 ///
 /// This requires the CSV to be a string file containing exactly two columns:
 /// a numeric column (compatible with an unsigned 64-bit value) and a string
 /// column representing the "name".
 ///
-/// The numeric column must be unique.
-fn load_from_csv(data: &str) -> SimpleResult<HashMap<usize, String>> {
+/// The numeric column must be unique. See [`crate::parse_unsigned`] for the
+/// formats it can be written in. Errors include the `filename` and line.
+fn load_from_csv(filename: &str, data: &str) -> SimpleResult<HashMap<usize, String>> {
     let mut out = HashMap::new();
 
     let mut rdr = csv::ReaderBuilder::new()
@@ -18,21 +21,25 @@ fn load_from_csv(data: &str) -> SimpleResult<HashMap<usize, String>> {
 
     for result in rdr.records() {
         let record = result.map_err(|e| {
-            SimpleError::new(format!("Couldn't read CSV: {}", e))
+            SimpleError::new(format!("Couldn't read CSV {}: {}", filename, e))
         })?;
 
+        // Quoted values can span lines, so count lines the way the reader
+        // does
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+
         if record.len() != 2 {
-            bail!("Bad enum CSV: must be 2 records per line, this line was {}", record.len());
+            bail!("Bad enum CSV {} line {}: must be 2 records per line, this line was {}", filename, line, record.len());
         }
 
-        let number: usize = record.get(0).ok_or(
+        let number = parse_unsigned(record.get(0).ok_or(
             SimpleError::new("Error reading the CSV file")
-        )?.parse().map_err(|e| {
-            SimpleError::new(format!("Couldn't parse first CSV field as integer: {}", e))
+        )?).map_err(|e| {
+            SimpleError::new(format!("Bad enum CSV {} line {}: {}", filename, line, e))
         })?;
 
         if out.contains_key(&number) {
-            bail!("Duplicate key in CSV enum: {}", number);
+            bail!("Bad enum CSV {} line {}: duplicate key {}", filename, line, number);
         }
 
         out.insert(number, record.get(1).ok_or(
@@ -47,16 +54,24 @@ lazy_static! {
     /// Enumerations comment
     pub static ref ENUMS: HashMap<String, HashMap<usize, String>> = {
         let mut h = HashMap::new();
-        h.insert("TerrariaAffix".to_string(),    load_from_csv(include_str!("./terraria_affix.csv")).unwrap());
-        h.insert("TerrariaBuff".to_string(),     load_from_csv(include_str!("./terraria_buff.csv")).unwrap());
-        h.insert("TerrariaGameMode".to_string(), load_from_csv(include_str!("./terraria_game_mode.csv")).unwrap());
-        h.insert("TerrariaItem".to_string(),     load_from_csv(include_str!("./terraria_item.csv")).unwrap());
-        h.insert("TerrariaVersion".to_string(),  load_from_csv(include_str!("./terraria_version.csv")).unwrap());
-        h.insert("TerrariaClothing".to_string(), load_from_csv(include_str!("./terraria_clothing.csv")).unwrap());
+        h.insert("BsonType".to_string(),         load_from_csv("bson_type.csv", include_str!("./bson_type.csv")).unwrap());
+        h.insert("ElfMachine".to_string(),       load_from_csv("elf_machine.csv", include_str!("./elf_machine.csv")).unwrap());
+        h.insert("ElfProgramType".to_string(),   load_from_csv("elf_program_type.csv", include_str!("./elf_program_type.csv")).unwrap());
+        h.insert("ElfSectionType".to_string(),   load_from_csv("elf_section_type.csv", include_str!("./elf_section_type.csv")).unwrap());
+        h.insert("ElfType".to_string(),          load_from_csv("elf_type.csv", include_str!("./elf_type.csv")).unwrap());
+        h.insert("GgmlType".to_string(),         load_from_csv("ggml_type.csv", include_str!("./ggml_type.csv")).unwrap());
+        h.insert("GgufType".to_string(),         load_from_csv("gguf_type.csv", include_str!("./gguf_type.csv")).unwrap());
+
+        h.insert("TerrariaAffix".to_string(),    load_from_csv("terraria_affix.csv", include_str!("./terraria_affix.csv")).unwrap());
+        h.insert("TerrariaBuff".to_string(),     load_from_csv("terraria_buff.csv", include_str!("./terraria_buff.csv")).unwrap());
+        h.insert("TerrariaGameMode".to_string(), load_from_csv("terraria_game_mode.csv", include_str!("./terraria_game_mode.csv")).unwrap());
+        h.insert("TerrariaItem".to_string(),     load_from_csv("terraria_item.csv", include_str!("./terraria_item.csv")).unwrap());
+        h.insert("TerrariaVersion".to_string(),  load_from_csv("terraria_version.csv", include_str!("./terraria_version.csv")).unwrap());
+        h.insert("TerrariaClothing".to_string(), load_from_csv("terraria_clothing.csv", include_str!("./terraria_clothing.csv")).unwrap());
 
-        h.insert("TestEnum".to_string(),         load_from_csv(include_str!("./test_enum.csv")).unwrap());
+        h.insert("TestEnum".to_string(),         load_from_csv("test_enum.csv", include_str!("./test_enum.csv")).unwrap());
 
-        h.insert("WindowsError".to_string(),     load_from_csv(include_str!("./windows_error.csv")).unwrap());
+        h.insert("WindowsError".to_string(),     load_from_csv("windows_error.csv", include_str!("./windows_error.csv")).unwrap());
 
         h
     };
@@ -66,8 +81,89 @@ pub fn enum_exists(name: &str) -> bool {
     ENUMS.contains_key(name)
 }
 
-pub fn from_enum(name: &str, value: usize) -> SimpleResult<Option<&str>> {
-    Ok(ENUMS.get(name).ok_or(
+/// An enum that's already been looked up by name.
+///
+/// Looking up a value through a handle skips finding the enum, which matters
+/// when the same enum is used thousands of times (an inventory full of
+/// items, say). Handles are cheap to copy, and are good forever.
+#[derive(Debug, Clone, Copy)]
+pub struct EnumHandle {
+    name: &'static str,
+    values: &'static HashMap<usize, String>,
+}
+
+impl EnumHandle {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get the name of `value`, if the enum has one.
+    pub fn get(&self, value: usize) -> Option<&'static str> {
+        self.values.get(&value).map(|s| &s[..])
+    }
+
+    /// Get the number of distinct values.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// Look up an enum once, to get its values quickly later.
+pub fn enum_handle(name: &str) -> SimpleResult<EnumHandle> {
+    let (name, values) = ENUMS.get_key_value(name).ok_or(
         SimpleError::new(format!("No such enum: {}", name))
-    )?.get(&value).map(|s| &s[..]))
+    )?;
+
+    Ok(EnumHandle {
+        name: &name[..],
+        values: values,
+    })
+}
+
+pub fn from_enum(name: &str, value: usize) -> SimpleResult<Option<&str>> {
+    Ok(enum_handle(name)?.get(value))
+}
+
+/// Get the names of every loaded enum, in alphabetical order.
+pub fn enum_names() -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = ENUMS.keys().map(|k| &k[..]).collect();
+    names.sort();
+
+    names
+}
+
+/// Get the number of distinct values in an enum.
+pub fn enum_size(name: &str) -> SimpleResult<usize> {
+    Ok(enum_handle(name)?.len())
+}
+
+/// Approximately how many bytes the loaded enums are using.
+pub(crate) fn memory_usage() -> usize {
+    ENUMS.iter().map(|(name, values)| {
+        string_size(name) + values.iter().map(|(_, value)| {
+            std::mem::size_of::<usize>() + string_size(value)
+        }).sum::<usize>()
+    }).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_lines() -> SimpleResult<()> {
+        let values = load_from_csv("test.csv", "0,A\n'B',\"Quoted\nname\"\n0x10,C\n")?;
+        assert_eq!(Some(&"Quoted\nname".to_string()), values.get(&0x42));
+        assert_eq!(Some(&"C".to_string()), values.get(&0x10));
+
+        // Both lines of the quoted value count
+        let e = load_from_csv("test.csv", "0,A\n1,\"Two\nlines\"\nbad,D\n").unwrap_err();
+        assert!(e.to_string().contains("test.csv line 4"), "{}", e);
+
+        Ok(())
+    }
 }
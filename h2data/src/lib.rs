@@ -4,6 +4,9 @@
 //! used by the datatypes `H2Enum` and `H2Bitmask` respectively. You probably
 //! don't want to use these directly.
 //!
+//! We also store Offsets, which are used by analyzers to find fields in
+//! formats that change between versions.
+//!
 //! There's also nothing stopping us from loading new Enums or Bitmasks at
 //! runtime. We'll have to see if that's reasonable.
 //!
@@ -25,8 +28,8 @@
 //! # Bitmask
 //!
 //! Bitmasks are similar to enums, in that they are loaded from .csv files. The
-//! big difference is that a single bitmask is made up of values between 0 and
-//! 63 that each correspond to bits. For example, the following configuration:
+//! big difference is that a single bitmask is made up of bit numbers that each
+//! correspond to a bit. For example, the following configuration:
 //!
 //! ```csv
 //! 0,VALUE0
@@ -37,9 +40,81 @@
 //! means that bit 0 (the rightmost) is `VALUE0`, bit 1 is `VALUE1`, and bit 2
 //! is `VALUE2`. That means if you match the number 0x05 (0101 in binary), it'll
 //! be `VALUE0 | ~VALUE1 | VALUE2`.
+//!
+//! Bit numbers can go past 63 (up to [`MAX_BITMASK_BITS`]), for flag fields
+//! that are 128 or 256 bits wide. Those are read as several words, and
+//! [`BitmaskHandle::bits_wide`] takes care of naming the bits; which bit is
+//! "bit 0" is up to the datatype that reads them.
+//!
+//! # String constants
+//!
+//! Some formats have canonical strings instead of numbers - FourCC chunk
+//! types (`RIFF`, `"fmt "`), country codes, and so on. A table of string
+//! constants is a .csv file in the format `<value>,<description>`, where both
+//! the values and the descriptions must be unique, since they can be looked
+//! up either way ([`from_string_constant`] and [`string_constant_value`]).
+//! Quote a value if it has spaces at the end. They must be added to
+//! [string_constants/mod.rs](string_constants/mod.rs) as well.
+//!
+//! # Handles
+//!
+//! Looking up an enum or bitmask by name hashes the name every time. Code that
+//! uses the same one over and over can look it up once with [`enum_handle`]
+//! or [`bitmask_handle`] (or [`string_constants_handle`]), and use the handle from then on.
+//!
+//! # Offsets
+//!
+//! An offset table maps field names to their offsets within a file, so
+//! analyzers don't need to hardcode them. Like the others, they're loaded from
+//! .csv files, in the format `<name>,<offset>`; the offset can be decimal or
+//! hex (prefixed with `0x`). What the offset is relative to is up to the
+//! analyzer.
+//!
+//! Since file formats change, each offset table has a line named `@versions`
+//! with the range of versions it applies to - `0..230` is versions 0 through
+//! 229, and `230..` is version 230 and everything after. When a new version of
+//! a format moves things around, it only needs a new .csv file, registered in
+//! [offsets/mod.rs](offsets/mod.rs) under the same name as the others.
+//!
+//! # Numbers
+//!
+//! Numbers in any of the data files can be written in decimal, hex (`0x`),
+//! octal (`0o`), or binary (`0b`), with `_` to group digits (`0xffff_ffff`),
+//! with a type suffix (`255u8`), or as a character literal (`'A'`) - the same
+//! rules as [`generic_number::Integer::parse`]. The parsers are exported as
+//! [`parse_integer`], [`parse_unsigned`], and [`parse_float`], so anything
+//! else that reads hand-written data can accept the same formats. Errors from
+//! loading a file include the file name and line number.
+//!
+//! # Memory usage
+//!
+//! Everything here is loaded the first time it's used, and stays loaded.
+//! [`memory_usage`] gives a rough idea of how much memory that is.
 
 mod enums;
-pub use enums::{from_enum, enum_exists};
+pub use enums::{from_enum, enum_exists, enum_names, enum_size, enum_handle, EnumHandle};
 
 mod bitmasks;
-pub use bitmasks::{from_bitmask, from_bitmask_str, bitmask_exists};
+pub use bitmasks::{from_bitmask, from_bitmask_str, bitmask_exists, bitmask_handle, BitmaskHandle, MAX_BITMASK_BITS};
+
+mod string_constants;
+pub use string_constants::{from_string_constant, string_constant_value, string_constants_exist, string_constants_names, string_constants_handle, StringConstantsHandle};
+
+mod offsets;
+pub use offsets::{from_offsets, offsets_exist};
+
+mod parse;
+pub use parse::{parse_integer, parse_unsigned, parse_float};
+
+/// The approximate size of a string, including its heap allocation.
+pub(crate) fn string_size(s: &str) -> usize {
+    std::mem::size_of::<String>() + s.len()
+}
+
+/// Approximately how many bytes all the loaded data is using.
+///
+/// This counts the keys and values in every table, but not the overhead of
+/// the hash tables themselves. Note that calling this loads everything.
+pub fn memory_usage() -> usize {
+    enums::memory_usage() + bitmasks::memory_usage() + string_constants::memory_usage() + offsets::memory_usage()
+}
@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use simple_error::{SimpleResult, SimpleError, bail};
+use lazy_static::lazy_static;
+
+use crate::{parse_unsigned, string_size};
+
+/// The name of the line that gives the range of versions a table applies to.
+const VERSIONS: &str = "@versions";
+
+/// Parse a range of versions, written as `<start>..<end>` - the start is
+/// inclusive and the end is exclusive, the same as a Rust range. Leave out the
+/// end (`<start>..`) for a table that applies to the latest version.
+fn parse_versions(s: &str) -> SimpleResult<Range<usize>> {
+    let (start, end) = s.split_once("..").ok_or(
+        SimpleError::new(format!("Versions must be written as <start>..<end>, not {}", s))
+    )?;
+
+    let start = parse_unsigned(start)?;
+    let end = match end.trim() {
+        ""  => usize::MAX,
+        end => parse_unsigned(end)?,
+    };
+
+    if start >= end {
+        bail!("Version range {} is empty", s);
+    }
+
+    Ok(start..end)
+}
+
+/// Load an offset table from a .csv file.
+///
+/// This requires the CSV to be a string file containing exactly two columns:
+/// a string column representing the "name" of the field, and a numeric column
+/// with its offset. See [`crate::parse_unsigned`] for the formats the offset
+/// can be written in.
+///
+/// One line must be named `@versions`, and give the range of versions the
+/// table applies to (see [`parse_versions`]).
+///
+/// The name column must be unique. Errors include the `filename` and line.
+fn load_from_csv(filename: &str, data: &str) -> SimpleResult<(Range<usize>, HashMap<String, usize>)> {
+    let mut out = HashMap::new();
+    let mut versions = None;
+
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(data.as_bytes());
+
+    for result in rdr.records() {
+        let record = result.map_err(|e| {
+            SimpleError::new(format!("Couldn't read offsets CSV {}: {}", filename, e))
+        })?;
+
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+
+        if record.len() != 2 {
+            bail!("Bad offsets CSV {} line {}: must be 2 records per line, this line was {}", filename, line, record.len());
+        }
+
+        let name = record.get(0).ok_or(
+            SimpleError::new("Couldn't parse the CSV")
+        )?.to_string();
+
+        let value = record.get(1).ok_or(
+            SimpleError::new("Error reading the CSV file")
+        )?;
+
+        if name == VERSIONS {
+            if versions.is_some() {
+                bail!("Bad offsets CSV {} line {}: duplicate {}", filename, line, VERSIONS);
+            }
+
+            versions = Some(parse_versions(value).map_err(|e| {
+                SimpleError::new(format!("Bad offsets CSV {} line {}: {}", filename, line, e))
+            })?);
+
+            continue;
+        }
+
+        let offset = parse_unsigned(value).map_err(|e| {
+            SimpleError::new(format!("Bad offsets CSV {} line {}: {}", filename, line, e))
+        })?;
+
+        if out.contains_key(&name) {
+            bail!("Bad offsets CSV {} line {}: duplicate name {}", filename, line, name);
+        }
+
+        out.insert(name, offset);
+    }
+
+    let versions = versions.ok_or(
+        SimpleError::new(format!("Bad offsets CSV {}: no {} line", filename, VERSIONS))
+    )?;
+
+    Ok((versions, out))
+}
+
+lazy_static! {
+    /// Pre-load the OFFSETS structure
+    ///
+    /// Each name maps to a list of tables, keyed by the range of versions they
+    /// apply to (which comes from the table's `@versions` line).
+    pub static ref OFFSETS: HashMap<String, Vec<(Range<usize>, HashMap<String, usize>)>> = {
+        let mut h = HashMap::new();
+        h.insert("Terraria".to_string(), vec![
+            load_from_csv("terraria_1_3.csv", include_str!("./terraria_1_3.csv")).unwrap(),
+            load_from_csv("terraria_1_4.csv", include_str!("./terraria_1_4.csv")).unwrap(),
+        ]);
+
+        h
+    };
+}
+
+/// Does an offset table with the given name exist (for any version)?
+pub fn offsets_exist(name: &str) -> bool {
+    OFFSETS.contains_key(name)
+}
+
+/// Get the offset table for a particular version.
+///
+/// The table maps field names to offsets, exactly as they're written in the
+/// original CSV file. Fields that don't exist in a particular version are
+/// simply left out.
+pub fn from_offsets(name: &str, version: usize) -> SimpleResult<&HashMap<String, usize>> {
+    let tables = OFFSETS.get(name).ok_or(
+        SimpleError::new(format!("No such offset table: {}", name))
+    )?;
+
+    tables.iter().find(|(versions, _)| versions.contains(&version)).map(|(_, table)| table).ok_or(
+        SimpleError::new(format!("No offset table for {} matches version {}", name, version))
+    )
+}
+
+/// Approximately how many bytes the loaded offset tables are using.
+pub(crate) fn memory_usage() -> usize {
+    OFFSETS.iter().map(|(name, tables)| {
+        string_size(name) + tables.iter().map(|(_, table)| {
+            std::mem::size_of::<Range<usize>>() + table.iter().map(|(field, _)| {
+                string_size(field) + std::mem::size_of::<usize>()
+            }).sum::<usize>()
+        }).sum::<usize>()
+    }).sum()
+}
@@ -0,0 +1,116 @@
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use generic_number::Integer;
+
+/// Parse an integer from a hand-written data file.
+///
+/// This accepts everything [`Integer::parse`] does - decimal, hex, octal, and
+/// binary (`123`, `0x7b`, `0o173`, `0b1111011`), a sign, `_` separators,
+/// type suffixes (`255u8`), and character literals (`'A'`, `'\n'`).
+///
+/// Surrounding whitespace is ignored. Nothing depends on the locale - `,` and
+/// `.` are never treated as separators, since they'd be ambiguous.
+pub fn parse_integer(s: &str) -> SimpleResult<i128> {
+    Integer::parse(s)?.to_i128()
+}
+
+/// Parse an integer that can't be negative, such as an enum value or offset.
+///
+/// See [`parse_integer`] for the accepted formats.
+pub fn parse_unsigned(s: &str) -> SimpleResult<usize> {
+    let value = parse_integer(s)?;
+
+    if value < 0 {
+        bail!("'{}' is negative, which isn't allowed here", s.trim());
+    }
+
+    if value > usize::MAX as i128 {
+        bail!("'{}' is too large (the maximum is {})", s.trim(), usize::MAX);
+    }
+
+    Ok(value as usize)
+}
+
+/// Parse a floating point value from a hand-written data file.
+///
+/// This accepts anything Rust does (`1.5`, `-2e10`, `inf`, etc), plus `_` as
+/// a group separator. Integers in any of the formats [`parse_integer`]
+/// accepts work too. As with integers, `,` is never a decimal separator.
+pub fn parse_float(s: &str) -> SimpleResult<f64> {
+    let s = s.trim();
+
+    if let Ok(i) = parse_integer(s) {
+        return Ok(i as f64);
+    }
+
+    let cleaned: String = s.chars().filter(|c| *c != '_').collect();
+    cleaned.parse::<f64>().map_err(|e| {
+        SimpleError::new(format!("Couldn't parse '{}' as a number: {}", s, e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_integer() -> SimpleResult<()> {
+        assert_eq!(123,   parse_integer("123")?);
+        assert_eq!(123,   parse_integer(" 123 ")?);
+        assert_eq!(-123,  parse_integer("-123")?);
+        assert_eq!(0x7b,  parse_integer("0x7b")?);
+        assert_eq!(-0x7b, parse_integer("-0x7b")?);
+        assert_eq!(0o173, parse_integer("0o173")?);
+        assert_eq!(0b101, parse_integer("0b101")?);
+        assert_eq!(0xffff_ffff, parse_integer("0xffff_ffff")?);
+        assert_eq!(255,   parse_integer("255u8")?);
+
+        assert!(parse_integer("").is_err());
+        assert!(parse_integer("abc").is_err());
+        assert!(parse_integer("0xfg").is_err());
+        assert!(parse_integer("1,000").is_err());
+        assert!(parse_integer("1.5").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_character() -> SimpleResult<()> {
+        assert_eq!(0x41, parse_integer("'A'")?);
+        assert_eq!(0x0a, parse_integer("'\\n'")?);
+        assert_eq!(0x41, parse_unsigned("'A'")?);
+
+        assert!(parse_integer("'AB'").is_err());
+        assert!(parse_integer("'A").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_unsigned() -> SimpleResult<()> {
+        assert_eq!(0,    parse_unsigned("0")?);
+        assert_eq!(0x10, parse_unsigned("0x10")?);
+
+        assert!(parse_unsigned("-1").is_err());
+        assert!(parse_unsigned("-0x10").is_err());
+        assert!(parse_unsigned("0x1_0000_0000_0000_0000").is_err());
+        assert!(parse_unsigned("ten").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_float() -> SimpleResult<()> {
+        assert_eq!(1.5,     parse_float("1.5")?);
+        assert_eq!(-2e10,   parse_float("-2e10")?);
+        assert_eq!(1000.5,  parse_float("1_000.5")?);
+        assert_eq!(16.0,    parse_float("0x10")?);
+        assert_eq!(65.0,    parse_float("'A'")?);
+        assert!(parse_float("inf")?.is_infinite());
+
+        assert!(parse_float("1,5").is_err());
+        assert!(parse_float("one").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use simple_error::{SimpleResult, SimpleError, bail};
+use lazy_static::lazy_static;
+
+use crate::string_size;
+
+/// A loaded table of string constants, indexed both ways.
+#[derive(Debug)]
+pub struct StringConstants {
+    /// Value -> name
+    names: HashMap<String, String>,
+
+    /// Name -> value
+    values: HashMap<String, String>,
+}
+
+/// Load a table of string constants from a .csv file.
+///
+/// This requires the CSV to be a string file containing exactly two columns:
+/// the value (exactly as it appears in the data - quote it if it has spaces
+/// at the end, like `"fmt "`) and its name.
+///
+/// Both columns must be unique, since either one can be looked up. Errors
+/// include the `filename` and line.
+fn load_from_csv(filename: &str, data: &str) -> SimpleResult<StringConstants> {
+    let mut out = StringConstants {
+        names: HashMap::new(),
+        values: HashMap::new(),
+    };
+
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(data.as_bytes());
+
+    for result in rdr.records() {
+        let record = result.map_err(|e| {
+            SimpleError::new(format!("Couldn't read CSV {}: {}", filename, e))
+        })?;
+
+        let line = record.position().map(|p| p.line()).unwrap_or(0);
+
+        if record.len() != 2 {
+            bail!("Bad string constant CSV {} line {}: must be 2 records per line, this line was {}", filename, line, record.len());
+        }
+
+        let value = record.get(0).ok_or(
+            SimpleError::new("Error reading the CSV file")
+        )?.to_string();
+        let name = record.get(1).ok_or(
+            SimpleError::new("Couldn't parse the CSV")
+        )?.to_string();
+
+        if value.is_empty() {
+            bail!("Bad string constant CSV {} line {}: empty value", filename, line);
+        }
+
+        if out.names.contains_key(&value) {
+            bail!("Bad string constant CSV {} line {}: duplicate value {:?}", filename, line, value);
+        }
+
+        if out.values.contains_key(&name) {
+            bail!("Bad string constant CSV {} line {}: duplicate name {:?}", filename, line, name);
+        }
+
+        out.names.insert(value.clone(), name.clone());
+        out.values.insert(name, value);
+    }
+
+    Ok(out)
+}
+
+lazy_static! {
+    /// Pre-load the string constants
+    pub static ref STRING_CONSTANTS: HashMap<String, StringConstants> = {
+        let mut h = HashMap::new();
+        h.insert("FourCC".to_string(),      load_from_csv("fourcc.csv", include_str!("./fourcc.csv")).unwrap());
+
+        h.insert("TestStrings".to_string(), load_from_csv("test_strings.csv", include_str!("./test_strings.csv")).unwrap());
+
+        h
+    };
+}
+
+pub fn string_constants_exist(name: &str) -> bool {
+    STRING_CONSTANTS.contains_key(name)
+}
+
+/// A table of string constants that's already been looked up by name - see
+/// [`crate::EnumHandle`], which does the same thing for enums.
+#[derive(Debug, Clone, Copy)]
+pub struct StringConstantsHandle {
+    name: &'static str,
+    constants: &'static StringConstants,
+}
+
+impl StringConstantsHandle {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get the name of `value` (like `"fmt "` -> `"Format chunk"`), if the
+    /// table has one.
+    pub fn get(&self, value: &str) -> Option<&'static str> {
+        self.constants.names.get(value).map(|s| &s[..])
+    }
+
+    /// Get the value with a given name - the opposite of
+    /// [`StringConstantsHandle::get`].
+    pub fn value_of(&self, name: &str) -> Option<&'static str> {
+        self.constants.values.get(name).map(|s| &s[..])
+    }
+
+    /// Get every `(value, name)` pair, sorted by value.
+    pub fn all(&self) -> Vec<(&'static str, &'static str)> {
+        let mut out: Vec<(&'static str, &'static str)> = self.constants.names.iter().map(|(value, name)| (&value[..], &name[..])).collect();
+        out.sort();
+
+        out
+    }
+
+    /// Get the number of constants.
+    pub fn len(&self) -> usize {
+        self.constants.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.constants.names.is_empty()
+    }
+}
+
+/// Look up a table of string constants once, to get its values quickly
+/// later.
+pub fn string_constants_handle(name: &str) -> SimpleResult<StringConstantsHandle> {
+    let (name, constants) = STRING_CONSTANTS.get_key_value(name).ok_or(
+        SimpleError::new(format!("No such string constants: {}", name))
+    )?;
+
+    Ok(StringConstantsHandle {
+        name: &name[..],
+        constants: constants,
+    })
+}
+
+/// Get the name of a string constant by its value.
+pub fn from_string_constant(name: &str, value: &str) -> SimpleResult<Option<&'static str>> {
+    Ok(string_constants_handle(name)?.get(value))
+}
+
+/// Get the value of a string constant by its name.
+pub fn string_constant_value(name: &str, constant: &str) -> SimpleResult<Option<&'static str>> {
+    Ok(string_constants_handle(name)?.value_of(constant))
+}
+
+/// Get the names of every loaded table of string constants, in alphabetical
+/// order.
+pub fn string_constants_names() -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = STRING_CONSTANTS.keys().map(|k| &k[..]).collect();
+    names.sort();
+
+    names
+}
+
+/// Approximately how many bytes the loaded string constants are using. Each
+/// string is stored twice, once for each direction.
+pub(crate) fn memory_usage() -> usize {
+    STRING_CONSTANTS.iter().map(|(name, constants)| {
+        string_size(name) + constants.names.iter().map(|(value, name)| {
+            2 * (string_size(value) + string_size(name))
+        }).sum::<usize>()
+    }).sum()
+}
@@ -0,0 +1,207 @@
+//! A derive macro that maps a Rust struct onto an `H2Struct`.
+//!
+//! This is re-exported by `h2datatype` - use it from there, as
+//! `#[derive(h2datatype::H2Layout)]`. See `h2datatype::layout` for the
+//! traits it implements and how to read a value with it.
+//!
+//! Each field needs an `#[h2(...)]` attribute with an expression for its
+//! `H2Type`, unless the field's own type implements `H2Layout` (that is, it's
+//! another derived struct). The expression can use `?`. Fields end up in the
+//! `H2Struct` in the order they're declared, with the same names.
+//!
+//! ```ignore
+//! #[derive(H2Layout)]
+//! struct Header {
+//!     #[h2(H2String::new(4, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?)]
+//!     magic: String,
+//!
+//!     #[h2(H2Integer::new(IntegerReader::U32(Endian::Little), DefaultFormatter::new_integer()))]
+//!     version: u32,
+//! }
+//! ```
+//!
+//! Only structs with named fields (and no generics) are supported.
+
+use proc_macro::{Delimiter, TokenStream, TokenTree};
+
+/// A field, as far as we care about it.
+struct Field {
+    /// The Rust name, as written
+    name: String,
+
+    /// The name in the `H2Struct` (without any `r#`)
+    field_name: String,
+
+    /// The `#[h2(...)]` expression, if there was one
+    datatype: Option<String>,
+
+    /// The Rust type
+    rust_type: String,
+}
+
+#[proc_macro_derive(H2Layout, attributes(h2))]
+pub fn derive_h2_layout(input: TokenStream) -> TokenStream {
+    let generated = match parse_struct(input) {
+        Ok((name, fields)) => generate(&name, &fields),
+        Err(e)             => format!("compile_error!({:?});", e),
+    };
+
+    generated.parse().unwrap()
+}
+
+/// Find the struct's name and fields.
+fn parse_struct(input: TokenStream) -> Result<(String, Vec<Field>), String> {
+    let mut tokens = input.into_iter();
+
+    // Skip attributes and visibility - anything inside them is in a group, so
+    // the first "struct" we see at this level is the real one
+    let name = loop {
+        match tokens.next() {
+            Some(TokenTree::Ident(i)) if i.to_string() == "struct" => match tokens.next() {
+                Some(TokenTree::Ident(name)) => break name.to_string(),
+                _                            => return Err("Expected a struct name".to_string()),
+            },
+            Some(TokenTree::Ident(i)) if i.to_string() == "enum" || i.to_string() == "union" => {
+                return Err("H2Layout can only be derived for structs".to_string());
+            },
+            Some(_) => (),
+            None    => return Err("Expected a struct".to_string()),
+        }
+    };
+
+    match tokens.next() {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Brace => Ok((name, parse_fields(g.stream())?)),
+        Some(TokenTree::Punct(p)) if p.as_char() == '<'                => Err("H2Layout can't be derived for generic structs".to_string()),
+        _                                                              => Err("H2Layout can only be derived for structs with named fields".to_string()),
+    }
+}
+
+/// Split the body of a struct into fields.
+fn parse_fields(body: TokenStream) -> Result<Vec<Field>, String> {
+    let mut fields: Vec<Field> = vec![];
+    let mut current: Vec<TokenTree> = vec![];
+
+    // Commas inside generics (like `HashMap<A, B>`) aren't in a group, so
+    // keep track of how deep we are
+    let mut depth = 0;
+
+    for token in body {
+        match &token {
+            TokenTree::Punct(p) if p.as_char() == '<' => depth += 1,
+            TokenTree::Punct(p) if p.as_char() == '>' => depth -= 1,
+            TokenTree::Punct(p) if p.as_char() == ',' && depth == 0 => {
+                fields.push(parse_field(std::mem::take(&mut current))?);
+                continue;
+            },
+            _ => (),
+        }
+
+        current.push(token);
+    }
+
+    if !current.is_empty() {
+        fields.push(parse_field(current)?);
+    }
+
+    if fields.is_empty() {
+        return Err("H2Layout needs at least one field".to_string());
+    }
+
+    Ok(fields)
+}
+
+fn parse_field(tokens: Vec<TokenTree>) -> Result<Field, String> {
+    let mut tokens = tokens.into_iter().peekable();
+    let mut datatype: Option<String> = None;
+
+    // Attributes - we only care about #[h2(...)]
+    while let Some(TokenTree::Punct(p)) = tokens.peek() {
+        if p.as_char() != '#' {
+            break;
+        }
+        tokens.next();
+
+        if let Some(TokenTree::Group(attribute)) = tokens.next() {
+            let mut inner = attribute.stream().into_iter();
+
+            if let (Some(TokenTree::Ident(i)), Some(TokenTree::Group(arguments))) = (inner.next(), inner.next()) {
+                if i.to_string() == "h2" {
+                    if arguments.stream().is_empty() {
+                        return Err("#[h2(...)] needs a type".to_string());
+                    }
+
+                    datatype = Some(arguments.stream().to_string());
+                }
+            }
+        }
+    }
+
+    // Visibility
+    if let Some(TokenTree::Ident(i)) = tokens.peek() {
+        if i.to_string() == "pub" {
+            tokens.next();
+
+            if let Some(TokenTree::Group(g)) = tokens.peek() {
+                if g.delimiter() == Delimiter::Parenthesis {
+                    tokens.next();
+                }
+            }
+        }
+    }
+
+    let name = match tokens.next() {
+        Some(TokenTree::Ident(i)) => i.to_string(),
+        _                         => return Err("Expected a field name".to_string()),
+    };
+
+    match tokens.next() {
+        Some(TokenTree::Punct(p)) if p.as_char() == ':' => (),
+        _                                               => return Err(format!("Expected a type for field {}", name)),
+    }
+
+    let rust_type: TokenStream = tokens.collect();
+    if rust_type.is_empty() {
+        return Err(format!("Expected a type for field {}", name));
+    }
+
+    Ok(Field {
+        field_name: name.trim_start_matches("r#").to_string(),
+        name: name,
+        datatype: datatype,
+        rust_type: rust_type.to_string(),
+    })
+}
+
+/// Write out the `H2Layout` and `FromResolved` implementations.
+fn generate(name: &str, fields: &[Field]) -> String {
+    let definitions: Vec<String> = fields.iter().map(|field| {
+        let datatype = match &field.datatype {
+            Some(datatype) => format!("{{ {} }}", datatype),
+            None           => format!("<{} as ::h2datatype::H2Layout>::h2type()?", field.rust_type),
+        };
+
+        format!("(::std::string::String::from({:?}), {})", field.field_name, datatype)
+    }).collect();
+
+    let values: Vec<String> = fields.iter().map(|field| {
+        format!("{}: ::h2datatype::FromResolved::from_resolved(::h2datatype::layout::field(resolved, {:?})?)?", field.name, field.field_name)
+    }).collect();
+
+    format!("
+        impl ::h2datatype::H2Layout for {name} {{
+            fn h2type() -> ::std::result::Result<::h2datatype::H2Type, ::h2datatype::layout::SimpleError> {{
+                ::h2datatype::composite::H2Struct::new(::std::vec![{definitions}])
+            }}
+        }}
+
+        impl ::h2datatype::FromResolved for {name} {{
+            fn from_resolved(resolved: &::h2datatype::ResolvedType) -> ::std::result::Result<Self, ::h2datatype::layout::SimpleError> {{
+                ::std::result::Result::Ok(Self {{ {values} }})
+            }}
+        }}
+    ",
+        name = name,
+        definitions = definitions.join(", "),
+        values = values.join(", "),
+    )
+}
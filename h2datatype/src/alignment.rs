@@ -14,6 +14,11 @@ use std::ops::Range;
 /// multiple of the alignment size, it also throws an error if an unaligned
 /// value (that is, a value that doesn't also *start* on a multiple of the
 /// alignment size) is attempted.
+///
+/// [`Alignment::Warn`] is in between: an unaligned value is padded like
+/// [`Alignment::Loose`], but resolving it adds a warning to the
+/// [`crate::ResolvedType`] - a misaligned structure is often a sign that it's
+/// in the wrong place.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum Alignment {
     /// Don't align at all
@@ -26,6 +31,10 @@ pub enum Alignment {
 
     /// Only pad after, but error out if the start isn't aligned.
     Strict(u64),
+
+    /// Pad like [`Alignment::Loose`], but warn if the start isn't aligned
+    /// (see [`crate::ResolvedType::warnings`]).
+    Warn(u64),
 }
 
 impl Alignment {
@@ -51,8 +60,9 @@ impl Alignment {
             // Do nothing
             Self::None => Ok(range),
 
-            // Ensure the size is a multiple of the pad value
-            Self::Loose(m) => {
+            // Ensure the size is a multiple of the pad value (warnings are
+            // added when resolving)
+            Self::Loose(m) | Self::Warn(m) => {
                 let new_size = Self::round_up(range.end - range.start, m);
                 Ok(range.start..(range.start + new_size))
             },
@@ -68,6 +78,15 @@ impl Alignment {
             },
         }
     }
+
+    /// If a value starting at `start` isn't aligned the way it's expected to
+    /// be, describe the problem (only [`Alignment::Warn`] does this).
+    pub fn warning(self, start: u64) -> Option<String> {
+        match self {
+            Self::Warn(m) if m != 0 && !start.is_multiple_of(m) => Some(format!("Starts at 0x{:x}, which isn't aligned to {} bytes", start, m)),
+            _                                                    => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -154,4 +173,18 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_warn() -> SimpleResult<()> {
+        // Pads the same as loose alignment, even when it's not aligned
+        assert_eq!(0..4, Alignment::Warn(4).align(0..1)?);
+        assert_eq!(3..7, Alignment::Warn(4).align(3..4)?);
+
+        assert_eq!(None, Alignment::Warn(4).warning(8));
+        assert_eq!(None, Alignment::Warn(0).warning(3));
+        assert_eq!(Some("Starts at 0x3, which isn't aligned to 4 bytes".to_string()), Alignment::Warn(4).warning(3));
+        assert_eq!(None, Alignment::Loose(4).warning(3));
+
+        Ok(())
+    }
+
 }
@@ -0,0 +1,281 @@
+//! Builders for composite types.
+//!
+//! Big definitions - a whole save file, say - written with
+//! [`H2Struct::new`] need an `unwrap()` or `?` on every field that can fail,
+//! and when one does, the error doesn't say which field it was. The
+//! builders take fields that may have failed, check each field as it's added,
+//! and report every problem (with the field's name) from `build()`.
+
+use simple_error::{SimpleResult, SimpleError};
+
+use crate::{Alignment, H2Type};
+use crate::composite::{H2Array, H2Struct};
+
+/// Anything that a builder can take as a field - an [`H2Type`], or the
+/// result of a constructor that can fail.
+pub trait IntoH2Type {
+    fn into_h2type(self) -> SimpleResult<H2Type>;
+}
+
+impl IntoH2Type for H2Type {
+    fn into_h2type(self) -> SimpleResult<H2Type> {
+        Ok(self)
+    }
+}
+
+impl IntoH2Type for SimpleResult<H2Type> {
+    fn into_h2type(self) -> SimpleResult<H2Type> {
+        self
+    }
+}
+
+impl IntoH2Type for H2StructBuilder {
+    fn into_h2type(self) -> SimpleResult<H2Type> {
+        self.build()
+    }
+}
+
+impl IntoH2Type for H2ArrayBuilder {
+    fn into_h2type(self) -> SimpleResult<H2Type> {
+        self.build()
+    }
+}
+
+/// Builds an [`H2Struct`] one field at a time.
+///
+/// ```
+/// use h2datatype::composite::*;
+/// use h2datatype::simple::numeric::*;
+/// use generic_number::*;
+///
+/// let t = H2StructBuilder::new()
+///     .field("magic", H2Integer::new(IntegerReader::U32(Endian::Little), HexFormatter::pretty_integer()))
+///     .field("items", H2ArrayBuilder::new(H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())).length(4))
+///     .build()
+///     .unwrap();
+/// assert_eq!("struct { u32le magic; u8[4] items; }", t.describe());
+///
+/// // Every problem is reported, with the field it belongs to
+/// let error = H2StructBuilder::new()
+///     .field("magic", H2Integer::new(IntegerReader::U32(Endian::Little), HexFormatter::pretty_integer()))
+///     .field("items", H2ArrayBuilder::new(H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())))
+///     .field("magic", H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer()))
+///     .build()
+///     .unwrap_err();
+/// assert_eq!("Field 'items': Array length must be set; Field 'magic': A field with that name already exists", error.to_string());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct H2StructBuilder {
+    alignment: Option<Alignment>,
+    fields: Vec<(String, H2Type)>,
+    errors: Vec<String>,
+}
+
+impl H2StructBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Align the struct (the fields are aligned separately).
+    pub fn aligned(mut self, alignment: Alignment) -> Self {
+        self.alignment = Some(alignment);
+        self
+    }
+
+    /// Add a field to the end of the struct.
+    ///
+    /// If something's wrong with the field, it's remembered and reported by
+    /// [`H2StructBuilder::build`].
+    pub fn field(mut self, name: &str, field_type: impl IntoH2Type) -> Self {
+        let result = field_type.into_h2type().and_then(|field_type| {
+            if name.is_empty() {
+                return Err(SimpleError::new("Field names can't be empty"));
+            }
+
+            if self.fields.iter().any(|(existing, _)| existing == name) {
+                return Err(SimpleError::new("A field with that name already exists"));
+            }
+
+            Ok(field_type)
+        });
+
+        match result {
+            Ok(field_type) => self.fields.push((name.to_string(), field_type)),
+            Err(e)         => self.errors.push(format!("Field '{}': {}", name, e)),
+        }
+
+        self
+    }
+
+    /// Create the struct, or report everything that went wrong.
+    pub fn build(self) -> SimpleResult<H2Type> {
+        if !self.errors.is_empty() {
+            return Err(SimpleError::new(self.errors.join("; ")));
+        }
+
+        H2Struct::new_aligned(self.alignment.unwrap_or(Alignment::None), self.fields)
+    }
+}
+
+/// Builds an [`H2Array`].
+///
+/// The length has to be set. Setting a stride or a number of planes makes it
+/// a strided or interleaved array (see [`H2Array`]), but not both.
+#[derive(Debug, Clone)]
+pub struct H2ArrayBuilder {
+    field_type: SimpleResult<H2Type>,
+    alignment: Option<Alignment>,
+    length: Option<u64>,
+    stride: Option<u64>,
+    planes: Option<u64>,
+}
+
+impl H2ArrayBuilder {
+    pub fn new(field_type: impl IntoH2Type) -> Self {
+        Self {
+            field_type: field_type.into_h2type(),
+            alignment: None,
+            length: None,
+            stride: None,
+            planes: None,
+        }
+    }
+
+    pub fn aligned(mut self, alignment: Alignment) -> Self {
+        self.alignment = Some(alignment);
+        self
+    }
+
+    pub fn length(mut self, length: u64) -> Self {
+        self.length = Some(length);
+        self
+    }
+
+    pub fn stride(mut self, stride: u64) -> Self {
+        self.stride = Some(stride);
+        self
+    }
+
+    pub fn planes(mut self, planes: u64) -> Self {
+        self.planes = Some(planes);
+        self
+    }
+
+    /// Create the array, or report everything that went wrong.
+    pub fn build(self) -> SimpleResult<H2Type> {
+        let mut errors: Vec<String> = vec![];
+
+        let field_type = match self.field_type {
+            Ok(t)  => Some(t),
+            Err(e) => { errors.push(format!("Array element: {}", e)); None },
+        };
+
+        let length = match self.length {
+            Some(length) => Some(length),
+            None         => { errors.push("Array length must be set".to_string()); None },
+        };
+
+        if self.stride.is_some() && self.planes.is_some() {
+            errors.push("Arrays can't have both a stride and planes".to_string());
+        }
+
+        let (field_type, length) = match (field_type, length) {
+            (Some(t), Some(length)) if errors.is_empty() => (t, length),
+            _ => return Err(SimpleError::new(errors.join("; "))),
+        };
+
+        let alignment = self.alignment.unwrap_or(Alignment::None);
+
+        match (self.stride, self.planes) {
+            (Some(stride), _) => H2Array::new_strided_aligned(alignment, length, stride, field_type),
+            (_, Some(planes)) => H2Array::new_interleaved_aligned(alignment, length, planes, field_type),
+            (None, None)      => H2Array::new_aligned(alignment, length, field_type),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Offset;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+
+    use generic_number::{Context, Endian, IntegerReader, CharacterReader, CharacterFormatter, DefaultFormatter};
+    use crate::simple::numeric::H2Integer;
+    use crate::simple::string::LPString;
+
+    fn u8() -> H2Type {
+        H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())
+    }
+
+    #[test]
+    fn test_struct_builder() -> SimpleResult<()> {
+        let data = b"\x01\x02\x03\x04\x05\x03abc".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2StructBuilder::new()
+            .aligned(Alignment::Loose(2))
+            .field("a", u8())
+            .field("b", H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer()))
+            .field("c", H2ArrayBuilder::new(u8()).length(2))
+            .field("name", LPString::new(IntegerReader::U8, CharacterReader::ASCII, CharacterFormatter::pretty_str_character()))
+            .build()?;
+
+        assert_eq!("struct { u8 a; u16be b; u8[2] c; lpstring<u8, ascii> name; } aligned(2)", t.describe());
+        assert_eq!("{ a: 1, b: 515, c: [ 4, 5 ], name: \"abc\" }", t.to_display(offset)?);
+
+        // Nested builders work
+        let t = H2StructBuilder::new()
+            .field("inner", H2StructBuilder::new().field("x", u8()))
+            .build()?;
+        assert_eq!("struct { struct { u8 x; } inner; }", t.describe());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_struct_builder_errors() -> SimpleResult<()> {
+        // No fields at all
+        assert!(H2StructBuilder::new().build().is_err());
+
+        // Every bad field is reported, by name
+        let error = H2StructBuilder::new()
+            .field("", u8())
+            .field("a", u8())
+            .field("a", u8())
+            .field("b", H2Array::new(0, u8()))
+            .field("c", u8())
+            .build()
+            .unwrap_err();
+        assert_eq!("Field '': Field names can't be empty; Field 'a': A field with that name already exists; Field 'b': Arrays must be at least one element long", error.to_string());
+
+        // Errors in nested structs include the whole path
+        let error = H2StructBuilder::new()
+            .field("outer", H2StructBuilder::new().field("inner", H2ArrayBuilder::new(u8())))
+            .build()
+            .unwrap_err();
+        assert_eq!("Field 'outer': Field 'inner': Array length must be set", error.to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_array_builder() -> SimpleResult<()> {
+        assert_eq!("u8[4]", H2ArrayBuilder::new(u8()).length(4).build()?.describe());
+        assert_eq!(8, H2ArrayBuilder::new(u8()).length(4).aligned(Alignment::Loose(8)).build()?.aligned_size(Offset::Static(0))?);
+        assert_eq!(7, H2ArrayBuilder::new(u8()).length(4).stride(2).build()?.actual_size(Offset::Static(0))?);
+        assert_eq!(2, H2ArrayBuilder::new(u8()).length(4).planes(2).build()?.children(Offset::Static(0))?.len());
+
+        // Errors
+        assert_eq!("Array length must be set", H2ArrayBuilder::new(u8()).build().unwrap_err().to_string());
+        assert_eq!(
+            "Array element: Arrays must be at least one element long; Array length must be set; Arrays can't have both a stride and planes",
+            H2ArrayBuilder::new(H2Array::new(0, u8())).stride(2).planes(2).build().unwrap_err().to_string()
+        );
+        assert!(H2ArrayBuilder::new(u8()).length(0).build().is_err());
+        assert!(H2ArrayBuilder::new(u8()).length(4).stride(0).build().is_err());
+
+        Ok(())
+    }
+}
@@ -1,8 +1,12 @@
+use std::collections::BTreeSet;
+use std::ops::Range;
+
 use serde::{Serialize, Deserialize};
 
 use simple_error::{bail, SimpleResult};
 
-use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset, H2DataReference};
+use crate::composite::H2Variables;
 
 /// Defines an array of values.
 ///
@@ -13,10 +17,27 @@ use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
 /// Arrays can be nested, can contain
 /// [`crate::composite::H2Struct`]s/[`crate::composite::H2Array`]s,
 /// and can be as complex or simple as you need.
+///
+/// Two other layouts are supported, both of which need a static element type:
+///
+/// * A strided array ([`H2Array::new_strided`]) has a fixed distance between
+///   the start of each element, which can be larger than the element. That
+///   covers padding between elements, or one channel of interleaved data.
+/// * An interleaved array ([`H2Array::new_interleaved`]) splits interleaved
+///   data - like stereo audio samples, or RGB pixels - into one strided
+///   array per channel (or "plane").
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct H2Array {
     field_type: Box<H2Type>,
     length: u64,
+
+    /// The distance from the start of one element to the start of the next
+    #[serde(default)]
+    stride: Option<u64>,
+
+    /// The number of interleaved planes, each of which is a child array
+    #[serde(default)]
+    planes: Option<u64>,
 }
 
 impl H2Array {
@@ -28,12 +49,82 @@ impl H2Array {
         Ok(H2Type::new(alignment, H2Types::H2Array(Self {
             field_type: Box::new(field_type),
             length: length,
+            stride: None,
+            planes: None,
         })))
     }
 
     pub fn new(length: u64, field_type: H2Type) -> SimpleResult<H2Type> {
         Self::new_aligned(Alignment::None, length, field_type)
     }
+
+    /// Get the (aligned) size of a static element type.
+    fn element_size(field_type: &H2Type) -> SimpleResult<u64> {
+        if !field_type.is_static() {
+            bail!("Strided and interleaved arrays need an element type with a known size");
+        }
+
+        field_type.aligned_size(Offset::Static(0))
+    }
+
+    /// An array where each element starts `stride` bytes after the last.
+    ///
+    /// The stride must be at least as big as the element, and the array ends
+    /// at the end of the last element (not at the end of its stride).
+    pub fn new_strided_aligned(alignment: Alignment, length: u64, stride: u64, field_type: H2Type) -> SimpleResult<H2Type> {
+        if length == 0 {
+            bail!("Arrays must be at least one element long");
+        }
+
+        let element_size = Self::element_size(&field_type)?;
+        if stride < element_size {
+            bail!("Array stride ({}) is smaller than the element ({})", stride, element_size);
+        }
+
+        Ok(H2Type::new(alignment, H2Types::H2Array(Self {
+            field_type: Box::new(field_type),
+            length: length,
+            stride: Some(stride),
+            planes: None,
+        })))
+    }
+
+    pub fn new_strided(length: u64, stride: u64, field_type: H2Type) -> SimpleResult<H2Type> {
+        Self::new_strided_aligned(Alignment::None, length, stride, field_type)
+    }
+
+    /// An array of `length` interleaved groups, each with one element from
+    /// each of `planes` planes.
+    ///
+    /// The children are the planes - each one is a strided array of `length`
+    /// elements. For example, 4 stereo samples (`LRLRLRLR`) with 2 planes
+    /// become `[ [ L, L, L, L ], [ R, R, R, R ] ]`.
+    pub fn new_interleaved_aligned(alignment: Alignment, length: u64, planes: u64, field_type: H2Type) -> SimpleResult<H2Type> {
+        if length == 0 || planes == 0 {
+            bail!("Arrays must be at least one element long");
+        }
+
+        // Make sure the element type works
+        Self::element_size(&field_type)?;
+
+        Ok(H2Type::new(alignment, H2Types::H2Array(Self {
+            field_type: Box::new(field_type),
+            length: length,
+            stride: None,
+            planes: Some(planes),
+        })))
+    }
+
+    pub fn new_interleaved(length: u64, planes: u64, field_type: H2Type) -> SimpleResult<H2Type> {
+        Self::new_interleaved_aligned(Alignment::None, length, planes, field_type)
+    }
+
+    /// Get the type for a single plane of an interleaved array.
+    fn plane(&self, planes: u64) -> SimpleResult<H2Type> {
+        let element_size = Self::element_size(&self.field_type)?;
+
+        Self::new_strided(self.length, element_size * planes, self.field_type.as_ref().clone())
+    }
 }
 
 impl H2TypeTrait for H2Array {
@@ -42,13 +133,77 @@ impl H2TypeTrait for H2Array {
         self.field_type.is_static()
     }
 
+    fn describe(&self) -> String {
+        match (self.stride, self.planes) {
+            (Some(stride), _) => format!("{}[{}; stride {}]", self.field_type.describe(), self.length, stride),
+            (_, Some(planes)) => format!("{}[{}; {} planes]", self.field_type.describe(), self.length, planes),
+            _                 => format!("{}[{}]", self.field_type.describe(), self.length),
+        }
+    }
+
+    fn bind(&self, variables: &H2Variables) -> SimpleResult<Option<H2Types>> {
+        Ok(Some(H2Types::H2Array(Self {
+            field_type: Box::new(self.field_type.bind(variables)?),
+            length: self.length,
+            stride: self.stride,
+            planes: self.planes,
+        })))
+    }
+
+    fn data_references(&self, references: &mut BTreeSet<H2DataReference>) {
+        self.field_type.add_data_references(references);
+    }
+
+    fn freeze(&self) -> Option<H2Types> {
+        Some(H2Types::H2Array(Self {
+            field_type: Box::new(self.field_type.freeze()),
+            length: self.length,
+            stride: self.stride,
+            planes: self.planes,
+        }))
+    }
+
     fn children(&self, _offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        if let Some(planes) = self.planes {
+            let plane = self.plane(planes)?;
+
+            return Ok((0..planes).into_iter().map(|_index| {
+                (None, plane.clone())
+            }).collect());
+        }
+
         // Just clone the child type over and over
         Ok((0..self.length).into_iter().map(|_index| {
             (None, self.field_type.as_ref().clone())
         }).collect())
     }
 
+    fn children_with_range(&self, offset: Offset) -> SimpleResult<Vec<(Range<u64>, Option<String>, H2Type)>> {
+        // Strided elements and interleaved planes both start at fixed
+        // distances from each other; everything else is back-to-back
+        let spacing = match (self.stride, self.planes) {
+            (Some(stride), _) => Some(stride),
+            (_, Some(_))      => Some(Self::element_size(&self.field_type)?),
+            _                 => None,
+        };
+
+        let mut child_offset = offset;
+
+        self.children(offset)?.into_iter().enumerate().map(|(index, (name, child))| {
+            if let Some(spacing) = spacing {
+                child_offset = offset.at(offset.position() + index as u64 * spacing);
+            }
+
+            let range = child.aligned_range(child_offset)?;
+
+            if spacing.is_none() {
+                child_offset = offset.at(range.end);
+            }
+
+            Ok((range, name, child))
+        }).collect::<SimpleResult<Vec<_>>>()
+    }
+
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
         // Because the collect() expects a result, this will end and bubble
         // up errors automatically!
@@ -324,4 +479,63 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_strided_array() -> SimpleResult<()> {
+        let data = b"AxxBxxCxxDxx".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let a = H2Array::new_strided(4, 3, H2Character::new_ascii())?;
+        assert_eq!(true, a.is_static());
+        assert_eq!("char<ascii>[4; stride 3]", a.describe());
+
+        // The array ends at the end of the last element
+        assert_eq!(10, a.actual_size(offset)?);
+        assert_eq!("[ 'A', 'B', 'C', 'D' ]", a.to_display(offset)?);
+
+        let r = a.resolve(offset, None)?;
+        assert_eq!(0..10, r.actual_range);
+        assert_eq!(0..1,  r.children[0].actual_range);
+        assert_eq!(3..4,  r.children[1].actual_range);
+        assert_eq!(9..10, r.children[3].actual_range);
+
+        // Part of interleaved data, starting partway in
+        let data = b"xAxBxC".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data).at(1));
+        let a = H2Array::new_strided(3, 2, H2Character::new_ascii())?;
+        assert_eq!(1..6, a.actual_range(offset)?);
+        assert_eq!("[ 'A', 'B', 'C' ]", a.to_display(offset)?);
+
+        // Bad strides
+        assert!(H2Array::new_strided(4, 0, H2Character::new_ascii()).is_err());
+        assert!(H2Array::new_strided(0, 3, H2Character::new_ascii()).is_err());
+        assert!(H2Array::new_strided(4, 3, H2Character::new_utf8()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_interleaved_array() -> SimpleResult<()> {
+        let data = b"RGBRGBRGBRGB".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let a = H2Array::new_interleaved(4, 3, H2Character::new_ascii())?;
+        assert_eq!("char<ascii>[4; 3 planes]", a.describe());
+        assert_eq!(12, a.actual_size(offset)?);
+        assert_eq!("[ [ 'R', 'R', 'R', 'R' ], [ 'G', 'G', 'G', 'G' ], [ 'B', 'B', 'B', 'B' ] ]", a.to_display(offset)?);
+
+        // Each plane is a child, and they overlap
+        let r = a.resolve(offset, None)?;
+        assert_eq!(0..12, r.actual_range);
+        assert_eq!(3, r.children.len());
+        assert_eq!(0..10, r.children[0].actual_range);
+        assert_eq!(1..11, r.children[1].actual_range);
+        assert_eq!(2..12, r.children[2].actual_range);
+        assert_eq!(4..5,  r.children[1].children[1].actual_range);
+
+        assert!(H2Array::new_interleaved(4, 0, H2Character::new_ascii()).is_err());
+        assert!(H2Array::new_interleaved(4, 2, H2Character::new_utf8()).is_err());
+
+        Ok(())
+    }
 }
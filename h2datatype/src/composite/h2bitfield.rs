@@ -0,0 +1,188 @@
+use std::ops::Range;
+
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use generic_number::{BitOrder, IntegerReader, IntegerRenderer};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+use crate::simple::H2Bits;
+
+/// Defines packed bit fields - several small values that share one integer.
+///
+/// The integer is read with `reader`, and each field takes the next `width`
+/// bits, starting from bit 0 (numbered with `bit_order`). So a `u8` with
+/// fields of 3 and 5 bits has the first field in bits `0..3` and the second
+/// in `3..8`. Any bits left over at the end are ignored.
+///
+/// Each field is an [`H2Bits`] child. They all cover the whole integer, since
+/// bytes are the smallest thing that can be addressed, but each one's
+/// [`crate::ResolvedType::bit_range`] says which bits it uses.
+///
+/// A bitfield can be used anywhere a type can - usually as a field of an
+/// [`crate::composite::H2Struct`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct H2Bitfield {
+    reader: IntegerReader,
+    bit_order: BitOrder,
+
+    /// Each field's name, the bits it covers, and its type
+    fields: Vec<(String, Range<u64>, H2Type)>,
+}
+
+impl H2Bitfield {
+    /// Create a bitfield from each field's name, width (in bits), and
+    /// renderer.
+    pub fn new_aligned(alignment: Alignment, reader: IntegerReader, bit_order: BitOrder, fields: Vec<(String, u64, IntegerRenderer)>) -> SimpleResult<H2Type> {
+        if fields.len() == 0 {
+            bail!("Bitfields must contain at least one field");
+        }
+
+        let mut position = 0;
+        let fields = fields.into_iter().map(|(name, width, renderer)| {
+            let bits = position..(position + width);
+            position = bits.end;
+
+            match H2Bits::new(reader, bits.clone(), bit_order, renderer) {
+                Ok(t)  => Ok((name, bits, t)),
+                Err(e) => bail!("Bitfield field {}: {}", name, e),
+            }
+        }).collect::<SimpleResult<Vec<_>>>()?;
+
+        Ok(H2Type::new(alignment, H2Types::H2Bitfield(Self {
+            reader: reader,
+            bit_order: bit_order,
+            fields: fields,
+        })))
+    }
+
+    pub fn new(reader: IntegerReader, bit_order: BitOrder, fields: Vec<(String, u64, IntegerRenderer)>) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, reader, bit_order, fields)
+    }
+}
+
+impl H2TypeTrait for H2Bitfield {
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn describe(&self) -> String {
+        let fields: Vec<String> = self.fields.iter().map(|(name, bits, _)| {
+            format!("{} {};", bits.end - bits.start, name)
+        }).collect();
+
+        // LSB0 is the usual, so it's left out
+        match self.bit_order {
+            BitOrder::Lsb0 => format!("bitfield<{}> {{ {} }}", self.reader, fields.join(" ")),
+            bit_order      => format!("bitfield<{}, {}> {{ {} }}", self.reader, bit_order, fields.join(" ")),
+        }
+    }
+
+    fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
+        Ok(self.reader.size() as u64)
+    }
+
+    fn children(&self, _offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        Ok(self.fields.iter().map(|(name, _, field_type)| {
+            (Some(name.clone()), field_type.clone())
+        }).collect())
+    }
+
+    // Every field is in the same integer
+    fn children_with_range(&self, offset: Offset) -> SimpleResult<Vec<(Range<u64>, Option<String>, H2Type)>> {
+        self.fields.iter().map(|(name, _, field_type)| {
+            Ok((field_type.aligned_range(offset)?, Some(name.clone()), field_type.clone()))
+        }).collect()
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        let strings: Vec<String> = self.fields.iter().map(|(name, _, field_type)| {
+            Ok(format!("{}: {}", name, field_type.to_display(offset)?))
+        }).collect::<SimpleResult<Vec<String>>>()?;
+
+        Ok(format!("{{ {} }}", strings.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+    use generic_number::{Context, Endian, DefaultFormatter, HexFormatter};
+
+    use crate::composite::H2Struct;
+    use crate::simple::numeric::H2Integer;
+
+    #[test]
+    fn test_bitfield() -> SimpleResult<()> {
+        // 1011 0110
+        let data = b"\xb6".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Bitfield::new(IntegerReader::U8, BitOrder::Lsb0, vec![
+            ("flags".to_string(), 3, HexFormatter::pretty_integer()),
+            ("kind".to_string(),  5, DefaultFormatter::new_integer()),
+        ])?;
+        assert_eq!("bitfield<u8> { 3 flags; 5 kind; }", t.describe());
+        assert_eq!(1, t.actual_size(offset)?);
+        assert_eq!("{ flags: 0x06, kind: 22 }", t.to_display(offset)?);
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(0..1, r.actual_range);
+        assert_eq!(None, r.bit_range);
+        assert_eq!(
+            vec![(0..1, Some(0..3)), (0..1, Some(3..8))],
+            r.children.iter().map(|c| (c.actual_range.clone(), c.bit_range.clone())).collect::<Vec<_>>()
+        );
+
+        // The same bits, counted from the top
+        let t = H2Bitfield::new(IntegerReader::U8, BitOrder::Msb0, vec![
+            ("flags".to_string(), 3, DefaultFormatter::new_integer()),
+            ("kind".to_string(),  5, DefaultFormatter::new_integer()),
+        ])?;
+        assert_eq!("bitfield<u8, msb0> { 3 flags; 5 kind; }", t.describe());
+        assert_eq!("{ flags: 5, kind: 22 }", t.to_display(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitfield_in_struct() -> SimpleResult<()> {
+        let data = b"\x01\x34\x12\x02".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Struct::new(vec![
+            ("before".to_string(), H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+            ("bits".to_string(), H2Bitfield::new(IntegerReader::U16(Endian::Little), BitOrder::Lsb0, vec![
+                ("low".to_string(),  4, HexFormatter::pretty_integer()),
+                ("mid".to_string(),  8, HexFormatter::pretty_integer()),
+                ("high".to_string(), 4, HexFormatter::pretty_integer()),
+            ])?),
+            ("after".to_string(), H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+        ])?;
+
+        assert_eq!("{ before: 1, bits: { low: 0x04, mid: 0x23, high: 0x01 }, after: 2 }", t.to_display(offset)?);
+        assert_eq!(4, t.actual_size(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_bitfield() -> SimpleResult<()> {
+        // Too many bits
+        assert!(H2Bitfield::new(IntegerReader::U8, BitOrder::Lsb0, vec![
+            ("a".to_string(), 4, DefaultFormatter::new_integer()),
+            ("b".to_string(), 5, DefaultFormatter::new_integer()),
+        ]).is_err());
+
+        // Empty fields
+        assert!(H2Bitfield::new(IntegerReader::U8, BitOrder::Lsb0, vec![
+            ("a".to_string(), 0, DefaultFormatter::new_integer()),
+        ]).is_err());
+
+        assert!(H2Bitfield::new(IntegerReader::U8, BitOrder::Lsb0, vec![]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use generic_number::{Context, Endian, Float, Integer};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+use super::self_describing::{self, Decoder, Header};
+
+/// Defines a CBOR (RFC 8949) value, decoded without a schema.
+///
+/// Like [`super::H2MessagePack`], maps and arrays become children (with each
+/// map value named after its key), and everything else is displayed directly.
+/// Tagged values are displayed as `tag(value)`, like CBOR's diagnostic
+/// notation.
+///
+/// Indefinite-length strings, arrays, and maps aren't supported, and are
+/// errors.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct H2Cbor {
+}
+
+impl H2Cbor {
+    pub fn new_aligned(alignment: Alignment) -> H2Type {
+        H2Type::new(alignment, H2Types::H2Cbor(Self {
+        }))
+    }
+
+    pub fn new() -> H2Type {
+        Self::new_aligned(Alignment::None)
+    }
+}
+
+impl Decoder for H2Cbor {
+    fn header(&self, context: Context) -> SimpleResult<(u64, Header)> {
+        let start = context.position();
+        let byte = context.read_u8()?;
+        let next = context.at(start + 1);
+
+        let major = byte >> 5;
+        let info = byte & 0x1f;
+
+        // Simple values and floats use the additional info differently
+        if major == 7 {
+            return Ok(match info {
+                0..=19 => (1, Header::Simple(info)),
+                20     => (1, Header::Boolean(false)),
+                21     => (1, Header::Boolean(true)),
+                22     => (1, Header::Empty("null")),
+                23     => (1, Header::Empty("undefined")),
+                24     => (2, Header::Simple(next.read_u8()?)),
+                25     => (3, Header::Float(next.read_f16(Endian::Big)? as f64)),
+                26     => (5, Header::Float(next.read_f32(Endian::Big)? as f64)),
+                27     => (9, Header::Float(next.read_f64(Endian::Big)?)),
+                31     => bail!("Unexpected CBOR break at offset {}", start),
+                _      => bail!("Reserved CBOR value 0x{:02x} at offset {}", byte, start),
+            });
+        }
+
+        let (size, argument) = match info {
+            0..=23 => (1, info as u64),
+            24     => (2, next.read_u8()? as u64),
+            25     => (3, next.read_u16(Endian::Big)? as u64),
+            26     => (5, next.read_u32(Endian::Big)? as u64),
+            27     => (9, next.read_u64(Endian::Big)?),
+            31     => bail!("Indefinite-length CBOR values aren't supported (offset {})", start),
+            _      => bail!("Reserved CBOR length 0x{:02x} at offset {}", info, start),
+        };
+
+        Ok((size, match major {
+            0 => Header::Integer(Integer::from(argument)),
+            1 => match argument <= i64::MAX as u64 {
+                // -1 - argument, which only fits in 64 bits most of the time
+                true  => Header::Integer(Integer::from(-1 - argument as i64)),
+                false => Header::Integer(Integer::from(-1 - argument as i128)),
+            },
+            2 => Header::Binary(argument),
+            3 => Header::Text(argument),
+            4 => Header::Array(argument),
+            5 => Header::Map(argument),
+            _ => Header::Tag(argument),
+        }))
+    }
+
+    fn nested(&self) -> H2Type {
+        Self::new()
+    }
+}
+
+impl H2TypeTrait for H2Cbor {
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    fn describe(&self) -> String {
+        "cbor".to_string()
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        self_describing::actual_size(self, offset)
+    }
+
+    fn children(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        self_describing::children(self, offset)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        self_describing::to_display(self, offset)
+    }
+
+    fn can_be_string(&self) -> bool {
+        true
+    }
+
+    fn to_string(&self, offset: Offset) -> SimpleResult<String> {
+        self_describing::to_string(self, offset)
+    }
+
+    fn can_be_integer(&self) -> bool {
+        true
+    }
+
+    fn to_integer(&self, offset: Offset) -> SimpleResult<Integer> {
+        self_describing::to_integer(self, offset)
+    }
+
+    fn can_be_float(&self) -> bool {
+        true
+    }
+
+    fn to_float(&self, offset: Offset) -> SimpleResult<Float> {
+        self_describing::to_float(self, offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use generic_number::Context;
+
+    #[test]
+    fn test_scalars() -> SimpleResult<()> {
+        let t = H2Cbor::new();
+        assert_eq!("cbor", t.describe());
+
+        // Mostly from the examples in RFC 8949, appendix A
+        let tests: Vec<(&[u8], &str, u64)> = vec![
+            (b"\x17",                         "23",                    1),
+            (b"\x18\x64",                     "100",                   2),
+            (b"\x1b\x00\x00\x00\xe8\xd4\xa5\x10\x00", "1000000000000", 9),
+            (b"\x3b\xff\xff\xff\xff\xff\xff\xff\xff", "-18446744073709551616", 9),
+            (b"\x38\x63",                     "-100",                  2),
+            (b"\xf9\x3e\x00",                 "1.5",                   3),
+            (b"\xf9\xc4\x00",                 "-4",                    3),
+            (b"\xf9\x00\x01",                 "0.00000005960464477539063",  3),
+            (b"\xfa\x47\xc3\x50\x00",         "100000",                5),
+            (b"\xf4",                         "false",                 1),
+            (b"\xf6",                         "null",                  1),
+            (b"\xf7",                         "undefined",             1),
+            (b"\xf0",                         "simple(16)",            1),
+            (b"\x44\x01\x02\x03\x04",         "bin[4]",                5),
+            (b"\x62\xc3\xbc",                 "\"\u{fc}\"",            3),
+            (b"\xc1\x1a\x51\x4b\x67\xb0",     "1(1363896240)",         6),
+        ];
+
+        for (data, display, size) in tests {
+            let data = data.to_vec();
+            let offset = Offset::Dynamic(Context::new(&data));
+
+            assert_eq!(display, t.to_display(offset)?);
+            assert_eq!(size, t.actual_size(offset)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested() -> SimpleResult<()> {
+        // {"a": 1, "b": [2, 3]}
+        let data = b"\xa2\x61\x61\x01\x61\x62\x82\x02\x03".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Cbor::new();
+        assert_eq!(9, t.actual_size(offset)?);
+        assert_eq!("{ \"a\": 1, \"b\": [ 2, 3 ] }", t.to_display(offset)?);
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(5, r.children.len());
+        assert_eq!(Some("a".to_string()), r.children[2].field_name);
+        assert_eq!(Some("b".to_string()), r.children[4].field_name);
+        assert_eq!(6..9, r.children[4].actual_range);
+
+        // Tags have the tagged value as their child
+        let data = b"\xd8\x20\x63abc".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+        let r = t.resolve(offset, None)?;
+        assert_eq!("32(\"abc\")", r.display);
+        assert_eq!(2, r.children.len());
+        assert_eq!(Some("abc".to_string()), r.children[1].as_string);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_data() -> SimpleResult<()> {
+        let t = H2Cbor::new();
+
+        // Indefinite length, a break, reserved, and truncated
+        for data in vec![b"\x9f\x01\xff".to_vec(), b"\xff".to_vec(), b"\x1c".to_vec(), b"\x82\x01".to_vec()] {
+            let offset = Offset::Dynamic(Context::new(&data));
+            assert!(t.to_display(offset).is_err());
+        }
+
+        // Nested too deeply to measure (or display) without running out of
+        // stack
+        let mut data = vec![b'\x81'; 200000];
+        data.push(b'\xf6');
+        let offset = Offset::Dynamic(Context::new(&data));
+        assert!(t.actual_size(offset).is_err());
+        assert!(t.to_display(offset).is_err());
+
+        // But a reasonable amount of nesting is fine
+        let mut data = vec![b'\x81'; 50];
+        data.push(b'\xf6');
+        assert_eq!(51, t.actual_size(Offset::Dynamic(Context::new(&data)))?);
+
+        Ok(())
+    }
+}
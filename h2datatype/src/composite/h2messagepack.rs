@@ -0,0 +1,238 @@
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use generic_number::{Context, Endian, Float, Integer};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+use super::self_describing::{self, Decoder, Header};
+
+/// Defines a MessagePack value, decoded without a schema.
+///
+/// MessagePack is self-describing, so a value can be read without knowing
+/// what it's supposed to be: maps and arrays become children (with each map
+/// value named after its key), and everything else is displayed directly. The
+/// display looks a lot like JSON - `{ "a": [ 1, 2 ], "b": nil }`.
+///
+/// The size depends entirely on the data. To decode MessagePack embedded in
+/// another structure, use this as the field type.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct H2MessagePack {
+}
+
+impl H2MessagePack {
+    pub fn new_aligned(alignment: Alignment) -> H2Type {
+        H2Type::new(alignment, H2Types::H2MessagePack(Self {
+        }))
+    }
+
+    pub fn new() -> H2Type {
+        Self::new_aligned(Alignment::None)
+    }
+}
+
+impl Decoder for H2MessagePack {
+    fn header(&self, context: Context) -> SimpleResult<(u64, Header)> {
+        let start = context.position();
+        let byte = context.read_u8()?;
+
+        // Lengths, counts, and payloads come right after the first byte
+        let next = context.at(start + 1);
+
+        Ok(match byte {
+            0x00..=0x7f => (1, Header::Integer(Integer::from(byte))),
+            0x80..=0x8f => (1, Header::Map((byte & 0x0f) as u64)),
+            0x90..=0x9f => (1, Header::Array((byte & 0x0f) as u64)),
+            0xa0..=0xbf => (1, Header::Text((byte & 0x1f) as u64)),
+            0xc0        => (1, Header::Empty("nil")),
+            0xc1        => bail!("Invalid MessagePack type 0xc1 at offset {}", start),
+            0xc2        => (1, Header::Boolean(false)),
+            0xc3        => (1, Header::Boolean(true)),
+            0xc4        => (2, Header::Binary(next.read_u8()? as u64)),
+            0xc5        => (3, Header::Binary(next.read_u16(Endian::Big)? as u64)),
+            0xc6        => (5, Header::Binary(next.read_u32(Endian::Big)? as u64)),
+            0xc7        => (3, Header::Extension(context.at(start + 2).read_i8()?, next.read_u8()? as u64)),
+            0xc8        => (4, Header::Extension(context.at(start + 3).read_i8()?, next.read_u16(Endian::Big)? as u64)),
+            0xc9        => (6, Header::Extension(context.at(start + 5).read_i8()?, next.read_u32(Endian::Big)? as u64)),
+            0xca        => (5, Header::Float(next.read_f32(Endian::Big)? as f64)),
+            0xcb        => (9, Header::Float(next.read_f64(Endian::Big)?)),
+            0xcc        => (2, Header::Integer(Integer::from(next.read_u8()?))),
+            0xcd        => (3, Header::Integer(Integer::from(next.read_u16(Endian::Big)?))),
+            0xce        => (5, Header::Integer(Integer::from(next.read_u32(Endian::Big)?))),
+            0xcf        => (9, Header::Integer(Integer::from(next.read_u64(Endian::Big)?))),
+            0xd0        => (2, Header::Integer(Integer::from(next.read_i8()?))),
+            0xd1        => (3, Header::Integer(Integer::from(next.read_i16(Endian::Big)?))),
+            0xd2        => (5, Header::Integer(Integer::from(next.read_i32(Endian::Big)?))),
+            0xd3        => (9, Header::Integer(Integer::from(next.read_i64(Endian::Big)?))),
+            0xd4..=0xd8 => (2, Header::Extension(next.read_i8()?, 1 << (byte - 0xd4))),
+            0xd9        => (2, Header::Text(next.read_u8()? as u64)),
+            0xda        => (3, Header::Text(next.read_u16(Endian::Big)? as u64)),
+            0xdb        => (5, Header::Text(next.read_u32(Endian::Big)? as u64)),
+            0xdc        => (3, Header::Array(next.read_u16(Endian::Big)? as u64)),
+            0xdd        => (5, Header::Array(next.read_u32(Endian::Big)? as u64)),
+            0xde        => (3, Header::Map(next.read_u16(Endian::Big)? as u64)),
+            0xdf        => (5, Header::Map(next.read_u32(Endian::Big)? as u64)),
+            0xe0..=0xff => (1, Header::Integer(Integer::from(byte as i8))),
+        })
+    }
+
+    fn nested(&self) -> H2Type {
+        Self::new()
+    }
+}
+
+impl H2TypeTrait for H2MessagePack {
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    fn describe(&self) -> String {
+        "msgpack".to_string()
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        self_describing::actual_size(self, offset)
+    }
+
+    fn children(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        self_describing::children(self, offset)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        self_describing::to_display(self, offset)
+    }
+
+    fn can_be_string(&self) -> bool {
+        true
+    }
+
+    fn to_string(&self, offset: Offset) -> SimpleResult<String> {
+        self_describing::to_string(self, offset)
+    }
+
+    fn can_be_integer(&self) -> bool {
+        true
+    }
+
+    fn to_integer(&self, offset: Offset) -> SimpleResult<Integer> {
+        self_describing::to_integer(self, offset)
+    }
+
+    fn can_be_float(&self) -> bool {
+        true
+    }
+
+    fn to_float(&self, offset: Offset) -> SimpleResult<Float> {
+        self_describing::to_float(self, offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use generic_number::{Context, IntegerReader, DefaultFormatter};
+    use crate::composite::H2Struct;
+    use crate::simple::numeric::H2Integer;
+
+    #[test]
+    fn test_scalars() -> SimpleResult<()> {
+        let t = H2MessagePack::new();
+        assert_eq!("msgpack", t.describe());
+
+        let tests: Vec<(&[u8], &str, u64)> = vec![
+            (b"\x05",                         "5",           1),
+            (b"\xff",                         "-1",          1),
+            (b"\xcd\x01\x00",                 "256",         3),
+            (b"\xd2\xff\xff\xff\xfe",         "-2",          5),
+            (b"\xc0",                         "nil",         1),
+            (b"\xc3",                         "true",        1),
+            (b"\xcb\x3f\xf8\x00\x00\x00\x00\x00\x00", "1.5", 9),
+            (b"\xa2hi",                       "\"hi\"",      3),
+            (b"\xd9\x03abc",                  "\"abc\"",     5),
+            (b"\xc4\x02\x00\x01",             "bin[2]",      4),
+            (b"\xd5\x07\x00\x01",             "ext(7)[2]",   4),
+        ];
+
+        for (data, display, size) in tests {
+            let data = data.to_vec();
+            let offset = Offset::Dynamic(Context::new(&data));
+
+            assert_eq!(display, t.to_display(offset)?);
+            assert_eq!(size, t.actual_size(offset)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested() -> SimpleResult<()> {
+        // { "a": [ 1, 2 ], "b": { "c": nil } }
+        let data = b"\x82\xa1a\x92\x01\x02\xa1b\x81\xa1c\xc0".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2MessagePack::new();
+        assert_eq!(12, t.actual_size(offset)?);
+        assert_eq!("{ \"a\": [ 1, 2 ], \"b\": { \"c\": nil } }", t.to_display(offset)?);
+
+        // The header, then keys (unnamed) and values (named after the key)
+        let r = t.resolve(offset, None)?;
+        assert_eq!(5, r.children.len());
+        assert_eq!(0..1, r.children[0].actual_range);
+        assert_eq!(None, r.children[1].field_name);
+        assert_eq!(Some("a".to_string()), r.children[2].field_name);
+        assert_eq!(3..6, r.children[2].actual_range);
+        assert_eq!(3, r.children[2].children.len());
+        assert_eq!(Some(2), r.children[2].children[2].as_integer.map(|i| i.as_usize().unwrap()));
+        assert_eq!(Some("b".to_string()), r.children[4].field_name);
+        assert_eq!("{ \"c\": nil }", r.children[4].display);
+
+        // Scalars don't have children
+        assert_eq!(0, r.children[1].children.len());
+        assert_eq!(Some("a".to_string()), r.children[1].as_string);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_embedded() -> SimpleResult<()> {
+        let data = b"\x02\x92\x01\xa1x\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Struct::new(vec![
+            ("count".to_string(),   H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+            ("payload".to_string(), H2MessagePack::new()),
+            ("end".to_string(),     H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+        ])?;
+
+        assert_eq!("{ count: 2, payload: [ 1, \"x\" ], end: 255 }", t.to_display(offset)?);
+        assert_eq!(6, t.actual_size(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_data() -> SimpleResult<()> {
+        let t = H2MessagePack::new();
+
+        // Never used, truncated, and a count that can't possibly fit
+        for data in vec![b"\xc1".to_vec(), b"\x92\x01".to_vec(), b"\xa5hi".to_vec(), b"\xdd\xff\xff\xff\xff".to_vec()] {
+            let offset = Offset::Dynamic(Context::new(&data));
+            assert!(t.to_display(offset).is_err());
+        }
+
+        // Nested too deeply to measure (or display) without running out of
+        // stack
+        let mut data = vec![b'\x91'; 200000];
+        data.push(b'\xc0');
+        let offset = Offset::Dynamic(Context::new(&data));
+        assert!(t.actual_size(offset).is_err());
+        assert!(t.to_display(offset).is_err());
+
+        // But a reasonable amount of nesting is fine
+        let mut data = vec![b'\x91'; 50];
+        data.push(b'\xc0');
+        assert_eq!(51, t.actual_size(Offset::Dynamic(Context::new(&data)))?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::BTreeSet;
+
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset, H2DataReference};
+use crate::composite::H2Variables;
+use crate::simple::H2Blob;
+
+/// Defines a struct where only some of the fields are known.
+///
+/// Each field has an explicit offset from the start of the struct, instead of
+/// following the field before it. Anything between the fields (and between
+/// the last field and `size`, if there is one) becomes an unnamed
+/// [`H2Blob`] child, so the children still cover the whole struct.
+///
+/// This is handy while a structure is only partly understood - the known
+/// fields can be defined without making up names for everything in between.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct H2SparseStruct {
+    /// Each field's offset (from the start of the struct), name, and type
+    fields: Vec<(u64, String, H2Type)>,
+
+    /// The full size of the struct, if it's known
+    size: Option<u64>,
+}
+
+impl H2SparseStruct {
+    /// Create a sparse struct.
+    ///
+    /// The fields must be in order, and can't overlap. If `size` is set, the
+    /// struct is padded out to that size.
+    pub fn new_aligned(alignment: Alignment, size: Option<u64>, fields: Vec<(u64, String, H2Type)>) -> SimpleResult<H2Type> {
+        if fields.len() == 0 {
+            bail!("Structs must contain at least one field");
+        }
+
+        // Check what we can without data - fields with a static size
+        let mut end = 0;
+        for (offset, name, field_type) in &fields {
+            if *offset < end {
+                bail!("Field {} at offset {} overlaps the field before it", name, offset);
+            }
+
+            end = match field_type.is_static() {
+                true  => field_type.aligned_range(Offset::Static(*offset))?.end,
+                false => *offset,
+            };
+        }
+
+        if let Some(size) = size {
+            if size < end {
+                bail!("Struct size ({}) is smaller than its fields ({})", size, end);
+            }
+        }
+
+        Ok(H2Type::new(alignment, H2Types::H2SparseStruct(Self {
+            fields: fields,
+            size: size,
+        })))
+    }
+
+    pub fn new(size: Option<u64>, fields: Vec<(u64, String, H2Type)>) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, size, fields)
+    }
+}
+
+impl H2TypeTrait for H2SparseStruct {
+    // Is the size known ahead of time?
+    fn is_static(&self) -> bool {
+        // Even with a known size, the padding depends on where the fields end
+        self.fields.iter().find(|(_, _, t)| {
+            t.is_static() == false
+        }).is_none()
+    }
+
+    fn describe(&self) -> String {
+        let fields: Vec<String> = self.fields.iter().map(|(offset, name, field_type)| {
+            format!("0x{:x}: {} {};", offset, field_type.describe(), name)
+        }).collect();
+
+        match self.size {
+            Some(size) => format!("sparse struct[0x{:x}] {{ {} }}", size, fields.join(" ")),
+            None       => format!("sparse struct {{ {} }}", fields.join(" ")),
+        }
+    }
+
+    fn bind(&self, variables: &H2Variables) -> SimpleResult<Option<H2Types>> {
+        Ok(Some(H2Types::H2SparseStruct(Self {
+            fields: self.fields.iter().map(|(offset, name, field_type)| {
+                Ok((*offset, name.clone(), field_type.bind(variables)?))
+            }).collect::<SimpleResult<Vec<_>>>()?,
+            size: self.size,
+        })))
+    }
+
+    fn data_references(&self, references: &mut BTreeSet<H2DataReference>) {
+        for (_, _, field_type) in &self.fields {
+            field_type.add_data_references(references);
+        }
+    }
+
+    fn freeze(&self) -> Option<H2Types> {
+        Some(H2Types::H2SparseStruct(Self {
+            fields: self.fields.iter().map(|(offset, name, field_type)| {
+                (*offset, name.clone(), field_type.freeze())
+            }).collect(),
+            size: self.size,
+        }))
+    }
+
+    fn children(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        let start = offset.position();
+        let mut position = 0;
+        let mut children = vec![];
+
+        for (field_offset, name, field_type) in &self.fields {
+            // Dynamic fields can only be checked once we have the data
+            if *field_offset < position {
+                bail!("Field {} at offset {} overlaps the field before it", name, field_offset);
+            }
+
+            if *field_offset > position {
+                children.push((None, H2Blob::new(field_offset - position)?));
+            }
+            children.push((Some(name.clone()), field_type.clone()));
+
+            position = field_type.aligned_range(offset.at(start + field_offset))?.end - start;
+        }
+
+        if let Some(size) = self.size {
+            if size < position {
+                bail!("Struct size ({}) is smaller than its fields ({})", size, position);
+            }
+
+            if size > position {
+                children.push((None, H2Blob::new(size - position)?));
+            }
+        }
+
+        Ok(children)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        // Only show the fields we know about, not the padding
+        let strings: Vec<String> = self.children_with_range(offset)?.iter().filter_map(|(range, name, child)| {
+            name.as_ref().map(|name| {
+                Ok(format!("{}: {}", name, child.to_display(offset.at(range.start))?))
+            })
+        }).collect::<SimpleResult<Vec<String>>>()?;
+
+        Ok(format!("{{ {} }}", strings.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use generic_number::{Context, IntegerReader, Endian, HexFormatter, DefaultFormatter, CharacterReader, CharacterFormatter};
+    use crate::simple::numeric::H2Integer;
+    use crate::simple::string::LPString;
+
+    #[test]
+    fn test_sparse_struct() -> SimpleResult<()> {
+        //           -- --------pad-------- ----u16---- --pad--
+        let data = b"\x01\x00\x00\x00\x00\x00\x00\x12\x34\x00\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2SparseStruct::new(Some(11), vec![
+            (0, "a".to_string(), H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+            (7, "b".to_string(), H2Integer::new(IntegerReader::U16(Endian::Big), HexFormatter::pretty_integer())),
+        ])?;
+
+        assert_eq!(true, t.is_static());
+        assert_eq!(11, t.actual_size(offset)?);
+        assert_eq!("{ a: 1, b: 0x1234 }", t.to_display(offset)?);
+        assert_eq!("sparse struct[0xb] { 0x0: u8 a; 0x7: u16be b; }", t.describe());
+
+        // The gaps are unnamed children
+        let r = t.resolve(offset, None)?;
+        assert_eq!(0..11, r.actual_range);
+        assert_eq!(4, r.children.len());
+        assert_eq!(Some("a".to_string()), r.children[0].field_name);
+        assert_eq!(None,                  r.children[1].field_name);
+        assert_eq!(1..7,                  r.children[1].actual_range);
+        assert_eq!(Some("b".to_string()), r.children[2].field_name);
+        assert_eq!(7..9,                  r.children[2].actual_range);
+        assert_eq!(None,                  r.children[3].field_name);
+        assert_eq!(9..11,                 r.children[3].actual_range);
+
+        // Without a size, it ends at the last field
+        let t = H2SparseStruct::new(None, vec![
+            (2, "b".to_string(), H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+        ])?;
+        assert_eq!(0..3, t.actual_range(offset)?);
+        assert_eq!(2, t.resolve(offset, None)?.children.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sparse_struct_dynamic() -> SimpleResult<()> {
+        let data = b"\x00\x02hi\x00\x00\x00\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let s = LPString::new(IntegerReader::U8, CharacterReader::UTF8, CharacterFormatter::pretty_str_character())?;
+        let t = H2SparseStruct::new(Some(8), vec![
+            (1, "name".to_string(), s.clone()),
+        ])?;
+        assert_eq!("{ name: \"hi\" }", t.to_display(offset)?);
+        assert_eq!(8, t.actual_size(offset)?);
+
+        // A dynamic field that runs into the next one is only caught with
+        // data
+        let t = H2SparseStruct::new(None, vec![
+            (1, "name".to_string(), s),
+            (3, "next".to_string(), H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+        ])?;
+        assert!(t.to_display(offset).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sparse_struct_errors() -> SimpleResult<()> {
+        let u16 = H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer());
+
+        assert!(H2SparseStruct::new(None, vec![]).is_err());
+
+        // Out of order / overlapping
+        assert!(H2SparseStruct::new(None, vec![(4, "a".to_string(), u16.clone()), (0, "b".to_string(), u16.clone())]).is_err());
+        assert!(H2SparseStruct::new(None, vec![(0, "a".to_string(), u16.clone()), (1, "b".to_string(), u16.clone())]).is_err());
+
+        // Too small
+        assert!(H2SparseStruct::new(Some(3), vec![(2, "a".to_string(), u16.clone())]).is_err());
+        assert!(H2SparseStruct::new(Some(4), vec![(2, "a".to_string(), u16)]).is_ok());
+
+        Ok(())
+    }
+}
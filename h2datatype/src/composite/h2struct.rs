@@ -1,8 +1,12 @@
+use std::collections::BTreeSet;
+use std::ops::Range;
+
 use serde::{Serialize, Deserialize};
 
 use simple_error::{bail, SimpleResult};
 
-use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset, H2DataReference};
+use crate::composite::{H2Switch, H2Variables};
 
 /// Defines a struct.
 ///
@@ -11,6 +15,12 @@ use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct H2Struct {
     fields: Vec<(String, H2Type)>,
+
+    /// Where each field goes (relative to the start of the struct), if the
+    /// struct has been frozen (see [`H2Type::freeze`]) and every field has a
+    /// fixed size.
+    #[serde(skip)]
+    layout: Option<Vec<Range<u64>>>,
 }
 
 impl H2Struct {
@@ -20,13 +30,96 @@ impl H2Struct {
         }
 
         Ok(H2Type::new(alignment, H2Types::H2Struct(Self {
-            fields: fields
+            fields: fields,
+            layout: None,
         })))
     }
 
     pub fn new(fields: Vec<(String, H2Type)>) -> SimpleResult<H2Type> {
         Self::new_aligned(Alignment::None, fields)
     }
+
+    pub fn fields(&self) -> &Vec<(String, H2Type)> {
+        &self.fields
+    }
+
+    /// Has the struct been frozen with a fixed layout?
+    pub fn is_frozen(&self) -> bool {
+        self.layout.is_some()
+    }
+
+    /// Work out where each field goes, if they all have a fixed size.
+    ///
+    /// Strict alignment is checked against the actual position, so a struct
+    /// with a strictly aligned field is never given a layout.
+    fn layout(fields: &[(String, H2Type)]) -> Option<Vec<Range<u64>>> {
+        let mut position = 0;
+
+        fields.iter().map(|(_, field_type)| {
+            if !field_type.is_static() || matches!(field_type.alignment, Alignment::Strict(_)) {
+                return None;
+            }
+
+            // Some static types still can't be measured without data
+            let range = field_type.aligned_range(Offset::Static(position)).ok()?;
+            position = range.end;
+
+            Some(range)
+        }).collect()
+    }
+
+    /// Build a new struct from `base` (which must be a struct), with its
+    /// fields changed by `f`. The alignment is kept.
+    fn derive(base: &H2Type, new_fields: &[(String, H2Type)], f: impl FnOnce(&mut Vec<(String, H2Type)>, Vec<(String, H2Type)>) -> SimpleResult<()>) -> SimpleResult<H2Type> {
+        let mut fields = match base.field.as_ref() {
+            H2Types::H2Struct(s) => s.fields.clone(),
+            _                    => bail!("Can't extend {}: it's not a struct", base),
+        };
+
+        for (name, _) in new_fields {
+            if fields.iter().any(|(existing, _)| existing == name) {
+                bail!("Can't add field {:?}: the struct already has one", name);
+            }
+        }
+
+        f(&mut fields, new_fields.to_vec())?;
+
+        Self::new_aligned(base.alignment, fields)
+    }
+
+    /// A new struct with the fields of `base`, then `fields`.
+    ///
+    /// This (and [`H2Struct::prepend`] and [`H2Struct::insert_after`]) make
+    /// it easy to define a new version of a format as changes to the old one,
+    /// rather than a copy.
+    pub fn append(base: &H2Type, fields: Vec<(String, H2Type)>) -> SimpleResult<H2Type> {
+        Self::derive(base, &fields, |existing, fields| {
+            existing.extend(fields);
+            Ok(())
+        })
+    }
+
+    /// A new struct with `fields`, then the fields of `base`.
+    pub fn prepend(base: &H2Type, fields: Vec<(String, H2Type)>) -> SimpleResult<H2Type> {
+        Self::derive(base, &fields, |existing, fields| {
+            existing.splice(0..0, fields);
+            Ok(())
+        })
+    }
+
+    /// A new struct with the fields of `base`, with `fields` added after the
+    /// field named `after`.
+    pub fn insert_after(base: &H2Type, after: &str, fields: Vec<(String, H2Type)>) -> SimpleResult<H2Type> {
+        Self::derive(base, &fields, |existing, fields| {
+            let index = match existing.iter().position(|(name, _)| name == after) {
+                Some(index) => index + 1,
+                None        => bail!("Can't insert after {:?}: no such field", after),
+            };
+
+            existing.splice(index..index, fields);
+            Ok(())
+        })
+    }
 }
 
 impl H2TypeTrait for H2Struct {
@@ -39,12 +132,101 @@ impl H2TypeTrait for H2Struct {
         }).is_none()
     }
 
+    fn describe(&self) -> String {
+        let fields: Vec<String> = self.fields.iter().map(|(name, field_type)| {
+            format!("{} {};", field_type.describe(), name)
+        }).collect();
+
+        format!("struct {{ {} }}", fields.join(" "))
+    }
+
+    fn bind(&self, variables: &H2Variables) -> SimpleResult<Option<H2Types>> {
+        let fields = self.fields.iter().map(|(name, field_type)| {
+            Ok((name.clone(), field_type.bind(variables)?))
+        }).collect::<SimpleResult<Vec<_>>>()?;
+
+        // Binding can change the size of a field, so a frozen struct needs a
+        // new layout
+        let layout = match self.layout {
+            Some(_) => Self::layout(&fields),
+            None    => None,
+        };
+
+        Ok(Some(H2Types::H2Struct(Self {
+            fields: fields,
+            layout: layout,
+        })))
+    }
+
+    fn data_references(&self, references: &mut BTreeSet<H2DataReference>) {
+        for (_, field_type) in &self.fields {
+            field_type.add_data_references(references);
+        }
+    }
+
+    fn freeze(&self) -> Option<H2Types> {
+        let fields: Vec<(String, H2Type)> = self.fields.iter().map(|(name, field_type)| {
+            (name.clone(), field_type.freeze())
+        }).collect();
+
+        Some(H2Types::H2Struct(Self {
+            layout: Self::layout(&fields),
+            fields: fields,
+        }))
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        let children = match &self.layout {
+            Some(layout) => return Ok(layout.last().map(|range| range.end).unwrap_or(0)),
+            None         => self.children_with_range(offset)?,
+        };
+
+        match (children.first(), children.last()) {
+            (Some((first, _, _)), Some((last, _, _))) => Ok(last.end - first.start),
+            _                                         => bail!("Can't calculate size with no child types"),
+        }
+    }
+
     fn children(&self, _offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
         Ok(self.fields.iter().map(|(name, field_type)| {
             (Some(name.clone()), field_type.clone())
         }).collect())
     }
 
+    fn children_with_range(&self, offset: Offset) -> SimpleResult<Vec<(Range<u64>, Option<String>, H2Type)>> {
+        let start = offset.position();
+
+        match &self.layout {
+            // Frozen structs already know where everything goes
+            Some(layout) => Ok(self.fields.iter().zip(layout).map(|((name, field_type), range)| {
+                ((start + range.start)..(start + range.end), Some(name.clone()), field_type.clone())
+            }).collect()),
+
+            // Otherwise, each field starts where the last one ended
+            None => {
+                let mut position = start;
+
+                // Integer fields, for switches on fields that come later (see
+                // [`H2Switch::new_on_field`])
+                let mut values = H2Variables::new();
+
+                self.fields.iter().map(|(name, field_type)| {
+                    let field_type = H2Switch::select_field(field_type, &values)?;
+                    let range = field_type.aligned_range(offset.at(position))?;
+                    position = range.end;
+
+                    if let (Offset::Dynamic(_), true) = (offset, field_type.can_be_integer()) {
+                        if let Ok(value) = field_type.to_integer(offset.at(range.start)).and_then(|i| i.to_u64()) {
+                            values.insert(name.clone(), value);
+                        }
+                    }
+
+                    Ok((range, Some(name.clone()), field_type))
+                }).collect()
+            },
+        }
+    }
+
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
         // Because the collect() expects a result, this will end and bubble
         // up errors automatically!
@@ -63,7 +245,9 @@ mod tests {
     use generic_number::{Context, IntegerReader, Endian, HexFormatter, OctalFormatter, DefaultFormatter};
     use crate::simple::numeric::{H2Integer, H2Character};
     use crate::simple::network::IPv4;
-    use crate::composite::H2Array;
+    use crate::composite::{H2Array, H2Switch};
+    use crate::simple::string::LPString;
+    use generic_number::{CharacterReader, CharacterFormatter};
 
     #[test]
     fn test_struct() -> SimpleResult<()> {
@@ -246,4 +430,205 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_field() -> SimpleResult<()> {
+        let data = b"\x00\x01\x02\x03\x04\x05\x06".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+        let t = H2Struct::new(vec![
+            ("a".to_string(), u8.clone()),
+            ("inner".to_string(), H2Struct::new(vec![
+                ("b".to_string(), H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer())),
+                ("items".to_string(), H2Array::new(3, u8.clone())?),
+            ])?),
+        ])?;
+
+        let (field, field_offset) = t.field(offset, "inner.b")?;
+        assert_eq!(1, field_offset.position());
+        assert_eq!("258", field.to_display(field_offset)?);
+
+        // Array elements are found by index
+        let (field, field_offset) = t.field(offset, "inner.items.2")?;
+        assert_eq!(5, field_offset.position());
+        assert_eq!("5", field.to_display(field_offset)?);
+
+        // An empty path is the type itself
+        assert_eq!(0, t.field(offset, "")?.1.position());
+
+        assert!(t.field(offset, "inner.c").is_err());
+        assert!(t.field(offset, "inner.items.3").is_err());
+        assert!(t.field(offset, "a.b").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extend() -> SimpleResult<()> {
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+        let v1 = H2Struct::new_aligned(Alignment::Loose(4), vec![
+            ("magic".to_string(), u8.clone()),
+            ("health".to_string(), u8.clone()),
+        ])?;
+
+        // Version 2 adds fields all over the place
+        let v2 = H2Struct::append(&v1, vec![("mana".to_string(), u8.clone())])?;
+        let v2 = H2Struct::prepend(&v2, vec![("version".to_string(), u8.clone())])?;
+        let v2 = H2Struct::insert_after(&v2, "magic", vec![
+            ("flags".to_string(), u8.clone()),
+            ("team".to_string(), u8.clone()),
+        ])?;
+        assert_eq!("struct { u8 version; u8 magic; u8 flags; u8 team; u8 health; u8 mana; } aligned(4)", v2.describe());
+
+        // The original is untouched
+        assert_eq!("struct { u8 magic; u8 health; } aligned(4)", v1.describe());
+
+        // Inserting after the last field is the same as appending
+        let v3 = H2Struct::insert_after(&v1, "health", vec![("mana".to_string(), u8.clone())])?;
+        assert_eq!(H2Struct::append(&v1, vec![("mana".to_string(), u8.clone())])?.describe(), v3.describe());
+
+        // Errors
+        assert!(H2Struct::insert_after(&v1, "nope", vec![("mana".to_string(), u8.clone())]).is_err());
+        assert!(H2Struct::append(&v1, vec![("health".to_string(), u8.clone())]).is_err());
+        assert!(H2Struct::append(&u8, vec![("mana".to_string(), u8.clone())]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_alignment_warning() -> SimpleResult<()> {
+        let data = b"\x00\x01\x02\x03\x04\x05\x06\x07".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let u32 = H2Integer::new_aligned(Alignment::Warn(4), IntegerReader::U32(Endian::Big), DefaultFormatter::new_integer());
+        let t = H2Struct::new(vec![
+            ("a".to_string(), H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+            ("b".to_string(), u32.clone()),
+        ])?;
+
+        // The u32 is misaligned, but it still resolves (and is padded)
+        let resolved = t.resolve(offset, None)?;
+        assert_eq!(1..5, resolved.children[1].actual_range);
+        assert_eq!(Vec::<String>::new(), resolved.warnings);
+        assert_eq!(vec!["Starts at 0x1, which isn't aligned to 4 bytes"], resolved.all_warnings());
+
+        // Aligned is fine
+        assert!(u32.resolve(offset.at(4), None)?.all_warnings().is_empty());
+        assert_eq!("u32be aligned_warn(4)", u32.describe());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_describe() -> SimpleResult<()> {
+        let t = H2Struct::new(vec![
+            ("x".to_string(), H2Integer::new(IntegerReader::U32(Endian::Little), DefaultFormatter::new_integer())),
+            ("y".to_string(), H2Integer::new_aligned(Alignment::Loose(4), IntegerReader::U16(Endian::Big), HexFormatter::pretty_integer())),
+            ("ips".to_string(), H2Array::new(2, IPv4::new(Endian::Big))?),
+            ("inner".to_string(), H2Struct::new(vec![
+                ("c".to_string(), H2Character::new_ascii()),
+            ])?),
+        ])?;
+
+        assert_eq!("struct { u32le x; u16be aligned(4) y; ipv4<be>[2] ips; struct { char<ascii> c; } inner; }", t.describe());
+        assert_eq!(t.describe(), format!("{}", t));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_clone_shares_fields() -> SimpleResult<()> {
+        let data = b"\x01\x02".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let inner = H2Struct::new(vec![
+            ("a".to_string(), H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+        ])?;
+        let t = H2Array::new(2, inner.clone())?;
+
+        // Clones use the same definition, instead of copying it
+        let copy = t.clone();
+        assert!(std::sync::Arc::ptr_eq(&t.field, &copy.field));
+
+        // And they still work the same
+        assert_eq!("[ { a: 1 }, { a: 2 } ]", copy.to_display(offset)?);
+
+        Ok(())
+    }
+
+    fn is_frozen(t: &H2Type) -> bool {
+        match t.field.as_ref() {
+            H2Types::H2Struct(s) => s.is_frozen(),
+            _                    => false,
+        }
+    }
+
+    #[test]
+    fn test_freeze() -> SimpleResult<()> {
+        let data = b"\x01\x00\x00\x00\x02\x03PP\x04\x05\x00\x00\x00\x06\x07PP\x08".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Array::new(2, H2Struct::new(vec![
+            ("a".to_string(), H2Integer::new(IntegerReader::U32(Endian::Little), DefaultFormatter::new_integer())),
+            ("b".to_string(), H2Integer::new_aligned(Alignment::Loose(4), IntegerReader::U16(Endian::Big), HexFormatter::pretty_integer())),
+            ("c".to_string(), H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+        ])?)?;
+        let frozen = t.freeze();
+
+        // The struct inside the array was frozen
+        assert!(is_frozen(&frozen.children(offset)?[0].1));
+        assert!(!is_frozen(&t.children(offset)?[0].1));
+
+        // Everything's the same either way
+        assert_eq!(t.describe(), frozen.describe());
+        assert_eq!(18, frozen.actual_size(offset)?);
+        assert_eq!(t.to_display(offset)?, frozen.to_display(offset)?);
+        assert_eq!("[ { a: 1, b: 0x0203, c: 4 }, { a: 5, b: 0x0607, c: 8 } ]", frozen.to_display(offset)?);
+
+        let resolved = t.resolve(offset.at(0), None)?;
+        let resolved_frozen = frozen.resolve(offset.at(0), None)?;
+        for (a, b) in resolved.children[1].children.iter().zip(&resolved_frozen.children[1].children) {
+            assert_eq!(a.aligned_range, b.aligned_range);
+            assert_eq!(a.display, b.display);
+        }
+        assert_eq!(13..15, resolved_frozen.children[1].children[1].actual_range);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_freeze_dynamic() -> SimpleResult<()> {
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+
+        // Strings depend on the data, and strict alignment depends on where
+        // the struct is, so neither can be laid out ahead of time
+        let t = H2Struct::new(vec![
+            ("a".to_string(), u8.clone()),
+            ("b".to_string(), LPString::new(IntegerReader::U8, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?),
+        ])?;
+        assert!(!is_frozen(&t.freeze()));
+
+        let t = H2Struct::new(vec![
+            ("a".to_string(), H2Integer::new_aligned(Alignment::Strict(2), IntegerReader::U8, DefaultFormatter::new_integer())),
+        ])?;
+        assert!(!is_frozen(&t.freeze()));
+
+        // A switch with a default is laid out as its default, but binding it
+        // can pick a different size
+        let t = H2Struct::new(vec![
+            ("a".to_string(), H2Switch::new("v", vec![
+                (1..2, H2Integer::new(IntegerReader::U32(Endian::Big), DefaultFormatter::new_integer())),
+            ], Some(u8.clone()))?),
+            ("b".to_string(), u8.clone()),
+        ])?.freeze();
+        assert!(is_frozen(&t));
+        assert_eq!(2, t.actual_size(Offset::Static(0))?);
+
+        let bound = t.bind(&vec![("v".to_string(), 1)].into_iter().collect())?;
+        assert!(is_frozen(&bound));
+        assert_eq!(5, bound.actual_size(Offset::Static(0))?);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,496 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use generic_number::{Integer, Float, Character, IntegerReader, IntegerRenderer};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset, H2DataReference};
+use crate::simple::numeric::H2Integer;
+
+/// Named values that an [`H2Switch`] can choose a type with - things like
+/// `format_version`, usually found by an analyzer.
+pub type H2Variables = BTreeMap<String, u64>;
+
+/// Where an [`H2Switch`] gets the value that picks its case.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum SwitchSource {
+    /// A variable that's set ahead of time - see [`H2Type::bind`]
+    Variable,
+
+    /// An earlier field in the same [`crate::composite::H2Struct`]
+    Field,
+
+    /// A tag that's read from the data, right before the case - like the
+    /// type in a TLV record
+    Tag(IntegerReader, IntegerRenderer),
+}
+
+impl Default for SwitchSource {
+    fn default() -> Self {
+        Self::Variable
+    }
+}
+
+/// Defines a type that depends on a value.
+///
+/// Each case is a range of values (the start is inclusive and the end is
+/// exclusive, the same as a Rust range) and the type to use when the value
+/// is in that range. If no case matches, the default type is used (if there
+/// is one).
+///
+/// The value comes from one of three places (see [`SwitchSource`]):
+///
+/// * A variable ([`H2Switch::new`]), like the version of a format. Variables
+///   aren't part of the data, so the switch has to be *bound* (see
+///   [`H2Type::bind`]) to pick a case before it's resolved. Until it's bound,
+///   it acts like its default type, if it has one, and fails otherwise.
+///
+/// * An earlier field of the struct it's in ([`H2Switch::new_on_field`]),
+///   which must be an integer. The struct picks the case as it's resolved.
+///   Outside of a struct, it acts like an unbound variable switch.
+///
+/// * A tag that's read from the data ([`H2Switch::new_tagged`]). The switch
+///   is the tag, then whichever case it picks, as two children - the tag
+///   (with the switch's `name`) and the `body`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct H2Switch {
+    /// The name of the variable, field, or tag
+    variable: String,
+
+    #[serde(default)]
+    source: SwitchSource,
+
+    cases: Vec<(Range<u64>, H2Type)>,
+    default: Option<Box<H2Type>>,
+}
+
+impl H2Switch {
+    fn new_from(alignment: Alignment, source: SwitchSource, variable: &str, cases: Vec<(Range<u64>, H2Type)>, default: Option<H2Type>) -> SimpleResult<H2Type> {
+        if cases.len() == 0 && default.is_none() {
+            bail!("Switches must have at least one case or a default");
+        }
+
+        for (i, (range, _)) in cases.iter().enumerate() {
+            if range.start >= range.end {
+                bail!("Switch case {}..{} on {} is empty", range.start, range.end, variable);
+            }
+
+            if let Some((other, _)) = cases[..i].iter().find(|(other, _)| other.start < range.end && range.start < other.end) {
+                bail!("Switch cases {}..{} and {}..{} on {} overlap", other.start, other.end, range.start, range.end, variable);
+            }
+        }
+
+        if let SwitchSource::Tag(reader, _) = source {
+            if !reader.can_be_usize() {
+                bail!("Switch tags must be unsigned integers");
+            }
+        }
+
+        Ok(H2Type::new(alignment, H2Types::H2Switch(Self {
+            variable: variable.to_string(),
+            source: source,
+            cases: cases,
+            default: default.map(Box::new),
+        })))
+    }
+
+    pub fn new_aligned(alignment: Alignment, variable: &str, cases: Vec<(Range<u64>, H2Type)>, default: Option<H2Type>) -> SimpleResult<H2Type> {
+        Self::new_from(alignment, SwitchSource::Variable, variable, cases, default)
+    }
+
+    pub fn new(variable: &str, cases: Vec<(Range<u64>, H2Type)>, default: Option<H2Type>) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, variable, cases, default)
+    }
+
+    /// Create a switch on the value of an earlier field, when it's part of
+    /// an [`crate::composite::H2Struct`].
+    pub fn new_on_field_aligned(alignment: Alignment, field: &str, cases: Vec<(Range<u64>, H2Type)>, default: Option<H2Type>) -> SimpleResult<H2Type> {
+        Self::new_from(alignment, SwitchSource::Field, field, cases, default)
+    }
+
+    pub fn new_on_field(field: &str, cases: Vec<(Range<u64>, H2Type)>, default: Option<H2Type>) -> SimpleResult<H2Type> {
+        Self::new_on_field_aligned(Alignment::None, field, cases, default)
+    }
+
+    /// Create a switch that reads a tag with `reader`, then picks the type
+    /// that comes after it.
+    pub fn new_tagged_aligned(alignment: Alignment, reader: IntegerReader, renderer: IntegerRenderer, name: &str, cases: Vec<(Range<u64>, H2Type)>, default: Option<H2Type>) -> SimpleResult<H2Type> {
+        Self::new_from(alignment, SwitchSource::Tag(reader, renderer), name, cases, default)
+    }
+
+    pub fn new_tagged(reader: IntegerReader, renderer: IntegerRenderer, name: &str, cases: Vec<(Range<u64>, H2Type)>, default: Option<H2Type>) -> SimpleResult<H2Type> {
+        Self::new_tagged_aligned(Alignment::None, reader, renderer, name, cases, default)
+    }
+
+    /// The name of the variable (or field, or tag) that picks the case.
+    pub fn variable(&self) -> &str {
+        &self.variable
+    }
+
+    /// Where the value that picks the case comes from.
+    pub fn source(&self) -> SwitchSource {
+        self.source
+    }
+
+    /// Pick the type to use, based on the value of the variable (if it's
+    /// set).
+    pub fn select(&self, variables: &H2Variables) -> SimpleResult<&H2Type> {
+        self.select_value(variables.get(&self.variable).copied())
+    }
+
+    /// Pick the type to use for a value.
+    fn select_value(&self, value: Option<u64>) -> SimpleResult<&H2Type> {
+        let case = value.and_then(|value| {
+            self.cases.iter().find(|(range, _)| range.contains(&value))
+        });
+
+        match (case, &self.default, value) {
+            (Some((_, t)), _, _)  => Ok(t),
+            (None, Some(t), _)    => Ok(t),
+            (None, None, Some(v)) => bail!("No switch case matches {} = {}", self.variable, v),
+            (None, None, None)    => bail!("Variable {} isn't set, and the switch has no default", self.variable),
+        }
+    }
+
+    /// If `field_type` is a switch on a field, pick its case from the values
+    /// of the fields before it (`fields`); anything else is returned as-is.
+    ///
+    /// Like binding, a switch with alignment overrides the alignment of the
+    /// case it picks.
+    pub(crate) fn select_field(field_type: &H2Type, fields: &H2Variables) -> SimpleResult<H2Type> {
+        let switch = match field_type.field.as_ref() {
+            H2Types::H2Switch(s) if matches!(s.source, SwitchSource::Field) => s,
+            _ => return Ok(field_type.clone()),
+        };
+
+        let mut selected = switch.select(fields)?.clone();
+        if !matches!(field_type.alignment, Alignment::None) {
+            selected.alignment = field_type.alignment;
+        }
+
+        Ok(selected)
+    }
+
+    /// The type to use before the switch is bound.
+    fn fallback(&self) -> SimpleResult<&H2Type> {
+        if let SwitchSource::Tag(_, _) = self.source {
+            bail!("Switch on tag {} is read from the data, it isn't a single type", self.variable);
+        }
+
+        match &self.default {
+            Some(t) => Ok(t),
+            None    => bail!("Switch on {} must be bound to variables before it's used", self.variable),
+        }
+    }
+
+    /// Read the tag, and pick the type that goes with it.
+    fn tagged(&self, offset: Offset, reader: IntegerReader, renderer: IntegerRenderer) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        let tag = reader.read(offset.get_dynamic()?)?.to_u64()?;
+
+        Ok(vec![
+            (Some(self.variable.clone()), H2Integer::new(reader, renderer)),
+            (Some("body".to_string()),    self.select_value(Some(tag))?.clone()),
+        ])
+    }
+}
+
+impl H2TypeTrait for H2Switch {
+    fn is_static(&self) -> bool {
+        match (&self.source, &self.default) {
+            (SwitchSource::Variable, Some(t)) => t.is_static(),
+            _                                 => false,
+        }
+    }
+
+    fn describe(&self) -> String {
+        let mut cases: Vec<String> = self.cases.iter().map(|(range, field_type)| {
+            match range.end {
+                u64::MAX => format!("{}..: {};", range.start, field_type.describe()),
+                end      => format!("{}..{}: {};", range.start, end, field_type.describe()),
+            }
+        }).collect();
+
+        if let Some(t) = &self.default {
+            cases.push(format!("default: {};", t.describe()));
+        }
+
+        let on = match self.source {
+            SwitchSource::Variable    => self.variable.clone(),
+            SwitchSource::Field       => format!("field {}", self.variable),
+            SwitchSource::Tag(r, _)   => format!("{} {}", r, self.variable),
+        };
+
+        format!("switch({}) {{ {} }}", on, cases.join(" "))
+    }
+
+    // Only variable switches are picked by binding - the rest just bind
+    // their cases (see [`H2Type::bind`])
+    fn bind(&self, variables: &H2Variables) -> SimpleResult<Option<H2Types>> {
+        Ok(Some(H2Types::H2Switch(Self {
+            variable: self.variable.clone(),
+            source: self.source,
+            cases: self.cases.iter().map(|(range, field_type)| {
+                Ok((range.clone(), field_type.bind(variables)?))
+            }).collect::<SimpleResult<Vec<_>>>()?,
+            default: match &self.default {
+                Some(t) => Some(Box::new(t.bind(variables)?)),
+                None    => None,
+            },
+        })))
+    }
+
+    // Any case could be picked, so they're all included
+    fn data_references(&self, references: &mut BTreeSet<H2DataReference>) {
+        for (_, field_type) in &self.cases {
+            field_type.add_data_references(references);
+        }
+
+        if let Some(t) = &self.default {
+            t.add_data_references(references);
+        }
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        match self.source {
+            SwitchSource::Tag(_, _) => {
+                let children = self.children_with_range(offset)?;
+
+                match children.last() {
+                    Some((range, _, _)) => Ok(range.end - offset.position()),
+                    None                => bail!("Can't calculate size with no child types"),
+                }
+            },
+            _ => self.fallback()?.actual_size(offset),
+        }
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        match self.source {
+            SwitchSource::Tag(_, _) => {
+                let strings = self.children_with_range(offset)?.iter().map(|(range, name, child)| {
+                    Ok(format!("{}: {}", name.clone().unwrap_or_default(), child.to_display(offset.at(range.start))?))
+                }).collect::<SimpleResult<Vec<String>>>()?;
+
+                Ok(format!("{{ {} }}", strings.join(", ")))
+            },
+            _ => self.fallback()?.to_display(offset),
+        }
+    }
+
+    fn related(&self, offset: Offset) -> SimpleResult<Vec<(u64, H2Type)>> {
+        match self.source {
+            SwitchSource::Tag(_, _) => Ok(vec![]),
+            _                       => self.fallback()?.related(offset),
+        }
+    }
+
+    fn children(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        match self.source {
+            SwitchSource::Tag(reader, renderer) => self.tagged(offset, reader, renderer),
+            _                                   => self.fallback()?.children(offset),
+        }
+    }
+
+    fn can_be_string(&self) -> bool {
+        self.fallback().map(|t| t.can_be_string()).unwrap_or(false)
+    }
+
+    fn to_string(&self, offset: Offset) -> SimpleResult<String> {
+        self.fallback()?.to_string(offset)
+    }
+
+    fn can_be_integer(&self) -> bool {
+        self.fallback().map(|t| t.can_be_integer()).unwrap_or(false)
+    }
+
+    fn to_integer(&self, offset: Offset) -> SimpleResult<Integer> {
+        self.fallback()?.to_integer(offset)
+    }
+
+    fn can_be_float(&self) -> bool {
+        self.fallback().map(|t| t.can_be_float()).unwrap_or(false)
+    }
+
+    fn to_float(&self, offset: Offset) -> SimpleResult<Float> {
+        self.fallback()?.to_float(offset)
+    }
+
+    fn can_be_character(&self) -> bool {
+        self.fallback().map(|t| t.can_be_character()).unwrap_or(false)
+    }
+
+    fn to_character(&self, offset: Offset) -> SimpleResult<Character> {
+        self.fallback()?.to_character(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+    use generic_number::{Context, Endian, DefaultFormatter, HexFormatter, CharacterReader, CharacterFormatter};
+
+    use crate::simple::numeric::H2Integer;
+    use crate::composite::{H2Array, H2Struct};
+    use crate::simple::string::LPString;
+
+    fn variables(version: Option<u64>) -> H2Variables {
+        version.into_iter().map(|v| ("format_version".to_string(), v)).collect()
+    }
+
+    #[test]
+    fn test_switch() -> SimpleResult<()> {
+        let data = b"\x01\x02\x03\x04".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        // Version 1 has a 16-bit health, version 2+ has a 32-bit one
+        let t = H2Struct::new(vec![
+            ("health".to_string(), H2Switch::new("format_version", vec![
+                (1..2,        H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer())),
+                (2..u64::MAX, H2Integer::new(IntegerReader::U32(Endian::Big), DefaultFormatter::new_integer())),
+            ], None)?),
+        ])?;
+        assert_eq!("struct { switch(format_version) { 1..2: u16be; 2..: u32be; } health; }", t.describe());
+
+        let v1 = t.bind(&variables(Some(1)))?;
+        assert_eq!("struct { u16be health; }", v1.describe());
+        assert_eq!("{ health: 258 }", v1.to_display(offset)?);
+
+        let v2 = t.bind(&variables(Some(5)))?;
+        assert_eq!("struct { u32be health; }", v2.describe());
+        assert_eq!("{ health: 16909060 }", v2.to_display(offset)?);
+
+        // Without the variable (or a matching case), there's nothing to use
+        assert!(t.bind(&variables(None)).is_err());
+        assert!(t.bind(&variables(Some(0))).is_err());
+        assert!(t.to_display(offset).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_default() -> SimpleResult<()> {
+        let data = b"\x01\x02\x03\x04".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Switch::new("format_version", vec![
+            (3..4, H2Array::new(2, H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer()))?),
+        ], Some(H2Integer::new(IntegerReader::U16(Endian::Little), DefaultFormatter::new_integer())))?;
+
+        // Unbound, it's the default
+        assert_eq!(2, t.actual_size(offset)?);
+        assert_eq!("513", t.to_display(offset)?);
+
+        assert_eq!("513", t.bind(&variables(None))?.to_display(offset)?);
+        assert_eq!("513", t.bind(&variables(Some(1)))?.to_display(offset)?);
+        assert_eq!("[ 1, 2 ]", t.bind(&variables(Some(3)))?.to_display(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tagged() -> SimpleResult<()> {
+        //           tag len ---- body ---- tag -- body --  tag
+        let data = b"\x01\x03abc\x02\x01\x02\x09".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        // A TLV-style record: tag 1 is a string, tag 2 is a u16
+        let t = H2Switch::new_tagged(IntegerReader::U8, HexFormatter::pretty_integer(), "type", vec![
+            (1..2, LPString::new(IntegerReader::U8, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?),
+            (2..3, H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer())),
+        ], None)?;
+        assert_eq!("switch(u8 type) { 1..2: lpstring<u8, ascii>; 2..3: u16be; }", t.describe());
+        assert_eq!(false, t.is_static());
+
+        assert_eq!(5, t.actual_size(offset)?);
+        assert_eq!("{ type: 0x01, body: \"abc\" }", t.to_display(offset)?);
+
+        let r = t.resolve(offset.at(5), None)?;
+        assert_eq!(5..8, r.actual_range);
+        assert_eq!(Some("body".to_string()), r.children[1].field_name);
+        assert_eq!("258", r.children[1].display);
+
+        // Unknown tags need a default
+        assert!(t.resolve(offset.at(8), None).is_err());
+
+        // Binding doesn't pick a case
+        assert_eq!(t.describe(), t.bind(&variables(Some(1)))?.describe());
+
+        // An array of records
+        let records = H2Array::new(2, t)?;
+        assert_eq!("[ { type: 0x01, body: \"abc\" }, { type: 0x02, body: 258 } ]", records.to_display(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_on_field() -> SimpleResult<()> {
+        let data = b"\x02\x01\x02\x03\x04".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Struct::new(vec![
+            ("kind".to_string(), H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+            ("value".to_string(), H2Switch::new_on_field("kind", vec![
+                (1..2, H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+                (2..3, H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer())),
+            ], Some(H2Integer::new(IntegerReader::U32(Endian::Big), DefaultFormatter::new_integer())))?),
+            ("after".to_string(), H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+        ])?;
+        assert_eq!("struct { u8 kind; switch(field kind) { 1..2: u8; 2..3: u16be; default: u32be; } value; u8 after; }", t.describe());
+
+        // kind is 2, so value is a u16
+        assert_eq!("{ kind: 2, value: 258, after: 3 }", t.to_display(offset)?);
+        assert_eq!(4, t.actual_size(offset)?);
+
+        // kind is 1, so value is a u8
+        assert_eq!("{ kind: 1, value: 2, after: 3 }", t.to_display(offset.at(1))?);
+
+        // Freezing and binding leave it alone
+        assert_eq!("{ kind: 2, value: 258, after: 3 }", t.freeze().bind(&variables(Some(1)))?.to_display(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_cases() -> SimpleResult<()> {
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+
+        assert!(H2Switch::new("v", vec![], None).is_err());
+        assert!(H2Switch::new("v", vec![(2..2, u8.clone())], None).is_err());
+        assert!(H2Switch::new("v", vec![(1..3, u8.clone()), (2..4, u8.clone())], None).is_err());
+        assert!(H2Switch::new("v", vec![(1..3, u8.clone()), (3..4, u8.clone())], None).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_data_references() -> SimpleResult<()> {
+        use crate::simple::{H2Bitmask, H2Enum};
+
+        let t = H2Struct::new(vec![
+            ("version".to_string(), H2Enum::new(IntegerReader::U32(Endian::Little), "TerrariaVersion")?),
+            ("items".to_string(), H2Array::new(4, H2Enum::new(IntegerReader::U32(Endian::Little), "TerrariaItem")?)?),
+            ("extra".to_string(), H2Switch::new("format_version", vec![
+                (1..2, H2Bitmask::new(IntegerReader::U8, "TerrariaVisibility", false)?),
+            ], Some(H2Enum::new(IntegerReader::U8, "TerrariaItem")?))?),
+        ])?;
+
+        // Every case of a switch is included, and each name only once
+        assert_eq!(
+            vec![
+                H2DataReference::Enum("TerrariaItem".to_string()),
+                H2DataReference::Enum("TerrariaVersion".to_string()),
+                H2DataReference::Bitmask("TerrariaVisibility".to_string()),
+            ],
+            t.data_references().into_iter().collect::<Vec<_>>()
+        );
+
+        // Types that don't use h2data don't reference anything
+        assert!(H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer()).data_references().is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset, H2DataReference};
+use crate::composite::H2Variables;
+
+/// Defines a union.
+///
+/// Like a C union, every member starts at the same place - each one is a
+/// different way of looking at the same bytes. The union is as big as its
+/// biggest member.
+///
+/// When a union is resolved, every member becomes a child, and the
+/// [`crate::ResolvedType`] is marked with
+/// [`crate::ResolvedType::children_overlap`] so the members can be shown
+/// side by side instead of one after the other.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct H2Union {
+    members: Vec<(String, H2Type)>,
+}
+
+impl H2Union {
+    pub fn new_aligned(alignment: Alignment, members: Vec<(String, H2Type)>) -> SimpleResult<H2Type> {
+        if members.len() == 0 {
+            bail!("Unions must contain at least one member");
+        }
+
+        for (i, (name, _)) in members.iter().enumerate() {
+            if members[..i].iter().any(|(other, _)| other == name) {
+                bail!("Union member {:?} is defined more than once", name);
+            }
+        }
+
+        Ok(H2Type::new(alignment, H2Types::H2Union(Self {
+            members: members,
+        })))
+    }
+
+    pub fn new(members: Vec<(String, H2Type)>) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, members)
+    }
+
+    pub fn members(&self) -> &Vec<(String, H2Type)> {
+        &self.members
+    }
+}
+
+impl H2TypeTrait for H2Union {
+    fn is_static(&self) -> bool {
+        self.members.iter().all(|(_, t)| t.is_static())
+    }
+
+    fn describe(&self) -> String {
+        let members: Vec<String> = self.members.iter().map(|(name, member_type)| {
+            format!("{} {};", member_type.describe(), name)
+        }).collect();
+
+        format!("union {{ {} }}", members.join(" "))
+    }
+
+    fn bind(&self, variables: &H2Variables) -> SimpleResult<Option<H2Types>> {
+        Ok(Some(H2Types::H2Union(Self {
+            members: self.members.iter().map(|(name, member_type)| {
+                Ok((name.clone(), member_type.bind(variables)?))
+            }).collect::<SimpleResult<Vec<_>>>()?,
+        })))
+    }
+
+    fn data_references(&self, references: &mut BTreeSet<H2DataReference>) {
+        for (_, member_type) in &self.members {
+            member_type.add_data_references(references);
+        }
+    }
+
+    fn freeze(&self) -> Option<H2Types> {
+        Some(H2Types::H2Union(Self {
+            members: self.members.iter().map(|(name, member_type)| {
+                (name.clone(), member_type.freeze())
+            }).collect(),
+        }))
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        let start = offset.position();
+
+        // Every member starts at the start, so the biggest one is the size
+        Ok(self.children_with_range(offset)?.iter().map(|(range, _, _)| range.end - start).max().unwrap_or(0))
+    }
+
+    fn children(&self, _offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        Ok(self.members.iter().map(|(name, member_type)| {
+            (Some(name.clone()), member_type.clone())
+        }).collect())
+    }
+
+    fn children_with_range(&self, offset: Offset) -> SimpleResult<Vec<(Range<u64>, Option<String>, H2Type)>> {
+        self.members.iter().map(|(name, member_type)| {
+            Ok((member_type.aligned_range(offset)?, Some(name.clone()), member_type.clone()))
+        }).collect()
+    }
+
+    fn children_overlap(&self) -> bool {
+        true
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        let strings: Vec<String> = self.members.iter().map(|(name, member_type)| {
+            Ok(format!("{}: {}", name, member_type.to_display(offset)?))
+        }).collect::<SimpleResult<Vec<String>>>()?;
+
+        Ok(format!("{{ {} }}", strings.join(" | ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+    use generic_number::{Context, IntegerReader, FloatReader, Endian, DefaultFormatter, HexFormatter};
+
+    use crate::simple::numeric::{H2Integer, H2Float};
+    use crate::composite::{H2Array, H2Struct};
+
+    #[test]
+    fn test_union() -> SimpleResult<()> {
+        let data = b"\x00\x00\xc0\x3f\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Union::new(vec![
+            ("as_int".to_string(),   H2Integer::new(IntegerReader::U32(Endian::Little), HexFormatter::pretty_integer())),
+            ("as_float".to_string(), H2Float::new(FloatReader::F32(Endian::Little), DefaultFormatter::new_float())),
+            ("as_bytes".to_string(), H2Array::new(2, H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer()))?),
+        ])?;
+
+        assert_eq!("union { u32le as_int; f32le as_float; u8[2] as_bytes; }", t.describe());
+        assert_eq!(true, t.is_static());
+        assert_eq!(4, t.actual_size(offset)?);
+        assert_eq!("{ as_int: 0x3fc00000 | as_float: F32(1.5) | as_bytes: [ 0, 0 ] }", t.to_display(offset)?);
+
+        // Every member starts at the same place
+        let r = t.resolve(offset.at(0), None)?;
+        assert_eq!(0..4, r.actual_range);
+        assert_eq!(true, r.children_overlap);
+        assert_eq!(
+            vec![0..4, 0..4, 0..2],
+            r.children.iter().map(|c| c.actual_range.clone()).collect::<Vec<_>>()
+        );
+
+        // Nothing is padding, even though the members have different sizes
+        assert!(r.children_with_padding().iter().all(|c| !c.is_padding));
+
+        // Not enough data for the biggest member
+        assert!(t.resolve(offset.at(2), None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_union_in_struct() -> SimpleResult<()> {
+        let data = b"\x01\x02\x03\x04".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Struct::new(vec![
+            ("value".to_string(), H2Union::new(vec![
+                ("short".to_string(), H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer())),
+                ("byte".to_string(),  H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+            ])?),
+            ("after".to_string(), H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+        ])?;
+
+        // The field after the union starts after its biggest member
+        let r = t.resolve(offset, None)?;
+        assert_eq!(0..3, r.actual_range);
+        assert_eq!("3", r.children[1].display);
+        assert_eq!(false, r.children_overlap);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_union() -> SimpleResult<()> {
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+
+        assert!(H2Union::new(vec![]).is_err());
+        assert!(H2Union::new(vec![("a".to_string(), u8.clone()), ("a".to_string(), u8.clone())]).is_err());
+
+        Ok(())
+    }
+}
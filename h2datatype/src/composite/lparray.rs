@@ -0,0 +1,201 @@
+use std::collections::BTreeSet;
+
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use generic_number::{IntegerReader, IntegerRenderer};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset, H2DataReference};
+use crate::composite::H2Variables;
+use crate::simple::numeric::H2Integer;
+
+/// The most elements an array of empty elements (like markers) can have,
+/// since there's no data to check the count against
+const MAX_EMPTY_ELEMENTS: u64 = 0x10000;
+
+/// Defines a length-prefixed array.
+///
+/// This is the array version of [`crate::simple::string::LPString`] - an
+/// integer count, read with `length`, followed by that many elements. The
+/// elements can be any type, including ones whose size changes from element
+/// to element.
+///
+/// The count is the first child (named `count`), and each element is an
+/// unnamed child after it. The count isn't part of the display.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LPArray {
+    length: IntegerReader,
+    renderer: IntegerRenderer,
+    field_type: Box<H2Type>,
+}
+
+impl LPArray {
+    pub fn new_aligned(alignment: Alignment, length: IntegerReader, renderer: IntegerRenderer, field_type: H2Type) -> SimpleResult<H2Type> {
+        if !length.can_be_usize() {
+            bail!("Length type isn't numeric!");
+        }
+
+        Ok(H2Type::new(alignment, H2Types::LPArray(Self {
+            length: length,
+            renderer: renderer,
+            field_type: Box::new(field_type),
+        })))
+    }
+
+    pub fn new(length: IntegerReader, renderer: IntegerRenderer, field_type: H2Type) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, length, renderer, field_type)
+    }
+
+    /// Read the number of elements.
+    fn count(&self, offset: Offset) -> SimpleResult<u64> {
+        let context = offset.get_dynamic()?;
+        let count = self.length.read(context)?.to_u64()?;
+
+        // Elements are almost always at least a byte, which keeps bogus
+        // counts from running away; empty elements (like markers) can't run
+        // out of data, so they get a fixed limit instead
+        let available = context.remaining().saturating_sub(self.length.size() as u64);
+        if count > available {
+            let first = offset.at(offset.position() + self.length.size() as u64);
+            if self.field_type.aligned_size(first)? > 0 {
+                bail!("Array at offset {} claims {} elements, but only {} bytes are left", context.position(), count, available);
+            }
+
+            if count > MAX_EMPTY_ELEMENTS {
+                bail!("Array at offset {} claims {} empty elements, but the most allowed is {}", context.position(), count, MAX_EMPTY_ELEMENTS);
+            }
+        }
+
+        Ok(count)
+    }
+}
+
+impl H2TypeTrait for LPArray {
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    fn describe(&self) -> String {
+        format!("lparray<{}, {}>", self.length, self.field_type.describe())
+    }
+
+    fn bind(&self, variables: &H2Variables) -> SimpleResult<Option<H2Types>> {
+        Ok(Some(H2Types::LPArray(Self {
+            field_type: Box::new(self.field_type.bind(variables)?),
+            ..self.clone()
+        })))
+    }
+
+    fn data_references(&self, references: &mut BTreeSet<H2DataReference>) {
+        self.field_type.add_data_references(references);
+    }
+
+    fn freeze(&self) -> Option<H2Types> {
+        Some(H2Types::LPArray(Self {
+            field_type: Box::new(self.field_type.freeze()),
+            ..self.clone()
+        }))
+    }
+
+    fn children(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        let count = self.count(offset)?;
+
+        let mut children = vec![(Some("count".to_string()), H2Integer::new(self.length, self.renderer))];
+        children.extend((0..count).map(|_| (None, self.field_type.as_ref().clone())));
+
+        Ok(children)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        let strings: Vec<String> = self.children_with_range(offset)?.iter().skip(1).map(|(range, _name, child)| {
+            child.to_display(offset.at(range.start))
+        }).collect::<SimpleResult<Vec<String>>>()?;
+
+        match strings.len() {
+            0 => Ok("[ ]".to_string()),
+            _ => Ok(format!("[ {} ]", strings.join(", "))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+    use generic_number::{Context, Endian, DefaultFormatter, CharacterReader, CharacterFormatter};
+
+    use crate::composite::H2Struct;
+    use crate::simple::H2Marker;
+    use crate::simple::string::LPString;
+
+    #[test]
+    fn test_lparray() -> SimpleResult<()> {
+        let data = b"\x03\x01\x00\x02\x00\x03\x00\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = LPArray::new(IntegerReader::U8, DefaultFormatter::new_integer(), H2Integer::new(IntegerReader::U16(Endian::Little), DefaultFormatter::new_integer()))?;
+        assert_eq!("lparray<u8, u16le>", t.describe());
+        assert_eq!(false, t.is_static());
+        assert_eq!(7, t.actual_size(offset)?);
+        assert_eq!("[ 1, 2, 3 ]", t.to_display(offset)?);
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(0..7, r.actual_range);
+        assert_eq!(4, r.children.len());
+        assert_eq!(Some("count".to_string()), r.children[0].field_name);
+        assert_eq!("3", r.children[0].display);
+        assert_eq!(5..7, r.children[3].actual_range);
+
+        // Empty arrays are just the count
+        let empty = b"\x00".to_vec();
+        let empty_offset = Offset::Dynamic(Context::new(&empty));
+        assert_eq!(1, t.actual_size(empty_offset)?);
+        assert_eq!("[ ]", t.to_display(empty_offset)?);
+
+        // Counts that can't possibly fit
+        assert!(t.to_display(offset.at(7)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lparray_dynamic_elements() -> SimpleResult<()> {
+        let data = b"\x02\x00\x00\x00\x02hi\x01\x03abc\x03".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        // An array of structs that contain strings, so every element is a
+        // different size
+        let t = LPArray::new(IntegerReader::U32(Endian::Little), DefaultFormatter::new_integer(), H2Struct::new(vec![
+            ("name".to_string(), LPString::new(IntegerReader::U8, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?),
+            ("id".to_string(), H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+        ])?)?;
+
+        assert_eq!("[ { name: \"hi\", id: 1 }, { name: \"abc\", id: 3 } ]", t.to_display(offset)?);
+        assert_eq!(data.len() as u64, t.actual_size(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lparray_empty_elements() -> SimpleResult<()> {
+        // Markers don't take up any space, so the count can be bigger than
+        // the data
+        let data = b"\x05".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = LPArray::new(IntegerReader::U8, DefaultFormatter::new_integer(), H2Marker::new("x"))?;
+        assert_eq!(1, t.actual_size(offset)?);
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(6, r.children.len());
+        assert_eq!(1..1, r.children[5].actual_range);
+
+        // But not so much bigger that it runs out of memory
+        let data = b"\xff\xff\xff\xff".to_vec();
+        let t = LPArray::new(IntegerReader::U32(Endian::Little), DefaultFormatter::new_integer(), H2Marker::new("x"))?;
+        assert!(t.actual_size(Offset::Dynamic(Context::new(&data))).is_err());
+
+        Ok(())
+    }
+}
@@ -8,3 +8,32 @@ pub use h2array::*;
 
 mod h2struct;
 pub use h2struct::*;
+
+mod h2sparse_struct;
+pub use h2sparse_struct::*;
+
+mod lparray;
+pub use lparray::*;
+
+mod ntarray;
+pub use ntarray::*;
+
+mod h2union;
+pub use h2union::*;
+
+mod h2bitfield;
+pub use h2bitfield::*;
+
+mod h2switch;
+pub use h2switch::*;
+
+mod builder;
+pub use builder::*;
+
+mod self_describing;
+
+mod h2messagepack;
+pub use h2messagepack::*;
+
+mod h2cbor;
+pub use h2cbor::*;
@@ -0,0 +1,211 @@
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::ops::Range;
+
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use generic_number::{IntegerReader, IntegerRenderer};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset, H2DataReference};
+use crate::composite::H2Variables;
+use crate::simple::numeric::H2Integer;
+
+/// Defines a terminated array.
+///
+/// This is the array version of [`crate::simple::string::NTString`] -
+/// elements are read one after another until the next value, read with
+/// `terminator`, is `value` (like an `i32` that's `-1`, or a `u8` that's
+/// `0`). The terminator is checked where each element would start, so it's
+/// usually the first field of the element.
+///
+/// Each element is an unnamed child, and the terminator is the last child
+/// (named `terminator`). The terminator isn't part of the display.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NTArray {
+    field_type: Box<H2Type>,
+    terminator: IntegerReader,
+    renderer: IntegerRenderer,
+    value: i128,
+}
+
+impl NTArray {
+    pub fn new_aligned(alignment: Alignment, field_type: H2Type, terminator: IntegerReader, renderer: IntegerRenderer, value: i128) -> SimpleResult<H2Type> {
+        // A terminator that can't be read would never end the array
+        let fits = match terminator {
+            IntegerReader::U8      => u8::try_from(value).is_ok(),
+            IntegerReader::U16(_)  => u16::try_from(value).is_ok(),
+            IntegerReader::U32(_)  => u32::try_from(value).is_ok(),
+            IntegerReader::U64(_)  => u64::try_from(value).is_ok(),
+            IntegerReader::U128(_) => value >= 0,
+            IntegerReader::I8      => i8::try_from(value).is_ok(),
+            IntegerReader::I16(_)  => i16::try_from(value).is_ok(),
+            IntegerReader::I32(_)  => i32::try_from(value).is_ok(),
+            IntegerReader::I64(_)  => i64::try_from(value).is_ok(),
+            IntegerReader::I128(_) => true,
+        };
+
+        if !fits {
+            bail!("Terminator {} can't be read as a {}", value, terminator);
+        }
+
+        Ok(H2Type::new(alignment, H2Types::NTArray(Self {
+            field_type: Box::new(field_type),
+            terminator: terminator,
+            renderer: renderer,
+            value: value,
+        })))
+    }
+
+    pub fn new(field_type: H2Type, terminator: IntegerReader, renderer: IntegerRenderer, value: i128) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, field_type, terminator, renderer, value)
+    }
+
+    fn is_terminator(&self, offset: Offset) -> SimpleResult<bool> {
+        Ok(self.terminator.read(offset.get_dynamic()?)?.to_i128()? == self.value)
+    }
+}
+
+impl H2TypeTrait for NTArray {
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    fn describe(&self) -> String {
+        format!("ntarray<{}, {} = {}>", self.field_type.describe(), self.terminator, self.value)
+    }
+
+    fn bind(&self, variables: &H2Variables) -> SimpleResult<Option<H2Types>> {
+        Ok(Some(H2Types::NTArray(Self {
+            field_type: Box::new(self.field_type.bind(variables)?),
+            ..self.clone()
+        })))
+    }
+
+    fn data_references(&self, references: &mut BTreeSet<H2DataReference>) {
+        self.field_type.add_data_references(references);
+    }
+
+    fn freeze(&self) -> Option<H2Types> {
+        Some(H2Types::NTArray(Self {
+            field_type: Box::new(self.field_type.freeze()),
+            ..self.clone()
+        }))
+    }
+
+    fn children(&self, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+        Ok(self.children_with_range(offset)?.into_iter().map(|(_range, name, child)| (name, child)).collect())
+    }
+
+    // Finding the end means measuring every element anyways, so the ranges
+    // are worked out as we go
+    fn children_with_range(&self, offset: Offset) -> SimpleResult<Vec<(Range<u64>, Option<String>, H2Type)>> {
+        let mut children = vec![];
+        let mut position = offset.position();
+
+        loop {
+            if self.is_terminator(offset.at(position))? {
+                let terminator = H2Integer::new(self.terminator, self.renderer);
+                children.push((terminator.aligned_range(offset.at(position))?, Some("terminator".to_string()), terminator));
+
+                return Ok(children);
+            }
+
+            let range = self.field_type.aligned_range(offset.at(position))?;
+            if range.end <= position {
+                bail!("Array element at offset {} is empty, so the terminator will never be reached", position);
+            }
+
+            position = range.end;
+            children.push((range, None, self.field_type.as_ref().clone()));
+        }
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        let children = self.children_with_range(offset)?;
+
+        let strings: Vec<String> = children[..(children.len() - 1)].iter().map(|(range, _name, child)| {
+            child.to_display(offset.at(range.start))
+        }).collect::<SimpleResult<Vec<String>>>()?;
+
+        match strings.len() {
+            0 => Ok("[ ]".to_string()),
+            _ => Ok(format!("[ {} ]", strings.join(", "))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+    use generic_number::{Context, Endian, DefaultFormatter, CharacterReader, CharacterFormatter};
+
+    use crate::composite::H2Struct;
+    use crate::simple::string::LPString;
+
+    #[test]
+    fn test_ntarray() -> SimpleResult<()> {
+        let data = b"\x01\x02\x03\x00\x04".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = NTArray::new(H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer()), IntegerReader::U8, DefaultFormatter::new_integer(), 0)?;
+        assert_eq!("ntarray<u8, u8 = 0>", t.describe());
+        assert_eq!(false, t.is_static());
+        assert_eq!(4, t.actual_size(offset)?);
+        assert_eq!("[ 1, 2, 3 ]", t.to_display(offset)?);
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(0..4, r.actual_range);
+        assert_eq!(4, r.children.len());
+        assert_eq!(Some("terminator".to_string()), r.children[3].field_name);
+        assert_eq!(3..4, r.children[3].actual_range);
+
+        // An empty array is just the terminator
+        assert_eq!(1, t.actual_size(offset.at(3))?);
+        assert_eq!("[ ]", t.to_display(offset.at(3))?);
+
+        // Running out of data before the terminator
+        assert!(t.to_display(offset.at(4)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ntarray_of_structs() -> SimpleResult<()> {
+        // Two entries that each start with an i32, then -1
+        let data = b"\x01\x00\x00\x00\x02hi\x02\x00\x00\x00\x03abc\xff\xff\xff\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = NTArray::new(H2Struct::new(vec![
+            ("id".to_string(),   H2Integer::new(IntegerReader::I32(Endian::Little), DefaultFormatter::new_integer())),
+            ("name".to_string(), LPString::new(IntegerReader::U8, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?),
+        ])?, IntegerReader::I32(Endian::Little), DefaultFormatter::new_integer(), -1)?;
+
+        assert_eq!("ntarray<struct { i32le id; lpstring<u8, ascii> name; }, i32le = -1>", t.describe());
+        assert_eq!("[ { id: 1, name: \"hi\" }, { id: 2, name: \"abc\" } ]", t.to_display(offset)?);
+        assert_eq!(data.len() as u64, t.actual_size(offset)?);
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!("-1", r.children[2].display);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_ntarray() -> SimpleResult<()> {
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+
+        // Terminators that can never be read
+        assert!(NTArray::new(u8.clone(), IntegerReader::U8, DefaultFormatter::new_integer(), -1).is_err());
+        assert!(NTArray::new(u8.clone(), IntegerReader::I8, DefaultFormatter::new_integer(), 128).is_err());
+
+        // Empty elements never get anywhere
+        let data = b"\x01\x00".to_vec();
+        let t = NTArray::new(crate::simple::H2Marker::new("x"), IntegerReader::U8, DefaultFormatter::new_integer(), 0)?;
+        assert!(t.to_display(Offset::Dynamic(Context::new(&data))).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+// Shared code for self-describing formats (MessagePack and CBOR).
+//
+// Both formats are a tree of values, where each value starts with a header
+// that says what it is and how long it is. The formats only differ in how the
+// headers are encoded, so each format just decodes headers (see `Decoder`)
+// and everything else - sizes, children, displays - lives here.
+
+use simple_error::{bail, SimpleResult};
+use generic_number::{Context, Float, Integer};
+
+use crate::{H2Type, Offset};
+use crate::simple::H2Blob;
+
+/// How deeply values can be nested - displaying a value recurses into each
+/// nested value, so this keeps malicious data from overflowing the stack
+const MAX_DEPTH: usize = 100;
+
+/// What a value is, according to its header.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Header {
+    /// A value with nothing in it, with the name the format uses
+    Empty(&'static str),
+    Boolean(bool),
+    Integer(Integer),
+    Float(f64),
+
+    /// UTF-8 text, with the length in bytes
+    Text(u64),
+
+    /// Raw bytes, with the length
+    Binary(u64),
+
+    /// An application-defined type and its length in bytes (MessagePack)
+    Extension(i8, u64),
+
+    /// A simple value without a defined meaning (CBOR)
+    Simple(u8),
+
+    /// A number of values
+    Array(u64),
+
+    /// A number of key/value pairs
+    Map(u64),
+
+    /// A tag number, followed by the tagged value (CBOR)
+    Tag(u64),
+}
+
+impl Header {
+    /// How many nested values follow the header.
+    fn nested_count(self) -> u64 {
+        match self {
+            Self::Array(count) => count,
+            Self::Map(count)   => count.saturating_mul(2),
+            Self::Tag(_)       => 1,
+            _                  => 0,
+        }
+    }
+
+    /// How many bytes of data follow the header (not counting nested values).
+    fn data_length(self) -> u64 {
+        match self {
+            Self::Text(length)         => length,
+            Self::Binary(length)       => length,
+            Self::Extension(_, length) => length,
+            _                          => 0,
+        }
+    }
+}
+
+pub(crate) trait Decoder {
+    /// Read the header at `context`, and return its size along with it.
+    fn header(&self, context: Context) -> SimpleResult<(u64, Header)>;
+
+    /// The type of values nested in this one.
+    fn nested(&self) -> H2Type;
+}
+
+/// Read the header, and check that what it claims can actually fit.
+fn read_header(decoder: &dyn Decoder, context: Context) -> SimpleResult<(u64, Header)> {
+    let (header_size, header) = decoder.header(context)?;
+
+    // Every nested value is at least one byte, which keeps bogus counts from
+    // running away
+    let needed = header_size.saturating_add(header.data_length()).saturating_add(header.nested_count());
+    let available = context.remaining();
+    if needed > available {
+        bail!("Value at offset {} needs at least {} bytes, but only {} are left", context.position(), needed, available);
+    }
+
+    Ok((header_size, header))
+}
+
+/// Measure the value at `context`, including everything nested in it.
+///
+/// This walks the headers in a loop instead of recursing, and fails if the
+/// value is nested more than [`MAX_DEPTH`] deep - since everything else
+/// measures what's nested in it first, nothing gets to recurse further than
+/// that.
+fn measure(decoder: &dyn Decoder, context: Context) -> SimpleResult<u64> {
+    let start = context.position();
+    let mut position = start;
+
+    // How many values are left to read at each level we're inside of
+    let mut pending: Vec<u64> = vec![1];
+
+    while let Some(left) = pending.last_mut() {
+        if *left == 0 {
+            pending.pop();
+            continue;
+        }
+        *left -= 1;
+
+        let (header_size, header) = read_header(decoder, context.at(position))?;
+        position += header_size + header.data_length();
+
+        if header.nested_count() > 0 {
+            if pending.len() >= MAX_DEPTH {
+                bail!("Value at offset {} is nested more than {} deep", start, MAX_DEPTH);
+            }
+
+            pending.push(header.nested_count());
+        }
+    }
+
+    Ok(position - start)
+}
+
+/// Get the header, and the offset of each nested value.
+fn nested_offsets(decoder: &dyn Decoder, offset: Offset) -> SimpleResult<(u64, Header, Vec<u64>, u64)> {
+    let context = offset.get_dynamic()?;
+    let (header_size, header) = read_header(decoder, context)?;
+
+    let mut position = context.position() + header_size + header.data_length();
+    let mut offsets = vec![];
+    for _ in 0..header.nested_count() {
+        offsets.push(position);
+        position += measure(decoder, context.at(position))?;
+    }
+
+    Ok((header_size, header, offsets, position))
+}
+
+pub(crate) fn actual_size(decoder: &dyn Decoder, offset: Offset) -> SimpleResult<u64> {
+    measure(decoder, offset.get_dynamic()?)
+}
+
+/// Values with nested values have their header as the first (unnamed) child,
+/// then the nested values. Map values are named after their keys.
+pub(crate) fn children(decoder: &dyn Decoder, offset: Offset) -> SimpleResult<Vec<(Option<String>, H2Type)>> {
+    let (header_size, header, offsets, _) = nested_offsets(decoder, offset)?;
+    if offsets.len() == 0 {
+        return Ok(vec![]);
+    }
+
+    let nested = decoder.nested();
+    let mut children = vec![(None, H2Blob::new(header_size)?)];
+
+    for index in 0..offsets.len() {
+        let name = match (header, index % 2) {
+            (Header::Map(_), 1) => {
+                let key = offset.at(offsets[index - 1]);
+                Some(nested.to_string(key).or_else(|_| nested.to_display(key))?)
+            },
+            _ => None,
+        };
+
+        children.push((name, nested.clone()));
+    }
+
+    Ok(children)
+}
+
+pub(crate) fn to_display(decoder: &dyn Decoder, offset: Offset) -> SimpleResult<String> {
+    if let Offset::Static(_) = offset {
+        return Ok(decoder.nested().describe());
+    }
+
+    let (header_size, header, offsets, _) = nested_offsets(decoder, offset)?;
+    let nested = decoder.nested();
+    let displays = offsets.iter().map(|position| {
+        nested.to_display(offset.at(*position))
+    }).collect::<SimpleResult<Vec<String>>>()?;
+
+    Ok(match header {
+        Header::Empty(name)    => name.to_string(),
+        Header::Boolean(b)     => b.to_string(),
+        Header::Integer(i)     => i.to_string(),
+        Header::Float(f)       => f.to_string(),
+        Header::Text(length)   => {
+            // Show bad UTF-8 rather than failing, so the rest of the tree
+            // still displays
+            let bytes = offset.get_dynamic()?.at(offset.position() + header_size).read_bytes(length as usize)?;
+            format!("{:?}", String::from_utf8_lossy(&bytes))
+        },
+        Header::Binary(length) => format!("bin[{}]", length),
+        Header::Extension(extension_type, length) => format!("ext({})[{}]", extension_type, length),
+        Header::Simple(value)  => format!("simple({})", value),
+        Header::Array(_)       => format!("[ {} ]", displays.join(", ")),
+        Header::Map(_)         => {
+            let pairs: Vec<String> = displays.chunks(2).map(|pair| pair.join(": ")).collect();
+            format!("{{ {} }}", pairs.join(", "))
+        },
+        Header::Tag(tag)       => format!("{}({})", tag, displays.join("")),
+    })
+}
+
+pub(crate) fn to_string(decoder: &dyn Decoder, offset: Offset) -> SimpleResult<String> {
+    let context = offset.get_dynamic()?;
+
+    match read_header(decoder, context)? {
+        (header_size, Header::Text(length)) => {
+            let bytes = context.at(context.position() + header_size).read_bytes(length as usize)?;
+
+            match String::from_utf8(bytes) {
+                Ok(s)  => Ok(s),
+                Err(e) => bail!("Invalid UTF-8 text at offset {}: {}", context.position(), e),
+            }
+        },
+        _ => bail!("Value at offset {} isn't text", context.position()),
+    }
+}
+
+pub(crate) fn to_integer(decoder: &dyn Decoder, offset: Offset) -> SimpleResult<Integer> {
+    let context = offset.get_dynamic()?;
+
+    match read_header(decoder, context)? {
+        (_, Header::Integer(i)) => Ok(i),
+        _                       => bail!("Value at offset {} isn't an integer", context.position()),
+    }
+}
+
+pub(crate) fn to_float(decoder: &dyn Decoder, offset: Offset) -> SimpleResult<Float> {
+    let context = offset.get_dynamic()?;
+
+    match read_header(decoder, context)? {
+        (_, Header::Float(f)) => Ok(Float::from(f)),
+        _                     => bail!("Value at offset {} isn't a float", context.position()),
+    }
+}
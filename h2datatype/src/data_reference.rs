@@ -0,0 +1,30 @@
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+/// Something from [`h2data`] that a type needs to display its values.
+///
+/// When one of these changes (for example, a value in an enum is renamed),
+/// only values from types that reference it need to be displayed again - see
+/// [`crate::H2Type::data_references`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum H2DataReference {
+    /// An enum, by name (see [`crate::simple::H2Enum`])
+    Enum(String),
+
+    /// A bitmask, by name (see [`crate::simple::H2Bitmask`])
+    Bitmask(String),
+
+    /// A table of string constants, by name (see
+    /// [`crate::simple::H2FourCC`])
+    StringConstants(String),
+}
+
+impl fmt::Display for H2DataReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Enum(name)    => write!(f, "enum {}", name),
+            Self::Bitmask(name) => write!(f, "bitmask {}", name),
+            Self::StringConstants(name) => write!(f, "string constants {}", name),
+        }
+    }
+}
@@ -0,0 +1,676 @@
+//! Build types from short text expressions, like `u32le` or
+//! `struct { u32 x; u32 y; }`.
+//!
+//! The syntax is the same one [`H2Type::describe`] writes, so a description
+//! can always be parsed back into the type it came from. That's also what the
+//! command line uses to apply a type at an offset (`u32le@0x10`).
+
+use std::str::FromStr;
+
+use simple_error::{SimpleResult, SimpleError};
+
+use generic_number::{CharacterFormatter, CharacterReader, DefaultFormatter, Endian, FloatReader, HexFormatter, IntegerReader, IntegerRenderer};
+use h2data::parse_unsigned;
+
+use crate::H2Type;
+use crate::composite::{H2Array, H2Struct, H2Union, LPArray, NTArray};
+use crate::simple::{H2Bitmask, H2Blob, H2Enum, H2FourCC, H2Pointer, H2UUID, PointerBase, Rgb};
+use crate::simple::network::{IPv4, IPv6, MacAddress, MacAddress8};
+use crate::simple::numeric::{H2Character, H2Float, H2Integer, H2Leb128};
+use crate::simple::string::{H2String, LPString, LengthUnit, NTString};
+
+/// Parses type expressions.
+///
+/// The expressions look like C, mostly:
+///
+/// * Integers and floats are named by their size, like `u8`, `i32`, `u64be`,
+///   or `f32le` - without `le` or `be`, the parser's endian is used
+/// * Characters and strings take an encoding (`ascii`, `utf8`, `utf16le`,
+///   ...): `char<ascii>`, `ntstring<utf8>`, `lpstring<u8, ascii>`, and
+///   `string<ascii>[8]`; `lpstr` and `ntstr` are short forms, and length
+///   prefixes that count code units are written `lpstring<u16, utf16le units>`
+/// * Anything with a fixed layout has a name - `fourcc`, `rgb`, `macaddr`,
+///   `macaddr8`, `uleb128`, `sleb128`, `blob[16]`, and `uuid`, `ipv4`, and
+///   `ipv6` (which take an optional endian, like `ipv4<be>`)
+/// * Types from [`h2data`] give the reader and the table's name:
+///   `enum<u8, TerrariaGameMode>`, `bitmask<u32, TerrariaVisibility>`, and
+///   `fourcc<FourCC>`
+/// * Pointers give the integer type of the address, like `ptr<u32>`, and are
+///   always displayed in hex
+/// * Offsets and sizes are integers written like `offset<u32>` - they use the
+///   parser's renderer, which can show them differently (see
+///   [`generic_number::DefaultFormatter::hex_offsets`])
+/// * Any type can be made into an array with `[length]`, like `u16[4]`; arrays
+///   that start with their length are written `lparray<u32, u16>`, and arrays
+///   that end with a terminator give its type and value, like
+///   `ntarray<u16, u8 = 0>`
+/// * Pointers can have a target, and optionally a base: `ptr<u32, u16>`,
+///   `ptr<i32, u16, relative>`, or `ptr<u32, u16, base 0x40>`
+/// * Structs list their fields: `struct { u32 x; u32 y; }`, and unions
+///   list their members the same way: `union { u32 i; f32 f; }`
+///
+/// Arguments can be given in `<>` (like [`H2Type::describe`] writes them) or
+/// `()`, and whitespace between parts is ignored. Numbers are parsed with
+/// [`h2data::parse_unsigned`], so `0x10` works anywhere `16` does.
+///
+/// ```
+/// use h2datatype::*;
+/// use generic_number::*;
+///
+/// let data = b"\x01\x00\x02\x00\x03\x00".to_vec();
+/// let offset = Offset::Dynamic(Context::new(&data));
+///
+/// let t: H2Type = "struct { u16 x; u16[2] y; }".parse().unwrap();
+/// assert_eq!("struct { u16le x; u16le[2] y; }", t.describe());
+/// assert_eq!("{ x: 1, y: [ 2, 3 ] }", t.to_display(offset).unwrap());
+///
+/// let (t, at) = H2TypeParser::default().parse_at("u8@0x2").unwrap();
+/// assert_eq!(Some(2), at);
+/// assert_eq!("2", t.to_display(offset.at(2)).unwrap());
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct H2TypeParser {
+    /// The endian used when a type that needs one doesn't give it
+    pub endian: Endian,
+
+    /// How integers are displayed
+    pub integer_renderer: IntegerRenderer,
+}
+
+impl Default for H2TypeParser {
+    fn default() -> Self {
+        Self {
+            endian: Endian::Little,
+            integer_renderer: DefaultFormatter::new_integer(),
+        }
+    }
+}
+
+impl H2TypeParser {
+    pub fn new(endian: Endian, integer_renderer: IntegerRenderer) -> Self {
+        Self {
+            endian: endian,
+            integer_renderer: integer_renderer,
+        }
+    }
+
+    /// Parse a type expression.
+    pub fn parse(&self, expression: &str) -> SimpleResult<H2Type> {
+        let mut state = ParseState::new(expression, self);
+
+        let t = state.parse_type()?;
+        state.finish()?;
+
+        Ok(t)
+    }
+
+    /// Parse a type expression that can be followed by where to apply it,
+    /// like `u32le@0x10`. The offset is `None` if it's not there.
+    pub fn parse_at(&self, spec: &str) -> SimpleResult<(H2Type, Option<u64>)> {
+        match spec.rsplit_once('@') {
+            Some((expression, offset)) => {
+                let offset = parse_unsigned(offset).map_err(|e| {
+                    SimpleError::new(format!("Bad offset in '{}': {}", spec.trim(), e))
+                })?;
+
+                Ok((self.parse(expression)?, Some(offset as u64)))
+            },
+            None => Ok((self.parse(spec)?, None)),
+        }
+    }
+
+    fn integer_reader(&self, name: &str) -> Option<IntegerReader> {
+        let (size, endian) = split_endian(name, self.endian);
+
+        match (size, endian) {
+            ("u8", None)    => Some(IntegerReader::U8),
+            ("u16", Some(e))  => Some(IntegerReader::U16(e)),
+            ("u32", Some(e))  => Some(IntegerReader::U32(e)),
+            ("u64", Some(e))  => Some(IntegerReader::U64(e)),
+            ("u128", Some(e)) => Some(IntegerReader::U128(e)),
+
+            ("i8", None)    => Some(IntegerReader::I8),
+            ("i16", Some(e))  => Some(IntegerReader::I16(e)),
+            ("i32", Some(e))  => Some(IntegerReader::I32(e)),
+            ("i64", Some(e))  => Some(IntegerReader::I64(e)),
+            ("i128", Some(e)) => Some(IntegerReader::I128(e)),
+
+            _ => None,
+        }
+    }
+
+    fn float_reader(&self, name: &str) -> Option<FloatReader> {
+        match split_endian(name, self.endian) {
+            ("f16", Some(e)) => Some(FloatReader::F16(e)),
+            ("f32", Some(e)) => Some(FloatReader::F32(e)),
+            ("f64", Some(e)) => Some(FloatReader::F64(e)),
+            _                => None,
+        }
+    }
+
+    fn character_reader(&self, name: &str) -> Option<CharacterReader> {
+        match split_endian(name, self.endian) {
+            ("ascii", None)    => Some(CharacterReader::ASCII),
+            ("utf8", None)     => Some(CharacterReader::UTF8),
+            ("utf16", Some(e)) => Some(CharacterReader::UTF16(e)),
+            ("utf32", Some(e)) => Some(CharacterReader::UTF32(e)),
+            _                  => None,
+        }
+    }
+}
+
+/// Split the endian off the end of a name like `u32le`. Names that never
+/// have an endian (`u8`, `ascii`) get `None`; the rest get the one they
+/// end with, or `default`.
+fn split_endian(name: &str, default: Endian) -> (&str, Option<Endian>) {
+    let (base, endian) = if let Some(base) = name.strip_suffix("le") {
+        (base, Some(Endian::Little))
+    } else if let Some(base) = name.strip_suffix("be") {
+        (base, Some(Endian::Big))
+    } else {
+        (name, None)
+    };
+
+    match base {
+        // Single bytes don't have an endian - keep `u8le` from parsing
+        "u8" | "i8" | "ascii" | "utf8" => match endian {
+            Some(_) => (name, Some(default)),
+            None    => (base, None),
+        },
+        _ => (base, Some(endian.unwrap_or(default))),
+    }
+}
+
+impl H2Type {
+    /// Parse a type expression with the default [`H2TypeParser`].
+    pub fn parse(expression: &str) -> SimpleResult<H2Type> {
+        H2TypeParser::default().parse(expression)
+    }
+}
+
+impl FromStr for H2Type {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> SimpleResult<Self> {
+        Self::parse(s)
+    }
+}
+
+struct ParseState<'a> {
+    expression: &'a str,
+    chars: Vec<char>,
+    position: usize,
+    parser: &'a H2TypeParser,
+}
+
+impl<'a> ParseState<'a> {
+    fn new(expression: &'a str, parser: &'a H2TypeParser) -> Self {
+        Self {
+            expression: expression,
+            chars: expression.chars().collect(),
+            position: 0,
+            parser: parser,
+        }
+    }
+
+    fn error(&self, message: &str) -> SimpleError {
+        SimpleError::new(format!("Couldn't parse '{}' at position {}: {}", self.expression.trim(), self.position, message))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.position).map(|c| c.is_whitespace()).unwrap_or(false) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.position).copied()
+    }
+
+    /// Consume `c` if it's next.
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> SimpleResult<()> {
+        match self.eat(c) {
+            true  => Ok(()),
+            false => Err(self.error(&format!("expected '{}'", c))),
+        }
+    }
+
+    /// A name or number - anything made of letters, digits, and `_`.
+    fn word(&mut self, what: &str) -> SimpleResult<String> {
+        self.skip_whitespace();
+
+        let start = self.position;
+        while self.chars.get(self.position).map(|c| c.is_alphanumeric() || *c == '_').unwrap_or(false) {
+            self.position += 1;
+        }
+
+        if start == self.position {
+            return Err(self.error(&format!("expected {}", what)));
+        }
+
+        Ok(self.chars[start..self.position].iter().collect())
+    }
+
+    fn number(&mut self, what: &str) -> SimpleResult<u64> {
+        self.skip_whitespace();
+        let start = self.position;
+        let word = self.word(what)?;
+
+        parse_unsigned(&word).map(|n| n as u64).map_err(|e| {
+            self.position = start;
+            self.error(&e.to_string())
+        })
+    }
+
+    /// Open an argument list, returning the character that closes it.
+    fn open(&mut self) -> Option<char> {
+        if self.eat('<') {
+            Some('>')
+        } else if self.eat('(') {
+            Some(')')
+        } else {
+            None
+        }
+    }
+
+    fn expect_open(&mut self, name: &str) -> SimpleResult<char> {
+        self.open().ok_or_else(|| self.error(&format!("'{}' needs arguments, like {}<...>", name, name)))
+    }
+
+    fn integer_reader(&mut self) -> SimpleResult<IntegerReader> {
+        self.skip_whitespace();
+        let start = self.position;
+        let name = self.word("an integer type")?;
+
+        self.parser.integer_reader(&name).ok_or_else(|| {
+            self.position = start;
+            self.error(&format!("'{}' isn't an integer type", name))
+        })
+    }
+
+    fn character_reader(&mut self) -> SimpleResult<CharacterReader> {
+        self.skip_whitespace();
+        let start = self.position;
+        let name = self.word("a character encoding")?;
+
+        self.parser.character_reader(&name).ok_or_else(|| {
+            self.position = start;
+            self.error(&format!("'{}' isn't a character encoding", name))
+        })
+    }
+
+    /// An optional `<le>` or `<be>`.
+    fn optional_endian(&mut self) -> SimpleResult<Endian> {
+        let close = match self.open() {
+            Some(close) => close,
+            None        => return Ok(self.parser.endian),
+        };
+
+        let endian = match &self.word("an endian")?[..] {
+            "le" => Endian::Little,
+            "be" => Endian::Big,
+            e    => return Err(self.error(&format!("'{}' isn't an endian (le or be)", e))),
+        };
+        self.expect(close)?;
+
+        Ok(endian)
+    }
+
+    /// A `[length]` that a type requires.
+    fn length(&mut self, name: &str) -> SimpleResult<u64> {
+        if !self.eat('[') {
+            return Err(self.error(&format!("'{}' needs a length, like {}[4]", name, name)));
+        }
+
+        let length = self.number("a length")?;
+        self.expect(']')?;
+
+        Ok(length)
+    }
+
+    /// Wrap errors from the type constructors with where they happened.
+    fn check(&self, t: SimpleResult<H2Type>) -> SimpleResult<H2Type> {
+        t.map_err(|e| self.error(&e.to_string()))
+    }
+
+    fn parse_type(&mut self) -> SimpleResult<H2Type> {
+        self.skip_whitespace();
+        let start = self.position;
+        let name = self.word("a type")?;
+
+        let mut t = match &name[..] {
+            "struct" => self.parse_struct()?,
+            "union"  => self.parse_union()?,
+
+            "char" => {
+                let close = self.expect_open(&name)?;
+                let reader = self.character_reader()?;
+                self.expect(close)?;
+
+                H2Character::new(reader, CharacterFormatter::pretty_character())
+            },
+            "ntstring" | "ntstr" => {
+                let close = self.expect_open(&name)?;
+                let reader = self.character_reader()?;
+                self.expect(close)?;
+
+                NTString::new(reader, CharacterFormatter::pretty_str_character())
+            },
+            "lpstring" | "lpstr" => {
+                let close = self.expect_open(&name)?;
+                let length = self.integer_reader()?;
+                self.expect(',')?;
+                let reader = self.character_reader()?;
+
+                let unit = match self.peek() {
+                    Some(c) if c == close => LengthUnit::Characters,
+                    _ => match &self.word("'units'")?[..] {
+                        "units" => LengthUnit::CodeUnits,
+                        other   => return Err(self.error(&format!("unexpected '{}'", other))),
+                    },
+                };
+                self.expect(close)?;
+
+                self.check(LPString::new_counted(length, unit, reader, CharacterFormatter::pretty_str_character()))?
+            },
+            "string" | "str" => {
+                let close = self.expect_open(&name)?;
+                let reader = self.character_reader()?;
+                self.expect(close)?;
+                let length = self.length(&name)?;
+
+                self.check(H2String::new(length, reader, CharacterFormatter::pretty_str_character()))?
+            },
+            "blob" => {
+                let length = self.length(&name)?;
+
+                self.check(H2Blob::new(length))?
+            },
+
+            "enum" | "bitmask" => {
+                let close = self.expect_open(&name)?;
+                let reader = self.integer_reader()?;
+                self.expect(',')?;
+                let table = self.word("the name of a table")?;
+                self.expect(close)?;
+
+                match &name[..] {
+                    "enum" => self.check(H2Enum::new(reader, &table))?,
+                    _      => self.check(H2Bitmask::new(reader, &table, false))?,
+                }
+            },
+            "fourcc" => match self.open() {
+                Some(close) => {
+                    let table = self.word("the name of a table")?;
+                    self.expect(close)?;
+
+                    self.check(H2FourCC::new_with_constants(&table))?
+                },
+                None => H2FourCC::new(),
+            },
+
+            "ptr" => {
+                let close = self.expect_open(&name)?;
+                let reader = self.integer_reader()?;
+
+                // The target and base are optional
+                if !self.eat(',') {
+                    self.expect(close)?;
+                    H2Pointer::new(reader, HexFormatter::pretty_integer())
+                } else {
+                    let target = self.parse_type()?;
+
+                    let base = match self.eat(',') {
+                        false => PointerBase::Buffer,
+                        true  => match &self.word("'relative' or 'base'")?[..] {
+                            "relative" => PointerBase::Pointer,
+                            "base"     => PointerBase::Custom(self.number("a base")?),
+                            other      => return Err(self.error(&format!("unexpected '{}'", other))),
+                        },
+                    };
+                    self.expect(close)?;
+
+                    H2Pointer::new_to(reader, HexFormatter::pretty_integer(), base, target)
+                }
+            },
+
+            "lparray" => {
+                let close = self.expect_open(&name)?;
+                let length = self.integer_reader()?;
+                self.expect(',')?;
+                let field_type = self.parse_type()?;
+                self.expect(close)?;
+
+                self.check(LPArray::new(length, self.parser.integer_renderer, field_type))?
+            },
+
+            "ntarray" => {
+                let close = self.expect_open(&name)?;
+                let field_type = self.parse_type()?;
+                self.expect(',')?;
+                let terminator = self.integer_reader()?;
+                self.expect('=')?;
+                let negative = self.eat('-');
+                let value = self.number("a terminator")? as i128;
+                self.expect(close)?;
+
+                let value = if negative { -value } else { value };
+                self.check(NTArray::new(field_type, terminator, self.parser.integer_renderer, value))?
+            },
+
+            "offset" => {
+                let close = self.expect_open(&name)?;
+                let reader = self.integer_reader()?;
+                self.expect(close)?;
+
+                H2Integer::new_offset(reader, self.parser.integer_renderer)
+            },
+
+            "uuid"     => H2UUID::new(self.optional_endian()?),
+            "ipv4"     => IPv4::new(self.optional_endian()?),
+            "ipv6"     => IPv6::new(self.optional_endian()?),
+            "macaddr"  => MacAddress::new(),
+            "macaddr8" => MacAddress8::new(),
+            "rgb"      => Rgb::new(false),
+            "uleb128"  => H2Leb128::new(false, self.parser.integer_renderer),
+            "sleb128"  => H2Leb128::new(true, self.parser.integer_renderer),
+
+            _ => {
+                if let Some(reader) = self.parser.integer_reader(&name) {
+                    H2Integer::new(reader, self.parser.integer_renderer)
+                } else if let Some(reader) = self.parser.float_reader(&name) {
+                    H2Float::new(reader, DefaultFormatter::new_float())
+                } else {
+                    self.position = start;
+                    return Err(self.error(&format!("unknown type '{}'", name)));
+                }
+            },
+        };
+
+        // Any number of array suffixes - `u8[2][3]` is 3 arrays of 2
+        while self.peek() == Some('[') {
+            let length = self.length("an array")?;
+            t = self.check(H2Array::new(length, t))?;
+        }
+
+        Ok(t)
+    }
+
+    fn parse_struct(&mut self) -> SimpleResult<H2Type> {
+        let fields = self.parse_fields()?;
+
+        self.check(H2Struct::new(fields))
+    }
+
+    fn parse_union(&mut self) -> SimpleResult<H2Type> {
+        let members = self.parse_fields()?;
+
+        self.check(H2Union::new(members))
+    }
+
+    /// Parse `{ type name; type name; ... }`, for structs and unions.
+    fn parse_fields(&mut self) -> SimpleResult<Vec<(String, H2Type)>> {
+        self.expect('{')?;
+
+        let mut fields = vec![];
+        while !self.eat('}') {
+            let field_type = self.parse_type()?;
+            let field_name = self.word("a field name")?;
+            fields.push((field_name, field_type));
+
+            // The last field's `;` is optional
+            if !self.eat(';') && self.peek() != Some('}') {
+                return Err(self.error("expected ';' or '}'"));
+            }
+        }
+
+        Ok(fields)
+    }
+
+    fn finish(&mut self) -> SimpleResult<()> {
+        match self.peek() {
+            Some(c) => Err(self.error(&format!("unexpected '{}'", c))),
+            None    => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    use generic_number::{Context, HexFormatter};
+    use crate::Offset;
+
+    #[test]
+    fn test_parse() -> SimpleResult<()> {
+        let tests = vec![
+          // expression                                   description
+            ("u8",                                        "u8"),
+            ("u32",                                       "u32le"),
+            ("u32be",                                     "u32be"),
+            ("i128le",                                    "i128le"),
+            ("f64be",                                     "f64be"),
+            ("char<utf16be>",                             "char<utf16be>"),
+            ("ntstr(ascii)",                              "ntstring<ascii>"),
+            ("lpstr(u8,ascii)",                           "lpstring<u8, ascii>"),
+            ("lpstring<u16be, utf16 units>",              "lpstring<u16be, utf16le units>"),
+            ("str(utf8)[0x10]",                           "string<utf8>[16]"),
+            ("blob[4]",                                   "blob[4]"),
+            ("fourcc",                                    "fourcc"),
+            ("fourcc<TestStrings>",                       "fourcc<TestStrings>"),
+            ("ipv4<be>",                                  "ipv4<be>"),
+            ("uuid",                                      "uuid<le>"),
+            ("macaddr",                                   "macaddr"),
+            ("sleb128",                                   "sleb128"),
+            ("enum(u8, TestEnum)",                        "enum<u8, TestEnum>"),
+            ("bitmask<u32, TerrariaVisibility>",          "bitmask<u32le, TerrariaVisibility>"),
+            ("ptr(u64be)",                                "ptr<u64be>"),
+            ("ptr<u32, struct { u8 a; }>",                "ptr<u32le, struct { u8 a; }>"),
+            ("ptr<i16, u8[2], relative>",                 "ptr<i16le, u8[2], relative>"),
+            ("ptr<u32, ipv4<be>, base 0x40>",             "ptr<u32le, ipv4<be>, base 0x40>"),
+            ("offset(u32)",                               "offset<u32le>"),
+            ("u16[4]",                                    "u16le[4]"),
+            ("u8[2][3]",                                  "u8[2][3]"),
+            ("lparray(u32, struct { u8 a; })",            "lparray<u32le, struct { u8 a; }>"),
+            ("lparray<u8, lpstr<u8, ascii>>[2]",          "lparray<u8, lpstring<u8, ascii>>[2]"),
+            ("ntarray(u16, u8 = 0)",                      "ntarray<u16le, u8 = 0>"),
+            ("ntarray<struct { i32 x; }, i32 = -1>",      "ntarray<struct { i32le x; }, i32le = -1>"),
+            ("struct{u32 x; u32 y}",                      "struct { u32le x; u32le y; }"),
+            ("union { u32 i; f32be f; u8[4] b }",         "union { u32le i; f32be f; u8[4] b; }"),
+            ("  struct { rgb c; struct { u8 a; } s; }[2]", "struct { rgb c; struct { u8 a; } s; }[2]"),
+        ];
+
+        for (expression, description) in tests {
+            let t = H2Type::parse(expression)?;
+            assert_eq!(description, t.describe(), "parsing {}", expression);
+
+            // Descriptions parse back to the same type
+            assert_eq!(description, H2Type::parse(description)?.describe());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_with_defaults() -> SimpleResult<()> {
+        let data = b"\x00\x01\x00\x02".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let parser = H2TypeParser::new(Endian::Big, HexFormatter::pretty_integer());
+
+        let t = parser.parse("u16[2]")?;
+        assert_eq!("u16be[2]", t.describe());
+        assert_eq!("[ 0x0001, 0x0002 ]", t.to_display(offset)?);
+
+        // An explicit endian wins
+        assert_eq!("0x0100", parser.parse("u16le")?.to_display(offset)?);
+
+        // Offsets are shown however the renderer likes
+        let parser = H2TypeParser::new(Endian::Big, generic_number::DefaultFormatter::new_integer_auto(None, true));
+        assert_eq!("[ 1, 2 ]", parser.parse("u16[2]")?.to_display(offset)?);
+        assert_eq!("[ 0x1, 0x2 ]", parser.parse("offset<u16>[2]")?.to_display(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_at() -> SimpleResult<()> {
+        let parser = H2TypeParser::default();
+
+        let (t, offset) = parser.parse_at("u32le@0x10")?;
+        assert_eq!("u32le", t.describe());
+        assert_eq!(Some(16), offset);
+
+        let (t, offset) = parser.parse_at("struct { u8 a; } @ 8")?;
+        assert_eq!("struct { u8 a; }", t.describe());
+        assert_eq!(Some(8), offset);
+
+        let (_, offset) = parser.parse_at("u8")?;
+        assert_eq!(None, offset);
+
+        assert!(parser.parse_at("u8@").is_err());
+        assert!(parser.parse_at("u8@-1").is_err());
+        assert!(parser.parse_at("u8@x").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() -> SimpleResult<()> {
+        let tests = vec![
+            ("",                       "Couldn't parse '' at position 0: expected a type"),
+            ("u33",                    "Couldn't parse 'u33' at position 0: unknown type 'u33'"),
+            ("u8le",                   "Couldn't parse 'u8le' at position 0: unknown type 'u8le'"),
+            ("u8 u8",                  "Couldn't parse 'u8 u8' at position 3: unexpected 'u'"),
+            ("u8[",                    "Couldn't parse 'u8[' at position 3: expected a length"),
+            ("u8[2",                   "Couldn't parse 'u8[2' at position 4: expected ']'"),
+            ("u8[0]",                  "Couldn't parse 'u8[0]' at position 5: Arrays must be at least one element long"),
+            ("lpstr",                  "Couldn't parse 'lpstr' at position 5: 'lpstr' needs arguments, like lpstr<...>"),
+            ("lpstr(f32, ascii)",      "Couldn't parse 'lpstr(f32, ascii)' at position 6: 'f32' isn't an integer type"),
+            ("lpstr(u8, ebcdic)",      "Couldn't parse 'lpstr(u8, ebcdic)' at position 10: 'ebcdic' isn't a character encoding"),
+            ("str(ascii)",             "Couldn't parse 'str(ascii)' at position 10: 'str' needs a length, like str[4]"),
+            ("ipv4<me>",               "Couldn't parse 'ipv4<me>' at position 7: 'me' isn't an endian (le or be)"),
+            ("enum<u8, NotAnEnum>",    "Couldn't parse 'enum<u8, NotAnEnum>' at position 19: No such Enum: NotAnEnum"),
+            ("struct { u8 }",          "Couldn't parse 'struct { u8 }' at position 12: expected a field name"),
+            ("struct { u8 a u8 b }",   "Couldn't parse 'struct { u8 a u8 b }' at position 14: expected ';' or '}'"),
+        ];
+
+        for (expression, error) in tests {
+            assert_eq!(error, H2Type::parse(expression).unwrap_err().to_string(), "parsing {}", expression);
+        }
+
+        Ok(())
+    }
+}
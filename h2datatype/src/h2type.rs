@@ -1,11 +1,14 @@
 use serde::{Serialize, Deserialize};
 
-use simple_error::SimpleResult;
+use simple_error::{SimpleResult, bail};
+use std::collections::BTreeSet;
+use std::fmt;
 use std::ops::Range;
+use std::sync::Arc;
 
 use generic_number::{Integer, Float, Character};
 
-use crate::{H2TypeTrait, Offset, Alignment, ResolvedType};
+use crate::{H2TypeTrait, H2Unknown, Offset, Alignment, ResolvedType, FromResolved, H2DataReference};
 use crate::simple::*;
 use crate::simple::network::*;
 use crate::simple::numeric::*;
@@ -18,17 +21,21 @@ use crate::composite::*;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum H2Types {
     // Simple
-    //H2Pointer(H2Pointer),
+    H2Pointer(H2Pointer),
     Rgb(Rgb),
     H2Bitmask(H2Bitmask),
+    H2Bits(H2Bits),
     H2Enum(H2Enum),
     H2UUID(H2UUID),
+    H2FourCC(H2FourCC),
     H2Blob(H2Blob),
+    H2Marker(H2Marker),
 
     // Numeric
     H2Character(H2Character),
     H2Float(H2Float),
     H2Integer(H2Integer),
+    H2Leb128(H2Leb128),
 
     // Network
     IPv4(IPv4),
@@ -44,7 +51,70 @@ pub enum H2Types {
     // Composite
     H2Array(H2Array),
     H2Struct(H2Struct),
+    H2SparseStruct(H2SparseStruct),
+    LPArray(LPArray),
+    NTArray(NTArray),
+    H2Union(H2Union),
+    H2Bitfield(H2Bitfield),
+    H2Switch(H2Switch),
+    H2MessagePack(H2MessagePack),
+    H2Cbor(H2Cbor),
+
+    // Placeholder for types we can't load
+    H2Unknown(H2Unknown),
+}
+
+impl H2Types {
+    /// The name of the type, as it's stored in the serialized form.
+    ///
+    /// These are part of the saved format, so they must never change once
+    /// they exist!
+    pub fn type_name(&self) -> &str {
+        match self {
+            // Simple
+            Self::H2Pointer(_) => "H2Pointer",
+            Self::Rgb(_)       => "Rgb",
+            Self::H2Bitmask(_) => "H2Bitmask",
+            Self::H2Bits(_)    => "H2Bits",
+            Self::H2Enum(_)    => "H2Enum",
+            Self::H2UUID(_)    => "H2UUID",
+            Self::H2FourCC(_)  => "H2FourCC",
+            Self::H2Blob(_)    => "H2Blob",
+            Self::H2Marker(_)  => "H2Marker",
+
+            // Numeric
+            Self::H2Character(_) => "H2Character",
+            Self::H2Float(_)     => "H2Float",
+            Self::H2Integer(_)   => "H2Integer",
+            Self::H2Leb128(_)    => "H2Leb128",
+
+            // Network
+            Self::IPv4(_)        => "IPv4",
+            Self::IPv6(_)        => "IPv6",
+            Self::MacAddress(_)  => "MacAddress",
+            Self::MacAddress8(_) => "MacAddress8",
 
+            // Strings
+            Self::H2String(_) => "H2String",
+            Self::NTString(_) => "NTString",
+            Self::LPString(_) => "LPString",
+
+            // Composite
+            Self::H2Array(_)  => "H2Array",
+            Self::H2Struct(_) => "H2Struct",
+            Self::H2SparseStruct(_) => "H2SparseStruct",
+            Self::LPArray(_)        => "LPArray",
+            Self::NTArray(_)        => "NTArray",
+            Self::H2Union(_)        => "H2Union",
+            Self::H2Bitfield(_)     => "H2Bitfield",
+            Self::H2Switch(_)       => "H2Switch",
+            Self::H2MessagePack(_)  => "H2MessagePack",
+            Self::H2Cbor(_)         => "H2Cbor",
+
+            // Unknown types keep whatever name they were loaded with
+            Self::H2Unknown(t) => &t.type_name,
+        }
+    }
 }
 
 /// The core of this crate - defines any type of value abstractly.
@@ -63,34 +133,46 @@ pub enum H2Types {
 /// In terms of implementation, this basically passes everything through to
 /// [`H2TypeTrait`]. The biggest reason for having this layer above the trait
 /// is to store an alignment value.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Serialization is implemented by hand (see the `serialization` module) so
+/// the saved format is versioned and can survive new types being added.
+///
+/// The definition is shared, so cloning an `H2Type` - even a big struct - is
+/// cheap, and the clones (and every struct or array that contains them) use
+/// the same memory. Types can't be changed once they're created, so that's
+/// never visible.
+#[derive(Debug, Clone)]
 pub struct H2Type {
-    pub field: H2Types,
+    pub field: Arc<H2Types>,
     pub alignment: Alignment,
 }
 
 impl H2Type {
     pub fn new(alignment: Alignment, field: H2Types) -> Self {
         Self {
-            field: field,
+            field: Arc::new(field),
             alignment: alignment,
         }
     }
 
     fn field_type(&self) -> &dyn H2TypeTrait {
-        match &self.field {
+        match self.field.as_ref() {
             // Simple
-            //H2Types::H2Pointer(t) => t,
+            H2Types::H2Pointer(t) => t,
             H2Types::Rgb(t)       => t,
             H2Types::H2Bitmask(t) => t,
+            H2Types::H2Bits(t)    => t,
             H2Types::H2Enum(t)    => t,
             H2Types::H2UUID(t)    => t,
+            H2Types::H2FourCC(t)  => t,
             H2Types::H2Blob(t)    => t,
+            H2Types::H2Marker(t)  => t,
 
             // Numeric
             H2Types::H2Float(t)     => t,
             H2Types::H2Character(t) => t,
             H2Types::H2Integer(t)   => t,
+            H2Types::H2Leb128(t)    => t,
 
             // Network
             H2Types::IPv4(t)        => t,
@@ -101,11 +183,22 @@ impl H2Type {
             // Complex
             H2Types::H2Array(t)   => t,
             H2Types::H2Struct(t)  => t,
+            H2Types::H2SparseStruct(t) => t,
+            H2Types::LPArray(t)        => t,
+            H2Types::NTArray(t)        => t,
+            H2Types::H2Union(t)        => t,
+            H2Types::H2Bitfield(t)     => t,
+            H2Types::H2Switch(t)       => t,
+            H2Types::H2MessagePack(t)  => t,
+            H2Types::H2Cbor(t)         => t,
 
             // Strings
             H2Types::H2String(t)   => t,
             H2Types::NTString(t)  => t,
             H2Types::LPString(t)  => t,
+
+            // Unknown
+            H2Types::H2Unknown(t) => t,
         }
     }
 
@@ -114,6 +207,22 @@ impl H2Type {
         self.field_type().is_static()
     }
 
+    /// Describe the type definition as readable pseudo-C.
+    ///
+    /// For example, a struct might be described as
+    /// `struct { u32le x; u32le y; lpstring<u8, ascii> world; }`. Alignment,
+    /// if any, is appended to the type.
+    ///
+    /// This is also what's used by the [`fmt::Display`] implementation.
+    pub fn describe(&self) -> String {
+        match self.alignment {
+            Alignment::None      => self.field_type().describe(),
+            Alignment::Loose(a)  => format!("{} aligned({})", self.field_type().describe(), a),
+            Alignment::Strict(a) => format!("{} aligned_strict({})", self.field_type().describe(), a),
+            Alignment::Warn(a)   => format!("{} aligned_warn({})", self.field_type().describe(), a),
+        }
+    }
+
     /// Get the size of just the field - no alignment included.
     ///
     /// Note that if the type has children (such as a
@@ -153,13 +262,120 @@ impl H2Type {
         self.field_type().children(offset)
     }
 
+    /// Pick a case for every [`H2Switch`] on a variable in this type, based
+    /// on `variables` (usually the project's).
+    ///
+    /// The result has no variable switches left in it - switches on fields
+    /// and tags are picked from the data, so they stay. A switch with
+    /// alignment overrides the alignment of whichever case it picks.
+    pub fn bind(&self, variables: &H2Variables) -> SimpleResult<H2Type> {
+        // A switch on a variable is replaced by its case, so it can't be
+        // handled by the trait
+        if let H2Types::H2Switch(t) = self.field.as_ref() {
+            if matches!(t.source(), SwitchSource::Variable) {
+                let mut selected = t.select(variables)?.bind(variables)?;
+
+                if !matches!(self.alignment, Alignment::None) {
+                    selected.alignment = self.alignment;
+                }
+
+                return Ok(selected);
+            }
+        }
+
+        Ok(match self.field_type().bind(variables)? {
+            Some(field) => H2Type::new(self.alignment, field),
+            None        => self.clone(),
+        })
+    }
+
+    /// Get everything from [`h2data`] that this type (or any type inside it)
+    /// uses to display values.
+    ///
+    /// Values displayed by a type that doesn't reference something don't
+    /// change when it does.
+    pub fn data_references(&self) -> BTreeSet<H2DataReference> {
+        let mut references = BTreeSet::new();
+        self.field_type().data_references(&mut references);
+
+        references
+    }
+
+    /// Add this type's references to `references` - see
+    /// [`H2Type::data_references`].
+    pub fn add_data_references(&self, references: &mut BTreeSet<H2DataReference>) {
+        self.field_type().data_references(references);
+    }
+
+    /// Precompute what can be known about this type without any data, so
+    /// resolving it is faster.
+    ///
+    /// Right now, that means structs whose fields all have a fixed size (and
+    /// aren't strictly aligned) work out where each field goes once, instead
+    /// of asking every field for its size each time they're resolved. Types
+    /// inside arrays and other structs are frozen too. This is worth doing
+    /// for types that are resolved over and over, like the items in a big
+    /// array; the result acts exactly the same as the original.
+    ///
+    /// The precomputed data isn't saved, so a type that's loaded has to be
+    /// frozen again.
+    pub fn freeze(&self) -> H2Type {
+        match self.field_type().freeze() {
+            Some(field) => H2Type::new(self.alignment, field),
+            None        => self.clone(),
+        }
+    }
+
     /// Resolve this type into a concrete type.
     ///
     /// Once a type is resolved, the size, range, data, string value, and so on
     /// are "written in stone", so to speak, which means they no longer need to
     /// be calculated.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(self, offset, name), fields(type_name = self.field.type_name(), position = offset.position(), size = tracing::field::Empty, children = tracing::field::Empty)))]
     pub fn resolve(&self, offset: Offset, name: Option<String>) -> SimpleResult<ResolvedType> {
-        self.field_type().resolve(offset, self.alignment, name)
+        let resolved = self.field_type().resolve(offset, self.alignment, name)?;
+
+        #[cfg(feature = "tracing")]
+        tracing::Span::current()
+            .record("size", &resolved.aligned_size())
+            .record("children", &resolved.children.len());
+
+        Ok(resolved)
+    }
+
+    /// Resolve this type, and read the result into a Rust value - see
+    /// [`crate::layout`].
+    pub fn resolve_into<T: FromResolved>(&self, offset: Offset) -> SimpleResult<T> {
+        T::from_resolved(&self.resolve(offset, None)?)
+    }
+
+    /// Find a field within this type, by path.
+    ///
+    /// A path is a list of field names separated by `.`, like `health.max`.
+    /// Children without a name (like array elements) are found by their
+    /// index instead, like `items.3`. An empty path is this type.
+    ///
+    /// Returns the field's type, and the offset where it starts.
+    pub fn field<'a>(&self, offset: Offset<'a>, path: &str) -> SimpleResult<(H2Type, Offset<'a>)> {
+        let mut current = (self.clone(), offset);
+
+        for part in path.split('.').filter(|part| !part.is_empty()) {
+            let children = current.0.field_type().children_with_range(current.1)?;
+
+            let found = children.into_iter().enumerate().find(|(index, (_, name, _))| {
+                match name {
+                    Some(name) => name == part,
+                    None       => index.to_string() == part,
+                }
+            });
+
+            current = match found {
+                Some((_, (range, _, child))) => (child, offset.at(range.start)),
+                None                         => bail!("No field {:?} in path {:?}", part, path),
+            };
+        }
+
+        Ok(current)
     }
 
     /// Get a user-consumeable string
@@ -177,6 +393,18 @@ impl H2Type {
         self.field_type().to_string(offset)
     }
 
+    /// Is this value the address of something else? See
+    /// [`crate::simple::H2Pointer`].
+    pub fn is_pointer(&self) -> bool {
+        self.field_type().is_pointer()
+    }
+
+    /// Are the children different interpretations of the same bytes? See
+    /// [`crate::composite::H2Union`].
+    pub fn children_overlap(&self) -> bool {
+        self.field_type().children_overlap()
+    }
+
     pub fn can_be_integer(&self) -> bool {
         self.field_type().can_be_integer()
     }
@@ -200,4 +428,21 @@ impl H2Type {
     pub fn to_character(&self, offset: Offset) -> SimpleResult<Character> {
         self.field_type().to_character(offset)
     }
+
+    /// Can a new value be written over this type?
+    pub fn can_encode(&self) -> bool {
+        self.field_type().can_encode()
+    }
+
+    /// Convert a value, as a user would type it, into the bytes this type
+    /// would read back as that value.
+    pub fn encode(&self, offset: Offset, value: &str) -> SimpleResult<Vec<u8>> {
+        self.field_type().encode(offset, value)
+    }
+}
+
+impl fmt::Display for H2Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.describe())
+    }
 }
@@ -1,7 +1,9 @@
 use simple_error::{bail, SimpleResult};
+use std::collections::BTreeSet;
 use std::ops::Range;
 
-use crate::{Alignment, Offset, ResolvedType, H2Type};
+use crate::{Alignment, Offset, ResolvedType, H2Type, H2Types, H2DataReference};
+use crate::composite::H2Variables;
 use generic_number::{Integer, Float, Character};
 
 /// The core trait that makes a type into a type. All types must implement this.
@@ -30,6 +32,15 @@ pub trait H2TypeTrait {
     /// anyways (for now)!
     fn is_static(&self) -> bool;
 
+    /// Describe the type definition (not the data!) as pseudo-C.
+    ///
+    /// This is mostly for humans - logs, diffs, documentation, etc. Simple
+    /// types are typically their name with any parameters in angle brackets
+    /// (`enum<u32le, TerrariaItem>`), and composite types describe their
+    /// children. Most descriptions can be parsed back into the same type with
+    /// [`crate::H2TypeParser`].
+    fn describe(&self) -> String;
+
     /// The actual size, in bytes, of a type. This does not include alignment
     /// or padding.
     ///
@@ -92,6 +103,15 @@ pub trait H2TypeTrait {
         Ok(vec![])
     }
 
+    /// Resolve what the value points to, if it points to something (see
+    /// [`crate::simple::H2Pointer`]).
+    ///
+    /// Errors don't stop the value itself from resolving - they become a
+    /// warning instead, since pointers to nowhere are common in real data.
+    fn target(&self, _offset: Offset) -> SimpleResult<Option<ResolvedType>> {
+        Ok(None)
+    }
+
     /// Get children of the type - that is, other types that make up this type.
     ///
     /// Some types have no children - we refer to those as
@@ -131,11 +151,43 @@ pub trait H2TypeTrait {
         }).collect::<SimpleResult<Vec<_>>>()
     }
 
+    /// Bind any [`crate::composite::H2Switch`] inside this type to
+    /// `variables` - see [`H2Type::bind`].
+    ///
+    /// Only types that store other types need to implement this. `None` means
+    /// there's nothing to bind, and the type can be used as-is.
+    fn bind(&self, _variables: &H2Variables) -> SimpleResult<Option<H2Types>> {
+        Ok(None)
+    }
+
+    /// Precompute whatever can be known ahead of time - see
+    /// [`H2Type::freeze`].
+    ///
+    /// Only types that store other types (or that can precompute something
+    /// themselves) need to implement this. `None` means there's nothing to
+    /// do, and the type can be used as-is.
+    fn freeze(&self) -> Option<H2Types> {
+        None
+    }
+
+    /// Add everything from [`h2data`] that this type uses to display values
+    /// to `references` - see [`H2Type::data_references`].
+    ///
+    /// Types that use [`h2data`] or store other types need to implement
+    /// this.
+    fn data_references(&self, _references: &mut BTreeSet<H2DataReference>) {
+    }
+
     /// Create a [`ResolvedType`] from this [`H2Type`] and context.
     ///
     /// A resolved type has all the values calculated, and is therefore very
     /// quick to use.
     fn resolve(&self, offset: Offset, alignment: Alignment, field_name: Option<String>) -> SimpleResult<ResolvedType> {
+        let (target, target_warning) = match self.target(offset) {
+            Ok(target) => (target, None),
+            Err(e)     => (None, Some(format!("Couldn't resolve what the value points to: {}", e))),
+        };
+
         Ok(ResolvedType {
             actual_range: self.range(offset, Alignment::None)?,
             aligned_range: self.range(offset, alignment)?,
@@ -156,6 +208,14 @@ pub trait H2TypeTrait {
             as_integer: self.to_integer(offset).ok(),
             as_float: self.to_float(offset).ok(),
             as_character: self.to_character(offset).ok(),
+
+            warnings: alignment.warning(offset.position()).into_iter().chain(target_warning).collect(),
+            is_padding: false,
+            is_pointer: self.is_pointer(),
+            children_overlap: self.children_overlap(),
+            bit_range: self.bit_range(),
+            enum_name: self.enum_name(),
+            target: target.map(Box::new),
         })
     }
 
@@ -167,10 +227,38 @@ pub trait H2TypeTrait {
     }
 
     /// Convert to a [`String`], if it's sensible for this type.
+    ///
+    /// For string types, this is the decoded string, exactly as it is in the
+    /// data - unlike [`#to_display`], the renderer isn't used, so nothing is
+    /// escaped or replaced.
     fn to_string(&self, _offset: Offset) -> SimpleResult<String> {
         bail!("This type cannot be converted to a string");
     }
 
+    /// Is the value the address of something else (see
+    /// [`crate::simple::H2Pointer`])? Pointers must also be integers.
+    fn is_pointer(&self) -> bool {
+        false
+    }
+
+    /// Do the children all cover the same bytes, as different ways of
+    /// looking at them (see [`crate::composite::H2Union`])?
+    fn children_overlap(&self) -> bool {
+        false
+    }
+
+    /// The bits that hold the value, for values that aren't a whole number
+    /// of bytes (see [`crate::simple::H2Bits`]).
+    fn bit_range(&self) -> Option<Range<u64>> {
+        None
+    }
+
+    /// The [`h2data`] enum the value is named from, if it is (see
+    /// [`crate::simple::H2Enum`]).
+    fn enum_name(&self) -> Option<String> {
+        None
+    }
+
     fn can_be_integer(&self) -> bool {
         false
     }
@@ -194,4 +282,18 @@ pub trait H2TypeTrait {
     fn to_character(&self, _offset: Offset) -> SimpleResult<Character> {
         bail!("This type cannot be converted to a character");
     }
+
+    /// Can a new value be written over this type (see [`#encode`])?
+    fn can_encode(&self) -> bool {
+        false
+    }
+
+    /// Convert a value - written the way a user would type it, like `500` or
+    /// `0x1f4` - into the bytes that this type would read back as that value.
+    ///
+    /// The offset is where the value is going to be written; it only matters
+    /// for types whose size depends on the data.
+    fn encode(&self, _offset: Offset, _value: &str) -> SimpleResult<Vec<u8>> {
+        bail!("This type cannot be written");
+    }
 }
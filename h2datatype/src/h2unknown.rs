@@ -0,0 +1,50 @@
+use serde::{Serialize, Deserialize};
+use simple_error::{bail, SimpleResult};
+
+use crate::{H2TypeTrait, Offset};
+
+/// A placeholder for a type that this version of the crate doesn't know.
+///
+/// When a serialized [`crate::H2Type`] names a type that doesn't exist (most
+/// likely because it was saved by a newer version), the definition is kept
+/// as-is instead of failing to load. It'll be written back out unchanged when
+/// the type is serialized again, so nothing is lost by round-tripping a
+/// project through an older version.
+///
+/// An unknown type can't actually be used to read data - every attempt will
+/// return an error.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct H2Unknown {
+    /// The name of the type, exactly as it was serialized
+    pub type_name: String,
+
+    /// The serialized definition, preserved verbatim
+    pub definition: serde_json::Value,
+}
+
+impl H2Unknown {
+    pub fn new(type_name: &str, definition: serde_json::Value) -> Self {
+        Self {
+            type_name: type_name.to_string(),
+            definition: definition,
+        }
+    }
+}
+
+impl H2TypeTrait for H2Unknown {
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    fn describe(&self) -> String {
+        format!("unknown<{}>", self.type_name)
+    }
+
+    fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
+        bail!("Unknown type can't be used: {}", self.type_name);
+    }
+
+    fn to_display(&self, _offset: Offset) -> SimpleResult<String> {
+        bail!("Unknown type can't be used: {}", self.type_name);
+    }
+}
@@ -0,0 +1,219 @@
+//! Read values straight into Rust types.
+//!
+//! Resolving an [`H2Type`] gives a [`ResolvedType`], which is great for
+//! displaying but clumsy to compute with - every field is looked up by name and
+//! converted by hand. When the layout is known ahead of time, a Rust struct can
+//! describe it instead: `#[derive(H2Layout)]` builds the matching
+//! [`crate::composite::H2Struct`] and fills the struct in from what it
+//! resolves to.
+//!
+//! [`FromResolved`] is implemented for the primitive integers, floats, `bool`,
+//! `char`, `String`, and `Vec`s of any of those, so they can be used as field
+//! types as long as the `#[h2(...)]` type produces that kind of value.
+
+use std::convert::TryFrom;
+
+use simple_error::{SimpleResult, bail};
+
+use generic_number::{Float, Integer};
+
+use crate::{H2Type, Offset, ResolvedType};
+
+// The derive macro refers to this, so callers don't need simple_error
+#[doc(hidden)]
+pub use simple_error::SimpleError;
+
+/// A Rust value that can be read out of a [`ResolvedType`].
+pub trait FromResolved: Sized {
+    fn from_resolved(resolved: &ResolvedType) -> SimpleResult<Self>;
+}
+
+/// A Rust struct that describes a binary layout - use
+/// `#[derive(H2Layout)]` rather than implementing this by hand.
+pub trait H2Layout: FromResolved {
+    /// The [`crate::composite::H2Struct`] that matches this struct.
+    fn h2type() -> SimpleResult<H2Type>;
+}
+
+/// Resolve `T`'s layout at `offset`, and read it into a `T`.
+pub fn resolve_into<T: H2Layout>(offset: Offset) -> SimpleResult<T> {
+    T::from_resolved(&T::h2type()?.resolve(offset, None)?)
+}
+
+/// Find the child of a resolved struct with the given name.
+#[doc(hidden)]
+pub fn field<'a>(resolved: &'a ResolvedType, name: &str) -> SimpleResult<&'a ResolvedType> {
+    match resolved.children.iter().find(|child| child.field_name.as_deref() == Some(name)) {
+        Some(child) => Ok(child),
+        None        => bail!("Resolved value {} has no field named {}", resolved.display, name),
+    }
+}
+
+fn integer(resolved: &ResolvedType) -> SimpleResult<Integer> {
+    match resolved.as_integer {
+        Some(i) => Ok(i),
+        None    => bail!("Value {} is not an integer", resolved.display),
+    }
+}
+
+macro_rules! from_resolved_integer {
+    ($($t:ty),*) => {
+        $(
+            impl FromResolved for $t {
+                fn from_resolved(resolved: &ResolvedType) -> SimpleResult<Self> {
+                    let i = integer(resolved)?;
+
+                    let converted = match i {
+                        Integer::U8(n)   => <$t>::try_from(n).ok(),
+                        Integer::U16(n)  => <$t>::try_from(n).ok(),
+                        Integer::U32(n)  => <$t>::try_from(n).ok(),
+                        Integer::U64(n)  => <$t>::try_from(n).ok(),
+                        Integer::U128(n) => <$t>::try_from(n).ok(),
+                        Integer::I8(n)   => <$t>::try_from(n).ok(),
+                        Integer::I16(n)  => <$t>::try_from(n).ok(),
+                        Integer::I32(n)  => <$t>::try_from(n).ok(),
+                        Integer::I64(n)  => <$t>::try_from(n).ok(),
+                        Integer::I128(n) => <$t>::try_from(n).ok(),
+                    };
+
+                    match converted {
+                        Some(n) => Ok(n),
+                        None    => bail!("Value {} doesn't fit in a {}", i, stringify!($t)),
+                    }
+                }
+            }
+        )*
+    }
+}
+
+from_resolved_integer!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+impl FromResolved for bool {
+    fn from_resolved(resolved: &ResolvedType) -> SimpleResult<Self> {
+        Ok(u128::from_resolved(resolved)? != 0)
+    }
+}
+
+impl FromResolved for f64 {
+    fn from_resolved(resolved: &ResolvedType) -> SimpleResult<Self> {
+        match resolved.as_float {
+            Some(Float::F32(f)) => Ok(f as f64),
+            Some(Float::F64(f)) => Ok(f),
+            None                => bail!("Value {} is not a float", resolved.display),
+        }
+    }
+}
+
+impl FromResolved for f32 {
+    fn from_resolved(resolved: &ResolvedType) -> SimpleResult<Self> {
+        Ok(f64::from_resolved(resolved)? as f32)
+    }
+}
+
+impl FromResolved for char {
+    fn from_resolved(resolved: &ResolvedType) -> SimpleResult<Self> {
+        match resolved.as_character {
+            Some(c) => Ok(c.as_char()),
+            None    => bail!("Value {} is not a character", resolved.display),
+        }
+    }
+}
+
+impl FromResolved for String {
+    fn from_resolved(resolved: &ResolvedType) -> SimpleResult<Self> {
+        match &resolved.as_string {
+            Some(s) => Ok(s.clone()),
+            None    => bail!("Value {} is not a string", resolved.display),
+        }
+    }
+}
+
+/// Each child (for example, each element of an array) becomes an element.
+impl<T: FromResolved> FromResolved for Vec<T> {
+    fn from_resolved(resolved: &ResolvedType) -> SimpleResult<Self> {
+        resolved.children.iter().map(|child| T::from_resolved(child)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    use generic_number::{Context, IntegerReader, FloatReader, CharacterReader, CharacterFormatter, DefaultFormatter, Endian};
+
+    use crate::H2Layout;
+    use crate::composite::H2Array;
+    use crate::simple::numeric::{H2Integer, H2Float};
+    use crate::simple::string::{H2String, LPString};
+
+    #[derive(H2Layout, Debug, PartialEq)]
+    struct Point {
+        #[h2(H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer()))]
+        x: u16,
+
+        #[h2(H2Integer::new(IntegerReader::I8, DefaultFormatter::new_integer()))]
+        y: i64,
+    }
+
+    #[derive(H2Layout, Debug, PartialEq)]
+    pub struct Header {
+        #[h2(H2String::new(4, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?)]
+        magic: String,
+
+        /// Nested structs don't need a type
+        origin: Point,
+
+        #[h2(H2Array::new(2, H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer()))?)]
+        pub(crate) flags: Vec<bool>,
+
+        #[h2(H2Float::new(FloatReader::F32(Endian::Little), DefaultFormatter::new_float()))]
+        scale: f32,
+
+        #[h2(LPString::new(IntegerReader::U8, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?)]
+        r#type: String,
+    }
+
+    #[test]
+    fn test_resolve_into() -> SimpleResult<()> {
+        let data = b"H2GB\x01\x02\xff\x00\x01\x00\x00\xc0\x3f\x03abc".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let header: Header = resolve_into(offset)?;
+        assert_eq!(Header {
+            magic: "H2GB".to_string(),
+            origin: Point { x: 0x0102, y: -1 },
+            flags: vec![false, true],
+            scale: 1.5,
+            r#type: "abc".to_string(),
+        }, header);
+
+        // The layout is a regular H2Struct, with the same field names
+        let t = Header::h2type()?;
+        assert_eq!(17, t.actual_size(offset)?);
+        assert_eq!(vec![Some("magic".to_string()), Some("origin".to_string()), Some("flags".to_string()), Some("scale".to_string()), Some("type".to_string())],
+            t.children(offset)?.into_iter().map(|(name, _)| name).collect::<Vec<_>>());
+
+        // Reading it through an H2Type works too
+        assert_eq!(Point { x: 0x4832, y: 0x47 }, Point::h2type()?.resolve_into(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conversions() -> SimpleResult<()> {
+        let data = b"\xff\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer());
+        assert_eq!(0xffff, t.resolve_into::<u32>(offset)?);
+        assert!(t.resolve_into::<u8>(offset).is_err());
+        assert!(t.resolve_into::<String>(offset).is_err());
+
+        let t = H2Integer::new(IntegerReader::I16(Endian::Big), DefaultFormatter::new_integer());
+        assert_eq!(-1, t.resolve_into::<i32>(offset)?);
+        assert!(t.resolve_into::<u64>(offset).is_err());
+
+        Ok(())
+    }
+}
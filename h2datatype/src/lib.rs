@@ -30,6 +30,10 @@
 //! They can be fetched instantly, and have no chance of returning an error or
 //! changing - the field has been resolved.
 //!
+//! Types that display values from [`h2data`] - enums and bitmasks - can list
+//! what they use ([`H2Type::data_references`]), so when that data changes,
+//! only the values that use it have to be displayed again.
+//!
 //! ## Simple types
 //!
 //! A simple type, as mentioned above, is defined as a type that's not made up
@@ -38,6 +42,19 @@
 //!
 //! See the various classes in [`crate::simple`] for examples!
 //!
+//! One odd simple type is [`simple::H2Marker`], which takes up no space at
+//! all. It's used to label a position, usually inside a struct.
+//!
+//! A [`simple::H2Pointer`] is an integer that holds an address. It displays
+//! like any other integer, but its [`ResolvedType`] is marked
+//! ([`ResolvedType::is_pointer`]) so the address can be shown by name.
+//!
+//! A pointer can also have a target type. The value is counted from a
+//! [`simple::PointerBase`] - the start of the buffer, the pointer itself, or
+//! some other position - and the target is resolved there, so
+//! [`ResolvedType::target`] has both ends of the pointer. That's what file
+//! formats with offset tables need.
+//!
 //! ## Composite types
 //!
 //! A composite type is made up of other types. For example, a
@@ -46,6 +63,50 @@
 //! all recursive - an array can contain a struct which can contain an array and
 //! so on, for as long as you like.
 //!
+//! A [`composite::LPArray`] is an array that starts with its length, like
+//! an [`simple::string::LPString`] - the number of elements is read from the
+//! data, instead of being part of the type.
+//!
+//! A [`composite::NTArray`] is the same idea as a
+//! [`simple::string::NTString`] - elements are read until a terminator (like an
+//! `i32` that's `-1`) turns up.
+//!
+//! A [`composite::H2SparseStruct`] is a struct where each field has its own
+//! offset, and anything between the fields is left as padding. That's handy
+//! for structures that are only partly understood.
+//!
+//! A [`composite::H2Union`] is the other way around - every member starts at
+//! the same place, and is a different way of reading the same bytes. Its
+//! [`ResolvedType`] has all of them, with
+//! [`ResolvedType::children_overlap`] set.
+//!
+//! A [`composite::H2Bitfield`] packs several small values into one integer,
+//! like a C bitfield - 3 bits of flags and 5 bits of type in a single byte.
+//! Each field is a [`simple::H2Bits`], and its [`ResolvedType::bit_range`]
+//! says which bits it uses.
+//!
+//! For big definitions, [`composite::H2StructBuilder`] and
+//! [`composite::H2ArrayBuilder`] check each field as it's added, and report
+//! every problem - with the field's name - at the end, instead of needing an
+//! `unwrap()` on every constructor.
+//!
+//! Types can also be written as text - [`H2TypeParser`] turns expressions
+//! like `lpstring<u8, ascii>` or `struct { u32 x; u32 y; }` into types, in the
+//! same syntax [`H2Type::describe`] writes.
+//!
+//! A [`composite::H2Switch`] picks one of several types based on a variable,
+//! like the version of a format. Variables aren't part of the data, so types
+//! with switches are *bound* to a set of variables ([`H2Type::bind`]) before
+//! they're resolved.
+//!
+//! A switch can also be driven by the data itself - by a tag that's read
+//! right before the value (like the type of a TLV record or a PNG chunk), or by
+//! an earlier integer field of the same struct.
+//!
+//! [`composite::H2MessagePack`] and [`composite::H2Cbor`] decode
+//! self-describing formats without a schema - their children are whatever
+//! arrays and maps the data contains.
+//!
 //! ### String types
 //!
 //! A string type, which are defined in [`simple::string`], are a special
@@ -67,6 +128,16 @@
 //! That means if the alignment value is 4, all types must start on 0, 4, 8, ...
 //! and will be padded to end on 4, 8, 12, ...
 //!
+//! Finally, [`Alignment::Warn`] pads like [`Alignment::Loose`], but a value
+//! that doesn't start on a boundary gets a warning in its [`ResolvedType`]
+//! (see [`ResolvedType::warnings`]) - misaligned data often means the wrong
+//! offset was picked.
+//!
+//! A [`ResolvedType`] has both ranges - `actual_range` is the value, and
+//! `aligned_range` includes the padding. To draw the padding, use
+//! [`ResolvedType::children_with_padding`], which adds a node for every byte
+//! between the children that isn't part of a value.
+//!
 //! # Examples
 //!
 //! ## Reading a 16-bit decimal value, signed
@@ -202,6 +273,35 @@
 //! // Even though it takes up the extra space, the values don't change
 //! assert_eq!("[ \"hi\", \"bye\", \"test\" ]", t.to_display(offset).unwrap());
 //! ```
+//!
+//! ## Typed access
+//!
+//! When a layout is known ahead of time, a Rust struct can describe it, and
+//! be filled in directly - see [`layout`] for details.
+//!
+//! ```
+//! use h2datatype::*;
+//! use h2datatype::simple::numeric::*;
+//! use generic_number::*;
+//!
+//! #[derive(H2Layout, Debug, PartialEq)]
+//! struct Point {
+//!     #[h2(H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer()))]
+//!     x: u8,
+//!
+//!     #[h2(H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer()))]
+//!     y: u16,
+//! }
+//!
+//! let data = b"\x01\x00\x02".to_vec();
+//! let offset = Offset::Dynamic(Context::new(&data));
+//!
+//! let point: Point = resolve_into(offset).unwrap();
+//! assert_eq!(Point { x: 1, y: 2 }, point);
+//! ```
+
+// Lets the derive macro's `::h2datatype` paths work inside this crate, too
+extern crate self as h2datatype;
 
 mod alignment;
 pub use alignment::Alignment;
@@ -212,11 +312,27 @@ pub use resolved_type::ResolvedType;
 mod offset;
 pub use offset::Offset;
 
+mod data_reference;
+pub use data_reference::H2DataReference;
+
 mod h2typetrait;
 pub use h2typetrait::H2TypeTrait;
 
 mod h2type;
 pub use h2type::{H2Types, H2Type};
 
+mod h2unknown;
+pub use h2unknown::H2Unknown;
+
+mod expression;
+pub use expression::H2TypeParser;
+
+mod serialization;
+pub use serialization::H2TYPE_FORMAT_VERSION;
+
+pub mod layout;
+pub use layout::{FromResolved, H2Layout, resolve_into};
+pub use h2datatype_derive::H2Layout;
+
 pub mod simple;
 pub mod composite;
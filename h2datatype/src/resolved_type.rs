@@ -23,10 +23,51 @@ pub struct ResolvedType {
     pub children: Vec<ResolvedType>,
     pub related: Vec<(u64, H2Type)>,
 
+    /// The value as a string, if it can be one - for string types, this is
+    /// the decoded string, without the quotes and escaping of `display`
     pub as_string:    Option<String>,
     pub as_integer:   Option<Integer>,
     pub as_float:     Option<Float>,
     pub as_character: Option<Character>,
+
+    /// Problems that didn't stop the type from resolving, but might mean it's
+    /// in the wrong place - see [`crate::Alignment::Warn`]
+    #[serde(default)]
+    pub warnings: Vec<String>,
+
+    /// Is this a padding node from [`ResolvedType::children_with_padding`]?
+    /// Resolving never sets this.
+    #[serde(default)]
+    pub is_padding: bool,
+
+    /// Is the value an address (see [`crate::simple::H2Pointer`])? If it is,
+    /// the address is in `as_integer`.
+    #[serde(default)]
+    pub is_pointer: bool,
+
+    /// Are the children different interpretations of the same bytes (see
+    /// [`crate::composite::H2Union`])? If they are, they all start at the
+    /// same place and should be shown side by side.
+    #[serde(default)]
+    pub children_overlap: bool,
+
+    /// For values that only use some of the bits in `actual_range` (see
+    /// [`crate::composite::H2Bitfield`]), which bits they are. The bits are
+    /// numbered in the value's [`generic_number::BitOrder`], within the
+    /// integer that holds them.
+    #[serde(default)]
+    pub bit_range: Option<Range<u64>>,
+
+    /// For values named from an [`h2data`] enum (see
+    /// [`crate::simple::H2Enum`]), which enum it is. The value is in
+    /// `as_integer`, so the name can be looked up again if the enum changes.
+    #[serde(default)]
+    pub enum_name: Option<String>,
+
+    /// What the value points to, resolved where it points (see
+    /// [`crate::simple::H2Pointer`]). The address is `target.actual_range.start`.
+    #[serde(default)]
+    pub target: Option<Box<ResolvedType>>,
 }
 
 impl ResolvedType {
@@ -37,6 +78,79 @@ impl ResolvedType {
     pub fn aligned_size(&self) -> u64 {
         self.aligned_range.end - self.aligned_range.start
     }
+
+    /// The padding after the value - the bytes in `aligned_range` that
+    /// aren't in `actual_range` - if there is any.
+    pub fn padding(&self) -> Option<Range<u64>> {
+        match self.aligned_range.end > self.actual_range.end {
+            true  => Some(self.actual_range.end..self.aligned_range.end),
+            false => None,
+        }
+    }
+
+    /// A node that stands for padding, which has no value.
+    fn padding_node(range: Range<u64>) -> Self {
+        Self {
+            actual_range: range.clone(),
+            aligned_range: range.clone(),
+            field_name: None,
+            display: format!("<padding: {} bytes>", range.end - range.start),
+            children: vec![],
+            related: vec![],
+            as_string: None,
+            as_integer: None,
+            as_float: None,
+            as_character: None,
+            warnings: vec![],
+            is_padding: true,
+            is_pointer: false,
+            children_overlap: false,
+            bit_range: None,
+            enum_name: None,
+            target: None,
+        }
+    }
+
+    /// The children, with a padding node (see [`ResolvedType::is_padding`])
+    /// for every byte that doesn't belong to a value.
+    ///
+    /// That's the padding after each child (see [`ResolvedType::padding`]),
+    /// and any gap between children, like the space between the elements of
+    /// a strided array. A child's value is its `actual_range`, so between
+    /// those and the padding nodes, every byte in `actual_range` is either a
+    /// value or padding.
+    ///
+    /// Padding after this value itself isn't included, since it's outside
+    /// `actual_range` - it's part of the parent's children.
+    pub fn children_with_padding(&self) -> Vec<ResolvedType> {
+        let mut out: Vec<ResolvedType> = vec![];
+        let mut position = self.actual_range.start;
+
+        for child in &self.children {
+            if child.aligned_range.start > position {
+                out.push(Self::padding_node(position..child.aligned_range.start));
+            }
+
+            out.push(child.clone());
+            if let Some(padding) = child.padding() {
+                out.push(Self::padding_node(padding));
+            }
+
+            position = position.max(child.aligned_range.end);
+        }
+
+        // Anything left over, as long as there were children to begin with
+        if !self.children.is_empty() && position < self.actual_range.end {
+            out.push(Self::padding_node(position..self.actual_range.end));
+        }
+
+        out
+    }
+
+    /// Warnings for this value and all of its children.
+    pub fn all_warnings(&self) -> Vec<&str> {
+        self.warnings.iter().map(|w| w.as_str()).chain(self.children.iter().flat_map(|c| c.all_warnings())).collect()
+    }
 }
 
 impl fmt::Display for ResolvedType {
@@ -44,3 +158,72 @@ impl fmt::Display for ResolvedType {
         write!(f, "{}", self.display)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+
+    use generic_number::{Context, Endian, IntegerReader, DefaultFormatter};
+    use crate::{Alignment, Offset};
+    use crate::composite::{H2Array, H2Struct};
+    use crate::simple::numeric::H2Integer;
+
+    /// Summarize nodes as (the bytes they cover, is_padding)
+    fn ranges(nodes: &[ResolvedType]) -> Vec<(Range<u64>, bool)> {
+        nodes.iter().map(|n| (n.actual_range.clone(), n.is_padding)).collect()
+    }
+
+    #[test]
+    fn test_padding() -> SimpleResult<()> {
+        let data = b"\x01PPP\x02\x03PP\x04\x00PP".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Struct::new(vec![
+            ("a".to_string(), H2Integer::new_aligned(Alignment::Loose(4), IntegerReader::U8, DefaultFormatter::new_integer())),
+            ("b".to_string(), H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+            ("c".to_string(), H2Integer::new_aligned(Alignment::Loose(3), IntegerReader::U8, DefaultFormatter::new_integer())),
+            ("d".to_string(), H2Integer::new_aligned(Alignment::Loose(4), IntegerReader::U16(Endian::Little), DefaultFormatter::new_integer())),
+        ])?;
+        let resolved = t.resolve(offset, None)?;
+
+        // The children themselves don't change
+        assert_eq!(4, resolved.children.len());
+        assert!(resolved.children.iter().all(|c| !c.is_padding));
+
+        assert_eq!(vec![
+            (0..1, false), (1..4, true),
+            (4..5, false),
+            (5..6, false), (6..8, true),
+            (8..10, false), (10..12, true),
+        ], ranges(&resolved.children_with_padding()));
+
+        assert_eq!(Some(1..4), resolved.children[0].padding());
+        assert_eq!(None, resolved.children[1].padding());
+        assert_eq!("<padding: 3 bytes>", resolved.children_with_padding()[1].display);
+
+        // No children, no padding nodes
+        assert_eq!(0, resolved.children[0].children_with_padding().len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_padding_gaps() -> SimpleResult<()> {
+        let data = b"\x01P\x02P\x03".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        // The gaps between the elements of a strided array are padding too
+        let t = H2Array::new_strided(3, 2, H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer()))?;
+        let resolved = t.resolve(offset, None)?;
+
+        assert_eq!(vec![
+            (0..1, false), (1..2, true),
+            (2..3, false), (3..4, true),
+            (4..5, false),
+        ], ranges(&resolved.children_with_padding()));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,451 @@
+//! Stable, versioned serialization for [`H2Type`].
+//!
+//! Every serialized [`H2Type`] looks like this (shown as JSON):
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "alignment": "None",
+//!   "type": "H2Integer",
+//!   "definition": { ... }
+//! }
+//! ```
+//!
+//! The `type` field is the name from [`H2Types::type_name`], and the
+//! `definition` is whatever that type serializes to. Because the name is
+//! stored as a plain string instead of an enum tag, a type that we don't know
+//! about (because it was added in a newer version) can be loaded as an
+//! [`H2Unknown`], which keeps the definition around and writes it back out
+//! unchanged.
+//!
+//! Preserving unknown definitions requires a self-describing format (JSON,
+//! YAML, RON, etc); known types work with any format. The same goes for
+//! reading a `definition` that comes before its `type`, which happens when
+//! the keys get sorted (as `serde_json::Value` does).
+//!
+//! Types saved before versioning was added (`{ field: ..., alignment: ... }`)
+//! can still be loaded.
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::ser::SerializeStruct;
+use std::fmt;
+
+use crate::{Alignment, H2Type, H2Types, H2Unknown};
+use crate::simple::*;
+use crate::simple::network::*;
+use crate::simple::numeric::*;
+use crate::simple::string::*;
+use crate::composite::*;
+
+/// The current version of the serialized [`H2Type`] format.
+///
+/// This must be incremented whenever the format changes in a way that older
+/// versions can't read.
+pub const H2TYPE_FORMAT_VERSION: u32 = 1;
+
+const FIELDS: &[&str] = &["version", "alignment", "type", "definition"];
+
+impl Serialize for H2Type {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("H2Type", FIELDS.len())?;
+
+        s.serialize_field("version",   &H2TYPE_FORMAT_VERSION)?;
+        s.serialize_field("alignment", &self.alignment)?;
+        s.serialize_field("type",      self.field.type_name())?;
+
+        match self.field.as_ref() {
+            // Simple
+            H2Types::H2Pointer(t) => s.serialize_field("definition", t)?,
+            H2Types::Rgb(t)       => s.serialize_field("definition", t)?,
+            H2Types::H2Bitmask(t) => s.serialize_field("definition", t)?,
+            H2Types::H2Bits(t)    => s.serialize_field("definition", t)?,
+            H2Types::H2Enum(t)    => s.serialize_field("definition", t)?,
+            H2Types::H2UUID(t)    => s.serialize_field("definition", t)?,
+            H2Types::H2FourCC(t)  => s.serialize_field("definition", t)?,
+            H2Types::H2Blob(t)    => s.serialize_field("definition", t)?,
+            H2Types::H2Marker(t)  => s.serialize_field("definition", t)?,
+
+            // Numeric
+            H2Types::H2Character(t) => s.serialize_field("definition", t)?,
+            H2Types::H2Float(t)     => s.serialize_field("definition", t)?,
+            H2Types::H2Integer(t)   => s.serialize_field("definition", t)?,
+            H2Types::H2Leb128(t)    => s.serialize_field("definition", t)?,
+
+            // Network
+            H2Types::IPv4(t)        => s.serialize_field("definition", t)?,
+            H2Types::IPv6(t)        => s.serialize_field("definition", t)?,
+            H2Types::MacAddress(t)  => s.serialize_field("definition", t)?,
+            H2Types::MacAddress8(t) => s.serialize_field("definition", t)?,
+
+            // Strings
+            H2Types::H2String(t) => s.serialize_field("definition", t)?,
+            H2Types::NTString(t) => s.serialize_field("definition", t)?,
+            H2Types::LPString(t) => s.serialize_field("definition", t)?,
+
+            // Composite
+            H2Types::H2Array(t)  => s.serialize_field("definition", t)?,
+            H2Types::H2Struct(t) => s.serialize_field("definition", t)?,
+            H2Types::H2SparseStruct(t) => s.serialize_field("definition", t)?,
+            H2Types::LPArray(t)        => s.serialize_field("definition", t)?,
+            H2Types::NTArray(t)        => s.serialize_field("definition", t)?,
+            H2Types::H2Union(t)        => s.serialize_field("definition", t)?,
+            H2Types::H2Bitfield(t)     => s.serialize_field("definition", t)?,
+            H2Types::H2Switch(t)       => s.serialize_field("definition", t)?,
+            H2Types::H2MessagePack(t)  => s.serialize_field("definition", t)?,
+            H2Types::H2Cbor(t)         => s.serialize_field("definition", t)?,
+
+            // Write unknown definitions back exactly as we found them
+            H2Types::H2Unknown(t) => s.serialize_field("definition", &t.definition)?,
+        }
+
+        s.end()
+    }
+}
+
+/// Deserializes a `definition` once we know which type it is.
+struct Definition<'a>(&'a str);
+
+impl<'a, 'de> DeserializeSeed<'de> for Definition<'a> {
+    type Value = H2Types;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<H2Types, D::Error> {
+        Ok(match self.0 {
+            // Simple
+            "H2Pointer" => H2Types::H2Pointer(H2Pointer::deserialize(d)?),
+            "Rgb"       => H2Types::Rgb(Rgb::deserialize(d)?),
+            "H2Bitmask" => H2Types::H2Bitmask(H2Bitmask::deserialize(d)?),
+            "H2Bits"    => H2Types::H2Bits(H2Bits::deserialize(d)?),
+            "H2Enum"    => H2Types::H2Enum(H2Enum::deserialize(d)?),
+            "H2UUID"    => H2Types::H2UUID(H2UUID::deserialize(d)?),
+            "H2FourCC"  => H2Types::H2FourCC(H2FourCC::deserialize(d)?),
+            "H2Blob"    => H2Types::H2Blob(H2Blob::deserialize(d)?),
+            "H2Marker"  => H2Types::H2Marker(H2Marker::deserialize(d)?),
+
+            // Numeric
+            "H2Character" => H2Types::H2Character(H2Character::deserialize(d)?),
+            "H2Float"     => H2Types::H2Float(H2Float::deserialize(d)?),
+            "H2Integer"   => H2Types::H2Integer(H2Integer::deserialize(d)?),
+            "H2Leb128"    => H2Types::H2Leb128(H2Leb128::deserialize(d)?),
+
+            // Network
+            "IPv4"        => H2Types::IPv4(IPv4::deserialize(d)?),
+            "IPv6"        => H2Types::IPv6(IPv6::deserialize(d)?),
+            "MacAddress"  => H2Types::MacAddress(MacAddress::deserialize(d)?),
+            "MacAddress8" => H2Types::MacAddress8(MacAddress8::deserialize(d)?),
+
+            // Strings
+            "H2String" => H2Types::H2String(H2String::deserialize(d)?),
+            "NTString" => H2Types::NTString(NTString::deserialize(d)?),
+            "LPString" => H2Types::LPString(LPString::deserialize(d)?),
+
+            // Composite
+            "H2Array"  => H2Types::H2Array(H2Array::deserialize(d)?),
+            "H2Struct" => H2Types::H2Struct(H2Struct::deserialize(d)?),
+            "H2SparseStruct" => H2Types::H2SparseStruct(H2SparseStruct::deserialize(d)?),
+            "LPArray"        => H2Types::LPArray(LPArray::deserialize(d)?),
+            "NTArray"        => H2Types::NTArray(NTArray::deserialize(d)?),
+            "H2Union"        => H2Types::H2Union(H2Union::deserialize(d)?),
+            "H2Bitfield"     => H2Types::H2Bitfield(H2Bitfield::deserialize(d)?),
+            "H2Switch"       => H2Types::H2Switch(H2Switch::deserialize(d)?),
+            "H2MessagePack"  => H2Types::H2MessagePack(H2MessagePack::deserialize(d)?),
+            "H2Cbor"         => H2Types::H2Cbor(H2Cbor::deserialize(d)?),
+
+            // Anything else is preserved as-is
+            other => H2Types::H2Unknown(H2Unknown::new(other, serde_json::Value::deserialize(d)?)),
+        })
+    }
+}
+
+struct H2TypeVisitor;
+
+impl H2TypeVisitor {
+    /// Make sure we can read the given version.
+    ///
+    /// Newer versions are accepted - unknown types will be preserved - but
+    /// anything that claims to be from before versioning existed is wrong.
+    fn check_version<E: de::Error>(version: u32) -> Result<(), E> {
+        if version == 0 {
+            return Err(E::custom("Invalid H2Type format version: 0"));
+        }
+
+        Ok(())
+    }
+}
+
+impl<'de> Visitor<'de> for H2TypeVisitor {
+    type Value = H2Type;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a serialized H2Type")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<H2Type, A::Error> {
+        let mut version: Option<u32> = None;
+        let mut alignment: Option<Alignment> = None;
+        let mut type_name: Option<String> = None;
+        let mut field: Option<H2Types> = None;
+
+        // A definition that shows up before its type (sorted keys do that)
+        // is held onto until we know what it is
+        let mut buffered: Option<serde_json::Value> = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "version" => {
+                    let v: u32 = map.next_value()?;
+                    Self::check_version(v)?;
+                    version = Some(v);
+                },
+                "alignment" => alignment = Some(map.next_value()?),
+                "type"      => type_name = Some(map.next_value()?),
+                "definition" => match &type_name {
+                    Some(name) => field = Some(map.next_value_seed(Definition(name))?),
+                    None       => buffered = Some(map.next_value()?),
+                },
+
+                // The format used before versioning was added
+                "field" => field = Some(map.next_value()?),
+
+                // Ignore fields we don't know about, in case they're added later
+                _ => { map.next_value::<IgnoredAny>()?; },
+            }
+        }
+
+        // The old format has no version, but needs a field; the new format
+        // needs both a version and a type
+        if version.is_some() && type_name.is_none() {
+            return Err(de::Error::missing_field("type"));
+        }
+
+        if let (Some(definition), Some(name)) = (buffered, &type_name) {
+            field = Some(Definition(name).deserialize(definition).map_err(de::Error::custom)?);
+        }
+
+        Ok(H2Type::new(
+            alignment.ok_or_else(|| de::Error::missing_field("alignment"))?,
+            field.ok_or_else(|| de::Error::missing_field("definition"))?,
+        ))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<H2Type, A::Error> {
+        let version: u32 = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        Self::check_version(version)?;
+
+        let alignment: Alignment = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let type_name: String = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        let field = seq.next_element_seed(Definition(&type_name))?.ok_or_else(|| de::Error::invalid_length(3, &self))?;
+
+        Ok(H2Type::new(alignment, field))
+    }
+}
+
+impl<'de> Deserialize<'de> for H2Type {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<H2Type, D::Error> {
+        deserializer.deserialize_struct("H2Type", FIELDS, H2TypeVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+
+    use generic_number::{BitOrder, Context, Endian, IntegerReader, FloatReader, CharacterReader, DefaultFormatter, HexFormatter, CharacterFormatter, ScientificFormatter};
+    use crate::Offset;
+
+    /// Serialize, deserialize, and serialize again - the two serialized
+    /// versions should be identical.
+    fn round_trip(t: &H2Type) -> SimpleResult<H2Type> {
+        let serialized = serde_json::to_string(t).unwrap();
+        let deserialized: H2Type = serde_json::from_str(&serialized).unwrap();
+
+        assert_eq!(serialized, serde_json::to_string(&deserialized).unwrap());
+
+        Ok(deserialized)
+    }
+
+    fn all_types() -> SimpleResult<Vec<H2Type>> {
+        Ok(vec![
+            // Simple
+            H2Pointer::new(IntegerReader::U32(Endian::Little), HexFormatter::pretty_integer()),
+            Rgb::new(false),
+            H2Bitmask::new(IntegerReader::U8, "TerrariaVisibility", true)?,
+            H2Bits::new(IntegerReader::U8, 2..5, BitOrder::Lsb0, HexFormatter::pretty_integer())?,
+            H2Enum::new(IntegerReader::U32(Endian::Little), "TestEnum")?,
+            H2UUID::new(Endian::Big),
+            H2FourCC::new(),
+            H2Blob::new(4)?,
+            H2Marker::new("Marker"),
+
+            // Numeric
+            H2Character::new(CharacterReader::UTF8, CharacterFormatter::pretty_character()),
+            H2Float::new(FloatReader::F64(Endian::Big), ScientificFormatter::pretty_float()),
+            H2Integer::new_aligned(Alignment::Loose(4), IntegerReader::U16(Endian::Big), HexFormatter::pretty_integer()),
+            H2Leb128::new(true, DefaultFormatter::new_integer()),
+
+            // Network
+            IPv4::new(Endian::Big),
+            IPv6::new(Endian::Little),
+            MacAddress::new(),
+            MacAddress8::new(),
+
+            // Strings
+            H2String::new(4, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?,
+            NTString::new(CharacterReader::UTF8, DefaultFormatter::new_character()),
+            LPString::new(IntegerReader::U8, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?,
+            LPString::new_counted(IntegerReader::U32(Endian::Little), LengthUnit::CodeUnits, CharacterReader::UTF16(Endian::Little), CharacterFormatter::pretty_str_character())?,
+
+            // Composite
+            H2Array::new_aligned(Alignment::Strict(8), 4, IPv4::new(Endian::Big))?,
+            H2Struct::new(vec![
+                ("a".to_string(), H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+                ("b".to_string(), H2Array::new(2, MacAddress::new())?),
+            ])?,
+            H2SparseStruct::new(Some(8), vec![
+                (4, "a".to_string(), H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+            ])?,
+            LPArray::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer(), NTString::new(CharacterReader::ASCII, CharacterFormatter::pretty_str_character()))?,
+            NTArray::new(IPv4::new(Endian::Big), IntegerReader::I32(Endian::Little), DefaultFormatter::new_integer(), -1)?,
+            H2Union::new(vec![
+                ("a".to_string(), H2Integer::new(IntegerReader::U32(Endian::Big), DefaultFormatter::new_integer())),
+                ("b".to_string(), IPv4::new(Endian::Big)),
+            ])?,
+            H2Bitfield::new(IntegerReader::U16(Endian::Big), BitOrder::Msb0, vec![
+                ("a".to_string(), 3, DefaultFormatter::new_integer()),
+                ("b".to_string(), 13, DefaultFormatter::new_integer()),
+            ])?,
+            H2Switch::new("version", vec![
+                (1..2, H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+            ], Some(IPv4::new(Endian::Big)))?,
+            H2Switch::new_tagged(IntegerReader::U16(Endian::Little), HexFormatter::pretty_integer(), "type", vec![
+                (1..2, H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+            ], None)?,
+            H2MessagePack::new(),
+            H2Cbor::new(),
+        ])
+    }
+
+    #[test]
+    fn test_round_trip_all_types() -> SimpleResult<()> {
+        for t in all_types()? {
+            let name = t.field.type_name().to_string();
+            let deserialized = round_trip(&t)?;
+
+            assert_eq!(name, deserialized.field.type_name());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip_still_works() -> SimpleResult<()> {
+        let data = b"\x7f\x00\x00\x01\x01\x02\x03\x04".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = round_trip(&H2Array::new(2, IPv4::new(Endian::Big))?)?;
+        assert_eq!("[ 127.0.0.1, 1.2.3.4 ]", t.to_display(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_is_written() -> SimpleResult<()> {
+        let serialized: serde_json::Value = serde_json::to_value(&IPv4::new(Endian::Big)).unwrap();
+
+        assert_eq!(serde_json::json!(H2TYPE_FORMAT_VERSION), serialized["version"]);
+        assert_eq!(serde_json::json!("IPv4"), serialized["type"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_type_is_preserved() -> SimpleResult<()> {
+        let original = serde_json::json!({
+            "version": 1,
+            "alignment": "None",
+            "type": "H2Struct",
+            "definition": {
+                "fields": [
+                    ["known", { "version": 1, "alignment": "None", "type": "IPv4", "definition": { "endian": "Big" } }],
+                    ["unknown", { "version": 1, "alignment": "None", "type": "H2FromTheFuture", "definition": { "some": ["new", "data"] } }],
+                ],
+            },
+        });
+
+        let t: H2Type = serde_json::from_value(original.clone()).unwrap();
+
+        // The unknown type is loaded, but can't be used
+        let data = b"\x7f\x00\x00\x01".to_vec();
+        assert!(t.to_display(Offset::Dynamic(Context::new(&data))).is_err());
+
+        // Writing it back out should be identical
+        assert_eq!(original, serde_json::to_value(&t).unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_definition_before_type() -> SimpleResult<()> {
+        let text = r#"{ "definition": { "endian": "Big" }, "alignment": "None", "version": 1, "type": "IPv4" }"#;
+        let t: H2Type = serde_json::from_str(text).unwrap();
+
+        let data = b"\x7f\x00\x00\x01".to_vec();
+        assert_eq!("127.0.0.1", t.to_display(Offset::Dynamic(Context::new(&data)))?);
+
+        // Every type, with the definition first (printing a serde_json::Value
+        // sorts the keys of anything nested, so they're out of order too).
+        // Values can't hold an i128, so they're made from text
+        let to_value = |t: &H2Type| -> serde_json::Value {
+            serde_json::from_str(&serde_json::to_string(t).unwrap()).unwrap()
+        };
+
+        for t in all_types()? {
+            let value = to_value(&t);
+            let text = format!(
+                r#"{{ "definition": {}, "type": {}, "alignment": {}, "version": {} }}"#,
+                value["definition"], value["type"], value["alignment"], value["version"],
+            );
+
+            let loaded: H2Type = serde_json::from_str(&text).unwrap();
+            assert_eq!(t.field.type_name(), loaded.field.type_name());
+            assert_eq!(value, to_value(&loaded));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_format() -> SimpleResult<()> {
+        let legacy = serde_json::json!({
+            "field": { "IPv4": { "endian": "Big" } },
+            "alignment": "None",
+        });
+
+        let t: H2Type = serde_json::from_value(legacy).unwrap();
+
+        let data = b"\x7f\x00\x00\x01".to_vec();
+        assert_eq!("127.0.0.1", t.to_display(Offset::Dynamic(Context::new(&data)))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_version() -> SimpleResult<()> {
+        let bad = serde_json::json!({
+            "version": 0,
+            "alignment": "None",
+            "type": "IPv4",
+            "definition": { "endian": "Big" },
+        });
+        assert!(serde_json::from_value::<H2Type>(bad).is_err());
+
+        let missing_type = serde_json::json!({
+            "version": 1,
+            "alignment": "None",
+        });
+        assert!(serde_json::from_value::<H2Type>(missing_type).is_err());
+
+        Ok(())
+    }
+}
@@ -1,11 +1,17 @@
+use std::collections::BTreeSet;
+
 use serde::{Serialize, Deserialize};
 
 use simple_error::{SimpleResult, bail};
 
-use h2data::{bitmask_exists, from_bitmask_str};
-use generic_number::{IntegerReader, Integer};
+use h2data::{bitmask_handle, BitmaskHandle, MAX_BITMASK_BITS};
+use generic_number::{BitOrder, Context, IntegerReader, Integer};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset, H2DataReference};
 
-use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+fn default_words() -> usize {
+    1
+}
 
 /// Defines a numerical value.
 ///
@@ -21,34 +27,90 @@ pub struct H2Bitmask {
 
     bitmask_type: String,
     show_negative: bool,
+
+    /// How many `reader`-sized words make up the bitmask.
+    #[serde(default = "default_words")]
+    words: usize,
+
+    /// Which bit is bit 0 - the words are read in order, and the first word
+    /// holds the lowest-numbered bits either way.
+    #[serde(default)]
+    bit_order: BitOrder,
+
+    /// The bitmask from [`h2data`], looked up when the type is created (see
+    /// [`crate::simple::H2Enum`]).
+    #[serde(skip)]
+    handle: Option<BitmaskHandle>,
 }
 
 impl H2Bitmask {
     pub fn new_aligned(alignment: Alignment, reader: IntegerReader, bitmask_type: &str, show_negative: bool) -> SimpleResult<H2Type> {
+        Self::new_multiword_aligned(alignment, reader, 1, BitOrder::Lsb0, bitmask_type, show_negative)
+    }
+
+    pub fn new(reader: IntegerReader, bitmask_type: &str, show_negative: bool) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, reader, bitmask_type, show_negative)
+    }
+
+    /// Create a bitmask that's `words` integers wide, for flag fields wider
+    /// than any one integer (a 128-bit mask could be 2 `u64`s or 16 `u8`s).
+    ///
+    /// With [`BitOrder::Lsb0`], bit 0 is the least significant bit of the
+    /// first word (so a little-endian mask numbers bits like one big
+    /// integer); with [`BitOrder::Msb0`], it's the most significant bit of the
+    /// first word, like a bit string read left to right.
+    pub fn new_multiword_aligned(alignment: Alignment, reader: IntegerReader, words: usize, bit_order: BitOrder, bitmask_type: &str, show_negative: bool) -> SimpleResult<H2Type> {
         if !reader.can_be_usize() {
             bail!("Bitmask types must be compatible with usize values");
         }
 
-        // Make sure the bitmask type exists
-        if !bitmask_exists(bitmask_type) {
-            bail!("No such Bitmask: {}", bitmask_type);
+        if words == 0 {
+            bail!("Bitmask must be at least one word long");
+        }
+
+        if words * reader.size() * 8 > MAX_BITMASK_BITS {
+            bail!("Bitmask is too wide: {} words of {} bits (max is {} bits)", words, reader.size() * 8, MAX_BITMASK_BITS);
         }
 
+        // Make sure the bitmask type exists
+        let handle = match bitmask_handle(bitmask_type) {
+            Ok(handle) => handle,
+            Err(_)     => bail!("No such Bitmask: {}", bitmask_type),
+        };
+
         Ok(H2Type::new(alignment, H2Types::H2Bitmask(Self {
             reader: reader,
             bitmask_type: bitmask_type.to_string(),
             show_negative: show_negative,
+            words: words,
+            bit_order: bit_order,
+            handle: Some(handle),
         })))
-
     }
 
-    pub fn new(reader: IntegerReader, bitmask_type: &str, show_negative: bool) -> SimpleResult<H2Type> {
-        Self::new_aligned(Alignment::None, reader, bitmask_type, show_negative)
+    pub fn new_multiword(reader: IntegerReader, words: usize, bit_order: BitOrder, bitmask_type: &str, show_negative: bool) -> SimpleResult<H2Type> {
+        Self::new_multiword_aligned(Alignment::None, reader, words, bit_order, bitmask_type, show_negative)
     }
 
-    fn render(&self, number: usize) -> SimpleResult<String> {
-        let out = from_bitmask_str(&self.bitmask_type, number, self.show_negative)?;
-        Ok(out.join(" | "))
+    fn render(&self, context: Context) -> SimpleResult<String> {
+        let word_size = self.reader.size();
+        let word_bits = word_size * 8;
+
+        let words = (0..self.words).map(|i| {
+            self.reader.read(context.at(context.position() + (i * word_size) as u64))?.as_usize()
+        }).collect::<SimpleResult<Vec<usize>>>()?;
+
+        let is_set = |bit: usize| {
+            let word = words[bit / word_bits];
+            (word >> self.bit_order.shift(bit % word_bits, word_bits)) & 1 == 1
+        };
+
+        let handle = match self.handle {
+            Some(handle) => handle,
+            None         => bitmask_handle(&self.bitmask_type)?,
+        };
+
+        Ok(handle.strings_wide(self.words * word_bits, is_set, self.show_negative).join(" | "))
     }
 }
 
@@ -57,24 +119,44 @@ impl H2TypeTrait for H2Bitmask {
         true
     }
 
+    fn describe(&self) -> String {
+        let reader = match self.words {
+            1     => self.reader.to_string(),
+            words => format!("{}[{}]", self.reader, words),
+        };
+
+        // LSB0 is the usual, so it's left out
+        match self.bit_order {
+            BitOrder::Lsb0 => format!("bitmask<{}, {}>", reader, self.bitmask_type),
+            bit_order      => format!("bitmask<{}, {}, {}>", reader, self.bitmask_type, bit_order),
+        }
+    }
+
     fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
-        Ok(self.reader.size() as u64)
+        Ok((self.reader.size() * self.words) as u64)
+    }
+
+    fn data_references(&self, references: &mut BTreeSet<H2DataReference>) {
+        references.insert(H2DataReference::Bitmask(self.bitmask_type.clone()));
     }
 
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
         match offset {
             Offset::Static(_) => Ok("Bitmask".to_string()),
-            Offset::Dynamic(context) => {
-                self.render(self.reader.read(context)?.as_usize()?)
-            }
+            Offset::Dynamic(context) => self.render(context),
         }
     }
 
     fn can_be_integer(&self) -> bool {
-        true
+        // A multi-word bitmask can be wider than any integer
+        self.words == 1
     }
 
     fn to_integer(&self, offset: Offset) -> SimpleResult<Integer> {
+        if self.words != 1 {
+            bail!("A bitmask {} words wide can't be used as an integer", self.words);
+        }
+
         self.reader.read(offset.get_dynamic()?)
     }
 }
@@ -120,4 +202,44 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_multiword_bitmask() -> SimpleResult<()> {
+        // 128 bits, as two little-endian u64s
+        let test_buffer = b"\x01\x00\x00\x00\x00\x00\x00\x00\x02\x00\x00\x00\x00\x00\x00\x80".to_vec();
+        let offset = Offset::Dynamic(Context::new(&test_buffer));
+
+        let t = H2Bitmask::new_multiword(IntegerReader::U64(Endian::Little), 2, BitOrder::Lsb0, "TerrariaVisibility", false)?;
+        assert_eq!(16, t.actual_size(offset.at(0))?);
+        assert_eq!("bitmask<u64le[2], TerrariaVisibility>", t.describe());
+        assert_eq!("HIDE_SLOT_HEAD | Unknown_bit65 | Unknown_bit127", t.to_display(offset.at(0))?);
+        assert!(!t.can_be_integer());
+        assert!(t.to_integer(offset.at(0)).is_err());
+
+        // The same bytes, numbered from the top of each word
+        let t = H2Bitmask::new_multiword(IntegerReader::U64(Endian::Little), 2, BitOrder::Msb0, "TerrariaVisibility", false)?;
+        assert_eq!("bitmask<u64le[2], TerrariaVisibility, msb0>", t.describe());
+        assert_eq!("Unknown_0x8000000000000000 | Unknown_bit64 | Unknown_bit126", t.to_display(offset.at(0))?);
+
+        // A bit string, one byte at a time
+        let test_buffer = b"\xc0\x01".to_vec();
+        let offset = Offset::Dynamic(Context::new(&test_buffer));
+
+        let t = H2Bitmask::new_multiword(IntegerReader::U8, 2, BitOrder::Msb0, "TerrariaVisibility", true)?;
+        assert_eq!("HIDE_SLOT_HEAD | HIDE_SLOT_BODY | ~HIDE_SLOT_LEGS | ~HIDE_SLOT_ACCESSORY1 | ~HIDE_SLOT_ACCESSORY2 | ~HIDE_SLOT_ACCESSORY3 | ~HIDE_SLOT_ACCESSORY4 | ~HIDE_SLOT_ACCESSORY5 | ~HIDE_SLOT_ACCESSORY6 | ~HIDE_SLOT_ACCESSORY8 | Unknown_0x8000", t.to_display(offset.at(0))?);
+
+        // A single register from a datasheet, numbered from the top - the
+        // numbering agrees with reading the bits one at a time
+        let t = H2Bitmask::new_multiword(IntegerReader::U8, 1, BitOrder::Msb0, "TerrariaVisibility", false)?;
+        assert_eq!("bitmask<u8, TerrariaVisibility, msb0>", t.describe());
+        assert_eq!("HIDE_SLOT_HEAD | HIDE_SLOT_BODY", t.to_display(offset.at(0))?);
+        assert!(Context::new(&test_buffer).read_bit(0, BitOrder::Msb0)?);
+        assert!(Context::new(&test_buffer).read_bit(1, BitOrder::Msb0)?);
+
+        // Too narrow or too wide
+        assert!(H2Bitmask::new_multiword(IntegerReader::U8, 0, BitOrder::Lsb0, "TerrariaVisibility", false).is_err());
+        assert!(H2Bitmask::new_multiword(IntegerReader::U64(Endian::Little), 65, BitOrder::Lsb0, "TerrariaVisibility", false).is_err());
+
+        Ok(())
+    }
 }
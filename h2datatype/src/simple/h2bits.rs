@@ -0,0 +1,161 @@
+use std::ops::Range;
+
+use serde::{Serialize, Deserialize};
+
+use simple_error::{SimpleResult, bail};
+use generic_number::{BitOrder, Context, Integer, IntegerReader, IntegerRenderer};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+
+/// Defines a value that's only some of the bits of an integer.
+///
+/// The integer (the *container*) is read with `reader`, then bits
+/// `first..(first + width)` are pulled out (numbered with `bit_order`) and
+/// displayed with `renderer`. The value takes up the whole container, since
+/// that's the smallest thing that can be addressed, but its
+/// [`crate::ResolvedType::bit_range`] says which bits it really uses.
+///
+/// These are normally created by a [`crate::composite::H2Bitfield`], which
+/// packs several of them into the same container.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct H2Bits {
+    reader: IntegerReader,
+    first: u64,
+    width: u64,
+    bit_order: BitOrder,
+    renderer: IntegerRenderer,
+}
+
+impl H2Bits {
+    pub fn new_aligned(alignment: Alignment, reader: IntegerReader, bits: Range<u64>, bit_order: BitOrder, renderer: IntegerRenderer) -> SimpleResult<H2Type> {
+        if !reader.can_be_usize() {
+            bail!("Bit fields must be read from an unsigned integer that fits in a usize");
+        }
+
+        let container_bits = (reader.size() * 8) as u64;
+        if bits.start >= bits.end || bits.end > container_bits {
+            bail!("Bits {}..{} don't fit in a {}-bit integer", bits.start, bits.end, container_bits);
+        }
+
+        Ok(H2Type::new(alignment, H2Types::H2Bits(Self {
+            reader: reader,
+            first: bits.start,
+            width: bits.end - bits.start,
+            bit_order: bit_order,
+            renderer: renderer,
+        })))
+    }
+
+    pub fn new(reader: IntegerReader, bits: Range<u64>, bit_order: BitOrder, renderer: IntegerRenderer) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, reader, bits, bit_order, renderer)
+    }
+
+    fn read(&self, context: Context) -> SimpleResult<Integer> {
+        let container = self.reader.read(context)?.as_usize()? as u64;
+        let container_bits = (self.reader.size() * 8) as u64;
+
+        // Shift the lowest bit of the value down to bit 0
+        let shift = match self.bit_order {
+            BitOrder::Lsb0 => self.first,
+            BitOrder::Msb0 => container_bits - self.first - self.width,
+        };
+
+        let value = match self.width {
+            64    => container,
+            width => (container >> shift) & ((1 << width) - 1),
+        };
+
+        // Use the smallest type that fits, so renderers that pad (like hex)
+        // don't pad to the container's size
+        Ok(match self.width {
+            0..=8   => Integer::from(value as u8),
+            9..=16  => Integer::from(value as u16),
+            17..=32 => Integer::from(value as u32),
+            _       => Integer::from(value),
+        })
+    }
+}
+
+impl H2TypeTrait for H2Bits {
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn describe(&self) -> String {
+        // LSB0 is the usual, so it's left out
+        match self.bit_order {
+            BitOrder::Lsb0 => format!("bits<{}, {}..{}>", self.reader, self.first, self.first + self.width),
+            bit_order      => format!("bits<{}, {}..{}, {}>", self.reader, self.first, self.first + self.width, bit_order),
+        }
+    }
+
+    fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
+        Ok(self.reader.size() as u64)
+    }
+
+    fn bit_range(&self) -> Option<Range<u64>> {
+        Some(self.first..(self.first + self.width))
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        match offset {
+            Offset::Static(_)        => Ok("Integer".to_string()),
+            Offset::Dynamic(context) => Ok(self.renderer.render(self.read(context)?)),
+        }
+    }
+
+    fn can_be_integer(&self) -> bool {
+        true
+    }
+
+    fn to_integer(&self, offset: Offset) -> SimpleResult<Integer> {
+        self.read(offset.get_dynamic()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+    use generic_number::{Endian, DefaultFormatter, HexFormatter};
+
+    #[test]
+    fn test_bits() -> SimpleResult<()> {
+        // 1011 0110
+        let data = b"\xb6\x12\x34".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Bits::new(IntegerReader::U8, 0..3, BitOrder::Lsb0, DefaultFormatter::new_integer())?;
+        assert_eq!("bits<u8, 0..3>", t.describe());
+        assert_eq!(1, t.actual_size(offset)?);
+        assert_eq!("6", t.to_display(offset)?);
+
+        let t = H2Bits::new(IntegerReader::U8, 3..8, BitOrder::Lsb0, DefaultFormatter::new_integer())?;
+        assert_eq!("22", t.to_display(offset)?);
+
+        // Counting from the top, the first 3 bits are 101
+        let t = H2Bits::new(IntegerReader::U8, 0..3, BitOrder::Msb0, DefaultFormatter::new_integer())?;
+        assert_eq!("bits<u8, 0..3, msb0>", t.describe());
+        assert_eq!("5", t.to_display(offset)?);
+
+        // Values can cross byte boundaries, and are as small as they can be
+        let t = H2Bits::new(IntegerReader::U16(Endian::Big), 4..12, BitOrder::Lsb0, HexFormatter::pretty_integer())?;
+        assert_eq!("0x23", t.to_display(offset.at(1))?);
+
+        let r = t.resolve(offset.at(1), None)?;
+        assert_eq!(1..3, r.actual_range);
+        assert_eq!(Some(4..12), r.bit_range);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_bits() -> SimpleResult<()> {
+        assert!(H2Bits::new(IntegerReader::U8, 0..9, BitOrder::Lsb0, DefaultFormatter::new_integer()).is_err());
+        assert!(H2Bits::new(IntegerReader::U8, 3..3, BitOrder::Lsb0, DefaultFormatter::new_integer()).is_err());
+        assert!(H2Bits::new(IntegerReader::I8, 0..3, BitOrder::Lsb0, DefaultFormatter::new_integer()).is_err());
+
+        Ok(())
+    }
+}
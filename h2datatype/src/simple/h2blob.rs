@@ -36,6 +36,10 @@ impl H2TypeTrait for H2Blob {
         true
     }
 
+    fn describe(&self) -> String {
+        format!("blob[{}]", self.length)
+    }
+
     fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
         Ok(self.length)
     }
@@ -1,11 +1,13 @@
+use std::collections::BTreeSet;
+
 use serde::{Serialize, Deserialize};
 
 use simple_error::{SimpleResult, bail};
 
-use h2data::{enum_exists, from_enum};
+use h2data::{enum_exists, enum_handle, from_enum, EnumHandle};
 use generic_number::{IntegerReader, Integer};
 
-use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset, H2DataReference};
 
 /// Defines a numerical value.
 ///
@@ -20,6 +22,12 @@ pub struct H2Enum {
     reader: IntegerReader,
 
     enum_type: String,
+
+    /// The enum from [`h2data`], looked up when the type is created so
+    /// resolving doesn't have to find it by name every time. It isn't saved;
+    /// types that are loaded look the enum up by name instead.
+    #[serde(skip)]
+    handle: Option<EnumHandle>,
 }
 
 impl H2Enum {
@@ -29,13 +37,15 @@ impl H2Enum {
         }
 
         // Make sure the enum type exists
-        if !enum_exists(enum_type) {
-            bail!("No such Enum: {}", enum_type);
-        }
+        let handle = match enum_handle(enum_type) {
+            Ok(handle) => handle,
+            Err(_)     => bail!("No such Enum: {}", enum_type),
+        };
 
         Ok(H2Type::new(alignment, H2Types::H2Enum(Self {
             reader: reader,
             enum_type: enum_type.to_string(),
+            handle: Some(handle),
         })))
 
     }
@@ -44,9 +54,33 @@ impl H2Enum {
         Self::new_aligned(Alignment::None, reader, enum_type)
     }
 
+    /// Like [`H2Enum::new`], but the enum doesn't have to exist in
+    /// [`h2data`] - for enums defined somewhere else, like in a project.
+    ///
+    /// Values from an enum that [`h2data`] doesn't know about are displayed
+    /// as unknown (`Name::Unknown_0x1`), so whatever defines the enum needs
+    /// to update the display after resolving.
+    pub fn new_unchecked(reader: IntegerReader, enum_type: &str) -> SimpleResult<H2Type> {
+        if !reader.can_be_usize() {
+            bail!("Enum types must be compatible with u64 values");
+        }
+
+        Ok(H2Type::new(Alignment::None, H2Types::H2Enum(Self {
+            reader: reader,
+            enum_type: enum_type.to_string(),
+            handle: enum_handle(enum_type).ok(),
+        })))
+    }
+
     fn render(&self, value: usize) -> SimpleResult<String> {
-        let output = match from_enum(&self.enum_type, value)? {
-            Some(o) => o.to_string(),
+        let output = match (self.handle, enum_exists(&self.enum_type)) {
+            (Some(handle), _) => handle.get(value).map(|o| o.to_string()),
+            (None, true)      => from_enum(&self.enum_type, value)?.map(|o| o.to_string()),
+            (None, false)     => None,
+        };
+
+        let output = match output {
+            Some(o) => o,
             None => format!("Unknown_0x{:x}", value),
         };
 
@@ -59,10 +93,18 @@ impl H2TypeTrait for H2Enum {
         true
     }
 
+    fn describe(&self) -> String {
+        format!("enum<{}, {}>", self.reader, self.enum_type)
+    }
+
     fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
         Ok(self.reader.size() as u64)
     }
 
+    fn data_references(&self, references: &mut BTreeSet<H2DataReference>) {
+        references.insert(H2DataReference::Enum(self.enum_type.clone()));
+    }
+
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
         match offset {
             Offset::Static(_) => Ok("Enum".to_string()),
@@ -88,6 +130,10 @@ impl H2TypeTrait for H2Enum {
     fn to_integer(&self, offset: Offset) -> SimpleResult<Integer> {
         self.reader.read(offset.get_dynamic()?)
     }
+
+    fn enum_name(&self) -> Option<String> {
+        Some(self.enum_type.clone())
+    }
 }
 
 #[cfg(test)]
@@ -125,4 +171,46 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_enum_unchecked() -> SimpleResult<()> {
+        let test_buffer = b"\x01".to_vec();
+        let offset = Offset::Dynamic(Context::new(&test_buffer));
+
+        assert!(H2Enum::new(IntegerReader::U8, "NotAnEnum").is_err());
+
+        // Enums h2data doesn't know about are always unknown
+        let t = H2Enum::new_unchecked(IntegerReader::U8, "NotAnEnum")?;
+        assert_eq!("NotAnEnum::Unknown_0x1", t.to_display(offset.at(0))?);
+
+        // But ones it does know about work normally
+        let t = H2Enum::new_unchecked(IntegerReader::U8, "TerrariaGameMode")?;
+        assert_eq!("TerrariaGameMode::MediumCore", t.to_display(offset.at(0))?);
+
+        // Either way, the resolved value knows which enum it's from
+        assert_eq!(Some("TerrariaGameMode".to_string()), t.resolve(offset, None)?.enum_name);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_enum_without_handle() -> SimpleResult<()> {
+        let test_buffer = b"\x01\x20".to_vec();
+        let offset = Offset::Dynamic(Context::new(&test_buffer));
+
+        // Loaded types don't have a handle, but display the same
+        let t = H2Enum {
+            reader: IntegerReader::U8,
+            enum_type: "TerrariaGameMode".to_string(),
+            handle: None,
+        };
+        let with_handle = H2Enum::new(IntegerReader::U8, "TerrariaGameMode")?;
+
+        for o in 0..2 {
+            assert_eq!(with_handle.to_display(offset.at(o))?, t.to_display(offset.at(o))?);
+        }
+        assert_eq!("TerrariaGameMode::MediumCore", t.to_display(offset.at(0))?);
+
+        Ok(())
+    }
 }
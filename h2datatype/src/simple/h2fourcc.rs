@@ -0,0 +1,177 @@
+use std::collections::BTreeSet;
+
+use serde::{Serialize, Deserialize};
+
+use simple_error::{SimpleResult, bail};
+
+use h2data::{string_constants_exist, string_constants_handle, from_string_constant, StringConstantsHandle};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset, H2DataReference};
+
+/// The table of string constants that FourCC codes are looked up in, unless
+/// another one is given.
+pub const DEFAULT_FOURCC_CONSTANTS: &str = "FourCC";
+
+/// Defines a four-character code, like the chunk types in RIFF, PNG, and MP4
+/// files.
+///
+/// A FourCC is always 4 bytes. It's displayed as the tag in quotes, followed
+/// by what it means if it's in a table of string constants from [`h2data`]
+/// (`'IHDR' (PNG image header)`); bytes that aren't printable ASCII are
+/// escaped (`'\x00\x00\x00\x01'`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct H2FourCC {
+    constants: String,
+
+    /// The table from [`h2data`], looked up when the type is created - see
+    /// [`crate::simple::H2Enum`].
+    #[serde(skip)]
+    handle: Option<StringConstantsHandle>,
+}
+
+impl H2FourCC {
+    pub fn new_aligned(alignment: Alignment, constants: &str) -> SimpleResult<H2Type> {
+        let handle = match string_constants_handle(constants) {
+            Ok(handle) => handle,
+            Err(_)     => bail!("No such string constants: {}", constants),
+        };
+
+        Ok(H2Type::new(alignment, H2Types::H2FourCC(Self {
+            constants: constants.to_string(),
+            handle: Some(handle),
+        })))
+    }
+
+    /// Create a FourCC that's looked up in [`DEFAULT_FOURCC_CONSTANTS`].
+    pub fn new() -> H2Type {
+        // The default table is always there
+        Self::new_aligned(Alignment::None, DEFAULT_FOURCC_CONSTANTS).unwrap()
+    }
+
+    /// Create a FourCC that's looked up in a different table of string
+    /// constants.
+    pub fn new_with_constants(constants: &str) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, constants)
+    }
+
+    /// The tag, as a string. Printable ASCII is kept as-is so it can be
+    /// looked up, and anything else is escaped.
+    fn tag(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| match b {
+            0x20..=0x7e => (*b as char).to_string(),
+            _           => format!("\\x{:02x}", b),
+        }).collect()
+    }
+
+    fn read_tag(&self, offset: Offset) -> SimpleResult<String> {
+        Ok(Self::tag(&offset.get_dynamic()?.read_bytes(4)?))
+    }
+
+    fn render(&self, tag: &str) -> SimpleResult<String> {
+        let meaning = match (self.handle, string_constants_exist(&self.constants)) {
+            (Some(handle), _) => handle.get(tag),
+            (None, true)      => from_string_constant(&self.constants, tag)?,
+            (None, false)     => None,
+        };
+
+        Ok(match meaning {
+            Some(meaning) => format!("'{}' ({})", tag, meaning),
+            None          => format!("'{}'", tag),
+        })
+    }
+}
+
+impl H2TypeTrait for H2FourCC {
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn describe(&self) -> String {
+        match &self.constants[..] {
+            DEFAULT_FOURCC_CONSTANTS => "fourcc".to_string(),
+            constants                => format!("fourcc<{}>", constants),
+        }
+    }
+
+    fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
+        Ok(4)
+    }
+
+    fn data_references(&self, references: &mut BTreeSet<H2DataReference>) {
+        references.insert(H2DataReference::StringConstants(self.constants.clone()));
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        match offset {
+            Offset::Static(_) => Ok("FourCC".to_string()),
+            Offset::Dynamic(_) => self.render(&self.read_tag(offset)?),
+        }
+    }
+
+    fn can_be_string(&self) -> bool {
+        true
+    }
+
+    /// Just the tag, without the quotes or meaning.
+    fn to_string(&self, offset: Offset) -> SimpleResult<String> {
+        self.read_tag(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use generic_number::Context;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_fourcc() -> SimpleResult<()> {
+        let test_buffer = b"IHDRfmt abcd\x00\x00\x00\x01".to_vec();
+        let offset = Offset::Dynamic(Context::new(&test_buffer));
+
+        let t = H2FourCC::new();
+        assert_eq!("fourcc", t.describe());
+        assert_eq!(4, t.actual_size(offset.at(0))?);
+
+        let tests = vec![
+          // offset  display                       string
+            (0,      "'IHDR' (PNG image header)", "IHDR"),
+            (4,      "'fmt ' (Format chunk)",     "fmt "),
+            (8,      "'abcd'",                    "abcd"),
+            (12,     "'\\x00\\x00\\x00\\x01'",    "\\x00\\x00\\x00\\x01"),
+        ];
+
+        for (o, display, string) in tests {
+            assert_eq!(display, t.to_display(offset.at(o))?);
+            assert_eq!(string, t.to_string(offset.at(o))?);
+        }
+
+        // Not enough data
+        assert!(t.to_display(offset.at(14)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fourcc_constants() -> SimpleResult<()> {
+        let test_buffer = b"testIHDR".to_vec();
+        let offset = Offset::Dynamic(Context::new(&test_buffer));
+
+        assert!(H2FourCC::new_with_constants("NotConstants").is_err());
+
+        let t = H2FourCC::new_with_constants("TestStrings")?;
+        assert_eq!("fourcc<TestStrings>", t.describe());
+        assert_eq!("'test' (Test constant)", t.to_display(offset.at(0))?);
+        assert_eq!("'IHDR'", t.to_display(offset.at(4))?);
+
+        // Loaded types don't have a handle, but display the same
+        let t = H2FourCC {
+            constants: DEFAULT_FOURCC_CONSTANTS.to_string(),
+            handle: None,
+        };
+        assert_eq!("'IHDR' (PNG image header)", t.to_display(offset.at(4))?);
+
+        Ok(())
+    }
+}
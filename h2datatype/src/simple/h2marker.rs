@@ -0,0 +1,113 @@
+use serde::{Serialize, Deserialize};
+
+use simple_error::SimpleResult;
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+
+/// Defines a marker, which labels a position without consuming any bytes.
+///
+/// Markers are mostly useful inside a [`crate::composite::H2Struct`], to
+/// point out where something starts ("here begins section X") without
+/// claiming the data that follows. They always have a size of zero, and their
+/// value is just their label.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct H2Marker {
+    label: String,
+}
+
+impl H2Marker {
+    pub fn new_aligned(alignment: Alignment, label: &str) -> H2Type {
+        H2Type::new(alignment, H2Types::H2Marker(Self {
+            label: label.to_string(),
+        }))
+    }
+
+    pub fn new(label: &str) -> H2Type {
+        Self::new_aligned(Alignment::None, label)
+    }
+}
+
+impl H2TypeTrait for H2Marker {
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn describe(&self) -> String {
+        format!("marker({})", self.label)
+    }
+
+    fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
+        Ok(0)
+    }
+
+    fn to_display(&self, _offset: Offset) -> SimpleResult<String> {
+        Ok(format!("<{}>", self.label))
+    }
+
+    fn can_be_string(&self) -> bool {
+        true
+    }
+
+    fn to_string(&self, _offset: Offset) -> SimpleResult<String> {
+        Ok(self.label.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use generic_number::{Context, IntegerReader, Endian, DefaultFormatter};
+    use crate::composite::H2Struct;
+    use crate::simple::numeric::H2Integer;
+
+    #[test]
+    fn test_marker() -> SimpleResult<()> {
+        let data = b"\x00\x01\x00\x02".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data)).at(2);
+
+        let t = H2Marker::new("Header");
+        assert_eq!(0, t.actual_size(offset)?);
+        assert_eq!(2..2, t.actual_range(offset)?);
+        assert_eq!("<Header>", t.to_display(offset)?);
+        assert_eq!("marker(Header)", t.describe());
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(2..2, r.aligned_range);
+        assert_eq!(Some("Header".to_string()), r.as_string);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_marker_in_struct() -> SimpleResult<()> {
+        let data = b"\x00\x01\x00\x02".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+        let u16 = H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer());
+
+        let t = H2Struct::new(vec![
+            ("start".to_string(), H2Marker::new("Header")),
+            ("a".to_string(),     u16.clone()),
+            ("middle".to_string(), H2Marker::new("Body")),
+            ("b".to_string(),     u16.clone()),
+            ("end".to_string(),   H2Marker::new("End")),
+        ])?;
+
+        assert_eq!(4, t.actual_size(offset)?);
+        assert_eq!("{ start: <Header>, a: 1, middle: <Body>, b: 2, end: <End> }", t.to_display(offset)?);
+
+        let r = t.resolve(offset, None)?;
+        assert_eq!(0..4, r.actual_range);
+        assert_eq!(0..0, r.children[0].actual_range);
+        assert_eq!(2..2, r.children[2].actual_range);
+        assert_eq!(4..4, r.children[4].actual_range);
+
+        // A struct of only markers is fine too, it just takes no space
+        let t = H2Struct::new(vec![
+            ("only".to_string(), H2Marker::new("Nothing")),
+        ])?;
+        assert_eq!(0, t.actual_size(offset)?);
+
+        Ok(())
+    }
+}
@@ -1,141 +1,297 @@
-//use serde::{Serialize, Deserialize};
-//use simple_error::SimpleResult;
-
-//use generic_number::{IntegerReader, GenericFormatter};
-//use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
-
-///// Defines a pointer type - a numeric type that points to another location.
-/////
-///// This is defined very similarly to [`crate::simple::numeric::H2Integer`],
-///// with one additional field: the `target_type`, which is the type of the value
-///// that the pointer points to.
-//#[derive(Debug, Clone, Serialize, Deserialize)]
-//pub struct H2Pointer {
-//    definition: IntegerReader,
-//    display: GenericFormatter,
-
-//    target_type: Box<H2Type>,
-//}
-
-//impl H2Pointer {
-//    pub fn new_aligned(alignment: Alignment, definition: IntegerReader, display: GenericFormatter, target_type: H2Type) -> H2Type {
-//        // TODO: Ensure the definition can be a u64
-//        H2Type::new(alignment, H2Types::H2Pointer(Self {
-//            definition: definition,
-//            display: display,
-//            target_type: Box::new(target_type),
-//        }))
-//    }
-
-//    pub fn new(definition: IntegerReader, display: GenericFormatter, target_type: H2Type) -> H2Type {
-//        Self::new_aligned(Alignment::None, definition, display, target_type)
-//    }
-//}
-
-//impl H2TypeTrait for H2Pointer {
-//    fn is_static(&self) -> bool {
-//        true
-//    }
-
-//    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
-//        // TODO: I'm not sure if using the static size here is really something I should care about, as opposed to just reading + checking
-//        match self.definition.size() {
-//            Some(v) => Ok(v as u64),
-//            None    => Ok(self.definition.read(offset.get_dynamic()?)?.size() as u64),
-//        }
-//    }
-
-//    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
-//        match offset {
-//            Offset::Static(_) => Ok(format!("Pointer to {}", self.target_type.to_display(offset)?)),
-//            Offset::Dynamic(context) => {
-//                // Read the current value
-//                let target_offset = self.definition.read(context)?;
-//                let pointer_display = self.display.render(target_offset)?;
-
-//                // Read the target from a separate context
-//                let target = Offset::from(context.at(target_offset.as_u64()?));
-//                let target_display = match self.target_type.to_display(target) {
-//                    Ok(v) => v,
-//                    Err(e) => format!("Invalid pointer target: {}", e),
-//                };
-
-//                Ok(format!("(ref) {} => {}", pointer_display, target_display))
-//            }
-//        }
-//    }
-
-//    fn related(&self, offset: Offset) -> SimpleResult<Vec<(u64, H2Type)>> {
-//        let context = offset.get_dynamic()?;
-//        let target = self.definition.read(context)?;
-
-//        Ok(vec![
-//            (target.as_u64()?, *self.target_type.clone())
-//        ])
-//    }
-//}
-
-//#[cfg(test)]
-//mod tests {
-//    use super::*;
-//    use simple_error::SimpleResult;
-
-//    use generic_number::{Context, Endian, HexFormatter};
-//    use crate::simple::numeric::H2Integer;
-
-//    #[test]
-//    fn test_pointer() -> SimpleResult<()> {
-//        let data = b"\x00\x08AAAAAA\x00\x01\x02\x03".to_vec();
-//        let s_offset = Offset::Static(0);
-//        let d_offset = Offset::Dynamic(Context::new(&data));
-
-//        // 16-bit big-endian pointer (0x0008) that displays as hex
-//        let t = H2Pointer::new(
-//            IntegerReader::U16(Endian::Big),
-//            HexFormatter::pretty(),
-
-//            // ...pointing to a 32-bit big-endian number (0x00010203)
-//            H2Integer::new(
-//                IntegerReader::U32(Endian::Big),
-//                HexFormatter::pretty_string(),
-//            )
-//        );
-
-//        // A 16-bit pointer is 2 bytes
-//        assert_eq!(2, t.actual_size(s_offset).unwrap());
-//        assert_eq!(2, t.actual_size(d_offset).unwrap());
-
-//        // Make sure it resolves the other variable
-//        assert!(t.to_display(d_offset)?.starts_with("(ref) 0x0008"));
-
-//        // It has one related value - the int it points to
-//        assert!(t.related(s_offset).is_err());
-//        assert_eq!(1, t.related(d_offset)?.len());
-
-//        Ok(())
-//    }
-
-//    #[test]
-//    fn test_nested_pointer() -> SimpleResult<()> {
-//        //           -P1-  --P2-- -----P3--------
-//        let data = b"\x01\x00\x03\x07\x00\x00\x00ABCDEFGH".to_vec();
-//        let s_offset = Offset::Static(0);
-//        let d_offset = Offset::Dynamic(Context::new(&data));
-
-//        let t = H2Pointer::new(IntegerReader::U8, HexFormatter::pretty(), // P1
-//            H2Pointer::new(IntegerReader::U16(Endian::Big), HexFormatter::pretty(), // P2
-//                H2Pointer::new(IntegerReader::U32(Endian::Little), HexFormatter::pretty(), // P3
-//                    H2Integer::new(IntegerReader::U64(Endian::Big), HexFormatter::pretty()),
-//                )
-//            )
-//        );
-
-//        assert_eq!(1, t.actual_size(s_offset).unwrap());
-//        assert_eq!(1, t.actual_size(d_offset).unwrap());
-
-//        assert_eq!(1, t.related(d_offset)?.len());
-//        assert!(t.to_display(d_offset)?.ends_with("0x4142434445464748"));
-
-//        Ok(())
-//    }
-//}
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+
+use serde::{Serialize, Deserialize};
+
+use simple_error::{SimpleResult, bail};
+use generic_number::{Integer, IntegerReader, IntegerRenderer, IntegerWriter};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset, ResolvedType, H2DataReference};
+use crate::composite::H2Variables;
+
+/// What a pointer's value is counted from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PointerBase {
+    /// The value is an offset from the start of the buffer
+    Buffer,
+
+    /// The value is an offset from where the pointer itself is (it can be
+    /// negative, if the reader is signed)
+    Pointer,
+
+    /// The value is an offset from a fixed position - for example, the start
+    /// of the entry or header that the offsets are relative to
+    Custom(u64),
+}
+
+impl Default for PointerBase {
+    fn default() -> Self {
+        Self::Buffer
+    }
+}
+
+/// Defines a pointer - an integer that holds the address of something else.
+///
+/// On its own, a pointer reads and displays exactly like an
+/// [`crate::simple::numeric::H2Integer`]. The difference is that its
+/// [`crate::ResolvedType`] is marked with
+/// [`crate::ResolvedType::is_pointer`], so whatever displays it can look the
+/// address up - in a symbol table, say - and show `0x401000 (main+0x16)`
+/// instead of just the number.
+///
+/// A pointer can also have a target type (see [`H2Pointer::new_to`]). The
+/// value is turned into an address with a [`PointerBase`], and the target is
+/// resolved there - it's in [`crate::ResolvedType::target`], and it's also
+/// the pointer's [`H2Type::related`] value. A value of 0 is a null pointer,
+/// and has no target.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct H2Pointer {
+    /// How the address is stored.
+    reader: IntegerReader,
+
+    /// How the address is displayed (usually hex).
+    renderer: IntegerRenderer,
+
+    /// What the value is counted from.
+    #[serde(default)]
+    base: PointerBase,
+
+    /// What the pointer points to, if we know.
+    #[serde(default)]
+    target: Option<Box<H2Type>>,
+}
+
+impl H2Pointer {
+    pub fn new_aligned(alignment: Alignment, reader: IntegerReader, renderer: IntegerRenderer) -> H2Type {
+        H2Type::new(alignment, H2Types::H2Pointer(Self {
+            reader: reader,
+            renderer: renderer,
+            base: PointerBase::Buffer,
+            target: None,
+        }))
+    }
+
+    pub fn new(reader: IntegerReader, renderer: IntegerRenderer) -> H2Type {
+        Self::new_aligned(Alignment::None, reader, renderer)
+    }
+
+    /// Create a pointer to a `target`, which is resolved at the address the
+    /// pointer holds.
+    pub fn new_to_aligned(alignment: Alignment, reader: IntegerReader, renderer: IntegerRenderer, base: PointerBase, target: H2Type) -> H2Type {
+        H2Type::new(alignment, H2Types::H2Pointer(Self {
+            reader: reader,
+            renderer: renderer,
+            base: base,
+            target: Some(Box::new(target)),
+        }))
+    }
+
+    pub fn new_to(reader: IntegerReader, renderer: IntegerRenderer, base: PointerBase, target: H2Type) -> H2Type {
+        Self::new_to_aligned(Alignment::None, reader, renderer, base, target)
+    }
+
+    /// The type the pointer points to, if it has one.
+    pub fn target_type(&self) -> Option<&H2Type> {
+        self.target.as_deref()
+    }
+
+    /// Work out the address that the pointer points to, or `None` if it's
+    /// null.
+    pub fn address(&self, offset: Offset) -> SimpleResult<Option<u64>> {
+        let value = self.reader.read(offset.get_dynamic()?)?.to_i128()?;
+        if value == 0 {
+            return Ok(None);
+        }
+
+        let base = match self.base {
+            PointerBase::Buffer    => 0,
+            PointerBase::Pointer   => offset.position(),
+            PointerBase::Custom(b) => b,
+        };
+
+        match u64::try_from(base as i128 + value) {
+            Ok(address) => Ok(Some(address)),
+            Err(_)      => bail!("Pointer value {} from base {} isn't a valid address", value, base),
+        }
+    }
+}
+
+impl H2TypeTrait for H2Pointer {
+    fn is_static(&self) -> bool {
+        true
+    }
+
+    fn describe(&self) -> String {
+        let target = match &self.target {
+            Some(t) => t,
+            None    => return format!("ptr<{}>", self.reader),
+        };
+
+        match self.base {
+            PointerBase::Buffer    => format!("ptr<{}, {}>", self.reader, target.describe()),
+            PointerBase::Pointer   => format!("ptr<{}, {}, relative>", self.reader, target.describe()),
+            PointerBase::Custom(b) => format!("ptr<{}, {}, base 0x{:x}>", self.reader, target.describe(), b),
+        }
+    }
+
+    fn bind(&self, variables: &H2Variables) -> SimpleResult<Option<H2Types>> {
+        let target = match &self.target {
+            Some(t) => t.bind(variables)?,
+            None    => return Ok(None),
+        };
+
+        Ok(Some(H2Types::H2Pointer(Self {
+            target: Some(Box::new(target)),
+            ..self.clone()
+        })))
+    }
+
+    fn freeze(&self) -> Option<H2Types> {
+        let target = self.target.as_ref()?.freeze();
+
+        Some(H2Types::H2Pointer(Self {
+            target: Some(Box::new(target)),
+            ..self.clone()
+        }))
+    }
+
+    fn data_references(&self, references: &mut BTreeSet<H2DataReference>) {
+        if let Some(t) = &self.target {
+            t.add_data_references(references);
+        }
+    }
+
+    fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
+        Ok(self.reader.size() as u64)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        match offset {
+            Offset::Static(_) => Ok("Pointer".to_string()),
+            Offset::Dynamic(context) => {
+                Ok(self.renderer.render_offset(self.reader.read(context)?))
+            }
+        }
+    }
+
+    fn related(&self, offset: Offset) -> SimpleResult<Vec<(u64, H2Type)>> {
+        let (target, address) = match (&self.target, offset) {
+            (Some(t), Offset::Dynamic(_)) => (t, self.address(offset)?),
+            _                             => return Ok(vec![]),
+        };
+
+        Ok(address.map(|address| (address, target.as_ref().clone())).into_iter().collect())
+    }
+
+    fn target(&self, offset: Offset) -> SimpleResult<Option<ResolvedType>> {
+        let (target, address) = match (&self.target, offset) {
+            (Some(t), Offset::Dynamic(_)) => (t, self.address(offset)?),
+            _                             => return Ok(None),
+        };
+
+        match address {
+            Some(address) => Ok(Some(target.resolve(offset.at(address), None)?)),
+            None          => Ok(None),
+        }
+    }
+
+    fn is_pointer(&self) -> bool {
+        true
+    }
+
+    fn can_be_integer(&self) -> bool {
+        true
+    }
+
+    fn to_integer(&self, offset: Offset) -> SimpleResult<Integer> {
+        self.reader.read(offset.get_dynamic()?)
+    }
+
+    fn can_encode(&self) -> bool {
+        true
+    }
+
+    fn encode(&self, _offset: Offset, value: &str) -> SimpleResult<Vec<u8>> {
+        IntegerWriter::from(self.reader).write(value.parse()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use generic_number::{Context, Endian, HexFormatter, DefaultFormatter};
+
+    use crate::simple::numeric::H2Integer;
+
+    #[test]
+    fn test_pointer() -> SimpleResult<()> {
+        let data = b"\x00\x10\x40\x00\xff".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Pointer::new(IntegerReader::U32(Endian::Little), HexFormatter::pretty_integer());
+        assert_eq!("ptr<u32le>", t.describe());
+        assert_eq!(4, t.actual_size(offset)?);
+        assert_eq!("0x00401000", t.to_display(offset)?);
+
+        // The resolved value is marked, and has the address
+        let resolved = t.resolve(offset, None)?;
+        assert!(resolved.is_pointer);
+        assert_eq!(0x401000, resolved.as_integer.unwrap().to_u64()?);
+
+        // Integers aren't pointers
+        let t = crate::simple::numeric::H2Integer::new(IntegerReader::U32(Endian::Little), HexFormatter::pretty_integer());
+        assert!(!t.resolve(offset, None)?.is_pointer);
+
+        // Not enough data
+        assert!(H2Pointer::new(IntegerReader::U32(Endian::Little), HexFormatter::pretty_integer()).to_display(offset.at(2)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pointer_target() -> SimpleResult<()> {
+        //           ptr  ptr  ptr  null -- u16 --
+        let data = b"\x04\x02\xfe\x00\x01\x02".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+        let target = H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer());
+
+        // From the start of the buffer
+        let t = H2Pointer::new_to(IntegerReader::U8, HexFormatter::pretty_integer(), PointerBase::Buffer, target.clone());
+        assert_eq!("ptr<u8, u16be>", t.describe());
+        assert_eq!(1, t.actual_size(offset)?);
+        assert_eq!("0x04", t.to_display(offset)?);
+
+        let resolved = t.resolve(offset, None)?;
+        assert_eq!(4, resolved.as_integer.unwrap().to_u64()?);
+        let resolved_target = resolved.target.unwrap();
+        assert_eq!(4..6, resolved_target.actual_range);
+        assert_eq!("258", resolved_target.display);
+        assert_eq!(vec![4], t.related(offset)?.iter().map(|(address, _)| *address).collect::<Vec<_>>());
+
+        // From the pointer itself, forwards and backwards
+        let t = H2Pointer::new_to(IntegerReader::I8, HexFormatter::pretty_integer(), PointerBase::Pointer, target.clone());
+        assert_eq!("ptr<i8, u16be, relative>", t.describe());
+        assert_eq!(3..5, t.resolve(offset.at(1), None)?.target.unwrap().actual_range);
+        assert_eq!(0..2, t.resolve(offset.at(2), None)?.target.unwrap().actual_range);
+
+        // From somewhere else
+        let t = H2Pointer::new_to(IntegerReader::U8, HexFormatter::pretty_integer(), PointerBase::Custom(2), target.clone());
+        assert_eq!("ptr<u8, u16be, base 0x2>", t.describe());
+        assert_eq!(4..6, t.resolve(offset.at(1), None)?.target.unwrap().actual_range);
+
+        // Null pointers have no target
+        let resolved = t.resolve(offset.at(3), None)?;
+        assert!(resolved.target.is_none());
+        assert!(resolved.warnings.is_empty());
+        assert_eq!(0, t.related(offset.at(3))?.len());
+
+        // Pointers off the end still resolve, but with a warning
+        let t = H2Pointer::new_to(IntegerReader::U8, HexFormatter::pretty_integer(), PointerBase::Buffer, target.clone());
+        let resolved = t.resolve(offset.at(2), None)?;
+        assert!(resolved.target.is_none());
+        assert_eq!(1, resolved.warnings.len());
+
+        Ok(())
+    }
+}
@@ -32,6 +32,10 @@ impl H2TypeTrait for H2UUID {
         true
     }
 
+    fn describe(&self) -> String {
+        format!("uuid<{}>", self.endian)
+    }
+
     fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
         Ok(16)
     }
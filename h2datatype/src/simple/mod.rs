@@ -1,18 +1,30 @@
+mod h2pointer;
+pub use h2pointer::*;
+
 mod rgb;
 pub use rgb::*;
 
 mod h2bitmask;
 pub use h2bitmask::*;
 
+mod h2bits;
+pub use h2bits::*;
+
 mod h2enum;
 pub use h2enum::*;
 
 mod h2uuid;
 pub use h2uuid::*;
 
+mod h2fourcc;
+pub use h2fourcc::*;
+
 mod h2blob;
 pub use h2blob::*;
 
+mod h2marker;
+pub use h2marker::*;
+
 pub mod numeric;
 pub mod network;
 pub mod string;
@@ -32,6 +32,10 @@ impl H2TypeTrait for IPv6 {
         true
     }
 
+    fn describe(&self) -> String {
+        format!("ipv6<{}>", self.endian)
+    }
+
     fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
         Ok(16)
     }
@@ -28,6 +28,10 @@ impl H2TypeTrait for MacAddress8 {
         true
     }
 
+    fn describe(&self) -> String {
+        "macaddr8".to_string()
+    }
+
     fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
         Ok(8)
     }
@@ -1,7 +1,7 @@
 use serde::{Serialize, Deserialize};
 
-use simple_error::SimpleResult;
-use generic_number::{Character, CharacterReader, CharacterRenderer, CharacterFormatter};
+use simple_error::{SimpleResult, bail};
+use generic_number::{Character, CharacterReader, CharacterRenderer, CharacterFormatter, CharacterWriter};
 
 use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
 
@@ -85,6 +85,10 @@ impl H2TypeTrait for H2Character {
         }
     }
 
+    fn describe(&self) -> String {
+        format!("char<{}>", self.reader)
+    }
+
     fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
         match self.reader.size() {
             Some(v) => Ok(v as u64),
@@ -108,8 +112,53 @@ impl H2TypeTrait for H2Character {
     fn to_character(&self, offset: Offset) -> SimpleResult<Character> {
         self.reader.read(offset.get_dynamic()?)
     }
+
+    fn can_encode(&self) -> bool {
+        true
+    }
+
+    /// The value is a single character, optionally in single quotes (like
+    /// it's displayed).
+    fn encode(&self, _offset: Offset, value: &str) -> SimpleResult<Vec<u8>> {
+        let unquoted = match value.len() > 2 && value.starts_with('\'') && value.ends_with('\'') {
+            true  => &value[1..(value.len() - 1)],
+            false => value,
+        };
+
+        let mut chars = unquoted.chars();
+        let c = match (chars.next(), chars.next()) {
+            (Some(c), None) => c,
+            _               => bail!("Expected a single character, not {:?}", value),
+        };
+
+        // The size is recalculated by the writer
+        CharacterWriter::from(self.reader).write(Character::from((c, 0)))
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use generic_number::{Context, Endian};
+
+    #[test]
+    fn test_encode() -> SimpleResult<()> {
+        let data = b"a".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Character::new_ascii();
+        assert_eq!(b"b".to_vec(), t.encode(offset, "b")?);
+        assert_eq!(b"'".to_vec(), t.encode(offset, "'")?);
+        assert_eq!(b"c".to_vec(), t.encode(offset, "'c'")?);
+        assert!(t.encode(offset, "ab").is_err());
+        assert!(t.encode(offset, "").is_err());
+        assert!(t.encode(offset, "❄").is_err());
+
+        // The size comes from the new character
+        let t = H2Character::new(CharacterReader::UTF16(Endian::Big), CharacterFormatter::pretty_character());
+        assert_eq!(b"\x27\x44".to_vec(), t.encode(offset, "❄")?);
+
+        Ok(())
+    }
 }
@@ -1,7 +1,7 @@
 use serde::{Serialize, Deserialize};
 
-use simple_error::SimpleResult;
-use generic_number::{Float, FloatReader, FloatRenderer};
+use simple_error::{SimpleResult, SimpleError};
+use generic_number::{Float, FloatReader, FloatRenderer, FloatWriter};
 
 use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
 
@@ -42,6 +42,10 @@ impl H2TypeTrait for H2Float {
         true
     }
 
+    fn describe(&self) -> String {
+        self.reader.to_string()
+    }
+
     fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
         Ok(self.reader.size() as u64)
     }
@@ -62,4 +66,53 @@ impl H2TypeTrait for H2Float {
     fn to_float(&self, offset: Offset) -> SimpleResult<Float> {
         self.reader.read(offset.get_dynamic()?)
     }
+
+    fn can_encode(&self) -> bool {
+        true
+    }
+
+    fn encode(&self, _offset: Offset, value: &str) -> SimpleResult<Vec<u8>> {
+        let f = value.trim().parse::<f64>().map_err(|e| {
+            SimpleError::new(format!("Couldn't parse {:?} as a float: {}", value, e))
+        })?;
+
+        FloatWriter::from(self.reader).write(Float::from(f))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use generic_number::{Context, Endian, DefaultFormatter};
+
+    #[test]
+    fn test_float() -> SimpleResult<()> {
+        let data = b"\x3f\xc0\x00\x00\x3e\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Float::new(FloatReader::F32(Endian::Big), DefaultFormatter::new_float());
+        assert_eq!("F32(1.5)", t.to_display(offset)?);
+        assert_eq!(4, t.actual_size(offset)?);
+
+        // Half floats are read into an f32
+        let t = H2Float::new(FloatReader::F16(Endian::Big), DefaultFormatter::new_float());
+        assert_eq!("F32(1.5)", t.to_display(offset.at(4))?);
+        assert_eq!(2, t.actual_size(offset.at(4))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode() -> SimpleResult<()> {
+        let data = b"\x3f\xc0\x00\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Float::new(FloatReader::F32(Endian::Big), DefaultFormatter::new_float());
+        assert_eq!(data, t.encode(offset, "1.5")?);
+        assert_eq!(b"\xc0\x00\x00\x00".to_vec(), t.encode(offset, "-2")?);
+        assert!(t.encode(offset, "abc").is_err());
+
+        Ok(())
+    }
 }
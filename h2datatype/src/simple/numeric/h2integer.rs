@@ -1,7 +1,7 @@
 use serde::{Serialize, Deserialize};
 
 use simple_error::SimpleResult;
-use generic_number::{Integer, IntegerReader, IntegerRenderer};
+use generic_number::{Integer, IntegerReader, IntegerRenderer, IntegerWriter};
 
 use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
 
@@ -22,6 +22,12 @@ pub struct H2Integer {
     /// This is created by the various --Formatter modules in GenericNumber.
     /// For example, [`DefaultFormatter::new()`] or [`HexFormatter::pretty()`].
     renderer: IntegerRenderer,
+
+    /// Is the value an offset or a size? These are displayed with
+    /// [`IntegerRenderer::render_offset`], so renderers that care (like an
+    /// automatic [`generic_number::DefaultFormatter`]) can show them in hex.
+    #[serde(default)]
+    offset: bool,
 }
 
 impl H2Integer {
@@ -29,12 +35,27 @@ impl H2Integer {
         H2Type::new(alignment, H2Types::H2Integer(Self {
             reader: reader,
             renderer: renderer,
+            offset: false,
         }))
     }
 
     pub fn new(reader: IntegerReader, renderer: IntegerRenderer) -> H2Type {
         Self::new_aligned(Alignment::None, reader, renderer)
     }
+
+    /// Create an integer that's an offset or a size (see
+    /// [`IntegerRenderer::render_offset`]).
+    pub fn new_offset_aligned(alignment: Alignment, reader: IntegerReader, renderer: IntegerRenderer) -> H2Type {
+        H2Type::new(alignment, H2Types::H2Integer(Self {
+            reader: reader,
+            renderer: renderer,
+            offset: true,
+        }))
+    }
+
+    pub fn new_offset(reader: IntegerReader, renderer: IntegerRenderer) -> H2Type {
+        Self::new_offset_aligned(Alignment::None, reader, renderer)
+    }
 }
 
 impl H2TypeTrait for H2Integer {
@@ -42,6 +63,13 @@ impl H2TypeTrait for H2Integer {
         true
     }
 
+    fn describe(&self) -> String {
+        match self.offset {
+            true  => format!("offset<{}>", self.reader),
+            false => self.reader.to_string(),
+        }
+    }
+
     fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
         Ok(self.reader.size() as u64)
     }
@@ -50,7 +78,12 @@ impl H2TypeTrait for H2Integer {
         match offset {
             Offset::Static(_) => Ok("Integer".to_string()),
             Offset::Dynamic(context) => {
-                Ok(self.renderer.render(self.reader.read(context)?))
+                let value = self.reader.read(context)?;
+
+                match self.offset {
+                    true  => Ok(self.renderer.render_offset(value)),
+                    false => Ok(self.renderer.render(value)),
+                }
             }
         }
     }
@@ -62,6 +95,14 @@ impl H2TypeTrait for H2Integer {
     fn to_integer(&self, offset: Offset) -> SimpleResult<Integer> {
         self.reader.read(offset.get_dynamic()?)
     }
+
+    fn can_encode(&self) -> bool {
+        true
+    }
+
+    fn encode(&self, _offset: Offset, value: &str) -> SimpleResult<Vec<u8>> {
+        IntegerWriter::from(self.reader).write(value.parse()?)
+    }
 }
 
 #[cfg(test)]
@@ -116,6 +157,27 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_offset() -> SimpleResult<()> {
+        let data = b"\x00\x10\x00\x00\x10\x00\x00\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let renderer = DefaultFormatter::new_integer_auto(None, true);
+
+        let t = H2Integer::new_offset(IntegerReader::U32(Endian::Little), renderer);
+        assert_eq!("offset<u32le>", t.describe());
+        assert_eq!("0x1000", t.to_display(offset)?);
+        assert_eq!(16, t.to_integer(offset.at(4))?.to_u64()?);
+
+        // Plain integers with the same renderer stay decimal
+        assert_eq!("4096", H2Integer::new(IntegerReader::U32(Endian::Little), renderer).to_display(offset)?);
+
+        // Other renderers don't care
+        assert_eq!("4096", H2Integer::new_offset(IntegerReader::U32(Endian::Little), DefaultFormatter::new_integer()).to_display(offset)?);
+
+        Ok(())
+    }
+
     #[test]
     fn test_number_alignment() -> SimpleResult<()> {
         let data = b"\x00\x00\x7f\xff\x80\x00\xff\xff".to_vec();
@@ -195,4 +257,22 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_encode() -> SimpleResult<()> {
+        let offset = Offset::Static(0);
+
+        let t = H2Integer::new(IntegerReader::U16(Endian::Little), DefaultFormatter::new_integer());
+        assert!(t.can_encode());
+        assert_eq!(b"\xf4\x01".to_vec(), t.encode(offset, "500")?);
+        assert_eq!(b"\xf4\x01".to_vec(), t.encode(offset, "0x1f4")?);
+        assert!(t.encode(offset, "65536").is_err());
+        assert!(t.encode(offset, "-1").is_err());
+        assert!(t.encode(offset, "hello").is_err());
+
+        let t = H2Integer::new(IntegerReader::I8, DefaultFormatter::new_integer());
+        assert_eq!(b"\xff".to_vec(), t.encode(offset, "-1")?);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,196 @@
+use serde::{Serialize, Deserialize};
+
+use simple_error::{bail, SimpleResult};
+use generic_number::{Context, Integer, IntegerRenderer};
+
+use crate::{Alignment, H2Type, H2Types, H2TypeTrait, Offset};
+
+/// The most bytes a 64-bit LEB128 value can take up.
+const MAX_LENGTH: u64 = 10;
+
+/// Defines a variable-length LEB128 integer.
+///
+/// LEB128 stores seven bits per byte, least significant first, and sets the
+/// top bit of every byte except the last. It's used by DWARF, WebAssembly,
+/// and Android's DEX format, among others. The signed variant sign-extends
+/// from the last bit that was read.
+///
+/// Values are read into 64 bits, so the size is between 1 and 10 bytes and
+/// depends on the data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct H2Leb128 {
+    signed: bool,
+
+    /// How the value is to be displayed.
+    renderer: IntegerRenderer,
+}
+
+impl H2Leb128 {
+    pub fn new_aligned(alignment: Alignment, signed: bool, renderer: IntegerRenderer) -> H2Type {
+        H2Type::new(alignment, H2Types::H2Leb128(Self {
+            signed: signed,
+            renderer: renderer,
+        }))
+    }
+
+    pub fn new(signed: bool, renderer: IntegerRenderer) -> H2Type {
+        Self::new_aligned(Alignment::None, signed, renderer)
+    }
+
+    /// Read the value, and return its length in bytes along with it.
+    fn analyze(&self, context: Context) -> SimpleResult<(u64, Integer)> {
+        let start = context.position();
+        let mut value: u64 = 0;
+
+        for i in 0..MAX_LENGTH {
+            let byte = context.at(start + i).read_u8()?;
+
+            // Only the lowest bit of the last byte fits in 64 bits - the rest
+            // have to be zero (or, for signed values, copies of that bit)
+            if i == MAX_LENGTH - 1 {
+                let fits = match self.signed {
+                    true  => byte & 0x7f == 0x00 || byte & 0x7f == 0x7f,
+                    false => byte & 0x7e == 0x00,
+                };
+
+                if !fits {
+                    bail!("LEB128 value at offset {} doesn't fit in 64 bits", start);
+                }
+            }
+
+            value |= ((byte & 0x7f) as u64).checked_shl(7 * i as u32).unwrap_or(0);
+
+            if byte & 0x80 == 0 {
+                let bits = 7 * (i + 1);
+
+                if !self.signed {
+                    return Ok((i + 1, Integer::from(value)));
+                }
+
+                // Sign-extend if the last bit we read is set
+                let value = match bits < 64 && (byte & 0x40) != 0 {
+                    true  => (value | (!0u64 << bits)) as i64,
+                    false => value as i64,
+                };
+                return Ok((i + 1, Integer::from(value)));
+            }
+        }
+
+        bail!("LEB128 value at offset {} is longer than {} bytes", start, MAX_LENGTH);
+    }
+}
+
+impl H2TypeTrait for H2Leb128 {
+    fn is_static(&self) -> bool {
+        false
+    }
+
+    fn describe(&self) -> String {
+        match self.signed {
+            true  => "sleb128".to_string(),
+            false => "uleb128".to_string(),
+        }
+    }
+
+    fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
+        Ok(self.analyze(offset.get_dynamic()?)?.0)
+    }
+
+    fn to_display(&self, offset: Offset) -> SimpleResult<String> {
+        match offset {
+            Offset::Static(_) => Ok("LEB128".to_string()),
+            Offset::Dynamic(context) => {
+                Ok(self.renderer.render(self.analyze(context)?.1))
+            }
+        }
+    }
+
+    fn can_be_integer(&self) -> bool {
+        true
+    }
+
+    fn to_integer(&self, offset: Offset) -> SimpleResult<Integer> {
+        Ok(self.analyze(offset.get_dynamic()?)?.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+    use generic_number::{Context, DefaultFormatter};
+
+    #[test]
+    fn test_uleb128() -> SimpleResult<()> {
+        //           0   1   --127--  ---624485---
+        let data = b"\x00\x01\xff\x00\xe5\x8e\x26\x80".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Leb128::new(false, DefaultFormatter::new_integer());
+        assert_eq!("uleb128", t.describe());
+
+        assert_eq!("0", t.to_display(offset.at(0))?);
+        assert_eq!(1, t.actual_size(offset.at(0))?);
+        assert_eq!("1", t.to_display(offset.at(1))?);
+        assert_eq!("127", t.to_display(offset.at(2))?);
+        assert_eq!(2, t.actual_size(offset.at(2))?);
+        assert_eq!(624485, t.to_integer(offset.at(4))?.as_usize()?);
+        assert_eq!(4..7, t.actual_range(offset.at(4))?);
+
+        // Runs off the end
+        assert!(t.to_display(offset.at(7)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sleb128() -> SimpleResult<()> {
+        //           2   -2  --127-- --(-128)- -123456-
+        let data = b"\x02\x7e\xff\x00\x80\x7f\xc0\xbb\x78".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Leb128::new(true, DefaultFormatter::new_integer());
+        assert_eq!("sleb128", t.describe());
+
+        assert_eq!("2",       t.to_display(offset.at(0))?);
+        assert_eq!("-2",      t.to_display(offset.at(1))?);
+        assert_eq!("127",     t.to_display(offset.at(2))?);
+        assert_eq!("-128",    t.to_display(offset.at(4))?);
+        assert_eq!("-123456", t.to_display(offset.at(6))?);
+        assert_eq!(3, t.actual_size(offset.at(6))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_too_long() -> SimpleResult<()> {
+        let data = vec![0x80; 11];
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Leb128::new(false, DefaultFormatter::new_integer());
+        assert!(t.to_display(offset).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_overflow() -> SimpleResult<()> {
+        // u64::MAX, then the same with an extra bit that doesn't fit
+        let data = b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01\xff\xff\xff\xff\xff\xff\xff\xff\xff\x03".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Leb128::new(false, DefaultFormatter::new_integer());
+        assert_eq!(u64::MAX.to_string(), t.to_display(offset)?);
+        assert!(t.to_display(offset.at(10)).is_err());
+
+        // i64::MIN, then the same with a last byte that isn't a sign extension
+        let data = b"\x80\x80\x80\x80\x80\x80\x80\x80\x80\x7f\x80\x80\x80\x80\x80\x80\x80\x80\x80\x41".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let t = H2Leb128::new(true, DefaultFormatter::new_integer());
+        assert_eq!(i64::MIN.to_string(), t.to_display(offset)?);
+        assert!(t.to_display(offset.at(10)).is_err());
+
+        Ok(())
+    }
+}
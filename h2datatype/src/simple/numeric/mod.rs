@@ -6,3 +6,6 @@ pub use h2float::*;
 
 mod h2integer;
 pub use h2integer::*;
+
+mod h2leb128;
+pub use h2leb128::*;
@@ -34,6 +34,10 @@ impl H2TypeTrait for Rgb {
         true
     }
 
+    fn describe(&self) -> String {
+        "rgb".to_string()
+    }
+
     fn actual_size(&self, _offset: Offset) -> SimpleResult<u64> {
         Ok(3)
     }
@@ -60,6 +60,10 @@ impl H2TypeTrait for H2String {
         self.character.size().is_some()
     }
 
+    fn describe(&self) -> String {
+        format!("string<{}>[{}]", self.character, self.length)
+    }
+
     fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
         match self.character.size() {
             Some(s) => Ok(s as u64 * self.length),
@@ -75,12 +79,14 @@ impl H2TypeTrait for H2String {
         // Get the length so we can truncate
         let (_, chars) = self.analyze(offset)?;
 
-        // Convert into a string
-        Ok(String::from_iter(chars.into_iter().map(|c| self.renderer.render(c))))
+        Ok(String::from_iter(chars.into_iter().map(|c| c.as_char())))
     }
 
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
-        Ok(format!("\"{}\"", self.to_string(offset)?))
+        let (_, chars) = self.analyze(offset)?;
+
+        // Render each character
+        Ok(format!("\"{}\"", String::from_iter(chars.into_iter().map(|c| self.renderer.render(c)))))
     }
 }
 
@@ -167,4 +173,19 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_h2string_as_string() -> SimpleResult<()> {
+        let data = b"A\nB".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let a = H2String::new(3, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?;
+        let resolved = a.resolve(offset, None)?;
+
+        // The display is escaped, but the string isn't
+        assert_eq!("\"A\\nB\"", resolved.display);
+        assert_eq!(Some("A\nB".to_string()), resolved.as_string);
+
+        Ok(())
+    }
 }
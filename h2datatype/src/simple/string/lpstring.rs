@@ -2,14 +2,33 @@ use std::iter::FromIterator;
 use serde::{Serialize, Deserialize};
 use simple_error::{bail, SimpleResult};
 
-use generic_number::{IntegerReader, Character, CharacterReader, CharacterRenderer};
+use generic_number::{Integer, IntegerReader, IntegerWriter, Character, CharacterReader, CharacterRenderer, CharacterWriter};
 
 use crate::{H2Type, H2Types, H2TypeTrait, Offset, Alignment};
 
+/// What the length prefix of an [`LPString`] counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LengthUnit {
+    /// Whole characters, however many bytes each one takes
+    Characters,
+
+    /// Code units of the character type (see
+    /// [`CharacterReader::code_unit_size`]) - for example, a UTF-16 character
+    /// outside the BMP counts as 2. This is how Windows and .NET count.
+    CodeUnits,
+}
+
+impl Default for LengthUnit {
+    fn default() -> Self {
+        Self::Characters
+    }
+}
+
 /// Defines a length-prefixed string.
 ///
 /// This is a string with a numerical prefix that denotes the length of the
-/// string (in *characters*). The length is any numerical value as defined in
+/// string (in *characters*, or in code units with [`LPString::new_counted`]).
+/// The length is any numerical value as defined in
 /// [`generic_number::IntegerReader`] that `can_be_u64()`, and the
 /// character type is from [`generic_number::CharacterReader`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,10 +36,23 @@ pub struct LPString {
     length: IntegerReader,
     character: CharacterReader,
     renderer: CharacterRenderer,
+
+    #[serde(default)]
+    unit: LengthUnit,
 }
 
 impl LPString {
     pub fn new_aligned(alignment: Alignment, length: IntegerReader, character: CharacterReader, renderer: CharacterRenderer) -> SimpleResult<H2Type> {
+        Self::new_counted_aligned(alignment, length, LengthUnit::Characters, character, renderer)
+    }
+
+    pub fn new(length: IntegerReader, character: CharacterReader, renderer: CharacterRenderer) -> SimpleResult<H2Type> {
+        Self::new_aligned(Alignment::None, length, character, renderer)
+    }
+
+    /// Create a string where the prefix counts something other than
+    /// characters - see [`LengthUnit`].
+    pub fn new_counted_aligned(alignment: Alignment, length: IntegerReader, unit: LengthUnit, character: CharacterReader, renderer: CharacterRenderer) -> SimpleResult<H2Type> {
         if !length.can_be_usize() {
             bail!("Length type isn't numeric!");
         }
@@ -29,11 +61,12 @@ impl LPString {
             length: length,
             character: character,
             renderer: renderer,
+            unit: unit,
         })))
     }
 
-    pub fn new(length: IntegerReader, character: CharacterReader, renderer: CharacterRenderer) -> SimpleResult<H2Type> {
-        Self::new_aligned(Alignment::None, length, character, renderer)
+    pub fn new_counted(length: IntegerReader, unit: LengthUnit, character: CharacterReader, renderer: CharacterRenderer) -> SimpleResult<H2Type> {
+        Self::new_counted_aligned(Alignment::None, length, unit, character, renderer)
     }
 
     fn analyze(&self, offset: Offset) -> SimpleResult<(u64, Vec<Character>)> {
@@ -42,12 +75,34 @@ impl LPString {
         let mut position = offset.position() + self.length.size() as u64;
 
         let mut result = Vec::new();
-        for _ in 0..length {
-            let character = self.character.read(offset.at(position).get_dynamic()?)?;
-
-            result.push(character);
-            position = position + character.size() as u64;
-        }
+        match self.unit {
+            LengthUnit::Characters => {
+                for _ in 0..length {
+                    let character = self.character.read(offset.at(position).get_dynamic()?)?;
+
+                    result.push(character);
+                    position = position + character.size() as u64;
+                }
+            },
+            LengthUnit::CodeUnits => {
+                let end = match (length as u64).checked_mul(self.character.code_unit_size() as u64).and_then(|size| position.checked_add(size)) {
+                    Some(end) => end,
+                    None => bail!("String length is too long: {} units", length),
+                };
+
+                while position < end {
+                    let character = self.character.read(offset.at(position).get_dynamic()?)?;
+
+                    result.push(character);
+                    position = position + character.size() as u64;
+                }
+
+                // A multi-unit character can't straddle the end
+                if position != end {
+                    bail!("The last character of the string runs past its length ({} units)", length);
+                }
+            },
+        };
 
         Ok((position - offset.position(), result))
     }
@@ -58,6 +113,13 @@ impl H2TypeTrait for LPString {
         self.character.size().is_some()
     }
 
+    fn describe(&self) -> String {
+        match self.unit {
+            LengthUnit::Characters => format!("lpstring<{}, {}>", self.length, self.character),
+            LengthUnit::CodeUnits  => format!("lpstring<{}, {} units>", self.length, self.character),
+        }
+    }
+
     fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
         Ok(self.analyze(offset)?.0)
     }
@@ -70,12 +132,47 @@ impl H2TypeTrait for LPString {
         // Get the length so we can truncate
         let (_, chars) = self.analyze(offset)?;
 
-        // Convert into a string
-        Ok(String::from_iter(chars.into_iter().map(|c| self.renderer.render(c))))
+        Ok(String::from_iter(chars.into_iter().map(|c| c.as_char())))
     }
 
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
-        Ok(format!("\"{}\"", self.to_string(offset)?))
+        let (_, chars) = self.analyze(offset)?;
+
+        // Render each character
+        Ok(format!("\"{}\"", String::from_iter(chars.into_iter().map(|c| self.renderer.render(c)))))
+    }
+
+    fn can_encode(&self) -> bool {
+        true
+    }
+
+    /// The value is the new string, optionally in double quotes (like it's
+    /// displayed). The length prefix is worked out from the string, so the
+    /// size can change.
+    fn encode(&self, _offset: Offset, value: &str) -> SimpleResult<Vec<u8>> {
+        let value = match value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+            true  => &value[1..(value.len() - 1)],
+            false => value,
+        };
+
+        let writer = CharacterWriter::from(self.character);
+
+        let mut length: u64 = 0;
+        let mut characters: Vec<u8> = vec![];
+        for c in value.chars() {
+            let encoded = writer.write(Character::from((c, 0)))?;
+
+            length += match self.unit {
+                LengthUnit::Characters => 1,
+                LengthUnit::CodeUnits  => (encoded.len() / self.character.code_unit_size()) as u64,
+            };
+            characters.extend(encoded);
+        }
+
+        let mut out = IntegerWriter::from(self.length).write(Integer::from(length))?;
+        out.extend(characters);
+
+        Ok(out)
     }
 }
 
@@ -198,4 +295,115 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_utf16_code_units() -> SimpleResult<()> {
+        // 4 code units - "A", then "𝄞" (a surrogate pair), then "B" - then
+        // junk
+        let data = b"\x04\x00\x41\x00\x34\xd8\x1e\xdd\x42\x00\x43\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let a = LPString::new_counted(
+            IntegerReader::U16(Endian::Little),
+            LengthUnit::CodeUnits,
+            CharacterReader::UTF16(Endian::Little),
+            CharacterFormatter::pretty_str_character(),
+        )?;
+        assert_eq!("lpstring<u16le, utf16le units>", a.describe());
+        assert_eq!("\"A𝄞B\"", a.to_display(offset)?);
+        assert_eq!(10, a.actual_size(offset)?);
+
+        // Counting characters instead reads one more
+        let a = LPString::new(
+            IntegerReader::U16(Endian::Little),
+            CharacterReader::UTF16(Endian::Little),
+            CharacterFormatter::pretty_str_character(),
+        )?;
+        assert_eq!("\"A𝄞BC\"", a.to_display(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_code_units_split_character() -> SimpleResult<()> {
+        // 2 code units, but the second character is a surrogate pair
+        let data = b"\x02\x41\x00\x34\xd8\x1e\xdd".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let a = LPString::new_counted(
+            IntegerReader::U8,
+            LengthUnit::CodeUnits,
+            CharacterReader::UTF16(Endian::Little),
+            CharacterFormatter::pretty_str_character(),
+        )?;
+        assert!(a.to_display(offset).is_err());
+
+        // UTF-8 code units are just bytes
+        let data = b"\x03\xc3\xb7!".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let a = LPString::new_counted(
+            IntegerReader::U8,
+            LengthUnit::CodeUnits,
+            CharacterReader::UTF8,
+            CharacterFormatter::pretty_str_character(),
+        )?;
+        assert_eq!("\"÷!\"", a.to_display(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_code_units_overflow() -> SimpleResult<()> {
+        let data = b"\xff\xff\xff\xff\xff\xff\xff\xffAB".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let a = LPString::new_counted(
+            IntegerReader::U64(Endian::Little),
+            LengthUnit::CodeUnits,
+            CharacterReader::UTF16(Endian::Little),
+            CharacterFormatter::pretty_str_character(),
+        )?;
+        assert!(a.actual_size(offset).is_err());
+        assert!(a.to_display(offset).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lpstring_as_string() -> SimpleResult<()> {
+        let data = b"\x03A\nB".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        // The length isn't part of the string
+        let a = LPString::new(
+            IntegerReader::U8,
+            CharacterReader::ASCII,
+            CharacterFormatter::pretty_str_character(),
+        )?;
+        assert_eq!(Some("A\nB".to_string()), a.resolve(offset, None)?.as_string);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode() -> SimpleResult<()> {
+        let offset = Offset::Static(0);
+
+        let t = LPString::new(IntegerReader::U8, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?;
+        assert!(t.can_encode());
+        assert_eq!(b"\x05hello".to_vec(), t.encode(offset, "hello")?);
+        assert_eq!(b"\x02hi".to_vec(),     t.encode(offset, "\"hi\"")?);
+        assert_eq!(b"\x00".to_vec(),       t.encode(offset, "")?);
+        assert!(t.encode(offset, "❄").is_err());
+        assert!(t.encode(offset, &"a".repeat(256)).is_err());
+
+        // Code units count the encoded size, not the characters
+        let t = LPString::new_counted(IntegerReader::U16(Endian::Little), LengthUnit::CodeUnits, CharacterReader::UTF16(Endian::Little), CharacterFormatter::pretty_str_character())?;
+        let encoded = t.encode(offset, "a😈")?;
+        assert_eq!(b"\x03\x00a\x00\x3d\xd8\x08\xde".to_vec(), encoded);
+        assert_eq!("\"a😈\"", t.to_display(Offset::Dynamic(Context::new(&encoded)))?);
+
+        Ok(())
+    }
 }
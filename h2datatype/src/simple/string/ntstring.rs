@@ -53,6 +53,10 @@ impl H2TypeTrait for NTString {
         self.character.size().is_some()
     }
 
+    fn describe(&self) -> String {
+        format!("ntstring<{}>", self.character)
+    }
+
     fn actual_size(&self, offset: Offset) -> SimpleResult<u64> {
         Ok(self.analyze(offset)?.0)
     }
@@ -65,12 +69,14 @@ impl H2TypeTrait for NTString {
         // Get the length so we can truncate
         let (_, chars) = self.analyze(offset)?;
 
-        // Convert into a string
-        Ok(String::from_iter(chars.into_iter().map(|c| self.renderer.render(c))))
+        Ok(String::from_iter(chars.into_iter().map(|c| c.as_char())))
     }
 
     fn to_display(&self, offset: Offset) -> SimpleResult<String> {
-        Ok(format!("\"{}\"", self.to_string(offset)?))
+        let (_, chars) = self.analyze(offset)?;
+
+        // Render each character
+        Ok(format!("\"{}\"", String::from_iter(chars.into_iter().map(|c| self.renderer.render(c)))))
     }
 }
 
@@ -160,4 +166,16 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_ntstring_as_string() -> SimpleResult<()> {
+        let data = b"A\tB\x00".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        // The terminator isn't part of the string
+        let a = NTString::new(CharacterReader::ASCII, CharacterFormatter::pretty_str_character());
+        assert_eq!(Some("A\tB".to_string()), a.resolve(offset, None)?.as_string);
+
+        Ok(())
+    }
 }
@@ -5,19 +5,26 @@ use serde::{Serialize, Deserialize};
 use simple_error::{SimpleResult, SimpleError, bail};
 
 use crate::project::H2Project;
-use crate::project::H2Buffer;
-use crate::actions::Action;
+use crate::project::{H2Buffer, H2Id};
+use crate::actions::{Action, ActionCategory};
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Forward {
     name: String,
     size: usize,
     base_address: usize,
+
+    // Set once the buffer has been created, so redo gets the same ID
+    #[serde(default)]
+    id: Option<H2Id>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Backward {
     name: String,
+
+    #[serde(default)]
+    id: H2Id,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -37,10 +44,24 @@ impl ActionBufferCreateEmpty {
                     name: String::from(name),
                     size: size,
                     base_address: base_address,
+                    id: None,
                 })
             )
         )
     }
+
+    pub fn description(&self) -> String {
+        let name = match &self.0 {
+            State::Forward(f)  => &f.name,
+            State::Backward(b) => &b.name,
+        };
+
+        format!("Create empty buffer '{}'", name)
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Buffer
+    }
 }
 
 impl Command for ActionBufferCreateEmpty {
@@ -55,12 +76,16 @@ impl Command for ActionBufferCreateEmpty {
         };
 
         // Do stuff with it
-        let buffer = H2Buffer::new(&forward.name, vec![0; forward.size], forward.base_address)?;
-        project.buffer_insert(&forward.name, buffer)?;
+        let mut buffer = H2Buffer::new(&forward.name, vec![0; forward.size], forward.base_address)?;
+        if let Some(id) = forward.id {
+            buffer.set_id(id);
+        }
+        let id = project.buffer_insert(&forward.name, buffer)?;
 
         // Save the backward struct
         self.0 = State::Backward(Backward {
             name: forward.name.to_string(),
+            id: id,
         });
 
         Ok(())
@@ -73,15 +98,19 @@ impl Command for ActionBufferCreateEmpty {
             _                    => bail!("Failed to undo: action ended up in a broken undo/redo state"),
         };
 
-        // Do stuff with it
-        let name = &backward.name;
-        let buffer = project.buffer_remove(name)?;
+        // Find it by ID, in case the name has changed
+        let name = project.buffer_name(backward.id).ok_or(
+            SimpleError::new(format!("Could not find buffer with ID {}", backward.id))
+        )?.to_string();
+        let buffer = project.buffer_remove(&name)?;
+        let id = buffer.id();
 
         // Save the forward struct
         self.0 = State::Forward(Forward {
-            name: name.clone(),
+            name: name,
             size: buffer.data.len(),
             base_address: buffer.base_address,
+            id: Some(id),
         });
 
         Ok(())
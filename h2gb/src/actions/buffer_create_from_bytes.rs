@@ -5,19 +5,26 @@ use serde::{Serialize, Deserialize};
 use simple_error::{SimpleResult, SimpleError, bail};
 
 use crate::project::H2Project;
-use crate::project::H2Buffer;
-use crate::actions::Action;
+use crate::project::{H2Buffer, H2Id};
+use crate::actions::{Action, ActionCategory};
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Forward {
     name: String,
     data: Vec<u8>,
     base_address: usize,
+
+    // Set once the buffer has been created, so redo gets the same ID
+    #[serde(default)]
+    id: Option<H2Id>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Backward {
     name: String,
+
+    #[serde(default)]
+    id: H2Id,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -37,10 +44,24 @@ impl ActionBufferCreateFromBytes {
                     name: String::from(name),
                     data: Vec::from(data),
                     base_address: base_address,
+                    id: None,
                 })
             )
         )
     }
+
+    pub fn description(&self) -> String {
+        let name = match &self.0 {
+            State::Forward(f)  => &f.name,
+            State::Backward(b) => &b.name,
+        };
+
+        format!("Create buffer '{}'", name)
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Buffer
+    }
 }
 
 impl Command for ActionBufferCreateFromBytes {
@@ -55,12 +76,16 @@ impl Command for ActionBufferCreateFromBytes {
         };
 
         // Do stuff with it
-        let buffer = H2Buffer::new(&forward.name, forward.data.clone(), forward.base_address)?;
-        project.buffer_insert(&forward.name, buffer)?;
+        let mut buffer = H2Buffer::new(&forward.name, forward.data.clone(), forward.base_address)?;
+        if let Some(id) = forward.id {
+            buffer.set_id(id);
+        }
+        let id = project.buffer_insert(&forward.name, buffer)?;
 
         // Save the backward struct
         self.0 = State::Backward(Backward {
             name: forward.name.to_string(),
+            id: id,
         });
 
         Ok(())
@@ -73,15 +98,19 @@ impl Command for ActionBufferCreateFromBytes {
             _                    => bail!("Failed to undo: action ended up in a broken undo/redo state"),
         };
 
-        // Do stuff with it
-        let name = &backward.name;
-        let buffer = project.buffer_remove(name)?;
+        // Find it by ID, in case the name has changed
+        let name = project.buffer_name(backward.id).ok_or(
+            SimpleError::new(format!("Could not find buffer with ID {}", backward.id))
+        )?.to_string();
+        let buffer = project.buffer_remove(&name)?;
+        let id = buffer.id();
 
         // Save the forward struct
         self.0 = State::Forward(Forward {
-            name: name.clone(),
+            name: name,
             data: buffer.data,
             base_address: buffer.base_address,
+            id: Some(id),
         });
 
         Ok(())
@@ -0,0 +1,273 @@
+//! Create a new buffer from the contents of a file.
+
+use redo::Command;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::project::H2Project;
+use crate::project::{H2Buffer, H2Id};
+use crate::actions::{Action, ActionCategory};
+
+/// The biggest file [`ActionBufferCreateFromFile::new`] will load (1 GiB).
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 0x4000_0000;
+
+/// How much to read between progress updates.
+const CHUNK_SIZE: usize = 0x10000;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Forward {
+    name: String,
+    path: PathBuf,
+    data: Vec<u8>,
+    base_address: usize,
+
+    // Set once the buffer has been created, so redo gets the same ID
+    #[serde(default)]
+    id: Option<H2Id>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Backward {
+    name: String,
+    path: PathBuf,
+
+    #[serde(default)]
+    id: H2Id,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum State {
+    Forward(Forward),
+    Backward(Backward),
+}
+
+/// Read a file in chunks, calling `progress` with `(bytes_read, total)` after
+/// each one.
+pub(crate) fn read_file(path: &Path, max_size: u64, mut progress: impl FnMut(u64, u64)) -> SimpleResult<Vec<u8>> {
+    let mut file = File::open(path).map_err(|e| {
+        SimpleError::new(format!("Couldn't open {}: {}", path.display(), e))
+    })?;
+
+    let total = file.metadata().map_err(|e| {
+        SimpleError::new(format!("Couldn't get the size of {}: {}", path.display(), e))
+    })?.len();
+
+    if total == 0 {
+        bail!("Can't create a buffer from {}: the file is empty", path.display());
+    }
+
+    if total > max_size {
+        bail!("Can't create a buffer from {}: the file is {} bytes, but the limit is {}", path.display(), total, max_size);
+    }
+
+    let mut data: Vec<u8> = Vec::with_capacity(total as usize);
+    let mut chunk = vec![0; CHUNK_SIZE];
+    loop {
+        let count = file.read(&mut chunk).map_err(|e| {
+            SimpleError::new(format!("Couldn't read {} (after {} bytes): {}", path.display(), data.len(), e))
+        })?;
+
+        if count == 0 {
+            break;
+        }
+
+        // The file can grow while we're reading it
+        if data.len() as u64 + count as u64 > max_size {
+            bail!("Can't create a buffer from {}: the file grew past the limit of {} bytes", path.display(), max_size);
+        }
+
+        data.extend_from_slice(&chunk[..count]);
+        progress(data.len() as u64, total.max(data.len() as u64));
+    }
+
+    Ok(data)
+}
+
+/// Create a buffer from a file on disk.
+///
+/// The file is read when the action is created, not when it's applied, so
+/// undo and redo always get the same data even if the file changes. The path
+/// is saved with the buffer - see [`H2Buffer::source`].
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionBufferCreateFromFile(State);
+
+impl ActionBufferCreateFromFile {
+    /// Read the file, up to [`DEFAULT_MAX_FILE_SIZE`] bytes.
+    pub fn new(name: &str, path: impl AsRef<Path>, base_address: usize) -> SimpleResult<Action> {
+        Self::new_with_progress(name, path, base_address, DEFAULT_MAX_FILE_SIZE, |_, _| ())
+    }
+
+    /// Read the file, calling `progress` with `(bytes_read, total)` as it
+    /// goes. Files bigger than `max_size` are an error.
+    pub fn new_with_progress(name: &str, path: impl AsRef<Path>, base_address: usize, max_size: u64, progress: impl FnMut(u64, u64)) -> SimpleResult<Action> {
+        let path = path.as_ref();
+        let data = read_file(path, max_size, progress)?;
+
+        Ok(Action::BufferCreateFromFile(
+            ActionBufferCreateFromFile(
+                State::Forward(Forward {
+                    name: String::from(name),
+                    path: path.to_path_buf(),
+                    data: data,
+                    base_address: base_address,
+                    id: None,
+                })
+            )
+        ))
+    }
+
+    pub fn description(&self) -> String {
+        let (name, path) = match &self.0 {
+            State::Forward(f)  => (&f.name, &f.path),
+            State::Backward(b) => (&b.name, &b.path),
+        };
+
+        format!("Create buffer '{}' from {}", name, path.display())
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Buffer
+    }
+}
+
+impl Command for ActionBufferCreateFromFile {
+    type Target = H2Project;
+    type Error = SimpleError;
+
+    fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the forward struct
+        let forward = match &self.0 {
+            State::Forward(f) => f,
+            _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
+        };
+
+        let mut buffer = H2Buffer::new(&forward.name, forward.data.clone(), forward.base_address)?;
+        buffer.set_source(Some(forward.path.clone()));
+        if let Some(id) = forward.id {
+            buffer.set_id(id);
+        }
+        let id = project.buffer_insert(&forward.name, buffer)?;
+
+        // Save the backward struct
+        self.0 = State::Backward(Backward {
+            name: forward.name.to_string(),
+            path: forward.path.clone(),
+            id: id,
+        });
+
+        Ok(())
+    }
+
+    fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the backward struct
+        let backward = match &self.0 {
+            State::Backward(b) => b,
+            _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
+        };
+
+        // Find it by ID, in case the name has changed
+        let name = project.buffer_name(backward.id).ok_or(
+            SimpleError::new(format!("Could not find buffer with ID {}", backward.id))
+        )?.to_string();
+        let buffer = project.buffer_remove(&name)?;
+        let id = buffer.id();
+
+        // Save the forward struct
+        self.0 = State::Forward(Forward {
+            name: name,
+            path: backward.path.clone(),
+            data: buffer.data,
+            base_address: buffer.base_address,
+            id: Some(id),
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+
+    use crate::project::H2Project;
+    use redo::Record;
+    use pretty_assertions::assert_eq;
+
+    fn test_file(name: &str, data: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("h2gb-buffer-{}-{}", std::process::id(), name));
+        std::fs::write(&path, data).unwrap();
+
+        path
+    }
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let path = test_file("action", b"\x00\x01\x02\x03");
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+
+        record.apply(ActionBufferCreateFromFile::new("buffer", &path, 0x100)?)?;
+        let buffer = record.target().buffer_get_or_err("buffer")?;
+        assert_eq!(b"\x00\x01\x02\x03".to_vec(), buffer.data);
+        assert_eq!(0x100, buffer.base_address);
+        assert_eq!(Some(path.as_path()), buffer.source());
+
+        record.undo()?;
+        assert!(!record.target().buffer_exists("buffer"));
+
+        // Redo doesn't need the file anymore
+        std::fs::remove_file(&path).unwrap();
+        record.redo()?;
+        assert_eq!(b"\x00\x01\x02\x03".to_vec(), record.target().buffer_get_or_err("buffer")?.data);
+        assert_eq!(Some(path.as_path()), record.target().buffer_get_or_err("buffer")?.source());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_progress() -> SimpleResult<()> {
+        let path = test_file("progress", &vec![0x41; CHUNK_SIZE * 2 + 1]);
+
+        let mut updates = vec![];
+        let action = ActionBufferCreateFromFile::new_with_progress("buffer", &path, 0, DEFAULT_MAX_FILE_SIZE, |read, total| updates.push((read, total)));
+        std::fs::remove_file(&path).unwrap();
+        action?;
+
+        let total = (CHUNK_SIZE * 2 + 1) as u64;
+        assert_eq!(total, updates.last().unwrap().0);
+        assert!(updates.iter().all(|(_, t)| *t == total));
+        assert!(updates.len() >= 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() -> SimpleResult<()> {
+        // Too big
+        let path = test_file("too-big", b"ABCDEFGH");
+        let result = ActionBufferCreateFromFile::new_with_progress("buffer", &path, 0, 4, |_, _| ());
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+
+        // Empty
+        let path = test_file("empty", b"");
+        let result = ActionBufferCreateFromFile::new("buffer", &path, 0);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+
+        // Missing, and the error mentions the path
+        let path = std::env::temp_dir().join("h2gb-this-file-does-not-exist");
+        match ActionBufferCreateFromFile::new("buffer", &path, 0) {
+            Ok(_)  => panic!("Loading a missing file should fail"),
+            Err(e) => assert!(e.to_string().contains("h2gb-this-file-does-not-exist")),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+use redo::{Command, Merge};
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use crate::project::H2Project;
+use crate::actions::{Action, ActionCategory};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Forward {
+    buffer: String,
+    offset: usize,
+    data: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Backward {
+    buffer: String,
+    offset: usize,
+    original_data: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum State {
+    Forward(Forward),
+    Backward(Backward),
+}
+
+/// Overwrite bytes in a buffer (a "patch").
+///
+/// Consecutive edits to the same buffer that touch or overlap each other are
+/// merged into a single undo step, so typing a run of bytes can be undone all
+/// at once.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionBufferEdit(State);
+
+impl ActionBufferEdit {
+    pub fn new(buffer: &str, offset: usize, data: Vec<u8>) -> Action {
+        Action::BufferEdit(
+            ActionBufferEdit(
+                State::Forward(Forward {
+                    buffer: buffer.to_string(),
+                    offset: offset,
+                    data: data,
+                })
+            )
+        )
+    }
+
+    pub fn description(&self) -> String {
+        let (buffer, offset, length) = match &self.0 {
+            State::Forward(f)  => (&f.buffer, f.offset, f.data.len()),
+            State::Backward(b) => (&b.buffer, b.offset, b.original_data.len()),
+        };
+
+        format!("Edit {} byte(s) @ 0x{:x} in buffer '{}'", length, offset, buffer)
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Buffer
+    }
+}
+
+impl Command for ActionBufferEdit {
+    type Target = H2Project;
+    type Error = SimpleError;
+
+    fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the forward struct
+        let forward = match &self.0 {
+            State::Forward(f) => f,
+            _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
+        };
+
+        let original_data = project
+            .buffer_get_mut_or_err(&forward.buffer)?
+            .edit(forward.data.clone(), forward.offset)?;
+
+        // Save the backward struct
+        self.0 = State::Backward(Backward {
+            buffer: forward.buffer.clone(),
+            offset: forward.offset,
+            original_data: original_data,
+        });
+
+        Ok(())
+    }
+
+    fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the backward struct
+        let backward = match &self.0 {
+            State::Backward(b) => b,
+            _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
+        };
+
+        // Editing the original data back gives us the data to redo with
+        let data = project
+            .buffer_get_mut_or_err(&backward.buffer)?
+            .edit(backward.original_data.clone(), backward.offset)?;
+
+        // Save the forward struct
+        self.0 = State::Forward(Forward {
+            buffer: backward.buffer.clone(),
+            offset: backward.offset,
+            data: data,
+        });
+
+        Ok(())
+    }
+
+    fn merge(&mut self, command: Self) -> Merge<Self> {
+        // Only merge two edits that have both been applied
+        let (first, second) = match (&mut self.0, &command.0) {
+            (State::Backward(first), State::Backward(second)) => (first, second),
+            _ => return Merge::No(command),
+        };
+
+        if first.buffer != second.buffer {
+            return Merge::No(command);
+        }
+
+        // They have to touch or overlap, so the result is one contiguous run
+        let first_range = first.offset..(first.offset + first.original_data.len());
+        let second_range = second.offset..(second.offset + second.original_data.len());
+        if second_range.start > first_range.end || second_range.end < first_range.start {
+            return Merge::No(command);
+        }
+
+        // The original data for the combined range comes from the first edit
+        // wherever it has it (since the second edit's "original" data there
+        // is really the first edit's data), and from the second everywhere
+        // else
+        let start = std::cmp::min(first_range.start, second_range.start);
+        let end = std::cmp::max(first_range.end, second_range.end);
+        let original_data: Vec<u8> = (start..end).map(|i| {
+            if first_range.contains(&i) {
+                first.original_data[i - first_range.start]
+            } else {
+                second.original_data[i - second_range.start]
+            }
+        }).collect();
+
+        first.offset = start;
+        first.original_data = original_data;
+
+        Merge::Yes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+    use simple_error::SimpleResult;
+
+    use generic_number::{IntegerReader, DefaultFormatter};
+    use h2datatype::simple::numeric::H2Integer;
+
+    use crate::project::H2Project;
+    use crate::actions::{ActionBufferCreateFromBytes, ActionLayerCreate, ActionEntryCreate};
+
+    fn data(record: &Record<Action>) -> Vec<u8> {
+        record.target().buffer_get("buffer").unwrap().data.clone()
+    }
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"ABCDEFGH", 0))?;
+
+        record.apply(ActionBufferEdit::new("buffer", 2, b"xy".to_vec()))?;
+        assert_eq!(b"ABxyEFGH".to_vec(), data(&record));
+
+        record.undo()?;
+        assert_eq!(b"ABCDEFGH".to_vec(), data(&record));
+
+        record.redo()?;
+        assert_eq!(b"ABxyEFGH".to_vec(), data(&record));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_merges() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"ABCDEFGH", 0))?;
+
+        // Type a few bytes in a row, going back over one of them
+        record.apply(ActionBufferEdit::new("buffer", 2, b"1".to_vec()))?;
+        record.apply(ActionBufferEdit::new("buffer", 3, b"2".to_vec()))?;
+        record.apply(ActionBufferEdit::new("buffer", 3, b"3".to_vec()))?;
+        record.apply(ActionBufferEdit::new("buffer", 1, b"45".to_vec()))?;
+        assert_eq!(b"A453EFGH".to_vec(), data(&record));
+
+        // This one isn't touching, so it's separate
+        record.apply(ActionBufferEdit::new("buffer", 6, b"9".to_vec()))?;
+        assert_eq!(b"A453EF9H".to_vec(), data(&record));
+
+        record.undo()?;
+        assert_eq!(b"A453EFGH".to_vec(), data(&record));
+
+        // One undo takes care of all the rest
+        record.undo()?;
+        assert_eq!(b"ABCDEFGH".to_vec(), data(&record));
+
+        record.redo()?;
+        assert_eq!(b"A453EFGH".to_vec(), data(&record));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_fails_on_entry() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"ABCDEFGH", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "default"))?;
+
+        let t = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+        let resolved = record.target().buffer_get_or_err("buffer")?.peek(&t, 4)?;
+        record.apply(ActionEntryCreate::new("buffer", "default", resolved, Some(t)))?;
+
+        assert!(record.apply(ActionBufferEdit::new("buffer", 3, b"xy".to_vec())).is_err());
+        assert!(record.apply(ActionBufferEdit::new("buffer", 8, b"x".to_vec())).is_err());
+        assert!(record.apply(ActionBufferEdit::new("buffer", 3, b"x".to_vec())).is_ok());
+
+        Ok(())
+    }
+}
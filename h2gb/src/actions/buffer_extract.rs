@@ -4,8 +4,8 @@ use simple_error::{SimpleResult, SimpleError, bail};
 use std::ops::Range;
 
 use crate::project::H2Project;
-use crate::project::H2Buffer;
-use crate::actions::Action;
+use crate::project::{H2Buffer, H2Id};
+use crate::actions::{Action, ActionCategory};
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Forward {
@@ -13,6 +13,10 @@ struct Forward {
     source_buffer: String,
     range: Range<usize>,
     base_address: usize,
+
+    // Set once the buffer has been created, so redo gets the same ID
+    #[serde(default)]
+    id: Option<H2Id>,
 }
 
 // TODO(ron) It'd be nice to store this with the buffer so we can get it without storing it here
@@ -21,6 +25,9 @@ struct Backward {
     name: String,
     source_buffer: String,
     range: Range<usize>,
+
+    #[serde(default)]
+    id: H2Id,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -41,10 +48,24 @@ impl ActionBufferExtract {
                     source_buffer: String::from(source_buffer),
                     range: range.clone(),
                     base_address: base_address,
+                    id: None,
                 })
             )
         )
     }
+
+    pub fn description(&self) -> String {
+        let (name, source_buffer, range) = match &self.0 {
+            State::Forward(f)  => (&f.name, &f.source_buffer, &f.range),
+            State::Backward(b) => (&b.name, &b.source_buffer, &b.range),
+        };
+
+        format!("Extract 0x{:x}..0x{:x} from buffer '{}' into buffer '{}'", range.start, range.end, source_buffer, name)
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Buffer
+    }
 }
 
 impl Command for ActionBufferExtract {
@@ -60,14 +81,18 @@ impl Command for ActionBufferExtract {
 
         let source = project.buffer_get(&forward.source_buffer).ok_or(SimpleError::new("Couldn't find buffer"))?;
         let data = source.byte_range(forward.range.clone())?;
-        let buffer = H2Buffer::new(&forward.name, data.to_vec(), forward.base_address)?;
-        project.buffer_insert(&forward.name, buffer)?;
+        let mut buffer = H2Buffer::new(&forward.name, data.to_vec(), forward.base_address)?;
+        if let Some(id) = forward.id {
+            buffer.set_id(id);
+        }
+        let id = project.buffer_insert(&forward.name, buffer)?;
 
         // Save the backward struct
         self.0 = State::Backward(Backward {
             name: forward.name.to_string(),
             source_buffer: forward.source_buffer.to_string(),
             range: forward.range.clone(),
+            id: id,
         });
 
         Ok(())
@@ -80,16 +105,20 @@ impl Command for ActionBufferExtract {
             _                    => bail!("Failed to undo: action ended up in a broken undo/redo state"),
         };
 
-        // Do stuff with it
-        let name = &backward.name;
-        let buffer = project.buffer_remove(name)?;
+        // Find it by ID, in case the name has changed
+        let name = project.buffer_name(backward.id).ok_or(
+            SimpleError::new(format!("Could not find buffer with ID {}", backward.id))
+        )?.to_string();
+        let buffer = project.buffer_remove(&name)?;
+        let id = buffer.id();
 
         // Save the forward struct
         self.0 = State::Forward(Forward {
-            name: name.clone(),
+            name: name,
             source_buffer: backward.source_buffer.clone(),
             range: backward.range.clone(),
             base_address: buffer.base_address,
+            id: Some(id),
         });
 
         Ok(())
@@ -0,0 +1,297 @@
+//! Re-read a buffer from the file it was created from.
+
+use redo::Command;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use std::path::PathBuf;
+
+use crate::project::{H2Buffer, H2Entry, H2Id, H2Project};
+use crate::actions::{Action, ActionCategory};
+use super::buffer_create_from_file::{read_file, DEFAULT_MAX_FILE_SIZE};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Forward {
+    buffer: String,
+    path: PathBuf,
+    data: Vec<u8>,
+}
+
+/// What had to be taken out of a layer for the new data to fit.
+#[derive(Serialize, Deserialize, Debug)]
+struct Removed {
+    layer: String,
+    entries: Vec<H2Entry>,
+    comments: Vec<(usize, String)>,
+    bookmarks: Vec<(usize, String)>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Backward {
+    buffer: String,
+    path: PathBuf,
+    buffer_id: H2Id,
+
+    // The data from before the reload
+    data: Vec<u8>,
+    removed: Vec<Removed>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum State {
+    Forward(Forward),
+    Backward(Backward),
+}
+
+/// Reload a buffer from its source file, for when the file has been
+/// regenerated.
+///
+/// Entries over bytes that didn't change are kept. Entries that overlap
+/// anything that changed are removed, since they wouldn't match the data
+/// anymore - use [`H2Buffer::entries_invalidated_by`] to find out which ones
+/// that'll be. If the file got shorter, comments, bookmarks, and points past
+/// the new end are removed too. Undo puts everything back.
+///
+/// Like [`crate::actions::ActionBufferCreateFromFile`], the file is read when
+/// the action is created.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionBufferReload(State);
+
+impl ActionBufferReload {
+    pub fn new(project: &H2Project, buffer: &str) -> SimpleResult<Action> {
+        let path = match project.buffer_get_or_err(buffer)?.source() {
+            Some(path) => path.to_path_buf(),
+            None       => bail!("Buffer {} wasn't loaded from a file, so it can't be reloaded", buffer),
+        };
+        let data = read_file(&path, DEFAULT_MAX_FILE_SIZE, |_, _| ())?;
+
+        Ok(Action::BufferReload(
+            ActionBufferReload(
+                State::Forward(Forward {
+                    buffer: buffer.to_string(),
+                    path: path,
+                    data: data,
+                })
+            )
+        ))
+    }
+
+    pub fn description(&self) -> String {
+        match &self.0 {
+            State::Forward(f)  => format!("Reload buffer '{}' from {}", f.buffer, f.path.display()),
+            State::Backward(b) => {
+                let count: usize = b.removed.iter().map(|r| r.entries.len()).sum();
+                format!("Reload buffer '{}' from {} ({} entry(s) invalidated)", b.buffer, b.path.display(), count)
+            },
+        }
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Buffer
+    }
+}
+
+/// Put back whatever was removed.
+fn restore(buffer: &mut H2Buffer, removed: &[Removed]) -> SimpleResult<()> {
+    for removed in removed {
+        let layer = buffer.layer_get_mut_or_err(&removed.layer)?;
+
+        for entry in &removed.entries {
+            layer.entry_insert(entry.clone())?;
+        }
+
+        for (offset, comment) in &removed.comments {
+            layer.comment_set(*offset, Some(comment.clone()))?;
+        }
+
+        for (offset, name) in &removed.bookmarks {
+            layer.bookmark_set(*offset, Some(name.clone()))?;
+        }
+    }
+
+    Ok(())
+}
+
+impl Command for ActionBufferReload {
+    type Target = H2Project;
+    type Error = SimpleError;
+
+    fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the forward struct
+        let forward = match &self.0 {
+            State::Forward(f) => f,
+            _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
+        };
+
+        let mut buffer = project.buffer_get_mut_or_err(&forward.buffer)?;
+        let old_length = buffer.len();
+        let new_length = forward.data.len();
+
+        let invalidated: Vec<(String, H2Id)> = buffer.entries_invalidated_by(&forward.data)?.into_iter().map(|(layer, entry)| {
+            (layer.name().to_string(), entry.id())
+        }).collect();
+
+        let layer_names: Vec<String> = buffer.layer_names().into_iter().map(|name| name.to_string()).collect();
+
+        // Take out everything that won't match the new data
+        let mut removed: Vec<Removed> = vec![];
+        for layer_name in layer_names {
+            let layer = buffer.layer_get_mut_or_err(&layer_name)?;
+
+            let mut entries = vec![];
+            for (_, id) in invalidated.iter().filter(|(name, _)| *name == layer_name) {
+                entries.push(layer.entry_remove_by_id(*id)?);
+            }
+
+            let mut comments = vec![];
+            let mut bookmarks = vec![];
+            if new_length < old_length {
+                comments = layer.comments_get_with_offsets(new_length..old_length)?.into_iter().map(|(offset, comment)| (offset, comment.clone())).collect();
+                bookmarks = layer.bookmarks_get(new_length..old_length)?.into_iter().map(|(offset, name)| (offset, name.clone())).collect();
+            }
+
+            for (offset, _) in &comments {
+                layer.comment_set(*offset, None)?;
+            }
+
+            for (offset, _) in &bookmarks {
+                layer.bookmark_set(*offset, None)?;
+            }
+
+            removed.push(Removed {
+                layer: layer_name,
+                entries: entries,
+                comments: comments,
+                bookmarks: bookmarks,
+            });
+        }
+
+        // If the buffer won't take the data, put it all back
+        let data = match buffer.reload(forward.data.clone()) {
+            Ok(data) => data,
+            Err(e)   => {
+                restore(&mut buffer, &removed)?;
+                return Err(e);
+            },
+        };
+
+        // Save the backward struct
+        self.0 = State::Backward(Backward {
+            buffer: forward.buffer.clone(),
+            path: forward.path.clone(),
+            buffer_id: buffer.id(),
+            data: data,
+            removed: removed,
+        });
+
+        Ok(())
+    }
+
+    fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the backward struct
+        let backward = match &self.0 {
+            State::Backward(b) => b,
+            _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
+        };
+
+        // Find it by ID, in case the name has changed
+        let mut buffer = project.buffer_get_mut_by_id_or_err(backward.buffer_id)?;
+        let data = buffer.reload(backward.data.clone())?;
+        restore(&mut buffer, &backward.removed)?;
+
+        // Save the forward struct
+        self.0 = State::Forward(Forward {
+            buffer: buffer.name().to_string(),
+            path: backward.path.clone(),
+            data: data,
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+
+    use generic_number::{DefaultFormatter, Endian, IntegerReader};
+    use h2datatype::simple::numeric::H2Integer;
+
+    use crate::actions::*;
+
+    fn entry(record: &mut Record<Action>, reader: IntegerReader, offset: usize) -> SimpleResult<()> {
+        let datatype = H2Integer::new(reader, DefaultFormatter::new_integer());
+        let resolved = record.target().peek("buffer", &datatype, offset)?;
+        record.apply(ActionEntryCreate::new("buffer", "layer", resolved, Some(datatype)))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let path = std::env::temp_dir().join(format!("h2gb-reload-{}-action", std::process::id()));
+        std::fs::write(&path, b"\x01\x02\x03\x04\x05\x06\x07\x08").unwrap();
+
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromFile::new("buffer", &path, 0)?)?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        entry(&mut record, IntegerReader::U8, 0)?;
+        entry(&mut record, IntegerReader::U16(Endian::Big), 2)?;
+        entry(&mut record, IntegerReader::U8, 5)?;
+        record.apply(ActionEntrySetComment::new("buffer", "layer", 1, Some("kept".to_string())))?;
+        record.apply(ActionEntrySetComment::new("buffer", "layer", 6, Some("past the end".to_string())))?;
+
+        // Change the second entry, and chop off the end
+        std::fs::write(&path, b"\x01\x02\x03\xff\x05\x06").unwrap();
+        let action = ActionBufferReload::new(record.target(), "buffer")?;
+        std::fs::remove_file(&path).unwrap();
+
+        let buffer = record.target().buffer_get_or_err("buffer")?;
+        let invalidated = buffer.entries_invalidated_by(b"\x01\x02\x03\xff\x05\x06")?;
+        assert_eq!(1, invalidated.len());
+        assert_eq!(2..4, invalidated[0].1.resolved().actual_range);
+
+        record.apply(action)?;
+        let buffer = record.target().buffer_get_or_err("buffer")?;
+        let layer = buffer.layer_get_or_err("layer")?;
+        assert_eq!(b"\x01\x02\x03\xff\x05\x06".to_vec(), buffer.data);
+        assert_eq!("1", layer.entry_get_or_err(0)?.resolved().display);
+        assert!(layer.entry_get(2)?.is_none());
+        assert_eq!("6", layer.entry_get_or_err(5)?.resolved().display);
+        assert_eq!(Some(&"kept".to_string()), layer.comment_get(1)?);
+        assert_eq!(1, layer.comments_get_with_offsets(0..6)?.len());
+
+        // Undo brings back the old data and everything that was removed
+        record.undo()?;
+        let buffer = record.target().buffer_get_or_err("buffer")?;
+        let layer = buffer.layer_get_or_err("layer")?;
+        assert_eq!(8, buffer.len());
+        assert_eq!("772", layer.entry_get_or_err(2)?.resolved().display);
+        assert_eq!(Some(&"past the end".to_string()), layer.comment_get(6)?);
+
+        record.redo()?;
+        assert_eq!(6, record.target().buffer_get_or_err("buffer")?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_not_from_a_file() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"ABCD", 0))?;
+
+        assert!(ActionBufferReload::new(record.target(), "buffer").is_err());
+        assert!(ActionBufferReload::new(record.target(), "nobuffer").is_err());
+
+        Ok(())
+    }
+}
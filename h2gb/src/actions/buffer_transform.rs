@@ -3,7 +3,7 @@ use serde::{Serialize, Deserialize};
 use simple_error::{SimpleResult, SimpleError, bail};
 
 use crate::project::H2Project;
-use crate::actions::Action;
+use crate::actions::{Action, ActionCategory};
 use h2transformation::Transformation;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -38,6 +38,19 @@ impl ActionBufferTransform {
             )
         )
     }
+
+    pub fn description(&self) -> String {
+        let name = match &self.0 {
+            State::Forward(f)  => &f.name,
+            State::Backward(b) => &b.name,
+        };
+
+        format!("Transform buffer '{}'", name)
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Buffer
+    }
 }
 
 impl Command for ActionBufferTransform {
@@ -52,7 +65,7 @@ impl Command for ActionBufferTransform {
         };
 
         // Get a handle to the buffer
-        let buffer = match project.buffer_get_mut(&forward.name) {
+        let mut buffer = match project.buffer_get_mut(&forward.name) {
             Some(b) => b,
             None => bail!("Could not find buffer {} to transform", &forward.name),
         };
@@ -77,7 +90,7 @@ impl Command for ActionBufferTransform {
         };
 
         // Get a handle to the buffer
-        let buffer = match project.buffer_get_mut(&backward.name) {
+        let mut buffer = match project.buffer_get_mut(&backward.name) {
             Some(b) => b,
             None => bail!("Could not find buffer {} to under the transformation", &backward.name),
         };
@@ -0,0 +1,132 @@
+//! Change the project's configuration.
+//!
+//! See [`crate::project::H2Config`] for what can be configured. Entries that
+//! already exist aren't changed - the config only affects what's created
+//! afterwards.
+
+use redo::Command;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use crate::project::{H2Config, H2Project};
+use crate::actions::{Action, ActionCategory};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Forward {
+    config: H2Config,
+}
+
+// Backward is identical to forward (but holds the old config)
+type Backward = Forward;
+
+#[derive(Serialize, Deserialize, Debug)]
+enum State {
+    Forward(Forward),
+    Backward(Backward),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionConfigSet(State);
+
+impl ActionConfigSet {
+    pub fn new(config: H2Config) -> Action {
+        Action::ConfigSet(
+            ActionConfigSet(
+                State::Forward(Forward {
+                    config: config,
+                })
+            )
+        )
+    }
+
+    pub fn description(&self) -> String {
+        "Change project configuration".to_string()
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Other
+    }
+}
+
+impl Command for ActionConfigSet {
+    type Target = H2Project;
+    type Error = SimpleError;
+
+    fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the forward struct
+        let forward = match &self.0 {
+            State::Forward(f) => f,
+            _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
+        };
+
+        let old_config = project.config_set(forward.config);
+
+        // Save the backward struct
+        self.0 = State::Backward(Backward {
+            config: old_config,
+        });
+
+        Ok(())
+    }
+
+    fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the backward struct
+        let backward = match &self.0 {
+            State::Backward(b) => b,
+            _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
+        };
+
+        let config = project.config_set(backward.config);
+
+        // Save the forward struct
+        self.0 = State::Forward(Forward {
+            config: config,
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+    use simple_error::SimpleResult;
+
+    use generic_number::{Endian, IntegerReader, CharacterReader, CharacterFormatter};
+    use h2datatype::simple::string::LPString;
+
+    use crate::actions::ActionBufferCreateFromBytes;
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x0bHello World", 0))?;
+
+        let s = LPString::new(IntegerReader::U8, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?;
+        assert_eq!("\"Hello World\"", record.target().peek("buffer", &s, 0)?.display);
+        assert_eq!(Endian::Little, record.target().config().endian);
+
+        let config = H2Config {
+            endian: Endian::Big,
+            display_limit: Some(8),
+            ..*record.target().config()
+        };
+        record.apply(ActionConfigSet::new(config))?;
+        assert_eq!(Endian::Big, record.target().config().endian);
+        assert_eq!("\"Hell...", record.target().peek("buffer", &s, 0)?.display);
+
+        record.undo()?;
+        assert_eq!(Endian::Little, record.target().config().endian);
+        assert_eq!("\"Hello World\"", record.target().peek("buffer", &s, 0)?.display);
+
+        record.redo()?;
+        assert_eq!(Endian::Big, record.target().config().endian);
+
+        Ok(())
+    }
+}
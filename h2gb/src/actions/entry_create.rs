@@ -4,8 +4,8 @@ use simple_error::{SimpleResult, SimpleError, bail};
 
 use h2datatype::{H2Type, ResolvedType};
 
-use crate::actions::Action;
-use crate::project::H2Project;
+use crate::actions::{Action, ActionCategory, shorten};
+use crate::project::{H2Creator, H2Entry, H2Nesting, H2Project, H2Id, H2Provenance};
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Forward {
@@ -13,6 +13,18 @@ struct Forward {
     layer: String,
     resolved_type: ResolvedType,
     origin: Option<H2Type>,
+
+    // Set once the entry has been created, so redo gets the same ID
+    #[serde(default)]
+    id: Option<H2Id>,
+
+    #[serde(default)]
+    provenance: H2Provenance,
+
+    // How far the entry is shown as a tree - the project's default if it's
+    // not set
+    #[serde(default)]
+    nesting: Option<H2Nesting>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -20,6 +32,17 @@ struct Backward {
     buffer: String,
     layer: String,
     offset: usize,
+
+    // Only used for the description
+    #[serde(default)]
+    display: String,
+
+    #[serde(default)]
+    buffer_id: H2Id,
+    #[serde(default)]
+    layer_id: H2Id,
+    #[serde(default)]
+    id: H2Id,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -28,11 +51,37 @@ enum State {
     Backward(Backward),
 }
 
+/// Create an entry in a layer.
+///
+/// The entry's provenance (see [`crate::project::H2Provenance`]) is stamped
+/// when the action is created, so redo - and replaying a journal - gets the
+/// same creator and timestamp.
+///
+/// The entry's fields can be shown as child entries (see
+/// [`crate::project::H2Entry::children`]) - as far as the project's
+/// [`crate::project::H2Config::nesting`] says, or as far as
+/// [`ActionEntryCreate::new_nested`] says for this one entry.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ActionEntryCreate(State);
 
 impl ActionEntryCreate {
+    /// Create an entry made by the user.
     pub fn new(buffer: &str, layer: &str, resolved_type: ResolvedType, origin: Option<H2Type>) -> Action {
+        Self::new_with_creator(buffer, layer, resolved_type, origin, H2Creator::User)
+    }
+
+    /// Create an entry made by an analyzer or script.
+    pub fn new_with_creator(buffer: &str, layer: &str, resolved_type: ResolvedType, origin: Option<H2Type>, creator: H2Creator) -> Action {
+        Self::new_common(buffer, layer, resolved_type, origin, creator, None)
+    }
+
+    /// Like [`ActionEntryCreate::new_with_creator`], but the entry is shown
+    /// as a tree as far as `nesting` says, instead of the project's default.
+    pub fn new_nested(buffer: &str, layer: &str, resolved_type: ResolvedType, origin: Option<H2Type>, creator: H2Creator, nesting: H2Nesting) -> Action {
+        Self::new_common(buffer, layer, resolved_type, origin, creator, Some(nesting))
+    }
+
+    fn new_common(buffer: &str, layer: &str, resolved_type: ResolvedType, origin: Option<H2Type>, creator: H2Creator, nesting: Option<H2Nesting>) -> Action {
         Action::EntryCreate(
             ActionEntryCreate(
                 State::Forward(Forward {
@@ -40,10 +89,26 @@ impl ActionEntryCreate {
                     layer: layer.to_string(),
                     resolved_type: resolved_type,
                     origin: origin,
+                    id: None,
+                    provenance: H2Provenance::new(creator),
+                    nesting: nesting,
                 })
             )
         )
     }
+
+    pub fn description(&self) -> String {
+        let (buffer, offset, display) = match &self.0 {
+            State::Forward(f)  => (&f.buffer, f.resolved_type.actual_range.start as usize, &f.resolved_type.display),
+            State::Backward(b) => (&b.buffer, b.offset, &b.display),
+        };
+
+        format!("Create entry '{}' @ 0x{:x} in buffer '{}'", shorten(display), offset, buffer)
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Entry
+    }
 }
 
 impl Command for ActionEntryCreate {
@@ -57,17 +122,30 @@ impl Command for ActionEntryCreate {
             _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
         };
 
+        let id = match forward.id {
+            Some(id) => id,
+            None     => project.id_allocate(),
+        };
+        let nesting = forward.nesting.unwrap_or(project.config().nesting);
+
         // Create the entry
-        project
-            .buffer_get_mut_or_err(&forward.buffer)?
-            .layer_get_mut_or_err(&forward.layer)?
-            .entry_create(forward.resolved_type.clone(), forward.origin.clone())?;
+        let mut buffer = project.buffer_get_mut_or_err(&forward.buffer)?;
+        let buffer_id = buffer.id();
+
+        let layer = buffer.layer_get_mut_or_err(&forward.layer)?;
+        let mut entry = H2Entry::new(forward.resolved_type.clone(), forward.origin.clone(), id, forward.provenance.clone());
+        entry.set_nesting(nesting);
+        layer.entry_insert(entry)?;
 
         // Save the backward struct
         self.0 = State::Backward(Backward {
             buffer: forward.buffer.clone(),
             layer: forward.layer.clone(),
             offset: forward.resolved_type.actual_range.start as usize,
+            display: forward.resolved_type.display.clone(),
+            buffer_id: buffer_id,
+            layer_id: layer.id(),
+            id: id,
         });
 
         Ok(())
@@ -80,23 +158,29 @@ impl Command for ActionEntryCreate {
             _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
         };
 
-        project.buffer_get_mut(&backward.buffer).ok_or(
-            SimpleError::new(format!("Could not find buffer: {}", backward.buffer))
-        )?;
+        // Find everything by ID, in case the names have changed
+        let mut buffer = project.buffer_get_mut_by_id_or_err(backward.buffer_id)?;
+        let buffer_name = buffer.name().to_string();
+
+        let layer = buffer.layer_get_mut_by_id_or_err(backward.layer_id)?;
+        let layer_name = layer.name().to_string();
 
         // Remove the entry
-        let (resolved_type, origin) = project
-            .buffer_get_mut_or_err(&backward.buffer)?
-            .layer_get_mut_or_err(&backward.layer)?
-            .entry_remove(backward.offset)?
-            .ok_or(SimpleError::new("Could not remove entry: not found"))?;
+        let entry = layer.entry_remove_by_id(backward.id)?;
+        let id = entry.id();
+        let provenance = entry.provenance().clone();
+        let nesting = entry.nesting();
+        let (resolved_type, origin) = entry.split_up();
 
         // Save the backward struct
         self.0 = State::Forward(Forward {
-            buffer: backward.buffer.clone(),
-            layer: backward.layer.clone(),
+            buffer: buffer_name,
+            layer: layer_name,
             resolved_type: resolved_type,
             origin: origin,
+            id: Some(id),
+            provenance: provenance,
+            nesting: Some(nesting),
         });
 
         Ok(())
@@ -107,11 +191,15 @@ impl Command for ActionEntryCreate {
 mod tests {
     use super::*;
 
+    use std::ops::Range;
+
     use redo::Record;
     use pretty_assertions::assert_eq;
 
-    use crate::actions::{Action, ActionBufferCreateFromBytes, ActionLayerCreate};
+    use crate::actions::{Action, ActionBufferCreateFromBytes, ActionConfigSet, ActionLayerCreate};
 
+    use h2datatype::composite::{H2Array, H2Struct};
+    use h2datatype::simple::H2Marker;
     use h2datatype::simple::numeric::H2Integer;
     use h2datatype::simple::string::LPString;
 
@@ -316,4 +404,223 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_action_keeps_id() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &b"\x01\x02\x03\x04".to_vec(), 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "default"))?;
+
+        let datatype = H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer());
+        let resolved = record.target().buffer_get_or_err("buffer")?.peek(&datatype, 2)?;
+        record.apply(ActionEntryCreate::new("buffer", "default", resolved, None))?;
+
+        let id = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("default")?.entry_get(2)?.unwrap().id();
+        assert!(id.is_assigned());
+
+        record.undo()?;
+        assert!(record.target().buffer_get_or_err("buffer")?.layer_get_or_err("default")?.entry_get_by_id(id).is_none());
+
+        record.redo()?;
+        let entry = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("default")?.entry_get_by_id(id).unwrap();
+        assert_eq!(2..4, entry.resolved().actual_range);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_create_point() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &b"\x01\x02\x03\x04".to_vec(), 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "default"))?;
+
+        // A regular entry, with two markers at its start and one at the very end
+        let datatype = H2Integer::new(IntegerReader::U32(Endian::Big), DefaultFormatter::new_integer());
+        let resolved = record.target().buffer_get_or_err("buffer")?.peek(&datatype, 0)?;
+        record.apply(ActionEntryCreate::new("buffer", "default", resolved, None))?;
+
+        for (label, offset) in vec![("Start", 0), ("Header", 0), ("End", 4)] {
+            let marker = H2Marker::new(label);
+            let resolved = record.target().buffer_get_or_err("buffer")?.peek(&marker, offset)?;
+            record.apply(ActionEntryCreate::new("buffer", "default", resolved, Some(marker)))?;
+        }
+
+        let labels = |record: &Record<Action>, range: Range<usize>| -> SimpleResult<Vec<(usize, String)>> {
+            Ok(record.target().buffer_get_or_err("buffer")?.layer_get_or_err("default")?.points_get(range)?.into_iter().map(|(offset, point)| {
+                (offset, point.resolved().as_string.clone().unwrap())
+            }).collect())
+        };
+        assert_eq!(vec![(0, "Start".to_string()), (0, "Header".to_string()), (4, "End".to_string())], labels(&record, 0..5)?);
+        assert_eq!(vec![(0, "Start".to_string()), (0, "Header".to_string())], labels(&record, 0..4)?);
+
+        // The regular entry isn't affected
+        let layer = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("default")?;
+        assert_eq!(0..4, layer.entry_get(0)?.unwrap().resolved().actual_range);
+        assert_eq!(2, layer.point_get(0)?.len());
+        assert_eq!(0, layer.point_get(2)?.len());
+        assert!(layer.point_get(5).is_err());
+
+        // Undo removes just the one point
+        record.undo()?;
+        record.undo()?;
+        assert_eq!(vec![(0, "Start".to_string())], labels(&record, 0..5)?);
+        record.redo()?;
+        assert_eq!(vec![(0, "Start".to_string()), (0, "Header".to_string())], labels(&record, 0..5)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_provenance() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &b"\x01\x02\x03\x04".to_vec(), 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "default"))?;
+
+        let datatype = H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer());
+        let resolved = record.target().buffer_get_or_err("buffer")?.peek(&datatype, 0)?;
+        record.apply(ActionEntryCreate::new("buffer", "default", resolved, None))?;
+
+        let resolved = record.target().buffer_get_or_err("buffer")?.peek(&datatype, 2)?;
+        record.apply(ActionEntryCreate::new_with_creator("buffer", "default", resolved, None, H2Creator::analyzer("test")))?;
+
+        let layer = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("default")?;
+        assert!(layer.entry_get_or_err(0)?.provenance().is_user());
+        let provenance = layer.entry_get_or_err(2)?.provenance().clone();
+        assert_eq!(&H2Creator::analyzer("test"), provenance.creator());
+        assert!(provenance.created().is_some());
+
+        let found: Vec<usize> = layer.entries_by_creator(&H2Creator::analyzer("test")).iter().map(|e| e.resolved().actual_range.start as usize).collect();
+        assert_eq!(vec![2], found);
+        assert_eq!(1, layer.entries_by_creator(&H2Creator::User).len());
+        assert_eq!(0, layer.entries_by_creator(&H2Creator::analyzer("other")).len());
+
+        // Redo brings back the same provenance
+        record.undo()?;
+        record.redo()?;
+        let layer = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("default")?;
+        assert_eq!(&provenance, layer.entry_get_or_err(2)?.provenance());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_create_nested() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &b"\x01\x02\x03\x04\x05\x06".to_vec(), 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "default"))?;
+
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+        let datatype = H2Struct::new(vec![
+            ("a".to_string(), u8.clone()),
+            ("b".to_string(), H2Array::new(2, u8.clone())?),
+        ])?;
+
+        // The same struct, flat and nested
+        let resolved = record.target().buffer_get_or_err("buffer")?.peek(&datatype, 0)?;
+        record.apply(ActionEntryCreate::new("buffer", "default", resolved, Some(datatype.clone())))?;
+        let resolved = record.target().buffer_get_or_err("buffer")?.peek(&datatype, 3)?;
+        record.apply(ActionEntryCreate::new_nested("buffer", "default", resolved, Some(datatype.clone()), H2Creator::User, H2Nesting::full()))?;
+
+        let layer = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("default")?;
+        let flat = layer.entry_get_or_err(0)?;
+        assert!(!flat.is_nested());
+        assert_eq!(0, flat.children().len());
+
+        let nested = layer.entry_get_or_err(3)?;
+        assert!(nested.is_nested());
+
+        let children = nested.children();
+        assert_eq!(vec![
+            (3..4, Some("a".to_string()), "4".to_string()),
+            (4..6, Some("b".to_string()), "[ 5, 6 ]".to_string()),
+        ], children.iter().map(|c| (c.resolved().actual_range.clone(), c.resolved().field_name.clone(), c.resolved().display.clone())).collect::<Vec<_>>());
+
+        // Nested all the way down, with nothing below the leaves
+        assert!(children[1].is_nested());
+        assert_eq!(vec!["5", "6"], children[1].children().iter().map(|c| c.resolved().display.clone()).collect::<Vec<_>>());
+        assert_eq!(0, children[0].children().len());
+        assert!(!children[0].id().is_assigned());
+
+        // The children go with the entry, and come back with it
+        record.undo()?;
+        assert!(record.target().buffer_get_or_err("buffer")?.layer_get_or_err("default")?.entry_get(3)?.is_none());
+        record.redo()?;
+        let nested = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("default")?.entry_get_or_err(3)?;
+        assert!(nested.is_nested());
+        assert_eq!(2, nested.children().len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_create_nesting_depth() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &b"\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c".to_vec(), 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "default"))?;
+
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+        let datatype = H2Struct::new(vec![
+            ("a".to_string(), u8.clone()),
+            ("b".to_string(), H2Array::new(2, u8.clone())?),
+        ])?;
+
+        // Each entry's fields, and their fields, as "name=display"
+        let tree = |record: &Record<Action>, offset: usize| -> SimpleResult<Vec<Vec<String>>> {
+            let entry = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("default")?.entry_get_or_err(offset)?;
+
+            Ok(entry.children().iter().map(|child| {
+                let mut out = vec![format!("{}={}", child.resolved().field_name.clone().unwrap_or_default(), child.resolved().display)];
+                out.extend(child.children().iter().map(|c| c.resolved().display.clone()));
+                out
+            }).collect())
+        };
+
+        // One level, then two levels with compact arrays, then everything
+        for (offset, nesting) in [(0, H2Nesting::new(1, true)), (3, H2Nesting::new(2, false)), (6, H2Nesting::new(2, true))] {
+            let resolved = record.target().buffer_get_or_err("buffer")?.peek(&datatype, offset)?;
+            record.apply(ActionEntryCreate::new_nested("buffer", "default", resolved, Some(datatype.clone()), H2Creator::User, nesting))?;
+        }
+
+        assert_eq!(vec![vec!["a=1"], vec!["b=[ 2, 3 ]"]], tree(&record, 0)?);
+        assert_eq!(vec![vec!["a=4"], vec!["b=[ 5, 6 ]"]], tree(&record, 3)?);
+        assert_eq!(vec![vec!["a=7"], vec!["b=[ 8, 9 ]", "8", "9"]], tree(&record, 6)?);
+
+        // An array at the top is kept compact too
+        let array = H2Array::new(2, u8.clone())?;
+        let resolved = record.target().buffer_get_or_err("buffer")?.peek(&array, 9)?;
+        record.apply(ActionEntryCreate::new_nested("buffer", "default", resolved, Some(array.clone()), H2Creator::User, H2Nesting::new(1, false)))?;
+        assert_eq!(0, tree(&record, 9)?.len());
+        record.undo()?;
+
+        // Without a nesting, the project's default is used, and kept through
+        // undo and redo
+        let mut config = *record.target().config();
+        config.nesting = H2Nesting::new(1, false);
+        record.apply(ActionConfigSet::new(config))?;
+
+        let resolved = record.target().buffer_get_or_err("buffer")?.peek(&datatype, 9)?;
+        record.apply(ActionEntryCreate::new("buffer", "default", resolved, Some(datatype.clone())))?;
+        assert_eq!(vec![vec!["a=10"], vec!["b=[ 11, 12 ]"]], tree(&record, 9)?);
+
+        record.undo()?;
+        record.redo()?;
+        assert_eq!(H2Nesting::new(1, false), record.target().buffer_get_or_err("buffer")?.layer_get_or_err("default")?.entry_get_or_err(9)?.nesting());
+
+        Ok(())
+    }
 }
@@ -57,7 +57,7 @@ impl Command for ActionEntryCreateAndInsert {
         };
 
         // Create the entry and saved the ResolvedType
-        let buffer = match project.buffer_get_mut(&forward.buffer) {
+        let mut buffer = match project.buffer_get_mut(&forward.buffer) {
             Some(b) => b,
             None => bail!("No such buffer: {}", forward.buffer),
         };
@@ -82,7 +82,7 @@ impl Command for ActionEntryCreateAndInsert {
         };
 
         // Get a handle to the buffer
-        let buffer = match project.buffer_get_mut(&backward.buffer) {
+        let mut buffer = match project.buffer_get_mut(&backward.buffer) {
             Some(b) => b,
             None => bail!("No such buffer: {}", backward.buffer),
         };
@@ -0,0 +1,471 @@
+use redo::Command;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use generic_number::Context;
+use h2datatype::Offset;
+
+use crate::actions::{Action, ActionCategory, shorten};
+use crate::project::{H2Buffer, H2Entry, H2Id, H2Project};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Forward {
+    buffer: String,
+    layer: String,
+    offset: usize,
+    path: String,
+    value: String,
+
+    #[serde(default)]
+    relocate: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Backward {
+    buffer: String,
+    layer: String,
+    offset: usize,
+    path: String,
+    value: String,
+
+    #[serde(default)]
+    relocate: bool,
+
+    buffer_id: H2Id,
+    layer_id: H2Id,
+
+    // Where the bytes were written, and what was there before
+    field_offset: usize,
+    original_data: Vec<u8>,
+
+    // How much was written in its place (only different when relocating)
+    #[serde(default)]
+    written_length: usize,
+
+    // The entry as it was, so undo can put it back exactly
+    original_entry: H2Entry,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum State {
+    Forward(Forward),
+    Backward(Backward),
+}
+
+/// Set a field of an entry to a new value - for example, set `health.max` to
+/// `500`.
+///
+/// The entry is found by any offset inside it, and the field by a path (see
+/// [`h2datatype::H2Type::field`]); an empty path is the whole entry. The
+/// value is typed the way a user would type it, and is converted to bytes by
+/// the field's type (see [`h2datatype::H2Type::encode`]), so it's written
+/// with the right size and endian. Afterwards, the entry is resolved again so
+/// it shows the new value.
+///
+/// The entry needs to know its type, and the new bytes need to be the same
+/// size as the old ones. Since the bytes change, no other layer can have an
+/// entry on top of the field (just like [`crate::actions::ActionBufferEdit`]).
+///
+/// [`ActionEntryEdit::new_relocating`] lifts the size restriction: a value
+/// that grows or shrinks (like a length-prefixed string) is spliced in, and
+/// every annotation after it moves along (see [`crate::project::H2Relocation`]).
+/// If anything overlaps the field - an entry in another layer, a comment in
+/// the middle of it - the edit fails and lists them; use
+/// [`H2Buffer::relocation_check`] to find out beforehand.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionEntryEdit(State);
+
+impl ActionEntryEdit {
+    pub fn new(buffer: &str, layer: &str, offset: usize, path: &str, value: &str) -> Action {
+        Self::new_common(buffer, layer, offset, path, value, false)
+    }
+
+    /// Like [`ActionEntryEdit::new`], but the value can change size.
+    pub fn new_relocating(buffer: &str, layer: &str, offset: usize, path: &str, value: &str) -> Action {
+        Self::new_common(buffer, layer, offset, path, value, true)
+    }
+
+    fn new_common(buffer: &str, layer: &str, offset: usize, path: &str, value: &str, relocate: bool) -> Action {
+        Action::EntryEdit(
+            ActionEntryEdit(
+                State::Forward(Forward {
+                    buffer: buffer.to_string(),
+                    layer: layer.to_string(),
+                    offset: offset,
+                    path: path.to_string(),
+                    value: value.to_string(),
+                    relocate: relocate,
+                })
+            )
+        )
+    }
+
+    pub fn description(&self) -> String {
+        let (buffer, offset, path, value) = match &self.0 {
+            State::Forward(f)  => (&f.buffer, f.offset, &f.path, &f.value),
+            State::Backward(b) => (&b.buffer, b.offset, &b.path, &b.value),
+        };
+
+        match path.is_empty() {
+            true  => format!("Set entry @ 0x{:x} to {} in buffer '{}'", offset, shorten(value), buffer),
+            false => format!("Set {} @ 0x{:x} to {} in buffer '{}'", path, offset, shorten(value), buffer),
+        }
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Entry
+    }
+}
+
+/// Write `data` over `length` bytes at `offset`, moving everything after it
+/// if the size changes.
+fn write(buffer: &mut H2Buffer, data: Vec<u8>, offset: usize, length: usize, relocate: bool) -> SimpleResult<Vec<u8>> {
+    match relocate {
+        true  => buffer.splice(data, offset, length),
+        false => buffer.edit(data, offset),
+    }
+}
+
+/// Put an entry back the way it was.
+fn restore(buffer: &mut H2Buffer, layer_id: H2Id, entry: &H2Entry) -> SimpleResult<()> {
+    buffer.layer_get_mut_by_id_or_err(layer_id)?.entry_insert(entry.clone())
+}
+
+impl Command for ActionEntryEdit {
+    type Target = H2Project;
+    type Error = SimpleError;
+
+    fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the forward struct
+        let forward = match &self.0 {
+            State::Forward(f) => f,
+            _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
+        };
+
+        let mut buffer = project.buffer_get_mut_or_err(&forward.buffer)?;
+        let buffer_id = buffer.id();
+        let layer = buffer.layer_get_or_err(&forward.layer)?;
+        let layer_id = layer.id();
+
+        let entry = layer.entry_get_or_err(forward.offset)?;
+        let origin = match entry.origin() {
+            Some(origin) => origin.clone(),
+            None         => bail!("Can't edit entry @ 0x{:x}: it doesn't have a type", forward.offset),
+        };
+        let start = entry.resolved().actual_range.start as usize;
+
+        // Work out what to write, and where
+        let (field_offset, data, size) = {
+            let offset = Offset::Dynamic(Context::new(&buffer.data).at(start as u64));
+            let (field, field_offset) = origin.field(offset, &forward.path)?;
+
+            let data = field.encode(field_offset, &forward.value)?;
+            let size = field.actual_size(field_offset)?;
+            if data.len() as u64 != size && !forward.relocate {
+                bail!("Can't set {:?} to {}: the new value is {} byte(s), but the old one is {}", forward.path, forward.value, data.len(), size);
+            }
+
+            (field_offset.position() as usize, data, size as usize)
+        };
+        let written_length = data.len();
+
+        // Take the entry out of the way so the bytes under it can change
+        let original_entry = buffer.layer_get_mut_by_id_or_err(layer_id)?.entry_remove_by_id(entry.id())?;
+
+        let original_data = match write(&mut buffer, data, field_offset, size, forward.relocate) {
+            Ok(original_data) => original_data,
+            Err(e) => {
+                restore(&mut buffer, layer_id, &original_entry)?;
+                return Err(e);
+            },
+        };
+
+        // Resolve it again - the new value might change the entry's shape
+        // (if it's a length, say), so it might not fit anymore. This goes
+        // through the project, so it's resolved the same way it was created
+        let result = original_entry.resolve_again(original_entry.provenance().clone(), |origin| project.peek(&forward.buffer, origin, start)).and_then(|entry| {
+            project.buffer_get_mut_or_err(&forward.buffer)?.layer_get_mut_by_id_or_err(layer_id)?.entry_insert(entry)
+        });
+
+        if let Err(e) = result {
+            let mut buffer = project.buffer_get_mut_or_err(&forward.buffer)?;
+            write(&mut buffer, original_data, field_offset, written_length, forward.relocate)?;
+            restore(&mut buffer, layer_id, &original_entry)?;
+            return Err(e);
+        }
+
+        // Save the backward struct
+        self.0 = State::Backward(Backward {
+            buffer: forward.buffer.clone(),
+            layer: forward.layer.clone(),
+            offset: forward.offset,
+            path: forward.path.clone(),
+            value: forward.value.clone(),
+            relocate: forward.relocate,
+
+            buffer_id: buffer_id,
+            layer_id: layer_id,
+
+            field_offset: field_offset,
+            original_data: original_data,
+            written_length: written_length,
+            original_entry: original_entry,
+        });
+
+        Ok(())
+    }
+
+    fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the backward struct
+        let backward = match &self.0 {
+            State::Backward(b) => b,
+            _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
+        };
+
+        // Find everything by ID, in case the names have changed
+        let mut buffer = project.buffer_get_mut_by_id_or_err(backward.buffer_id)?;
+        buffer.layer_get_mut_by_id_or_err(backward.layer_id)?.entry_remove_by_id(backward.original_entry.id())?;
+        write(&mut buffer, backward.original_data.clone(), backward.field_offset, backward.written_length, backward.relocate)?;
+        restore(&mut buffer, backward.layer_id, &backward.original_entry)?;
+
+        let layer = match buffer.layer_name(backward.layer_id) {
+            Some(layer) => layer.to_string(),
+            None        => bail!("Failed to undo: layer {} disappeared", backward.layer_id),
+        };
+
+        // Save the forward struct
+        self.0 = State::Forward(Forward {
+            buffer: buffer.name().to_string(),
+            layer: layer,
+            offset: backward.offset,
+            path: backward.path.clone(),
+            value: backward.value.clone(),
+            relocate: backward.relocate,
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+    use simple_error::SimpleResult;
+
+    use generic_number::{CharacterReader, CharacterFormatter, DefaultFormatter, Endian, IntegerReader};
+    use h2datatype::H2Type;
+    use h2datatype::composite::H2Struct;
+    use h2datatype::simple::H2Enum;
+    use h2datatype::simple::numeric::{H2Character, H2Integer};
+    use h2datatype::simple::string::LPString;
+
+    use crate::actions::{ActionBufferCreateFromBytes, ActionLayerCreate, ActionEntryCreate, ActionEnumMemberRename};
+    use crate::project::H2Creator;
+
+    fn data(record: &Record<Action>) -> SimpleResult<Vec<u8>> {
+        Ok(record.target().buffer_get_or_err("buffer")?.data.clone())
+    }
+
+    fn display(record: &Record<Action>, layer: &str, offset: usize) -> SimpleResult<String> {
+        Ok(record.target().buffer_get_or_err("buffer")?.layer_get_or_err(layer)?.entry_get_or_err(offset)?.resolved().display.clone())
+    }
+
+    fn entry(record: &mut Record<Action>, layer: &str, datatype: H2Type, offset: usize) -> SimpleResult<()> {
+        let resolved = record.target().peek("buffer", &datatype, offset)?;
+        record.apply(ActionEntryCreate::new_with_creator("buffer", layer, resolved, Some(datatype), H2Creator::analyzer("test")))?;
+
+        Ok(())
+    }
+
+    fn player() -> SimpleResult<H2Type> {
+        H2Struct::new(vec![
+            ("health".to_string(), H2Struct::new(vec![
+                ("current".to_string(), H2Integer::new(IntegerReader::U16(Endian::Little), DefaultFormatter::new_integer())),
+                ("max".to_string(),     H2Integer::new(IntegerReader::U16(Endian::Little), DefaultFormatter::new_integer())),
+            ])?),
+            ("initial".to_string(), H2Character::new(CharacterReader::UTF8, CharacterFormatter::pretty_character())),
+        ])
+    }
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\xff\x64\x00\xc8\x00a\xff", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+        entry(&mut record, "layer", player()?, 1)?;
+        assert_eq!("{ health: { current: 100, max: 200 }, initial: 'a' }", display(&record, "layer", 1)?);
+
+        // Any offset in the entry works
+        let action = ActionEntryEdit::new("buffer", "layer", 3, "health.max", "500");
+        assert_eq!("Set health.max @ 0x3 to 500 in buffer 'buffer'", action.description());
+        record.apply(action)?;
+        assert_eq!(b"\xff\x64\x00\xf4\x01a\xff".to_vec(), data(&record)?);
+        assert_eq!("{ health: { current: 100, max: 500 }, initial: 'a' }", display(&record, "layer", 1)?);
+
+        // The entry is the same entry, just with a new value
+        let layer = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?;
+        assert_eq!(1, layer.entries_by_creator(&H2Creator::analyzer("test")).len());
+
+        record.apply(ActionEntryEdit::new("buffer", "layer", 1, "initial", "z"))?;
+        assert_eq!("{ health: { current: 100, max: 500 }, initial: 'z' }", display(&record, "layer", 1)?);
+
+        record.undo()?;
+        record.undo()?;
+        assert_eq!(b"\xff\x64\x00\xc8\x00a\xff".to_vec(), data(&record)?);
+        assert_eq!("{ health: { current: 100, max: 200 }, initial: 'a' }", display(&record, "layer", 1)?);
+
+        record.redo()?;
+        assert_eq!("{ health: { current: 100, max: 500 }, initial: 'a' }", display(&record, "layer", 1)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_uses_project() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x01\x02", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+        record.apply(ActionEnumMemberRename::new("TerrariaGameMode", 1, "Medium"))?;
+
+        let t = H2Struct::new(vec![
+            ("mode".to_string(),  H2Enum::new(IntegerReader::U8, "TerrariaGameMode")?),
+            ("level".to_string(), H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+        ])?;
+        entry(&mut record, "layer", t, 0)?;
+        assert_eq!("{ mode: TerrariaGameMode::Medium, level: 2 }", display(&record, "layer", 0)?);
+
+        // The project's name for the enum value is still used afterwards
+        record.apply(ActionEntryEdit::new("buffer", "layer", 0, "level", "5"))?;
+        assert_eq!("{ mode: TerrariaGameMode::Medium, level: 5 }", display(&record, "layer", 0)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_whole_entry() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x00\x00\x00\x00", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+        entry(&mut record, "layer", H2Integer::new(IntegerReader::I32(Endian::Big), DefaultFormatter::new_integer()), 0)?;
+
+        record.apply(ActionEntryEdit::new("buffer", "layer", 0, "", "-2"))?;
+        assert_eq!(b"\xff\xff\xff\xfe".to_vec(), data(&record)?);
+        assert_eq!("-2", display(&record, "layer", 0)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_fails() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\xff\x64\x00\xc8\x00a\xff", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+        record.apply(ActionLayerCreate::new("buffer", "other"))?;
+        entry(&mut record, "layer", player()?, 1)?;
+
+        // A type with no origin
+        let resolved = record.target().peek("buffer", &H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer()), 0)?;
+        record.apply(ActionEntryCreate::new("buffer", "layer", resolved, None))?;
+
+        let original = data(&record)?;
+        let bad = vec![
+            ActionEntryEdit::new("buffer", "layer", 0,  "",            "1"),     // No type
+            ActionEntryEdit::new("buffer", "layer", 1,  "health.nope", "1"),     // No field
+            ActionEntryEdit::new("buffer", "layer", 1,  "health.max",  "70000"), // Doesn't fit
+            ActionEntryEdit::new("buffer", "layer", 1,  "health",      "1"),     // Not writeable
+            ActionEntryEdit::new("buffer", "layer", 1,  "initial",     "❄"),     // Wrong size
+            ActionEntryEdit::new("buffer", "layer", 6,  "",            "1"),     // No entry
+            ActionEntryEdit::new("buffer", "nope",  1,  "health.max",  "1"),     // No layer
+        ];
+
+        for action in bad {
+            assert!(record.apply(action).is_err());
+            assert_eq!(original, data(&record)?);
+            assert_eq!("{ health: { current: 100, max: 200 }, initial: 'a' }", display(&record, "layer", 1)?);
+        }
+
+        // Another layer on top of the field
+        entry(&mut record, "other", H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer()), 4)?;
+        assert!(record.apply(ActionEntryEdit::new("buffer", "layer", 1, "health.max", "1")).is_err());
+        assert_eq!("{ health: { current: 100, max: 200 }, initial: 'a' }", display(&record, "layer", 1)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_relocating() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x02hi\x01\x02", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+        record.apply(ActionLayerCreate::new("buffer", "other"))?;
+
+        let string = LPString::new(IntegerReader::U8, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?;
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+        entry(&mut record, "layer", string, 0)?;
+        entry(&mut record, "layer", u8.clone(), 3)?;
+        entry(&mut record, "other", u8, 4)?;
+        let id = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?.entry_get_or_err(3)?.id();
+
+        // A normal edit can't change the size
+        assert!(record.apply(ActionEntryEdit::new("buffer", "layer", 0, "", "hello")).is_err());
+
+        // Relocating can, and everything after it moves along
+        record.apply(ActionEntryEdit::new_relocating("buffer", "layer", 0, "", "hello"))?;
+        assert_eq!(b"\x05hello\x01\x02".to_vec(), data(&record)?);
+        assert_eq!("\"hello\"", display(&record, "layer", 0)?);
+        assert_eq!("1", display(&record, "layer", 6)?);
+        assert_eq!("2", display(&record, "other", 7)?);
+        assert_eq!(id, record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?.entry_get_or_err(6)?.id());
+
+        record.undo()?;
+        assert_eq!(b"\x02hi\x01\x02".to_vec(), data(&record)?);
+        assert_eq!("\"hi\"", display(&record, "layer", 0)?);
+        assert_eq!("1", display(&record, "layer", 3)?);
+        assert_eq!("2", display(&record, "other", 4)?);
+
+        record.redo()?;
+        assert_eq!(b"\x05hello\x01\x02".to_vec(), data(&record)?);
+
+        // Shrinking works too
+        record.apply(ActionEntryEdit::new_relocating("buffer", "layer", 0, "", "a"))?;
+        assert_eq!(b"\x01a\x01\x02".to_vec(), data(&record)?);
+        assert_eq!("2", display(&record, "other", 3)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_relocating_invalidated() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x02hi\x01", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+        record.apply(ActionLayerCreate::new("buffer", "other"))?;
+
+        let string = LPString::new(IntegerReader::U8, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?;
+        entry(&mut record, "layer", string, 0)?;
+        entry(&mut record, "other", H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer()), 2)?;
+
+        // The other layer's entry is inside the string, so it can't move
+        assert!(record.apply(ActionEntryEdit::new_relocating("buffer", "layer", 0, "", "hello")).is_err());
+        assert_eq!(b"\x02hi\x01".to_vec(), data(&record)?);
+        assert_eq!("\"hi\"", display(&record, "layer", 0)?);
+        assert_eq!("105", display(&record, "other", 2)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+use redo::Command;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use h2datatype::{H2Type, ResolvedType};
+
+use crate::actions::{Action, ActionCategory, shorten};
+use crate::project::{H2Project, H2Id};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Forward {
+    buffer: String,
+    layer: String,
+    offset: usize,
+    name: String,
+    resolved_type: ResolvedType,
+    origin: Option<H2Type>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Backward {
+    buffer: String,
+    layer: String,
+    offset: usize,
+    name: String,
+
+    // Only used for the description
+    display: String,
+
+    buffer_id: H2Id,
+    layer_id: H2Id,
+    id: H2Id,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum State {
+    Forward(Forward),
+    Backward(Backward),
+}
+
+/// Give an entry another interpretation - another way of looking at the
+/// same bytes, like a second version of a header.
+///
+/// The entry is found by any offset inside it. The new interpretation isn't
+/// shown until it's selected (see
+/// [`crate::actions::ActionEntrySelectInterpretation`]); see
+/// [`crate::project::H2Entry::interpretations`] for the rules.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionEntryInterpret(State);
+
+impl ActionEntryInterpret {
+    pub fn new(buffer: &str, layer: &str, offset: usize, name: &str, resolved_type: ResolvedType, origin: Option<H2Type>) -> Action {
+        Action::EntryInterpret(
+            ActionEntryInterpret(
+                State::Forward(Forward {
+                    buffer: buffer.to_string(),
+                    layer: layer.to_string(),
+                    offset: offset,
+                    name: name.to_string(),
+                    resolved_type: resolved_type,
+                    origin: origin,
+                })
+            )
+        )
+    }
+
+    pub fn description(&self) -> String {
+        let (buffer, offset, name, display) = match &self.0 {
+            State::Forward(f)  => (&f.buffer, f.offset, &f.name, &f.resolved_type.display),
+            State::Backward(b) => (&b.buffer, b.offset, &b.name, &b.display),
+        };
+
+        format!("Interpret entry @ 0x{:x} as {} ({}) in buffer '{}'", offset, name, shorten(display), buffer)
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Entry
+    }
+}
+
+impl Command for ActionEntryInterpret {
+    type Target = H2Project;
+    type Error = SimpleError;
+
+    fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the forward struct
+        let forward = match &self.0 {
+            State::Forward(f) => f,
+            _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
+        };
+
+        let mut buffer = project.buffer_get_mut_or_err(&forward.buffer)?;
+        let buffer_id = buffer.id();
+
+        let layer = buffer.layer_get_mut_or_err(&forward.layer)?;
+        let layer_id = layer.id();
+
+        let entry = layer.entry_get_mut_or_err(forward.offset)?;
+        entry.interpretation_add(&forward.name, forward.resolved_type.clone(), forward.origin.clone())?;
+
+        // Save the backward struct
+        self.0 = State::Backward(Backward {
+            buffer: forward.buffer.clone(),
+            layer: forward.layer.clone(),
+            offset: forward.offset,
+            name: forward.name.clone(),
+            display: forward.resolved_type.display.clone(),
+
+            buffer_id: buffer_id,
+            layer_id: layer_id,
+            id: entry.id(),
+        });
+
+        Ok(())
+    }
+
+    fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the backward struct
+        let backward = match &self.0 {
+            State::Backward(b) => b,
+            _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
+        };
+
+        // Find everything by ID, in case the names have changed
+        let mut buffer = project.buffer_get_mut_by_id_or_err(backward.buffer_id)?;
+        let buffer_name = buffer.name().to_string();
+
+        let layer = buffer.layer_get_mut_by_id_or_err(backward.layer_id)?;
+        let layer_name = layer.name().to_string();
+
+        let interpretation = layer.entry_get_mut_by_id_or_err(backward.id)?.interpretation_remove(&backward.name)?;
+        let (resolved_type, origin) = interpretation.split_up();
+
+        // Save the forward struct
+        self.0 = State::Forward(Forward {
+            buffer: buffer_name,
+            layer: layer_name,
+            offset: backward.offset,
+            name: backward.name.clone(),
+            resolved_type: resolved_type,
+            origin: origin,
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+    use simple_error::SimpleResult;
+
+    use generic_number::{DefaultFormatter, Endian, IntegerReader};
+    use h2datatype::simple::numeric::H2Integer;
+
+    use crate::actions::{ActionBufferCreateFromBytes, ActionLayerCreate, ActionEntryCreate};
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x01\x02\x03\x04", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        let u32 = H2Integer::new(IntegerReader::U32(Endian::Big), DefaultFormatter::new_integer());
+        let u16 = H2Integer::new(IntegerReader::U16(Endian::Little), DefaultFormatter::new_integer());
+
+        let resolved = record.target().peek("buffer", &u32, 0)?;
+        record.apply(ActionEntryCreate::new("buffer", "layer", resolved, Some(u32)))?;
+
+        // Any offset in the entry works
+        let resolved = record.target().peek("buffer", &u16, 0)?;
+        let action = ActionEntryInterpret::new("buffer", "layer", 2, "short", resolved, Some(u16.clone()));
+        assert_eq!("Interpret entry @ 0x2 as short (513) in buffer 'buffer'", action.description());
+        record.apply(action)?;
+
+        let entry = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?.entry_get_or_err(0)?;
+        assert_eq!("16909060", entry.resolved().display);
+        assert_eq!(1, entry.interpretations().len());
+        assert_eq!("short", entry.interpretations()[0].name());
+        assert_eq!("513", entry.interpretations()[0].resolved().display);
+
+        // It takes up the whole entry, even though it's smaller
+        assert_eq!(0..2, entry.interpretations()[0].resolved().actual_range);
+        assert_eq!(0..4, entry.interpretations()[0].resolved().aligned_range);
+
+        // Can't use the same name twice, or start somewhere else
+        let resolved = record.target().peek("buffer", &u16, 0)?;
+        assert!(record.apply(ActionEntryInterpret::new("buffer", "layer", 0, "short", resolved.clone(), None)).is_err());
+        assert!(record.apply(ActionEntryInterpret::new("buffer", "layer", 0, "default", resolved, None)).is_err());
+
+        let resolved = record.target().peek("buffer", &u16, 2)?;
+        assert!(record.apply(ActionEntryInterpret::new("buffer", "layer", 0, "other", resolved, None)).is_err());
+
+        record.undo()?;
+        let entry = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?.entry_get_or_err(0)?;
+        assert_eq!(0, entry.interpretations().len());
+
+        record.redo()?;
+        let entry = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?.entry_get_or_err(0)?;
+        assert_eq!("513", entry.interpretations()[0].resolved().display);
+
+        Ok(())
+    }
+}
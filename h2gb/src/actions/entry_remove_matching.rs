@@ -0,0 +1,272 @@
+//! Remove every entry in a buffer that matches a filter.
+
+use redo::Command;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use crate::project::{H2Entry, H2EntryFilter, H2Id, H2Project};
+use crate::actions::{Action, ActionCategory};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Forward {
+    buffer: String,
+    filter: H2EntryFilter,
+}
+
+/// The entries taken out of a single layer.
+#[derive(Serialize, Deserialize, Debug)]
+struct Removed {
+    layer_id: H2Id,
+    entries: Vec<H2Entry>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Backward {
+    buffer: String,
+    filter: H2EntryFilter,
+    buffer_id: H2Id,
+    removed: Vec<Removed>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum State {
+    Forward(Forward),
+    Backward(Backward),
+}
+
+/// Remove every entry (and point) in a buffer that matches an
+/// [`H2EntryFilter`], as a single action.
+///
+/// This is mostly for re-running an analyzer: filter on its creator (see
+/// [`crate::project::H2Provenance`]) to clear out its old output, without
+/// touching anything made by hand. Comments and bookmarks are left alone.
+///
+/// Matching nothing isn't an error - the action just doesn't change anything.
+/// The filter is checked when the action is applied, so redo removes whatever
+/// matches at that point.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionEntryRemoveMatching(State);
+
+impl ActionEntryRemoveMatching {
+    pub fn new(buffer: &str, filter: H2EntryFilter) -> Action {
+        Action::EntryRemoveMatching(
+            ActionEntryRemoveMatching(
+                State::Forward(Forward {
+                    buffer: buffer.to_string(),
+                    filter: filter,
+                })
+            )
+        )
+    }
+
+    pub fn description(&self) -> String {
+        match &self.0 {
+            State::Forward(f)  => format!("Remove matching entries from buffer '{}'", f.buffer),
+            State::Backward(b) => {
+                let count: usize = b.removed.iter().map(|r| r.entries.len()).sum();
+                format!("Remove {} matching entry(s) from buffer '{}'", count, b.buffer)
+            },
+        }
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Entry
+    }
+}
+
+impl Command for ActionEntryRemoveMatching {
+    type Target = H2Project;
+    type Error = SimpleError;
+
+    fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the forward struct
+        let forward = match &self.0 {
+            State::Forward(f) => f,
+            _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
+        };
+
+        let mut buffer = project.buffer_get_mut_or_err(&forward.buffer)?;
+
+        // A filter on a layer that doesn't exist is probably a typo
+        if let Some(layer) = &forward.filter.layer {
+            buffer.layer_get_or_err(layer)?;
+        }
+
+        // Work out what to remove before removing anything
+        let mut matching: Vec<(H2Id, Vec<H2Id>)> = vec![];
+        for (layer, entry) in buffer.entries_matching(&forward.filter) {
+            match matching.last_mut() {
+                Some((layer_id, ids)) if *layer_id == layer.id() => ids.push(entry.id()),
+                _                                                => matching.push((layer.id(), vec![entry.id()])),
+            }
+        }
+
+        let mut removed: Vec<Removed> = vec![];
+        for (layer_id, ids) in matching {
+            let layer = buffer.layer_get_mut_by_id_or_err(layer_id)?;
+
+            removed.push(Removed {
+                layer_id: layer_id,
+                entries: ids.into_iter().map(|id| layer.entry_remove_by_id(id)).collect::<SimpleResult<Vec<H2Entry>>>()?,
+            });
+        }
+
+        // Save the backward struct
+        self.0 = State::Backward(Backward {
+            buffer: forward.buffer.clone(),
+            filter: forward.filter.clone(),
+            buffer_id: buffer.id(),
+            removed: removed,
+        });
+
+        Ok(())
+    }
+
+    fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the backward struct
+        let backward = match &self.0 {
+            State::Backward(b) => b,
+            _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
+        };
+
+        // Find everything by ID, in case the names have changed
+        let mut buffer = project.buffer_get_mut_by_id_or_err(backward.buffer_id)?;
+        for removed in &backward.removed {
+            let layer = buffer.layer_get_mut_by_id_or_err(removed.layer_id)?;
+
+            for entry in &removed.entries {
+                layer.entry_insert(entry.clone())?;
+            }
+        }
+
+        // Save the forward struct
+        self.0 = State::Forward(Forward {
+            buffer: buffer.name().to_string(),
+            filter: backward.filter.clone(),
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_error::SimpleResult;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+
+    use generic_number::{DefaultFormatter, IntegerReader};
+    use h2datatype::H2Type;
+    use h2datatype::simple::H2Marker;
+    use h2datatype::simple::numeric::H2Integer;
+
+    use crate::actions::*;
+    use crate::project::H2Creator;
+
+    fn entry(record: &mut Record<Action>, layer: &str, datatype: &H2Type, offset: usize, creator: H2Creator) -> SimpleResult<()> {
+        let resolved = record.target().peek("buffer", datatype, offset)?;
+        record.apply(ActionEntryCreate::new_with_creator("buffer", layer, resolved, Some(datatype.clone()), creator))?;
+
+        Ok(())
+    }
+
+    fn offsets(record: &Record<Action>, layer: &str) -> SimpleResult<Vec<usize>> {
+        Ok(record.target().buffer_get_or_err("buffer")?.layer_get_or_err(layer)?.entries_all().into_iter().map(|e| e.resolved().actual_range.start as usize).collect())
+    }
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x00\x01\x02\x03\x04\x05\x06\x07", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+        record.apply(ActionLayerCreate::new("buffer", "other"))?;
+
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+        let marker = H2Marker::new("here");
+
+        // An analyzer made a few entries and a point, and the user made some
+        for offset in 0..4 {
+            entry(&mut record, "layer", &u8, offset, H2Creator::analyzer("test"))?;
+        }
+        entry(&mut record, "layer", &marker, 2, H2Creator::analyzer("test"))?;
+        entry(&mut record, "layer", &u8, 4, H2Creator::User)?;
+        entry(&mut record, "other", &u8, 0, H2Creator::analyzer("test"))?;
+        record.apply(ActionEntrySetComment::new("buffer", "layer", 1, Some("kept".to_string())))?;
+
+        // Clear the analyzer's output from one layer, in one step
+        let filter = H2EntryFilter {
+            layer: Some("layer".to_string()),
+            creator: Some(H2Creator::analyzer("test")),
+            ..Default::default()
+        };
+        record.apply(ActionEntryRemoveMatching::new("buffer", filter))?;
+        assert_eq!(vec![4], offsets(&record, "layer")?);
+        assert_eq!(vec![0], offsets(&record, "other")?);
+        assert_eq!(Some(&"kept".to_string()), record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?.comment_get(1)?);
+
+        // One undo brings it all back, with the same IDs and provenance
+        record.undo()?;
+        assert_eq!(vec![0, 1, 2, 2, 3, 4], offsets(&record, "layer")?);
+        let layer = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?;
+        assert_eq!(5, layer.entries_by_creator(&H2Creator::analyzer("test")).len());
+
+        record.redo()?;
+        assert_eq!(vec![4], offsets(&record, "layer")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_filters() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x00\x01\x02\x03\x04\x05\x06\x07", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+        let marker = H2Marker::new("here");
+        for offset in 0..8 {
+            entry(&mut record, "layer", &u8, offset, H2Creator::User)?;
+        }
+        entry(&mut record, "layer", &marker, 4, H2Creator::User)?;
+
+        // By range - only what's entirely inside it
+        record.apply(ActionEntryRemoveMatching::new("buffer", H2EntryFilter {
+            range: Some(2..4),
+            ..Default::default()
+        }))?;
+        assert_eq!(vec![0, 1, 4, 4, 5, 6, 7], offsets(&record, "layer")?);
+
+        // By type
+        record.apply(ActionEntryRemoveMatching::new("buffer", H2EntryFilter {
+            type_name: Some("H2Marker".to_string()),
+            ..Default::default()
+        }))?;
+        assert_eq!(vec![0, 1, 4, 5, 6, 7], offsets(&record, "layer")?);
+
+        // Nothing matches, which is fine
+        record.apply(ActionEntryRemoveMatching::new("buffer", H2EntryFilter {
+            creator: Some(H2Creator::script("nope")),
+            ..Default::default()
+        }))?;
+        assert_eq!(6, offsets(&record, "layer")?.len());
+
+        // Everything
+        record.apply(ActionEntryRemoveMatching::new("buffer", H2EntryFilter::default()))?;
+        assert_eq!(0, offsets(&record, "layer")?.len());
+
+        // Bad buffer or layer
+        assert!(record.apply(ActionEntryRemoveMatching::new("nobuffer", H2EntryFilter::default())).is_err());
+        assert!(record.apply(ActionEntryRemoveMatching::new("buffer", H2EntryFilter {
+            layer: Some("nolayer".to_string()),
+            ..Default::default()
+        })).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+use redo::Command;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use crate::actions::{Action, ActionCategory};
+use crate::project::{H2Project, H2Id};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Forward {
+    buffer: String,
+    layer: String,
+    offset: usize,
+    name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Backward {
+    buffer: String,
+    layer: String,
+    offset: usize,
+    name: String,
+
+    buffer_id: H2Id,
+    layer_id: H2Id,
+    id: H2Id,
+
+    // The interpretation that was shown before
+    previous: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum State {
+    Forward(Forward),
+    Backward(Backward),
+}
+
+/// Show a different interpretation of an entry (see
+/// [`crate::project::H2Entry::interpretations`]).
+///
+/// The entry is found by any offset inside it. Use
+/// [`crate::project::DEFAULT_INTERPRETATION`] to go back to the original.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionEntrySelectInterpretation(State);
+
+impl ActionEntrySelectInterpretation {
+    pub fn new(buffer: &str, layer: &str, offset: usize, name: &str) -> Action {
+        Action::EntrySelectInterpretation(
+            ActionEntrySelectInterpretation(
+                State::Forward(Forward {
+                    buffer: buffer.to_string(),
+                    layer: layer.to_string(),
+                    offset: offset,
+                    name: name.to_string(),
+                })
+            )
+        )
+    }
+
+    pub fn description(&self) -> String {
+        let (buffer, offset, name) = match &self.0 {
+            State::Forward(f)  => (&f.buffer, f.offset, &f.name),
+            State::Backward(b) => (&b.buffer, b.offset, &b.name),
+        };
+
+        format!("Show entry @ 0x{:x} as {} in buffer '{}'", offset, name, buffer)
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Entry
+    }
+}
+
+impl Command for ActionEntrySelectInterpretation {
+    type Target = H2Project;
+    type Error = SimpleError;
+
+    fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the forward struct
+        let forward = match &self.0 {
+            State::Forward(f) => f,
+            _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
+        };
+
+        let mut buffer = project.buffer_get_mut_or_err(&forward.buffer)?;
+        let buffer_id = buffer.id();
+
+        let layer = buffer.layer_get_mut_or_err(&forward.layer)?;
+        let layer_id = layer.id();
+
+        let entry = layer.entry_get_mut_or_err(forward.offset)?;
+        let previous = entry.interpretation_select(&forward.name)?;
+
+        // Save the backward struct
+        self.0 = State::Backward(Backward {
+            buffer: forward.buffer.clone(),
+            layer: forward.layer.clone(),
+            offset: forward.offset,
+            name: forward.name.clone(),
+
+            buffer_id: buffer_id,
+            layer_id: layer_id,
+            id: entry.id(),
+
+            previous: previous,
+        });
+
+        Ok(())
+    }
+
+    fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the backward struct
+        let backward = match &self.0 {
+            State::Backward(b) => b,
+            _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
+        };
+
+        // Find everything by ID, in case the names have changed
+        let mut buffer = project.buffer_get_mut_by_id_or_err(backward.buffer_id)?;
+        let buffer_name = buffer.name().to_string();
+
+        let layer = buffer.layer_get_mut_by_id_or_err(backward.layer_id)?;
+        let layer_name = layer.name().to_string();
+
+        layer.entry_get_mut_by_id_or_err(backward.id)?.interpretation_select(&backward.previous)?;
+
+        // Save the forward struct
+        self.0 = State::Forward(Forward {
+            buffer: buffer_name,
+            layer: layer_name,
+            offset: backward.offset,
+            name: backward.name.clone(),
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+    use simple_error::SimpleResult;
+
+    use generic_number::{DefaultFormatter, Endian, IntegerReader};
+    use h2datatype::simple::numeric::H2Integer;
+
+    use crate::actions::{ActionBufferCreateFromBytes, ActionLayerCreate, ActionEntryCreate, ActionEntryEdit, ActionEntryInterpret};
+    use crate::project::{H2Project, DEFAULT_INTERPRETATION};
+
+    fn shown(record: &Record<Action>) -> SimpleResult<(String, String)> {
+        let entry = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?.entry_get_or_err(0)?;
+
+        Ok((entry.interpretation_name().to_string(), entry.resolved().display.clone()))
+    }
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x01\x02\x03\x04", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        let big = H2Integer::new(IntegerReader::U32(Endian::Big), DefaultFormatter::new_integer());
+        let little = H2Integer::new(IntegerReader::U32(Endian::Little), DefaultFormatter::new_integer());
+
+        let resolved = record.target().peek("buffer", &big, 0)?;
+        record.apply(ActionEntryCreate::new("buffer", "layer", resolved, Some(big)))?;
+        let resolved = record.target().peek("buffer", &little, 0)?;
+        record.apply(ActionEntryInterpret::new("buffer", "layer", 0, "little", resolved, Some(little)))?;
+        assert_eq!(("default".to_string(), "16909060".to_string()), shown(&record)?);
+
+        let action = ActionEntrySelectInterpretation::new("buffer", "layer", 1, "little");
+        assert_eq!("Show entry @ 0x1 as little in buffer 'buffer'", action.description());
+        record.apply(action)?;
+        assert_eq!(("little".to_string(), "67305985".to_string()), shown(&record)?);
+
+        // The one that was shown is still there
+        let entry = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?.entry_get_or_err(0)?;
+        assert_eq!(DEFAULT_INTERPRETATION, entry.interpretations()[0].name());
+        assert_eq!("16909060", entry.interpretations()[0].resolved().display);
+
+        // Editing the entry changes every interpretation
+        record.apply(ActionEntryEdit::new("buffer", "layer", 0, "", "1"))?;
+        assert_eq!(b"\x01\x00\x00\x00".to_vec(), record.target().buffer_get_or_err("buffer")?.data);
+        assert_eq!(("little".to_string(), "1".to_string()), shown(&record)?);
+        let entry = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?.entry_get_or_err(0)?;
+        assert_eq!("16777216", entry.interpretations()[0].resolved().display);
+        record.undo()?;
+
+        assert!(record.apply(ActionEntrySelectInterpretation::new("buffer", "layer", 0, "nope")).is_err());
+
+        record.undo()?;
+        assert_eq!(("default".to_string(), "16909060".to_string()), shown(&record)?);
+
+        record.redo()?;
+        assert_eq!(("little".to_string(), "67305985".to_string()), shown(&record)?);
+
+        // And back to the original
+        record.apply(ActionEntrySelectInterpretation::new("buffer", "layer", 0, DEFAULT_INTERPRETATION))?;
+        assert_eq!(("default".to_string(), "16909060".to_string()), shown(&record)?);
+
+        Ok(())
+    }
+}
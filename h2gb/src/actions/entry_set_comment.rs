@@ -1,9 +1,9 @@
-use redo::Command;
+use redo::{Command, Merge};
 use serde::{Serialize, Deserialize};
 use simple_error::{SimpleResult, SimpleError, bail};
 
 use crate::project::H2Project;
-use crate::actions::Action;
+use crate::actions::{Action, ActionCategory};
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Forward {
@@ -22,6 +22,11 @@ enum State {
     Backward(Backward),
 }
 
+/// Set or clear the comment at an offset.
+///
+/// Setting the comment at the same offset several times in a row (say, while
+/// somebody is typing it) is merged into a single undo step, which restores
+/// whatever comment was there before the first one.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ActionEntrySetComment(State);
 
@@ -38,6 +43,19 @@ impl ActionEntrySetComment {
             )
         )
     }
+
+    pub fn description(&self) -> String {
+        let (buffer, offset) = match &self.0 {
+            State::Forward(f)  => (&f.buffer, f.offset),
+            State::Backward(b) => (&b.buffer, b.offset),
+        };
+
+        format!("Set comment @ 0x{:x} in buffer '{}'", offset, buffer)
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Comment
+    }
 }
 
 impl Command for ActionEntrySetComment {
@@ -91,8 +109,92 @@ impl Command for ActionEntrySetComment {
 
         Ok(())
     }
+
+    fn merge(&mut self, command: Self) -> Merge<Self> {
+        // Only merge two comments that have both been applied
+        let (first, second) = match (&self.0, &command.0) {
+            (State::Backward(first), State::Backward(second)) => (first, second),
+            _ => return Merge::No(command),
+        };
+
+        // Keep our backward struct as-is, since it has the original comment
+        if first.buffer == second.buffer && first.layer == second.layer && first.offset == second.offset {
+            Merge::Yes
+        } else {
+            Merge::No(command)
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+    use simple_error::SimpleResult;
+
+    use crate::project::H2Project;
+    use crate::actions::{ActionBufferCreateEmpty, ActionLayerCreate};
+
+    fn comment(record: &Record<Action>, offset: usize) -> Option<String> {
+        record.target().buffer_get("buffer").unwrap().layer_get("layer").unwrap().comment_get(offset).unwrap().cloned()
+    }
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateEmpty::new("buffer", 16, 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        record.apply(ActionEntrySetComment::new("buffer", "layer", 4, Some("Hello".to_string())))?;
+        assert_eq!(Some("Hello".to_string()), comment(&record, 4));
+
+        record.undo()?;
+        assert_eq!(None, comment(&record, 4));
+
+        record.redo()?;
+        assert_eq!(Some("Hello".to_string()), comment(&record, 4));
+
+        assert!(record.apply(ActionEntrySetComment::new("buffer", "layer", 16, Some("Bad".to_string()))).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_merges() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateEmpty::new("buffer", 16, 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+        record.apply(ActionEntrySetComment::new("buffer", "layer", 4, Some("Original".to_string())))?;
+        record.apply(ActionEntrySetComment::new("buffer", "layer", 8, Some("Other".to_string())))?;
+
+        // Type out a comment one letter at a time
+        for c in vec!["H", "He", "Hel", "Hell", "Hello"] {
+            record.apply(ActionEntrySetComment::new("buffer", "layer", 4, Some(c.to_string())))?;
+        }
+        assert_eq!(Some("Hello".to_string()), comment(&record, 4));
+
+        // One undo goes back to the original
+        record.undo()?;
+        assert_eq!(Some("Original".to_string()), comment(&record, 4));
+        assert_eq!(Some("Other".to_string()), comment(&record, 8));
+
+        // And redo goes straight to the final comment
+        record.redo()?;
+        assert_eq!(Some("Hello".to_string()), comment(&record, 4));
+
+        // Back to before anything at 4 changed
+        record.undo()?;
+        record.undo()?;
+        record.undo()?;
+        assert_eq!(None, comment(&record, 4));
+        assert_eq!(None, comment(&record, 8));
+
+        Ok(())
+    }
 }
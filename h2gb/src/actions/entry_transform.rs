@@ -0,0 +1,343 @@
+use redo::Command;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use generic_number::Context;
+use h2datatype::Offset;
+use h2transformation::Transformation;
+
+use crate::actions::{Action, ActionCategory};
+use crate::project::{H2Buffer, H2Entry, H2Id, H2Project};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Forward {
+    buffer: String,
+    layer: String,
+    offset: usize,
+    path: String,
+    transformation: Transformation,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Backward {
+    buffer: String,
+    layer: String,
+    offset: usize,
+    path: String,
+    transformation: Transformation,
+
+    buffer_id: H2Id,
+    layer_id: H2Id,
+
+    // Where the bytes were transformed, and what was there before
+    field_offset: usize,
+    original_data: Vec<u8>,
+
+    // The entry as it was, so undo can put it back exactly
+    original_entry: H2Entry,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum State {
+    Forward(Forward),
+    Backward(Backward),
+}
+
+/// Transform the bytes of one field of an entry in place - for example,
+/// decrypt just `header.name` with an XOR key.
+///
+/// The entry is found by any offset inside it, and the field by a path (see
+/// [`h2datatype::H2Type::field`]); an empty path is the whole entry. Only
+/// transformations that can be undone and don't change the length (XOR,
+/// bit reversal, translation tables, and so on) can be used. The entry keeps
+/// its type and is resolved again to show the new bytes, and the
+/// transformation is recorded in its provenance (see
+/// [`crate::project::H2Provenance::untransform`]) so the original bytes can
+/// be worked out when writing back.
+///
+/// Like [`crate::actions::ActionEntryEdit`], the entry needs to know its type,
+/// and no other layer can have an entry on top of the field.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionEntryTransform(State);
+
+impl ActionEntryTransform {
+    pub fn new(buffer: &str, layer: &str, offset: usize, path: &str, transformation: Transformation) -> Action {
+        Action::EntryTransform(
+            ActionEntryTransform(
+                State::Forward(Forward {
+                    buffer: buffer.to_string(),
+                    layer: layer.to_string(),
+                    offset: offset,
+                    path: path.to_string(),
+                    transformation: transformation,
+                })
+            )
+        )
+    }
+
+    pub fn description(&self) -> String {
+        let (buffer, offset, path, transformation) = match &self.0 {
+            State::Forward(f)  => (&f.buffer, f.offset, &f.path, &f.transformation),
+            State::Backward(b) => (&b.buffer, b.offset, &b.path, &b.transformation),
+        };
+
+        match path.is_empty() {
+            true  => format!("Transform entry @ 0x{:x} with {} in buffer '{}'", offset, transformation, buffer),
+            false => format!("Transform {} @ 0x{:x} with {} in buffer '{}'", path, offset, transformation, buffer),
+        }
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Entry
+    }
+}
+
+/// Put an entry back the way it was.
+fn restore(buffer: &mut H2Buffer, layer_id: H2Id, entry: &H2Entry) -> SimpleResult<()> {
+    buffer.layer_get_mut_by_id_or_err(layer_id)?.entry_insert(entry.clone())
+}
+
+impl Command for ActionEntryTransform {
+    type Target = H2Project;
+    type Error = SimpleError;
+
+    fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the forward struct
+        let forward = match &self.0 {
+            State::Forward(f) => f,
+            _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
+        };
+
+        if !forward.transformation.is_two_way() {
+            bail!("Can't transform an entry in place with {}: it can't be undone", forward.transformation);
+        }
+
+        let mut buffer = project.buffer_get_mut_or_err(&forward.buffer)?;
+        let buffer_id = buffer.id();
+        let layer = buffer.layer_get_or_err(&forward.layer)?;
+        let layer_id = layer.id();
+
+        let entry = layer.entry_get_or_err(forward.offset)?;
+        let origin = match entry.origin() {
+            Some(origin) => origin.clone(),
+            None         => bail!("Can't transform entry @ 0x{:x}: it doesn't have a type", forward.offset),
+        };
+        let start = entry.resolved().actual_range.start as usize;
+
+        // Find the field's bytes, and transform them
+        let (field_offset, data) = {
+            let offset = Offset::Dynamic(Context::new(&buffer.data).at(start as u64));
+            let (field, field_offset) = origin.field(offset, &forward.path)?;
+
+            let size = field.actual_size(field_offset)? as usize;
+            let field_offset = field_offset.position() as usize;
+
+            let data = forward.transformation.transform(&buffer.byte_range(field_offset..(field_offset + size))?.to_vec())?;
+            if data.len() != size {
+                bail!("Can't transform {:?} in place with {}: it changes the size from {} to {} byte(s)", forward.path, forward.transformation, size, data.len());
+            }
+
+            (field_offset, data)
+        };
+        let range = (field_offset - start)..(field_offset - start + data.len());
+
+        // Take the entry out of the way so the bytes under it can change
+        let original_entry = buffer.layer_get_mut_by_id_or_err(layer_id)?.entry_remove_by_id(entry.id())?;
+
+        let original_data = match buffer.edit(data, field_offset) {
+            Ok(original_data) => original_data,
+            Err(e) => {
+                restore(&mut buffer, layer_id, &original_entry)?;
+                return Err(e);
+            },
+        };
+
+        // Resolve it again (through the project, like it was created), and
+        // remember what was done to it
+        let provenance = original_entry.provenance().clone().with_transformation(range, forward.transformation);
+        let result = original_entry.resolve_again(provenance, |origin| project.peek(&forward.buffer, origin, start)).and_then(|entry| {
+            project.buffer_get_mut_or_err(&forward.buffer)?.layer_get_mut_by_id_or_err(layer_id)?.entry_insert(entry)
+        });
+
+        if let Err(e) = result {
+            let mut buffer = project.buffer_get_mut_or_err(&forward.buffer)?;
+            buffer.edit(original_data, field_offset)?;
+            restore(&mut buffer, layer_id, &original_entry)?;
+            return Err(e);
+        }
+
+        // Save the backward struct
+        self.0 = State::Backward(Backward {
+            buffer: forward.buffer.clone(),
+            layer: forward.layer.clone(),
+            offset: forward.offset,
+            path: forward.path.clone(),
+            transformation: forward.transformation,
+
+            buffer_id: buffer_id,
+            layer_id: layer_id,
+
+            field_offset: field_offset,
+            original_data: original_data,
+            original_entry: original_entry,
+        });
+
+        Ok(())
+    }
+
+    fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the backward struct
+        let backward = match &self.0 {
+            State::Backward(b) => b,
+            _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
+        };
+
+        // Find everything by ID, in case the names have changed
+        let mut buffer = project.buffer_get_mut_by_id_or_err(backward.buffer_id)?;
+        buffer.layer_get_mut_by_id_or_err(backward.layer_id)?.entry_remove_by_id(backward.original_entry.id())?;
+        buffer.edit(backward.original_data.clone(), backward.field_offset)?;
+        restore(&mut buffer, backward.layer_id, &backward.original_entry)?;
+
+        let layer = match buffer.layer_name(backward.layer_id) {
+            Some(layer) => layer.to_string(),
+            None        => bail!("Failed to undo: layer {} disappeared", backward.layer_id),
+        };
+
+        // Save the forward struct
+        self.0 = State::Forward(Forward {
+            buffer: buffer.name().to_string(),
+            layer: layer,
+            offset: backward.offset,
+            path: backward.path.clone(),
+            transformation: backward.transformation,
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+    use simple_error::SimpleResult;
+
+    use generic_number::{CharacterReader, CharacterFormatter, DefaultFormatter, Endian, IntegerReader};
+    use h2datatype::H2Type;
+    use h2datatype::composite::H2Struct;
+    use h2datatype::simple::H2Enum;
+    use h2datatype::simple::numeric::H2Integer;
+    use h2datatype::simple::string::LPString;
+    use h2transformation::{TransformHex, TransformXorByConstant, XorSettings};
+
+    use crate::actions::{ActionBufferCreateFromBytes, ActionLayerCreate, ActionEntryCreate, ActionEnumMemberRename};
+    use crate::project::H2RangeTransformation;
+
+    fn data(record: &Record<Action>) -> SimpleResult<Vec<u8>> {
+        Ok(record.target().buffer_get_or_err("buffer")?.data.clone())
+    }
+
+    fn entry(record: &Record<Action>, layer: &str, offset: usize) -> SimpleResult<H2Entry> {
+        record.target().buffer_get_or_err("buffer")?.layer_get_or_err(layer)?.entry_get_or_err(offset)
+    }
+
+    fn create(record: &mut Record<Action>, layer: &str, datatype: H2Type, offset: usize) -> SimpleResult<()> {
+        let resolved = record.target().peek("buffer", &datatype, offset)?;
+        record.apply(ActionEntryCreate::new("buffer", layer, resolved, Some(datatype)))?;
+
+        Ok(())
+    }
+
+    fn record() -> SimpleResult<Record<Action>> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+
+        // A count, and a name that's XORed with 0x20 (so it's upper case)
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x00\x07\x02HI\xff", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+        record.apply(ActionLayerCreate::new("buffer", "other"))?;
+
+        create(&mut record, "layer", H2Struct::new(vec![
+            ("count".to_string(), H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer())),
+            ("name".to_string(),  LPString::new(IntegerReader::U8, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?),
+        ])?, 0)?;
+
+        Ok(record)
+    }
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let mut record = record()?;
+        assert_eq!("{ count: 7, name: \"HI\" }", entry(&record, "layer", 0)?.resolved().display);
+
+        // Any offset in the entry works
+        let xor = TransformXorByConstant::new(XorSettings::SixteenBit(0x0003));
+        let action = ActionEntryTransform::new("buffer", "layer", 3, "count", xor);
+        assert_eq!(format!("Transform count @ 0x3 with {} in buffer 'buffer'", xor), action.description());
+        record.apply(action)?;
+
+        let e = entry(&record, "layer", 0)?;
+        assert_eq!(b"\x00\x04\x02HI\xff".to_vec(), data(&record)?);
+        assert_eq!("{ count: 4, name: \"HI\" }", e.resolved().display);
+        assert_eq!(&[H2RangeTransformation { range: 0..2, transformation: xor }], e.provenance().transformations());
+
+        // The original bytes can be worked out again
+        assert_eq!(b"\x00\x07\x02HI".to_vec(), e.provenance().untransform(&data(&record)?[0..5])?);
+
+        record.undo()?;
+        assert_eq!(b"\x00\x07\x02HI\xff".to_vec(), data(&record)?);
+        assert_eq!("{ count: 7, name: \"HI\" }", entry(&record, "layer", 0)?.resolved().display);
+        assert_eq!(0, entry(&record, "layer", 0)?.provenance().transformations().len());
+
+        record.redo()?;
+        assert_eq!("{ count: 4, name: \"HI\" }", entry(&record, "layer", 0)?.resolved().display);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_uses_project() -> SimpleResult<()> {
+        let mut record = record()?;
+        record.apply(ActionEnumMemberRename::new("TerrariaGameMode", 2, "Hard"))?;
+
+        // An enum, then a byte that's XORed
+        create(&mut record, "other", H2Struct::new(vec![
+            ("mode".to_string(),  H2Enum::new(IntegerReader::U8, "TerrariaGameMode")?),
+            ("level".to_string(), H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())),
+        ])?, 2)?;
+        assert_eq!("{ mode: TerrariaGameMode::Hard, level: 72 }", entry(&record, "other", 2)?.resolved().display);
+
+        // The project's name for the enum value is still used afterwards
+        record.apply(ActionEntryTransform::new("buffer", "other", 2, "level", TransformXorByConstant::new(XorSettings::EightBit(0x01))))?;
+        assert_eq!("{ mode: TerrariaGameMode::Hard, level: 73 }", entry(&record, "other", 2)?.resolved().display);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_fails() -> SimpleResult<()> {
+        let mut record = record()?;
+        create(&mut record, "other", H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer()), 1)?;
+
+        let xor = TransformXorByConstant::new(XorSettings::EightBit(0x20));
+        let bad = vec![
+            ActionEntryTransform::new("buffer", "layer", 0, "nope",  xor),                 // No field
+            ActionEntryTransform::new("buffer", "layer", 5, "",      xor),                 // No entry
+            ActionEntryTransform::new("buffer", "nope",  0, "count", xor),                 // No layer
+            ActionEntryTransform::new("buffer", "layer", 0, "name",  xor),                 // Breaks the length prefix
+            ActionEntryTransform::new("buffer", "layer", 3, "name",  TransformHex::new()), // Changes the size
+            ActionEntryTransform::new("buffer", "layer", 0, "count", xor),                 // Another layer on top
+        ];
+
+        for action in bad {
+            assert!(record.apply(action).is_err());
+            assert_eq!(b"\x00\x07\x02HI\xff".to_vec(), data(&record)?);
+            assert_eq!("{ count: 7, name: \"HI\" }", entry(&record, "layer", 0)?.resolved().display);
+        }
+
+        Ok(())
+    }
+}
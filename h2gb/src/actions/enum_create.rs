@@ -0,0 +1,183 @@
+//! Create an enum that belongs to the project.
+//!
+//! If [`h2data`] has an enum with the same name, the project's one is used
+//! instead - see [`crate::project::H2DataOverlay`].
+
+use std::collections::BTreeMap;
+
+use redo::Command;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use crate::project::H2Project;
+use crate::actions::{Action, ActionCategory};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Forward {
+    name: String,
+    values: BTreeMap<usize, String>,
+}
+
+// Backward is identical to forward
+type Backward = Forward;
+
+#[derive(Serialize, Deserialize, Debug)]
+enum State {
+    Forward(Forward),
+    Backward(Backward),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionEnumCreate(State);
+
+impl ActionEnumCreate {
+    pub fn new(name: &str, values: BTreeMap<usize, String>) -> Action {
+        Action::EnumCreate(
+            ActionEnumCreate(
+                State::Forward(Forward {
+                    name: name.to_string(),
+                    values: values,
+                })
+            )
+        )
+    }
+
+    pub fn description(&self) -> String {
+        let name = match &self.0 {
+            State::Forward(f)  => &f.name,
+            State::Backward(b) => &b.name,
+        };
+
+        format!("Create enum '{}'", name)
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Enum
+    }
+}
+
+impl Command for ActionEnumCreate {
+    type Target = H2Project;
+    type Error = SimpleError;
+
+    fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the forward struct
+        let forward = match &self.0 {
+            State::Forward(f) => f,
+            _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
+        };
+
+        if project.data().enum_is_local(&forward.name) {
+            bail!("The project already has an enum called {}", forward.name);
+        }
+
+        project.enum_define(&forward.name, forward.values.clone())?;
+
+        // Save the backward struct
+        self.0 = State::Backward(Backward {
+            name: forward.name.clone(),
+            values: forward.values.clone(),
+        });
+
+        Ok(())
+    }
+
+    fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the backward struct
+        let backward = match &self.0 {
+            State::Backward(b) => b,
+            _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
+        };
+
+        let values = project.enum_undefine(&backward.name)?;
+
+        // Save the forward struct
+        self.0 = State::Forward(Forward {
+            name: backward.name.clone(),
+            values: values,
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+    use simple_error::SimpleResult;
+
+    use generic_number::IntegerReader;
+
+    use crate::actions::{ActionBufferCreateFromBytes, ActionEntryCreate, ActionEnumMemberAdd, ActionLayerCreate};
+
+    fn display(record: &Record<Action>, offset: usize) -> SimpleResult<String> {
+        Ok(record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?.entry_get_or_err(offset)?.resolved().display.clone())
+    }
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x01\x02", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        let mut values = BTreeMap::new();
+        values.insert(1, "One".to_string());
+        record.apply(ActionEnumCreate::new("Numbers", values.clone()))?;
+
+        // Entries are displayed with the project's enum
+        let t = record.target().data().enum_type(IntegerReader::U8, "Numbers")?;
+        for offset in 0..2 {
+            let resolved = record.target().peek("buffer", &t, offset)?;
+            record.apply(ActionEntryCreate::new("buffer", "layer", resolved, Some(t.clone())))?;
+        }
+        assert_eq!("Numbers::One", display(&record, 0)?);
+        assert_eq!("Numbers::Unknown_0x2", display(&record, 1)?);
+
+        // It can be changed like any other enum
+        record.apply(ActionEnumMemberAdd::new("Numbers", 2, "Two"))?;
+        assert_eq!("Numbers::Two", display(&record, 1)?);
+
+        // Undo everything (including the entries), then just the enum
+        for _ in 0..4 {
+            record.undo()?;
+        }
+        assert!(!record.target().data().enum_exists("Numbers"));
+        for _ in 0..4 {
+            record.redo()?;
+        }
+        assert_eq!("Numbers::Two", display(&record, 1)?);
+
+        assert!(record.apply(ActionEnumCreate::new("Numbers", values)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_shadows_shared_enum() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x00", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        let t = record.target().data().enum_type(IntegerReader::U8, "TerrariaGameMode")?;
+        let resolved = record.target().peek("buffer", &t, 0)?;
+        record.apply(ActionEntryCreate::new("buffer", "layer", resolved, Some(t)))?;
+        assert_eq!("TerrariaGameMode::Classic", display(&record, 0)?);
+
+        let mut values = BTreeMap::new();
+        values.insert(0, "Easy".to_string());
+        record.apply(ActionEnumCreate::new("TerrariaGameMode", values))?;
+        assert_eq!("TerrariaGameMode::Easy", display(&record, 0)?);
+
+        record.undo()?;
+        assert_eq!("TerrariaGameMode::Classic", display(&record, 0)?);
+
+        Ok(())
+    }
+}
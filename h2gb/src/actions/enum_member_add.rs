@@ -0,0 +1,167 @@
+//! Give a name to a value that doesn't have one in an enum.
+//!
+//! The change is only made to the project, not to the shared enum - see
+//! [`crate::project::H2DataOverlay`].
+
+use redo::Command;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use crate::project::{H2EnumChange, H2Project};
+use crate::actions::{Action, ActionCategory};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Forward {
+    enum_name: String,
+    value: usize,
+    name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Backward {
+    enum_name: String,
+    value: usize,
+    name: String,
+
+    // The change that was there before (if any)
+    old_change: Option<H2EnumChange>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum State {
+    Forward(Forward),
+    Backward(Backward),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionEnumMemberAdd(State);
+
+impl ActionEnumMemberAdd {
+    pub fn new(enum_name: &str, value: usize, name: &str) -> Action {
+        Action::EnumMemberAdd(
+            ActionEnumMemberAdd(
+                State::Forward(Forward {
+                    enum_name: enum_name.to_string(),
+                    value: value,
+                    name: name.to_string(),
+                })
+            )
+        )
+    }
+
+    pub fn description(&self) -> String {
+        let (enum_name, value, name) = match &self.0 {
+            State::Forward(f)  => (&f.enum_name, f.value, &f.name),
+            State::Backward(b) => (&b.enum_name, b.value, &b.name),
+        };
+
+        format!("Add '{}' (0x{:x}) to enum '{}'", name, value, enum_name)
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Enum
+    }
+}
+
+impl Command for ActionEnumMemberAdd {
+    type Target = H2Project;
+    type Error = SimpleError;
+
+    fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the forward struct
+        let forward = match &self.0 {
+            State::Forward(f) => f,
+            _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
+        };
+
+        if let Some(existing) = project.data().get(&forward.enum_name, forward.value)? {
+            bail!("Value 0x{:x} in enum {} is already named {}", forward.value, forward.enum_name, existing);
+        }
+
+        let old_change = project.enum_member_set(&forward.enum_name, forward.value, Some(&forward.name))?;
+
+        // Save the backward struct
+        self.0 = State::Backward(Backward {
+            enum_name: forward.enum_name.clone(),
+            value: forward.value,
+            name: forward.name.clone(),
+            old_change: old_change,
+        });
+
+        Ok(())
+    }
+
+    fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the backward struct
+        let backward = match &self.0 {
+            State::Backward(b) => b,
+            _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
+        };
+
+        project.enum_member_restore(&backward.enum_name, backward.value, backward.old_change.clone())?;
+
+        // Save the forward struct
+        self.0 = State::Forward(Forward {
+            enum_name: backward.enum_name.clone(),
+            value: backward.value,
+            name: backward.name.clone(),
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+    use simple_error::SimpleResult;
+
+    use generic_number::IntegerReader;
+    use h2datatype::simple::H2Enum;
+
+    use crate::actions::{ActionBufferCreateFromBytes, ActionEntryCreate, ActionLayerCreate};
+
+    fn display(record: &Record<Action>, offset: usize) -> SimpleResult<String> {
+        Ok(record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?.entry_get_or_err(offset)?.resolved().display.clone())
+    }
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x00\x20\x20", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        let t = H2Enum::new(IntegerReader::U8, "TerrariaGameMode")?;
+        for offset in 0..3 {
+            let resolved = record.target().buffer_get_or_err("buffer")?.peek(&t, offset)?;
+            record.apply(ActionEntryCreate::new("buffer", "layer", resolved, Some(t.clone())))?;
+        }
+        assert_eq!("TerrariaGameMode::Unknown_0x20", display(&record, 1)?);
+
+        // Every entry with that value is updated
+        record.apply(ActionEnumMemberAdd::new("TerrariaGameMode", 0x20, "Secret"))?;
+        assert_eq!("TerrariaGameMode::Classic", display(&record, 0)?);
+        assert_eq!("TerrariaGameMode::Secret", display(&record, 1)?);
+        assert_eq!("TerrariaGameMode::Secret", display(&record, 2)?);
+        assert_eq!(Some("Secret".to_string()), record.target().data().get("TerrariaGameMode", 0x20)?);
+
+        record.undo()?;
+        assert_eq!("TerrariaGameMode::Unknown_0x20", display(&record, 1)?);
+        assert!(record.target().data().is_empty());
+
+        record.redo()?;
+        assert_eq!("TerrariaGameMode::Secret", display(&record, 2)?);
+
+        // Values that already have a name can't be added
+        assert!(record.apply(ActionEnumMemberAdd::new("TerrariaGameMode", 0x20, "Other")).is_err());
+        assert!(record.apply(ActionEnumMemberAdd::new("TerrariaGameMode", 0, "Other")).is_err());
+        assert!(record.apply(ActionEnumMemberAdd::new("NotAnEnum", 0, "Other")).is_err());
+
+        Ok(())
+    }
+}
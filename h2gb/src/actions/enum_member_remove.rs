@@ -0,0 +1,154 @@
+//! Remove the name from a value in an enum.
+//!
+//! The change is only made to the project, not to the shared enum - see
+//! [`crate::project::H2DataOverlay`].
+
+use redo::Command;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use crate::project::{H2EnumChange, H2Project};
+use crate::actions::{Action, ActionCategory};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Forward {
+    enum_name: String,
+    value: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Backward {
+    enum_name: String,
+    value: usize,
+
+    // The change that was there before (if any)
+    old_change: Option<H2EnumChange>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum State {
+    Forward(Forward),
+    Backward(Backward),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionEnumMemberRemove(State);
+
+impl ActionEnumMemberRemove {
+    pub fn new(enum_name: &str, value: usize) -> Action {
+        Action::EnumMemberRemove(
+            ActionEnumMemberRemove(
+                State::Forward(Forward {
+                    enum_name: enum_name.to_string(),
+                    value: value,
+                })
+            )
+        )
+    }
+
+    pub fn description(&self) -> String {
+        let (enum_name, value) = match &self.0 {
+            State::Forward(f)  => (&f.enum_name, f.value),
+            State::Backward(b) => (&b.enum_name, b.value),
+        };
+
+        format!("Remove 0x{:x} from enum '{}'", value, enum_name)
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Enum
+    }
+}
+
+impl Command for ActionEnumMemberRemove {
+    type Target = H2Project;
+    type Error = SimpleError;
+
+    fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the forward struct
+        let forward = match &self.0 {
+            State::Forward(f) => f,
+            _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
+        };
+
+        if project.data().get(&forward.enum_name, forward.value)?.is_none() {
+            bail!("Value 0x{:x} in enum {} doesn't have a name to remove", forward.value, forward.enum_name);
+        }
+
+        let old_change = project.enum_member_set(&forward.enum_name, forward.value, None)?;
+
+        // Save the backward struct
+        self.0 = State::Backward(Backward {
+            enum_name: forward.enum_name.clone(),
+            value: forward.value,
+            old_change: old_change,
+        });
+
+        Ok(())
+    }
+
+    fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the backward struct
+        let backward = match &self.0 {
+            State::Backward(b) => b,
+            _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
+        };
+
+        project.enum_member_restore(&backward.enum_name, backward.value, backward.old_change.clone())?;
+
+        // Save the forward struct
+        self.0 = State::Forward(Forward {
+            enum_name: backward.enum_name.clone(),
+            value: backward.value,
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+    use simple_error::SimpleResult;
+
+    use generic_number::IntegerReader;
+    use h2datatype::simple::H2Enum;
+
+    use crate::actions::{ActionBufferCreateFromBytes, ActionEntryCreate, ActionLayerCreate};
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x02", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        let t = H2Enum::new(IntegerReader::U8, "TerrariaGameMode")?;
+        let resolved = record.target().buffer_get_or_err("buffer")?.peek(&t, 0)?;
+        record.apply(ActionEntryCreate::new("buffer", "layer", resolved, Some(t)))?;
+
+        let display = |record: &Record<Action>| -> SimpleResult<String> {
+            Ok(record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?.entry_get_or_err(0)?.resolved().display.clone())
+        };
+        assert_eq!("TerrariaGameMode::HardCore", display(&record)?);
+
+        record.apply(ActionEnumMemberRemove::new("TerrariaGameMode", 2))?;
+        assert_eq!("TerrariaGameMode::Unknown_0x2", display(&record)?);
+        assert_eq!(None, record.target().data().get("TerrariaGameMode", 2)?);
+
+        record.undo()?;
+        assert_eq!("TerrariaGameMode::HardCore", display(&record)?);
+
+        record.redo()?;
+        assert_eq!("TerrariaGameMode::Unknown_0x2", display(&record)?);
+
+        // It's already gone
+        assert!(record.apply(ActionEnumMemberRemove::new("TerrariaGameMode", 2)).is_err());
+
+        Ok(())
+    }
+}
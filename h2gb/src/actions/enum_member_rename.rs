@@ -0,0 +1,166 @@
+//! Change the name of a value in an enum.
+//!
+//! The change is only made to the project, not to the shared enum - see
+//! [`crate::project::H2DataOverlay`].
+
+use redo::Command;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use crate::project::{H2EnumChange, H2Project};
+use crate::actions::{Action, ActionCategory};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Forward {
+    enum_name: String,
+    value: usize,
+    name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Backward {
+    enum_name: String,
+    value: usize,
+    name: String,
+
+    // The change that was there before (if any)
+    old_change: Option<H2EnumChange>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum State {
+    Forward(Forward),
+    Backward(Backward),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionEnumMemberRename(State);
+
+impl ActionEnumMemberRename {
+    pub fn new(enum_name: &str, value: usize, name: &str) -> Action {
+        Action::EnumMemberRename(
+            ActionEnumMemberRename(
+                State::Forward(Forward {
+                    enum_name: enum_name.to_string(),
+                    value: value,
+                    name: name.to_string(),
+                })
+            )
+        )
+    }
+
+    pub fn description(&self) -> String {
+        let (enum_name, value, name) = match &self.0 {
+            State::Forward(f)  => (&f.enum_name, f.value, &f.name),
+            State::Backward(b) => (&b.enum_name, b.value, &b.name),
+        };
+
+        format!("Rename 0x{:x} in enum '{}' to '{}'", value, enum_name, name)
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Enum
+    }
+}
+
+impl Command for ActionEnumMemberRename {
+    type Target = H2Project;
+    type Error = SimpleError;
+
+    fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the forward struct
+        let forward = match &self.0 {
+            State::Forward(f) => f,
+            _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
+        };
+
+        if project.data().get(&forward.enum_name, forward.value)?.is_none() {
+            bail!("Value 0x{:x} in enum {} doesn't have a name to change", forward.value, forward.enum_name);
+        }
+
+        let old_change = project.enum_member_set(&forward.enum_name, forward.value, Some(&forward.name))?;
+
+        // Save the backward struct
+        self.0 = State::Backward(Backward {
+            enum_name: forward.enum_name.clone(),
+            value: forward.value,
+            name: forward.name.clone(),
+            old_change: old_change,
+        });
+
+        Ok(())
+    }
+
+    fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the backward struct
+        let backward = match &self.0 {
+            State::Backward(b) => b,
+            _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
+        };
+
+        project.enum_member_restore(&backward.enum_name, backward.value, backward.old_change.clone())?;
+
+        // Save the forward struct
+        self.0 = State::Forward(Forward {
+            enum_name: backward.enum_name.clone(),
+            value: backward.value,
+            name: backward.name.clone(),
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+    use simple_error::SimpleResult;
+
+    use generic_number::IntegerReader;
+    use h2datatype::simple::H2Enum;
+    use h2datatype::composite::H2Array;
+
+    use crate::actions::{ActionBufferCreateFromBytes, ActionEntryCreate, ActionEnumMemberAdd, ActionLayerCreate};
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x00\x01\x20", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        // Use an array, to make sure children are updated too
+        let t = H2Array::new(3, H2Enum::new(IntegerReader::U8, "TerrariaGameMode")?)?;
+        let resolved = record.target().buffer_get_or_err("buffer")?.peek(&t, 0)?;
+        record.apply(ActionEntryCreate::new("buffer", "layer", resolved, Some(t)))?;
+
+        let children = |record: &Record<Action>| -> SimpleResult<Vec<String>> {
+            Ok(record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?.entry_get_or_err(0)?.resolved().children.iter().map(|c| c.display.clone()).collect())
+        };
+        assert_eq!(vec!["TerrariaGameMode::Classic", "TerrariaGameMode::MediumCore", "TerrariaGameMode::Unknown_0x20"], children(&record)?);
+
+        record.apply(ActionEnumMemberRename::new("TerrariaGameMode", 1, "Medium"))?;
+        assert_eq!(vec!["TerrariaGameMode::Classic", "TerrariaGameMode::Medium", "TerrariaGameMode::Unknown_0x20"], children(&record)?);
+
+        // Rename something that was added, then undo both
+        record.apply(ActionEnumMemberAdd::new("TerrariaGameMode", 0x20, "Secret"))?;
+        record.apply(ActionEnumMemberRename::new("TerrariaGameMode", 0x20, "VerySecret"))?;
+        assert_eq!(vec!["TerrariaGameMode::Classic", "TerrariaGameMode::Medium", "TerrariaGameMode::VerySecret"], children(&record)?);
+
+        record.undo()?;
+        assert_eq!(vec!["TerrariaGameMode::Classic", "TerrariaGameMode::Medium", "TerrariaGameMode::Secret"], children(&record)?);
+        record.undo()?;
+        record.undo()?;
+        assert_eq!(vec!["TerrariaGameMode::Classic", "TerrariaGameMode::MediumCore", "TerrariaGameMode::Unknown_0x20"], children(&record)?);
+        assert!(record.target().data().is_empty());
+
+        // Values without a name can't be renamed
+        assert!(record.apply(ActionEnumMemberRename::new("TerrariaGameMode", 0x20, "Secret")).is_err());
+
+        Ok(())
+    }
+}
@@ -4,17 +4,29 @@ use redo::Command;
 use serde::{Serialize, Deserialize};
 use simple_error::{SimpleResult, SimpleError, bail};
 
-use crate::project::H2Project;
-use crate::actions::Action;
+use crate::project::{H2Project, H2Id};
+use crate::actions::{Action, ActionCategory};
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Forward {
     buffer: String,
     name: String,
+
+    // Set once the layer has been created, so redo gets the same ID
+    #[serde(default)]
+    id: Option<H2Id>,
 }
 
-// Backward == forward here
-type Backward = Forward;
+#[derive(Serialize, Deserialize, Debug)]
+struct Backward {
+    buffer: String,
+    name: String,
+
+    #[serde(default)]
+    buffer_id: H2Id,
+    #[serde(default)]
+    id: H2Id,
+}
 
 #[derive(Serialize, Deserialize, Debug)]
 enum State {
@@ -32,10 +44,24 @@ impl ActionLayerCreate {
                 State::Forward(Forward {
                     buffer: String::from(buffer),
                     name: String::from(name),
+                    id: None,
                 })
             )
         )
     }
+
+    pub fn description(&self) -> String {
+        let (buffer, name) = match &self.0 {
+            State::Forward(f)  => (&f.buffer, &f.name),
+            State::Backward(b) => (&b.buffer, &b.name),
+        };
+
+        format!("Create layer '{}' in buffer '{}'", name, buffer)
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Layer
+    }
 }
 
 impl Command for ActionLayerCreate {
@@ -49,16 +75,21 @@ impl Command for ActionLayerCreate {
             _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
         };
 
+        let id = match forward.id {
+            Some(id) => id,
+            None     => project.id_allocate(),
+        };
+
         // Do stuff with it
-        let buffer = project.buffer_get_mut(&forward.buffer).ok_or(
-            SimpleError::new(format!("Could not find buffer {}", forward.buffer))
-        )?;
-        buffer.layer_add(&forward.name)?;
+        let mut buffer = project.buffer_get_mut_or_err(&forward.buffer)?;
+        buffer.layer_add(&forward.name, id)?;
 
         // Save the backward struct
         self.0 = State::Backward(Backward {
             buffer: forward.buffer.clone(),
             name: forward.name.clone(),
+            buffer_id: buffer.id(),
+            id: id,
         });
 
         Ok(())
@@ -71,19 +102,20 @@ impl Command for ActionLayerCreate {
             _                    => bail!("Failed to undo: action ended up in a broken undo/redo state"),
         };
 
-        // Do stuff with it
-        let buffer = project.buffer_get_mut(&backward.buffer).ok_or(
-            SimpleError::new(format!("Could not find buffer {}", backward.buffer))
-        )?;
-        buffer.layer_remove(&backward.name)?;
+        // Find everything by ID, in case the names have changed
+        let mut buffer = project.buffer_get_mut_by_id_or_err(backward.buffer_id)?;
+        let name = buffer.layer_name(backward.id).ok_or(
+            SimpleError::new(format!("Could not find layer with ID {}", backward.id))
+        )?.to_string();
+        let id = buffer.layer_remove(&name)?;
 
         // Save the forward struct
         self.0 = State::Forward(Forward {
-            buffer: backward.buffer.clone(),
-            name: backward.name.clone(),
+            buffer: buffer.name().to_string(),
+            name: name,
+            id: Some(id),
         });
 
-
         Ok(())
     }
 }
@@ -92,7 +124,7 @@ impl Command for ActionLayerCreate {
 mod tests {
     use super::*;
 
-    // use pretty_assertions::assert_eq;
+    use pretty_assertions::assert_eq;
     use redo::Record;
     use simple_error::SimpleResult;
 
@@ -123,6 +155,32 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_action_keeps_id() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+
+        record.apply(ActionBufferCreateEmpty::new("buffer", 100, 0))?;
+        let buffer_id = record.target().buffer_id("buffer").unwrap();
+
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+        let layer_id = record.target().buffer_get("buffer").unwrap().layer_id("layer").unwrap();
+        assert_ne!(buffer_id, layer_id);
+
+        // Undo everything, then redo it - the IDs should come back the same
+        record.undo()?;
+        record.undo()?;
+        assert_eq!(None, record.target().buffer_id("buffer"));
+
+        record.redo()?;
+        record.redo()?;
+        assert_eq!(Some(buffer_id), record.target().buffer_id("buffer"));
+        assert_eq!(Some(layer_id), record.target().buffer_get("buffer").unwrap().layer_id("layer"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_action_fails_if_layer_already_exists() -> SimpleResult<()> {
         let mut record: Record<Action> = Record::new(
@@ -0,0 +1,250 @@
+use redo::Command;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use crate::project::H2Project;
+use crate::actions::{Action, ActionCategory};
+use crate::import::ImportedBookmark;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Forward {
+    buffer: String,
+    layer: String,
+    bookmarks: Vec<ImportedBookmark>,
+}
+
+/// What was at an offset before the import, so it can be put back.
+#[derive(Serialize, Deserialize, Debug)]
+struct Original {
+    offset: usize,
+    name: Option<String>,
+    comment: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Backward {
+    buffer: String,
+    layer: String,
+    bookmarks: Vec<ImportedBookmark>,
+    originals: Vec<Original>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum State {
+    Forward(Forward),
+    Backward(Backward),
+}
+
+/// Create bookmarks and comments in bulk, as a single action.
+///
+/// The bookmarks are typically parsed from another tool's output using
+/// [`crate::import::bookmarks_from_csv`] or
+/// [`crate::import::bookmarks_from_json`]. Undoing the action puts back
+/// whatever bookmarks and comments were there before.
+///
+/// If any offset is outside of the buffer, nothing is changed.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionLayerImportBookmarks(State);
+
+impl ActionLayerImportBookmarks {
+    pub fn new(buffer: &str, layer: &str, bookmarks: Vec<ImportedBookmark>) -> Action {
+        Action::LayerImportBookmarks(
+            ActionLayerImportBookmarks(
+                State::Forward(Forward {
+                    buffer: buffer.to_string(),
+                    layer: layer.to_string(),
+                    bookmarks: bookmarks,
+                })
+            )
+        )
+    }
+
+    pub fn description(&self) -> String {
+        let (buffer, layer, bookmarks) = match &self.0 {
+            State::Forward(f)  => (&f.buffer, &f.layer, &f.bookmarks),
+            State::Backward(b) => (&b.buffer, &b.layer, &b.bookmarks),
+        };
+
+        format!("Import {} bookmark(s) into layer '{}' in buffer '{}'", bookmarks.len(), layer, buffer)
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Bookmark
+    }
+}
+
+impl Command for ActionLayerImportBookmarks {
+    type Target = H2Project;
+    type Error = SimpleError;
+
+    fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the forward struct
+        let forward = match &self.0 {
+            State::Forward(f) => f,
+            _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
+        };
+
+        let mut buffer = project.buffer_get_mut_or_err(&forward.buffer)?;
+        let layer = buffer.layer_get_mut_or_err(&forward.layer)?;
+
+        // Validate everything before changing anything, so a bad offset
+        // doesn't leave us half-imported
+        for bookmark in &forward.bookmarks {
+            layer.bookmark_get(bookmark.offset)?;
+        }
+
+        let mut originals: Vec<Original> = vec![];
+        for bookmark in &forward.bookmarks {
+            let name = match &bookmark.name {
+                Some(name) => layer.bookmark_set(bookmark.offset, Some(name.clone()))?,
+                None       => layer.bookmark_get(bookmark.offset)?.cloned(),
+            };
+
+            let comment = match &bookmark.comment {
+                Some(comment) => layer.comment_set(bookmark.offset, Some(comment.clone()))?,
+                None          => layer.comment_get(bookmark.offset)?.cloned(),
+            };
+
+            originals.push(Original {
+                offset: bookmark.offset,
+                name: name,
+                comment: comment,
+            });
+        }
+
+        // Save the backward struct
+        self.0 = State::Backward(Backward {
+            buffer: forward.buffer.clone(),
+            layer: forward.layer.clone(),
+            bookmarks: forward.bookmarks.clone(),
+            originals: originals,
+        });
+
+        Ok(())
+    }
+
+    fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the backward struct
+        let backward = match &self.0 {
+            State::Backward(b) => b,
+            _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
+        };
+
+        let mut buffer = project.buffer_get_mut_or_err(&backward.buffer)?;
+        let layer = buffer.layer_get_mut_or_err(&backward.layer)?;
+
+        // Restore in reverse, in case the same offset was imported twice
+        for original in backward.originals.iter().rev() {
+            layer.bookmark_set(original.offset, original.name.clone())?;
+            layer.comment_set(original.offset, original.comment.clone())?;
+        }
+
+        // Save the forward struct
+        self.0 = State::Forward(Forward {
+            buffer: backward.buffer.clone(),
+            layer: backward.layer.clone(),
+            bookmarks: backward.bookmarks.clone(),
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+    use simple_error::SimpleResult;
+
+    use crate::project::H2Project;
+    use crate::actions::{ActionBufferCreateEmpty, ActionLayerCreate, ActionEntrySetComment};
+    use crate::import::bookmarks_from_csv;
+
+    fn bookmark(record: &Record<Action>, offset: usize) -> Option<String> {
+        record.target().buffer_get("buffer").unwrap().layer_get("layer").unwrap().bookmark_get(offset).unwrap().cloned()
+    }
+
+    fn comment(record: &Record<Action>, offset: usize) -> Option<String> {
+        record.target().buffer_get("buffer").unwrap().layer_get("layer").unwrap().comment_get(offset).unwrap().cloned()
+    }
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+
+        record.apply(ActionBufferCreateEmpty::new("buffer", 100, 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        // Put a comment there to start, to make sure it comes back
+        record.apply(ActionEntrySetComment::new("buffer", "layer", 0x20, Some("Original comment".to_string())))?;
+
+        let bookmarks = bookmarks_from_csv("0x10,header,The file header\n0x20,body,New comment\n0x30,footer")?;
+        record.apply(ActionLayerImportBookmarks::new("buffer", "layer", bookmarks))?;
+
+        assert_eq!(Some("header".to_string()), bookmark(&record, 0x10));
+        assert_eq!(Some("The file header".to_string()), comment(&record, 0x10));
+        assert_eq!(Some("body".to_string()), bookmark(&record, 0x20));
+        assert_eq!(Some("New comment".to_string()), comment(&record, 0x20));
+        assert_eq!(Some("footer".to_string()), bookmark(&record, 0x30));
+        assert_eq!(None, comment(&record, 0x30));
+
+        // Undo should put everything back in one go
+        record.undo()?;
+        assert_eq!(None, bookmark(&record, 0x10));
+        assert_eq!(None, comment(&record, 0x10));
+        assert_eq!(None, bookmark(&record, 0x20));
+        assert_eq!(Some("Original comment".to_string()), comment(&record, 0x20));
+        assert_eq!(None, bookmark(&record, 0x30));
+
+        record.redo()?;
+        assert_eq!(Some("header".to_string()), bookmark(&record, 0x10));
+        assert_eq!(Some("New comment".to_string()), comment(&record, 0x20));
+        assert_eq!(3, record.target().buffer_get("buffer").unwrap().layer_get("layer").unwrap().bookmarks_get(0..100)?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_duplicate_offsets() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+
+        record.apply(ActionBufferCreateEmpty::new("buffer", 100, 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        let bookmarks = bookmarks_from_csv("0x10,first\n0x10,second")?;
+        record.apply(ActionLayerImportBookmarks::new("buffer", "layer", bookmarks))?;
+        assert_eq!(Some("second".to_string()), bookmark(&record, 0x10));
+
+        record.undo()?;
+        assert_eq!(None, bookmark(&record, 0x10));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_fails_on_bad_offset() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+
+        record.apply(ActionBufferCreateEmpty::new("buffer", 100, 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        // The second one is off the end, so neither should be created
+        let bookmarks = bookmarks_from_csv("0x10,good\n1000,bad")?;
+        assert!(record.apply(ActionLayerImportBookmarks::new("buffer", "layer", bookmarks)).is_err());
+        assert_eq!(None, bookmark(&record, 0x10));
+
+        // Bad layer
+        let bookmarks = bookmarks_from_csv("0x10,good")?;
+        assert!(record.apply(ActionLayerImportBookmarks::new("buffer", "nolayer", bookmarks)).is_err());
+
+        Ok(())
+    }
+}
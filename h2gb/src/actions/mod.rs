@@ -3,9 +3,10 @@
 // Most of the methods here are simply wrappers for the actual action, which
 // are all defined in their respective modules. For documentation, see them.
 
-use redo::Command;
+use redo::{Command, Merge};
 use serde::{Serialize, Deserialize};
 use simple_error::{SimpleResult, SimpleError};
+use std::fmt;
 
 use crate::project::H2Project;
 
@@ -21,7 +22,6 @@ use crate::project::H2Project;
 //   * entry_delete
 //   * entry_unlink
 //   * entry_recompute / entry_rebase (maybe?)
-//   * entry_edit
 //
 // * create_buffer_from_entry
 //
@@ -34,70 +34,363 @@ pub use buffer_create_empty::ActionBufferCreateEmpty;
 mod buffer_create_from_bytes;
 pub use buffer_create_from_bytes::ActionBufferCreateFromBytes;
 
+mod buffer_create_from_file;
+pub use buffer_create_from_file::{ActionBufferCreateFromFile, DEFAULT_MAX_FILE_SIZE};
+
 mod buffer_extract;
 pub use buffer_extract::ActionBufferExtract;
 
 mod buffer_transform;
 pub use buffer_transform::ActionBufferTransform;
 
+mod buffer_edit;
+pub use buffer_edit::ActionBufferEdit;
+
+mod buffer_reload;
+pub use buffer_reload::ActionBufferReload;
+
 mod null;
 pub use null::NullAction;
 
 mod layer_create;
 pub use layer_create::ActionLayerCreate;
 
+mod layer_import_bookmarks;
+pub use layer_import_bookmarks::ActionLayerImportBookmarks;
+
 // mod entry_create_and_insert;
 // pub use entry_create_and_insert::ActionEntryCreateAndInsert;
 
 mod entry_create;
 pub use entry_create::ActionEntryCreate;
 
+mod entry_edit;
+pub use entry_edit::ActionEntryEdit;
+
+mod entry_transform;
+pub use entry_transform::ActionEntryTransform;
+
+mod entry_remove_matching;
+pub use entry_remove_matching::ActionEntryRemoveMatching;
+
 mod entry_set_comment;
 pub use entry_set_comment::ActionEntrySetComment;
 
+mod entry_interpret;
+pub use entry_interpret::ActionEntryInterpret;
+
+mod entry_select_interpretation;
+pub use entry_select_interpretation::ActionEntrySelectInterpretation;
+
+mod enum_create;
+pub use enum_create::ActionEnumCreate;
+
+mod enum_member_add;
+pub use enum_member_add::ActionEnumMemberAdd;
+
+mod enum_member_rename;
+pub use enum_member_rename::ActionEnumMemberRename;
+
+mod enum_member_remove;
+pub use enum_member_remove::ActionEnumMemberRemove;
+
+mod symbol_set;
+pub use symbol_set::ActionSymbolSet;
+
+mod config_set;
+pub use config_set::ActionConfigSet;
+
+mod variable_set;
+pub use variable_set::ActionVariableSet;
+
+mod project_merge;
+pub use project_merge::ActionProjectMerge;
+
+/// The longest a value can be before [`shorten`] cuts it off.
+const MAX_DESCRIPTION_VALUE: usize = 32;
+
+/// Shorten a value (like an entry's display) to fit in a description.
+fn shorten(s: &str) -> String {
+    if s.chars().count() <= MAX_DESCRIPTION_VALUE {
+        return s.to_string();
+    }
+
+    format!("{}...", s.chars().take(MAX_DESCRIPTION_VALUE - 3).collect::<String>())
+}
+
+/// A broad grouping of actions, so front-ends can group or filter history
+/// (and pick an icon!).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionCategory {
+    Buffer,
+    Layer,
+    Entry,
+    Comment,
+    Bookmark,
+    Enum,
+    Symbol,
+    Other,
+}
+
 // Don't create this directly - use the actions' new() functions
 #[derive(Serialize, Deserialize, Debug)]
 pub enum Action {
     Null(NullAction),
     BufferCreateEmpty(ActionBufferCreateEmpty),
     BufferCreateFromBytes(ActionBufferCreateFromBytes),
+    BufferCreateFromFile(ActionBufferCreateFromFile),
     BufferExtract(ActionBufferExtract),
     BufferTransform(ActionBufferTransform),
+    BufferEdit(ActionBufferEdit),
+    BufferReload(ActionBufferReload),
     LayerCreate(ActionLayerCreate),
+    LayerImportBookmarks(ActionLayerImportBookmarks),
     // EntryCreateAndInsert(ActionEntryCreateAndInsert),
     EntryCreate(ActionEntryCreate),
+    EntryEdit(ActionEntryEdit),
+    EntryTransform(ActionEntryTransform),
+    EntryRemoveMatching(ActionEntryRemoveMatching),
     EntrySetComment(ActionEntrySetComment),
+    EntryInterpret(ActionEntryInterpret),
+    EntrySelectInterpretation(ActionEntrySelectInterpretation),
+    EnumCreate(ActionEnumCreate),
+    EnumMemberAdd(ActionEnumMemberAdd),
+    EnumMemberRename(ActionEnumMemberRename),
+    EnumMemberRemove(ActionEnumMemberRemove),
+    SymbolSet(ActionSymbolSet),
+    ConfigSet(ActionConfigSet),
+    VariableSet(ActionVariableSet),
+    ProjectMerge(ActionProjectMerge),
+}
+
+impl Action {
+    /// A human-readable description of the action, suitable for an undo or
+    /// redo menu - for example, `Create layer 'default' in buffer 'buffer'`.
+    ///
+    /// The description is the same whether the action has been applied or
+    /// not. It's also used for [`fmt::Display`], which is what redo uses to
+    /// describe the commands in a record.
+    pub fn description(&self) -> String {
+        match self {
+            Action::Null(a)                  => a.description(),
+            Action::BufferCreateEmpty(a)     => a.description(),
+            Action::BufferCreateFromBytes(a) => a.description(),
+            Action::BufferCreateFromFile(a)  => a.description(),
+            Action::BufferExtract(a)         => a.description(),
+            Action::BufferTransform(a)       => a.description(),
+            Action::BufferEdit(a)            => a.description(),
+            Action::BufferReload(a)          => a.description(),
+            Action::LayerCreate(a)           => a.description(),
+            Action::LayerImportBookmarks(a)  => a.description(),
+            // Action::EntryCreateAndInsert(a)  => a.description(),
+            Action::EntryCreate(a)           => a.description(),
+            Action::EntryEdit(a)             => a.description(),
+            Action::EntryTransform(a)        => a.description(),
+            Action::EntryRemoveMatching(a)   => a.description(),
+            Action::EntrySetComment(a)       => a.description(),
+            Action::EntryInterpret(a)        => a.description(),
+            Action::EntrySelectInterpretation(a) => a.description(),
+            Action::EnumCreate(a)            => a.description(),
+            Action::EnumMemberAdd(a)         => a.description(),
+            Action::EnumMemberRename(a)      => a.description(),
+            Action::EnumMemberRemove(a)      => a.description(),
+            Action::SymbolSet(a)             => a.description(),
+            Action::ConfigSet(a)             => a.description(),
+            Action::VariableSet(a)           => a.description(),
+            Action::ProjectMerge(a)          => a.description(),
+        }
+    }
+
+    /// The broad category of the action.
+    pub fn category(&self) -> ActionCategory {
+        match self {
+            Action::Null(a)                  => a.category(),
+            Action::BufferCreateEmpty(a)     => a.category(),
+            Action::BufferCreateFromBytes(a) => a.category(),
+            Action::BufferCreateFromFile(a)  => a.category(),
+            Action::BufferExtract(a)         => a.category(),
+            Action::BufferTransform(a)       => a.category(),
+            Action::BufferEdit(a)            => a.category(),
+            Action::BufferReload(a)          => a.category(),
+            Action::LayerCreate(a)           => a.category(),
+            Action::LayerImportBookmarks(a)  => a.category(),
+            // Action::EntryCreateAndInsert(a)  => a.category(),
+            Action::EntryCreate(a)           => a.category(),
+            Action::EntryEdit(a)             => a.category(),
+            Action::EntryTransform(a)        => a.category(),
+            Action::EntryRemoveMatching(a)   => a.category(),
+            Action::EntrySetComment(a)       => a.category(),
+            Action::EntryInterpret(a)        => a.category(),
+            Action::EntrySelectInterpretation(a) => a.category(),
+            Action::EnumCreate(a)            => a.category(),
+            Action::EnumMemberAdd(a)         => a.category(),
+            Action::EnumMemberRename(a)      => a.category(),
+            Action::EnumMemberRemove(a)      => a.category(),
+            Action::SymbolSet(a)             => a.category(),
+            Action::ConfigSet(a)             => a.category(),
+            Action::VariableSet(a)           => a.category(),
+            Action::ProjectMerge(a)          => a.category(),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.description())
+    }
 }
 
 impl Command for Action {
     type Target = H2Project;
     type Error = SimpleError;
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(self, project), fields(action = %self, category = ?self.category())))]
     fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
         match self {
             Action::Null(a)                  => a.apply(project),
             Action::BufferCreateEmpty(a)     => a.apply(project),
             Action::BufferCreateFromBytes(a) => a.apply(project),
+            Action::BufferCreateFromFile(a)  => a.apply(project),
             Action::BufferExtract(a)         => a.apply(project),
             Action::BufferTransform(a)       => a.apply(project),
+            Action::BufferEdit(a)            => a.apply(project),
+            Action::BufferReload(a)          => a.apply(project),
             Action::LayerCreate(a)           => a.apply(project),
+            Action::LayerImportBookmarks(a)  => a.apply(project),
             // Action::EntryCreateAndInsert(a)  => a.apply(project),
             Action::EntryCreate(a)           => a.apply(project),
+            Action::EntryEdit(a)             => a.apply(project),
+            Action::EntryTransform(a)        => a.apply(project),
+            Action::EntryRemoveMatching(a)   => a.apply(project),
             Action::EntrySetComment(a)       => a.apply(project),
+            Action::EntryInterpret(a)        => a.apply(project),
+            Action::EntrySelectInterpretation(a) => a.apply(project),
+            Action::EnumCreate(a)            => a.apply(project),
+            Action::EnumMemberAdd(a)         => a.apply(project),
+            Action::EnumMemberRename(a)      => a.apply(project),
+            Action::EnumMemberRemove(a)      => a.apply(project),
+            Action::SymbolSet(a)             => a.apply(project),
+            Action::ConfigSet(a)             => a.apply(project),
+            Action::VariableSet(a)           => a.apply(project),
+            Action::ProjectMerge(a)          => a.apply(project),
         }
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(self, project), fields(action = %self, category = ?self.category())))]
     fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
         match self {
             Action::Null(a)                  => a.undo(project),
             Action::BufferCreateEmpty(a)     => a.undo(project),
             Action::BufferCreateFromBytes(a) => a.undo(project),
+            Action::BufferCreateFromFile(a)  => a.undo(project),
             Action::BufferExtract(a)         => a.undo(project),
             Action::BufferTransform(a)       => a.undo(project),
+            Action::BufferEdit(a)            => a.undo(project),
+            Action::BufferReload(a)          => a.undo(project),
             Action::LayerCreate(a)           => a.undo(project),
+            Action::LayerImportBookmarks(a)  => a.undo(project),
             // Action::EntryCreateAndInsert(a)  => a.undo(project),
             Action::EntryCreate(a)           => a.undo(project),
+            Action::EntryEdit(a)             => a.undo(project),
+            Action::EntryTransform(a)        => a.undo(project),
+            Action::EntryRemoveMatching(a)   => a.undo(project),
             Action::EntrySetComment(a)       => a.undo(project),
+            Action::EntryInterpret(a)        => a.undo(project),
+            Action::EntrySelectInterpretation(a) => a.undo(project),
+            Action::EnumCreate(a)            => a.undo(project),
+            Action::EnumMemberAdd(a)         => a.undo(project),
+            Action::EnumMemberRename(a)      => a.undo(project),
+            Action::EnumMemberRemove(a)      => a.undo(project),
+            Action::SymbolSet(a)             => a.undo(project),
+            Action::ConfigSet(a)             => a.undo(project),
+            Action::VariableSet(a)           => a.undo(project),
+            Action::ProjectMerge(a)          => a.undo(project),
         }
     }
+
+    // Only actions of the same type can be merged; the actions themselves
+    // decide whether the details line up. None of them ever annul, so
+    // anything but "no" means they merged.
+    fn merge(&mut self, command: Self) -> Merge<Self> {
+        match (self, command) {
+            (Action::BufferEdit(a), Action::BufferEdit(b)) => match a.merge(b) {
+                Merge::No(b) => Merge::No(Action::BufferEdit(b)),
+                _            => Merge::Yes,
+            },
+            (Action::EntrySetComment(a), Action::EntrySetComment(b)) => match a.merge(b) {
+                Merge::No(b) => Merge::No(Action::EntrySetComment(b)),
+                _            => Merge::Yes,
+            },
+            (_, command) => Merge::No(command),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+    use simple_error::SimpleResult;
+
+    use generic_number::{IntegerReader, Endian, HexFormatter};
+    use h2datatype::simple::numeric::H2Integer;
+
+    #[test]
+    fn test_description() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+
+        let action = ActionBufferCreateFromBytes::new("buffer", b"\x00\x01\x02\x03\x04\x05\x06\x07", 0);
+        assert_eq!("Create buffer 'buffer'", action.description());
+        assert_eq!(ActionCategory::Buffer, action.category());
+        record.apply(action)?;
+
+        let action = ActionLayerCreate::new("buffer", "default");
+        assert_eq!("Create layer 'default' in buffer 'buffer'", action.to_string());
+        assert_eq!(ActionCategory::Layer, action.category());
+        record.apply(action)?;
+
+        let t = H2Integer::new(IntegerReader::U16(Endian::Big), HexFormatter::pretty_integer());
+        let resolved = record.target().buffer_get_or_err("buffer")?.peek(&t, 4)?;
+        let action = ActionEntryCreate::new("buffer", "default", resolved, Some(t));
+        assert_eq!("Create entry '0x0405' @ 0x4 in buffer 'buffer'", action.description());
+        assert_eq!(ActionCategory::Entry, action.category());
+
+        let action = ActionEntrySetComment::new("buffer", "default", 0x4, Some("Hi".to_string()));
+        assert_eq!("Set comment @ 0x4 in buffer 'buffer'", action.description());
+        assert_eq!(ActionCategory::Comment, action.category());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_description_after_apply() -> SimpleResult<()> {
+        let mut project = H2Project::new("name", "1.0");
+        ActionBufferCreateFromBytes::new("buffer", b"\x00\x01\x02\x03", 0).apply(&mut project)?;
+        ActionLayerCreate::new("buffer", "default").apply(&mut project)?;
+
+        // Once the entry is applied, it only knows its offset and display -
+        // make sure the description survives
+        let t = H2Integer::new(IntegerReader::U16(Endian::Big), HexFormatter::pretty_integer());
+        let resolved = project.buffer_get_or_err("buffer")?.peek(&t, 2)?;
+        let mut action = ActionEntryCreate::new("buffer", "default", resolved, Some(t));
+        action.apply(&mut project)?;
+        assert_eq!("Create entry '0x0203' @ 0x2 in buffer 'buffer'", action.description());
+
+        action.undo(&mut project)?;
+        assert_eq!("Create entry '0x0203' @ 0x2 in buffer 'buffer'", action.description());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_shorten() -> SimpleResult<()> {
+        assert_eq!("short", shorten("short"));
+        assert_eq!(32, shorten(&"A".repeat(100)).len());
+        assert!(shorten(&"A".repeat(100)).ends_with("..."));
+
+        Ok(())
+    }
 }
@@ -8,7 +8,7 @@ use serde::{Serialize, Deserialize};
 use simple_error::{SimpleResult, SimpleError, bail};
 
 use crate::project::H2Project;
-use crate::actions::Action;
+use crate::actions::{Action, ActionCategory};
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Forward {
@@ -31,6 +31,14 @@ impl NullAction {
     pub fn new() -> Action {
         Action::Null(NullAction(State::Forward(Forward{})))
     }
+
+    pub fn description(&self) -> String {
+        "Do nothing".to_string()
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Other
+    }
 }
 
 impl Command for NullAction {
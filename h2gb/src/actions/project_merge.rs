@@ -0,0 +1,306 @@
+use redo::Command;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use crate::project::{H2Project, H2Id};
+use crate::actions::{Action, ActionCategory};
+use crate::import::{MergeLayer, ProjectMerge};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Forward {
+    layers: Vec<MergeLayer>,
+
+    // Set once the merge has been applied, so redo gets the same IDs
+    #[serde(default)]
+    ids: Vec<H2Id>,
+}
+
+/// What was done to a single layer, so it can be taken back out.
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Applied {
+    buffer: String,
+    layer: String,
+    created: bool,
+    entry_ids: Vec<H2Id>,
+    comments: Vec<(usize, Option<String>)>,
+    bookmarks: Vec<(usize, Option<String>)>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Backward {
+    layers: Vec<MergeLayer>,
+    ids: Vec<H2Id>,
+    applied: Vec<Applied>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum State {
+    Forward(Forward),
+    Backward(Backward),
+}
+
+/// Apply a [`ProjectMerge`] from [`crate::import::merge_projects`], as a
+/// single action.
+///
+/// Conflicts aren't touched - only what could be merged cleanly is. Undoing
+/// the action removes the merged entries and layers, and puts back the
+/// comments and bookmarks.
+///
+/// If anything can't be applied (say, the project changed since the merge was
+/// worked out), nothing is changed.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionProjectMerge(State);
+
+impl ActionProjectMerge {
+    pub fn new(merge: &ProjectMerge) -> Action {
+        Action::ProjectMerge(
+            ActionProjectMerge(
+                State::Forward(Forward {
+                    layers: merge.layers.clone(),
+                    ids: vec![],
+                })
+            )
+        )
+    }
+
+    pub fn description(&self) -> String {
+        let layers = match &self.0 {
+            State::Forward(f)  => &f.layers,
+            State::Backward(b) => &b.layers,
+        };
+
+        let entries: usize = layers.iter().map(|l| l.entries.len()).sum();
+        let comments: usize = layers.iter().map(|l| l.comments.len()).sum();
+        let bookmarks: usize = layers.iter().map(|l| l.bookmarks.len()).sum();
+
+        format!("Merge {} entry(s), {} comment(s), and {} bookmark(s) from another project", entries, comments, bookmarks)
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Layer
+    }
+}
+
+/// Take back whatever was applied, newest first.
+fn revert(project: &mut H2Project, applied: &[Applied]) -> SimpleResult<()> {
+    for applied in applied.iter().rev() {
+        let mut buffer = project.buffer_get_mut_or_err(&applied.buffer)?;
+        let layer = buffer.layer_get_mut_or_err(&applied.layer)?;
+
+        for id in applied.entry_ids.iter().rev() {
+            layer.entry_remove_by_id(*id)?;
+        }
+
+        for (offset, original) in applied.comments.iter().rev() {
+            layer.comment_set(*offset, original.clone())?;
+        }
+
+        for (offset, original) in applied.bookmarks.iter().rev() {
+            layer.bookmark_set(*offset, original.clone())?;
+        }
+
+        if applied.created {
+            buffer.layer_remove(&applied.layer)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply a single layer, recording everything as we go (so even a partial
+/// application can be reverted).
+fn apply_layer(project: &mut H2Project, merge_layer: &MergeLayer, ids: &mut Vec<H2Id>, next_id: &mut usize, applied: &mut Applied) -> SimpleResult<()> {
+    // Re-use the IDs from the last time this was applied, if there was one
+    let mut id = |project: &mut H2Project| {
+        let id = match ids.get(*next_id) {
+            Some(id) => *id,
+            None     => {
+                let id = project.id_allocate();
+                ids.push(id);
+                id
+            },
+        };
+        *next_id += 1;
+
+        id
+    };
+
+    if merge_layer.create {
+        let layer_id = id(project);
+        project.buffer_get_mut_or_err(&merge_layer.buffer)?.layer_add(&merge_layer.layer, layer_id)?;
+        applied.created = true;
+    }
+
+    for (resolved, origin, provenance) in &merge_layer.entries {
+        let entry_id = id(project);
+        project
+            .buffer_get_mut_or_err(&merge_layer.buffer)?
+            .layer_get_mut_or_err(&merge_layer.layer)?
+            .entry_create(resolved.clone(), origin.clone(), entry_id, provenance.clone())?;
+        applied.entry_ids.push(entry_id);
+    }
+
+    let mut buffer = project.buffer_get_mut_or_err(&merge_layer.buffer)?;
+    let layer = buffer.layer_get_mut_or_err(&merge_layer.layer)?;
+
+    for (offset, comment) in &merge_layer.comments {
+        let original = layer.comment_set(*offset, Some(comment.clone()))?;
+        applied.comments.push((*offset, original));
+    }
+
+    for (offset, name) in &merge_layer.bookmarks {
+        let original = layer.bookmark_set(*offset, Some(name.clone()))?;
+        applied.bookmarks.push((*offset, original));
+    }
+
+    Ok(())
+}
+
+impl Command for ActionProjectMerge {
+    type Target = H2Project;
+    type Error = SimpleError;
+
+    fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the forward struct
+        let forward = match &self.0 {
+            State::Forward(f) => f,
+            _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
+        };
+
+        let mut ids = forward.ids.clone();
+        let mut next_id = 0;
+        let mut applied: Vec<Applied> = vec![];
+
+        for merge_layer in &forward.layers {
+            applied.push(Applied {
+                buffer: merge_layer.buffer.clone(),
+                layer: merge_layer.layer.clone(),
+                ..Default::default()
+            });
+
+            // Back out on failure, so we don't leave a half-merged project
+            if let Err(e) = apply_layer(project, merge_layer, &mut ids, &mut next_id, applied.last_mut().unwrap()) {
+                revert(project, &applied)?;
+                bail!("Failed to merge into layer {} in buffer {}: {}", merge_layer.layer, merge_layer.buffer, e);
+            }
+        }
+
+        // Save the backward struct
+        self.0 = State::Backward(Backward {
+            layers: forward.layers.clone(),
+            ids: ids,
+            applied: applied,
+        });
+
+        Ok(())
+    }
+
+    fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the backward struct
+        let backward = match &self.0 {
+            State::Backward(b) => b,
+            _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
+        };
+
+        revert(project, &backward.applied)?;
+
+        // Save the forward struct
+        self.0 = State::Forward(Forward {
+            layers: backward.layers.clone(),
+            ids: backward.ids.clone(),
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+    use simple_error::SimpleResult;
+
+    use generic_number::{DefaultFormatter, IntegerReader};
+    use h2datatype::simple::numeric::H2Integer;
+
+    use crate::actions::*;
+    use crate::import::merge_projects;
+
+    fn project() -> SimpleResult<Record<Action>> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x01\x02\x03\x04", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "default"))?;
+
+        Ok(record)
+    }
+
+    fn entry(record: &mut Record<Action>, layer: &str, offset: usize) -> SimpleResult<()> {
+        let datatype = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+        let resolved = record.target().peek("buffer", &datatype, offset)?;
+        record.apply(ActionEntryCreate::new("buffer", layer, resolved, Some(datatype)))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let mut ours = project()?;
+        let mut theirs = project()?;
+
+        entry(&mut ours, "default", 0)?;
+        entry(&mut theirs, "default", 1)?;
+        theirs.apply(ActionEntrySetComment::new("buffer", "default", 2, Some("theirs".to_string())))?;
+        theirs.apply(ActionLayerCreate::new("buffer", "extra"))?;
+        entry(&mut theirs, "extra", 3)?;
+
+        let merge = merge_projects(ours.target(), theirs.target())?;
+        ours.apply(ActionProjectMerge::new(&merge))?;
+
+        let buffer = ours.target().buffer_get_or_err("buffer")?;
+        assert_eq!("1", buffer.layer_get_or_err("default")?.entry_get_or_err(0)?.resolved().display);
+        assert_eq!("2", buffer.layer_get_or_err("default")?.entry_get_or_err(1)?.resolved().display);
+        assert_eq!(Some(&"theirs".to_string()), buffer.layer_get_or_err("default")?.comment_get(2)?);
+        assert_eq!("4", buffer.layer_get_or_err("extra")?.entry_get_or_err(3)?.resolved().display);
+
+        // Undo takes out everything that came from them
+        ours.undo()?;
+        let buffer = ours.target().buffer_get_or_err("buffer")?;
+        assert!(buffer.layer_get_or_err("default")?.entry_get_or_err(0).is_ok());
+        assert!(buffer.layer_get_or_err("default")?.entry_get(1)?.is_none());
+        assert_eq!(None, buffer.layer_get_or_err("default")?.comment_get(2)?);
+        assert!(!buffer.layer_exists("extra"));
+
+        // Redo puts it back, with the same IDs
+        ours.redo()?;
+        let buffer = ours.target().buffer_get_or_err("buffer")?;
+        assert_eq!(
+            theirs.target().buffer_get_or_err("buffer")?.layer_get_or_err("extra")?.entry_get_or_err(3)?.resolved().display,
+            buffer.layer_get_or_err("extra")?.entry_get_or_err(3)?.resolved().display,
+        );
+        assert_eq!(1, ours.target().buffer_get_or_err("buffer")?.layer_get_or_err("default")?.entry_get_or_err(1)?.resolved().actual_range.start);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_fails_cleanly() -> SimpleResult<()> {
+        let mut ours = project()?;
+        let mut theirs = project()?;
+
+        theirs.apply(ActionEntrySetComment::new("buffer", "default", 0, Some("theirs".to_string())))?;
+        entry(&mut theirs, "default", 1)?;
+        let merge = merge_projects(ours.target(), theirs.target())?;
+
+        // Get in the way of the merge after it's worked out
+        entry(&mut ours, "default", 1)?;
+        assert!(ours.apply(ActionProjectMerge::new(&merge)).is_err());
+        assert_eq!(None, ours.target().buffer_get_or_err("buffer")?.layer_get_or_err("default")?.comment_get(0)?);
+
+        Ok(())
+    }
+}
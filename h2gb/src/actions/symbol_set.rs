@@ -0,0 +1,242 @@
+use redo::Command;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use crate::actions::{Action, ActionCategory};
+use crate::project::{H2Creator, H2Id, H2Project, H2Provenance, H2Symbol, H2SymbolScope};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Forward {
+    name: String,
+    scope: H2SymbolScope,
+    buffer: String,
+
+    // Where the symbol points, or None to remove it
+    offset: Option<usize>,
+    provenance: H2Provenance,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Backward {
+    name: String,
+    scope: H2SymbolScope,
+    buffer: String,
+    offset: Option<usize>,
+    provenance: H2Provenance,
+
+    buffer_id: H2Id,
+
+    // The symbol that was replaced or removed, if any
+    old_symbol: Option<H2Symbol>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum State {
+    Forward(Forward),
+    Backward(Backward),
+}
+
+/// Add, move, or remove a symbol - see [`crate::project::H2SymbolTable`].
+///
+/// Setting a symbol that already exists (with the same name and scope)
+/// replaces it. For a local symbol, `buffer` is both where it points and
+/// where it can be seen from; for a global one, it's just where it points.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionSymbolSet(State);
+
+impl ActionSymbolSet {
+    pub fn new(name: &str, scope: H2SymbolScope, buffer: &str, offset: usize) -> Action {
+        Self::new_with_creator(name, scope, buffer, offset, H2Creator::User)
+    }
+
+    /// Create a symbol found by an analyzer or script.
+    pub fn new_with_creator(name: &str, scope: H2SymbolScope, buffer: &str, offset: usize, creator: H2Creator) -> Action {
+        Self::new_common(name, scope, buffer, Some(offset), H2Provenance::new(creator))
+    }
+
+    /// Remove a symbol. `buffer` is only used to find local symbols.
+    pub fn new_remove(name: &str, scope: H2SymbolScope, buffer: &str) -> Action {
+        Self::new_common(name, scope, buffer, None, H2Provenance::default())
+    }
+
+    fn new_common(name: &str, scope: H2SymbolScope, buffer: &str, offset: Option<usize>, provenance: H2Provenance) -> Action {
+        Action::SymbolSet(
+            ActionSymbolSet(
+                State::Forward(Forward {
+                    name: name.to_string(),
+                    scope: scope,
+                    buffer: buffer.to_string(),
+                    offset: offset,
+                    provenance: provenance,
+                })
+            )
+        )
+    }
+
+    pub fn description(&self) -> String {
+        let (name, scope, buffer, offset) = match &self.0 {
+            State::Forward(f)  => (&f.name, f.scope, &f.buffer, f.offset),
+            State::Backward(b) => (&b.name, b.scope, &b.buffer, b.offset),
+        };
+
+        match offset {
+            Some(offset) => format!("Set {} symbol '{}' to 0x{:x} in buffer '{}'", scope, name, offset, buffer),
+            None         => format!("Remove {} symbol '{}'", scope, name),
+        }
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Symbol
+    }
+}
+
+impl Command for ActionSymbolSet {
+    type Target = H2Project;
+    type Error = SimpleError;
+
+    fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the forward struct
+        let forward = match &self.0 {
+            State::Forward(f) => f,
+            _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
+        };
+
+        let buffer_id = project.buffer_get_or_err(&forward.buffer)?.id();
+
+        let old_symbol = match forward.offset {
+            Some(offset) => project.symbol_set(H2Symbol::new(&forward.name, forward.scope, buffer_id, offset, forward.provenance.clone()))?,
+            None         => Some(project.symbol_remove(&forward.name, forward.scope, buffer_id)?),
+        };
+
+        // Save the backward struct
+        self.0 = State::Backward(Backward {
+            name: forward.name.clone(),
+            scope: forward.scope,
+            buffer: forward.buffer.clone(),
+            offset: forward.offset,
+            provenance: forward.provenance.clone(),
+
+            buffer_id: buffer_id,
+            old_symbol: old_symbol,
+        });
+
+        Ok(())
+    }
+
+    fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the backward struct
+        let backward = match &self.0 {
+            State::Backward(b) => b,
+            _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
+        };
+
+        if backward.offset.is_some() {
+            project.symbol_remove(&backward.name, backward.scope, backward.buffer_id)?;
+        }
+
+        if let Some(old_symbol) = &backward.old_symbol {
+            project.symbol_set(old_symbol.clone())?;
+        }
+
+        // Find the buffer by ID, in case it's been renamed
+        let buffer = match project.buffer_name(backward.buffer_id) {
+            Some(buffer) => buffer.to_string(),
+            None         => bail!("Failed to undo: buffer {} disappeared", backward.buffer_id),
+        };
+
+        // Save the forward struct
+        self.0 = State::Forward(Forward {
+            name: backward.name.clone(),
+            scope: backward.scope,
+            buffer: buffer,
+            offset: backward.offset,
+            provenance: backward.provenance.clone(),
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+    use simple_error::SimpleResult;
+
+    use crate::actions::ActionBufferCreateFromBytes;
+
+    fn build() -> SimpleResult<Record<Action>> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer1", &[0; 0x20], 0))?;
+        record.apply(ActionBufferCreateFromBytes::new("buffer2", &[0; 0x20], 0))?;
+
+        Ok(record)
+    }
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let mut record = build()?;
+
+        let action = ActionSymbolSet::new("player_name", H2SymbolScope::Global, "buffer1", 0x10);
+        assert_eq!("Set global symbol 'player_name' to 0x10 in buffer 'buffer1'", action.description());
+        assert_eq!(ActionCategory::Symbol, action.category());
+        record.apply(action)?;
+
+        record.apply(ActionSymbolSet::new_with_creator("main", H2SymbolScope::Local, "buffer2", 0x4, H2Creator::analyzer("elf")))?;
+
+        // Found by name, and used to describe addresses
+        assert_eq!(Some(("buffer1", 0x10)), record.target().symbol_lookup("player_name", None)?);
+        assert_eq!(Some(("buffer1", 0x10)), record.target().symbol_lookup("player_name", Some("buffer2"))?);
+        assert_eq!(Some("player_name".to_string()), record.target().symbolize("buffer1", 0x10)?);
+        assert_eq!(Some("player_name+0x4".to_string()), record.target().symbolize("buffer1", 0x14)?);
+        assert_eq!(None, record.target().symbolize("buffer1", 0x0f)?);
+
+        // Local symbols are only found from their own buffer
+        assert_eq!(Some(("buffer2", 0x4)), record.target().symbol_lookup("main", Some("buffer2"))?);
+        assert_eq!(None, record.target().symbol_lookup("main", Some("buffer1"))?);
+        assert_eq!(None, record.target().symbol_lookup("main", None)?);
+        let main = record.target().symbols().lookup("main", record.target().buffer_id("buffer2")).unwrap();
+        assert_eq!(&H2Creator::analyzer("elf"), main.provenance.creator());
+
+        // Moving and removing can be undone
+        record.apply(ActionSymbolSet::new("player_name", H2SymbolScope::Global, "buffer2", 0x0))?;
+        assert_eq!(Some(("buffer2", 0x0)), record.target().symbol_lookup("player_name", None)?);
+        record.apply(ActionSymbolSet::new_remove("player_name", H2SymbolScope::Global, "buffer1"))?;
+        assert_eq!(None, record.target().symbol_lookup("player_name", None)?);
+        assert_eq!(1, record.target().symbols().len());
+
+        record.undo()?;
+        assert_eq!(Some(("buffer2", 0x0)), record.target().symbol_lookup("player_name", None)?);
+        record.undo()?;
+        assert_eq!(Some(("buffer1", 0x10)), record.target().symbol_lookup("player_name", None)?);
+        record.undo()?;
+        record.undo()?;
+        assert!(record.target().symbols().is_empty());
+
+        record.redo()?;
+        assert_eq!(Some(("buffer1", 0x10)), record.target().symbol_lookup("player_name", None)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_fails() -> SimpleResult<()> {
+        let mut record = build()?;
+
+        assert!(record.apply(ActionSymbolSet::new("name", H2SymbolScope::Global, "nope", 0)).is_err());
+        assert!(record.apply(ActionSymbolSet::new("name", H2SymbolScope::Global, "buffer1", 0x20)).is_err());
+        assert!(record.apply(ActionSymbolSet::new_remove("name", H2SymbolScope::Global, "buffer1")).is_err());
+
+        // Local symbols are removed from the right buffer
+        record.apply(ActionSymbolSet::new("name", H2SymbolScope::Local, "buffer1", 0))?;
+        assert!(record.apply(ActionSymbolSet::new_remove("name", H2SymbolScope::Local, "buffer2")).is_err());
+        assert!(record.apply(ActionSymbolSet::new_remove("name", H2SymbolScope::Global, "buffer1")).is_err());
+        assert_eq!(1, record.target().symbols().len());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+//! Set or remove a project variable.
+//!
+//! Variables are named numbers - usually found by an analyzer, like a
+//! format's version - that [`h2datatype::composite::H2Switch`] types use to
+//! pick a case (see [`crate::project::H2DataOverlay`]). Entries that already
+//! exist aren't changed - the variable only affects what's resolved
+//! afterwards.
+
+use redo::Command;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use crate::project::H2Project;
+use crate::actions::{Action, ActionCategory};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Forward {
+    name: String,
+
+    // The new value, or None to remove it
+    value: Option<u64>,
+}
+
+// Backward is identical to forward (but holds the old value)
+type Backward = Forward;
+
+#[derive(Serialize, Deserialize, Debug)]
+enum State {
+    Forward(Forward),
+    Backward(Backward),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionVariableSet(State);
+
+impl ActionVariableSet {
+    pub fn new(name: &str, value: u64) -> Action {
+        Self::new_common(name, Some(value))
+    }
+
+    pub fn new_remove(name: &str) -> Action {
+        Self::new_common(name, None)
+    }
+
+    fn new_common(name: &str, value: Option<u64>) -> Action {
+        Action::VariableSet(
+            ActionVariableSet(
+                State::Forward(Forward {
+                    name: name.to_string(),
+                    value: value,
+                })
+            )
+        )
+    }
+
+    pub fn description(&self) -> String {
+        match &self.0 {
+            State::Forward(Forward { name, value: Some(value) }) => format!("Set variable '{}' to {}", name, value),
+            State::Forward(Forward { name, value: None })        => format!("Remove variable '{}'", name),
+            State::Backward(b)                                   => format!("Change variable '{}'", b.name),
+        }
+    }
+
+    pub fn category(&self) -> ActionCategory {
+        ActionCategory::Other
+    }
+}
+
+impl Command for ActionVariableSet {
+    type Target = H2Project;
+    type Error = SimpleError;
+
+    fn apply(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the forward struct
+        let forward = match &self.0 {
+            State::Forward(f) => f,
+            _                 => bail!("Failed to apply: action ended up in a broken undo/redo state"),
+        };
+
+        let old_value = project.variable_set(&forward.name, forward.value)?;
+
+        // Save the backward struct
+        self.0 = State::Backward(Backward {
+            name: forward.name.clone(),
+            value: old_value,
+        });
+
+        Ok(())
+    }
+
+    fn undo(&mut self, project: &mut H2Project) -> SimpleResult<()> {
+        // Get the backward struct
+        let backward = match &self.0 {
+            State::Backward(b) => b,
+            _                  => bail!("Failed to undo: action ended up in a broken undo/redo state"),
+        };
+
+        let value = project.variable_set(&backward.name, backward.value)?;
+
+        // Save the forward struct
+        self.0 = State::Forward(Forward {
+            name: backward.name.clone(),
+            value: value,
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+    use simple_error::SimpleResult;
+
+    use generic_number::{Endian, IntegerReader, DefaultFormatter};
+    use h2datatype::composite::{H2Struct, H2Switch};
+    use h2datatype::simple::numeric::H2Integer;
+
+    use crate::actions::ActionBufferCreateFromBytes;
+
+    #[test]
+    fn test_action() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x01\x02\x03\x04", 0))?;
+
+        // Old versions have a 16-bit value, new ones have a 32-bit one
+        let t = H2Struct::new(vec![
+            ("value".to_string(), H2Switch::new("format_version", vec![
+                (0..2,        H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer())),
+                (2..u64::MAX, H2Integer::new(IntegerReader::U32(Endian::Big), DefaultFormatter::new_integer())),
+            ], None)?),
+        ])?;
+
+        // Without the variable, the switch can't be resolved
+        assert!(record.target().peek("buffer", &t, 0).is_err());
+
+        record.apply(ActionVariableSet::new("format_version", 1))?;
+        assert_eq!(Some(1), record.target().data().variable_get("format_version"));
+        assert_eq!("{ value: 258 }", record.target().peek("buffer", &t, 0)?.display);
+
+        record.apply(ActionVariableSet::new("format_version", 2))?;
+        assert_eq!("{ value: 16909060 }", record.target().peek("buffer", &t, 0)?.display);
+
+        record.undo()?;
+        assert_eq!("{ value: 258 }", record.target().peek("buffer", &t, 0)?.display);
+
+        record.apply(ActionVariableSet::new_remove("format_version"))?;
+        assert_eq!(None, record.target().data().variable_get("format_version"));
+
+        record.undo()?;
+        assert_eq!(Some(1), record.target().data().variable_get("format_version"));
+
+        record.undo()?;
+        assert_eq!(None, record.target().data().variable_get("format_version"));
+
+        // Empty names are rejected
+        assert!(record.apply(ActionVariableSet::new("", 1)).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,418 @@
+//! Analyze BSON documents, like the `.bson` files written by `mongodump`.
+//!
+//! A BSON document is a length, a list of elements, and a terminating NUL.
+//! Each element is a type byte, a NUL-terminated name, and a value whose
+//! layout depends on the type - including embedded documents and arrays,
+//! which are documents in their own right.
+//!
+//! Each top-level document becomes a single nested entry, with its elements
+//! (and the elements of any documents inside it) as children named after the
+//! elements themselves.
+
+use redo::Record;
+use simple_error::{SimpleResult, SimpleError, bail};
+use lazy_static::lazy_static;
+
+use h2datatype::{H2Type, ResolvedType};
+use h2datatype::simple::{H2Blob, H2Enum};
+use h2datatype::simple::numeric::{H2Integer, H2Float};
+use h2datatype::simple::string::NTString;
+use h2datatype::composite::{H2Array, H2Struct};
+
+use generic_number::{IntegerReader, FloatReader, CharacterReader, CharacterFormatter, Endian, DefaultFormatter, HexFormatter, BooleanFormatter};
+
+use crate::actions::*;
+use crate::project::{H2Creator, H2Nesting};
+use super::Cursor;
+
+const LAYER: &'static str = "default";
+
+/// Documents can be nested, but not forever - the MongoDB server stops at 100
+const MAX_DEPTH: usize = 100;
+
+lazy_static! {
+    /// Lengths are signed in the spec, but can't be negative
+    static ref LENGTH: H2Type = {
+        H2Integer::new(IntegerReader::U32(Endian::Little), DefaultFormatter::new_integer())
+    };
+
+    static ref I32: H2Type = {
+        H2Integer::new(IntegerReader::I32(Endian::Little), DefaultFormatter::new_integer())
+    };
+
+    static ref I64: H2Type = {
+        H2Integer::new(IntegerReader::I64(Endian::Little), DefaultFormatter::new_integer())
+    };
+
+    static ref U64_HEX: H2Type = {
+        H2Integer::new(IntegerReader::U64(Endian::Little), HexFormatter::pretty_integer())
+    };
+
+    static ref U8_HEX: H2Type = {
+        H2Integer::new(IntegerReader::U8, HexFormatter::pretty_integer())
+    };
+
+    static ref DOUBLE: H2Type = {
+        H2Float::new(FloatReader::F64(Endian::Little), DefaultFormatter::new_float())
+    };
+
+    static ref BOOLEAN: H2Type = {
+        H2Integer::new(IntegerReader::U8, BooleanFormatter::new_integer())
+    };
+
+    static ref ELEMENT_TYPE: H2Type = {
+        H2Enum::new(IntegerReader::U8, "BsonType").unwrap()
+    };
+
+    /// Element names (and regex patterns) are "cstrings"
+    static ref CSTRING: H2Type = {
+        NTString::new(CharacterReader::UTF8, CharacterFormatter::pretty_str_character())
+    };
+
+    /// Strings have a length (which counts the NUL), but are also
+    /// NUL-terminated
+    static ref STRING: H2Type = {
+        H2Struct::new(vec![
+            ("length".to_string(), LENGTH.clone()),
+            ("value".to_string(),  CSTRING.clone()),
+        ]).unwrap()
+    };
+
+    static ref OBJECT_ID: H2Type = {
+        H2Array::new(12, H2Integer::new(IntegerReader::U8, HexFormatter::new_integer(false, false, true))).unwrap()
+    };
+
+    static ref REGEX: H2Type = {
+        H2Struct::new(vec![
+            ("pattern".to_string(), CSTRING.clone()),
+            ("options".to_string(), CSTRING.clone()),
+        ]).unwrap()
+    };
+}
+
+/// Resolve a type at the cursor without creating an entry, and move past it.
+///
+/// Documents are read ahead this way to work out their layout, and only then
+/// created as a single entry.
+fn read(cursor: &mut Cursor, datatype: &H2Type) -> SimpleResult<ResolvedType> {
+    let resolved = cursor.peek(datatype)?;
+    cursor.seek(resolved.aligned_range.end as usize);
+
+    Ok(resolved)
+}
+
+/// Read a type with a fixed layout, and get it back for building a struct.
+fn read_type(cursor: &mut Cursor, datatype: &H2Type) -> SimpleResult<H2Type> {
+    read(cursor, datatype)?;
+
+    Ok(datatype.clone())
+}
+
+/// Read an integer, and get it as a [`usize`].
+fn read_usize(cursor: &mut Cursor, datatype: &H2Type) -> SimpleResult<usize> {
+    read(cursor, datatype)?.as_integer.ok_or(
+        SimpleError::new("Could not interpret BSON field as an integer")
+    )?.as_usize()
+}
+
+/// Build a struct out of named fields.
+fn fields(named: Vec<(&str, H2Type)>) -> SimpleResult<H2Type> {
+    H2Struct::new(named.into_iter().map(|(name, datatype)| (name.to_string(), datatype)).collect())
+}
+
+/// Read a string value, and check that its length matches.
+fn string_type(cursor: &mut Cursor) -> SimpleResult<H2Type> {
+    let resolved = read(cursor, &*STRING)?;
+
+    let length = resolved.children[0].as_integer.ok_or(
+        SimpleError::new("Couldn't read string length")
+    )?.as_usize()?;
+
+    if length != resolved.children[1].actual_range.end as usize - resolved.children[1].actual_range.start as usize {
+        bail!("BSON string at offset {} has the wrong length ({})", resolved.actual_range.start, length);
+    }
+
+    Ok(STRING.clone())
+}
+
+/// Read the value of a single element, and get its type based on the
+/// element's type (or `None` for types without a value).
+fn value_type(cursor: &mut Cursor, element_type: usize, path: &str, depth: usize) -> SimpleResult<Option<H2Type>> {
+    let datatype = match element_type {
+        0x01 => read_type(cursor, &*DOUBLE)?,
+        0x02 => string_type(cursor)?,
+        0x03 => document_type(cursor, path, depth + 1)?,
+        0x04 => document_type(cursor, path, depth + 1)?,
+        0x05 => {
+            let length = read_usize(cursor, &*LENGTH)?;
+            read(cursor, &*U8_HEX)?;
+
+            // Empty binaries are allowed, but blobs can't be empty
+            let mut binary = vec![("length", LENGTH.clone()), ("subtype", U8_HEX.clone())];
+            if length > 0 {
+                binary.push(("data", read_type(cursor, &H2Blob::new(length as u64)?)?));
+            }
+
+            fields(binary)?
+        },
+        0x06 => return Ok(None), // Undefined (deprecated), no value
+        0x07 => read_type(cursor, &*OBJECT_ID)?,
+        0x08 => read_type(cursor, &*BOOLEAN)?,
+        0x09 => read_type(cursor, &*I64)?, // UTC datetime (milliseconds since the epoch)
+        0x0a => return Ok(None), // Null, no value
+        0x0b => read_type(cursor, &*REGEX)?,
+        0x0c => {
+            // DBPointer (deprecated)
+            fields(vec![
+                ("namespace", string_type(cursor)?),
+                ("id",        read_type(cursor, &*OBJECT_ID)?),
+            ])?
+        },
+        0x0d => string_type(cursor)?, // JavaScript code
+        0x0e => string_type(cursor)?, // Symbol (deprecated)
+        0x0f => {
+            // JavaScript code with scope
+            let start = cursor.position();
+            let length = read_usize(cursor, &*LENGTH)?;
+            let code = string_type(cursor)?;
+            let scope = document_type(cursor, &format!("{}.$scope", path), depth + 1)?;
+
+            if cursor.position() != start + length {
+                bail!("BSON code with scope at offset {} has the wrong length ({})", start, length);
+            }
+
+            fields(vec![
+                ("length", LENGTH.clone()),
+                ("code",   code),
+                ("scope",  scope),
+            ])?
+        },
+        0x10 => read_type(cursor, &*I32)?,
+        0x11 => read_type(cursor, &*U64_HEX)?, // Timestamp
+        0x12 => read_type(cursor, &*I64)?,
+        0x13 => read_type(cursor, &H2Blob::new(16)?)?, // Decimal128
+        0x7f => return Ok(None), // Max key, no value
+        0xff => return Ok(None), // Min key, no value
+        _    => bail!("Unknown BSON element type 0x{:02x} at {}", element_type, path),
+    };
+
+    Ok(Some(datatype))
+}
+
+/// Read a document (or array) and everything in it, and get its type.
+///
+/// `path` is where the document is, for errors - empty for the top level.
+fn document_type(cursor: &mut Cursor, path: &str, depth: usize) -> SimpleResult<H2Type> {
+    if depth > MAX_DEPTH {
+        bail!("BSON documents are nested too deeply at {}", path);
+    }
+
+    let start = cursor.position();
+    let length = read_usize(cursor, &*LENGTH)?;
+
+    let mut elements = vec![];
+    loop {
+        let element_type = read_usize(cursor, &*ELEMENT_TYPE)?;
+
+        // A type of 0 is the document's terminator
+        if element_type == 0 {
+            break;
+        }
+
+        let name = read(cursor, &*CSTRING)?.as_string.ok_or(
+            SimpleError::new("Could not read BSON element name")
+        )?;
+        let path = match path {
+            "" => name.clone(),
+            _  => format!("{}.{}", path, name),
+        };
+
+        let mut element = vec![("type", ELEMENT_TYPE.clone()), ("name", CSTRING.clone())];
+        if let Some(value) = value_type(cursor, element_type, &path, depth)? {
+            element.push(("value", value));
+        }
+
+        elements.push((name, fields(element)?));
+    }
+
+    if cursor.position() != start + length {
+        bail!("BSON document at offset {} has the wrong length ({}, but ended at {})", start, length, cursor.position() - start);
+    }
+
+    // The elements get a struct of their own, so their names can't clash
+    // with the length or terminator
+    let mut document = vec![("length", LENGTH.clone())];
+    if !elements.is_empty() {
+        document.push(("elements", H2Struct::new(elements)?));
+    }
+    document.push(("terminator", ELEMENT_TYPE.clone()));
+
+    fields(document)
+}
+
+/// Annotate every document in a buffer of BSON documents, and return how
+/// many there were.
+///
+/// A single document works, as does a `mongodump` collection file, which is
+/// just documents one after the other.
+pub fn analyze_bson(record: &mut Record<Action>, buffer: &str) -> SimpleResult<usize> {
+    let length = record.target().buffer_get_or_err(buffer)?.len();
+    record.apply(ActionLayerCreate::new(buffer, LAYER))?;
+
+    let mut cursor = Cursor::new_with_creator(record, buffer, LAYER, 0, H2Creator::analyzer("bson"));
+    let mut count = 0;
+    while cursor.position() < length {
+        let start = cursor.position();
+        let document = document_type(&mut cursor, "", 0)?;
+
+        cursor.seek(start);
+        cursor.entry_nested(&document, H2Nesting::full(), None)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::project::H2Project;
+
+    /// Wrap a list of elements into a document.
+    fn document(elements: &[u8]) -> Vec<u8> {
+        let mut data = ((elements.len() + 5) as i32).to_le_bytes().to_vec();
+        data.extend_from_slice(elements);
+        data.push(0);
+
+        data
+    }
+
+    /// `{ "name": "hi", "n": 7, "ok": true, "nothing": null, "tags": [ 1.5 ] }`
+    fn test_bson() -> Vec<u8> {
+        let mut elements = b"\x02name\x00\x03\x00\x00\x00hi\x00".to_vec();
+        elements.extend_from_slice(b"\x10n\x00\x07\x00\x00\x00");
+        elements.extend_from_slice(b"\x08ok\x00\x01");
+        elements.extend_from_slice(b"\x0anothing\x00");
+
+        let mut array = b"\x010\x00".to_vec();
+        array.extend_from_slice(&1.5f64.to_le_bytes());
+        elements.extend_from_slice(b"\x04tags\x00");
+        elements.extend_from_slice(&document(&array));
+
+        document(&elements)
+    }
+
+    #[test]
+    fn test_analyze() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("BSON Test", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &test_bson(), 0x0))?;
+
+        assert_eq!(1, analyze_bson(&mut record, "buffer")?);
+
+        // The whole document is one entry
+        let layer = record.target().buffer_get_or_err("buffer")?.layer_get_or_err(LAYER)?;
+        let entry = layer.entry_get_or_err(0)?;
+        assert!(entry.is_nested());
+
+        let document = entry.resolved();
+        assert_eq!(0..61, document.actual_range);
+        assert_eq!("61", document.children[0].display);
+
+        // Its elements are named after themselves
+        let elements = &document.children[1];
+        assert_eq!(
+            vec!["name", "n", "ok", "nothing", "tags"],
+            elements.children.iter().map(|e| e.field_name.clone().unwrap()).collect::<Vec<_>>()
+        );
+
+        // "name": "hi"
+        let name = &elements.children[0];
+        assert_eq!(4..20, name.actual_range);
+        assert_eq!("BsonType::String", name.children[0].display);
+        assert_eq!("\"name\"", name.children[1].display);
+        assert_eq!("{ length: 3, value: \"hi\" }", name.children[2].display);
+
+        // "n": 7
+        assert_eq!("7", elements.children[1].children[2].display);
+
+        // "ok": true
+        assert_eq!("true", elements.children[2].children[2].display);
+
+        // "nothing": null has no value
+        assert_eq!("BsonType::Null", elements.children[3].children[0].display);
+        assert_eq!(2, elements.children[3].children.len());
+
+        // The array is a document of its own
+        let tags = &elements.children[4].children[2];
+        assert_eq!("16", tags.children[0].display);
+        assert_eq!(Some("0".to_string()), tags.children[1].children[0].field_name);
+        assert_eq!(51..59, tags.children[1].children[0].children[2].actual_range);
+
+        // Both terminators
+        assert_eq!(59..60, tags.children[2].actual_range);
+        assert_eq!(60..61, document.children[2].actual_range);
+
+        // The length, elements, and terminator are shown as children
+        assert_eq!(3, entry.children().len());
+
+        // Everything is marked as coming from this analyzer
+        assert!(layer.entries_by_creator(&H2Creator::User).is_empty());
+        assert_eq!(1, layer.entries_by_creator(&H2Creator::analyzer("bson")).len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("BSON Test", "1.0")
+        );
+
+        // Three documents back to back, like a mongodump file
+        let mut data = test_bson();
+        data.extend_from_slice(&document(b"\x12big\x00\xff\xff\xff\xff\xff\xff\xff\xff"));
+        data.extend_from_slice(&document(b""));
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &data, 0x0))?;
+
+        assert_eq!(3, analyze_bson(&mut record, "buffer")?);
+
+        let layer = record.target().buffer_get_or_err("buffer")?.layer_get_or_err(LAYER)?;
+        assert_eq!("-1", layer.entry_get_or_err(61)?.resolved().children[1].children[0].children[2].display);
+
+        // An empty document has no elements
+        let empty = layer.entry_get_or_err(79)?;
+        assert_eq!("5", empty.resolved().children[0].display);
+        assert_eq!(2, empty.resolved().children.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_documents() -> SimpleResult<()> {
+        // The document's length doesn't match its elements
+        let mut data = test_bson();
+        data[0] = 50;
+
+        // An unknown type
+        let bad_type = document(b"\x42x\x00");
+
+        // A string whose length doesn't match
+        let bad_string = document(b"\x02s\x00\x05\x00\x00\x00hi\x00");
+
+        for data in vec![data, bad_type, bad_string] {
+            let mut record: Record<Action> = Record::new(
+                H2Project::new("BSON Test", "1.0")
+            );
+            record.apply(ActionBufferCreateFromBytes::new("buffer", &data, 0x0))?;
+
+            assert!(analyze_bson(&mut record, "buffer").is_err());
+        }
+
+        Ok(())
+    }
+}
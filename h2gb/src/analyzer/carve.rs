@@ -0,0 +1,102 @@
+//! Mark (and optionally pull out) files embedded in a buffer, like images in
+//! a game archive or a PDF stuck to the end of an executable.
+//!
+//! This isn't a format analyzer - it works on anything - so it isn't in the
+//! registry. The files are found by [`crate::inference::find_signatures`];
+//! see there for what's recognized.
+
+use redo::Record;
+use simple_error::SimpleResult;
+
+use h2datatype::simple::H2Marker;
+
+use crate::actions::*;
+use crate::inference::{find_signatures, CarvedFile};
+use crate::project::H2Creator;
+use super::create_entry;
+
+/// The layer the markers go in.
+pub const CARVE_LAYER: &str = "carved";
+
+/// Find the files embedded in a buffer, and put a marker at the start of
+/// each in the [`CARVE_LAYER`] layer, with a comment saying how long it is.
+///
+/// If `extract` is set, each file is also copied into its own buffer, named
+/// after the buffer, offset, and type (like `buffer.1f00.png`), with the base
+/// address it had in the original.
+///
+/// Every action is applied to `record`, so it can all be undone; the files
+/// that were found are returned.
+pub fn carve_files(record: &mut Record<Action>, buffer: &str, extract: bool) -> SimpleResult<Vec<CarvedFile>> {
+    let (files, base_address) = {
+        let b = record.target().buffer_get_or_err(buffer)?;
+        (find_signatures(b, 0..b.len())?, b.base_address)
+    };
+
+    if !record.target().buffer_get_or_err(buffer)?.layer_exists(CARVE_LAYER) {
+        record.apply(ActionLayerCreate::new(buffer, CARVE_LAYER))?;
+    }
+
+    let creator = H2Creator::analyzer("carve");
+    for file in &files {
+        let comment = format!("{} file, 0x{:x} bytes{}", file.signature.name, file.range.len(), if file.exact { "" } else { " (guessed)" });
+        create_entry(record, buffer, CARVE_LAYER, &creator, &H2Marker::new(file.signature.name), file.range.start, Some(&comment))?;
+
+        if extract {
+            let name = format!("{}.{:x}.{}", buffer, file.range.start, file.signature.extension);
+            record.apply(ActionBufferExtract::new(&name, buffer, file.range.clone(), base_address + file.range.start))?;
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::project::H2Project;
+
+    #[test]
+    fn test_carve_files() -> SimpleResult<()> {
+        // Junk, a tiny PNG (just the signature and IEND), junk, then a PDF
+        // with no end
+        let mut data = b"junk".to_vec();
+        data.extend(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x00IEND\xae\x42\x60\x82");
+        data.extend(b"more junk");
+        data.extend(b"%PDF-1.4\n...");
+
+        let mut record: Record<Action> = Record::new(H2Project::new("name", "1.0"));
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &data, 0x1000))?;
+
+        let files = carve_files(&mut record, "buffer", true)?;
+        assert_eq!(vec![
+            "PNG at 0x4 (0x14 bytes)",
+            "PDF at 0x21 (0xc bytes, guessed)",
+        ], files.iter().map(|f| f.to_string()).collect::<Vec<_>>());
+
+        let project = record.target();
+        let layer = project.buffer_get_or_err("buffer")?.layer_get_or_err(CARVE_LAYER)?;
+        assert_eq!(vec![
+            (4..4, "<PNG>".to_string()),
+            (0x21..0x21, "<PDF>".to_string()),
+        ], layer.entries_all().iter().map(|e| (e.resolved().actual_range.clone(), e.resolved().display.clone())).collect::<Vec<_>>());
+        assert_eq!(Some(&"PNG file, 0x14 bytes".to_string()), layer.comment_get(4)?);
+        assert_eq!(Some(&"PDF file, 0xc bytes (guessed)".to_string()), layer.comment_get(0x21)?);
+
+        let png = project.buffer_get_or_err("buffer.4.png")?;
+        assert_eq!(&data[4..0x18], &png.data[..]);
+        assert_eq!(0x1004, png.base_address);
+        assert_eq!(b"%PDF-1.4\n...".to_vec(), project.buffer_get_or_err("buffer.21.pdf")?.data);
+
+        // It all comes back out
+        while record.can_undo() {
+            record.undo()?;
+        }
+        assert!(record.target().buffer_get("buffer.4.png").is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+use redo::Record;
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use generic_number::Integer;
+use h2datatype::{H2Type, ResolvedType};
+
+use crate::actions::*;
+use crate::project::{H2Creator, H2Nesting};
+use super::helpers::*;
+
+/// Creates entries one after another, keeping track of where we are.
+///
+/// Most of an analyzer is reading one field, then reading the next field
+/// wherever that one ended. Rather than carrying around a running offset (and
+/// a base for relative offsets), a `Cursor` moves forward on its own each time
+/// an entry is created.
+///
+/// Positions can be saved with [`Cursor::push`] and restored with
+/// [`Cursor::pop`], for jumping somewhere else in the buffer and coming back.
+pub struct Cursor<'a> {
+    record: &'a mut Record<Action>,
+    buffer: String,
+    layer: String,
+    creator: H2Creator,
+
+    position: usize,
+    saved: Vec<usize>,
+}
+
+impl<'a> Cursor<'a> {
+    /// Create a cursor whose entries are made by the user.
+    pub fn new(record: &'a mut Record<Action>, buffer: &str, layer: &str, position: usize) -> Self {
+        Self::new_with_creator(record, buffer, layer, position, H2Creator::User)
+    }
+
+    /// Create a cursor whose entries are made by an analyzer or script.
+    pub fn new_with_creator(record: &'a mut Record<Action>, buffer: &str, layer: &str, position: usize, creator: H2Creator) -> Self {
+        Self {
+            record: record,
+            buffer: buffer.to_string(),
+            layer: layer.to_string(),
+            creator: creator,
+
+            position: position,
+            saved: vec![],
+        }
+    }
+
+    /// The underlying record, for anything the cursor doesn't do.
+    pub fn record(&mut self) -> &mut Record<Action> {
+        self.record
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Move to an absolute offset.
+    pub fn seek(&mut self, position: usize) {
+        self.position = position;
+    }
+
+    /// Move forward without creating anything.
+    pub fn skip(&mut self, bytes: usize) {
+        self.position += bytes;
+    }
+
+    /// Move forward to the next multiple of `multiple` (if we aren't already
+    /// on one).
+    pub fn align(&mut self, multiple: usize) -> SimpleResult<()> {
+        if multiple == 0 {
+            bail!("Can't align to a multiple of 0");
+        }
+
+        let remainder = self.position % multiple;
+        if remainder != 0 {
+            self.position += multiple - remainder;
+        }
+
+        Ok(())
+    }
+
+    /// Save the current position, and move to `position`.
+    pub fn push(&mut self, position: usize) {
+        self.saved.push(self.position);
+        self.position = position;
+    }
+
+    /// Go back to the position saved by the last [`Cursor::push`], and return
+    /// the position we were at.
+    pub fn pop(&mut self) -> SimpleResult<usize> {
+        let previous = self.saved.pop().ok_or(
+            SimpleError::new("Can't pop: no saved positions")
+        )?;
+
+        Ok(std::mem::replace(&mut self.position, previous))
+    }
+
+    /// Resolve a type at the current position, without creating an entry or
+    /// moving.
+    pub fn peek(&mut self, datatype: &H2Type) -> SimpleResult<ResolvedType> {
+        peek_entry(self.record, &self.buffer, datatype, self.position)
+    }
+
+    /// Create an entry at the current position, and move to the end of it.
+    pub fn entry(&mut self, datatype: &H2Type, comment: Option<&str>) -> SimpleResult<ResolvedType> {
+        let resolved = create_entry(self.record, &self.buffer, &self.layer, &self.creator, datatype, self.position, comment)?;
+        self.position = resolved.aligned_range.end as usize;
+
+        Ok(resolved)
+    }
+
+    /// Create an entry whose fields are shown as their own entries (see
+    /// [`H2Nesting`]), and move to the end of it.
+    pub fn entry_nested(&mut self, datatype: &H2Type, nesting: H2Nesting, comment: Option<&str>) -> SimpleResult<ResolvedType> {
+        let resolved = self.peek(datatype)?;
+        self.record.apply(ActionEntryCreate::new_nested(&self.buffer, &self.layer, resolved.clone(), Some(datatype.clone()), self.creator.clone(), nesting))?;
+
+        if let Some(c) = comment {
+            self.comment(c)?;
+        }
+        self.position = resolved.aligned_range.end as usize;
+
+        Ok(resolved)
+    }
+
+    /// Create an entry, and get its value as an [`Integer`].
+    pub fn entry_integer(&mut self, datatype: &H2Type, comment: Option<&str>) -> SimpleResult<Integer> {
+        if !datatype.can_be_integer() {
+            bail!("Attempting to create a numeric entry from a non-numeric datatype");
+        }
+
+        self.entry(datatype, comment)?.as_integer.ok_or(
+            SimpleError::new("Could not interpret entry as an integer")
+        )
+    }
+
+    /// Create an entry, and get its value as a [`String`].
+    pub fn entry_string(&mut self, datatype: &H2Type, comment: Option<&str>) -> SimpleResult<String> {
+        if !datatype.can_be_string() {
+            bail!("Attempting to create a string entry from a non-string datatype");
+        }
+
+        self.entry(datatype, comment)?.as_string.ok_or(
+            SimpleError::new("Could not create entry as a String value")
+        )
+    }
+
+    /// Create `count` entries of the same type, one after the other.
+    pub fn entries(&mut self, datatype: &H2Type, count: usize) -> SimpleResult<Vec<ResolvedType>> {
+        (0..count).map(|_| self.entry(datatype, None)).collect()
+    }
+
+    /// Add a comment to the entry at the current position.
+    pub fn comment(&mut self, comment: &str) -> SimpleResult<()> {
+        add_comment(self.record, &self.buffer, &self.layer, self.position, comment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    use generic_number::{IntegerReader, CharacterReader, CharacterFormatter, DefaultFormatter, Endian};
+    use h2datatype::simple::numeric::H2Integer;
+    use h2datatype::simple::string::LPString;
+    use h2datatype::composite::H2Struct;
+
+    use crate::project::H2Project;
+
+    fn setup(data: &[u8]) -> SimpleResult<Record<Action>> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", data, 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        Ok(record)
+    }
+
+    #[test]
+    fn test_cursor() -> SimpleResult<()> {
+        let mut record = setup(b"\x02hi\x00\x01\x00\x02\x00\x03\x00\x00\x00\x00\x00\x00\x00\x41\x42\x43\x44")?;
+        let u16 = H2Integer::new(IntegerReader::U16(Endian::Little), DefaultFormatter::new_integer());
+        let s = LPString::new(IntegerReader::U8, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?;
+
+        let mut cursor = Cursor::new(&mut record, "buffer", "layer", 0);
+        assert_eq!("hi", cursor.entry_string(&s, Some("Name"))?);
+        assert_eq!(3, cursor.position());
+
+        // Three u16s, one after the other
+        cursor.skip(1);
+        let values = cursor.entries(&u16, 3)?;
+        assert_eq!(vec![4..6, 6..8, 8..10], values.iter().map(|v| v.actual_range.clone()).collect::<Vec<_>>());
+
+        // Align to the next 8-byte boundary, and jump ahead and back
+        cursor.align(8)?;
+        assert_eq!(16, cursor.position());
+        cursor.push(18);
+        assert_eq!(0x4443, cursor.entry_integer(&u16, None)?.as_usize()?);
+        assert_eq!(20, cursor.pop()?);
+        assert_eq!(16, cursor.position());
+        assert!(cursor.pop().is_err());
+
+        // Peeking doesn't move or create anything
+        assert_eq!(16..18, cursor.peek(&u16)?.actual_range);
+        assert_eq!(16, cursor.position());
+
+        let layer = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?;
+        assert_eq!(Some(&"Name".to_string()), layer.comment_get(0)?);
+        assert!(layer.entry_get_or_err(18).is_ok());
+        assert!(layer.entry_get_or_err(16).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_nested() -> SimpleResult<()> {
+        let mut record = setup(b"\x01\x00\x02\x00")?;
+        let u16 = H2Integer::new(IntegerReader::U16(Endian::Little), DefaultFormatter::new_integer());
+        let pair = H2Struct::new(vec![
+            ("a".to_string(), u16.clone()),
+            ("b".to_string(), u16),
+        ])?;
+
+        let mut cursor = Cursor::new(&mut record, "buffer", "layer", 0);
+        assert_eq!(0..4, cursor.entry_nested(&pair, H2Nesting::full(), Some("Pair"))?.actual_range);
+        assert_eq!(4, cursor.position());
+
+        let layer = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?;
+        let entry = layer.entry_get_or_err(0)?;
+        assert!(entry.is_nested());
+        assert_eq!(vec!["1", "2"], entry.children().iter().map(|c| c.resolved().display.clone()).collect::<Vec<_>>());
+        assert_eq!(Some(&"Pair".to_string()), layer.comment_get(0)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,345 @@
+//! Analyze an Android DEX file.
+//!
+//! This covers the header and the tables that everything else refers to -
+//! strings, types, and prototypes. Classes, methods, and code are left for
+//! later.
+
+use std::collections::HashMap;
+
+use redo::Record;
+use simple_error::{SimpleResult, SimpleError, bail};
+use lazy_static::lazy_static;
+
+use h2datatype::H2Type;
+use h2datatype::simple::H2Blob;
+use h2datatype::simple::numeric::{H2Integer, H2Leb128};
+use h2datatype::simple::string::{H2String, NTString};
+use h2datatype::composite::{H2Array, H2Struct};
+
+use generic_number::{IntegerReader, CharacterReader, CharacterFormatter, Endian, DefaultFormatter, HexFormatter};
+
+use crate::actions::*;
+use crate::project::H2Creator;
+use super::Cursor;
+
+const LAYER: &'static str = "default";
+
+/// Every DEX file we know how to read uses this tag - big-endian files exist
+/// in theory, but not in practice
+const ENDIAN_CONSTANT: usize = 0x12345678;
+
+/// The 32-bit fields that follow the magic, checksum, and signature, in order
+const HEADER_FIELDS: &[&str] = &[
+    "file_size",
+    "header_size",
+    "endian_tag",
+    "link_size",
+    "link_off",
+    "map_off",
+    "string_ids_size",
+    "string_ids_off",
+    "type_ids_size",
+    "type_ids_off",
+    "proto_ids_size",
+    "proto_ids_off",
+    "field_ids_size",
+    "field_ids_off",
+    "method_ids_size",
+    "method_ids_off",
+    "class_defs_size",
+    "class_defs_off",
+    "data_size",
+    "data_off",
+];
+
+lazy_static! {
+    static ref U32: H2Type = {
+        H2Integer::new(IntegerReader::U32(Endian::Little), DefaultFormatter::new_integer())
+    };
+
+    static ref U32_HEX: H2Type = {
+        H2Integer::new(IntegerReader::U32(Endian::Little), HexFormatter::pretty_integer())
+    };
+
+    /// The length of a string, in UTF-16 code units (not bytes)
+    static ref ULEB128: H2Type = {
+        H2Leb128::new(false, DefaultFormatter::new_integer())
+    };
+
+    /// Strings are actually MUTF-8, which only differs from UTF-8 for NUL
+    /// and characters outside the BMP - those are read by [`read_mutf8`]
+    /// instead
+    static ref STRING_DATA: H2Type = {
+        NTString::new(CharacterReader::UTF8, CharacterFormatter::pretty_str_character())
+    };
+
+    static ref PROTO_ID: H2Type = {
+        H2Struct::new(vec![
+            ("shorty_idx".to_string(),      U32.clone()),
+            ("return_type_idx".to_string(), U32.clone()),
+            ("parameters_off".to_string(),  U32_HEX.clone()),
+        ]).unwrap()
+    };
+}
+
+/// Look up an index into the string table, for comments.
+fn string_at<'a>(strings: &'a Vec<String>, index: usize) -> SimpleResult<&'a str> {
+    strings.get(index).map(|s| &s[..]).ok_or(
+        SimpleError::new(format!("String index {} is out of range ({} strings)", index, strings.len()))
+    )
+}
+
+/// Decode a NUL-terminated MUTF-8 string, and return it along with its size
+/// in bytes (including the terminator).
+///
+/// MUTF-8 encodes each UTF-16 code unit on its own, the same way UTF-8 would -
+/// so a character outside the BMP is a surrogate pair, six bytes long - and
+/// writes NUL as the overlong `C0 80` so it can't end the string. Anything
+/// that doesn't decode becomes U+FFFD.
+fn decode_mutf8(data: &[u8]) -> SimpleResult<(String, usize)> {
+    let continuation = |i: usize| data.get(i).filter(|b| **b & 0xc0 == 0x80).map(|b| (*b & 0x3f) as u16);
+
+    let mut units: Vec<u16> = Vec::new();
+    let mut i = 0;
+    loop {
+        let b = match data.get(i) {
+            Some(b) => *b as u16,
+            None    => bail!("MUTF-8 string is missing its terminator"),
+        };
+
+        let (unit, size) = match (b, continuation(i + 1), continuation(i + 2)) {
+            (0x00, _, _)                      => return Ok((String::from_utf16_lossy(&units), i + 1)),
+            (0x01..=0x7f, _, _)               => (b, 1),
+            (0xc0..=0xdf, Some(c1), _)        => (((b & 0x1f) << 6) | c1, 2),
+            (0xe0..=0xef, Some(c1), Some(c2)) => (((b & 0x0f) << 12) | (c1 << 6) | c2, 3),
+            _                                 => (0xfffd, 1),
+        };
+
+        units.push(unit);
+        i += size;
+    }
+}
+
+/// Read the string data at the cursor.
+///
+/// Most strings are valid UTF-8 as well, and get a string entry. The ones that
+/// aren't (because they have a NUL or a character outside the BMP) get a blob,
+/// with the decoded string as a comment.
+fn read_mutf8(cursor: &mut Cursor, buffer: &str) -> SimpleResult<String> {
+    if cursor.peek(&*STRING_DATA).is_ok() {
+        return cursor.entry_string(&*STRING_DATA, None);
+    }
+
+    let position = cursor.position();
+    let (string, size) = {
+        let buffer = cursor.record().target().buffer_get_or_err(buffer)?;
+        if position >= buffer.len() {
+            bail!("String data at 0x{:x} is past the end of the file", position);
+        }
+
+        decode_mutf8(buffer.byte_range(position..buffer.len())?)?
+    };
+
+    cursor.entry(&H2Blob::new(size as u64)?, Some(&format!("MUTF-8: {:?}", string)))?;
+
+    Ok(string)
+}
+
+fn parse_header(cursor: &mut Cursor) -> SimpleResult<HashMap<&'static str, usize>> {
+    cursor.entry(&H2String::new(8, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?, Some("\"Magic\" value and version"))?;
+    cursor.entry(&*U32_HEX, Some("Adler-32 checksum"))?;
+    cursor.entry(&H2Array::new(20, H2Integer::new(IntegerReader::U8, HexFormatter::new_integer(false, false, true)))?, Some("SHA-1 signature"))?;
+
+    let mut header = HashMap::new();
+    for field in HEADER_FIELDS {
+        let value = cursor.entry_integer(&*U32_HEX, Some(field))?.as_usize()?;
+        header.insert(*field, value);
+    }
+
+    if header["endian_tag"] != ENDIAN_CONSTANT {
+        bail!("Unsupported DEX endian tag: 0x{:x}", header["endian_tag"]);
+    }
+
+    Ok(header)
+}
+
+fn parse_string_ids(cursor: &mut Cursor, buffer: &str, count: usize, offset: usize) -> SimpleResult<Vec<String>> {
+    cursor.seek(offset);
+
+    let mut strings = Vec::new();
+    for i in 0..count {
+        let string_data_off = cursor.entry_integer(&*U32_HEX, Some(&format!("string_ids[{}]", i)))?.as_usize()?;
+
+        // Go read the string, then come back for the next ID
+        cursor.push(string_data_off);
+        cursor.entry(&*ULEB128, Some(&format!("Length of string {} (UTF-16 units)", i)))?;
+        strings.push(read_mutf8(cursor, buffer)?);
+        cursor.pop()?;
+    }
+
+    Ok(strings)
+}
+
+fn parse_type_ids(cursor: &mut Cursor, count: usize, offset: usize, strings: &Vec<String>) -> SimpleResult<()> {
+    cursor.seek(offset);
+
+    for i in 0..count {
+        let position = cursor.position();
+        let descriptor_idx = cursor.entry_integer(&*U32, None)?.as_usize()?;
+
+        cursor.push(position);
+        cursor.comment(&format!("type_ids[{}]: {}", i, string_at(strings, descriptor_idx)?))?;
+        cursor.pop()?;
+    }
+
+    Ok(())
+}
+
+fn parse_proto_ids(cursor: &mut Cursor, count: usize, offset: usize, strings: &Vec<String>) -> SimpleResult<()> {
+    cursor.seek(offset);
+
+    for i in 0..count {
+        let position = cursor.position();
+        let proto = cursor.entry(&*PROTO_ID, None)?;
+
+        let shorty_idx = proto.children[0].as_integer.ok_or(
+            SimpleError::new("Couldn't read shorty_idx")
+        )?.as_usize()?;
+
+        cursor.push(position);
+        cursor.comment(&format!("proto_ids[{}]: {}", i, string_at(strings, shorty_idx)?))?;
+        cursor.pop()?;
+    }
+
+    Ok(())
+}
+
+/// Annotate the header, strings, types, and prototypes of a DEX file, and
+/// return the string table.
+///
+/// Other analyses (and users) mostly want the strings, since every other
+/// table refers to them by index.
+pub fn analyze_dex(record: &mut Record<Action>, buffer: &str) -> SimpleResult<Vec<String>> {
+    record.apply(ActionLayerCreate::new(buffer, LAYER))?;
+
+    let mut cursor = Cursor::new_with_creator(record, buffer, LAYER, 0, H2Creator::analyzer("dex"));
+    let header = parse_header(&mut cursor)?;
+
+    let strings = parse_string_ids(&mut cursor, buffer, header["string_ids_size"], header["string_ids_off"])?;
+    parse_type_ids(&mut cursor, header["type_ids_size"], header["type_ids_off"], &strings)?;
+    parse_proto_ids(&mut cursor, header["proto_ids_size"], header["proto_ids_off"], &strings)?;
+
+    Ok(strings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::project::H2Project;
+
+    /// Build a tiny DEX file, with two strings, one type, and one prototype.
+    fn test_dex() -> Vec<u8> {
+        let mut data = b"dex\n035\x00".to_vec();
+        data.resize(0x70, 0);
+
+        let mut set = |offset: usize, value: u32| {
+            data[offset..(offset + 4)].copy_from_slice(&value.to_le_bytes());
+        };
+        set(0x20, 0x92);       // file_size
+        set(0x24, 0x70);       // header_size
+        set(0x28, 0x12345678); // endian_tag
+        set(0x38, 2);          // string_ids_size
+        set(0x3c, 0x70);       // string_ids_off
+        set(0x40, 1);          // type_ids_size
+        set(0x44, 0x78);       // type_ids_off
+        set(0x48, 1);          // proto_ids_size
+        set(0x4c, 0x7c);       // proto_ids_off
+        set(0x68, 0x0a);       // data_size
+        set(0x6c, 0x88);       // data_off
+
+        // string_ids
+        data.extend_from_slice(&0x88u32.to_le_bytes());
+        data.extend_from_slice(&0x8bu32.to_le_bytes());
+
+        // type_ids (string 1)
+        data.extend_from_slice(&1u32.to_le_bytes());
+
+        // proto_ids (shorty "V", returns "LFoo;", no parameters)
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+
+        // string_data
+        data.extend_from_slice(b"\x01V\x00");
+        data.extend_from_slice(b"\x05LFoo;\x00");
+
+        data
+    }
+
+    #[test]
+    fn test_analyze() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("DEX Test", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &test_dex(), 0x0))?;
+
+        let strings = analyze_dex(&mut record, "buffer")?;
+        assert_eq!(vec!["V".to_string(), "LFoo;".to_string()], strings);
+
+        let layer = record.target().buffer_get_or_err("buffer")?.layer_get_or_err(LAYER)?;
+        assert_eq!("\"dex\\n035\\0\"", layer.entry_get_or_err(0)?.resolved().display);
+        assert_eq!(Some(&"string_ids_size".to_string()), layer.comment_get(0x38)?);
+        assert_eq!(Some(&"type_ids[0]: LFoo;".to_string()), layer.comment_get(0x78)?);
+        assert_eq!(Some(&"proto_ids[0]: V".to_string()), layer.comment_get(0x7c)?);
+
+        // The string data, with its ULEB128 length
+        assert_eq!("5", layer.entry_get_or_err(0x8b)?.resolved().display);
+        assert_eq!(0x8c..0x92, layer.entry_get_or_err(0x8c)?.resolved().actual_range);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mutf8() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("DEX Test", "1.0")
+        );
+
+        // Point the first string at "a", NUL, and U+1F600 (a surrogate pair)
+        let mut data = test_dex();
+        data[0x70..0x74].copy_from_slice(&0x92u32.to_le_bytes());
+        data.extend_from_slice(b"\x04a\xc0\x80\xed\xa0\xbd\xed\xb8\x80\x00");
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &data, 0x0))?;
+
+        let strings = analyze_dex(&mut record, "buffer")?;
+        assert_eq!("a\0\u{1f600}", strings[0]);
+
+        let layer = record.target().buffer_get_or_err("buffer")?.layer_get_or_err(LAYER)?;
+        assert_eq!(0x93..0x9d, layer.entry_get_or_err(0x93)?.resolved().actual_range);
+        assert_eq!(Some(&"MUTF-8: \"a\\0\u{1f600}\"".to_string()), layer.comment_get(0x93)?);
+
+        // Bad sequences are replaced, and a missing terminator is an error
+        assert_eq!(("a\u{fffd}b".to_string(), 4), decode_mutf8(b"a\xffb\x00")?);
+        assert!(decode_mutf8(b"abc").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_endian() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("DEX Test", "1.0")
+        );
+
+        let mut data = test_dex();
+        data[0x28..0x2c].copy_from_slice(&0x12345678u32.to_be_bytes());
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &data, 0x0))?;
+
+        assert!(analyze_dex(&mut record, "buffer").is_err());
+
+        Ok(())
+    }
+}
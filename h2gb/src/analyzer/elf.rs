@@ -0,0 +1,626 @@
+//! Analyze an ELF file - the executables, shared libraries, object files, and
+//! core dumps used by Linux and most other Unix-likes.
+//!
+//! This covers the file's structure: the ELF header, the program headers, the
+//! section headers, and the symbol and string tables. Both 32- and 64-bit
+//! files are supported, in either byte order. What's inside the sections
+//! (code, relocations, dynamic linking info) is left for later.
+//!
+//! Each of those regions gets its own layer, so they can be looked at (or
+//! hidden) separately. Named symbols that are loaded from the file also
+//! become symbols in the project (see [`crate::project::H2SymbolTable`]),
+//! at the file offset they're loaded from.
+
+use redo::Record;
+use simple_error::{SimpleResult, SimpleError, bail};
+use lazy_static::lazy_static;
+
+use h2datatype::{H2Type, ResolvedType};
+use h2datatype::simple::{H2Bitmask, H2Blob, H2Enum};
+use h2datatype::simple::numeric::H2Integer;
+use h2datatype::simple::string::NTString;
+use h2datatype::composite::{H2Bitfield, H2Struct};
+
+use generic_number::{IntegerReader, CharacterReader, CharacterFormatter, BitOrder, Endian, DefaultFormatter, HexFormatter};
+
+use crate::actions::*;
+use crate::project::{H2Creator, H2SymbolScope};
+use super::Cursor;
+
+pub const LAYER_HEADER: &'static str = "header";
+pub const LAYER_PROGRAM_HEADERS: &'static str = "program_headers";
+pub const LAYER_SECTION_HEADERS: &'static str = "section_headers";
+pub const LAYER_SYMBOLS: &'static str = "symbols";
+pub const LAYER_STRINGS: &'static str = "strings";
+
+/// The size of `e_ident`, the part of the header that's the same everywhere
+const IDENT_SIZE: usize = 16;
+
+/// Section types (from the `ElfSectionType` enum) that we look inside
+const SHT_SYMTAB: usize = 2;
+const SHT_STRTAB: usize = 3;
+const SHT_DYNSYM: usize = 11;
+
+/// The program header type (from the `ElfProgramType` enum) for a segment
+/// that's loaded from the file
+const PT_LOAD: usize = 1;
+
+/// The section index of a symbol that's defined somewhere else
+const SHN_UNDEF: usize = 0;
+
+lazy_static! {
+    static ref U8: H2Type = {
+        H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer())
+    };
+
+    /// Symbol and section names are just bytes, but they're UTF-8 in practice
+    static ref STRING: H2Type = {
+        NTString::new(CharacterReader::UTF8, CharacterFormatter::pretty_str_character())
+    };
+
+    /// A symbol's type (low 4 bits) and binding (high 4 bits)
+    static ref SYMBOL_INFO: H2Type = {
+        H2Bitfield::new(IntegerReader::U8, BitOrder::Lsb0, vec![
+            ("type".to_string(), 4, DefaultFormatter::new_integer()),
+            ("bind".to_string(), 4, DefaultFormatter::new_integer()),
+        ]).unwrap()
+    };
+}
+
+/// The things `e_ident` tells us about how the rest of the file is laid out.
+#[derive(Debug, Clone, Copy)]
+struct ElfFormat {
+    is_64: bool,
+    endian: Endian,
+
+    /// The size of the whole file, which every offset is checked against
+    length: usize,
+}
+
+impl ElfFormat {
+    fn half(&self) -> H2Type {
+        H2Integer::new(IntegerReader::U16(self.endian), DefaultFormatter::new_integer())
+    }
+
+    fn word(&self) -> H2Type {
+        H2Integer::new(IntegerReader::U32(self.endian), DefaultFormatter::new_integer())
+    }
+
+    /// The reader for addresses, offsets, and sizes, which depend on the class
+    fn native_reader(&self) -> IntegerReader {
+        match self.is_64 {
+            true  => IntegerReader::U64(self.endian),
+            false => IntegerReader::U32(self.endian),
+        }
+    }
+
+    fn address(&self) -> H2Type {
+        H2Integer::new(self.native_reader(), HexFormatter::pretty_integer())
+    }
+
+    fn size(&self) -> H2Type {
+        H2Integer::new(self.native_reader(), DefaultFormatter::new_integer())
+    }
+
+    fn program_header(&self) -> SimpleResult<H2Type> {
+        let p_type = H2Enum::new(IntegerReader::U32(self.endian), "ElfProgramType")?;
+        let p_flags = H2Bitmask::new(IntegerReader::U32(self.endian), "ElfProgramFlags", false)?;
+
+        // The flags moved to keep the 64-bit fields aligned
+        H2Struct::new(match self.is_64 {
+            true => vec![
+                ("p_type".to_string(),   p_type),
+                ("p_flags".to_string(),  p_flags),
+                ("p_offset".to_string(), self.address()),
+                ("p_vaddr".to_string(),  self.address()),
+                ("p_paddr".to_string(),  self.address()),
+                ("p_filesz".to_string(), self.size()),
+                ("p_memsz".to_string(),  self.size()),
+                ("p_align".to_string(),  self.size()),
+            ],
+            false => vec![
+                ("p_type".to_string(),   p_type),
+                ("p_offset".to_string(), self.address()),
+                ("p_vaddr".to_string(),  self.address()),
+                ("p_paddr".to_string(),  self.address()),
+                ("p_filesz".to_string(), self.size()),
+                ("p_memsz".to_string(),  self.size()),
+                ("p_flags".to_string(),  p_flags),
+                ("p_align".to_string(),  self.size()),
+            ],
+        })
+    }
+
+    fn section_header(&self) -> SimpleResult<H2Type> {
+        H2Struct::new(vec![
+            ("sh_name".to_string(),      self.word()),
+            ("sh_type".to_string(),      H2Enum::new(IntegerReader::U32(self.endian), "ElfSectionType")?),
+            ("sh_flags".to_string(),     H2Bitmask::new(self.native_reader(), "ElfSectionFlags", false)?),
+            ("sh_addr".to_string(),      self.address()),
+            ("sh_offset".to_string(),    self.address()),
+            ("sh_size".to_string(),      self.size()),
+            ("sh_link".to_string(),      self.word()),
+            ("sh_info".to_string(),      self.word()),
+            ("sh_addralign".to_string(), self.size()),
+            ("sh_entsize".to_string(),   self.size()),
+        ])
+    }
+
+    fn symbol(&self) -> SimpleResult<H2Type> {
+        H2Struct::new(match self.is_64 {
+            true => vec![
+                ("st_name".to_string(),  self.word()),
+                ("st_info".to_string(),  SYMBOL_INFO.clone()),
+                ("st_other".to_string(), U8.clone()),
+                ("st_shndx".to_string(), self.half()),
+                ("st_value".to_string(), self.address()),
+                ("st_size".to_string(),  self.size()),
+            ],
+            false => vec![
+                ("st_name".to_string(),  self.word()),
+                ("st_value".to_string(), self.address()),
+                ("st_size".to_string(),  self.size()),
+                ("st_info".to_string(),  SYMBOL_INFO.clone()),
+                ("st_other".to_string(), U8.clone()),
+                ("st_shndx".to_string(), self.half()),
+            ],
+        })
+    }
+}
+
+/// The parts of the ELF header that tell us where everything else is.
+#[derive(Debug)]
+struct ElfHeader {
+    phoff: usize,
+    phentsize: usize,
+    phnum: usize,
+    shoff: usize,
+    shentsize: usize,
+    shnum: usize,
+    shstrndx: usize,
+}
+
+/// The parts of a loadable program header we need for finding where an
+/// address is in the file.
+#[derive(Debug)]
+struct Segment {
+    offset: usize,
+    vaddr: usize,
+    filesz: usize,
+}
+
+/// Find where `address` is loaded from in the file, if it's loaded from the
+/// file at all.
+fn file_offset(segments: &Vec<Segment>, address: usize) -> Option<usize> {
+    segments.iter().find(|segment| {
+        address >= segment.vaddr && address - segment.vaddr < segment.filesz
+    }).and_then(|segment| segment.offset.checked_add(address - segment.vaddr))
+}
+
+/// The parts of a section header we need for finding tables.
+#[derive(Debug)]
+struct Section {
+    name: usize,
+    section_type: usize,
+    offset: usize,
+    size: usize,
+    link: usize,
+    entsize: usize,
+}
+
+/// Get a field of a resolved struct as a number.
+fn field_usize(resolved: &ResolvedType, name: &str) -> SimpleResult<usize> {
+    resolved.children.iter().find(|c| c.field_name.as_deref() == Some(name)).and_then(|c| c.as_integer).ok_or(
+        SimpleError::new(format!("Couldn't read {}", name))
+    )?.as_usize()
+}
+
+/// Read the class and byte order out of `e_ident`, before anything is created.
+fn read_format(record: &Record<Action>, buffer: &str) -> SimpleResult<ElfFormat> {
+    let data = &record.target().buffer_get_or_err(buffer)?.data;
+
+    if data.len() < IDENT_SIZE || &data[0..4] != b"\x7fELF" {
+        bail!("Not an ELF file");
+    }
+
+    let is_64 = match data[4] {
+        1 => false,
+        2 => true,
+        c => bail!("Unknown ELF class: {}", c),
+    };
+
+    let endian = match data[5] {
+        1 => Endian::Little,
+        2 => Endian::Big,
+        d => bail!("Unknown ELF byte order: {}", d),
+    };
+
+    Ok(ElfFormat {
+        is_64: is_64,
+        endian: endian,
+        length: data.len(),
+    })
+}
+
+/// Make sure that `size` bytes starting at `offset` are in the file, and
+/// return where they end.
+fn end_of(format: ElfFormat, offset: usize, size: usize) -> SimpleResult<usize> {
+    match offset.checked_add(size) {
+        Some(end) if end <= format.length => Ok(end),
+        _ => bail!("{} bytes at offset {} go past the end of the file", size, offset),
+    }
+}
+
+/// Find entry `index` in a table of `entsize`-byte entries, making sure the
+/// whole entry is in the file.
+fn entry_offset(format: ElfFormat, base: usize, index: usize, entsize: usize) -> SimpleResult<usize> {
+    let offset = match index.checked_mul(entsize).and_then(|o| o.checked_add(base)) {
+        Some(o) => o,
+        None => bail!("Entry {} of the table at offset {} goes past the end of the file", index, base),
+    };
+
+    end_of(format, offset, entsize)?;
+
+    Ok(offset)
+}
+
+fn parse_header(cursor: &mut Cursor, format: ElfFormat) -> SimpleResult<ElfHeader> {
+    cursor.entry(&H2Integer::new(IntegerReader::U32(Endian::Big), HexFormatter::pretty_integer()), Some("Magic"))?;
+    cursor.entry(&*U8, Some("EI_CLASS (1 = 32-bit, 2 = 64-bit)"))?;
+    cursor.entry(&*U8, Some("EI_DATA (1 = little endian, 2 = big endian)"))?;
+    cursor.entry(&*U8, Some("EI_VERSION"))?;
+    cursor.entry(&*U8, Some("EI_OSABI"))?;
+    cursor.entry(&*U8, Some("EI_ABIVERSION"))?;
+    cursor.entry(&H2Blob::new((IDENT_SIZE - 9) as u64)?, Some("Padding"))?;
+
+    cursor.entry(&H2Enum::new(IntegerReader::U16(format.endian), "ElfType")?, Some("e_type"))?;
+    cursor.entry(&H2Enum::new(IntegerReader::U16(format.endian), "ElfMachine")?, Some("e_machine"))?;
+    cursor.entry(&format.word(), Some("e_version"))?;
+    cursor.entry(&format.address(), Some("e_entry"))?;
+    let phoff = cursor.entry_integer(&format.address(), Some("e_phoff"))?.as_usize()?;
+    let shoff = cursor.entry_integer(&format.address(), Some("e_shoff"))?.as_usize()?;
+    cursor.entry(&H2Integer::new(IntegerReader::U32(format.endian), HexFormatter::pretty_integer()), Some("e_flags"))?;
+    cursor.entry(&format.half(), Some("e_ehsize"))?;
+
+    Ok(ElfHeader {
+        phoff:     phoff,
+        phentsize: cursor.entry_integer(&format.half(), Some("e_phentsize"))?.as_usize()?,
+        phnum:     cursor.entry_integer(&format.half(), Some("e_phnum"))?.as_usize()?,
+        shoff:     shoff,
+        shentsize: cursor.entry_integer(&format.half(), Some("e_shentsize"))?.as_usize()?,
+        shnum:     cursor.entry_integer(&format.half(), Some("e_shnum"))?.as_usize()?,
+        shstrndx:  cursor.entry_integer(&format.half(), Some("e_shstrndx"))?.as_usize()?,
+    })
+}
+
+fn parse_program_headers(cursor: &mut Cursor, format: ElfFormat, header: &ElfHeader) -> SimpleResult<Vec<Segment>> {
+    let program_header = format.program_header()?;
+    let mut segments = Vec::new();
+
+    for i in 0..header.phnum {
+        cursor.seek(entry_offset(format, header.phoff, i, header.phentsize)?);
+
+        let resolved = cursor.peek(&program_header)?;
+        if (resolved.actual_size() as usize) > header.phentsize {
+            bail!("Program headers are {} bytes, which is too small", header.phentsize);
+        }
+
+        cursor.entry(&program_header, Some(&format!("Program header {}: {}", i, resolved.children[0].display)))?;
+
+        if field_usize(&resolved, "p_type")? == PT_LOAD {
+            segments.push(Segment {
+                offset: field_usize(&resolved, "p_offset")?,
+                vaddr:  field_usize(&resolved, "p_vaddr")?,
+                filesz: field_usize(&resolved, "p_filesz")?,
+            });
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Read the section headers (without creating anything).
+fn read_section_headers(cursor: &mut Cursor, format: ElfFormat, header: &ElfHeader) -> SimpleResult<Vec<Section>> {
+    let section_header = format.section_header()?;
+
+    (0..header.shnum).map(|i| {
+        cursor.seek(entry_offset(format, header.shoff, i, header.shentsize)?);
+
+        let resolved = cursor.peek(&section_header)?;
+        if (resolved.actual_size() as usize) > header.shentsize {
+            bail!("Section headers are {} bytes, which is too small", header.shentsize);
+        }
+
+        Ok(Section {
+            name:         field_usize(&resolved, "sh_name")?,
+            section_type: field_usize(&resolved, "sh_type")?,
+            offset:       field_usize(&resolved, "sh_offset")?,
+            size:         field_usize(&resolved, "sh_size")?,
+            link:         field_usize(&resolved, "sh_link")?,
+            entsize:      field_usize(&resolved, "sh_entsize")?,
+        })
+    }).collect()
+}
+
+/// Look up a string in a string table.
+fn string_at(cursor: &mut Cursor, format: ElfFormat, table: &Section, index: usize) -> SimpleResult<String> {
+    if table.section_type != SHT_STRTAB || index >= table.size {
+        bail!("String {} isn't in the string table", index);
+    }
+
+    cursor.push(entry_offset(format, table.offset, index, 1)?);
+    let resolved = cursor.peek(&*STRING);
+    cursor.pop()?;
+
+    resolved?.as_string.ok_or(
+        SimpleError::new(format!("Couldn't read string {}", index))
+    )
+}
+
+fn parse_section_headers(cursor: &mut Cursor, format: ElfFormat, header: &ElfHeader, sections: &Vec<Section>) -> SimpleResult<Vec<String>> {
+    let section_header = format.section_header()?;
+
+    // Files without section names (or with a bad index) are still fine
+    let names: Vec<String> = sections.iter().map(|section| {
+        match sections.get(header.shstrndx) {
+            Some(table) => string_at(cursor, format, table, section.name).unwrap_or_default(),
+            None        => String::new(),
+        }
+    }).collect();
+
+    for (i, name) in names.iter().enumerate() {
+        cursor.seek(entry_offset(format, header.shoff, i, header.shentsize)?);
+        cursor.entry(&section_header, Some(&format!("Section {}: {}", i, name)))?;
+    }
+
+    Ok(names)
+}
+
+fn parse_symbols(cursor: &mut Cursor, buffer: &str, creator: &H2Creator, format: ElfFormat, segments: &Vec<Segment>, sections: &Vec<Section>, names: &Vec<String>) -> SimpleResult<()> {
+    let symbol = format.symbol()?;
+    let symbol_size = symbol.actual_size(h2datatype::Offset::Static(0))? as usize;
+
+    for (section, section_name) in sections.iter().zip(names.iter()).filter(|(s, _)| s.section_type == SHT_SYMTAB || s.section_type == SHT_DYNSYM) {
+        let entsize = match section.entsize {
+            0 => symbol_size,
+            e if e < symbol_size => bail!("Symbols in {} are {} bytes, which is too small", section_name, e),
+            e => e,
+        };
+
+        let strings = sections.get(section.link);
+
+        for i in 0..(section.size / entsize) {
+            let offset = entry_offset(format, section.offset, i, entsize)?;
+
+            cursor.seek(offset);
+            let resolved = cursor.entry(&symbol, None)?;
+
+            // The first symbol is always empty, and plenty of others (like
+            // section symbols) don't have names either
+            let name = match strings {
+                Some(table) => string_at(cursor, format, table, field_usize(&resolved, "st_name")?).unwrap_or_default(),
+                None        => String::new(),
+            };
+
+            if name.is_empty() {
+                continue;
+            }
+
+            cursor.seek(offset);
+            cursor.comment(&name)?;
+
+            // Symbols in the file can be shown by name - for example, when a
+            // pointer points at one
+            if field_usize(&resolved, "st_shndx")? == SHN_UNDEF {
+                continue;
+            }
+
+            if let Some(target) = file_offset(segments, field_usize(&resolved, "st_value")?).filter(|target| *target < format.length) {
+                cursor.record().apply(ActionSymbolSet::new_with_creator(&name, H2SymbolScope::Local, buffer, target, creator.clone()))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_strings(cursor: &mut Cursor, format: ElfFormat, sections: &Vec<Section>, names: &Vec<String>) -> SimpleResult<()> {
+    for (section, section_name) in sections.iter().zip(names.iter()).filter(|(s, _)| s.section_type == SHT_STRTAB && s.size > 0) {
+        let end = end_of(format, section.offset, section.size)?;
+
+        cursor.seek(section.offset);
+        cursor.comment(&format!("String table {}", section_name))?;
+
+        while cursor.position() < end {
+            cursor.entry(&*STRING, None)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Annotate the headers and tables of an ELF file, and return the name of
+/// each section.
+///
+/// Everything is created in its own layer: [`LAYER_HEADER`],
+/// [`LAYER_PROGRAM_HEADERS`], [`LAYER_SECTION_HEADERS`], [`LAYER_SYMBOLS`],
+/// and [`LAYER_STRINGS`].
+pub fn analyze_elf(record: &mut Record<Action>, buffer: &str) -> SimpleResult<Vec<String>> {
+    let format = read_format(record, buffer)?;
+    let creator = H2Creator::analyzer("elf");
+
+    for layer in &[LAYER_HEADER, LAYER_PROGRAM_HEADERS, LAYER_SECTION_HEADERS, LAYER_SYMBOLS, LAYER_STRINGS] {
+        record.apply(ActionLayerCreate::new(buffer, layer))?;
+    }
+
+    let header = parse_header(&mut Cursor::new_with_creator(record, buffer, LAYER_HEADER, 0, creator.clone()), format)?;
+    let segments = parse_program_headers(&mut Cursor::new_with_creator(record, buffer, LAYER_PROGRAM_HEADERS, 0, creator.clone()), format, &header)?;
+
+    let mut cursor = Cursor::new_with_creator(record, buffer, LAYER_SECTION_HEADERS, 0, creator.clone());
+    let sections = read_section_headers(&mut cursor, format, &header)?;
+    let names = parse_section_headers(&mut cursor, format, &header, &sections)?;
+
+    parse_symbols(&mut Cursor::new_with_creator(record, buffer, LAYER_SYMBOLS, 0, creator.clone()), buffer, &creator, format, &segments, &sections, &names)?;
+    parse_strings(&mut Cursor::new_with_creator(record, buffer, LAYER_STRINGS, 0, creator), format, &sections, &names)?;
+
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::project::H2Project;
+
+    /// Build a tiny 64-bit little-endian executable, with one program
+    /// header, and a symbol table with one symbol (`main`).
+    fn test_elf() -> Vec<u8> {
+        let mut data = vec![];
+        let p16 = |data: &mut Vec<u8>, v: u16| data.extend_from_slice(&v.to_le_bytes());
+        let p32 = |data: &mut Vec<u8>, v: u32| data.extend_from_slice(&v.to_le_bytes());
+        let p64 = |data: &mut Vec<u8>, v: u64| data.extend_from_slice(&v.to_le_bytes());
+
+        // e_ident
+        data.extend_from_slice(b"\x7fELF\x02\x01\x01\x00");
+        data.resize(IDENT_SIZE, 0);
+
+        p16(&mut data, 2);        // e_type (executable)
+        p16(&mut data, 62);       // e_machine (x86-64)
+        p32(&mut data, 1);        // e_version
+        p64(&mut data, 0x400100); // e_entry
+        p64(&mut data, 0x40);     // e_phoff
+        p64(&mut data, 0xd0);     // e_shoff
+        p32(&mut data, 0);        // e_flags
+        p16(&mut data, 64);       // e_ehsize
+        p16(&mut data, 56);       // e_phentsize
+        p16(&mut data, 1);        // e_phnum
+        p16(&mut data, 64);       // e_shentsize
+        p16(&mut data, 4);        // e_shnum
+        p16(&mut data, 1);        // e_shstrndx
+
+        // 0x40: A readable, executable PT_LOAD
+        p32(&mut data, 1);
+        p32(&mut data, 5);
+        for value in &[0, 0x400000, 0x400000, 0x1d0, 0x1d0, 0x1000] {
+            p64(&mut data, *value);
+        }
+
+        // 0x78: .shstrtab, then 0x93: .strtab
+        data.extend_from_slice(b"\x00.shstrtab\x00.strtab\x00.symtab\x00");
+        data.extend_from_slice(b"\x00main\x00");
+        data.resize(0xa0, 0);
+
+        // 0xa0: .symtab - the empty symbol, then main (a global function,
+        // loaded from 0x100)
+        data.resize(0xa0 + 24, 0);
+        p32(&mut data, 1);
+        data.extend_from_slice(b"\x12\x00");
+        p16(&mut data, 1);
+        p64(&mut data, 0x400100);
+        p64(&mut data, 0x10);
+
+        // 0xd0: Section headers - name, type, flags, addr, offset, size,
+        // link, info, addralign, entsize
+        let section = |data: &mut Vec<u8>, name: u32, section_type: u32, offset: u64, size: u64, link: u32, entsize: u64| {
+            p32(data, name);
+            p32(data, section_type);
+            p64(data, 0);
+            p64(data, 0);
+            p64(data, offset);
+            p64(data, size);
+            p32(data, link);
+            p32(data, 0);
+            p64(data, 1);
+            p64(data, entsize);
+        };
+        section(&mut data, 0,  0, 0,    0,  0, 0);
+        section(&mut data, 1,  3, 0x78, 27, 0, 0);
+        section(&mut data, 11, 3, 0x93, 6,  0, 0);
+        section(&mut data, 19, 2, 0xa0, 48, 2, 24);
+
+        data
+    }
+
+    #[test]
+    fn test_analyze() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("ELF Test", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &test_elf(), 0x0))?;
+
+        let names = analyze_elf(&mut record, "buffer")?;
+        assert_eq!(vec!["", ".shstrtab", ".strtab", ".symtab"], names);
+
+        let buffer = record.target().buffer_get_or_err("buffer")?;
+
+        let layer = buffer.layer_get_or_err(LAYER_HEADER)?;
+        assert_eq!("0x7f454c46", layer.entry_get_or_err(0)?.resolved().display);
+        assert_eq!("ElfType::Executable", layer.entry_get_or_err(0x10)?.resolved().display);
+        assert_eq!("ElfMachine::X86_64", layer.entry_get_or_err(0x12)?.resolved().display);
+        assert_eq!(Some(&"e_shstrndx".to_string()), layer.comment_get(0x3e)?);
+
+        let layer = buffer.layer_get_or_err(LAYER_PROGRAM_HEADERS)?;
+        assert_eq!(Some(&"Program header 0: ElfProgramType::Load".to_string()), layer.comment_get(0x40)?);
+        assert_eq!("EXECUTE | READ", layer.entry_get_or_err(0x40)?.resolved().children[1].display);
+
+        let layer = buffer.layer_get_or_err(LAYER_SECTION_HEADERS)?;
+        assert_eq!(Some(&"Section 3: .symtab".to_string()), layer.comment_get(0xd0 + (3 * 64))?);
+        assert_eq!("ElfSectionType::SymTab", layer.entry_get_or_err(0xd0 + (3 * 64))?.resolved().children[1].display);
+
+        let layer = buffer.layer_get_or_err(LAYER_SYMBOLS)?;
+        assert_eq!(Some(&"main".to_string()), layer.comment_get(0xb8)?);
+        assert_eq!("{ type: 2, bind: 1 }", layer.entry_get_or_err(0xb8)?.resolved().children[1].display);
+        assert_eq!(None, layer.comment_get(0xa0)?);
+
+        // main is also a symbol, where it's loaded from in the file
+        assert_eq!(Some(("buffer", 0x100)), record.target().symbol_lookup("main", Some("buffer"))?);
+        assert_eq!(Some("main+0x4".to_string()), record.target().symbolize("buffer", 0x104)?);
+        assert_eq!(1, record.target().symbols().len());
+
+        let layer = buffer.layer_get_or_err(LAYER_STRINGS)?;
+        assert_eq!(Some(&"String table .strtab".to_string()), layer.comment_get(0x93)?);
+        assert_eq!(Some("main".to_string()), layer.entry_get_or_err(0x94)?.resolved().as_string);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_files() -> SimpleResult<()> {
+        let mut not_elf = test_elf();
+        not_elf[0] = b'X';
+
+        let mut bad_class = test_elf();
+        bad_class[4] = 3;
+
+        let mut truncated = test_elf();
+        truncated.truncate(0x100);
+
+        // Offsets that overflow when the entry size is added
+        let mut bad_phoff = test_elf();
+        bad_phoff[0x20..0x28].copy_from_slice(&u64::MAX.to_le_bytes());
+
+        let mut bad_shoff = test_elf();
+        bad_shoff[0x28..0x30].copy_from_slice(&u64::MAX.to_le_bytes());
+
+        // A symbol table that starts past the end, and a string table that
+        // ends (well) past it
+        let mut bad_section_offset = test_elf();
+        bad_section_offset[0x1a8..0x1b0].copy_from_slice(&0x10000u64.to_le_bytes());
+
+        let mut bad_section_size = test_elf();
+        bad_section_size[0x170..0x178].copy_from_slice(&u64::MAX.to_le_bytes());
+
+        for data in vec![not_elf, bad_class, truncated, bad_phoff, bad_shoff, bad_section_offset, bad_section_size, b"\x7fELF".to_vec()] {
+            let mut record: Record<Action> = Record::new(
+                H2Project::new("ELF Test", "1.0")
+            );
+            record.apply(ActionBufferCreateFromBytes::new("buffer", &data, 0x0))?;
+
+            assert!(analyze_elf(&mut record, "buffer").is_err());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+//! Start support for a new format from a working template.
+//!
+//! [`generate_analyzer`] copies the example analyzer (see [`super::widget`])
+//! under a new name and magic number, along with a sample file it can
+//! analyze - so the new analyzer builds, registers, and passes its tests
+//! before any of the real format has been written. From there, it's a matter
+//! of replacing the header and record parsing, one field at a time.
+//!
+//! The `h2gb` binary runs this with `h2gb new-analyzer <name> <magic>
+//! [description]`.
+
+use simple_error::{SimpleResult, SimpleError, bail};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::analyzer_get;
+
+/// The source of the example analyzer, which new ones are copied from.
+const TEMPLATE: &str = include_str!("widget.rs");
+
+/// The name the example uses, which is replaced in every form it appears.
+const TEMPLATE_NAME: &str = "widget";
+
+/// How the example's magic is written in its source.
+const TEMPLATE_MAGIC: &str = "b\"WDGT\"";
+
+/// The longest magic number a template will take.
+const MAX_MAGIC: usize = 16;
+
+/// A new analyzer, ready to be written out with
+/// [`GeneratedAnalyzer::write`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedAnalyzer {
+    pub name: String,
+
+    /// The module, which goes in `src/analyzer/<name>.rs`
+    pub module: String,
+
+    /// A file the new analyzer understands, which goes in
+    /// `testdata/<name>/sample.bin` for the snapshot test
+    pub sample: Vec<u8>,
+
+    /// The lines to add to `src/analyzer/mod.rs` and `registry.rs`
+    pub registration: String,
+}
+
+impl GeneratedAnalyzer {
+    /// Write the module into `analyzer_directory` (normally `src/analyzer`)
+    /// and the sample into `testdata_directory/<name>`, and return the paths
+    /// that were written.
+    ///
+    /// Nothing is ever overwritten - if either file exists, nothing is
+    /// written.
+    pub fn write(&self, analyzer_directory: &Path, testdata_directory: &Path) -> SimpleResult<Vec<PathBuf>> {
+        let module = analyzer_directory.join(format!("{}.rs", self.name));
+        let samples = testdata_directory.join(&self.name);
+        let sample = samples.join("sample.bin");
+
+        for path in [&module, &sample] {
+            if path.exists() {
+                bail!("{} already exists", path.display());
+            }
+        }
+
+        fs::create_dir_all(&samples).map_err(|e| {
+            SimpleError::new(format!("Couldn't create {}: {}", samples.display(), e))
+        })?;
+
+        for (path, contents) in [(&module, self.module.as_bytes()), (&sample, &self.sample[..])] {
+            fs::write(path, contents).map_err(|e| {
+                SimpleError::new(format!("Couldn't write {}: {}", path.display(), e))
+            })?;
+        }
+
+        Ok(vec![module, sample])
+    }
+}
+
+/// `foo_bar` -> `FooBar`
+fn camel_case(name: &str) -> String {
+    name.split('_').map(|word| {
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+            None        => String::new(),
+        }
+    }).collect()
+}
+
+/// Write bytes as a Rust byte string, like `b"PK\x03\x04"`.
+fn byte_string(bytes: &[u8]) -> String {
+    let escaped: String = bytes.iter().map(|&b| match b {
+        b'"' | b'\\' => format!("\\{}", b as char),
+        0x20..=0x7e  => (b as char).to_string(),
+        _            => format!("\\x{:02x}", b),
+    }).collect();
+
+    format!("b\"{}\"", escaped)
+}
+
+/// A file with two records, in the example's layout, starting with `magic`.
+pub fn template_sample(magic: &[u8]) -> Vec<u8> {
+    let mut data = magic.to_vec();
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&2u16.to_le_bytes());
+
+    for (id, name) in [(1u32, "alpha"), (2u32, "beta")] {
+        data.extend_from_slice(&id.to_le_bytes());
+        data.push(name.len() as u8);
+        data.extend_from_slice(name.as_bytes());
+    }
+
+    data
+}
+
+/// Make a new analyzer called `name` (like `png` or `zip_archive`) for files
+/// that start with `magic`.
+///
+/// `description` is what the registry shows (see
+/// [`super::H2Analyzer::description`]).
+///
+/// # Errors
+///
+/// * The name has to be a lowercase Rust identifier, and not already be an
+///   analyzer
+/// * The magic has to be between 1 and 16 bytes
+pub fn generate_analyzer(name: &str, description: &str, magic: &[u8]) -> SimpleResult<GeneratedAnalyzer> {
+    let valid_name = name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid_name {
+        bail!("Analyzer names are lowercase letters, digits, and underscores, starting with a letter: {}", name);
+    }
+
+    if analyzer_get(name).is_some() || name == TEMPLATE_NAME {
+        bail!("There's already an analyzer called {}", name);
+    }
+
+    if magic.is_empty() || magic.len() > MAX_MAGIC {
+        bail!("Magic numbers have to be between 1 and {} bytes", MAX_MAGIC);
+    }
+
+    let camel = camel_case(name);
+
+    // Swap the example's documentation for a placeholder
+    let body = match TEMPLATE.find("\nuse ") {
+        Some(start) => &TEMPLATE[(start + 1)..],
+        None        => bail!("The analyzer template is missing its imports"),
+    };
+
+    let header = format!(
+        "//! Analyze a {} file.\n//!\n//! TODO: describe the format, and anything surprising about it.\n\n",
+        description,
+    );
+
+    let module = header + &body
+        .replace(TEMPLATE_MAGIC, &byte_string(magic))
+        .replace(TEMPLATE_NAME, name)
+        .replace(&camel_case(TEMPLATE_NAME), &camel);
+
+    let registration = format!(concat!(
+        "// In src/analyzer/mod.rs:\n",
+        "mod {name};\n",
+        "pub use {name}::{{analyze_{name}, detect_{name}, {camel}Report}};\n",
+        "\n",
+        "// In src/analyzer/registry.rs, import analyze_{name} and detect_{name} from super, then add:\n",
+        "fn run_{name}(record: &mut Record<Action>, buffer: &str) -> SimpleResult<()> {{\n",
+        "    analyze_{name}(record, buffer).map(|_| ())\n",
+        "}}\n",
+        "\n",
+        "// ...and this to ANALYZERS:\n",
+        "    H2Analyzer {{ name: {name:?}, description: {description:?}, detect: detect_{name}, analyze: run_{name} }},\n",
+        "\n",
+        "// Then create the snapshot for the sample with:\n",
+        "//   H2GB_BLESS=1 cargo test {name}\n",
+    ), name = name, camel = camel, description = description);
+
+    Ok(GeneratedAnalyzer {
+        name: name.to_string(),
+        module: module,
+        sample: template_sample(magic),
+        registration: registration,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_generate() -> SimpleResult<()> {
+        let generated = generate_analyzer("zip_archive", "ZIP archive", b"PK\x03\x04")?;
+
+        assert!(generated.module.starts_with("//! Analyze a ZIP archive file.\n"));
+        assert!(generated.module.contains("const MAGIC: &[u8] = b\"PK\\x03\\x04\";"));
+        assert!(generated.module.contains("pub fn analyze_zip_archive(record: &mut Record<Action>, buffer: &str) -> SimpleResult<ZipArchiveReport>"));
+        assert!(generated.module.contains("pub fn detect_zip_archive(data: &[u8]) -> f64"));
+        assert!(generated.module.contains("H2Creator::analyzer(\"zip_archive\")"));
+        assert!(generated.module.contains("check_snapshots(\"zip_archive\", &testdata(\"zip_archive\"), &testdata(\"zip_archive/snapshots\"))"));
+
+        // Nothing of the example is left
+        assert!(!generated.module.to_lowercase().contains("widget"));
+        assert!(!generated.module.contains("WDGT"));
+
+        assert!(generated.sample.starts_with(b"PK\x03\x04\x01\x00\x02\x00"));
+        assert!(generated.registration.contains("pub use zip_archive::{analyze_zip_archive, detect_zip_archive, ZipArchiveReport};"));
+        assert!(generated.registration.contains("H2Analyzer { name: \"zip_archive\", description: \"ZIP archive\", detect: detect_zip_archive, analyze: run_zip_archive },"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_errors() {
+        assert!(generate_analyzer("Zip", "ZIP", b"PK").is_err());
+        assert!(generate_analyzer("1zip", "ZIP", b"PK").is_err());
+        assert!(generate_analyzer("zip-archive", "ZIP", b"PK").is_err());
+        assert!(generate_analyzer("", "ZIP", b"PK").is_err());
+        assert!(generate_analyzer("bson", "BSON", b"PK").is_err());
+        assert!(generate_analyzer("widget", "Widget", b"PK").is_err());
+        assert!(generate_analyzer("zip", "ZIP", b"").is_err());
+        assert!(generate_analyzer("zip", "ZIP", &[0; 17]).is_err());
+    }
+
+    #[test]
+    fn test_write() -> SimpleResult<()> {
+        let directory = std::env::temp_dir().join(format!("h2gb-generator-{}", std::process::id()));
+        let (analyzers, testdata) = (directory.join("analyzer"), directory.join("testdata"));
+        fs::create_dir_all(&analyzers).unwrap();
+
+        let generated = generate_analyzer("thing", "thing", b"THNG")?;
+        let paths = generated.write(&analyzers, &testdata)?;
+        assert_eq!(vec![analyzers.join("thing.rs"), testdata.join("thing/sample.bin")], paths);
+        assert_eq!(generated.module, fs::read_to_string(&paths[0]).unwrap());
+        assert_eq!(generated.sample, fs::read(&paths[1]).unwrap());
+
+        // Never overwrite
+        assert!(generated.write(&analyzers, &testdata).is_err());
+
+        fs::remove_dir_all(&directory).unwrap();
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_byte_string() {
+        assert_eq!("b\"WDGT\"", byte_string(b"WDGT"));
+        assert_eq!("b\"PK\\x03\\x04\"", byte_string(b"PK\x03\x04"));
+        assert_eq!("b\"\\\"\\\\\\xff\"", byte_string(b"\"\\\xff"));
+        assert_eq!("ZipArchive", camel_case("zip_archive"));
+    }
+
+    #[test]
+    fn test_sample_matches_template() {
+        // The example's sample is the one the generator makes
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../testdata/widget/sample.bin");
+
+        assert_eq!(template_sample(b"WDGT"), fs::read(path).unwrap());
+    }
+}
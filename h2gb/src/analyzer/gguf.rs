@@ -0,0 +1,407 @@
+//! Analyze a GGUF model file, as used by llama.cpp and friends.
+//!
+//! A GGUF file is a header, a list of typed key/value metadata, a list of
+//! tensor descriptions, and then the tensor data, aligned to
+//! `general.alignment` (32 bytes by default). Only version 2 and later are
+//! supported - version 1 used 32-bit counts and is long gone.
+//!
+//! The tensor data itself is mostly quantized and enormous, so each tensor
+//! just gets a marker with its name. Small `F32` and `F16` tensors (like
+//! norms in small models) are typed as arrays of floats.
+
+use redo::Record;
+use simple_error::{SimpleResult, SimpleError, bail};
+use lazy_static::lazy_static;
+
+use h2datatype::H2Type;
+use h2datatype::simple::{H2Enum, H2Marker};
+use h2datatype::simple::numeric::{H2Integer, H2Float};
+use h2datatype::simple::string::{H2String, LPString, LengthUnit};
+use h2datatype::composite::H2Array;
+
+use generic_number::{IntegerReader, FloatReader, CharacterReader, CharacterFormatter, Endian, DefaultFormatter, HexFormatter, BooleanFormatter};
+
+use crate::actions::*;
+use crate::project::H2Creator;
+use super::Cursor;
+
+const LAYER: &'static str = "default";
+
+/// Tensor data is aligned to this, unless the metadata says otherwise
+const DEFAULT_ALIGNMENT: usize = 32;
+
+/// Arrays can contain arrays, but that's never more than a couple deep
+const MAX_DEPTH: usize = 8;
+
+/// Float tensors up to this many elements are typed as arrays
+const MAX_TYPED_ELEMENTS: u64 = 256;
+
+/// The value type of strings in the metadata
+const TYPE_STRING: usize = 8;
+
+/// The value type of arrays in the metadata
+const TYPE_ARRAY: usize = 9;
+
+lazy_static! {
+    static ref U32: H2Type = {
+        H2Integer::new(IntegerReader::U32(Endian::Little), DefaultFormatter::new_integer())
+    };
+
+    static ref U64: H2Type = {
+        H2Integer::new(IntegerReader::U64(Endian::Little), DefaultFormatter::new_integer())
+    };
+
+    static ref U64_HEX: H2Type = {
+        H2Integer::new(IntegerReader::U64(Endian::Little), HexFormatter::pretty_integer())
+    };
+
+    static ref VALUE_TYPE: H2Type = {
+        H2Enum::new(IntegerReader::U32(Endian::Little), "GgufType").unwrap()
+    };
+
+    static ref TENSOR_TYPE: H2Type = {
+        H2Enum::new(IntegerReader::U32(Endian::Little), "GgmlType").unwrap()
+    };
+
+    /// Strings have a 64-bit length, in bytes
+    static ref GGUF_STRING: H2Type = {
+        LPString::new_counted(IntegerReader::U64(Endian::Little), LengthUnit::CodeUnits, CharacterReader::UTF8, CharacterFormatter::pretty_str_character()).unwrap()
+    };
+}
+
+/// Get the type for a fixed-size metadata value, or `None` for strings and
+/// arrays.
+fn scalar_type(value_type: usize) -> SimpleResult<Option<H2Type>> {
+    let reader = match value_type {
+        0  => IntegerReader::U8,
+        1  => IntegerReader::I8,
+        2  => IntegerReader::U16(Endian::Little),
+        3  => IntegerReader::I16(Endian::Little),
+        4  => IntegerReader::U32(Endian::Little),
+        5  => IntegerReader::I32(Endian::Little),
+        6  => return Ok(Some(H2Float::new(FloatReader::F32(Endian::Little), DefaultFormatter::new_float()))),
+        7  => return Ok(Some(H2Integer::new(IntegerReader::U8, BooleanFormatter::new_integer()))),
+        8  => return Ok(None),
+        9  => return Ok(None),
+        10 => IntegerReader::U64(Endian::Little),
+        11 => IntegerReader::I64(Endian::Little),
+        12 => return Ok(Some(H2Float::new(FloatReader::F64(Endian::Little), DefaultFormatter::new_float()))),
+        _  => bail!("Unknown GGUF value type: {}", value_type),
+    };
+
+    Ok(Some(H2Integer::new(reader, DefaultFormatter::new_integer())))
+}
+
+/// Read a metadata value, and return a display for it.
+fn parse_value(cursor: &mut Cursor, value_type: usize, depth: usize) -> SimpleResult<String> {
+    if depth > MAX_DEPTH {
+        bail!("GGUF arrays are nested too deeply");
+    }
+
+    match value_type {
+        TYPE_STRING => Ok(cursor.entry(&*GGUF_STRING, None)?.display),
+        TYPE_ARRAY  => {
+            let element_type = cursor.entry_integer(&*VALUE_TYPE, Some("Array type"))?.as_usize()?;
+            let count = cursor.entry_integer(&*U64, Some("Array length"))?.as_usize()?;
+
+            match scalar_type(element_type)? {
+                // Arrays of numbers are a single entry (if they aren't empty)
+                Some(t) => {
+                    if count > 0 {
+                        cursor.entry(&H2Array::new(count as u64, t)?, None)?;
+                    }
+                },
+
+                // Arrays of strings (like a tokenizer's vocabulary) or arrays
+                // get one entry per element
+                None => {
+                    for _ in 0..count {
+                        parse_value(cursor, element_type, depth + 1)?;
+                    }
+                },
+            };
+
+            Ok(format!("array[{}]", count))
+        },
+        _ => {
+            let t = scalar_type(value_type)?.ok_or(
+                SimpleError::new(format!("GGUF value type {} isn't a scalar", value_type))
+            )?;
+
+            Ok(cursor.entry(&t, None)?.display)
+        },
+    }
+}
+
+/// Read the metadata, and return each key and a display of its value.
+fn parse_metadata(cursor: &mut Cursor, count: usize) -> SimpleResult<Vec<(String, String)>> {
+    let mut metadata = vec![];
+
+    for _ in 0..count {
+        let start = cursor.position();
+        let key = cursor.entry_string(&*GGUF_STRING, None)?;
+        let value_type = cursor.entry_integer(&*VALUE_TYPE, None)?.as_usize()?;
+        let value = parse_value(cursor, value_type, 0)?;
+
+        cursor.push(start);
+        cursor.comment(&format!("{} = {}", key, value))?;
+        cursor.pop()?;
+
+        metadata.push((key, value));
+    }
+
+    Ok(metadata)
+}
+
+/// Read the tensor descriptions, and return each one's name, type, element
+/// count, and offset (relative to the start of the tensor data).
+fn parse_tensor_infos(cursor: &mut Cursor, count: usize) -> SimpleResult<Vec<(String, usize, u64, u64)>> {
+    let mut tensors = vec![];
+
+    for _ in 0..count {
+        let start = cursor.position();
+        let name = cursor.entry_string(&*GGUF_STRING, None)?;
+        let dimension_count = cursor.entry_integer(&*U32, Some("Dimensions"))?.as_usize()?;
+
+        let mut dimensions = vec![];
+        if dimension_count > 0 {
+            let resolved = cursor.entry(&H2Array::new(dimension_count as u64, U64.clone())?, None)?;
+
+            for child in resolved.children {
+                dimensions.push(child.as_integer.ok_or(
+                    SimpleError::new("Couldn't read tensor dimension")
+                )?.as_usize()? as u64);
+            }
+        }
+
+        let tensor_type = cursor.entry(&*TENSOR_TYPE, None)?;
+        let offset = cursor.entry_integer(&*U64_HEX, Some("Offset into tensor data"))?.as_usize()? as u64;
+
+        let dimension_strings: Vec<String> = dimensions.iter().map(|d| d.to_string()).collect();
+        cursor.push(start);
+        cursor.comment(&format!("Tensor {}: {} [{}]", name, tensor_type.display, dimension_strings.join(", ")))?;
+        cursor.pop()?;
+
+        let elements = dimensions.iter().fold(1u64, |total, d| total.saturating_mul(*d));
+        let tensor_type = tensor_type.as_integer.ok_or(
+            SimpleError::new("Couldn't read tensor type")
+        )?.as_usize()?;
+
+        tensors.push((name, tensor_type, elements, offset));
+    }
+
+    Ok(tensors)
+}
+
+/// Mark where each tensor's data starts, and type the small float ones.
+fn parse_tensor_data(cursor: &mut Cursor, tensors: &Vec<(String, usize, u64, u64)>, length: usize) -> SimpleResult<()> {
+    let data_start = cursor.position();
+    cursor.entry(&H2Marker::new("tensor data"), None)?;
+
+    for (name, tensor_type, elements, offset) in tensors {
+        let position = match data_start.checked_add(*offset as usize) {
+            Some(position) if position < length => position,
+            _ => bail!("Tensor {} starts past the end of the file (offset {})", name, offset),
+        };
+
+        cursor.push(position);
+        cursor.entry(&H2Marker::new(name), None)?;
+
+        let reader = match tensor_type {
+            0 => Some(FloatReader::F32(Endian::Little)),
+            1 => Some(FloatReader::F16(Endian::Little)),
+            _ => None,
+        };
+
+        if let Some(reader) = reader {
+            if *elements > 0 && *elements <= MAX_TYPED_ELEMENTS {
+                cursor.entry(&H2Array::new(*elements, H2Float::new(reader, DefaultFormatter::new_float()))?, None)?;
+            }
+        }
+
+        cursor.pop()?;
+    }
+
+    Ok(())
+}
+
+/// Annotate a GGUF file, and return its metadata as keys and displayed
+/// values.
+pub fn analyze_gguf(record: &mut Record<Action>, buffer: &str) -> SimpleResult<Vec<(String, String)>> {
+    let length = record.target().buffer_get_or_err(buffer)?.len();
+    record.apply(ActionLayerCreate::new(buffer, LAYER))?;
+
+    let mut cursor = Cursor::new_with_creator(record, buffer, LAYER, 0, H2Creator::analyzer("gguf"));
+
+    let magic = cursor.entry_string(&H2String::new(4, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?, Some("Magic"))?;
+    if magic != "GGUF" {
+        bail!("Not a GGUF file (magic is {:?})", magic);
+    }
+
+    let version = cursor.entry_integer(&*U32, Some("Version"))?.as_usize()?;
+    if version < 2 {
+        bail!("Unsupported GGUF version: {}", version);
+    }
+
+    let tensor_count = cursor.entry_integer(&*U64, Some("Tensor count"))?.as_usize()?;
+    let metadata_count = cursor.entry_integer(&*U64, Some("Metadata count"))?.as_usize()?;
+
+    let metadata = parse_metadata(&mut cursor, metadata_count)?;
+    let tensors = parse_tensor_infos(&mut cursor, tensor_count)?;
+
+    let alignment = match metadata.iter().find(|(key, _)| key == "general.alignment") {
+        Some((_, value)) => value.parse::<usize>().map_err(|e| {
+            SimpleError::new(format!("Bad general.alignment value {}: {}", value, e))
+        })?,
+        None => DEFAULT_ALIGNMENT,
+    };
+
+    cursor.align(alignment)?;
+    parse_tensor_data(&mut cursor, &tensors, length)?;
+
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::project::H2Project;
+
+    fn push_string(data: &mut Vec<u8>, s: &str) {
+        data.extend_from_slice(&(s.len() as u64).to_le_bytes());
+        data.extend_from_slice(s.as_bytes());
+    }
+
+    /// Build a tiny GGUF file with three metadata values and two tensors.
+    fn test_gguf(alignment: u32) -> Vec<u8> {
+        let mut data = b"GGUF".to_vec();
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(&2u64.to_le_bytes());
+        data.extend_from_slice(&3u64.to_le_bytes());
+
+        // general.architecture = "llama"
+        push_string(&mut data, "general.architecture");
+        data.extend_from_slice(&8u32.to_le_bytes());
+        push_string(&mut data, "llama");
+
+        // general.alignment = <alignment>
+        push_string(&mut data, "general.alignment");
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(&alignment.to_le_bytes());
+
+        // tokenizer.ggml.tokens = [ "a", "bc" ]
+        push_string(&mut data, "tokenizer.ggml.tokens");
+        data.extend_from_slice(&9u32.to_le_bytes());
+        data.extend_from_slice(&8u32.to_le_bytes());
+        data.extend_from_slice(&2u64.to_le_bytes());
+        push_string(&mut data, "a");
+        push_string(&mut data, "bc");
+
+        // norm: F16 [2], at 0
+        push_string(&mut data, "norm");
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&2u64.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+
+        // weights: Q4_0 [32, 2], at 16
+        push_string(&mut data, "weights");
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&32u64.to_le_bytes());
+        data.extend_from_slice(&2u64.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&16u64.to_le_bytes());
+
+        // Pad to the alignment, then the data: 1.5 and -4 as f16, then the
+        // quantized weights
+        while data.len() % alignment as usize != 0 {
+            data.push(0);
+        }
+        data.extend_from_slice(b"\x00\x3e\x00\xc4");
+        data.resize(data.len() + 12 + 36, 0);
+
+        data
+    }
+
+    #[test]
+    fn test_analyze() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("GGUF Test", "1.0")
+        );
+        let data = test_gguf(32);
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &data, 0x0))?;
+
+        let metadata = analyze_gguf(&mut record, "buffer")?;
+        assert_eq!(vec![
+            ("general.architecture".to_string(),  "\"llama\"".to_string()),
+            ("general.alignment".to_string(),     "32".to_string()),
+            ("tokenizer.ggml.tokens".to_string(), "array[2]".to_string()),
+        ], metadata);
+
+        let layer = record.target().buffer_get_or_err("buffer")?.layer_get_or_err(LAYER)?;
+        assert_eq!("\"GGUF\"", layer.entry_get_or_err(0)?.resolved().display);
+        assert_eq!(Some(&"general.architecture = \"llama\"".to_string()), layer.comment_get(0x18)?);
+
+        // The key is 8 + 20 bytes, then the type
+        assert_eq!("GgufType::String", layer.entry_get_or_err(0x18 + 28)?.resolved().display);
+
+        // The tensor data starts on an alignment boundary
+        let data_start = data.len() - 52;
+        assert_eq!(0, data_start % 32);
+        let points = layer.points_get(data_start..(data_start + 17))?;
+        let labels: Vec<String> = points.iter().map(|(_, entry)| entry.resolved().display.clone()).collect();
+        assert_eq!(vec!["<tensor data>", "<norm>", "<weights>"], labels);
+
+        // The small F16 tensor is typed
+        assert_eq!("[ F32(1.5), F32(-4.0) ]", layer.entry_get_or_err(data_start)?.resolved().display);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_alignment() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("GGUF Test", "1.0")
+        );
+        let data = test_gguf(64);
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &data, 0x0))?;
+
+        analyze_gguf(&mut record, "buffer")?;
+
+        let layer = record.target().buffer_get_or_err("buffer")?.layer_get_or_err(LAYER)?;
+        let data_start = data.len() - 52;
+        assert_eq!(0, data_start % 64);
+        assert_eq!("[ F32(1.5), F32(-4.0) ]", layer.entry_get_or_err(data_start)?.resolved().display);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_files() -> SimpleResult<()> {
+        let mut not_gguf = test_gguf(32);
+        not_gguf[0] = b'X';
+
+        let mut version_1 = test_gguf(32);
+        version_1[4] = 1;
+
+        // The weights' offset comes after the name, the dimension count, two
+        // dimensions and the type
+        let mut bad_offset = test_gguf(32);
+        let weights = bad_offset.windows(7).position(|w| w == b"weights").unwrap() + 7 + 4 + 16 + 4;
+        bad_offset[weights..(weights + 8)].copy_from_slice(&u64::MAX.to_le_bytes());
+
+        for data in vec![not_gguf, version_1, bad_offset] {
+            let mut record: Record<Action> = Record::new(
+                H2Project::new("GGUF Test", "1.0")
+            );
+            record.apply(ActionBufferCreateFromBytes::new("buffer", &data, 0x0))?;
+
+            assert!(analyze_gguf(&mut record, "buffer").is_err());
+        }
+
+        Ok(())
+    }
+}
@@ -1,43 +1,87 @@
 //! So far, this is a simple demonstration of what we can do
+//!
+//! To catch regressions, [`snapshot::check_snapshots`] runs an analyzer
+//! against a directory of sample files and compares what it creates to
+//! stored snapshots (see `testdata/bson`).
+//!
+//! To add a new format, start from [`generator::generate_analyzer`], which
+//! copies the example analyzer ([`widget`]) under a new name.
+//!
+//! To analyze a lot of buffers at once, [`parallel::analyze_parallel`] runs
+//! an analyzer against each of them on worker threads.
 
 use redo::Record;
-use simple_error::SimpleResult;
+use simple_error::{SimpleResult, SimpleError};
 use lazy_static::lazy_static;
 use std::time::Duration;
 use hhmmss::Hhmmss;
 
 use h2transformation::{Transformation, TransformBlockCipher, BlockCipherType, BlockCipherMode, BlockCipherPadding};
 
-use h2datatype::H2Type;
+use h2datatype::{H2Type, ResolvedType};
 use h2datatype::simple::{H2Bitmask, H2Enum, Rgb};
 use h2datatype::simple::numeric::H2Integer;
 use h2datatype::simple::string::{H2String, LPString};
-use h2datatype::composite::H2Struct;
+use h2datatype::composite::{H2Struct, NTArray};
 
 use generic_number::{IntegerReader, CharacterReader, CharacterFormatter, Endian, DefaultFormatter, BooleanFormatter};
 
 use crate::actions::*;
+use crate::project::H2Creator;
 
 mod helpers;
 use helpers::*;
 
+mod cursor;
+pub use cursor::Cursor;
+
+mod dex;
+pub use dex::analyze_dex;
+
+mod bson;
+pub use bson::analyze_bson;
+
+pub mod elf;
+pub use elf::analyze_elf;
+
+mod gguf;
+pub use gguf::analyze_gguf;
+
+mod self_describing;
+pub use self_describing::{analyze_messagepack, analyze_cbor};
+
+mod registry;
+pub use registry::*;
+
+mod widget;
+pub use widget::{analyze_widget, detect_widget, WidgetReport};
+
+mod carve;
+pub use carve::{carve_files, CARVE_LAYER};
+
+pub mod snapshot;
+
+pub mod parallel;
+pub mod generator;
+pub use parallel::{analyze_parallel, ParallelReport};
+
 const LAYER: &'static str = "default";
 
 const TERRARIA_KEY: &[u8] = b"h\x003\x00y\x00_\x00g\x00U\x00y\x00Z\x00";
 const TERRARIA_IV:  &[u8] = b"h\x003\x00y\x00_\x00g\x00U\x00y\x00Z\x00";
 
-const OFFSET_SPAWN_POINTS: usize = 0x99c;
-const OFFSET_JOURNEY_DATA: usize = 0x6b;
-
 // Things left:
 // Cellphone HUDs
 
+/// Offsets for the fields in a Terraria save, loaded from [`h2data`] based on
+/// the version number.
 #[derive(Debug, Clone, Copy)]
 struct TerrariaOffsets {
+    // Relative to the start of the file
     magic:          usize,
     name:           usize,
 
-    // Relative to name
+    // Relative to end of name
     time_played:    usize,
     face:           usize,
     visibility:     usize,
@@ -61,69 +105,53 @@ struct TerrariaOffsets {
     journey_data:   Option<usize>,
 }
 
-lazy_static! {
-    /// Offsets for Terraria address from pre-1.4
-    static ref TERRARIA_OLD_OFFSETS: TerrariaOffsets = {
-        TerrariaOffsets {
-            magic:          0x04,
-            name:           0x18,
-
-            // Offset from end of name
-            game_mode:      0x00,
-            time_played:    0x01,
-            face:           0x09,
-            visibility:     0x0e,
-            clothing:       0x11,
-            health:         0x12,
-            mana:           0x1a,
-            colours:        0x28,
-            equipment:      0x3d,
-            inventory:      0xd3,
-            coins_and_ammo: 0x2c7,
-            other_equipment: 0x317,
-            piggy_bank:     0x349,
-            safe:           0x4b1,
-            defenders_forge: 0x619,
-            void_vault:     None,
-            buffs:          None,
-            spawnpoints:    0x831,
-
-            // No JourneyMode
-            journey_data:   None,
-        }
-    };
+impl TerrariaOffsets {
+    /// Load the offsets for the given version of Terraria.
+    ///
+    /// Fields that aren't optional must be present in the data file.
+    fn load(version: usize) -> SimpleResult<Self> {
+        let table = h2data::from_offsets("Terraria", version)?;
+
+        let required = |field: &str| -> SimpleResult<usize> {
+            table.get(field).copied().ok_or(
+                SimpleError::new(format!("Terraria offsets for version {} are missing a required field: {}", version, field))
+            )
+        };
+        let optional = |field: &str| -> Option<usize> {
+            table.get(field).copied()
+        };
+
+        Ok(Self {
+            magic:           required("magic")?,
+            name:            required("name")?,
+
+            time_played:     required("time_played")?,
+            face:            required("face")?,
+            visibility:      required("visibility")?,
+            clothing:        required("clothing")?,
+            health:          required("health")?,
+            mana:            required("mana")?,
+            game_mode:       required("game_mode")?,
+            colours:         required("colours")?,
+            equipment:       required("equipment")?,
+            inventory:       required("inventory")?,
+            coins_and_ammo:  required("coins_and_ammo")?,
+            other_equipment: required("other_equipment")?,
+            piggy_bank:      required("piggy_bank")?,
+            safe:            required("safe")?,
+            spawnpoints:     required("spawnpoints")?,
+            buffs:           optional("buffs"),
+            defenders_forge: required("defenders_forge")?,
+            void_vault:      optional("void_vault"),
+
+            journey_data:    optional("journey_data"),
+        })
+    }
+}
 
-    /// Offsets for Terraria fields from version 1.4 (and up, so far)
-    static ref TERRARIA_NEW_OFFSETS: TerrariaOffsets = {
-        TerrariaOffsets {
-            // Offset from start of file
-            magic:           0x04,
-            name:            0x18,
-
-            // Offset from end of name
-            game_mode:       0x00,
-            time_played:     0x01,
-            face:            0x09,
-            visibility:      0x0e,
-            clothing:        0x11,
-            health:          0x12,
-            mana:            0x1a,
-            colours:         0x2a,
-            equipment:       0x3f,
-            inventory:       0xd5,
-            coins_and_ammo:  0x2c9,
-            other_equipment: 0x319,
-            piggy_bank:      0x34b,
-            safe:            0x4b3,
-            defenders_forge: 0x61b,
-            void_vault:      Some(0x783),
-            buffs:           Some(0x8ec),
-            spawnpoints:     0x99c,
-
-            // Offset from the end of spawnpoints
-            journey_data:   Some(0x6b),
-        }
-    };
+lazy_static! {
+    /// Everything the Terraria analyzer creates is marked as its own
+    static ref CREATOR: H2Creator = H2Creator::analyzer("terraria");
 
     /// This transformation will decrypt the Terraria savefile
     static ref TRANSFORMATION_DECRYPT: Transformation = {
@@ -170,13 +198,36 @@ lazy_static! {
         ]).unwrap()
     };
 
+    /// Spawn points go until an x coordinate of -1
+    static ref SPAWNPOINTS: H2Type = {
+        NTArray::new(
+            SPAWNPOINT_ENTRY.clone(),
+            IntegerReader::I32(Endian::Little),
+            DefaultFormatter::new_integer(),
+            -1,
+        ).unwrap()
+    };
+
+    /// Journey mode items go until a length of 8 (where the next item's name
+    /// would be)
+    static ref JOURNEYMODE_ITEMS: H2Type = {
+        NTArray::new(
+            JOURNEYMODE_ITEM_ENTRY.clone(),
+            IntegerReader::U8,
+            DefaultFormatter::new_integer(),
+            8,
+        ).unwrap()
+    };
+
+    /// Items (and the other fixed-size structs below) are resolved by the
+    /// dozen, so they're frozen to skip measuring each field every time
     static ref INVENTORY_ITEM: H2Type = {
         H2Struct::new(vec![
             ("id".to_string(),          H2Enum::new(IntegerReader::U32(Endian::Little), "TerrariaItem").unwrap()),
             ("quantity".to_string(),    H2Integer::new(IntegerReader::U32(Endian::Little), DefaultFormatter::new_integer())),
             ("affix".to_string(),       H2Enum::new(IntegerReader::U8, "TerrariaAffix").unwrap()),
             ("is_favorite".to_string(), H2Integer::new(IntegerReader::U8, BooleanFormatter::new_integer())),
-        ]).unwrap()
+        ]).unwrap().freeze()
     };
 
     static ref STORED_ITEM: H2Type = {
@@ -184,28 +235,28 @@ lazy_static! {
             ("id".to_string(),          H2Enum::new(IntegerReader::U32(Endian::Little), "TerrariaItem").unwrap()),
             ("quantity".to_string(),    H2Integer::new(IntegerReader::U32(Endian::Little), DefaultFormatter::new_integer())),
             ("affix".to_string(),       H2Enum::new(IntegerReader::U8, "TerrariaAffix").unwrap()),
-        ]).unwrap()
+        ]).unwrap().freeze()
     };
 
     static ref EQUIPPED_ITEM: H2Type = {
         H2Struct::new(vec![
             ("id".to_string(),          H2Enum::new(IntegerReader::U32(Endian::Little), "TerrariaItem").unwrap()),
             ("affix".to_string(),       H2Enum::new(IntegerReader::U8, "TerrariaAffix").unwrap()),
-        ]).unwrap()
+        ]).unwrap().freeze()
     };
 
     static ref BUFF: H2Type = {
         H2Struct::new(vec![
             ("id".to_string(),          H2Enum::new(IntegerReader::U32(Endian::Little), "TerrariaBuff").unwrap()),
             ("duration".to_string(),    H2Integer::new(IntegerReader::U32(Endian::Little), DefaultFormatter::new_integer())),
-        ]).unwrap()
+        ]).unwrap().freeze()
     };
 
     static ref HEALTH_MANA: H2Type = {
         H2Struct::new(vec![
             ("current".to_string(), H2Integer::new(IntegerReader::U32(Endian::Little), DefaultFormatter::new_integer())),
             ("max".to_string(),     H2Integer::new(IntegerReader::U32(Endian::Little), DefaultFormatter::new_integer())),
-        ]).unwrap()
+        ]).unwrap().freeze()
     };
 
     static ref COLOURS: H2Type = {
@@ -217,7 +268,7 @@ lazy_static! {
             ("undershirt".to_string(), Rgb::new(true)),
             ("pants".to_string(),      Rgb::new(true)),
             ("shoes".to_string(),      Rgb::new(true)),
-        ]).unwrap()
+        ]).unwrap().freeze()
     };
 }
 
@@ -228,7 +279,7 @@ fn transform_decrypt(record: &mut Record<Action>, buffer: &str) -> SimpleResult<
 
 /// Special parser for time_played that calculates the proper duration
 fn parse_time_played(record: &mut Record<Action>, buffer: &str, offset: usize) -> SimpleResult<()> {
-    let time_played = create_entry_integer( record, buffer, LAYER, &H2Integer::new(IntegerReader::U64(Endian::Little), DefaultFormatter::new_integer()), offset, None)?;
+    let time_played = create_entry_integer( record, buffer, LAYER, &*CREATOR, &H2Integer::new(IntegerReader::U64(Endian::Little), DefaultFormatter::new_integer()), offset, None)?;
 
     let duration = Duration::from_micros(time_played.as_usize()? as u64 / 10);
     add_comment(record, buffer, LAYER, offset, &format!("Playtime: {}", duration.hhmmssxxx()))?;
@@ -241,6 +292,7 @@ fn parse_visibility(record: &mut Record<Action>, buffer: &str, offset: usize) ->
         record,
         buffer,
         LAYER,
+        &*CREATOR,
         &H2Bitmask::new(IntegerReader::U16(Endian::Little), "TerrariaVisibility", false)?,
         offset, // Offset
         Some("Equipment visibility"),
@@ -259,6 +311,7 @@ fn parse_equipment(record: &mut Record<Action>, buffer: &str, offset: usize) ->
             record,
             buffer,
             LAYER,
+            &*CREATOR,
             &*EQUIPPED_ITEM,
             offset + (i * 5),
             None,
@@ -278,6 +331,7 @@ fn parse_inventory(record: &mut Record<Action>, buffer: &str, offset: usize) ->
             record,
             buffer,
             LAYER,
+            &*CREATOR,
             &*INVENTORY_ITEM,
             i,
             None,
@@ -296,6 +350,7 @@ fn parse_coins_and_ammo(record: &mut Record<Action>, buffer: &str, offset: usize
             record,
             buffer,
             LAYER,
+            &*CREATOR,
             &*INVENTORY_ITEM,
             i,
             None,
@@ -315,6 +370,7 @@ fn parse_other_equipment(record: &mut Record<Action>, buffer: &str, offset: usiz
             record,
             buffer,
             LAYER,
+            &*CREATOR,
             &*EQUIPPED_ITEM,
             offset + (i * 5),
             None,
@@ -334,6 +390,7 @@ fn parse_piggy_bank(record: &mut Record<Action>, buffer: &str, offset: usize) ->
             record,
             buffer,
             LAYER,
+            &*CREATOR,
             &*STORED_ITEM,
             i,
             None,
@@ -352,6 +409,7 @@ fn parse_safe(record: &mut Record<Action>, buffer: &str, offset: usize) -> Simpl
             record,
             buffer,
             LAYER,
+            &*CREATOR,
             &*STORED_ITEM,
             i,
             None,
@@ -370,6 +428,7 @@ fn parse_defenders_forge(record: &mut Record<Action>, buffer: &str, offset: usiz
             record,
             buffer,
             LAYER,
+            &*CREATOR,
             &*STORED_ITEM,
             i,
             None,
@@ -388,6 +447,7 @@ fn parse_void_vault(record: &mut Record<Action>, buffer: &str, offset: usize) ->
             record,
             buffer,
             LAYER,
+            &*CREATOR,
             &*STORED_ITEM,
             i,
             None,
@@ -406,6 +466,7 @@ fn parse_buffs(record: &mut Record<Action>, buffer: &str, offset: usize) -> Simp
             record,
             buffer,
             LAYER,
+            &*CREATOR,
             &*BUFF,
             i,
             None,
@@ -416,59 +477,15 @@ fn parse_buffs(record: &mut Record<Action>, buffer: &str, offset: usize) -> Simp
     Ok(())
 }
 
-fn parse_spawnpoints(record: &mut Record<Action>, buffer: &str, starting_offset: usize) -> SimpleResult<usize> {
-    let mut current_spawn_offset = starting_offset;
-    loop {
-        // Check for the terminator
-        let terminator_type = H2Integer::new(IntegerReader::I32(Endian::Little), DefaultFormatter::new_integer());
-        let possible_terminator = peek_entry(record, buffer, &terminator_type, current_spawn_offset)?;
-        if let Some(n) = possible_terminator.as_integer {
-            if n.as_isize()? == -1 {
-                create_entry(record, buffer, LAYER, &terminator_type, current_spawn_offset, Some("Spawn point sentinel value (terminator)"))?;
-                break;
-            }
-        }
-
-        let spawn_point = create_entry(
-            record,
-            buffer,
-            LAYER,
-            &*SPAWNPOINT_ENTRY,
-            current_spawn_offset,
-            Some("Spawn point"),
-        )?;
-
-        // Update to the next spawn offset
-        current_spawn_offset = spawn_point.actual_range.end as usize;
-    }
-
-    Ok(current_spawn_offset)
-}
-
-fn parse_journeymode(record: &mut Record<Action>, buffer: &str, starting_offset: usize) -> SimpleResult<()> {
-    let mut current_journey_offset = starting_offset;
-
-    loop {
-        let terminator_type = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
-        let possible_terminator = peek_entry(record, buffer, &terminator_type, current_journey_offset)?;
-        if let Some(n) = possible_terminator.as_integer {
-            if n.as_usize()? == 8 {
-                create_entry(record, buffer, LAYER, &terminator_type, current_journey_offset, Some("Journey mode entry sentinel value (terminator)"))?;
-                break;
-            }
-        }
-
-        let journey_item = create_entry(
-            record,
-            buffer,
-            LAYER,
-            &*JOURNEYMODE_ITEM_ENTRY,
-            current_journey_offset,
-            Some("Journeymode item"),
-        )?;
+/// Comment each entry of a resolved [`NTArray`], and its terminator.
+fn comment_ntarray(record: &mut Record<Action>, buffer: &str, resolved: &ResolvedType, entry: &str, terminator: &str) -> SimpleResult<()> {
+    for child in &resolved.children {
+        let comment = match child.field_name.as_deref() {
+            Some("terminator") => terminator,
+            _                  => entry,
+        };
 
-        // Update to the next journey offset
-        current_journey_offset = journey_item.actual_range.end as usize;
+        add_comment(record, buffer, LAYER, child.actual_range.start as usize, comment)?;
     }
 
     Ok(())
@@ -482,20 +499,16 @@ pub fn analyze_terraria(record: &mut Record<Action>, buffer: &str) -> SimpleResu
     record.apply(ActionLayerCreate::new(buffer, LAYER))?;
 
     // Create an entry for the version
-    let version_number = create_entry_integer(record, buffer, LAYER, &H2Enum::new(IntegerReader::U32(Endian::Little), "TerrariaVersion")?, 0x00, Some("Version number"))?;
+    let version_number = create_entry_integer(record, buffer, LAYER, &*CREATOR, &H2Enum::new(IntegerReader::U32(Endian::Little), "TerrariaVersion")?, 0x00, Some("Version number"))?;
 
-    // Get the offsets for later
-    let offsets = if version_number.as_usize()? < 230 {
-        *TERRARIA_OLD_OFFSETS
-    } else {
-        *TERRARIA_NEW_OFFSETS
-    };
+    // Get the offsets for later - these are different between versions
+    let offsets = TerrariaOffsets::load(version_number.as_usize()?)?;
 
     // Get the "magic" value
-    create_entry(record, buffer, LAYER, &H2String::new(7, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?, offsets.magic, Some("\"Magic\" value"))?;
+    create_entry(record, buffer, LAYER, &*CREATOR, &H2String::new(7, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?, offsets.magic, Some("\"Magic\" value"))?;
 
     // Create an entry for the name
-    let name = create_entry(record, buffer, LAYER, &*TERRARIA_LPSTRING, offsets.name, Some("Character name"))?;
+    let name = create_entry(record, buffer, LAYER, &*CREATOR, &*TERRARIA_LPSTRING, offsets.name, Some("Character name"))?;
 
     // The end of the name is the starting offset for the next bunch of fields
     let base = name.actual_range.end as usize;
@@ -505,25 +518,25 @@ pub fn analyze_terraria(record: &mut Record<Action>, buffer: &str) -> SimpleResu
     parse_time_played(record, buffer, base + offsets.time_played)?;
 
     // Character face is an 8-bit number that we can't erally do much with
-    create_entry(record, buffer, LAYER, &H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer()), base + offsets.face, Some("Character face"))?;
+    create_entry(record, buffer, LAYER, &*CREATOR, &H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer()), base + offsets.face, Some("Character face"))?;
 
     // Equipment visibility is a 10-bit bitmask that we've created a definition for
-    create_entry(record, buffer, LAYER, &H2Bitmask::new(IntegerReader::U16(Endian::Little), "TerrariaVisibility", false)?, base + offsets.visibility, Some("Equipment visibility"))?;
+    create_entry(record, buffer, LAYER, &*CREATOR, &H2Bitmask::new(IntegerReader::U16(Endian::Little), "TerrariaVisibility", false)?, base + offsets.visibility, Some("Equipment visibility"))?;
 
     // Clothing is an enumeration (this also includes gender, and oddly enough
     // it's not in the same order as the UI shows)
-    create_entry(record, buffer, LAYER, &H2Enum::new(IntegerReader::U8, "TerrariaClothing")?, base + offsets.clothing, Some("Character clothing"))?;
+    create_entry(record, buffer, LAYER, &*CREATOR, &H2Enum::new(IntegerReader::U8, "TerrariaClothing")?, base + offsets.clothing, Some("Character clothing"))?;
 
     // Health and mana are both a simple struct with current + max
-    create_entry(record, buffer, LAYER, &*HEALTH_MANA, base + offsets.health, Some("Health"))?;
-    create_entry(record, buffer, LAYER, &*HEALTH_MANA, base + offsets.mana, Some("Mana"))?;
+    create_entry(record, buffer, LAYER, &*CREATOR, &*HEALTH_MANA, base + offsets.health, Some("Health"))?;
+    create_entry(record, buffer, LAYER, &*CREATOR, &*HEALTH_MANA, base + offsets.mana, Some("Mana"))?;
 
     // Create an entry for the game mode - we'll need this later to determine
     // if we have Journey Mode data
-    let game_mode = create_entry_string(record, buffer, LAYER, &H2Enum::new(IntegerReader::U8, "TerrariaGameMode")?, base + offsets.game_mode, Some("Game mode"))?;
+    let game_mode = create_entry_string(record, buffer, LAYER, &*CREATOR, &H2Enum::new(IntegerReader::U8, "TerrariaGameMode")?, base + offsets.game_mode, Some("Game mode"))?;
 
     // Parse character colours
-    create_entry(record, buffer, LAYER, &*COLOURS, base + offsets.colours, Some("Colours"))?;
+    create_entry(record, buffer, LAYER, &*CREATOR, &*COLOURS, base + offsets.colours, Some("Colours"))?;
 
     // These are all effectively arrays
     parse_equipment(record, buffer, base + offsets.equipment)?;
@@ -543,17 +556,19 @@ pub fn analyze_terraria(record: &mut Record<Action>, buffer: &str) -> SimpleResu
         parse_buffs(record, buffer, base + offset_buffs)?;
     }
 
-    // Spawnpoints consists of zero or more entries, each of which is a fixed
-    // length.
+    // Spawnpoints consists of zero or more entries, followed by a terminator.
     // Everything after spawnpoints (mostly just journeymode data) is relative
     // to the end of spawnpoints
-    let new_base = parse_spawnpoints(record, buffer, base + offsets.spawnpoints)?;
+    let spawnpoints = create_entry(record, buffer, LAYER, &*CREATOR, &*SPAWNPOINTS, base + offsets.spawnpoints, None)?;
+    comment_ntarray(record, buffer, &spawnpoints, "Spawn point", "Spawn point sentinel value (terminator)")?;
+    let new_base = spawnpoints.actual_range.end as usize;
 
     // game_mode 3 == Journey Mode
     if game_mode == "TerrariaGameMode::JourneyMode" {
         // Only parse this if we have a journey_data offset (1.4+)
         if let Some(offset) = offsets.journey_data {
-            parse_journeymode(record, buffer, new_base + offset)?;
+            let items = create_entry(record, buffer, LAYER, &*CREATOR, &*JOURNEYMODE_ITEMS, new_base + offset, None)?;
+            comment_ntarray(record, buffer, &items, "Journeymode item", "Journey mode entry sentinel value (terminator)")?;
         }
     }
 
@@ -591,4 +606,23 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_offsets_by_version() -> SimpleResult<()> {
+        // Pre-1.4 has no buffs or journey mode
+        let offsets = TerrariaOffsets::load(229)?;
+        assert_eq!(0x831, offsets.spawnpoints);
+        assert_eq!(None, offsets.buffs);
+        assert_eq!(None, offsets.journey_data);
+
+        // 1.4+, including versions newer than we know about
+        for version in vec![230, 238, 1000] {
+            let offsets = TerrariaOffsets::load(version)?;
+            assert_eq!(0x99c, offsets.spawnpoints);
+            assert_eq!(Some(0x8ec), offsets.buffs);
+            assert_eq!(Some(0x6b), offsets.journey_data);
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,238 @@
+//! Run an analyzer against a lot of buffers at once.
+//!
+//! Analyzers only look at the buffer they're given, so a project full of
+//! buffers - partitions, or archive members - can be analyzed in parallel.
+//! [`analyze_parallel`] hands each worker thread a snapshot of the project
+//! (see [`H2Project::snapshot`]) to run the analyzer against, then merges the
+//! results back into the real record one buffer at a time, with
+//! [`ActionProjectMerge`].
+//!
+//! Each buffer's analysis is a single action, so undo takes it back out as a
+//! unit - and, like [`super::auto_analyze`], a buffer whose analyzer fails is
+//! left exactly as it was.
+
+use redo::Record;
+use simple_error::{bail, SimpleResult, SimpleError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::actions::{Action, ActionProjectMerge};
+use crate::import::merge_projects;
+use crate::project::H2Project;
+
+use super::H2Analyzer;
+
+/// What happened when [`analyze_parallel`] ran an analyzer against one
+/// buffer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParallelReport {
+    pub buffer: String,
+
+    /// How many entries, comments, and bookmarks were merged in (see
+    /// [`crate::import::ProjectMerge::counts`])
+    pub counts: (usize, usize, usize),
+
+    /// How many of the analyzer's annotations clashed with ones the buffer
+    /// already had, and were left out
+    pub conflicts: usize,
+
+    /// Why it failed, if it did - nothing is merged for a failed buffer
+    pub error: Option<String>,
+}
+
+/// Run the analyzer against its own copy of the project.
+fn analyze_snapshot(project: &H2Project, analyzer: &H2Analyzer, buffer: &str) -> SimpleResult<H2Project> {
+    let mut record: Record<Action> = Record::new(project.clone());
+    analyzer.analyze(&mut record, buffer)?;
+
+    Ok(record.target().clone())
+}
+
+/// Run `analyzer` against each of `buffers`, using up to `threads` threads
+/// (`0` for one per CPU).
+///
+/// The analyses run against a snapshot of the project as it was when this was
+/// called; once they're all done, each buffer's results are merged into
+/// `record` in the order the buffers were given, as one
+/// [`ActionProjectMerge`] each. Buffers with nothing to merge don't get an
+/// action.
+///
+/// Only annotations make it back - layers, entries, comments, and
+/// bookmarks. An analyzer that changes the buffer itself (like `terraria`,
+/// which decrypts it) can't be merged, and is reported as failing; run those
+/// with [`H2Analyzer::analyze`] instead.
+///
+/// Returns a report for each buffer, in the same order. An analyzer failing
+/// isn't an error, but a buffer that doesn't exist is (and nothing is run).
+pub fn analyze_parallel(record: &mut Record<Action>, buffers: &[&str], analyzer: &H2Analyzer, threads: usize) -> SimpleResult<Vec<ParallelReport>> {
+    for buffer in buffers {
+        record.target().buffer_get_or_err(buffer)?;
+    }
+
+    let threads = match threads {
+        0 => thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        n => n,
+    }.min(buffers.len());
+
+    // The workers pull buffers off the list until it's empty
+    let snapshot = record.target().snapshot();
+    let next = AtomicUsize::new(0);
+
+    let mut results: Vec<SimpleResult<H2Project>> = buffers.iter().map(|buffer| {
+        Err(SimpleError::new(format!("Analyzer never ran against {}", buffer)))
+    }).collect();
+
+    thread::scope(|scope| -> SimpleResult<()> {
+        let (snapshot, next) = (&snapshot, &next);
+
+        let workers: Vec<_> = (0..threads).map(|_| {
+            scope.spawn(move || {
+                let mut done = vec![];
+
+                loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    if i >= buffers.len() {
+                        break done;
+                    }
+
+                    done.push((i, analyze_snapshot(snapshot, analyzer, buffers[i])));
+                }
+            })
+        }).collect();
+
+        for worker in workers {
+            let done = match worker.join() {
+                Ok(done) => done,
+                Err(_)   => bail!("Analyzer {} crashed a worker thread", analyzer.name),
+            };
+
+            for (i, result) in done {
+                results[i] = result;
+            }
+        }
+
+        Ok(())
+    })?;
+
+    // Apply the results one at a time, so each is its own action
+    let mut reports = vec![];
+    for (buffer, result) in buffers.iter().zip(results) {
+        let merge = result.and_then(|theirs| {
+            let mut merge = merge_projects(record.target(), &theirs)?;
+
+            // Only this buffer was analyzed
+            merge.layers.retain(|layer| layer.buffer == *buffer);
+            merge.conflicts.retain(|conflict| conflict.buffer == *buffer);
+
+            Ok(merge)
+        });
+
+        let report = match merge {
+            Ok(merge) => {
+                if !merge.layers.is_empty() {
+                    record.apply(ActionProjectMerge::new(&merge))?;
+                }
+
+                ParallelReport {
+                    buffer: buffer.to_string(),
+                    counts: merge.counts(),
+                    conflicts: merge.conflicts.len(),
+                    error: None,
+                }
+            },
+            Err(e) => ParallelReport {
+                buffer: buffer.to_string(),
+                counts: (0, 0, 0),
+                conflicts: 0,
+                error: Some(e.to_string()),
+            },
+        };
+
+        reports.push(report);
+    }
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::actions::ActionBufferCreateFromBytes;
+    use crate::analyzer::analyzer_get;
+
+    // { "hello": "world" }
+    const DOCUMENT: &[u8] = b"\x16\x00\x00\x00\x02hello\x00\x06\x00\x00\x00world\x00\x00";
+
+    fn setup() -> SimpleResult<Record<Action>> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("Parallel Test", "1.0")
+        );
+
+        for i in 0..4 {
+            record.apply(ActionBufferCreateFromBytes::new(&format!("doc{}", i), DOCUMENT, 0))?;
+        }
+        record.apply(ActionBufferCreateFromBytes::new("text", b"hello world", 0))?;
+
+        Ok(record)
+    }
+
+    #[test]
+    fn test_analyze_parallel() -> SimpleResult<()> {
+        let bson = analyzer_get("bson").ok_or_else(|| SimpleError::new("No bson analyzer"))?;
+
+        // What it looks like one at a time
+        let mut expected = setup()?;
+        bson.analyze(&mut expected, "doc0")?;
+        let expected_entries = expected.target().buffer_get_or_err("doc0")?.layer_get_or_err("default")?.entries_all().len();
+
+        let mut record = setup()?;
+        let start = record.current();
+
+        let reports = analyze_parallel(&mut record, &["doc0", "text", "doc1", "doc2", "doc3"], bson, 2)?;
+        assert_eq!(vec!["doc0", "text", "doc1", "doc2", "doc3"], reports.iter().map(|r| r.buffer.as_str()).collect::<Vec<_>>());
+
+        // One action per buffer that worked
+        assert_eq!(start + 4, record.current());
+        assert!(reports[1].error.is_some());
+        assert_eq!((0, 0, 0), reports[1].counts);
+
+        for report in reports.iter().filter(|r| r.buffer != "text") {
+            assert_eq!(None, report.error);
+            assert_eq!(0, report.conflicts);
+            assert_eq!(expected_entries, report.counts.0);
+            assert_eq!(expected_entries, record.target().buffer_get_or_err(&report.buffer)?.layer_get_or_err("default")?.entries_all().len());
+        }
+        assert!(record.target().buffer_get_or_err("text")?.layer_get("default").is_none());
+
+        // Undo takes out one buffer at a time
+        record.undo()?;
+        assert!(record.target().buffer_get_or_err("doc3")?.layer_get("default").is_none());
+        assert!(record.target().buffer_get_or_err("doc2")?.layer_get("default").is_some());
+
+        // Running it again has nothing new to add
+        let reports = analyze_parallel(&mut record, &["doc0", "doc1"], bson, 0)?;
+        assert_eq!(start + 3, record.current());
+        assert_eq!((0, 0, 0), reports[0].counts);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_analyze_parallel_errors() -> SimpleResult<()> {
+        let bson = analyzer_get("bson").ok_or_else(|| SimpleError::new("No bson analyzer"))?;
+        let mut record = setup()?;
+        let start = record.current();
+
+        // A missing buffer stops everything before it starts
+        assert!(analyze_parallel(&mut record, &["doc0", "nope"], bson, 2).is_err());
+        assert_eq!(start, record.current());
+
+        // Nothing to do
+        assert_eq!(0, analyze_parallel(&mut record, &[], bson, 4)?.len());
+
+        Ok(())
+    }
+}
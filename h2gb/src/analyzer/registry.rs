@@ -0,0 +1,347 @@
+//! Find the analyzers that fit a buffer, and run the best one.
+//!
+//! Each analyzer is registered with a quick check that looks at the raw bytes
+//! and returns a confidence from `0.0` (definitely not) to `1.0` (definitely).
+//! Formats with a magic number can be confident; self-describing formats like
+//! MessagePack will parse almost anything, so they never score very high.
+//!
+//! [`auto_analyze`] is the "just analyze this file" entry point: it tries the
+//! analyzers that score at or above a threshold, best first, until one
+//! succeeds. An analyzer that fails has everything it did undone, so a
+//! buffer is never left half-analyzed.
+
+use redo::Record;
+use simple_error::{SimpleResult, SimpleError};
+
+use generic_number::Context;
+use h2datatype::{H2Type, Offset};
+use h2datatype::composite::{H2Cbor, H2MessagePack};
+
+use crate::actions::Action;
+use super::{analyze_bson, analyze_cbor, analyze_dex, analyze_elf, analyze_gguf, analyze_messagepack, analyze_terraria};
+use super::TRANSFORMATION_DECRYPT;
+
+/// The confidence [`auto_analyze`] needs before it'll run an analyzer, unless
+/// told otherwise.
+pub const DEFAULT_CONFIDENCE_THRESHOLD: f64 = 0.5;
+
+/// An analyzer, and how to tell whether it applies.
+pub struct H2Analyzer {
+    pub name: &'static str,
+    pub description: &'static str,
+    detect: fn(&[u8]) -> f64,
+    analyze: fn(&mut Record<Action>, &str) -> SimpleResult<()>,
+}
+
+impl H2Analyzer {
+    /// How likely it is that this analyzer understands `data`, from `0.0` to
+    /// `1.0`.
+    pub fn detect(&self, data: &[u8]) -> f64 {
+        (self.detect)(data)
+    }
+
+    /// Run the analyzer against a buffer.
+    ///
+    /// Nothing is undone if it fails - see [`auto_analyze`] for that.
+    pub fn analyze(&self, record: &mut Record<Action>, buffer: &str) -> SimpleResult<()> {
+        (self.analyze)(record, buffer)
+    }
+}
+
+/// What happened when [`auto_analyze`] tried an analyzer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalyzerReport {
+    pub analyzer: &'static str,
+    pub confidence: f64,
+
+    /// How many actions it applied (zero if it failed)
+    pub actions: usize,
+
+    /// Why it failed, if it did
+    pub error: Option<String>,
+}
+
+/// Does `datatype` parse the whole buffer, one value after another?
+fn parses_as(data: &[u8], datatype: &H2Type) -> bool {
+    // Context wants a Vec
+    let data = data.to_vec();
+    let offset = Offset::Dynamic(Context::new(&data));
+
+    let mut position: usize = 0;
+    while position < data.len() {
+        match datatype.aligned_size(offset.at(position as u64)) {
+            Ok(0) | Err(_) => return false,
+            Ok(size)       => position += size as usize,
+        }
+    }
+
+    true
+}
+
+fn detect_terraria(data: &[u8]) -> f64 {
+    if data.is_empty() || data.len() % 16 != 0 {
+        return 0.0;
+    }
+
+    // The "magic" value follows the version number
+    match TRANSFORMATION_DECRYPT.transform(&data.to_vec()) {
+        Ok(decrypted) if decrypted.get(4..11) == Some(&b"relogic"[..]) => 1.0,
+        _                                                              => 0.0,
+    }
+}
+
+fn detect_dex(data: &[u8]) -> f64 {
+    // "dex\n", a three-digit version, and a NUL
+    if data.len() >= 8 && data.starts_with(b"dex\n") && data[4..7].iter().all(u8::is_ascii_digit) && data[7] == 0 {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+fn detect_elf(data: &[u8]) -> f64 {
+    if data.len() < 16 || !data.starts_with(b"\x7fELF") {
+        return 0.0;
+    }
+
+    // The class (32- or 64-bit) and byte order have to make sense
+    match (data[4], data[5]) {
+        (1..=2, 1..=2) => 1.0,
+        _              => 0.2,
+    }
+}
+
+fn detect_gguf(data: &[u8]) -> f64 {
+    if data.len() < 8 || !data.starts_with(b"GGUF") {
+        return 0.0;
+    }
+
+    // Only version 2 and later are supported
+    match u32::from_le_bytes([data[4], data[5], data[6], data[7]]) {
+        2..=0xff => 1.0,
+        _        => 0.2,
+    }
+}
+
+fn detect_bson(data: &[u8]) -> f64 {
+    if data.len() < 5 {
+        return 0.0;
+    }
+
+    // The first document's length has to fit, and it has to end with a NUL
+    let length = i32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    if length < 5 || length as usize > data.len() || data[length as usize - 1] != 0 {
+        return 0.0;
+    }
+
+    // One document that fills the buffer is a much better sign than one
+    // that happens to fit
+    if length as usize == data.len() {
+        0.8
+    } else {
+        0.4
+    }
+}
+
+fn detect_messagepack(data: &[u8]) -> f64 {
+    match parses_as(data, &H2MessagePack::new()) {
+        true  => 0.3,
+        false => 0.0,
+    }
+}
+
+fn detect_cbor(data: &[u8]) -> f64 {
+    match parses_as(data, &H2Cbor::new()) {
+        true  => 0.3,
+        false => 0.0,
+    }
+}
+
+fn run_terraria(record: &mut Record<Action>, buffer: &str) -> SimpleResult<()> {
+    analyze_terraria(record, buffer)
+}
+
+fn run_dex(record: &mut Record<Action>, buffer: &str) -> SimpleResult<()> {
+    analyze_dex(record, buffer).map(|_| ())
+}
+
+fn run_elf(record: &mut Record<Action>, buffer: &str) -> SimpleResult<()> {
+    analyze_elf(record, buffer).map(|_| ())
+}
+
+fn run_gguf(record: &mut Record<Action>, buffer: &str) -> SimpleResult<()> {
+    analyze_gguf(record, buffer).map(|_| ())
+}
+
+fn run_bson(record: &mut Record<Action>, buffer: &str) -> SimpleResult<()> {
+    analyze_bson(record, buffer).map(|_| ())
+}
+
+fn run_messagepack(record: &mut Record<Action>, buffer: &str) -> SimpleResult<()> {
+    analyze_messagepack(record, buffer).map(|_| ())
+}
+
+fn run_cbor(record: &mut Record<Action>, buffer: &str) -> SimpleResult<()> {
+    analyze_cbor(record, buffer).map(|_| ())
+}
+
+static ANALYZERS: &[H2Analyzer] = &[
+    H2Analyzer { name: "terraria",    description: "Terraria player save (.plr)",     detect: detect_terraria,    analyze: run_terraria    },
+    H2Analyzer { name: "dex",         description: "Android DEX file",                detect: detect_dex,         analyze: run_dex         },
+    H2Analyzer { name: "elf",         description: "ELF executable or library",       detect: detect_elf,         analyze: run_elf         },
+    H2Analyzer { name: "gguf",        description: "GGUF model file",                 detect: detect_gguf,        analyze: run_gguf        },
+    H2Analyzer { name: "bson",        description: "BSON documents",                  detect: detect_bson,        analyze: run_bson        },
+    H2Analyzer { name: "messagepack", description: "A stream of MessagePack values",  detect: detect_messagepack, analyze: run_messagepack },
+    H2Analyzer { name: "cbor",        description: "A stream of CBOR values",         detect: detect_cbor,        analyze: run_cbor        },
+];
+
+/// Every registered analyzer.
+pub fn analyzers() -> &'static [H2Analyzer] {
+    ANALYZERS
+}
+
+/// Find an analyzer by name.
+pub fn analyzer_get(name: &str) -> Option<&'static H2Analyzer> {
+    ANALYZERS.iter().find(|a| a.name == name)
+}
+
+/// Score every analyzer against `data`, and return the ones that think they
+/// have a chance, most confident first.
+pub fn detect(data: &[u8]) -> Vec<(&'static H2Analyzer, f64)> {
+    let mut out: Vec<(&'static H2Analyzer, f64)> = ANALYZERS.iter().map(|a| (a, a.detect(data))).filter(|(_, confidence)| *confidence > 0.0).collect();
+
+    // Stable, so ties stay in registration order
+    out.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+
+    out
+}
+
+/// Detect what's in a buffer, and run the matching analyzers (at or above
+/// `threshold`) best first, until one succeeds.
+///
+/// Each analyzer runs as a unit: if it fails, every action it applied is
+/// undone before the next one is tried. Only one analyzer is kept, since they
+/// all write to the same layer.
+///
+/// Returns a report for each analyzer that was tried - the last one is the
+/// one that was kept, unless they all failed. Nothing matching isn't an
+/// error; the list is just empty.
+pub fn auto_analyze(record: &mut Record<Action>, buffer: &str, threshold: f64) -> SimpleResult<Vec<AnalyzerReport>> {
+    let candidates = detect(&record.target().buffer_get_or_err(buffer)?.data);
+
+    let mut reports = vec![];
+    for (analyzer, confidence) in candidates.into_iter().filter(|(_, confidence)| *confidence >= threshold) {
+        let start = record.current();
+
+        match analyzer.analyze(record, buffer) {
+            Ok(()) => {
+                reports.push(AnalyzerReport {
+                    analyzer: analyzer.name,
+                    confidence: confidence,
+                    actions: record.current() - start,
+                    error: None,
+                });
+
+                break;
+            },
+            Err(e) => {
+                // Roll back whatever it managed to do
+                while record.current() > start {
+                    record.undo().map_err(|undo_error| {
+                        SimpleError::new(format!("Analyzer {} failed ({}), and undoing it failed too: {}", analyzer.name, e, undo_error))
+                    })?;
+                }
+
+                reports.push(AnalyzerReport {
+                    analyzer: analyzer.name,
+                    confidence: confidence,
+                    actions: 0,
+                    error: Some(e.to_string()),
+                });
+            },
+        }
+    }
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::actions::ActionBufferCreateFromBytes;
+    use crate::project::H2Project;
+
+    #[test]
+    fn test_detect() -> SimpleResult<()> {
+        let names = |data: &[u8]| -> Vec<&'static str> {
+            detect(data).into_iter().map(|(a, _)| a.name).collect()
+        };
+
+        assert_eq!("gguf", names(b"GGUF\x03\x00\x00\x00")[0]);
+        assert_eq!("dex", names(b"dex\n035\x00")[0]);
+        assert_eq!("elf", names(b"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00")[0]);
+        assert_eq!(0.2, detect_elf(b"\x7fELF\x09\x09\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00"));
+        assert_eq!(0.8, detect_bson(b"\x05\x00\x00\x00\x00"));
+
+        // Random-ish text isn't anything
+        assert_eq!(0.0, detect_gguf(b"hello world"));
+        assert_eq!(0.0, detect_bson(b"hello world"));
+
+        // Nothing is confident about a MessagePack number
+        assert!(detect(b"\x01").iter().all(|(_, confidence)| *confidence < DEFAULT_CONFIDENCE_THRESHOLD));
+
+        assert!(analyzer_get("bson").is_some());
+        assert!(analyzer_get("nope").is_none());
+
+        // The example analyzer isn't a real format
+        assert!(analyzer_get("widget").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_analyze() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("Auto Test", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x05\x00\x00\x00\x00", 0))?;
+
+        let reports = auto_analyze(&mut record, "buffer", DEFAULT_CONFIDENCE_THRESHOLD)?;
+        assert_eq!(1, reports.len());
+        assert_eq!("bson", reports[0].analyzer);
+        assert_eq!(None, reports[0].error);
+        assert!(reports[0].actions > 0);
+        assert!(record.target().buffer_get_or_err("buffer")?.layer_get("default").is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_analyze_rolls_back() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("Auto Test", "1.0")
+        );
+
+        // Looks like a GGUF file, but it's truncated
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"GGUF\x03\x00\x00\x00", 0))?;
+        let start = record.current();
+
+        let reports = auto_analyze(&mut record, "buffer", DEFAULT_CONFIDENCE_THRESHOLD)?;
+        assert_eq!("gguf", reports[0].analyzer);
+        assert_eq!(0, reports[0].actions);
+        assert!(reports[0].error.is_some());
+
+        // Nothing it did is left behind
+        assert_eq!(start, record.current());
+        assert!(record.target().buffer_get_or_err("buffer")?.layer_get("default").is_none());
+
+        // Nothing matches
+        record.apply(ActionBufferCreateFromBytes::new("text", b"hello world", 0))?;
+        assert_eq!(0, auto_analyze(&mut record, "text", DEFAULT_CONFIDENCE_THRESHOLD)?.len());
+
+        Ok(())
+    }
+}
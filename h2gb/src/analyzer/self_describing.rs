@@ -0,0 +1,107 @@
+//! Analyze buffers of MessagePack or CBOR values.
+//!
+//! Both formats describe themselves, so there's nothing to analyze beyond
+//! reading values one after another - the types do the rest. A buffer can hold
+//! one value, or a stream of them back to back.
+//!
+//! To decode a value embedded in something else, use
+//! [`h2datatype::composite::H2MessagePack`] or
+//! [`h2datatype::composite::H2Cbor`] as a field type instead.
+
+use redo::Record;
+use simple_error::SimpleResult;
+
+use h2datatype::H2Type;
+use h2datatype::composite::{H2Cbor, H2MessagePack};
+
+use crate::actions::*;
+use crate::project::H2Creator;
+use super::Cursor;
+
+const LAYER: &'static str = "default";
+
+/// Create an entry for each value in the buffer, and return how many there
+/// were.
+fn analyze_values(record: &mut Record<Action>, buffer: &str, datatype: &H2Type, analyzer: &str) -> SimpleResult<usize> {
+    let length = record.target().buffer_get_or_err(buffer)?.len();
+    record.apply(ActionLayerCreate::new(buffer, LAYER))?;
+
+    let mut cursor = Cursor::new_with_creator(record, buffer, LAYER, 0, H2Creator::analyzer(analyzer));
+    let mut count = 0;
+    while cursor.position() < length {
+        cursor.entry(datatype, None)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Annotate every MessagePack value in a buffer, and return how many there
+/// were.
+pub fn analyze_messagepack(record: &mut Record<Action>, buffer: &str) -> SimpleResult<usize> {
+    analyze_values(record, buffer, &H2MessagePack::new(), "messagepack")
+}
+
+/// Annotate every CBOR value in a buffer, and return how many there were.
+pub fn analyze_cbor(record: &mut Record<Action>, buffer: &str) -> SimpleResult<usize> {
+    analyze_values(record, buffer, &H2Cbor::new(), "cbor")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::project::H2Project;
+
+    #[test]
+    fn test_messagepack() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("MessagePack Test", "1.0")
+        );
+
+        // { "id": 1, "tags": [ "x" ] }, then a lone 5
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x82\xa2id\x01\xa4tags\x91\xa1x\x05", 0x0))?;
+        assert_eq!(2, analyze_messagepack(&mut record, "buffer")?);
+
+        let layer = record.target().buffer_get_or_err("buffer")?.layer_get_or_err(LAYER)?;
+        let entry = layer.entry_get_or_err(0)?;
+        let resolved = entry.resolved();
+        assert_eq!("{ \"id\": 1, \"tags\": [ \"x\" ] }", resolved.display);
+        assert_eq!(0..13, resolved.actual_range);
+        assert_eq!(Some("tags".to_string()), resolved.children[4].field_name);
+        assert_eq!("5", layer.entry_get_or_err(13)?.resolved().display);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cbor() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("CBOR Test", "1.0")
+        );
+
+        // [1, [2, 3]], then true
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x82\x01\x82\x02\x03\xf5", 0x0))?;
+        assert_eq!(2, analyze_cbor(&mut record, "buffer")?);
+
+        let layer = record.target().buffer_get_or_err("buffer")?.layer_get_or_err(LAYER)?;
+        assert_eq!("[ 1, [ 2, 3 ] ]", layer.entry_get_or_err(0)?.resolved().display);
+        assert_eq!("true", layer.entry_get_or_err(5)?.resolved().display);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("CBOR Test", "1.0")
+        );
+
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x01\x83\x01", 0x0))?;
+        assert!(analyze_cbor(&mut record, "buffer").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,327 @@
+//! Run an analyzer against a directory of sample files, and compare what it
+//! creates to stored snapshots.
+//!
+//! A snapshot is a plain text list of every entry and comment the analyzer
+//! created, one per line, so a change shows up as a readable diff. Each
+//! sample `foo.bin` has its snapshot in `<snapshots>/foo.bin.snap`.
+//!
+//! When an analyzer is changed on purpose, "bless" the new output to update
+//! the snapshots - set the environment variable [`BLESS_VARIABLE`] to `1`
+//! when running [`check_snapshots`] (usually through `cargo test`), then
+//! check the changes in.
+
+use redo::Record;
+use simple_error::{SimpleResult, SimpleError, bail};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::actions::{Action, ActionBufferCreateFromBytes};
+use crate::project::H2Project;
+use super::analyzer_get;
+
+/// Set this environment variable to `1` to update snapshots instead of
+/// checking them.
+pub const BLESS_VARIABLE: &str = "H2GB_BLESS";
+
+/// The name of the buffer that each sample is loaded into.
+const BUFFER: &str = "buffer";
+
+/// What happened to a single sample.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotOutcome {
+    /// The output matches the snapshot
+    Matched,
+
+    /// The snapshot was written (bless mode)
+    Blessed,
+
+    /// There's no snapshot yet
+    Missing,
+
+    /// The output doesn't match the snapshot
+    Changed { expected: String, actual: String },
+
+    /// The analyzer failed
+    Failed(String),
+}
+
+/// The result of running an analyzer on one sample - see
+/// [`snapshot_analyzer`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotReport {
+    pub sample: PathBuf,
+    pub snapshot: PathBuf,
+    pub outcome: SnapshotOutcome,
+}
+
+impl SnapshotReport {
+    /// Did the sample pass (or get blessed)?
+    pub fn is_ok(&self) -> bool {
+        matches!(self.outcome, SnapshotOutcome::Matched | SnapshotOutcome::Blessed)
+    }
+}
+
+impl fmt::Display for SnapshotReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.outcome {
+            SnapshotOutcome::Matched => write!(f, "{}: matched", self.sample.display()),
+            SnapshotOutcome::Blessed => write!(f, "{}: wrote {}", self.sample.display(), self.snapshot.display()),
+            SnapshotOutcome::Missing => write!(f, "{}: no snapshot at {} (set {}=1 to create it)", self.sample.display(), self.snapshot.display(), BLESS_VARIABLE),
+            SnapshotOutcome::Failed(e) => write!(f, "{}: analyzer failed: {}", self.sample.display(), e),
+            SnapshotOutcome::Changed { expected, actual } => {
+                write!(f, "{}: doesn't match {} - {}", self.sample.display(), self.snapshot.display(), first_difference(expected, actual))
+            },
+        }
+    }
+}
+
+/// Describe the first line that's different between two snapshots.
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+
+    for line in 1.. {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => continue,
+            (Some(e), Some(a)) => return format!("line {}: expected '{}', got '{}'", line, e, a),
+            (Some(e), None)    => return format!("line {}: expected '{}', got nothing", line, e),
+            (None, Some(a))    => return format!("line {}: expected nothing, got '{}'", line, a),
+            (None, None)       => break,
+        }
+    }
+
+    "only whitespace differs".to_string()
+}
+
+/// Newlines would break the one-entry-per-line format.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+/// Write every entry and comment in the project as text, one per line, in a
+/// stable order.
+///
+/// Buffers and layers are sorted by name, and entries and comments by
+/// offset. An entry's other interpretations (see
+/// [`crate::project::H2Entry::interpretations`]) follow it, marked with
+/// `or`. Only what's displayed is included - not provenance or IDs, which
+/// change from run to run.
+pub fn snapshot_project(project: &H2Project) -> String {
+    let mut out = String::new();
+    for (buffer_name, buffer) in project.buffers() {
+        for layer in buffer.layers() {
+            let layer_name = layer.name();
+
+            for entry in layer.entries_all() {
+                let resolved = entry.resolved();
+
+                out.push_str(&format!("{}/{} 0x{:x}..0x{:x} {}\n",
+                    buffer_name,
+                    layer_name,
+                    resolved.actual_range.start,
+                    resolved.actual_range.end,
+                    escape(&resolved.display),
+                ));
+
+                for interpretation in entry.interpretations() {
+                    out.push_str(&format!("{}/{} 0x{:x}..0x{:x} or ({}) {}\n",
+                        buffer_name,
+                        layer_name,
+                        interpretation.resolved().actual_range.start,
+                        interpretation.resolved().actual_range.end,
+                        escape(interpretation.name()),
+                        escape(&interpretation.resolved().display),
+                    ));
+                }
+            }
+
+            // Comments can't fail on a full range
+            for (offset, comment) in layer.comments_get_with_offsets(0..buffer.len()).unwrap_or_default() {
+                out.push_str(&format!("{}/{} 0x{:x} comment: {}\n", buffer_name, layer_name, offset, escape(comment)));
+            }
+        }
+    }
+
+    out
+}
+
+/// Run the analyzer named `analyzer` on one file, and snapshot the result.
+fn analyze_file(analyzer: &str, path: &Path) -> SimpleResult<String> {
+    let analyzer = analyzer_get(analyzer).ok_or(
+        SimpleError::new(format!("No such analyzer: {}", analyzer))
+    )?;
+
+    let data = fs::read(path).map_err(|e| {
+        SimpleError::new(format!("Couldn't read {}: {}", path.display(), e))
+    })?;
+
+    let mut record: Record<Action> = Record::new(
+        H2Project::new("Snapshot", "1.0")
+    );
+    record.apply(ActionBufferCreateFromBytes::new(BUFFER, &data, 0))?;
+    analyzer.analyze(&mut record, BUFFER)?;
+
+    Ok(snapshot_project(record.target()))
+}
+
+/// The sample files in a directory (not snapshots or hidden files), sorted
+/// by name.
+fn samples(directory: &Path) -> SimpleResult<Vec<PathBuf>> {
+    let entries = fs::read_dir(directory).map_err(|e| {
+        SimpleError::new(format!("Couldn't read sample directory {}: {}", directory.display(), e))
+    })?;
+
+    let mut out: Vec<PathBuf> = vec![];
+    for entry in entries {
+        let path = entry.map_err(|e| SimpleError::new(e.to_string()))?.path();
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+
+        if path.is_file() && !name.starts_with('.') && !name.ends_with(".snap") {
+            out.push(path);
+        }
+    }
+    out.sort();
+
+    Ok(out)
+}
+
+/// Run `analyzer` on every file in `samples_directory`, and compare the
+/// results to the snapshots in `snapshots_directory`.
+///
+/// With `bless`, the snapshots are written instead (creating the directory if
+/// it needs to be). An error means the samples couldn't be found, or a
+/// snapshot couldn't be read or written; problems with the samples
+/// themselves are in the reports.
+pub fn snapshot_analyzer(analyzer: &str, samples_directory: &Path, snapshots_directory: &Path, bless: bool) -> SimpleResult<Vec<SnapshotReport>> {
+    let samples = samples(samples_directory)?;
+    if samples.is_empty() {
+        bail!("No samples in {}", samples_directory.display());
+    }
+
+    if bless {
+        fs::create_dir_all(snapshots_directory).map_err(|e| {
+            SimpleError::new(format!("Couldn't create {}: {}", snapshots_directory.display(), e))
+        })?;
+    }
+
+    samples.into_iter().map(|sample| {
+        let name = sample.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let snapshot = snapshots_directory.join(format!("{}.snap", name));
+
+        let actual = match analyze_file(analyzer, &sample) {
+            Ok(actual) => actual,
+            Err(e)     => return Ok(SnapshotReport { sample: sample, snapshot: snapshot, outcome: SnapshotOutcome::Failed(e.to_string()) }),
+        };
+
+        let outcome = if bless {
+            fs::write(&snapshot, &actual).map_err(|e| {
+                SimpleError::new(format!("Couldn't write {}: {}", snapshot.display(), e))
+            })?;
+
+            SnapshotOutcome::Blessed
+        } else if !snapshot.exists() {
+            SnapshotOutcome::Missing
+        } else {
+            let expected = fs::read_to_string(&snapshot).map_err(|e| {
+                SimpleError::new(format!("Couldn't read {}: {}", snapshot.display(), e))
+            })?;
+
+            match expected == actual {
+                true  => SnapshotOutcome::Matched,
+                false => SnapshotOutcome::Changed { expected: expected, actual: actual },
+            }
+        };
+
+        Ok(SnapshotReport {
+            sample: sample,
+            snapshot: snapshot,
+            outcome: outcome,
+        })
+    }).collect()
+}
+
+/// Like [`snapshot_analyzer`], but fails if any sample doesn't match, and
+/// blesses if [`BLESS_VARIABLE`] is set to `1`. This is meant to be called
+/// from a test.
+pub fn check_snapshots(analyzer: &str, samples_directory: &Path, snapshots_directory: &Path) -> SimpleResult<()> {
+    let bless = std::env::var(BLESS_VARIABLE).map(|v| v == "1").unwrap_or(false);
+
+    let failures: Vec<String> = snapshot_analyzer(analyzer, samples_directory, snapshots_directory, bless)?.into_iter()
+        .filter(|report| !report.is_ok())
+        .map(|report| report.to_string())
+        .collect();
+
+    if !failures.is_empty() {
+        bail!("{} sample(s) failed:\n{}", failures.len(), failures.join("\n"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn testdata(path: &str) -> PathBuf {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("../testdata");
+        d.push(path);
+
+        d
+    }
+
+    #[test]
+    fn test_bson_corpus() -> SimpleResult<()> {
+        check_snapshots("bson", &testdata("bson"), &testdata("bson/snapshots"))
+    }
+
+    #[test]
+    fn test_snapshot_outcomes() -> SimpleResult<()> {
+        let samples = std::env::temp_dir().join(format!("h2gb-snapshot-{}-samples", std::process::id()));
+        let snapshots = samples.join("snapshots");
+        fs::create_dir_all(&samples).unwrap();
+
+        // An empty document, and something that isn't BSON at all
+        fs::write(samples.join("empty.bson"), b"\x05\x00\x00\x00\x00").unwrap();
+        fs::write(samples.join("broken.bson"), b"\xff\x00\x00\x00").unwrap();
+
+        // Nothing's been blessed yet
+        let reports = snapshot_analyzer("bson", &samples, &snapshots, false)?;
+        assert_eq!(2, reports.len());
+        assert!(matches!(reports[0].outcome, SnapshotOutcome::Failed(_)));
+        assert_eq!(SnapshotOutcome::Missing, reports[1].outcome);
+
+        // Bless, then everything that worked matches
+        let reports = snapshot_analyzer("bson", &samples, &snapshots, true)?;
+        assert_eq!(SnapshotOutcome::Blessed, reports[1].outcome);
+        assert!(snapshots.join("empty.bson.snap").exists());
+
+        let reports = snapshot_analyzer("bson", &samples, &snapshots, false)?;
+        assert_eq!(SnapshotOutcome::Matched, reports[1].outcome);
+        assert!(reports[1].is_ok());
+
+        // Changing the snapshot is caught
+        fs::write(snapshots.join("empty.bson.snap"), "something else\n").unwrap();
+        let reports = snapshot_analyzer("bson", &samples, &snapshots, false)?;
+        assert!(!reports[1].is_ok());
+        assert!(reports[1].to_string().contains("line 1: expected 'something else'"));
+
+        // Bad directories and analyzers
+        assert!(snapshot_analyzer("bson", &samples.join("nope"), &snapshots, false).is_err());
+        assert!(matches!(snapshot_analyzer("nope", &samples, &snapshots, false)?[1].outcome, SnapshotOutcome::Failed(_)));
+
+        fs::remove_dir_all(&samples).unwrap();
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_difference() {
+        assert_eq!("line 2: expected 'b', got 'c'", first_difference("a\nb\n", "a\nc\n"));
+        assert_eq!("line 2: expected nothing, got 'b'", first_difference("a\n", "a\nb\n"));
+        assert_eq!("line 1: expected 'a', got nothing", first_difference("a\n", ""));
+    }
+}
@@ -0,0 +1,181 @@
+//! Analyze a widget file - a made-up format, used as the example analyzer.
+//!
+//! This is the analyzer that [`super::generator::generate_analyzer`] copies
+//! when starting support for a new format, so it sticks to the usual pieces:
+//! a quick check for the registry, types built once with `lazy_static`, a
+//! [`Cursor`] walking through the fields, a report of what was found, and
+//! tests against both hand-built data and a sample file.
+//!
+//! Since widgets aren't real, it isn't in the registry - a copy needs to be
+//! registered (see [`super::generator::GeneratedAnalyzer::registration`]).
+//!
+//! A widget file is a magic number, a little-endian `u16` version and record
+//! count, then that many records - each a `u32` ID followed by a name with a
+//! one-byte length.
+
+use redo::Record;
+use simple_error::{SimpleResult, bail};
+use lazy_static::lazy_static;
+
+use h2datatype::H2Type;
+use h2datatype::simple::H2Blob;
+use h2datatype::simple::numeric::H2Integer;
+use h2datatype::simple::string::LPString;
+
+use generic_number::{IntegerReader, CharacterReader, CharacterFormatter, Endian, DefaultFormatter, HexFormatter};
+
+use crate::actions::*;
+use crate::project::H2Creator;
+use super::Cursor;
+
+const LAYER: &'static str = "default";
+
+/// What every file starts with
+const MAGIC: &[u8] = b"WDGT";
+
+/// Don't trust a record count above this (it's probably not really a widget
+/// file)
+const MAX_RECORDS: usize = 0x10000;
+
+lazy_static! {
+    static ref U16: H2Type = {
+        H2Integer::new(IntegerReader::U16(Endian::Little), DefaultFormatter::new_integer())
+    };
+
+    static ref ID: H2Type = {
+        H2Integer::new(IntegerReader::U32(Endian::Little), HexFormatter::pretty_integer())
+    };
+
+    static ref NAME: H2Type = {
+        LPString::new(IntegerReader::U8, CharacterReader::ASCII, CharacterFormatter::pretty_str_character()).unwrap()
+    };
+}
+
+/// What [`analyze_widget`] found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WidgetReport {
+    pub version: usize,
+
+    /// Each record's ID and name
+    pub records: Vec<(usize, String)>,
+}
+
+/// How likely it is that `data` is a widget file (see
+/// [`super::H2Analyzer::detect`]).
+pub fn detect_widget(data: &[u8]) -> f64 {
+    // Magic, version, and count
+    match data.len() >= MAGIC.len() + 4 && data.starts_with(MAGIC) {
+        true  => 1.0,
+        false => 0.0,
+    }
+}
+
+/// Annotate a widget file's header and records, and return what's in it.
+pub fn analyze_widget(record: &mut Record<Action>, buffer: &str) -> SimpleResult<WidgetReport> {
+    if !record.target().buffer_get_or_err(buffer)?.byte_range(0..MAGIC.len()).map(|magic| magic == MAGIC).unwrap_or(false) {
+        bail!("Not a widget file: bad magic");
+    }
+
+    record.apply(ActionLayerCreate::new(buffer, LAYER))?;
+    let mut cursor = Cursor::new_with_creator(record, buffer, LAYER, 0, H2Creator::analyzer("widget"));
+
+    // Header
+    cursor.entry(&H2Blob::new(MAGIC.len() as u64)?, Some("Magic"))?;
+    let version = cursor.entry_integer(&*U16, Some("Version"))?.as_usize()?;
+    let count = cursor.entry_integer(&*U16, Some("Record count"))?.as_usize()?;
+
+    if count > MAX_RECORDS {
+        bail!("Too many widget records: {}", count);
+    }
+
+    // Records
+    let mut records = vec![];
+    for i in 0..count {
+        let id = cursor.entry_integer(&*ID, Some(&format!("Record {}", i)))?.as_usize()?;
+        let name = cursor.entry_string(&*NAME, None)?;
+
+        records.push((id, name));
+    }
+
+    Ok(WidgetReport {
+        version: version,
+        records: records,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    use crate::analyzer::snapshot::check_snapshots;
+    use crate::project::H2Project;
+
+    fn testdata(path: &str) -> PathBuf {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("../testdata");
+        d.push(path);
+
+        d
+    }
+
+    /// Build a file with two records.
+    fn test_widget() -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+
+        data.extend_from_slice(&0x1234u32.to_le_bytes());
+        data.extend_from_slice(b"\x05first");
+        data.extend_from_slice(&0x5678u32.to_le_bytes());
+        data.extend_from_slice(b"\x06second");
+
+        data
+    }
+
+    #[test]
+    fn test_analyze() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("Widget Test", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &test_widget(), 0x0))?;
+
+        let report = analyze_widget(&mut record, "buffer")?;
+        assert_eq!(1, report.version);
+        assert_eq!(vec![(0x1234, "first".to_string()), (0x5678, "second".to_string())], report.records);
+
+        // Three header fields, and two for each record
+        let buffer = record.target().buffer_get_or_err("buffer")?;
+        assert_eq!(7, buffer.layer_get_or_err(LAYER)?.entries_all().len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_files() -> SimpleResult<()> {
+        let mut data = test_widget();
+        data.truncate(20);
+
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("Widget Test", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("truncated", &data, 0x0))?;
+        record.apply(ActionBufferCreateFromBytes::new("text", b"hello world", 0x0))?;
+
+        assert!(analyze_widget(&mut record, "truncated").is_err());
+        assert!(analyze_widget(&mut record, "text").is_err());
+
+        assert_eq!(1.0, detect_widget(&test_widget()));
+        assert_eq!(0.0, detect_widget(b"hello world"));
+        assert_eq!(0.0, detect_widget(MAGIC));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_corpus() -> SimpleResult<()> {
+        check_snapshots("widget", &testdata("widget"), &testdata("widget/snapshots"))
+    }
+}
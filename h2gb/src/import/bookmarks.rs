@@ -0,0 +1,188 @@
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use generic_number::Integer;
+
+/// A single bookmark and/or comment, read from another tool's output.
+///
+/// Either the name or comment can be left out, in which case the existing
+/// bookmark or comment at that offset (if any) is left alone.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportedBookmark {
+    /// The offset into the buffer
+    pub offset: usize,
+
+    /// The name of the bookmark
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// A comment to attach at the same offset
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+impl ImportedBookmark {
+    pub fn new(offset: usize, name: Option<&str>, comment: Option<&str>) -> Self {
+        Self {
+            offset: offset,
+            name: name.map(|s| s.to_string()),
+            comment: comment.map(|s| s.to_string()),
+        }
+    }
+}
+
+/// Parse an offset - see [`Integer::parse`] for the formats.
+fn parse_offset(offset: &str) -> SimpleResult<usize> {
+    Integer::parse(offset).and_then(|i| i.to_usize()).map_err(|e| {
+        SimpleError::new(format!("Bad offset '{}': {}", offset.trim(), e))
+    })
+}
+
+/// Turn an empty field into `None`.
+fn optional_field(field: Option<&str>) -> Option<String> {
+    match field.map(|f| f.trim()) {
+        Some("") | None => None,
+        Some(f)         => Some(f.to_string()),
+    }
+}
+
+/// Parse bookmarks from CSV.
+///
+/// Each line is `<offset>,<name>,<comment>`. The offset can be decimal, hex
+/// (`0x10`), or anything else [`Integer::parse`] accepts. The comment column is optional, and either the name
+/// or the comment can be empty - but not both.
+///
+/// If the first line is a header (starting with `offset`), it's skipped.
+pub fn bookmarks_from_csv(data: &str) -> SimpleResult<Vec<ImportedBookmark>> {
+    let mut out = Vec::new();
+
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(data.as_bytes());
+
+    for (line, result) in rdr.records().enumerate() {
+        let record = result.map_err(|e| {
+            SimpleError::new(format!("Couldn't read bookmark CSV: {}", e))
+        })?;
+
+        let offset = record.get(0).ok_or(
+            SimpleError::new("Error reading the CSV file")
+        )?;
+
+        // Skip an optional header
+        if line == 0 && offset.trim().eq_ignore_ascii_case("offset") {
+            continue;
+        }
+
+        if record.len() < 2 || record.len() > 3 {
+            bail!("Bad bookmark CSV: must be 2 or 3 fields per line, line {} was {}", line + 1, record.len());
+        }
+
+        let bookmark = ImportedBookmark {
+            offset: parse_offset(offset)?,
+            name: optional_field(record.get(1)),
+            comment: optional_field(record.get(2)),
+        };
+
+        if bookmark.name.is_none() && bookmark.comment.is_none() {
+            bail!("Bad bookmark CSV: line {} has neither a name nor a comment", line + 1);
+        }
+
+        out.push(bookmark);
+    }
+
+    Ok(out)
+}
+
+/// Parse bookmarks from JSON.
+///
+/// The JSON must be a list of objects, each with an `offset` (a number) and a
+/// `name` and/or `comment`:
+///
+/// ```json
+/// [
+///   { "offset": 16, "name": "header" },
+///   { "offset": 32, "name": "body", "comment": "Starts with a length" }
+/// ]
+/// ```
+pub fn bookmarks_from_json(data: &str) -> SimpleResult<Vec<ImportedBookmark>> {
+    let bookmarks: Vec<ImportedBookmark> = serde_json::from_str(data).map_err(|e| {
+        SimpleError::new(format!("Couldn't read bookmark JSON: {}", e))
+    })?;
+
+    for bookmark in &bookmarks {
+        if bookmark.name.is_none() && bookmark.comment.is_none() {
+            bail!("Bad bookmark JSON: offset {} has neither a name nor a comment", bookmark.offset);
+        }
+    }
+
+    Ok(bookmarks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+
+    #[test]
+    fn test_from_csv() -> SimpleResult<()> {
+        let csv = "offset,name,comment\n0x10,header,The file header\n32,body\n0x40,,Just a comment\n";
+
+        assert_eq!(vec![
+            ImportedBookmark::new(0x10, Some("header"), Some("The file header")),
+            ImportedBookmark::new(32,   Some("body"),   None),
+            ImportedBookmark::new(0x40, None,           Some("Just a comment")),
+        ], bookmarks_from_csv(csv)?);
+
+        // No header is fine too
+        assert_eq!(vec![
+            ImportedBookmark::new(1, Some("a"), None),
+        ], bookmarks_from_csv("1,a")?);
+
+        // Other formats work too
+        assert_eq!(vec![
+            ImportedBookmark::new(0x100, Some("a"), None),
+        ], bookmarks_from_csv("0x01_00,a")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_csv_errors() -> SimpleResult<()> {
+        // Bad offset
+        assert!(bookmarks_from_csv("hello,name").is_err());
+        assert!(bookmarks_from_csv("0xzz,name").is_err());
+        assert!(bookmarks_from_csv("-1,name").is_err());
+
+        // Wrong number of fields
+        assert!(bookmarks_from_csv("1").is_err());
+        assert!(bookmarks_from_csv("1,a,b,c").is_err());
+
+        // Nothing to import
+        assert!(bookmarks_from_csv("1,,").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_json() -> SimpleResult<()> {
+        let json = r#"[
+            { "offset": 16, "name": "header" },
+            { "offset": 32, "name": "body", "comment": "Starts with a length" },
+            { "offset": 64, "comment": "Just a comment" }
+        ]"#;
+
+        assert_eq!(vec![
+            ImportedBookmark::new(16, Some("header"), None),
+            ImportedBookmark::new(32, Some("body"),   Some("Starts with a length")),
+            ImportedBookmark::new(64, None,           Some("Just a comment")),
+        ], bookmarks_from_json(json)?);
+
+        assert!(bookmarks_from_json("[{ \"offset\": 16 }]").is_err());
+        assert!(bookmarks_from_json("not json").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,322 @@
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, bail};
+use std::sync::Arc;
+
+use h2datatype::{H2Type, ResolvedType};
+
+use crate::project::{H2Project, H2Buffer, H2Entry, H2Layer, H2Provenance};
+
+/// What kind of annotation a [`MergeConflict`] is about.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum MergeConflictKind {
+    Entry,
+    Comment,
+    Bookmark,
+}
+
+/// An annotation that both projects have, but that doesn't match.
+///
+/// These are left alone by the merge, for somebody to sort out by hand.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MergeConflict {
+    pub buffer: String,
+    pub layer: String,
+    pub offset: usize,
+    pub kind: MergeConflictKind,
+
+    /// What this project has (for entries, every overlapping entry)
+    pub ours: String,
+
+    /// What the other project has
+    pub theirs: String,
+}
+
+/// Everything that can be copied into a single layer without stepping on
+/// anything.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MergeLayer {
+    pub buffer: String,
+    pub layer: String,
+
+    /// Whether the layer only exists in the other project
+    pub create: bool,
+
+    /// Entries keep their provenance from the other project
+    pub entries: Vec<(ResolvedType, Option<H2Type>, H2Provenance)>,
+    pub comments: Vec<(usize, String)>,
+    pub bookmarks: Vec<(usize, String)>,
+}
+
+/// The result of comparing two projects, from [`merge_projects`].
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProjectMerge {
+    /// What can be merged, which is applied with
+    /// [`crate::actions::ActionProjectMerge`]
+    pub layers: Vec<MergeLayer>,
+
+    /// What can't be merged automatically
+    pub conflicts: Vec<MergeConflict>,
+
+    /// Buffers in the other project that this project doesn't have
+    pub skipped_buffers: Vec<String>,
+}
+
+impl ProjectMerge {
+    /// How many entries, comments, and bookmarks would be copied over.
+    pub fn counts(&self) -> (usize, usize, usize) {
+        self.layers.iter().fold((0, 0, 0), |(entries, comments, bookmarks), layer| {
+            (entries + layer.entries.len(), comments + layer.comments.len(), bookmarks + layer.bookmarks.len())
+        })
+    }
+}
+
+fn describe_entry(entry: &H2Entry) -> String {
+    format!("{} @ 0x{:x}", entry.resolved().display, entry.resolved().aligned_range.start)
+}
+
+/// Compare one of their layers against ours (if we have it).
+fn merge_layer(buffer: &H2Buffer, ours: Option<&H2Layer>, theirs: &H2Layer, conflicts: &mut Vec<MergeConflict>) -> SimpleResult<MergeLayer> {
+    let length = buffer.len();
+    let layer_name = theirs.name();
+    let mut merged = MergeLayer {
+        buffer: buffer.name().to_string(),
+        layer: layer_name.to_string(),
+        create: ours.is_none(),
+        entries: vec![],
+        comments: vec![],
+        bookmarks: vec![],
+    };
+
+    let conflict = |offset: usize, kind: MergeConflictKind, ours: String, theirs: String| MergeConflict {
+        buffer: buffer.name().to_string(),
+        layer: layer_name.to_string(),
+        offset: offset,
+        kind: kind,
+        ours: ours,
+        theirs: theirs,
+    };
+
+    let their_entries = match length {
+        0 => vec![],
+        _ => theirs.entries_get(0..length)?,
+    };
+
+    for entry in their_entries {
+        let resolved = entry.resolved();
+        let range = (resolved.aligned_range.start as usize)..(resolved.aligned_range.end as usize);
+
+        let overlapping = match ours {
+            Some(ours) => ours.entries_get(range.clone())?,
+            None       => vec![],
+        };
+
+        match overlapping.as_slice() {
+            [] => merged.entries.push((resolved.clone(), entry.origin().clone(), entry.provenance().clone())),
+
+            // The same entry in both projects is fine
+            [existing] if existing.resolved().aligned_range == resolved.aligned_range && existing.resolved().display == resolved.display => (),
+
+            _ => {
+                let existing: Vec<String> = overlapping.iter().map(|e| describe_entry(e)).collect();
+                conflicts.push(conflict(range.start, MergeConflictKind::Entry, existing.join(", "), describe_entry(entry)));
+            },
+        }
+    }
+
+    // Points never get in each other's way, so only skip exact duplicates
+    for (offset, point) in theirs.points_get(0..(length + 1))? {
+        let exists = match ours {
+            Some(ours) => ours.point_get(offset)?.iter().any(|p| p.resolved().display == point.resolved().display),
+            None       => false,
+        };
+
+        if !exists {
+            merged.entries.push((point.resolved().clone(), point.origin().clone(), point.provenance().clone()));
+        }
+    }
+
+    for (offset, comment) in theirs.comments_get_with_offsets(0..length)? {
+        match ours.map(|ours| ours.comment_get(offset)).transpose()?.flatten() {
+            None                                  => merged.comments.push((offset, comment.clone())),
+            Some(existing) if existing == comment => (),
+            Some(existing)                        => conflicts.push(conflict(offset, MergeConflictKind::Comment, existing.clone(), comment.clone())),
+        }
+    }
+
+    for (offset, name) in theirs.bookmarks_get(0..length)? {
+        match ours.map(|ours| ours.bookmark_get(offset)).transpose()?.flatten() {
+            None                               => merged.bookmarks.push((offset, name.clone())),
+            Some(existing) if existing == name => (),
+            Some(existing)                     => conflicts.push(conflict(offset, MergeConflictKind::Bookmark, existing.clone(), name.clone())),
+        }
+    }
+
+    Ok(merged)
+}
+
+/// Work out how to merge the annotations from another project into ours.
+///
+/// This is for when two people have been annotating the same file separately.
+/// Buffers are matched up by name, and have to have exactly the same contents
+/// - otherwise, the offsets wouldn't mean the same thing, and this fails.
+/// Buffers that only they have are skipped.
+///
+/// Anything they have that we don't - entries, points, comments, bookmarks,
+/// or whole layers - is merged. Anything that's in both and identical is
+/// ignored. Anything else - an entry that overlaps one of ours but isn't the
+/// same, or a different comment or bookmark at the same offset - is a
+/// conflict, and is left out.
+///
+/// Neither project is changed; apply the result to ours with
+/// [`crate::actions::ActionProjectMerge`].
+pub fn merge_projects(ours: &H2Project, theirs: &H2Project) -> SimpleResult<ProjectMerge> {
+    let mut merge = ProjectMerge::default();
+
+    // Buffers and layers come out sorted, so the results are always in the
+    // same order
+    for buffer_name in theirs.buffers().keys() {
+        let their_buffer = theirs.buffer_get_or_err(buffer_name)?;
+        let our_buffer = match ours.buffer_get(buffer_name) {
+            Some(b) => b,
+            None    => {
+                merge.skipped_buffers.push(buffer_name.to_string());
+                continue;
+            },
+        };
+
+        // Buffers shared with a snapshot (see `H2Project::snapshot`) haven't
+        // been changed by either side, so there's nothing to merge
+        if Arc::ptr_eq(&ours.buffers()[buffer_name], &theirs.buffers()[buffer_name]) {
+            continue;
+        }
+
+        if our_buffer.byte_range(0..our_buffer.len())? != their_buffer.byte_range(0..their_buffer.len())? {
+            bail!("Buffer {} has different contents in the two projects, so they can't be merged", buffer_name);
+        }
+
+        for layer_name in their_buffer.layer_names() {
+            let merged = merge_layer(
+                our_buffer,
+                our_buffer.layer_get(layer_name),
+                their_buffer.layer_get_or_err(layer_name)?,
+                &mut merge.conflicts,
+            )?;
+
+            // Don't bother with layers that have nothing new
+            if merged.create || !merged.entries.is_empty() || !merged.comments.is_empty() || !merged.bookmarks.is_empty() {
+                merge.layers.push(merged);
+            }
+        }
+    }
+
+    Ok(merge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+
+    use generic_number::{DefaultFormatter, IntegerReader};
+    use h2datatype::simple::numeric::H2Integer;
+
+    use crate::actions::*;
+
+    fn project(data: &[u8]) -> SimpleResult<Record<Action>> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+
+        record.apply(ActionBufferCreateFromBytes::new("buffer", data, 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "default"))?;
+
+        Ok(record)
+    }
+
+    fn entry(record: &mut Record<Action>, layer: &str, reader: IntegerReader, offset: usize) -> SimpleResult<()> {
+        let datatype = H2Integer::new(reader, DefaultFormatter::new_integer());
+        let resolved = record.target().peek("buffer", &datatype, offset)?;
+        record.apply(ActionEntryCreate::new("buffer", layer, resolved, Some(datatype)))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge() -> SimpleResult<()> {
+        let data = b"\x01\x02\x03\x04\x05\x06\x07\x08";
+        let mut ours = project(data)?;
+        let mut theirs = project(data)?;
+
+        // The same in both
+        entry(&mut ours, "default", IntegerReader::U8, 0)?;
+        entry(&mut theirs, "default", IntegerReader::U8, 0)?;
+
+        // Only theirs
+        entry(&mut theirs, "default", IntegerReader::U8, 1)?;
+
+        // Overlapping, but different
+        entry(&mut ours, "default", IntegerReader::U8, 2)?;
+        entry(&mut theirs, "default", IntegerReader::U16(generic_number::Endian::Big), 2)?;
+
+        ours.apply(ActionEntrySetComment::new("buffer", "default", 4, Some("same".to_string())))?;
+        theirs.apply(ActionEntrySetComment::new("buffer", "default", 4, Some("same".to_string())))?;
+        ours.apply(ActionEntrySetComment::new("buffer", "default", 5, Some("ours".to_string())))?;
+        theirs.apply(ActionEntrySetComment::new("buffer", "default", 5, Some("theirs".to_string())))?;
+        theirs.apply(ActionEntrySetComment::new("buffer", "default", 6, Some("new".to_string())))?;
+
+        // A layer that only they have
+        theirs.apply(ActionLayerCreate::new("buffer", "extra"))?;
+        entry(&mut theirs, "extra", IntegerReader::U32(generic_number::Endian::Little), 4)?;
+
+        let merge = merge_projects(ours.target(), theirs.target())?;
+        assert_eq!((2, 1, 0), merge.counts());
+        assert_eq!(2, merge.layers.len());
+
+        assert_eq!("default", merge.layers[0].layer);
+        assert_eq!(false, merge.layers[0].create);
+        assert_eq!(1..2, merge.layers[0].entries[0].0.actual_range);
+        assert_eq!(vec![(6, "new".to_string())], merge.layers[0].comments);
+
+        assert_eq!("extra", merge.layers[1].layer);
+        assert_eq!(true, merge.layers[1].create);
+
+        assert_eq!(vec![
+            MergeConflict {
+                buffer: "buffer".to_string(),
+                layer: "default".to_string(),
+                offset: 2,
+                kind: MergeConflictKind::Entry,
+                ours: "3 @ 0x2".to_string(),
+                theirs: "772 @ 0x2".to_string(),
+            },
+            MergeConflict {
+                buffer: "buffer".to_string(),
+                layer: "default".to_string(),
+                offset: 5,
+                kind: MergeConflictKind::Comment,
+                ours: "ours".to_string(),
+                theirs: "theirs".to_string(),
+            },
+        ], merge.conflicts);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_different_buffers() -> SimpleResult<()> {
+        let ours = project(b"AAAA")?;
+        let mut theirs = project(b"AAAB")?;
+        assert!(merge_projects(ours.target(), theirs.target()).is_err());
+
+        // A buffer that we don't have is skipped
+        theirs.apply(ActionBufferCreateFromBytes::new("other", b"BBBB", 0))?;
+        let ours = project(b"AAAB")?;
+        let merge = merge_projects(ours.target(), theirs.target())?;
+        assert_eq!(vec!["other".to_string()], merge.skipped_buffers);
+        assert_eq!(0, merge.layers.len());
+
+        Ok(())
+    }
+}
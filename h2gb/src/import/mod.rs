@@ -0,0 +1,22 @@
+//! Pull findings from other tools into a project.
+//!
+//! These functions parse data that was produced somewhere else - a list of
+//! offsets from `strings` or `grep`, notes from another disassembler, etc. -
+//! into something that an action can apply. Nothing in here changes a project
+//! directly; that's still done with actions, so imports can be undone like
+//! anything else.
+//!
+//! So far, we can import:
+//!
+//! * Bookmarks and comments, from either CSV or JSON
+//!   ([`bookmarks_from_csv`], [`bookmarks_from_json`]), which are applied with
+//!   [`crate::actions::ActionLayerImportBookmarks`]
+//! * Everything from another project over the same file ([`merge_projects`]),
+//!   which is applied with [`crate::actions::ActionProjectMerge`] - anything
+//!   that both projects have but disagree on is reported as a conflict
+
+mod bookmarks;
+pub use bookmarks::*;
+
+mod merge;
+pub use merge::*;
@@ -0,0 +1,280 @@
+use simple_error::SimpleResult;
+use std::fmt;
+use std::ops::Range;
+
+use crate::project::H2Buffer;
+
+/// A kind of file that [`find_signatures`] can find inside a buffer.
+#[derive(Debug)]
+pub struct FileSignature {
+    pub name: &'static str,
+
+    /// What a carved copy would be called (without the dot)
+    pub extension: &'static str,
+
+    /// The bytes every file of this kind starts with
+    pub magic: &'static [u8],
+
+    /// Work out how long a file is, from its header and structure - `None`
+    /// if the format doesn't say, or the file looks truncated
+    length: fn(&[u8]) -> Option<usize>,
+}
+
+impl FileSignature {
+    /// How long the file at the start of `data` is, if its format says.
+    pub fn length(&self, data: &[u8]) -> Option<usize> {
+        (self.length)(data).filter(|length| *length <= data.len())
+    }
+}
+
+impl PartialEq for FileSignature {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+/// The files [`find_signatures`] knows about.
+pub const FILE_SIGNATURES: &[FileSignature] = &[
+    FileSignature { name: "PNG", extension: "png", magic: b"\x89PNG\r\n\x1a\n", length: png_length },
+    FileSignature { name: "ZIP", extension: "zip", magic: b"PK\x03\x04",        length: zip_length },
+    FileSignature { name: "PDF", extension: "pdf", magic: b"%PDF-",             length: pdf_length },
+    FileSignature { name: "ELF", extension: "elf", magic: b"\x7fELF",           length: elf_length },
+];
+
+/// An embedded file found by [`find_signatures`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CarvedFile {
+    pub signature: &'static FileSignature,
+
+    /// Where the file is - see `exact`
+    pub range: Range<usize>,
+
+    /// Did the format say how long the file is? If not, it runs until the
+    /// next signature (or the end of the range), so it may well include
+    /// junk at the end.
+    pub exact: bool,
+}
+
+impl fmt::Display for CarvedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at 0x{:x} (0x{:x} bytes{})", self.signature.name, self.range.start, self.range.len(), if self.exact { "" } else { ", guessed" })
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize, little: bool) -> Option<usize> {
+    let b = data.get(offset..offset.checked_add(2)?)?;
+
+    Some(match little {
+        true  => u16::from_le_bytes([b[0], b[1]]),
+        false => u16::from_be_bytes([b[0], b[1]]),
+    } as usize)
+}
+
+fn u32_at(data: &[u8], offset: usize, little: bool) -> Option<usize> {
+    let b = data.get(offset..offset.checked_add(4)?)?;
+
+    Some(match little {
+        true  => u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        false => u32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+    } as usize)
+}
+
+fn u64_at(data: &[u8], offset: usize, little: bool) -> Option<usize> {
+    let mut b = [0u8; 8];
+    b.copy_from_slice(data.get(offset..offset.checked_add(8)?)?);
+
+    Some(match little {
+        true  => u64::from_le_bytes(b),
+        false => u64::from_be_bytes(b),
+    } as usize)
+}
+
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|w| w == needle)
+}
+
+/// A PNG is the signature, then chunks (length, type, data, CRC) until
+/// `IEND`.
+fn png_length(data: &[u8]) -> Option<usize> {
+    let mut position = 8;
+
+    loop {
+        let length = u32_at(data, position, false)?;
+        let chunk_type = data.get((position + 4)..(position + 8))?;
+        position = position.checked_add(12)?.checked_add(length)?;
+
+        if chunk_type == b"IEND" {
+            return Some(position);
+        }
+    }
+}
+
+/// A ZIP ends with the end of central directory record, which has a
+/// variable-length comment.
+fn zip_length(data: &[u8]) -> Option<usize> {
+    let end = find(data, b"PK\x05\x06")?;
+
+    Some(end + 22 + u16_at(data, end + 20, true)?)
+}
+
+/// A PDF ends with `%%EOF` (and maybe a newline). Only the first one counts,
+/// so a PDF that's been updated in place is cut short.
+fn pdf_length(data: &[u8]) -> Option<usize> {
+    let mut end = find(data, b"%%EOF")? + 5;
+
+    for newline in [b'\r', b'\n'] {
+        if data.get(end) == Some(&newline) {
+            end += 1;
+        }
+    }
+
+    Some(end)
+}
+
+/// An ELF file is as long as the furthest thing its headers point at - the
+/// section headers (usually last), program headers, or a section or
+/// segment's data.
+fn elf_length(data: &[u8]) -> Option<usize> {
+    let little = match data.get(5)? {
+        1 => true,
+        2 => false,
+        _ => return None,
+    };
+
+    // The fields are in different places, and words are a different size,
+    // in 32- and 64-bit files
+    let wide = match data.get(4)? {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+
+    let word = |offset: usize| match wide {
+        true  => u64_at(data, offset, little),
+        false => u32_at(data, offset, little),
+    };
+
+    let (phoff, shoff, sizes) = if wide { (0x20, 0x28, 0x36) } else { (0x1c, 0x20, 0x2a) };
+    let (p_offset, p_filesz)  = if wide { (0x08, 0x20) } else { (0x04, 0x10) };
+    let (sh_offset, sh_size)  = if wide { (0x18, 0x20) } else { (0x10, 0x14) };
+
+    let phoff     = word(phoff)?;
+    let shoff     = word(shoff)?;
+    let phentsize = u16_at(data, sizes, little)?;
+    let phnum     = u16_at(data, sizes + 2, little)?;
+    let shentsize = u16_at(data, sizes + 4, little)?;
+    let shnum     = u16_at(data, sizes + 6, little)?;
+
+    // The values come straight from the file, so don't trust them not to
+    // overflow - anything past the end is thrown out anyway
+    let mut end = std::cmp::max(phoff.saturating_add(phentsize * phnum), shoff.saturating_add(shentsize * shnum));
+
+    for i in 0..phnum {
+        let header = phoff.saturating_add(i * phentsize);
+        end = std::cmp::max(end, word(header.saturating_add(p_offset))?.saturating_add(word(header.saturating_add(p_filesz))?));
+    }
+
+    for i in 0..shnum {
+        let header = shoff.saturating_add(i * shentsize);
+
+        // SHT_NOBITS (like .bss) doesn't take up space in the file
+        if u32_at(data, header.saturating_add(4), little)? != 8 {
+            end = std::cmp::max(end, word(header.saturating_add(sh_offset))?.saturating_add(word(header.saturating_add(sh_size))?));
+        }
+    }
+
+    Some(end)
+}
+
+/// Find files embedded in part of a buffer, by their signatures (see
+/// [`FILE_SIGNATURES`]).
+///
+/// Where the format says how long the file is, that's used; otherwise, the
+/// file runs until the next signature, or the end of `range`. Files can
+/// overlap (a ZIP that stores a PNG finds both). The result is sorted by
+/// offset.
+///
+/// This only looks at magic numbers and headers, so expect some false
+/// positives in big binaries - especially ZIP, whose signature is short.
+pub fn find_signatures(buffer: &H2Buffer, range: Range<usize>) -> SimpleResult<Vec<CarvedFile>> {
+    let data = buffer.byte_range(range.clone())?;
+
+    let found: Vec<(usize, &'static FileSignature)> = (0..data.len()).flat_map(|i| {
+        FILE_SIGNATURES.iter().filter(move |signature| data[i..].starts_with(signature.magic)).map(move |signature| (i, signature))
+    }).collect();
+
+    Ok(found.iter().enumerate().map(|(index, (start, signature))| {
+        let (end, exact) = match signature.length(&data[*start..]) {
+            Some(length) => (start + length, true),
+            None         => (found.get(index + 1).map(|(next, _)| *next).unwrap_or(data.len()), false),
+        };
+
+        CarvedFile {
+            signature: signature,
+            range: (range.start + start)..(range.start + end),
+            exact: exact,
+        }
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// A 64-bit ELF header with two section headers right after it - a null
+    /// one, and a huge `.bss` that doesn't count.
+    fn elf64() -> Vec<u8> {
+        let mut data = b"\x7fELF\x02\x01\x01".to_vec();
+        data.resize(0x40, 0);
+        data[0x28..0x30].copy_from_slice(&0x40u64.to_le_bytes());
+        data[0x3a..0x3c].copy_from_slice(&0x40u16.to_le_bytes());
+        data[0x3c..0x3e].copy_from_slice(&2u16.to_le_bytes());
+
+        let mut bss = vec![0u8; 0x40];
+        bss[0x04..0x08].copy_from_slice(&8u32.to_le_bytes());
+        bss[0x20..0x28].copy_from_slice(&0x10000u64.to_le_bytes());
+
+        data.extend(vec![0u8; 0x40]);
+        data.extend(bss);
+        data
+    }
+
+    #[test]
+    fn test_find_signatures() -> SimpleResult<()> {
+        let mut data = b"????".to_vec();
+        data.extend(elf64());
+        data.extend(b"????");
+
+        // A ZIP with nothing in it but the end record and a comment
+        data.extend(b"PK\x03\x04....PK\x05\x06\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\x00hi");
+
+        // A truncated PNG
+        data.extend(b"\x89PNG\r\n\x1a\n\x00\x00\x01\x00IHDR");
+        let buffer = H2Buffer::new("buffer", data, 0)?;
+
+        let files = find_signatures(&buffer, 0..buffer.len())?;
+        assert_eq!(vec![
+            ("ELF", 0x04..0xc4, true),
+            ("ZIP", 0xc8..0xe8, true),
+            ("PNG", 0xe8..0xf8, false),
+        ], files.iter().map(|f| (f.signature.name, f.range.clone(), f.exact)).collect::<Vec<_>>());
+
+        // Only in the range - the ZIP is cut off before its end record
+        let files = find_signatures(&buffer, 0x04..0xd0)?;
+        assert_eq!(vec![
+            ("ELF", 0x04..0xc4, true),
+            ("ZIP", 0xc8..0xd0, false),
+        ], files.iter().map(|f| (f.signature.name, f.range.clone(), f.exact)).collect::<Vec<_>>());
+
+        // The section headers are past the end, so the length can't be trusted
+        let files = find_signatures(&buffer, 0x04..0x80)?;
+        assert_eq!(vec![
+            ("ELF", 0x04..0x80, false),
+        ], files.iter().map(|f| (f.signature.name, f.range.clone(), f.exact)).collect::<Vec<_>>());
+
+        assert!(find_signatures(&buffer, 0..0x1000).is_err());
+
+        Ok(())
+    }
+}
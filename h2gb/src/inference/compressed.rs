@@ -0,0 +1,367 @@
+use simple_error::{bail, SimpleResult};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Range;
+
+use h2transformation::{Transformation, TransformDeflate};
+
+use crate::project::H2Buffer;
+
+/// Entropy is measured over blocks of this many bytes (a partial block at
+/// the end isn't measured - entropy is meaningless for a handful of bytes).
+const ENTROPY_BLOCK: usize = 256;
+
+/// Blocks with at least this much entropy (in bits per byte) look compressed
+/// or encrypted. Text is usually around 4-5, and machine code around 6;
+/// random data is around 7.2, since 256 bytes isn't enough for every value to
+/// show up evenly.
+const HIGH_ENTROPY: f64 = 7.0;
+
+/// How much to trust a magic number on its own.
+const MAGIC_CONFIDENCE: f64 = 0.5;
+
+/// How much more to trust a magic number when the suggested transformation
+/// actually works.
+const VERIFIED_CONFIDENCE: f64 = 0.4;
+
+/// How much to trust high entropy on its own - it could be compression,
+/// encryption, or just noise.
+const ENTROPY_CONFIDENCE: f64 = 0.3;
+
+/// What [`find_compressed`] thinks a region is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionHint {
+    Zlib,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+    Lz4,
+
+    /// No magic number, but the data looks random - compressed with
+    /// something headerless, or encrypted
+    HighEntropy,
+}
+
+impl fmt::Display for CompressionHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Zlib        => write!(f, "zlib header"),
+            Self::Gzip        => write!(f, "gzip header"),
+            Self::Bzip2       => write!(f, "bzip2 header"),
+            Self::Xz          => write!(f, "xz header"),
+            Self::Zstd        => write!(f, "zstd header"),
+            Self::Lz4         => write!(f, "lz4 frame header"),
+            Self::HighEntropy => write!(f, "high-entropy block"),
+        }
+    }
+}
+
+/// A region that [`find_compressed`] thinks is compressed or encrypted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedCandidate {
+    pub hint: CompressionHint,
+
+    /// The whole region, including any header
+    pub range: Range<usize>,
+
+    /// The part of the region to transform - the same as `range` unless
+    /// there's a header or trailer that the transformation doesn't handle
+    /// (like gzip's)
+    pub data: Range<usize>,
+
+    /// What to transform `data` with, if we know of anything that might work
+    pub transformation: Option<Transformation>,
+
+    /// Has the transformation been tried, and did it work?
+    pub verified: bool,
+
+    /// The average entropy of the region, in bits per byte (`0.0` to `8.0`)
+    pub entropy: f64,
+
+    /// How likely this is to be right, from `0.0` to `1.0`
+    pub confidence: f64,
+}
+
+impl fmt::Display for CompressedCandidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at 0x{:x} (0x{:x} bytes, entropy {:.2})", self.hint, self.range.start, self.range.len(), self.entropy)?;
+
+        if let Some(transformation) = &self.transformation {
+            write!(f, ", try {} on 0x{:x}..0x{:x}", transformation, self.data.start, self.data.end)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The Shannon entropy of `data`, in bits per byte - `0.0` means every byte
+/// is the same, and `8.0` means every byte value is equally likely.
+pub fn shannon_entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+
+    let mut counts = [0usize; 256];
+    for b in data {
+        counts[*b as usize] += 1;
+    }
+
+    counts.iter().filter(|c| **c > 0).map(|c| {
+        let p = *c as f64 / data.len() as f64;
+        -p * p.log2()
+    }).sum()
+}
+
+/// A header found by [`check_magic`]: the hint, how many bytes at the start
+/// (and end) aren't part of the compressed stream, and the transformation
+/// that handles the stream.
+type Magic = (CompressionHint, usize, usize, Option<Transformation>);
+
+/// Look for a known header at the start of `data`.
+fn check_magic(data: &[u8]) -> Option<Magic> {
+    match data {
+        // zlib only has a two-byte header, so only the common compression
+        // levels are accepted to keep false positives down
+        [0x78, 0x01, ..] | [0x78, 0x5e, ..] | [0x78, 0x9c, ..] | [0x78, 0xda, ..] => {
+            Some((CompressionHint::Zlib, 0, 0, Some(TransformDeflate::with_header())))
+        },
+
+        // gzip has a variable-length header, then a raw deflate stream, then
+        // an 8-byte trailer
+        [0x1f, 0x8b, 0x08, flags, ..] => {
+            let header = gzip_header_length(data, *flags)?;
+            Some((CompressionHint::Gzip, header, 8, Some(TransformDeflate::without_header())))
+        },
+
+        [b'B', b'Z', b'h', b'1'..=b'9', 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, ..] => Some((CompressionHint::Bzip2, 0, 0, None)),
+        [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..]                                 => Some((CompressionHint::Xz,    0, 0, None)),
+        [0x28, 0xb5, 0x2f, 0xfd, ..]                                             => Some((CompressionHint::Zstd,  0, 0, None)),
+        [0x04, 0x22, 0x4d, 0x18, ..]                                             => Some((CompressionHint::Lz4,   0, 0, None)),
+
+        _ => None,
+    }
+}
+
+/// Work out how long a gzip header is, from its flags (see RFC 1952).
+fn gzip_header_length(data: &[u8], flags: u8) -> Option<usize> {
+    // Reserved bits must be zero
+    if flags & 0xe0 != 0 {
+        return None;
+    }
+
+    let mut length = 10;
+
+    // FEXTRA: a two-byte length, then that many bytes
+    if flags & 0x04 != 0 {
+        let extra = data.get(length..(length + 2))?;
+        length += 2 + u16::from_le_bytes([extra[0], extra[1]]) as usize;
+    }
+
+    // FNAME and FCOMMENT: null-terminated strings
+    for flag in [0x08, 0x10] {
+        if flags & flag != 0 {
+            length += data.get(length..)?.iter().position(|b| *b == 0)? + 1;
+        }
+    }
+
+    // FHCRC: a two-byte checksum
+    if flags & 0x02 != 0 {
+        length += 2;
+    }
+
+    match length < data.len() {
+        true  => Some(length),
+        false => None,
+    }
+}
+
+/// Look through `range` for regions that are probably compressed or
+/// encrypted, and suggest a [`Transformation`] for each one that might
+/// undo it.
+///
+/// Two things are used: known headers (zlib, gzip, bzip2, and so on), and
+/// entropy - compressed and encrypted data look random, so blocks with very
+/// high entropy are suspicious even without a header. A header's region runs
+/// until the data stops looking random (or the next header). Where there's a
+/// suggested transformation, it's tried, and candidates that actually
+/// transform are trusted more.
+///
+/// Candidates are sorted by confidence, most confident first (ties are
+/// sorted by offset).
+pub fn find_compressed(buffer: &H2Buffer, range: Range<usize>) -> SimpleResult<Vec<CompressedCandidate>> {
+    if range.is_empty() {
+        bail!("Can't look for compressed data in an empty range");
+    }
+
+    let data = buffer.byte_range(range.clone())?;
+
+    // Measure each block
+    let blocks: Vec<f64> = data.chunks_exact(ENTROPY_BLOCK).map(shannon_entropy).collect();
+    let is_high = |block: usize| blocks.get(block).map(|e| *e >= HIGH_ENTROPY).unwrap_or(false);
+
+    // Find the headers
+    let magics: Vec<(usize, Magic)> = (0..data.len()).filter_map(|i| {
+        check_magic(&data[i..]).map(|magic| (i, magic))
+    }).collect();
+
+    let mut candidates = vec![];
+    let mut covered = vec![false; blocks.len()];
+
+    for (index, (start, (hint, header, trailer, transformation))) in magics.iter().enumerate() {
+        // Keep going until the data stops looking random, or we hit the next
+        // header
+        let mut block = start / ENTROPY_BLOCK + 1;
+        while is_high(block) {
+            block += 1;
+        }
+
+        let next_magic = magics.get(index + 1).map(|(next, _)| *next).unwrap_or(data.len());
+        let end = std::cmp::min(std::cmp::min(block * ENTROPY_BLOCK, data.len()), next_magic);
+
+        if start + header + trailer >= end {
+            continue;
+        }
+
+        for c in covered.iter_mut().take(block).skip(start / ENTROPY_BLOCK) {
+            *c = true;
+        }
+
+        let stream = &data[(start + header)..(end - trailer)];
+        let verified = transformation.map(|t| t.can_transform(&stream.to_vec())).unwrap_or(false);
+        let entropy = shannon_entropy(&data[*start..end]);
+
+        candidates.push(CompressedCandidate {
+            hint: *hint,
+            range: (range.start + start)..(range.start + end),
+            data: (range.start + start + header)..(range.start + end - trailer),
+            transformation: *transformation,
+            verified: verified,
+            entropy: entropy,
+            confidence: MAGIC_CONFIDENCE + if verified { VERIFIED_CONFIDENCE } else { 0.0 } + (entropy / 8.0) * (1.0 - MAGIC_CONFIDENCE - VERIFIED_CONFIDENCE),
+        });
+    }
+
+    // Then any random-looking runs that didn't have a header
+    let mut block = 0;
+    while block < blocks.len() {
+        if !is_high(block) || covered[block] {
+            block += 1;
+            continue;
+        }
+
+        let first = block;
+        while is_high(block) && !covered[block] {
+            block += 1;
+        }
+
+        let run = (first * ENTROPY_BLOCK)..std::cmp::min(block * ENTROPY_BLOCK, data.len());
+        let entropy = shannon_entropy(&data[run.clone()]);
+
+        // Headerless deflate is worth a try
+        let deflate = TransformDeflate::without_header();
+        let verified = deflate.can_transform(&data[run.clone()].to_vec());
+
+        candidates.push(CompressedCandidate {
+            hint: CompressionHint::HighEntropy,
+            range: (range.start + run.start)..(range.start + run.end),
+            data: (range.start + run.start)..(range.start + run.end),
+            transformation: if verified { Some(deflate) } else { None },
+            verified: verified,
+            entropy: entropy,
+            confidence: ENTROPY_CONFIDENCE * (entropy - HIGH_ENTROPY) / (8.0 - HIGH_ENTROPY) + if verified { VERIFIED_CONFIDENCE } else { 0.0 },
+        });
+    }
+
+    candidates.sort_by(|a, b| {
+        b.confidence.partial_cmp(&a.confidence).unwrap_or(Ordering::Equal).then_with(|| a.range.start.cmp(&b.range.start))
+    });
+
+    Ok(candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Bytes that look random, without pulling in a random number generator.
+    fn noise(length: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+
+        (0..length).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state >> 24) as u8
+        }).collect()
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(0.0, shannon_entropy(b""));
+        assert_eq!(0.0, shannon_entropy(b"AAAA"));
+        assert_eq!(1.0, shannon_entropy(b"ABAB"));
+        assert_eq!(8.0, shannon_entropy(&(0..=255).collect::<Vec<u8>>()));
+        assert!(shannon_entropy(&noise(4096, 1)) > 7.9);
+    }
+
+    #[test]
+    fn test_find_compressed() -> SimpleResult<()> {
+        // Text, then a zlib stream, then text, then a gzip stream with a
+        // filename, then text, then headerless noise
+        let text = b"The quick brown fox jumps over the lazy dog. ".repeat(12);
+
+        let mut data = text.clone();
+        let zlib_start = data.len();
+        data.extend(b"\x78\x9c");
+        data.extend(noise(1024, 1));
+
+        data.extend(&text);
+        let gzip_start = data.len();
+        data.extend(b"\x1f\x8b\x08\x08\x00\x00\x00\x00\x00\x03file.txt\x00");
+        data.extend(noise(1024, 2));
+
+        data.extend(&text);
+        let noise_start = data.len();
+        data.extend(noise(1024, 3));
+        data.extend(&text);
+
+        let buffer = H2Buffer::new("buffer", data.clone(), 0)?;
+        let candidates = find_compressed(&buffer, 0..data.len())?;
+
+        let hints: Vec<CompressionHint> = candidates.iter().map(|c| c.hint).collect();
+        assert_eq!(vec![CompressionHint::Zlib, CompressionHint::Gzip, CompressionHint::HighEntropy], hints);
+
+        // The header regions start at the header, and run until the text
+        let zlib = &candidates[0];
+        assert_eq!(zlib_start, zlib.range.start);
+        assert_eq!(zlib.range, zlib.data);
+        assert_eq!(Some(TransformDeflate::with_header()), zlib.transformation);
+        assert!(zlib.range.end > zlib_start + 512);
+        assert!(zlib.range.end <= gzip_start);
+        assert!(zlib.to_string().starts_with(&format!("zlib header at 0x{:x}", zlib_start)));
+
+        // Gzip skips its header (and trailer) when transforming
+        let gzip = &candidates[1];
+        assert_eq!(gzip_start, gzip.range.start);
+        assert_eq!(gzip_start + 19, gzip.data.start);
+        assert_eq!(gzip.range.end - 8, gzip.data.end);
+        assert_eq!(Some(TransformDeflate::without_header()), gzip.transformation);
+
+        // The noise doesn't have a header, so it's only a guess
+        let random = &candidates[2];
+        assert!(random.range.start >= noise_start);
+        assert!(random.range.start < noise_start + ENTROPY_BLOCK);
+        assert!(random.entropy > HIGH_ENTROPY);
+        assert!(random.confidence < zlib.confidence);
+
+        // Plain text doesn't find anything
+        let buffer = H2Buffer::new("buffer", text.repeat(4), 0)?;
+        assert_eq!(0, find_compressed(&buffer, 0..buffer.len())?.len());
+
+        assert!(find_compressed(&buffer, 0..0).is_err());
+
+        Ok(())
+    }
+}
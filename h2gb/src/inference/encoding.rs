@@ -0,0 +1,281 @@
+use simple_error::{bail, SimpleResult};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Range;
+
+use generic_number::{CharacterReader, Endian};
+
+use crate::project::H2Buffer;
+
+/// How much to trust an encoding that didn't need any of its special
+/// features to decode the data (eg, UTF-8 that's entirely ASCII). This keeps
+/// the simplest encoding that fits at the top.
+const UNUSED_FEATURE_PENALTY: f64 = 0.95;
+
+/// Latin-1 can decode anything, so it's always a bit less likely than an
+/// encoding that had to validate.
+const LATIN1_PENALTY: f64 = 0.9;
+
+/// UTF-16 code units outside of ASCII / Latin-1 only count this much, since
+/// pairs of ASCII bytes often decode to valid (but meaningless) CJK
+/// characters.
+const UTF16_WIDE_WEIGHT: f64 = 0.5;
+
+/// A string encoding that [`detect_encoding`] can suggest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringEncoding {
+    ASCII,
+    UTF8,
+    UTF16(Endian),
+    Latin1,
+    ShiftJIS,
+}
+
+impl fmt::Display for StringEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ASCII                 => write!(f, "ASCII"),
+            Self::UTF8                  => write!(f, "UTF-8"),
+            Self::UTF16(Endian::Little) => write!(f, "UTF-16LE"),
+            Self::UTF16(Endian::Big)    => write!(f, "UTF-16BE"),
+            Self::Latin1                => write!(f, "Latin-1"),
+            Self::ShiftJIS              => write!(f, "Shift-JIS"),
+        }
+    }
+}
+
+impl StringEncoding {
+    /// Get the [`CharacterReader`] that reads this encoding, if there is one.
+    ///
+    /// Latin-1 and Shift-JIS can be detected, but can't be read yet.
+    pub fn character_reader(&self) -> Option<CharacterReader> {
+        match self {
+            Self::ASCII         => Some(CharacterReader::ASCII),
+            Self::UTF8          => Some(CharacterReader::UTF8),
+            Self::UTF16(endian) => Some(CharacterReader::UTF16(*endian)),
+            Self::Latin1        => None,
+            Self::ShiftJIS      => None,
+        }
+    }
+}
+
+/// A single suggestion from [`detect_encoding`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncodingCandidate {
+    pub encoding: StringEncoding,
+
+    /// Roughly, the fraction of the data that decodes to sensible
+    /// characters, from `0.0` to `1.0`
+    pub confidence: f64,
+}
+
+/// Is this a character we'd expect to see in a string?
+fn is_printable(c: char) -> bool {
+    match c {
+        '\t' | '\n' | '\r' => true,
+        '\u{fffd}'         => false,
+        _                  => !c.is_control(),
+    }
+}
+
+fn score_ascii(data: &[u8]) -> f64 {
+    let printable = data.iter().filter(|b| b.is_ascii() && is_printable(**b as char)).count();
+
+    printable as f64 / data.len() as f64
+}
+
+fn score_utf8(data: &[u8]) -> f64 {
+    let s = match std::str::from_utf8(data) {
+        Ok(s)  => s,
+        Err(_) => return 0.0,
+    };
+
+    let total = s.chars().count();
+    let printable = s.chars().filter(|c| is_printable(*c)).count();
+    let score = printable as f64 / total as f64;
+
+    if s.is_ascii() {
+        score * UNUSED_FEATURE_PENALTY
+    } else {
+        score
+    }
+}
+
+fn score_latin1(data: &[u8]) -> f64 {
+    // Latin-1 maps every byte directly to the same code point
+    let printable = data.iter().filter(|b| is_printable(**b as char)).count();
+    let score = printable as f64 / data.len() as f64 * LATIN1_PENALTY;
+
+    if data.is_ascii() {
+        score * UNUSED_FEATURE_PENALTY
+    } else {
+        score
+    }
+}
+
+fn score_utf16(data: &[u8], endian: Endian) -> f64 {
+    let units: Vec<u16> = data.chunks_exact(2).map(|pair| {
+        match endian {
+            Endian::Little => u16::from_le_bytes([pair[0], pair[1]]),
+            Endian::Big    => u16::from_be_bytes([pair[0], pair[1]]),
+        }
+    }).collect();
+
+    // Ignore a terminator (or padding)
+    let end = units.iter().rposition(|u| *u != 0).map(|i| i + 1).unwrap_or(0);
+    let units = &units[..end];
+    if units.is_empty() {
+        return 0.0;
+    }
+
+    let score: f64 = std::char::decode_utf16(units.iter().cloned()).map(|c| {
+        match c {
+            Ok(c) if is_printable(c) && (c as u32) < 0x100 => 1.0,
+            Ok(c) if is_printable(c)                        => UTF16_WIDE_WEIGHT * c.len_utf16() as f64,
+            _                                               => 0.0,
+        }
+    }).sum();
+
+    score / units.len() as f64
+}
+
+fn score_shift_jis(data: &[u8]) -> f64 {
+    let mut good = 0;
+    let mut double_byte = false;
+    let mut i = 0;
+
+    while i < data.len() {
+        let b = data[i];
+
+        match b {
+            // Single-byte ASCII (well, JIS X 0201) and half-width katakana
+            0x20..=0x7e | 0xa1..=0xdf | b'\t' | b'\n' | b'\r' => {
+                good += 1;
+                i += 1;
+            },
+
+            // Lead bytes - need a valid trail byte
+            0x81..=0x9f | 0xe0..=0xef => {
+                match data.get(i + 1) {
+                    Some(0x40..=0x7e) | Some(0x80..=0xfc) => {
+                        good += 2;
+                        double_byte = true;
+                        i += 2;
+                    },
+                    _ => i += 1,
+                }
+            },
+
+            _ => i += 1,
+        }
+    }
+
+    let score = good as f64 / data.len() as f64;
+
+    if double_byte {
+        score
+    } else {
+        score * UNUSED_FEATURE_PENALTY * UNUSED_FEATURE_PENALTY
+    }
+}
+
+/// Guess which encoding a string is in.
+///
+/// Every supported encoding is tried against the bytes in `range`, and scored
+/// by how much of the data decodes to printable characters. Encodings that
+/// can't decode the data at all (like invalid UTF-8) are left out. The result
+/// is sorted by confidence, best first.
+///
+/// This is a heuristic, and short strings especially can look like several
+/// encodings at once. Ties are broken in favour of the simplest encoding -
+/// plain ASCII text is reported as ASCII first, then UTF-8, then Latin-1.
+///
+/// A trailing NUL terminator is ignored.
+pub fn detect_encoding(buffer: &H2Buffer, range: Range<usize>) -> SimpleResult<Vec<EncodingCandidate>> {
+    if range.is_empty() {
+        bail!("Can't detect the encoding of an empty range");
+    }
+
+    let data = buffer.byte_range(range)?;
+
+    // Ignore a terminator for the single-byte encodings (UTF-16 handles its
+    // own)
+    let end = data.iter().rposition(|b| *b != 0).map(|i| i + 1).unwrap_or(0);
+    let narrow = &data[..end];
+
+    let mut candidates = vec![];
+
+    if !narrow.is_empty() {
+        candidates.push((StringEncoding::ASCII,    score_ascii(narrow)));
+        candidates.push((StringEncoding::UTF8,     score_utf8(narrow)));
+        candidates.push((StringEncoding::Latin1,   score_latin1(narrow)));
+        candidates.push((StringEncoding::ShiftJIS, score_shift_jis(narrow)));
+    }
+
+    candidates.push((StringEncoding::UTF16(Endian::Little), score_utf16(data, Endian::Little)));
+    candidates.push((StringEncoding::UTF16(Endian::Big),    score_utf16(data, Endian::Big)));
+
+    let mut candidates: Vec<EncodingCandidate> = candidates.into_iter().filter(|(_, confidence)| *confidence > 0.0).map(|(encoding, confidence)| {
+        EncodingCandidate {
+            encoding: encoding,
+            confidence: confidence,
+        }
+    }).collect();
+
+    // The sort is stable, so on a tie the order above (simplest first) wins
+    candidates.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(Ordering::Equal));
+
+    Ok(candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+
+    fn best(data: &[u8]) -> SimpleResult<StringEncoding> {
+        let buffer = H2Buffer::new("buffer", data.to_vec(), 0)?;
+
+        Ok(detect_encoding(&buffer, 0..buffer.len())?[0].encoding)
+    }
+
+    #[test]
+    fn test_detect_encoding() -> SimpleResult<()> {
+        assert_eq!(StringEncoding::ASCII,                  best(b"Hello, world!\n\0")?);
+        assert_eq!(StringEncoding::UTF8,                   best("Café crème brûlée".as_bytes())?);
+        assert_eq!(StringEncoding::UTF16(Endian::Little),  best(b"H\0e\0l\0l\0o\0\0\0")?);
+        assert_eq!(StringEncoding::UTF16(Endian::Big),     best(b"\0H\0e\0l\0l\0o")?);
+        assert_eq!(StringEncoding::Latin1,                 best(b"Caf\xe9 cr\xe8me")?);
+
+        // "日本語のテキスト"
+        assert_eq!(StringEncoding::ShiftJIS, best(b"\x93\xfa\x96\x7b\x8c\xea\x82\xcc\x83\x65\x83\x4c\x83\x58\x83\x67")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_encoding_confidence() -> SimpleResult<()> {
+        let buffer = H2Buffer::new("buffer", b"Hello\xff\xfe".to_vec(), 0)?;
+        let candidates = detect_encoding(&buffer, 0..buffer.len())?;
+
+        // Invalid UTF-8 isn't suggested at all
+        assert!(candidates.iter().all(|c| c.encoding != StringEncoding::UTF8));
+
+        // The results are sorted
+        for pair in candidates.windows(2) {
+            assert!(pair[0].confidence >= pair[1].confidence);
+        }
+
+        // Part of a buffer works too
+        let candidates = detect_encoding(&buffer, 0..5)?;
+        assert_eq!(StringEncoding::ASCII, candidates[0].encoding);
+        assert_eq!(1.0, candidates[0].confidence);
+        assert_eq!(Some(CharacterReader::ASCII), candidates[0].encoding.character_reader());
+
+        assert!(detect_encoding(&buffer, 0..0).is_err());
+        assert!(detect_encoding(&buffer, 0..100).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+use simple_error::{bail, SimpleResult};
+use std::collections::HashMap;
+use std::ops::Range;
+
+use generic_number::{Context, IntegerReader};
+
+use crate::project::{H2Buffer, H2DataOverlay};
+
+/// The distinct values in a column, and how often each one shows up.
+///
+/// Created by [`column_histogram`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnHistogram {
+    /// The number of records that were read
+    pub record_count: usize,
+
+    /// Each distinct `(value, count)`, most common first (ties are sorted by
+    /// value)
+    pub values: Vec<(usize, usize)>,
+}
+
+impl ColumnHistogram {
+    /// The number of distinct values in the column.
+    pub fn distinct(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Compare the column against every enum in `data` (see [`match_enums`])
+    /// and return the best one, if any enum matches at all.
+    pub fn best_enum(&self, data: &H2DataOverlay) -> SimpleResult<Option<EnumMatch>> {
+        Ok(match_enums(self, data)?.into_iter().next())
+    }
+}
+
+/// How well a [`ColumnHistogram`] fits a particular enum.
+///
+/// Created by [`match_enums`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumMatch {
+    /// The name of the enum, as used by [`h2datatype::simple::H2Enum`]
+    pub name: String,
+
+    /// The fraction of records whose value is defined in the enum, from `0.0`
+    /// to `1.0`
+    pub coverage: f64,
+
+    /// The number of distinct values in the column that the enum defines
+    pub matched: usize,
+
+    /// The number of distinct values in the column that the enum doesn't
+    /// define
+    pub unmatched: usize,
+
+    /// The total number of values in the enum
+    pub enum_size: usize,
+}
+
+/// Read a single integer column out of a region of repeating records, and
+/// count the distinct values.
+///
+/// `range` is split into records of `stride` bytes, and an integer is read
+/// from `offset` bytes into each record using `reader`. A partial record at
+/// the end of the range is ignored.
+///
+/// Values must fit in a `usize` (since that's what enums are indexed by), so
+/// `reader` must be unsigned.
+pub fn column_histogram(buffer: &H2Buffer, range: Range<usize>, stride: usize, offset: usize, reader: IntegerReader) -> SimpleResult<ColumnHistogram> {
+    if stride == 0 {
+        bail!("Stride must be at least one byte");
+    }
+
+    if offset + reader.size() > stride {
+        bail!("Column at offset {} (size {}) doesn't fit in a {}-byte record", offset, reader.size(), stride);
+    }
+
+    let data = buffer.byte_range(range)?.to_vec();
+    let context = Context::new(&data);
+
+    let mut counts: HashMap<usize, usize> = HashMap::new();
+    let mut record_count = 0;
+
+    for record_start in (0..data.len()).step_by(stride) {
+        if record_start + stride > data.len() {
+            break;
+        }
+
+        let value = reader.read(context.at((record_start + offset) as u64))?.as_usize()?;
+        *counts.entry(value).or_insert(0) += 1;
+        record_count += 1;
+    }
+
+    let mut values: Vec<(usize, usize)> = counts.into_iter().collect();
+    values.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    Ok(ColumnHistogram {
+        record_count: record_count,
+        values: values,
+    })
+}
+
+/// Compare a [`ColumnHistogram`] against every enum in `data` - that is, the
+/// project's enums plus the ones loaded by [`h2data`] (use
+/// [`crate::project::H2Project::data`], or [`H2DataOverlay::new`] for just
+/// the shared ones).
+///
+/// This is handy for working out which "ID space" a field belongs to - for
+/// example, whether a column is an item ID, a buff ID, or something else.
+///
+/// Each enum is scored by coverage: the fraction of records whose value is
+/// one of the enum's defined values. Enums that don't match any values are
+/// left out. The rest are returned best-first; when two enums cover the same
+/// fraction, the smaller one wins (since a small enum matching by chance is
+/// less likely than a big one).
+pub fn match_enums(histogram: &ColumnHistogram, data: &H2DataOverlay) -> SimpleResult<Vec<EnumMatch>> {
+    if histogram.record_count == 0 {
+        return Ok(vec![]);
+    }
+
+    let mut matches: Vec<EnumMatch> = vec![];
+
+    for name in data.enum_names() {
+        let mut matched = 0;
+        let mut unmatched = 0;
+        let mut matched_records = 0;
+
+        for (value, count) in &histogram.values {
+            match data.get(name, *value)? {
+                Some(_) => {
+                    matched += 1;
+                    matched_records += count;
+                },
+                None => unmatched += 1,
+            }
+        }
+
+        if matched == 0 {
+            continue;
+        }
+
+        matches.push(EnumMatch {
+            name: name.to_string(),
+            coverage: matched_records as f64 / histogram.record_count as f64,
+            matched: matched,
+            unmatched: unmatched,
+            enum_size: data.enum_size(name)?,
+        });
+    }
+
+    matches.sort_by(|a, b| {
+        b.coverage.partial_cmp(&a.coverage).unwrap()
+            .then(a.enum_size.cmp(&b.enum_size))
+            .then(a.name.cmp(&b.name))
+    });
+
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+
+    use generic_number::Endian;
+
+    /// Build 8-byte records of `u32be id`, `u16be version`, `u16be mode`.
+    fn build_records() -> SimpleResult<H2Buffer> {
+        let versions: Vec<u16> = vec![230, 234, 234, 238, 230, 234];
+        let modes:    Vec<u16> = vec![0, 3, 3, 1, 0, 60000];
+
+        let mut data: Vec<u8> = vec![];
+        for i in 0..versions.len() {
+            data.extend_from_slice(&(i as u32).to_be_bytes());
+            data.extend_from_slice(&versions[i].to_be_bytes());
+            data.extend_from_slice(&modes[i].to_be_bytes());
+        }
+
+        H2Buffer::new("buffer", data, 0)
+    }
+
+    #[test]
+    fn test_column_histogram() -> SimpleResult<()> {
+        let buffer = build_records()?;
+        let histogram = column_histogram(&buffer, 0..buffer.len(), 8, 4, IntegerReader::U16(Endian::Big))?;
+
+        assert_eq!(6, histogram.record_count);
+        assert_eq!(3, histogram.distinct());
+        assert_eq!(vec![(234, 3), (230, 2), (238, 1)], histogram.values);
+
+        // Ignore the partial record at the end
+        let histogram = column_histogram(&buffer, 0..(buffer.len() - 1), 8, 0, IntegerReader::U32(Endian::Big))?;
+        assert_eq!(5, histogram.record_count);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_match_enums() -> SimpleResult<()> {
+        let buffer = build_records()?;
+
+        let histogram = column_histogram(&buffer, 0..buffer.len(), 8, 4, IntegerReader::U16(Endian::Big))?;
+        let best = histogram.best_enum(&H2DataOverlay::new())?.unwrap();
+        assert_eq!("TerrariaVersion", best.name);
+        assert_eq!(1.0, best.coverage);
+        assert_eq!(3, best.matched);
+        assert_eq!(0, best.unmatched);
+
+        // One record (of 6) isn't a valid mode, and a smaller enum wins over
+        // a bigger one that covers the same values
+        let histogram = column_histogram(&buffer, 0..buffer.len(), 8, 6, IntegerReader::U16(Endian::Big))?;
+        let matches = match_enums(&histogram, &H2DataOverlay::new())?;
+        assert_eq!("TerrariaGameMode", matches[0].name);
+        assert_eq!(5.0 / 6.0, matches[0].coverage);
+        assert_eq!(1, matches[0].unmatched);
+        assert!(matches.iter().all(|m| m.coverage <= matches[0].coverage));
+
+        // A project enum that covers everything wins
+        let mut data = H2DataOverlay::new();
+        data.enum_define("ProjectModes", histogram.values.iter().map(|(value, _)| (*value, format!("Mode{}", value))).collect())?;
+        let best = histogram.best_enum(&data)?.unwrap();
+        assert_eq!("ProjectModes", best.name);
+        assert_eq!(1.0, best.coverage);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_match() -> SimpleResult<()> {
+        let buffer = H2Buffer::new("buffer", b"\xff\xff\xff\xff\xff\xff\xff\xff".to_vec(), 0)?;
+        let histogram = column_histogram(&buffer, 0..buffer.len(), 4, 0, IntegerReader::U32(Endian::Big))?;
+
+        assert_eq!(vec![(0xffffffff, 2)], histogram.values);
+        assert_eq!(None, histogram.best_enum(&H2DataOverlay::new())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_parameters() -> SimpleResult<()> {
+        let buffer = build_records()?;
+
+        assert!(column_histogram(&buffer, 0..buffer.len(), 0, 0, IntegerReader::U8).is_err());
+        assert!(column_histogram(&buffer, 0..buffer.len(), 8, 7, IntegerReader::U16(Endian::Big)).is_err());
+        assert!(column_histogram(&buffer, 0..1000, 8, 0, IntegerReader::U8).is_err());
+
+        // Signed values can't be enum values
+        assert!(column_histogram(&buffer, 0..buffer.len(), 8, 4, IntegerReader::I16(Endian::Big)).is_err());
+
+        Ok(())
+    }
+}
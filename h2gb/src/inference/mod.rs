@@ -0,0 +1,55 @@
+//! Heuristics for making sense of unannotated data.
+//!
+//! Nothing in here changes a project - these functions look at a buffer and
+//! make suggestions, which can then be turned into entries (or ignored!).
+//!
+//! So far, we can:
+//!
+//! * Guess at a struct definition, given a region of repeating records and
+//!   the record size ([`infer_struct`])
+//! * Suggest likely record sizes for a region, ranked by how well the data
+//!   lines up at each size ([`detect_stride`])
+//! * Count the distinct values in a column ([`column_histogram`]), and work
+//!   out which loaded enum they most likely belong to ([`match_enums`])
+//! * Guess which encoding a string is in, to pick the right
+//!   [`generic_number::CharacterReader`] ([`detect_encoding`])
+//! * Find regions that look compressed or encrypted, from known headers and
+//!   entropy, and suggest a [`h2transformation::Transformation`] for each
+//!   ([`find_compressed`])
+//! * Recover the XOR key that turns a region into some known plaintext, such
+//!   as a magic number ([`recover_xor_key`])
+//! * Search the ASCII and UTF-16 text in a buffer with a regular expression,
+//!   for URLs, keys, and the like ([`find_text`])
+//! * Find files embedded in a buffer - PNGs, ZIPs, PDFs, and ELFs - from
+//!   their signatures, and work out how long each one is
+//!   ([`find_signatures`])
+//! * Make a similarity digest of a buffer, which can be compared against
+//!   others to group near-identical files - the same format and version,
+//!   say - across a corpus ([`similarity_digest`], [`group_similar`])
+
+mod struct_inference;
+pub use struct_inference::*;
+
+mod stride;
+pub use stride::*;
+
+mod histogram;
+pub use histogram::*;
+
+mod encoding;
+pub use encoding::*;
+
+mod compressed;
+pub use compressed::*;
+
+mod xor;
+pub use xor::*;
+
+mod text_search;
+pub use text_search::*;
+
+mod carve;
+pub use carve::*;
+
+mod similarity;
+pub use similarity::*;
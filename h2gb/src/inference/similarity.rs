@@ -0,0 +1,355 @@
+use serde::{Serialize, Deserialize};
+use simple_error::{bail, SimpleResult, SimpleError};
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::project::H2Buffer;
+
+/// The fewest bytes a [`SimilarityDigest`] can be made from - anything
+/// shorter doesn't have enough in it to compare.
+pub const MIN_SIMILARITY_LENGTH: usize = 50;
+
+/// How far apart two digests can be (see [`SimilarityDigest::distance`]) and
+/// still be grouped together by [`group_similar`] - roughly, files in the same
+/// format and version, with different contents.
+pub const DEFAULT_SIMILARITY_THRESHOLD: usize = 150;
+
+/// The number of buckets the byte patterns are counted into.
+const BUCKETS: usize = 128;
+
+/// How a digest is written as a string - bump this if the digest changes, so
+/// old digests aren't compared against new ones.
+const PREFIX: &str = "sd1:";
+
+/// A short summary of some data that can be compared against others, to see
+/// how alike they are - even when they aren't identical.
+///
+/// This works like [TLSH](https://github.com/trendmicro/tlsh): every 5-byte
+/// window of the data is broken into byte triplets, which are hashed into
+/// buckets and counted. Each bucket is then stored as which quarter of the
+/// counts it falls into, along with the length of the data and the shape of
+/// the counts. Similar data has similar patterns, so it ends up with similar
+/// digests - unlike a cryptographic hash, where one changed byte changes
+/// everything.
+///
+/// Digests can be written out (and parsed back) as a string, so a corpus can
+/// be indexed once and compared later.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SimilarityDigest {
+    /// The length of the data, on a log scale
+    length: u8,
+
+    /// The first and second quartiles, as a fraction of the third (mod 16)
+    q1_ratio: u8,
+    q2_ratio: u8,
+
+    /// Two bits per bucket - which quartile the bucket's count is in
+    body: Vec<u8>,
+}
+
+impl SimilarityDigest {
+    /// Make a digest from some bytes.
+    ///
+    /// # Errors
+    ///
+    /// * The data must be at least [`MIN_SIMILARITY_LENGTH`] bytes long
+    /// * The data must have some variety - a long run of the same byte has
+    ///   nothing to compare
+    pub fn from_bytes(data: &[u8]) -> SimpleResult<Self> {
+        if data.len() < MIN_SIMILARITY_LENGTH {
+            bail!("Need at least {} bytes to compare, but only have {}", MIN_SIMILARITY_LENGTH, data.len());
+        }
+
+        let mut counts = [0u32; BUCKETS];
+        for window in data.windows(5) {
+            let (a, b, c, d, e) = (window[4], window[3], window[2], window[1], window[0]);
+
+            for (salt, x, y, z) in [(2, a, b, c), (3, a, b, d), (5, a, c, d), (7, a, c, e), (11, a, b, e), (13, a, d, e)] {
+                counts[bucket(salt, x, y, z)] += 1;
+            }
+        }
+
+        let mut sorted = counts;
+        sorted.sort_unstable();
+        let (q1, q2, q3) = (sorted[BUCKETS / 4 - 1], sorted[BUCKETS / 2 - 1], sorted[BUCKETS * 3 / 4 - 1]);
+
+        if q3 == 0 {
+            bail!("Data is too uniform to compare");
+        }
+
+        let mut body = vec![0u8; BUCKETS / 4];
+        for (i, count) in counts.iter().enumerate() {
+            let code = match *count {
+                c if c <= q1 => 0,
+                c if c <= q2 => 1,
+                c if c <= q3 => 2,
+                _            => 3,
+            };
+
+            body[i / 4] |= code << ((i % 4) * 2);
+        }
+
+        Ok(Self {
+            length: ((data.len() as f64).ln() / 1.5f64.ln()) as u8,
+            q1_ratio: ((q1 as u64 * 100 / q3 as u64) % 16) as u8,
+            q2_ratio: ((q2 as u64 * 100 / q3 as u64) % 16) as u8,
+            body: body,
+        })
+    }
+
+    /// How different two digests are - `0` means they're the same (or the
+    /// data is close enough that the digest can't tell), and bigger numbers
+    /// mean more different.
+    ///
+    /// There's no upper limit, but unrelated data is usually well over
+    /// [`DEFAULT_SIMILARITY_THRESHOLD`].
+    pub fn distance(&self, other: &Self) -> usize {
+        let mut distance = 0;
+
+        // The length matters a bit - a lot, if it's very different
+        distance += match (self.length as isize - other.length as isize).unsigned_abs() {
+            d if d <= 1 => d,
+            d           => d * 12,
+        };
+
+        for (a, b) in [(self.q1_ratio, other.q1_ratio), (self.q2_ratio, other.q2_ratio)] {
+            let d = (a as isize - b as isize).unsigned_abs();
+            distance += match d.min(16 - d) {
+                d if d <= 1 => d,
+                d           => (d - 1) * 12,
+            };
+        }
+
+        // Buckets at opposite ends count for extra
+        for (a, b) in self.body.iter().zip(other.body.iter()) {
+            for shift in [0, 2, 4, 6] {
+                distance += match (((a >> shift) & 3) as isize - ((b >> shift) & 3) as isize).unsigned_abs() {
+                    3 => 6,
+                    d => d,
+                };
+            }
+        }
+
+        distance
+    }
+
+    /// Is `other` within [`DEFAULT_SIMILARITY_THRESHOLD`] of this?
+    pub fn is_similar(&self, other: &Self) -> bool {
+        self.distance(other) <= DEFAULT_SIMILARITY_THRESHOLD
+    }
+}
+
+impl fmt::Display for SimilarityDigest {
+    /// Write the digest as `sd1:` and 68 hex digits.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{:02x}{:x}{:x}", PREFIX, self.length, self.q1_ratio, self.q2_ratio)?;
+        for b in self.body.iter() {
+            write!(f, "{:02x}", b)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for SimilarityDigest {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> SimpleResult<Self> {
+        let digits = match s.trim().strip_prefix(PREFIX) {
+            Some(digits) => digits,
+            None         => bail!("Similarity digests start with {}", PREFIX),
+        };
+
+        if digits.len() != 4 + BUCKETS / 2 || !digits.is_ascii() {
+            bail!("Similarity digests are {} hex digits after the {}", 4 + BUCKETS / 2, PREFIX);
+        }
+
+        let bytes = (0..digits.len()).step_by(2).map(|i| {
+            u8::from_str_radix(&digits[i..(i + 2)], 16)
+        }).collect::<Result<Vec<u8>, _>>().map_err(|e| {
+            SimpleError::new(format!("Couldn't parse similarity digest {}: {}", s.trim(), e))
+        })?;
+
+        Ok(Self {
+            length: bytes[0],
+            q1_ratio: bytes[1] >> 4,
+            q2_ratio: bytes[1] & 0x0f,
+            body: bytes[2..].to_vec(),
+        })
+    }
+}
+
+/// Hash a triplet of bytes (plus a salt, so each triplet in a window goes to
+/// a different place) into a bucket.
+fn bucket(salt: u8, x: u8, y: u8, z: u8) -> usize {
+    let mut h = u32::from_le_bytes([salt, x, y, z]);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846ca68b);
+    h ^= h >> 16;
+
+    h as usize % BUCKETS
+}
+
+/// Make a [`SimilarityDigest`] of part of a buffer.
+pub fn similarity_digest(buffer: &H2Buffer, range: Range<usize>) -> SimpleResult<SimilarityDigest> {
+    SimilarityDigest::from_bytes(buffer.byte_range(range)?)
+}
+
+/// Group things - files in a corpus, say - whose digests are within
+/// `max_distance` of each other (see [`SimilarityDigest::distance`]).
+///
+/// Anything within `max_distance` of anything else in a group joins that
+/// group, so a chain of small differences can end up together. Groups are in
+/// the order of their first item, and items stay in their original order;
+/// things that aren't like anything else are a group of one.
+pub fn group_similar<T: Clone>(items: &[(T, SimilarityDigest)], max_distance: usize) -> Vec<Vec<T>> {
+    // Union-find, where each item points at an earlier item in its group
+    let mut parents: Vec<usize> = (0..items.len()).collect();
+
+    fn root(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+
+        i
+    }
+
+    for i in 0..items.len() {
+        for j in 0..i {
+            if items[i].1.distance(&items[j].1) <= max_distance {
+                let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+                parents[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    let mut groups: Vec<(usize, Vec<T>)> = vec![];
+    for (i, (item, _)) in items.iter().enumerate() {
+        let r = root(&mut parents, i);
+
+        match groups.iter_mut().find(|(group, _)| *group == r) {
+            Some((_, group)) => group.push(item.clone()),
+            None             => groups.push((r, vec![item.clone()])),
+        }
+    }
+
+    groups.into_iter().map(|(_, group)| group).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    /// Bytes that look random, but are always the same.
+    fn noise(seed: u32, length: usize) -> Vec<u8> {
+        let mut state = seed;
+
+        (0..length).map(|_| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as u8
+        }).collect()
+    }
+
+    /// Something file-like: a header, then records with a fixed layout but
+    /// different values.
+    fn document(version: u8, seed: u32, records: usize) -> Vec<u8> {
+        let mut data = b"H2DOC\x00".to_vec();
+        data.push(version);
+
+        let values = noise(seed, records * 2);
+        for i in 0..records {
+            data.extend_from_slice(&(i as u32).to_le_bytes());
+            data.extend_from_slice(&[version, 0x00, values[i * 2], values[i * 2 + 1]]);
+            data.extend_from_slice(b"record\n");
+        }
+
+        data
+    }
+
+    #[test]
+    fn test_digest() -> SimpleResult<()> {
+        let a = SimilarityDigest::from_bytes(&document(1, 1, 80))?;
+        let b = SimilarityDigest::from_bytes(&document(1, 2, 84))?;
+        let other = SimilarityDigest::from_bytes(&noise(1, 2000))?;
+
+        assert_eq!(0, a.distance(&a));
+        assert_eq!(a.distance(&b), b.distance(&a));
+
+        // A couple of small edits barely matter
+        let mut edited = document(1, 1, 80);
+        edited[100] ^= 0xff;
+        edited.extend_from_slice(b"trailer");
+        assert!(a.distance(&SimilarityDigest::from_bytes(&edited)?) < 20);
+
+        // Similar files are closer to each other than to noise
+        assert!(a.is_similar(&b), "distance {}", a.distance(&b));
+        assert!(!a.is_similar(&other), "distance {}", a.distance(&other));
+        assert!(a.distance(&b) < a.distance(&other));
+
+        // Too short, or too boring
+        assert!(SimilarityDigest::from_bytes(&noise(1, MIN_SIMILARITY_LENGTH - 1)).is_err());
+        assert!(SimilarityDigest::from_bytes(&noise(1, MIN_SIMILARITY_LENGTH)).is_ok());
+        assert!(SimilarityDigest::from_bytes(&[0x41; 1000]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_digest_string() -> SimpleResult<()> {
+        let digest = SimilarityDigest::from_bytes(&document(2, 3, 10))?;
+
+        let s = digest.to_string();
+        assert!(s.starts_with("sd1:"));
+        assert_eq!(4 + 68, s.len());
+        assert_eq!(digest, s.parse()?);
+
+        assert!("".parse::<SimilarityDigest>().is_err());
+        assert!("sd1:00".parse::<SimilarityDigest>().is_err());
+        assert!(s.replace("sd1:", "sd2:").parse::<SimilarityDigest>().is_err());
+        assert!(format!("sd1:{}", "zz".repeat(34)).parse::<SimilarityDigest>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_similarity_digest() -> SimpleResult<()> {
+        let data = document(1, 4, 20);
+        let buffer = H2Buffer::new("buffer", data.clone(), 0)?;
+
+        assert_eq!(SimilarityDigest::from_bytes(&data)?, similarity_digest(&buffer, 0..buffer.len())?);
+        assert_eq!(SimilarityDigest::from_bytes(&data[10..80])?, similarity_digest(&buffer, 10..80)?);
+        assert!(similarity_digest(&buffer, 0..10).is_err());
+        assert!(similarity_digest(&buffer, 0..(buffer.len() + 1)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_similar() -> SimpleResult<()> {
+        let items = vec![
+            ("doc1",   SimilarityDigest::from_bytes(&document(1, 1, 80))?),
+            ("noise1", SimilarityDigest::from_bytes(&noise(1, 2000))?),
+            ("doc2",   SimilarityDigest::from_bytes(&document(1, 2, 90))?),
+            ("noise2", SimilarityDigest::from_bytes(&noise(2, 300))?),
+            ("doc3",   SimilarityDigest::from_bytes(&document(1, 3, 76))?),
+        ];
+
+        assert_eq!(vec![
+            vec!["doc1", "doc2", "doc3"],
+            vec!["noise1"],
+            vec!["noise2"],
+        ], group_similar(&items, DEFAULT_SIMILARITY_THRESHOLD));
+
+        // Nothing is that similar
+        assert_eq!(5, group_similar(&items, 0).len());
+        assert_eq!(0, group_similar::<&str>(&[], DEFAULT_SIMILARITY_THRESHOLD).len());
+
+        Ok(())
+    }
+}
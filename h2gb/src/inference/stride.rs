@@ -0,0 +1,210 @@
+use simple_error::{bail, SimpleResult};
+use std::ops::Range;
+
+use crate::project::H2Buffer;
+
+/// The minimum number of records that have to fit in the range before a
+/// stride is considered.
+const MINIMUM_RECORDS: usize = 2;
+
+/// How close a multiple of a stride has to score to be considered a harmonic
+/// of that stride (and therefore dropped).
+const HARMONIC_TOLERANCE: f64 = 0.9;
+
+/// A single suggestion from [`detect_stride`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrideCandidate {
+    /// The suggested record size, in bytes
+    pub stride: usize,
+
+    /// How much better than chance the data lines up at this stride, from
+    /// `0.0` (no better than random) to `1.0` (every byte repeats)
+    pub confidence: f64,
+}
+
+/// Get the fraction of bytes that match the byte `lag` bytes later.
+fn match_rate(data: &[u8], lag: usize) -> f64 {
+    let comparisons = data.len() - lag;
+    let matches = data.iter().zip(data[lag..].iter()).filter(|(a, b)| a == b).count();
+
+    matches as f64 / comparisons as f64
+}
+
+/// Get the chance that two randomly-chosen bytes from `data` are the same.
+///
+/// This is what we'd expect [`match_rate`] to be if the data had no structure
+/// at all.
+fn baseline(data: &[u8]) -> f64 {
+    let mut histogram = [0usize; 256];
+    for b in data {
+        histogram[*b as usize] += 1;
+    }
+
+    histogram.iter().map(|count| {
+        let p = *count as f64 / data.len() as f64;
+        p * p
+    }).sum()
+}
+
+/// Suggest likely record sizes for a region of repeating records.
+///
+/// For each stride from `minimum` to `maximum` (inclusive), this compares
+/// every byte in `range` against the byte one stride later - that is, it
+/// autocorrelates the data. Records tend to have the same bytes in the same
+/// places (padding, flags, high bytes of small integers, etc.), so the true
+/// record size lines up far better than chance.
+///
+/// The raw match rate is normalized against how often two random bytes from
+/// the range would match, so data that's mostly zeroes doesn't look like it
+/// has structure everywhere.
+///
+/// Multiples of a good stride line up just as well as the stride itself, so
+/// a candidate is dropped if a smaller stride that divides it scores nearly
+/// as well.
+///
+/// Strides that don't fit at least two records in the range are skipped. The
+/// candidates are returned best-first; anything that's no better than chance
+/// is left out, so the result may be empty.
+///
+/// The best candidate is a good starting point for
+/// [`crate::inference::infer_struct`].
+pub fn detect_stride(buffer: &H2Buffer, range: Range<usize>, minimum: usize, maximum: usize) -> SimpleResult<Vec<StrideCandidate>> {
+    if minimum == 0 {
+        bail!("Minimum stride must be at least one byte");
+    }
+
+    if minimum > maximum {
+        bail!("Minimum stride ({}) is greater than the maximum ({})", minimum, maximum);
+    }
+
+    let data = buffer.byte_range(range)?;
+    let maximum = std::cmp::min(maximum, data.len() / MINIMUM_RECORDS);
+
+    let baseline = baseline(data);
+    if baseline >= 1.0 {
+        // Every byte is the same, so any stride is as good as any other
+        return Ok(vec![]);
+    }
+
+    // Score each stride, smallest first
+    let mut candidates: Vec<StrideCandidate> = vec![];
+    for stride in minimum..=maximum {
+        let confidence = (match_rate(data, stride) - baseline) / (1.0 - baseline);
+        if confidence <= 0.0 {
+            continue;
+        }
+
+        // Drop harmonics of something we already have
+        let harmonic = candidates.iter().any(|c| {
+            stride % c.stride == 0 && c.confidence >= confidence * HARMONIC_TOLERANCE
+        });
+
+        if !harmonic {
+            candidates.push(StrideCandidate {
+                stride: stride,
+                confidence: confidence,
+            });
+        }
+    }
+
+    // Best first; on a tie, prefer the smaller stride
+    candidates.sort_by(|a, b| {
+        b.confidence.partial_cmp(&a.confidence).unwrap().then(a.stride.cmp(&b.stride))
+    });
+
+    Ok(candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+
+    /// Build `count` records of 12 bytes each:
+    ///
+    /// * u32le id
+    /// * u16le type (a few small values, in no particular order)
+    /// * u16 padding
+    /// * u32le noise
+    fn build_records(count: u32) -> SimpleResult<H2Buffer> {
+        let mut data: Vec<u8> = vec![];
+        let mut noise: u32 = 0x12345678;
+
+        for i in 0..count {
+            // Simple LCG, just so the noise isn't periodic
+            noise = noise.wrapping_mul(1103515245).wrapping_add(12345);
+
+            data.extend_from_slice(&(i + 1000).to_le_bytes());
+            data.extend_from_slice(&((noise >> 30) as u16 + 1).to_le_bytes());
+            data.extend_from_slice(b"\0\0");
+            data.extend_from_slice(&noise.to_le_bytes());
+        }
+
+        H2Buffer::new("buffer", data, 0)
+    }
+
+    #[test]
+    fn test_detect_stride() -> SimpleResult<()> {
+        let buffer = build_records(64)?;
+        let candidates = detect_stride(&buffer, 0..buffer.len(), 1, 64)?;
+
+        assert!(candidates.len() > 0);
+        assert_eq!(12, candidates[0].stride);
+        assert!(candidates[0].confidence > 0.0 && candidates[0].confidence <= 1.0);
+
+        // Multiples of the real stride shouldn't show up
+        assert!(!candidates.iter().any(|c| c.stride == 24 || c.stride == 36 || c.stride == 48));
+
+        // Results are sorted best-first
+        for pair in candidates.windows(2) {
+            assert!(pair[0].confidence >= pair[1].confidence);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_stride_offset_range() -> SimpleResult<()> {
+        // Starting partway through a record shouldn't matter
+        let buffer = build_records(64)?;
+        let candidates = detect_stride(&buffer, 5..buffer.len(), 4, 32)?;
+
+        assert_eq!(12, candidates[0].stride);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_stride_perfect() -> SimpleResult<()> {
+        let buffer = H2Buffer::new("buffer", b"ABCDEFGABCDEFGABCDEFGABCDEFG".to_vec(), 0)?;
+        let candidates = detect_stride(&buffer, 0..buffer.len(), 1, 14)?;
+
+        assert_eq!(vec![StrideCandidate { stride: 7, confidence: 1.0 }], candidates);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_stride_no_structure() -> SimpleResult<()> {
+        // All the same byte - every stride is equally meaningless
+        let buffer = H2Buffer::new("buffer", vec![0x41; 100], 0)?;
+        assert_eq!(0, detect_stride(&buffer, 0..buffer.len(), 1, 50)?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_parameters() -> SimpleResult<()> {
+        let buffer = build_records(4)?;
+
+        assert!(detect_stride(&buffer, 0..buffer.len(), 0, 10).is_err());
+        assert!(detect_stride(&buffer, 0..buffer.len(), 10, 5).is_err());
+        assert!(detect_stride(&buffer, 0..1000, 1, 10).is_err());
+
+        // Too short for two records means no candidates, not an error
+        assert_eq!(0, detect_stride(&buffer, 0..buffer.len(), 40, 50)?.len());
+
+        Ok(())
+    }
+}
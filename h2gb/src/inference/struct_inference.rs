@@ -0,0 +1,411 @@
+use simple_error::{bail, SimpleResult};
+use std::ops::Range;
+
+use generic_number::{IntegerReader, FloatReader, CharacterReader, Endian, DefaultFormatter, HexFormatter, CharacterFormatter};
+use h2datatype::H2Type;
+use h2datatype::simple::H2Blob;
+use h2datatype::simple::numeric::{H2Integer, H2Float};
+use h2datatype::simple::string::H2String;
+use h2datatype::composite::H2Struct;
+
+use crate::project::H2Buffer;
+
+/// The minimum number of records we need before we're willing to guess.
+const MINIMUM_RECORDS: usize = 2;
+
+/// The minimum length of a string column.
+const MINIMUM_STRING_LENGTH: usize = 4;
+
+/// What we think a column (or group of columns) in a record is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InferredFieldKind {
+    /// The same value in every record (magic values, padding, reserved fields)
+    Constant,
+
+    /// An integer that strictly increases from record to record (IDs, indexes)
+    Counter,
+
+    /// Printable ASCII text, possibly NUL-padded
+    Ascii,
+
+    /// A value that makes sense as a 32-bit float in every record
+    Float,
+
+    /// A small integer that's different between records
+    Integer,
+
+    /// We couldn't tell
+    Unknown,
+}
+
+impl InferredFieldKind {
+    fn prefix(self) -> &'static str {
+        match self {
+            Self::Constant => "constant",
+            Self::Counter  => "counter",
+            Self::Ascii    => "string",
+            Self::Float    => "float",
+            Self::Integer  => "integer",
+            Self::Unknown  => "unknown",
+        }
+    }
+}
+
+/// A single field from an [`InferredStruct`].
+#[derive(Debug, Clone)]
+pub struct InferredField {
+    /// The name we made up for the field (based on its kind and offset)
+    pub name: String,
+
+    /// The offset of the field, relative to the start of the record
+    pub offset: usize,
+
+    /// The size of the field, in bytes
+    pub size: usize,
+
+    /// What we think the field is
+    pub kind: InferredFieldKind,
+
+    /// The datatype we'd use to represent the field
+    pub field_type: H2Type,
+}
+
+/// The result of [`infer_struct`].
+#[derive(Debug, Clone)]
+pub struct InferredStruct {
+    /// The record size the inference was based on
+    pub stride: usize,
+
+    /// The number of records that were analyzed
+    pub record_count: usize,
+
+    /// The fields, in order - these cover the full record
+    pub fields: Vec<InferredField>,
+}
+
+impl InferredStruct {
+    /// Convert the guesses into an actual struct definition.
+    pub fn to_h2type(&self) -> SimpleResult<H2Type> {
+        H2Struct::new(self.fields.iter().map(|f| {
+            (f.name.clone(), f.field_type.clone())
+        }).collect())
+    }
+}
+
+/// Column statistics over a set of same-sized records.
+struct Records<'a> {
+    records: Vec<&'a [u8]>,
+    endian: Endian,
+}
+
+impl<'a> Records<'a> {
+    fn u16_at(&self, record: &[u8], offset: usize) -> u16 {
+        let b = [record[offset], record[offset + 1]];
+
+        match self.endian {
+            Endian::Big    => u16::from_be_bytes(b),
+            Endian::Little => u16::from_le_bytes(b),
+        }
+    }
+
+    fn u32_at(&self, record: &[u8], offset: usize) -> u32 {
+        let b = [record[offset], record[offset + 1], record[offset + 2], record[offset + 3]];
+
+        match self.endian {
+            Endian::Big    => u32::from_be_bytes(b),
+            Endian::Little => u32::from_le_bytes(b),
+        }
+    }
+
+    /// Are the bytes at `offset..offset+size` identical in every record?
+    fn is_constant(&self, offset: usize, size: usize) -> bool {
+        let first = &self.records[0][offset..(offset + size)];
+
+        self.records.iter().all(|r| &r[offset..(offset + size)] == first)
+    }
+
+    /// Does the u32 at `offset` strictly increase from record to record?
+    fn is_counter(&self, offset: usize) -> bool {
+        // We need at least three records to call something a counter with
+        // any confidence
+        if self.records.len() < 3 {
+            return false;
+        }
+
+        let values: Vec<u32> = self.records.iter().map(|r| self.u32_at(r, offset)).collect();
+
+        values.windows(2).all(|w| w[0] < w[1])
+    }
+
+    /// Is the u32 at `offset` a sane-looking float in every record?
+    ///
+    /// "Sane" is zero or a magnitude between 0.0001 and 10,000,000, with at
+    /// least one record being non-zero.
+    fn is_float(&self, offset: usize) -> bool {
+        let values: Vec<f32> = self.records.iter().map(|r| f32::from_bits(self.u32_at(r, offset))).collect();
+
+        let plausible = values.iter().all(|f| {
+            f.is_finite() && (*f == 0.0 || (f.abs() >= 0.0001 && f.abs() <= 10_000_000.0))
+        });
+
+        plausible && values.iter().any(|f| *f != 0.0)
+    }
+
+    /// Is the u32 at `offset` a "small" integer (top byte is zero) in every
+    /// record?
+    fn is_integer32(&self, offset: usize) -> bool {
+        self.records.iter().all(|r| self.u32_at(r, offset) < 0x01000000)
+    }
+
+    /// Is the u16 at `offset` a "small" integer (top byte is zero) in every
+    /// record?
+    fn is_integer16(&self, offset: usize) -> bool {
+        self.records.iter().all(|r| self.u16_at(r, offset) < 0x0100)
+    }
+
+    /// How long is the run of ASCII columns starting at `offset`?
+    ///
+    /// Each record must look like a NUL-padded string: zero or more printable
+    /// characters followed only by NUL bytes. To avoid mistaking small
+    /// integers for strings, at least one record must have three or more
+    /// printable characters, and on average there must be at least two.
+    ///
+    /// NUL padding is included up to the next 4-byte boundary.
+    fn ascii_length(&self, offset: usize, stride: usize) -> usize {
+        let is_printable = |b: u8| b >= 0x20 && b <= 0x7e;
+
+        // Find how far the printable-or-NUL columns extend
+        let mut end = offset;
+        while end < stride && self.records.iter().all(|r| is_printable(r[end]) || r[end] == 0) {
+            end += 1;
+        }
+
+        if end - offset < MINIMUM_STRING_LENGTH {
+            return 0;
+        }
+
+        // Make sure each record is printable characters then NULs
+        let mut total_printable = 0;
+        let mut longest = 0;
+        for r in &self.records {
+            let field = &r[offset..end];
+            let printable = field.iter().take_while(|b| is_printable(**b)).count();
+
+            if field[printable..].iter().any(|b| *b != 0) {
+                return 0;
+            }
+
+            total_printable += printable;
+            longest = longest.max(printable);
+        }
+
+        if longest < 3 || total_printable < 2 * self.records.len() {
+            return 0;
+        }
+
+        // Trailing NUL columns might belong to the next field (think of a
+        // little-endian integer), so only keep enough NUL padding to reach a
+        // 4-byte boundary
+        let content_end = offset + longest;
+        let padded_end = content_end + ((4 - (content_end % 4)) % 4);
+
+        padded_end.min(end) - offset
+    }
+}
+
+fn integer_type(size: usize, endian: Endian, hex: bool) -> H2Type {
+    let reader = match size {
+        1 => IntegerReader::U8,
+        2 => IntegerReader::U16(endian),
+        _ => IntegerReader::U32(endian),
+    };
+
+    match hex {
+        true  => H2Integer::new(reader, HexFormatter::pretty_integer()),
+        false => H2Integer::new(reader, DefaultFormatter::new_integer()),
+    }
+}
+
+/// Guess at a struct definition for a region of repeated records.
+///
+/// The `range` within `buffer` is split into records of `stride` bytes (any
+/// partial record at the end is ignored), then each column is analyzed across
+/// all records. We look for:
+///
+/// * Constant bytes (the same in every record)
+/// * Monotonic counters (32-bit values that always increase)
+/// * ASCII columns (NUL-padded printable strings)
+/// * Plausible 32-bit floats
+/// * Small integers (16- or 32-bit, top byte always zero)
+///
+/// Multi-byte fields are only considered at offsets aligned to their size,
+/// and are read with the given `endian`. Anything we can't identify becomes a
+/// single unknown byte.
+///
+/// The fields always cover the full record, so the result can be turned
+/// straight into an [`H2Struct`] with [`InferredStruct::to_h2type`].
+///
+/// These are guesses, not facts! The more records there are, the better the
+/// guesses get.
+pub fn infer_struct(buffer: &H2Buffer, range: Range<usize>, stride: usize, endian: Endian) -> SimpleResult<InferredStruct> {
+    if stride == 0 {
+        bail!("Stride must be at least one byte");
+    }
+
+    let data = buffer.byte_range(range)?;
+    let records = Records {
+        records: data.chunks_exact(stride).collect(),
+        endian: endian,
+    };
+
+    if records.records.len() < MINIMUM_RECORDS {
+        bail!("Need at least {} records to infer a struct, only have {}", MINIMUM_RECORDS, records.records.len());
+    }
+
+    let mut fields: Vec<InferredField> = vec![];
+    let mut offset = 0;
+
+    while offset < stride {
+        let aligned32 = offset % 4 == 0 && offset + 4 <= stride;
+        let aligned16 = offset % 2 == 0 && offset + 2 <= stride;
+        let string_length = records.ascii_length(offset, stride);
+
+        let (kind, size, field_type) = if aligned32 && records.is_constant(offset, 4) {
+            (InferredFieldKind::Constant, 4, integer_type(4, endian, true))
+        } else if string_length > 0 {
+            // Strings have to come before counters - sorted strings look a
+            // lot like an increasing big-endian integer!
+            (InferredFieldKind::Ascii, string_length, H2String::new(string_length as u64, CharacterReader::ASCII, CharacterFormatter::pretty_str_character())?)
+        } else if aligned32 && records.is_counter(offset) {
+            (InferredFieldKind::Counter, 4, integer_type(4, endian, false))
+        } else if aligned32 && records.is_integer32(offset) {
+            (InferredFieldKind::Integer, 4, integer_type(4, endian, false))
+        } else if aligned32 && records.is_float(offset) {
+            (InferredFieldKind::Float, 4, H2Float::new(FloatReader::F32(endian), DefaultFormatter::new_float()))
+        } else if aligned16 && !records.is_constant(offset, 2) && records.is_integer16(offset) {
+            (InferredFieldKind::Integer, 2, integer_type(2, endian, false))
+        } else if records.is_constant(offset, 1) {
+            (InferredFieldKind::Constant, 1, integer_type(1, endian, true))
+        } else {
+            (InferredFieldKind::Unknown, 1, H2Blob::new(1)?)
+        };
+
+        fields.push(InferredField {
+            name: format!("{}_0x{:x}", kind.prefix(), offset),
+            offset: offset,
+            size: size,
+            kind: kind,
+            field_type: field_type,
+        });
+
+        offset += size;
+    }
+
+    Ok(InferredStruct {
+        stride: stride,
+        record_count: records.records.len(),
+        fields: fields,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+
+    /// Build a buffer of records laid out as:
+    ///
+    /// * u32 id (counter)
+    /// * char[8] name (NUL-padded)
+    /// * f32 position
+    /// * u32 magic (constant 0xdeadbeef)
+    /// * u32 score (small integer)
+    /// * u8 flags (noise)
+    fn build_records(endian: Endian) -> SimpleResult<H2Buffer> {
+        let names: Vec<&[u8; 8]> = vec![b"alice\0\0\0", b"bob\0\0\0\0\0", b"carol\0\0\0", b"dave\0\0\0\0"];
+        let positions: Vec<f32> = vec![1.5, -20.25, 3.125, 1000.0];
+        let scores: Vec<u32> = vec![100, 2500, 7, 65000];
+        let flags: Vec<u8> = vec![0x81, 0x13, 0xfe, 0x42];
+
+        let mut data: Vec<u8> = vec![];
+        for i in 0..4 {
+            match endian {
+                Endian::Little => {
+                    data.extend_from_slice(&(i as u32 + 10).to_le_bytes());
+                    data.extend_from_slice(names[i]);
+                    data.extend_from_slice(&positions[i].to_le_bytes());
+                    data.extend_from_slice(&0xdeadbeefu32.to_le_bytes());
+                    data.extend_from_slice(&scores[i].to_le_bytes());
+                },
+                Endian::Big => {
+                    data.extend_from_slice(&(i as u32 + 10).to_be_bytes());
+                    data.extend_from_slice(names[i]);
+                    data.extend_from_slice(&positions[i].to_be_bytes());
+                    data.extend_from_slice(&0xdeadbeefu32.to_be_bytes());
+                    data.extend_from_slice(&scores[i].to_be_bytes());
+                },
+            }
+            data.push(flags[i]);
+        }
+
+        H2Buffer::new("buffer", data, 0)
+    }
+
+    #[test]
+    fn test_infer_struct() -> SimpleResult<()> {
+        for endian in vec![Endian::Little, Endian::Big] {
+            let buffer = build_records(endian)?;
+            let inferred = infer_struct(&buffer, 0..buffer.len(), 25, endian)?;
+
+            assert_eq!(4, inferred.record_count);
+
+            let summary: Vec<(usize, usize, InferredFieldKind)> = inferred.fields.iter().map(|f| (f.offset, f.size, f.kind)).collect();
+            assert_eq!(vec![
+                (0,  4, InferredFieldKind::Counter),
+                (4,  8, InferredFieldKind::Ascii),
+                (12, 4, InferredFieldKind::Float),
+                (16, 4, InferredFieldKind::Constant),
+                (20, 4, InferredFieldKind::Integer),
+                (24, 1, InferredFieldKind::Unknown),
+            ], summary);
+
+            assert_eq!("counter_0x0", inferred.fields[0].name);
+            assert_eq!("string_0x4", inferred.fields[1].name);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_h2type() -> SimpleResult<()> {
+        let buffer = build_records(Endian::Little)?;
+        let inferred = infer_struct(&buffer, 0..buffer.len(), 25, Endian::Little)?;
+        let t = inferred.to_h2type()?;
+
+        // The struct should cover exactly one record
+        let resolved = buffer.peek(&t, 25)?;
+        assert_eq!(25..50, resolved.actual_range);
+        assert_eq!(6, resolved.children.len());
+        assert_eq!(Some(1), resolved.children[0].as_integer.map(|i| i.as_usize().unwrap()).map(|i| i - 10));
+        assert!(resolved.children[1].display.contains("bob"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_parameters() -> SimpleResult<()> {
+        let buffer = build_records(Endian::Little)?;
+
+        // Zero stride
+        assert!(infer_struct(&buffer, 0..buffer.len(), 0, Endian::Little).is_err());
+
+        // Only one record
+        assert!(infer_struct(&buffer, 0..buffer.len(), 60, Endian::Little).is_err());
+
+        // Range off the end
+        assert!(infer_struct(&buffer, 0..1000, 25, Endian::Little).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+use regex::Regex;
+use simple_error::{bail, SimpleResult, SimpleError};
+use std::fmt;
+use std::ops::Range;
+
+use generic_number::Endian;
+
+use crate::project::H2Buffer;
+use crate::inference::StringEncoding;
+
+/// The most characters [`find_text`] decodes before searching them. Longer
+/// runs of text are searched in pieces of this size, so a match can't be
+/// longer than this.
+pub const MAX_TEXT_RUN: usize = 4096;
+
+/// Text found by [`find_text`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextMatch {
+    pub encoding: StringEncoding,
+
+    /// Where the matched text is, in bytes
+    pub range: Range<usize>,
+
+    /// The text that matched, decoded
+    pub text: String,
+}
+
+impl fmt::Display for TextMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at 0x{:x} (0x{:x} bytes): {}", self.encoding, self.range.start, self.range.len(), self.text)
+    }
+}
+
+/// Is this a character that'd be in a run of text? Like `strings`, only
+/// ASCII is counted - in UTF-16 as well, since pairs of ASCII bytes often
+/// decode to valid (but meaningless) CJK characters.
+fn is_text(c: u32) -> bool {
+    c == b'\t' as u32 || (0x20..0x7f).contains(&c)
+}
+
+/// A run of decoded text, and where each character came from.
+struct TextRun {
+    text: String,
+
+    /// The offset of every byte of `text` (relative to the start of the data)
+    /// - `text` is UTF-8, so it doesn't line up with the data
+    offsets: Vec<usize>,
+
+    /// Where the last character ends
+    end: usize,
+}
+
+impl TextRun {
+    fn new() -> Self {
+        Self {
+            text: String::new(),
+            offsets: vec![],
+            end: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    fn push(&mut self, c: char, offset: usize, size: usize) {
+        self.text.push(c);
+        self.offsets.resize(self.text.len(), offset);
+        self.end = offset + size;
+    }
+
+    /// Search the run, and empty it out for the next one.
+    fn flush(&mut self, regex: &Regex, encoding: StringEncoding, base: usize, out: &mut Vec<TextMatch>) {
+        if !self.text.is_empty() {
+            self.offsets.push(self.end);
+
+            out.extend(regex.find_iter(&self.text).filter(|m| !m.as_str().is_empty()).map(|m| TextMatch {
+                encoding: encoding,
+                range: (base + self.offsets[m.start()])..(base + self.offsets[m.end()]),
+                text: m.as_str().to_string(),
+            }));
+        }
+
+        self.text.clear();
+        self.offsets.clear();
+    }
+}
+
+/// Decode `data` from `start`, as `(offset, size, character)` - the
+/// character is `None` if it isn't text (see [`is_text`]).
+fn decode(data: &[u8], encoding: StringEncoding, start: usize) -> Box<dyn Iterator<Item=(usize, usize, Option<char>)> + '_> {
+    match encoding {
+        StringEncoding::ASCII => Box::new(data.iter().enumerate().skip(start).map(|(i, b)| {
+            (i, 1, Some(*b as char).filter(|c| is_text(*c as u32)))
+        })),
+        StringEncoding::UTF16(endian) => Box::new(data.get(start..).unwrap_or(&[]).chunks_exact(2).enumerate().map(move |(i, pair)| {
+            let unit = match endian {
+                Endian::Little => u16::from_le_bytes([pair[0], pair[1]]),
+                Endian::Big    => u16::from_be_bytes([pair[0], pair[1]]),
+            };
+
+            (start + i * 2, 2, std::char::from_u32(unit as u32).filter(|c| is_text(*c as u32)))
+        })),
+        _ => Box::new(std::iter::empty()),
+    }
+}
+
+/// Search the text in part of a buffer with a regular expression.
+///
+/// The bytes in `range` are decoded as each of `encodings` as they're
+/// searched, and every run of text (printable ASCII, like `strings` finds) is
+/// searched for `pattern` - the [`regex`] crate's syntax, where `(?i)`
+/// ignores case. This is for hunting through a binary for URLs, keys,
+/// and identifiers that aren't annotated yet; to search entries that already
+/// are, see [`crate::project::H2Project::search`].
+///
+/// Only a run of text is decoded at a time (at most [`MAX_TEXT_RUN`]
+/// characters), so this doesn't need a decoded copy of the whole range.
+/// UTF-16 is tried at both even and odd offsets - which means text in one
+/// byte order usually turns up in the other too, one byte later, if both
+/// are searched.
+///
+/// Matches are sorted by offset, then by the order of `encodings`. Empty
+/// matches are left out.
+///
+/// # Errors
+///
+/// * `pattern` has to be a valid regex
+/// * `range` has to be in the buffer
+/// * Only ASCII and UTF-16 can be searched so far
+pub fn find_text(buffer: &H2Buffer, range: Range<usize>, pattern: &str, encodings: &[StringEncoding]) -> SimpleResult<Vec<TextMatch>> {
+    let regex = Regex::new(pattern).map_err(|e| {
+        SimpleError::new(format!("Bad search pattern {}: {}", pattern, e))
+    })?;
+
+    if encodings.is_empty() {
+        bail!("Need at least one encoding to search");
+    }
+
+    let data = buffer.byte_range(range.clone())?;
+
+    let mut out: Vec<(usize, TextMatch)> = vec![];
+    for (index, encoding) in encodings.iter().enumerate() {
+        let alignments = match encoding {
+            StringEncoding::ASCII    => 0..1,
+            StringEncoding::UTF16(_) => 0..2,
+            _                        => bail!("Can't search {} text yet", encoding),
+        };
+
+        for alignment in alignments {
+            let mut matches = vec![];
+            let mut run = TextRun::new();
+
+            for (offset, size, c) in decode(data, *encoding, alignment) {
+                match c {
+                    Some(c) => {
+                        if run.len() >= MAX_TEXT_RUN {
+                            run.flush(&regex, *encoding, range.start, &mut matches);
+                        }
+                        run.push(c, offset, size);
+                    },
+                    None => run.flush(&regex, *encoding, range.start, &mut matches),
+                }
+            }
+            run.flush(&regex, *encoding, range.start, &mut matches);
+
+            out.extend(matches.into_iter().map(|m| (index, m)));
+        }
+    }
+
+    out.sort_by(|(a_index, a), (b_index, b)| a.range.start.cmp(&b.range.start).then_with(|| a_index.cmp(b_index)));
+
+    Ok(out.into_iter().map(|(_, m)| m).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn found(buffer: &H2Buffer, range: Range<usize>, pattern: &str, encodings: &[StringEncoding]) -> SimpleResult<Vec<(StringEncoding, Range<usize>, String)>> {
+        Ok(find_text(buffer, range, pattern, encodings)?.into_iter().map(|m| (m.encoding, m.range, m.text)).collect())
+    }
+
+    #[test]
+    fn test_find_text() -> SimpleResult<()> {
+        // ASCII, then UTF-16LE, then UTF-16BE at an odd offset
+        let mut data = b"\x00\x01http://a.example/x\x00\xff".to_vec();
+        data.extend(b"\x00h\x00t\x00t\x00p\x00:\x00/\x00/\x00b\x00\x00".iter().skip(1));
+        data.extend(b"\x00\x00\x00K\x00E\x00Y\x00=\x001\x002");
+        let buffer = H2Buffer::new("buffer", data, 0)?;
+
+        let all = [StringEncoding::ASCII, StringEncoding::UTF16(Endian::Little), StringEncoding::UTF16(Endian::Big)];
+        assert_eq!(vec![
+            (StringEncoding::ASCII,                 2..18,  "http://a.example".to_string()),
+            (StringEncoding::UTF16(Endian::Little), 22..38, "http://b".to_string()),
+        ], found(&buffer, 0..buffer.len(), "https?://[a-z.]+", &all)?);
+
+        // UTF-16BE text looks like UTF-16LE one byte later
+        assert_eq!(vec![
+            (StringEncoding::UTF16(Endian::Big),    41..53, "KEY=12".to_string()),
+            (StringEncoding::UTF16(Endian::Little), 42..52, "KEY=1".to_string()),
+        ], found(&buffer, 0..buffer.len(), "(?i)key=[0-9]+", &all)?);
+
+        // Only in the range, and offsets are still relative to the buffer
+        assert_eq!(vec![
+            (StringEncoding::ASCII, 9..10, "a".to_string()),
+        ], found(&buffer, 5..12, "a", &[StringEncoding::ASCII])?);
+
+        // Matches stay inside one run of text
+        assert_eq!(0, found(&buffer, 0..buffer.len(), "x.*h", &all)?.len());
+
+        // Errors
+        assert!(find_text(&buffer, 0..buffer.len(), "(", &all).is_err());
+        assert!(find_text(&buffer, 0..buffer.len(), "a", &[]).is_err());
+        assert!(find_text(&buffer, 0..buffer.len(), "a", &[StringEncoding::ShiftJIS]).is_err());
+        assert!(find_text(&buffer, 0..1000, "a", &all).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_long_run() -> SimpleResult<()> {
+        // A run longer than the limit is searched in pieces
+        let mut data = b"A".repeat(MAX_TEXT_RUN - 2);
+        data.extend(b"needle");
+        data.extend(b"B".repeat(10));
+        let buffer = H2Buffer::new("buffer", data, 0)?;
+
+        assert_eq!(0, found(&buffer, 0..buffer.len(), "needle", &[StringEncoding::ASCII])?.len());
+        assert_eq!(vec![
+            (StringEncoding::ASCII, (MAX_TEXT_RUN - 2)..MAX_TEXT_RUN, "ne".to_string()),
+            (StringEncoding::ASCII, MAX_TEXT_RUN..(MAX_TEXT_RUN + 4), "edle".to_string()),
+        ], found(&buffer, 0..buffer.len(), "ne|edle", &[StringEncoding::ASCII])?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use simple_error::{bail, SimpleResult};
+use std::ops::Range;
+
+use h2transformation::{Transformation, TransformXorByConstant, XorSettings};
+
+use crate::project::H2Buffer;
+
+/// The key sizes [`TransformXorByConstant`] can handle, smallest first.
+const KEY_SIZES: [usize; 4] = [1, 2, 4, 8];
+
+/// A key found by [`recover_xor_key`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XorKeyCandidate {
+    /// Where the plaintext was found
+    pub offset: usize,
+
+    /// The key, lined up with the start of the range (so it's ready to use
+    /// on the whole range)
+    pub key: Vec<u8>,
+
+    /// The transformation that decrypts the range
+    pub transformation: Transformation,
+}
+
+/// Turn a key into the [`TransformXorByConstant`] that uses it.
+fn transformation(key: &[u8]) -> Transformation {
+    let mut bytes = [0u8; 8];
+    bytes[..key.len()].copy_from_slice(key);
+
+    TransformXorByConstant::new(match key.len() {
+        1 => XorSettings::EightBit(bytes[0]),
+        2 => XorSettings::SixteenBit(u16::from_be_bytes([bytes[0], bytes[1]])),
+        4 => XorSettings::ThirtyTwoBit(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        _ => XorSettings::SixtyFourBit(u64::from_be_bytes(bytes)),
+    })
+}
+
+/// Try to find a key of `size` bytes that turns `data` (which starts
+/// `phase` bytes into the range) into `plaintext`.
+fn derive_key(data: &[u8], plaintext: &[u8], phase: usize, size: usize) -> Option<Vec<u8>> {
+    let mut key: Vec<Option<u8>> = vec![None; size];
+
+    for (i, (c, p)) in data.iter().zip(plaintext).enumerate() {
+        let k = &mut key[(phase + i) % size];
+
+        match k {
+            Some(k) if *k != c ^ p => return None,
+            _                      => *k = Some(c ^ p),
+        }
+    }
+
+    key.into_iter().collect()
+}
+
+/// Find the XOR key that turns part of `range` into a known `plaintext` - a
+/// magic number, say, or a string that's known to be in the file.
+///
+/// If `at` is set, the plaintext is expected at that offset; otherwise, every
+/// offset in the range is tried. Keys can be 1, 2, 4, or 8 bytes long (the
+/// sizes [`TransformXorByConstant`] supports), and the range has to be a
+/// multiple of the key size; the shortest key that works is used.
+///
+/// When searching, the plaintext has to be longer than the key, so there's
+/// something to check the key against - otherwise any data at all would
+/// match. Keys of all zeroes (where the plaintext is already there) aren't
+/// returned, and each key is only returned once, at the first offset it's
+/// found.
+///
+/// Candidates are sorted by key size, then offset.
+pub fn recover_xor_key(buffer: &H2Buffer, range: Range<usize>, plaintext: &[u8], at: Option<usize>) -> SimpleResult<Vec<XorKeyCandidate>> {
+    if plaintext.is_empty() {
+        bail!("Need some plaintext to recover an XOR key");
+    }
+
+    if range.len() < plaintext.len() {
+        bail!("Range 0x{:x?} is too short to hold {} bytes of plaintext", range, plaintext.len());
+    }
+
+    let data = buffer.byte_range(range.clone())?;
+
+    let offsets: Vec<usize> = match at {
+        Some(at) if at < range.start || at + plaintext.len() > range.end => bail!("Plaintext at 0x{:x} doesn't fit in range 0x{:x?}", at, range),
+        Some(at) => vec![at - range.start],
+        None     => (0..=(data.len() - plaintext.len())).collect(),
+    };
+
+    let mut candidates: Vec<XorKeyCandidate> = vec![];
+    for offset in offsets {
+        let ciphertext = &data[offset..(offset + plaintext.len())];
+
+        // The shortest key that fits is the only one worth returning - any
+        // key that works also works doubled
+        let key = KEY_SIZES.iter()
+            .filter(|size| data.len() % **size == 0)
+            .filter(|size| plaintext.len() >= **size && (at.is_some() || plaintext.len() > **size))
+            .find_map(|size| derive_key(ciphertext, plaintext, offset, *size));
+
+        if let Some(key) = key {
+            if key.iter().all(|k| *k == 0) || candidates.iter().any(|c| c.key == key) {
+                continue;
+            }
+
+            candidates.push(XorKeyCandidate {
+                offset: range.start + offset,
+                transformation: transformation(&key),
+                key: key,
+            });
+        }
+    }
+
+    // The sort is stable, so offsets stay in order
+    candidates.sort_by_key(|c| c.key.len());
+
+    Ok(candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn xor(data: &[u8], key: &[u8]) -> Vec<u8> {
+        data.iter().enumerate().map(|(i, b)| b ^ key[i % key.len()]).collect()
+    }
+
+    #[test]
+    fn test_recover_key() -> SimpleResult<()> {
+        // An ELF header, encrypted with a 4-byte key, after 4 bytes of junk
+        let plaintext = b"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+        let mut data = b"junk".to_vec();
+        data.extend(xor(plaintext, b"\x13\x37\xc0\xde"));
+
+        let buffer = H2Buffer::new("buffer", data, 0)?;
+
+        // Knowing where the plaintext is
+        let candidates = recover_xor_key(&buffer, 4..20, b"\x7fELF\x02\x01\x01\x00", Some(4))?;
+        assert_eq!(1, candidates.len());
+        assert_eq!(b"\x13\x37\xc0\xde".to_vec(), candidates[0].key);
+        assert_eq!(TransformXorByConstant::new(XorSettings::ThirtyTwoBit(0x1337c0de)), candidates[0].transformation);
+        assert_eq!(plaintext.to_vec(), candidates[0].transformation.transform(&buffer.byte_range(4..20)?.to_vec())?);
+
+        // Searching for it
+        let candidates = recover_xor_key(&buffer, 4..20, b"\x7fELF\x02\x01\x01\x00", None)?;
+        assert_eq!(4, candidates[0].offset);
+        assert_eq!(b"\x13\x37\xc0\xde".to_vec(), candidates[0].key);
+
+        // The key lines up with the range, not the plaintext
+        let candidates = recover_xor_key(&buffer, 4..20, b"\x02\x01\x01\x00\x00\x00", Some(8))?;
+        assert_eq!(b"\x13\x37\xc0\xde".to_vec(), candidates[0].key);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_single_byte() -> SimpleResult<()> {
+        let buffer = H2Buffer::new("buffer", xor(b"Hello, world!", b"\x42"), 0)?;
+
+        let candidates = recover_xor_key(&buffer, 0..13, b"world", None)?;
+        assert_eq!(1, candidates.len());
+        assert_eq!(7, candidates[0].offset);
+        assert_eq!(TransformXorByConstant::new(XorSettings::EightBit(0x42)), candidates[0].transformation);
+
+        // Plaintext that's already there isn't a key
+        let buffer = H2Buffer::new("buffer", b"Hello, world!".to_vec(), 0)?;
+        assert_eq!(0, recover_xor_key(&buffer, 0..13, b"world", None)?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_errors() -> SimpleResult<()> {
+        let buffer = H2Buffer::new("buffer", b"ABCDEFGH".to_vec(), 0)?;
+
+        assert!(recover_xor_key(&buffer, 0..8, b"", None).is_err());
+        assert!(recover_xor_key(&buffer, 0..2, b"ABC", None).is_err());
+        assert!(recover_xor_key(&buffer, 0..8, b"ABC", Some(6)).is_err());
+        assert!(recover_xor_key(&buffer, 0..16, b"ABC", None).is_err());
+
+        // Too short to check an 8-byte key, without knowing where it is
+        assert_eq!(0, recover_xor_key(&buffer, 0..8, b"\x00\x01\x02\x03\x04\x05\x06\x07", None)?.iter().filter(|c| c.key.len() == 8).count());
+
+        Ok(())
+    }
+}
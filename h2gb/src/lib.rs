@@ -12,11 +12,56 @@
 //!
 //! If you haven't already, you can install the pre-commit and pre-push hooks
 //! by running [./install-hooks.sh](/install-hooks.sh) in the root folder.
+//!
+//! # Features
+//!
+//! Only the datatype engine - [`h2datatype`], [`generic_number`], and
+//! [`h2data`], which are re-exported here - is always built. Everything else
+//! is behind a feature:
+//!
+//! * `project` - projects, actions (with undo and redo), rendering,
+//!   importing, and sessions; this pulls in transformations (and their
+//!   crypto libraries)
+//! * `analyzers` (the default) - analyzers and structure inference, which
+//!   build on `project`
+//!
+//! To use just the datatype engine, turn off the default features:
+//!
+//! ```toml
+//! libh2gb = { version = "0.1", default-features = false }
+//! ```
+//!
+//! (or depend on `h2datatype` and `generic-number` directly - neither needs
+//! anything else from h2gb.)
+//!
+//! # Tracing
+//!
+//! Building with the `tracing` feature adds [tracing](https://docs.rs/tracing)
+//! spans around applying and undoing actions, transformations, and resolving
+//! types (which records each type's size and number of children). Nothing is
+//! logged unless a subscriber is installed, so it's safe to leave on; it's off
+//! by default because resolving big types creates a lot of spans.
 #![allow(dead_code)] // TODO: Disable this
 
+// The datatype engine, so it can be used without depending on each crate
+pub use generic_number;
+pub use h2data;
+pub use h2datatype;
+
+#[cfg(feature = "analyzers")]
 pub mod analyzer;
+#[cfg(feature = "project")]
 pub mod project;
+#[cfg(feature = "project")]
 pub mod actions;
+#[cfg(feature = "analyzers")]
+pub mod inference;
+#[cfg(feature = "project")]
+pub mod import;
+#[cfg(feature = "project")]
+pub mod render;
+#[cfg(feature = "project")]
+pub mod session;
 
 // Actions we need:
 // * load data as buffer
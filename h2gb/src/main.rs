@@ -4,13 +4,64 @@ use std::{env, fs};
 use std::path::PathBuf;
 
 use redo::Record;
-use simple_error::SimpleResult;
+use simple_error::{SimpleResult, bail};
 
 use crate::actions::*;
-use crate::project::H2Project;
-use crate::analyzer::analyze_terraria;
+use crate::project::{H2Project, H2ReportOptions};
+use crate::analyzer::{auto_analyze, DEFAULT_CONFIDENCE_THRESHOLD};
+use crate::analyzer::generator::generate_analyzer;
+
+/// The smallest unannotated range worth listing.
+const MIN_UNKNOWN: usize = 16;
+
+/// The most entries to list for each buffer.
+const REPORT_LINES: usize = 200;
+
+/// Magic numbers are given as text (`GGUF`), or as hex (`0x504b0304`).
+fn parse_magic(magic: &str) -> SimpleResult<Vec<u8>> {
+    let hex = match magic.strip_prefix("0x") {
+        Some(hex) => hex,
+        None      => return Ok(magic.as_bytes().to_vec()),
+    };
+
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        bail!("Hex magic numbers need two digits per byte: {}", magic);
+    }
+
+    (0..hex.len()).step_by(2).map(|i| {
+        u8::from_str_radix(&hex[i..(i + 2)], 16).map_err(|e| {
+            simple_error::SimpleError::new(format!("Bad magic number {}: {}", magic, e))
+        })
+    }).collect()
+}
+
+/// `h2gb new-analyzer <name> <magic> [description]` - start a new analyzer
+/// from the template, in this crate's source tree.
+fn new_analyzer(args: &[String]) -> SimpleResult<()> {
+    let (name, magic) = match (args.first(), args.get(1)) {
+        (Some(name), Some(magic)) => (name, parse_magic(magic)?),
+        _ => bail!("Usage: h2gb new-analyzer <name> <magic> [description]"),
+    };
+    let description = args.get(2).unwrap_or(name);
+
+    let generated = generate_analyzer(name, description, &magic)?;
+
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    for path in generated.write(&root.join("src/analyzer"), &root.join("../testdata"))? {
+        println!("Wrote {}", path.display());
+    }
+
+    println!();
+    print!("{}", generated.registration);
+
+    Ok(())
+}
 
 fn main() -> SimpleResult<()> {
+    if env::args().nth(1).as_deref() == Some("new-analyzer") {
+        return new_analyzer(&env::args().skip(2).collect::<Vec<String>>());
+    }
+
     // Load the data
 
     let data = fs::read(match env::args().nth(1) {
@@ -42,12 +93,43 @@ fn main() -> SimpleResult<()> {
     //     record.undo()?;
     // }
 
-    match analyze_terraria(&mut record, "buffer") {
-        Ok(_) => (),
+    match auto_analyze(&mut record, "buffer", DEFAULT_CONFIDENCE_THRESHOLD) {
+        Ok(reports) if reports.is_empty() => println!("No analyzer recognized the file"),
+        Ok(reports) => {
+            for report in reports {
+                match report.error {
+                    Some(e) => println!("Analyzer {} ({:.0}% confident) failed: {}", report.analyzer, report.confidence * 100.0, e),
+                    None    => println!("Analyzer {} ({:.0}% confident) applied {} action(s)", report.analyzer, report.confidence * 100.0, report.actions),
+                }
+            }
+        },
         Err(e) => println!("Something went wrong: {}", e),
     };
 
-    println!("{}", record.target());
+    let project = record.target();
+    println!();
+    print!("{}", project);
+
+    println!();
+    print!("{}", project.report(&H2ReportOptions {
+        max_lines: Some(REPORT_LINES),
+        bytes: 8,
+        ..Default::default()
+    })?);
+
+    for (name, buffer) in project.buffers() {
+        println!();
+        println!("Unknown ranges in {}:", name);
+
+        // Walk through the bigger pieces nothing has been found in yet, the
+        // same way a "next unknown" jump would
+        let layers = buffer.layer_names();
+        let mut next = buffer.unannotated(&layers)?.into_iter().find(|gap| gap.len() >= MIN_UNKNOWN);
+        while let Some(gap) = next {
+            println!("Unknown: 0x{:x}..0x{:x} (0x{:x} bytes)", gap.start + buffer.base_address, gap.end + buffer.base_address, gap.len());
+            next = buffer.next_unannotated(&layers, gap.start, MIN_UNKNOWN)?;
+        }
+    }
 
     Ok(())
 }
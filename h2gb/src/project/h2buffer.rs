@@ -20,12 +20,13 @@ use std::mem;
 
 use serde::{Serialize, Deserialize};
 use simple_error::{bail, SimpleResult, SimpleError};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 use h2transformation::Transformation;
-use crate::project::H2Layer;
+use crate::project::{H2Entry, H2EntryFilter, H2Id, H2Layer, H2BufferMemoryUsage, H2Relocation, H2RelocationReport};
 use h2datatype::{Offset, H2Type, ResolvedType};
 use generic_number::Context;
 
@@ -41,10 +42,20 @@ pub struct H2Buffer {
     // A list of transformations that this buffer has undergone
     transformations: Vec<Transformation>,
 
-    layers: HashMap<String, H2Layer>,
+    layers: BTreeMap<String, H2Layer>,
+
+    // Assigned when the buffer is inserted into a project
+    #[serde(default)]
+    id: H2Id,
+    #[serde(default)]
+    layer_ids: BTreeMap<H2Id, String>,
 
     display_empty_addresses: bool,
     context_bytes: usize,
+
+    // The file the data was loaded from, if it came from one
+    #[serde(default)]
+    source: Option<PathBuf>,
 }
 
 impl fmt::Display for H2Buffer {
@@ -162,11 +173,16 @@ impl H2Buffer {
             name: name.to_string(),
             data: data,
             base_address: base_address,
-            layers: HashMap::new(),
+            layers: BTreeMap::new(),
             transformations: Vec::new(),
 
+            id: H2Id::default(),
+            layer_ids: BTreeMap::new(),
+
             display_empty_addresses: true, // TODO: Figure out how to handle empty addresses
             context_bytes: 16, // TODO: Figure out how to configure this
+
+            source: None,
         })
     }
 
@@ -180,6 +196,38 @@ impl H2Buffer {
         &self.name
     }
 
+    /// Get the ID, which is assigned when the buffer is added to a project
+    pub fn id(&self) -> H2Id {
+        self.id
+    }
+
+    pub(crate) fn set_id(&mut self, id: H2Id) {
+        self.id = id;
+    }
+
+    /// Get the transformations the data has been through, oldest first
+    pub fn transformations(&self) -> &[Transformation] {
+        &self.transformations
+    }
+
+    /// Get the file the data was originally loaded from, if any
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    pub(crate) fn set_source(&mut self, source: Option<PathBuf>) {
+        self.source = source;
+    }
+
+    /// Approximately how much memory the buffer and its layers are using.
+    pub fn memory_usage(&self) -> H2BufferMemoryUsage {
+        H2BufferMemoryUsage {
+            name: self.name.clone(),
+            data: self.data.len(),
+            layers: self.layer_names().into_iter().filter_map(|name| self.layer_get(name)).map(|layer| layer.memory_usage()).collect(),
+        }
+    }
+
     /// Clone the buffer and data (but not the layers and entries).
     ///
     /// The base address can be preserved or changed as part of the copy. The
@@ -356,22 +404,207 @@ impl H2Buffer {
         Ok(())
     }
 
-    // pub fn edit(&mut self, data: Vec<u8>, offset: usize) -> SimpleResult<Vec<u8>> {
-    //     // Get a handle to the buffer's data
-    //     let buffer_data = &mut self.data;
+    /// Overwrite part of the buffer with new data.
+    ///
+    /// Returns the data that was overwritten, so the edit can be undone by
+    /// editing it back.
+    ///
+    /// # Errors
+    ///
+    /// * The data must fit in the buffer, and can't be empty
+    /// * No layer may have an entry that overlaps the edit, since the entry
+    ///   would no longer match the data
+    pub fn edit(&mut self, data: Vec<u8>, offset: usize) -> SimpleResult<Vec<u8>> {
+        // Sanity check
+        if offset + data.len() > self.data.len() {
+            bail!("Editing data into buffer is too long");
+        }
+
+        if data.len() == 0 {
+            bail!("Can't edit zero bytes");
+        }
+
+        let range = offset..(offset + data.len());
+        for (name, layer) in &self.layers {
+            if layer.entries_get(range.clone())?.len() > 0 {
+                bail!("Can't edit 0x{:x?}: layer {} has entries there", range, name);
+            }
+        }
+
+        // Splice in our data, get the original data back
+        Ok(self.data.splice(range, data).collect())
+    }
+
+    /// Work out what replacing part of the buffer with a different amount of
+    /// data would do to the annotations, without doing it - see
+    /// [`H2Relocation`].
+    pub fn relocation_check(&self, relocation: &H2Relocation) -> SimpleResult<H2RelocationReport> {
+        if relocation.old_range().end > self.data.len() {
+            bail!("Relocation 0x{:x?} is past the end of buffer {}", relocation.old_range(), self.name);
+        }
 
-    //     // Sanity check
-    //     if offset + data.len() > buffer_data.len() {
-    //         bail!("Editing data into buffer is too long");
-    //     }
+        let mut report = H2RelocationReport::default();
+        for layer in self.layers() {
+            let layer_report = layer.relocation_check(relocation);
 
-    //     if data.len() == 0 {
-    //         bail!("Can't edit zero bytes");
-    //     }
+            report.moved += layer_report.moved;
+            report.invalidated.extend(layer_report.invalidated);
+        }
 
-    //     // Splice in our data, get the original data back
-    //     Ok(buffer_data.splice(offset..(offset+data.len()), data).collect())
-    // }
+        Ok(report)
+    }
+
+    /// Replace `length` bytes at `offset` with `data`, which can be a
+    /// different size, and move every annotation after it along to match.
+    ///
+    /// Returns the data that was replaced, so the splice can be undone by
+    /// splicing it back (over `data.len()` bytes).
+    ///
+    /// # Errors
+    ///
+    /// * The range must be in the buffer, and the buffer can't end up empty
+    /// * Nothing may overlap the replaced bytes (see
+    ///   [`H2Buffer::relocation_check`]) - every problem is listed in the
+    ///   error
+    pub fn splice(&mut self, data: Vec<u8>, offset: usize, length: usize) -> SimpleResult<Vec<u8>> {
+        let relocation = H2Relocation::new(offset, length, data.len());
+        let report = self.relocation_check(&relocation)?;
+
+        if !report.is_clean() {
+            let invalidated: Vec<String> = report.invalidated.iter().map(|i| i.to_string()).collect();
+            bail!("Can't change the size of 0x{:x?} in buffer {}; it would invalidate: {}", relocation.old_range(), self.name, invalidated.join(", "));
+        }
+
+        if self.data.len() - length + data.len() == 0 {
+            bail!("Can't splice buffer {} down to zero bytes", self.name);
+        }
+
+        for layer in self.layers.values_mut() {
+            layer.relocate(&relocation)?;
+        }
+
+        Ok(self.data.splice(relocation.old_range(), data).collect())
+    }
+
+    /// Find what would change if the data were replaced with `data`.
+    ///
+    /// The ranges are runs of differing bytes, using offsets in the current
+    /// data. If the length changes, everything past the end of the shorter
+    /// one is part of the last range.
+    pub fn changed_ranges(&self, data: &[u8]) -> Vec<Range<usize>> {
+        let common = self.data.len().min(data.len());
+        let mut ranges: Vec<Range<usize>> = vec![];
+
+        let mut start: Option<usize> = None;
+        for i in 0..common {
+            match (start, self.data[i] == data[i]) {
+                (None, false)    => start = Some(i),
+                (Some(s), true)  => {
+                    ranges.push(s..i);
+                    start = None;
+                },
+                _                => (),
+            }
+        }
+
+        let end = self.data.len().max(data.len());
+        match start {
+            Some(s)                => ranges.push(s..end),
+            None if common < end   => ranges.push(common..end),
+            None                   => (),
+        }
+
+        ranges
+    }
+
+    /// Get every entry (and point) in every layer, as `(layer, entry)`,
+    /// sorted by layer name then offset.
+    pub fn entries_all(&self) -> Vec<(&H2Layer, &H2Entry)> {
+        self.layers().flat_map(|layer| {
+            layer.entries_all().into_iter().map(move |entry| (layer, entry))
+        }).collect()
+    }
+
+    /// Get every entry (and point) that matches a filter, as `(layer,
+    /// entry)`, sorted by layer name then offset.
+    pub fn entries_matching(&self, filter: &H2EntryFilter) -> Vec<(&H2Layer, &H2Entry)> {
+        self.entries_all().into_iter().filter(|(layer, entry)| filter.matches(layer.name(), entry)).collect()
+    }
+
+    /// Get the entries, in every layer, that replacing the data with `data`
+    /// would invalidate - that is, every entry that overlaps a byte that
+    /// changed (see [`H2Buffer::changed_ranges`]), plus any points past the
+    /// new end.
+    ///
+    /// The entries are sorted by layer name, then offset.
+    pub fn entries_invalidated_by(&self, data: &[u8]) -> SimpleResult<Vec<(&H2Layer, &H2Entry)>> {
+        let ranges = self.changed_ranges(data);
+
+        let mut out: Vec<(&H2Layer, &H2Entry)> = vec![];
+        for layer in self.layers() {
+            for range in &ranges {
+                // Only the part of the range that's in the current data can
+                // have entries
+                let range = range.start..range.end.min(self.data.len());
+                if !range.is_empty() {
+                    for entry in layer.entries_get(range)? {
+                        // An entry can span two ranges
+                        if !out.iter().any(|(l, e)| l.id() == layer.id() && e.id() == entry.id()) {
+                            out.push((layer, entry));
+                        }
+                    }
+                }
+            }
+
+            if data.len() < self.data.len() {
+                for (_, point) in layer.points_get((data.len() + 1)..(self.data.len() + 1))? {
+                    out.push((layer, point));
+                }
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Replace all of the data, when it's reloaded from wherever it came
+    /// from. The length can change.
+    ///
+    /// Returns the data that was replaced, so the reload can be undone by
+    /// reloading it back.
+    ///
+    /// # Errors
+    ///
+    /// * The data can't be empty
+    /// * The buffer can't have been transformed, since the data wouldn't be
+    ///   comparable
+    /// * No layer may have an entry that overlaps a change (see
+    ///   [`H2Buffer::entries_invalidated_by`]), or anything else past the new
+    ///   end
+    pub fn reload(&mut self, data: Vec<u8>) -> SimpleResult<Vec<u8>> {
+        if data.len() == 0 {
+            bail!("Can't reload buffer {} with zero bytes", self.name);
+        }
+
+        if self.transformations.len() > 0 {
+            bail!("Can't reload buffer {}: it has been transformed", self.name);
+        }
+
+        if let Some((layer, entry)) = self.entries_invalidated_by(&data)?.first() {
+            bail!("Can't reload buffer {}: layer {} has an entry at 0x{:x} that would change", self.name, layer.name(), entry.resolved().aligned_range.start);
+        }
+
+        for layer in self.layers.values() {
+            if !layer.can_resize(data.len()) {
+                bail!("Can't reload buffer {}: layer {} has annotations past the new end", self.name, layer.name());
+            }
+        }
+
+        for layer in self.layers.values_mut() {
+            layer.resize(data.len())?;
+        }
+
+        Ok(mem::replace(&mut self.data, data))
+    }
 
     // pub fn rebase(&mut self, new_base_address: usize) -> SimpleResult<usize> {
     //     let old_base_address = self.base_address;
@@ -384,20 +617,27 @@ impl H2Buffer {
     //    remove, then a bunch of simple proxies to make it more ergonomic to
     //    deal with layers!
 
-    pub fn layer_add(&mut self, layer: &str) -> SimpleResult<()> {
+    /// Add a layer, with an ID from [`crate::project::H2Project::id_allocate`].
+    pub fn layer_add(&mut self, layer: &str, id: H2Id) -> SimpleResult<()> {
         // Get this up front, we won't be able to once we borrow self in the match
         let length = self.len();
 
+        if !id.is_assigned() || self.layer_ids.contains_key(&id) {
+            bail!("Invalid layer ID for {} in buffer {}: {}", layer, self.name, id);
+        }
+
         // Either insert, or error if there's already a layer there
         match self.layers.entry(layer.to_string()) {
-            std::collections::hash_map::Entry::Occupied(_) => bail!("A layer named {} already exists in the buffer {}", layer, self.name),
-            std::collections::hash_map::Entry::Vacant(v) => v.insert(H2Layer::new(layer, length)),
+            std::collections::btree_map::Entry::Occupied(_) => bail!("A layer named {} already exists in the buffer {}", layer, self.name),
+            std::collections::btree_map::Entry::Vacant(v) => v.insert(H2Layer::new(layer, id, length)),
         };
+        self.layer_ids.insert(id, layer.to_string());
 
         Ok(())
     }
 
-    pub fn layer_remove(&mut self, layer: &str) -> SimpleResult<()> {
+    /// Remove an empty layer, and return its ID.
+    pub fn layer_remove(&mut self, layer: &str) -> SimpleResult<H2Id> {
         let is_populated = match self.layers.get(layer) {
             Some(layer) => layer.is_populated(),
             None => bail!("Could not find layer {} in buffer {}", self.name, layer),
@@ -408,11 +648,57 @@ impl H2Buffer {
         }
 
         match self.layers.remove(layer) {
-            Some(_) => Ok(()),
+            Some(l) => {
+                self.layer_ids.remove(&l.id());
+                Ok(l.id())
+            },
             None => bail!("Failed to remove the layer"),
         }
     }
 
+    /// Get the ID of the layer with the given name.
+    pub fn layer_id(&self, layer: &str) -> Option<H2Id> {
+        self.layer_get(layer).map(|l| l.id())
+    }
+
+    /// Get the current name of the layer with the given ID.
+    pub fn layer_name(&self, id: H2Id) -> Option<&str> {
+        self.layer_ids.get(&id).map(|name| &name[..])
+    }
+
+    pub fn layer_get_by_id(&self, id: H2Id) -> Option<&H2Layer> {
+        self.layer_ids.get(&id).and_then(|name| self.layers.get(name))
+    }
+
+    pub fn layer_get_mut_by_id(&mut self, id: H2Id) -> Option<&mut H2Layer> {
+        match self.layer_ids.get(&id) {
+            Some(name) => self.layers.get_mut(name),
+            None       => None,
+        }
+    }
+
+    pub fn layer_get_mut_by_id_or_err(&mut self, id: H2Id) -> SimpleResult<&mut H2Layer> {
+        let name = self.name.clone();
+
+        self.layer_get_mut_by_id(id).ok_or(
+            SimpleError::new(format!("Could not find layer with ID {} in buffer {}", id, name))
+        )
+    }
+
+    /// Get the names of all layers, sorted alphabetically.
+    pub fn layer_names(&self) -> Vec<&str> {
+        self.layers.keys().map(|k| &k[..]).collect()
+    }
+
+    /// Get all layers, sorted by name.
+    pub fn layers(&self) -> impl Iterator<Item=&H2Layer> {
+        self.layers.values()
+    }
+
+    pub(crate) fn layers_mut(&mut self) -> impl Iterator<Item=&mut H2Layer> {
+        self.layers.values_mut()
+    }
+
     pub fn layer_exists(&self, layer: &str) -> bool {
         self.layers.contains_key(layer)
     }
@@ -442,6 +728,57 @@ impl H2Buffer {
 
         abstract_type.resolve(offset, None)
     }
+
+    /// Find every range of bytes that isn't part of an entry in any of
+    /// `layers`, sorted by offset.
+    ///
+    /// Alignment padding counts as part of its entry, and points (which have
+    /// no size) are ignored.
+    pub fn unannotated(&self, layers: &[&str]) -> SimpleResult<Vec<Range<usize>>> {
+        let mut covered: Vec<Range<usize>> = vec![];
+        for layer in layers {
+            for entry in self.layer_get_or_err(layer)?.entries_get(0..self.len())? {
+                let aligned = &entry.resolved().aligned_range;
+                covered.push((aligned.start as usize)..(aligned.end as usize));
+            }
+        }
+        covered.sort_by_key(|range| range.start);
+
+        // Walk the covered ranges in order - since they can overlap (from
+        // different layers), keep track of the furthest one has reached
+        let mut gaps = vec![];
+        let mut position = 0;
+        for range in covered {
+            if range.start > position {
+                gaps.push(position..range.start);
+            }
+            position = std::cmp::max(position, range.end);
+        }
+
+        if position < self.len() {
+            gaps.push(position..self.len());
+        }
+
+        Ok(gaps)
+    }
+
+    /// Find the next unannotated range (see [`H2Buffer::unannotated`]) that
+    /// starts after `offset` and is at least `min_size` bytes long, for
+    /// jumping through the parts of a buffer that nobody has looked at yet.
+    ///
+    /// Use a `min_size` of 1 to find any range.
+    pub fn next_unannotated(&self, layers: &[&str], offset: usize, min_size: usize) -> SimpleResult<Option<Range<usize>>> {
+        Ok(self.unannotated(layers)?.into_iter().find(|gap| gap.start > offset && gap.len() >= min_size))
+    }
+
+    /// Find the previous unannotated range that starts before `offset` and is
+    /// at least `min_size` bytes long.
+    ///
+    /// If `offset` is in the middle of an unannotated range, that's the range
+    /// that's returned - just like jumping to the start of it.
+    pub fn previous_unannotated(&self, layers: &[&str], offset: usize, min_size: usize) -> SimpleResult<Option<Range<usize>>> {
+        Ok(self.unannotated(layers)?.into_iter().rev().find(|gap| gap.start < offset && gap.len() >= min_size))
+    }
 }
 
 #[cfg(test)]
@@ -449,6 +786,11 @@ mod tests {
     use super::*;
     use simple_error::SimpleResult;
     use h2transformation::TransformHex;
+    use generic_number::{IntegerReader, DefaultFormatter};
+    use h2datatype::simple::H2Marker;
+    use h2datatype::simple::numeric::H2Integer;
+
+    use crate::project::{H2AnnotationKind, H2Provenance};
 
     #[test]
     fn test_new() -> SimpleResult<()> {
@@ -658,4 +1000,200 @@ mod tests {
 
     //     Ok(())
     // }
+
+    #[test]
+    fn test_edit() -> SimpleResult<()> {
+        let mut buffer = H2Buffer::new("name", b"ABCDEFGH".to_vec(), 0x4000)?;
+
+        assert_eq!(b"CD".to_vec(), buffer.edit(b"xy".to_vec(), 2)?);
+        assert_eq!(b"ABxyEFGH".to_vec(), buffer.data);
+
+        assert_eq!(b"H".to_vec(), buffer.edit(b"z".to_vec(), 7)?);
+        assert_eq!(b"ABxyEFGz".to_vec(), buffer.data);
+
+        // Too long, or empty
+        assert!(buffer.edit(b"zz".to_vec(), 7).is_err());
+        assert!(buffer.edit(vec![], 0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_splice() -> SimpleResult<()> {
+        let mut buffer = H2Buffer::new("name", b"ABCDEFGH".to_vec(), 0x4000)?;
+        buffer.layer_add("layer", H2Id::new(1))?;
+
+        // An entry before and after the spliced bytes, plus some other annotations
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+        for (offset, id) in [(0, 2), (6, 3)] {
+            let resolved = buffer.peek(&u8, offset)?;
+            buffer.layer_get_mut_or_err("layer")?.entry_create(resolved, Some(u8.clone()), H2Id::new(id), H2Provenance::default())?;
+        }
+        buffer.layer_get_mut_or_err("layer")?.comment_set(2, Some("edited".to_string()))?;
+        buffer.layer_get_mut_or_err("layer")?.bookmark_set(7, Some("end".to_string()))?;
+
+        let relocation = H2Relocation::new(2, 2, 5);
+        assert_eq!(2, buffer.relocation_check(&relocation)?.moved);
+
+        // Grow "CD" into five bytes
+        assert_eq!(b"CD".to_vec(), buffer.splice(b"vwxyz".to_vec(), 2, 2)?);
+        assert_eq!(b"ABvwxyzEFGH".to_vec(), buffer.data);
+        assert_eq!(11, buffer.len());
+
+        let layer = buffer.layer_get_or_err("layer")?;
+        assert_eq!(H2Id::new(2), layer.entry_get_or_err(0)?.id());
+        assert_eq!(H2Id::new(3), layer.entry_get_or_err(9)?.id());
+        assert_eq!(9..10, layer.entry_get_or_err(9)?.resolved().actual_range);
+        assert_eq!(Some(&"edited".to_string()), layer.comment_get(2)?);
+        assert_eq!(Some(&"end".to_string()), layer.bookmark_get(10)?);
+
+        // And back again
+        assert_eq!(b"vwxyz".to_vec(), buffer.splice(b"CD".to_vec(), 2, 5)?);
+        assert_eq!(b"ABCDEFGH".to_vec(), buffer.data);
+        assert_eq!(H2Id::new(3), buffer.layer_get_or_err("layer")?.entry_get_or_err(6)?.id());
+        assert_eq!(Some(&"end".to_string()), buffer.layer_get_or_err("layer")?.bookmark_get(7)?);
+
+        // Off the end, or emptying the buffer
+        assert!(buffer.splice(b"A".to_vec(), 7, 2).is_err());
+        assert!(buffer.splice(vec![], 0, 8).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_splice_invalidated() -> SimpleResult<()> {
+        let mut buffer = H2Buffer::new("name", b"ABCDEFGH".to_vec(), 0x4000)?;
+        buffer.layer_add("layer", H2Id::new(1))?;
+
+        let u32 = H2Integer::new(IntegerReader::U32(generic_number::Endian::Big), DefaultFormatter::new_integer());
+        let resolved = buffer.peek(&u32, 2)?;
+        buffer.layer_get_mut_or_err("layer")?.entry_create(resolved, Some(u32), H2Id::new(2), H2Provenance::default())?;
+        buffer.layer_get_mut_or_err("layer")?.comment_set(1, Some("inside".to_string()))?;
+
+        // The entry straddles the spliced bytes, and the comment is inside them
+        let report = buffer.relocation_check(&H2Relocation::new(0, 3, 1))?;
+        assert_eq!(2, report.invalidated.len());
+        assert_eq!(H2AnnotationKind::Entry,   report.invalidated[0].kind);
+        assert_eq!(H2AnnotationKind::Comment, report.invalidated[1].kind);
+
+        // Nothing changes when it fails
+        let error = buffer.splice(b"A".to_vec(), 0, 3).unwrap_err().to_string();
+        assert!(error.contains("entry @ 0x2 in layer 'layer'"));
+        assert!(error.contains("comment @ 0x1 in layer 'layer' (inside)"));
+        assert_eq!(b"ABCDEFGH".to_vec(), buffer.data);
+        assert_eq!(H2Id::new(2), buffer.layer_get_or_err("layer")?.entry_get_or_err(2)?.id());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unannotated() -> SimpleResult<()> {
+        let mut buffer = H2Buffer::new("name", b"ABCDEFGHIJKLMNOP".to_vec(), 0x4000)?;
+        buffer.layer_add("layer1", H2Id::new(1))?;
+        buffer.layer_add("layer2", H2Id::new(2))?;
+
+        // layer1 covers 2..4 and 10..11, layer2 covers 3..6
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+        let u16 = H2Integer::new(IntegerReader::U16(generic_number::Endian::Big), DefaultFormatter::new_integer());
+        for (layer, datatype, offset, id) in [("layer1", &u16, 2, 3), ("layer1", &u8, 10, 4), ("layer2", &u16, 3, 5), ("layer2", &u8, 5, 6)] {
+            let resolved = buffer.peek(datatype, offset)?;
+            buffer.layer_get_mut_or_err(layer)?.entry_create(resolved, None, H2Id::new(id), H2Provenance::default())?;
+        }
+
+        assert_eq!(vec![0..2, 6..10, 11..16], buffer.unannotated(&["layer1", "layer2"])?);
+        assert_eq!(vec![0..2, 4..10, 11..16], buffer.unannotated(&["layer1"])?);
+        assert_eq!(vec![0..16], buffer.unannotated(&[])?);
+        assert!(buffer.unannotated(&["nope"]).is_err());
+
+        let layers = ["layer1", "layer2"];
+        assert_eq!(Some(6..10),  buffer.next_unannotated(&layers, 0, 1)?);
+        assert_eq!(Some(11..16), buffer.next_unannotated(&layers, 6, 1)?);
+        assert_eq!(None,         buffer.next_unannotated(&layers, 11, 1)?);
+        assert_eq!(Some(11..16), buffer.next_unannotated(&layers, 0, 5)?);
+        assert_eq!(None,         buffer.next_unannotated(&layers, 0, 6)?);
+
+        assert_eq!(Some(6..10),  buffer.previous_unannotated(&layers, 11, 1)?);
+        assert_eq!(Some(6..10),  buffer.previous_unannotated(&layers, 8, 1)?);
+        assert_eq!(Some(0..2),   buffer.previous_unannotated(&layers, 6, 1)?);
+        assert_eq!(None,         buffer.previous_unannotated(&layers, 0, 1)?);
+        assert_eq!(None,         buffer.previous_unannotated(&layers, 11, 5)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entries_all() -> SimpleResult<()> {
+        let mut buffer = H2Buffer::new("name", b"ABCDEFGHIJKLMNOP".to_vec(), 0x4000)?;
+        buffer.layer_add("layer2", H2Id::new(1))?;
+        buffer.layer_add("layer1", H2Id::new(2))?;
+
+        // Create them out of order, and include a point
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+        let marker = H2Marker::new("marker");
+        for (layer, datatype, offset, id) in [("layer2", &u8, 8, 3), ("layer1", &u8, 12, 4), ("layer2", &u8, 1, 5), ("layer1", &u8, 4, 6), ("layer1", &marker, 6, 7)] {
+            let resolved = buffer.peek(datatype, offset)?;
+            buffer.layer_get_mut_or_err(layer)?.entry_create(resolved, None, H2Id::new(id), H2Provenance::default())?;
+        }
+
+        let entries: Vec<(&str, u64)> = buffer.entries_all().into_iter().map(|(layer, entry)| (layer.name(), entry.resolved().actual_range.start)).collect();
+        assert_eq!(vec![
+            ("layer1", 4),
+            ("layer1", 6),
+            ("layer1", 12),
+            ("layer2", 1),
+            ("layer2", 8),
+        ], entries);
+
+        let names: Vec<&str> = buffer.layers().map(|layer| layer.name()).collect();
+        assert_eq!(vec!["layer1", "layer2"], names);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reload() -> SimpleResult<()> {
+        let mut buffer = H2Buffer::new("name", b"ABCDEFGH".to_vec(), 0x4000)?;
+
+        assert_eq!(Vec::<Range<usize>>::new(), buffer.changed_ranges(b"ABCDEFGH"));
+        assert_eq!(vec![1..3, 5..6], buffer.changed_ranges(b"AxxDEyGH"));
+        assert_eq!(vec![6..7, 8..10], buffer.changed_ranges(b"ABCDEFxHIJ"));
+        assert_eq!(vec![4..8], buffer.changed_ranges(b"ABCD"));
+
+        // Growing and shrinking resizes the layers
+        buffer.layer_add("layer", H2Id::new(1))?;
+        assert_eq!(b"ABCDEFGH".to_vec(), buffer.reload(b"ABCDEFGHIJ".to_vec())?);
+        assert_eq!(10, buffer.len());
+        buffer.layer_get_mut_or_err("layer")?.comment_set(9, Some("end".to_string()))?;
+
+        // Can't drop the comment on the floor, or reload nothing
+        assert!(buffer.reload(b"ABCD".to_vec()).is_err());
+        assert!(buffer.reload(vec![]).is_err());
+
+        buffer.layer_get_mut_or_err("layer")?.comment_set(9, None)?;
+        assert_eq!(b"ABCDEFGHIJ".to_vec(), buffer.reload(b"ABCD".to_vec())?);
+        assert!(buffer.layer_get_or_err("layer")?.comment_get(4).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_layer_ids() -> SimpleResult<()> {
+        let mut buffer = H2Buffer::new("name", b"ABCDEFGH".to_vec(), 0x4000)?;
+
+        buffer.layer_add("layer1", H2Id::new(1))?;
+        buffer.layer_add("layer2", H2Id::new(2))?;
+        assert_eq!(Some(H2Id::new(1)), buffer.layer_id("layer1"));
+        assert_eq!(Some("layer2"), buffer.layer_name(H2Id::new(2)));
+        assert_eq!("layer2", buffer.layer_get_by_id(H2Id::new(2)).unwrap().name());
+
+        // Duplicate or unassigned IDs fail
+        assert!(buffer.layer_add("layer3", H2Id::new(1)).is_err());
+        assert!(buffer.layer_add("layer3", H2Id::default()).is_err());
+
+        assert_eq!(H2Id::new(1), buffer.layer_remove("layer1")?);
+        assert!(buffer.layer_get_by_id(H2Id::new(1)).is_none());
+        assert_eq!(None, buffer.layer_name(H2Id::new(1)));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,260 @@
+//! Per-project defaults for building types and displaying values.
+//!
+//! Rather than picking an endian and a formatter for every single integer in
+//! an analysis, types can be built from the project's [`H2Config`] - see
+//! [`H2Config::integer`] and friends.
+//!
+//! Only types built that way use the configured endian and renderer - a type
+//! built with its own renderer (even [`DefaultFormatter::new_integer`]) is
+//! displayed the way it was built. The display limit is different: it's
+//! applied to everything resolved through [`crate::project::H2Project::peek`].
+
+use serde::{Serialize, Deserialize};
+use simple_error::{bail, SimpleResult};
+
+use generic_number::{DefaultFormatter, Endian, IntegerReader, IntegerRenderer};
+use h2datatype::{Alignment, H2Type, H2TypeParser, ResolvedType};
+use h2datatype::simple::numeric::H2Integer;
+
+use crate::project::H2Nesting;
+
+/// Defaults and policies for a project.
+///
+/// All fields are public - to change them, copy the project's config, change
+/// it, and set it back with [`crate::actions::ActionConfigSet`].
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct H2Config {
+    /// The endian used by [`H2Config::integer_endian`]
+    pub endian: Endian,
+
+    /// How integers built by the config (or its type parser) are displayed -
+    /// use
+    /// [`DefaultFormatter::pretty_integer_auto`] to show big values, offsets,
+    /// and sizes in hex without picking a formatter for each one
+    pub integer_renderer: IntegerRenderer,
+
+    /// The longest an entry's display can be (in characters) before it's cut
+    /// off - `None` for no limit (see [`H2Config::truncate`])
+    pub display_limit: Option<usize>,
+
+    /// Whether [`H2Config::alignment`] requires values to start aligned (see
+    /// [`Alignment::Strict`]), or only pads them (see [`Alignment::Loose`])
+    pub strict_alignment: bool,
+
+    /// When alignment isn't strict, whether values that don't start aligned
+    /// resolve with a warning (see [`Alignment::Warn`])
+    #[serde(default)]
+    pub warn_alignment: bool,
+
+    /// How far new entries are shown as a tree, when the action that creates
+    /// them doesn't say (see [`crate::actions::ActionEntryCreate`])
+    #[serde(default)]
+    pub nesting: H2Nesting,
+}
+
+impl Default for H2Config {
+    fn default() -> Self {
+        Self {
+            endian: Endian::Little,
+            integer_renderer: DefaultFormatter::new_integer(),
+            display_limit: None,
+            strict_alignment: false,
+            warn_alignment: false,
+            nesting: H2Nesting::flat(),
+        }
+    }
+}
+
+impl H2Config {
+    /// Build an integer type that's displayed with the configured renderer.
+    pub fn integer(&self, reader: IntegerReader) -> H2Type {
+        H2Integer::new(reader, self.integer_renderer)
+    }
+
+    /// Build an integer type with the configured endian and renderer.
+    ///
+    /// The reader is given without an endian, like
+    /// `config.integer_endian(IntegerReader::U32)`.
+    pub fn integer_endian(&self, reader: fn(Endian) -> IntegerReader) -> H2Type {
+        self.integer(reader(self.endian))
+    }
+
+    /// Build an integer type that's an offset or a size, displayed with the
+    /// configured renderer (see [`IntegerRenderer::render_offset`]).
+    pub fn offset(&self, reader: IntegerReader) -> H2Type {
+        H2Integer::new_offset(reader, self.integer_renderer)
+    }
+
+    /// Build an offset or size type with the configured endian and renderer.
+    pub fn offset_endian(&self, reader: fn(Endian) -> IntegerReader) -> H2Type {
+        self.offset(reader(self.endian))
+    }
+
+    /// Get a parser for type expressions (like `u32` or `lpstr(u8, ascii)`)
+    /// that fills in the configured endian and renderer.
+    pub fn type_parser(&self) -> H2TypeParser {
+        H2TypeParser::new(self.endian, self.integer_renderer)
+    }
+
+    /// Get an [`Alignment`] to `multiple` bytes, following the configured
+    /// policy.
+    pub fn alignment(&self, multiple: u64) -> SimpleResult<Alignment> {
+        if multiple == 0 {
+            bail!("Can't align to a multiple of 0");
+        }
+
+        match (self.strict_alignment, self.warn_alignment) {
+            (true, _)      => Ok(Alignment::Strict(multiple)),
+            (false, true)  => Ok(Alignment::Warn(multiple)),
+            (false, false) => Ok(Alignment::Loose(multiple)),
+        }
+    }
+
+    /// Cut the displays in `resolved` (and its children) down to the
+    /// configured limit.
+    ///
+    /// Cut displays end with `...`, which counts toward the limit - below 3
+    /// characters, there's only room for part of it.
+    pub fn truncate(&self, resolved: &mut ResolvedType) {
+        let limit = match self.display_limit {
+            Some(limit) => limit,
+            None        => return,
+        };
+
+        if resolved.display.chars().count() > limit {
+            let dots = limit.min(3);
+            resolved.display = format!("{}{}", resolved.display.chars().take(limit - dots).collect::<String>(), ".".repeat(dots));
+        }
+
+        for child in resolved.children.iter_mut() {
+            self.truncate(child);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    use generic_number::{Context, HexFormatter};
+    use h2datatype::Offset;
+    use h2datatype::composite::H2Array;
+
+    #[test]
+    fn test_integer() -> SimpleResult<()> {
+        let data = b"\x01\x02\x03\x04".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let config = H2Config::default();
+        assert_eq!("513", config.integer_endian(IntegerReader::U16).to_display(offset)?);
+        assert_eq!("1", config.integer(IntegerReader::U8).to_display(offset)?);
+
+        let config = H2Config {
+            endian: Endian::Big,
+            integer_renderer: HexFormatter::pretty_integer(),
+            ..Default::default()
+        };
+        assert_eq!("0x0102", config.integer_endian(IntegerReader::U16).to_display(offset)?);
+        assert_eq!("0x01020304", config.integer_endian(IntegerReader::U32).to_display(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_offset() -> SimpleResult<()> {
+        let data = b"\x01\x02\x03\x04".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        // By default, offsets look like any other integer
+        let config = H2Config::default();
+        assert_eq!("513", config.offset_endian(IntegerReader::U16).to_display(offset)?);
+
+        // But with an automatic renderer, they're hex - as are big numbers
+        let config = H2Config {
+            integer_renderer: DefaultFormatter::pretty_integer_auto(),
+            ..Default::default()
+        };
+        assert_eq!("0x201", config.offset_endian(IntegerReader::U16).to_display(offset)?);
+        assert_eq!("513", config.integer_endian(IntegerReader::U16).to_display(offset)?);
+        assert_eq!("0x4030201", config.integer_endian(IntegerReader::U32).to_display(offset)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_parser() -> SimpleResult<()> {
+        let data = b"\x01\x02\x03\x04".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let config = H2Config {
+            endian: Endian::Big,
+            integer_renderer: HexFormatter::pretty_integer(),
+            ..Default::default()
+        };
+        let (t, at) = config.type_parser().parse_at("u16@2")?;
+        assert_eq!("u16be", t.describe());
+        assert_eq!("0x0304", t.to_display(offset.at(at.unwrap()))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_alignment() -> SimpleResult<()> {
+        let data = b"\x01\x02\x03\x04".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+
+        let config = H2Config::default();
+        let t = H2Integer::new_aligned(config.alignment(4)?, IntegerReader::U8, config.integer_renderer);
+        assert_eq!(1..5, t.aligned_range(offset.at(1))?);
+
+        let config = H2Config { strict_alignment: true, ..Default::default() };
+        let t = H2Integer::new_aligned(config.alignment(4)?, IntegerReader::U8, config.integer_renderer);
+        assert!(t.aligned_range(offset.at(1)).is_err());
+        assert_eq!(0..4, t.aligned_range(offset)?);
+
+        assert!(config.alignment(0).is_err());
+
+        // Misaligned values still resolve, but with a warning
+        let config = H2Config { warn_alignment: true, ..Default::default() };
+        let t = H2Integer::new_aligned(config.alignment(4)?, IntegerReader::U8, config.integer_renderer);
+        assert_eq!(1..5, t.aligned_range(offset.at(1))?);
+        assert_eq!(1, t.resolve(offset.at(1), None)?.warnings.len());
+        assert_eq!(0, t.resolve(offset, None)?.warnings.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncate() -> SimpleResult<()> {
+        let data = b"ABCDEFGH".to_vec();
+        let offset = Offset::Dynamic(Context::new(&data));
+        let t = H2Array::new(4, H2Integer::new(IntegerReader::U16(Endian::Big), HexFormatter::pretty_integer()))?;
+
+        let truncated = |limit: Option<usize>| -> SimpleResult<ResolvedType> {
+            let mut resolved = t.resolve(offset, None)?;
+            H2Config { display_limit: limit, ..Default::default() }.truncate(&mut resolved);
+
+            Ok(resolved)
+        };
+
+        let resolved = truncated(None)?;
+        assert_eq!("[ 0x4142, 0x4344, 0x4546, 0x4748 ]", resolved.display);
+
+        // The children are cut separately
+        let resolved = truncated(Some(8))?;
+        assert_eq!("[ 0x4...", resolved.display);
+        assert_eq!("0x4142", resolved.children[0].display);
+
+        let resolved = truncated(Some(5))?;
+        assert_eq!("[ ...", resolved.display);
+        assert_eq!("0x...", resolved.children[0].display);
+
+        // The limit holds even when it's shorter than the "..."
+        assert_eq!("..", truncated(Some(2))?.display);
+        assert_eq!("", truncated(Some(0))?.display);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,398 @@
+//! Project-specific data, layered over the shared data in [`h2data`].
+//!
+//! The enums in [`h2data`] are shared by every project, and never change.
+//! While reversing a format, though, it's handy to define new enums, and to
+//! name values as they're figured out. An [`H2DataOverlay`] holds both, and
+//! since it's saved with the project, the project doesn't depend on anybody
+//! else having the same enums.
+//!
+//! Lookups go through the overlay in this order:
+//!
+//! 1. A change to the value (added, renamed, or removed), if there is one
+//! 2. The project's own enum, if it defines one with that name
+//! 3. The shared enum in [`h2data`]
+//!
+//! A project enum replaces a shared enum with the same name completely - the
+//! shared one's values aren't merged in. Changes are kept separately, so they
+//! apply on top of whichever one is in use.
+//!
+//! The overlay also holds the project's variables - named numbers, like a
+//! format's version, that an analyzer works out and that
+//! [`h2datatype::composite::H2Switch`] types pick their case with.
+//!
+//! Like the rest of the project, this should only be changed by actions, so
+//! the changes can be undone.
+
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use generic_number::IntegerReader;
+use h2datatype::{H2Type, ResolvedType};
+use h2datatype::composite::H2Variables;
+use h2datatype::simple::H2Enum;
+
+/// A single change to one value of an enum.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum H2EnumChange {
+    /// The value has this name in the project, whether or not the enum
+    /// defines it
+    Named(String),
+
+    /// The enum defines the value, but the project removed it
+    Removed,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct H2DataOverlay {
+    // Enums defined by the project, which take precedence over h2data
+    #[serde(default)]
+    enums: BTreeMap<String, BTreeMap<usize, String>>,
+
+    // Enum name -> value -> change; enums with no changes are removed
+    changes: BTreeMap<String, BTreeMap<usize, H2EnumChange>>,
+
+    // Variables that switches in types depend on
+    #[serde(default)]
+    variables: H2Variables,
+}
+
+impl H2DataOverlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Does `enum_name` exist, either in the project or in [`h2data`]?
+    pub fn enum_exists(&self, enum_name: &str) -> bool {
+        self.enums.contains_key(enum_name) || h2data::enum_exists(enum_name)
+    }
+
+    /// Get the names of every enum - the project's and [`h2data`]'s - in
+    /// alphabetical order.
+    pub fn enum_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = h2data::enum_names();
+        names.extend(self.enums.keys().map(|name| &name[..]).filter(|name| !h2data::enum_exists(name)));
+        names.sort();
+
+        names
+    }
+
+    /// Is `enum_name` defined by the project (as opposed to [`h2data`])?
+    pub fn enum_is_local(&self, enum_name: &str) -> bool {
+        self.enums.contains_key(enum_name)
+    }
+
+    /// Get the name of `value` before any changes are applied.
+    fn enum_base(&self, enum_name: &str, value: usize) -> SimpleResult<Option<String>> {
+        match self.enums.get(enum_name) {
+            Some(values) => Ok(values.get(&value).cloned()),
+            None         => Ok(h2data::from_enum(enum_name, value)?.map(|s| s.to_string())),
+        }
+    }
+
+    /// Get the name of `value` in `enum_name`, with any changes applied.
+    pub fn get(&self, enum_name: &str, value: usize) -> SimpleResult<Option<String>> {
+        if !self.enum_exists(enum_name) {
+            bail!("No such enum: {}", enum_name);
+        }
+
+        match self.change_get(enum_name, value) {
+            Some(H2EnumChange::Named(name)) => Ok(Some(name.clone())),
+            Some(H2EnumChange::Removed)     => Ok(None),
+            None                            => self.enum_base(enum_name, value),
+        }
+    }
+
+    /// Get the number of named values in `enum_name`, with any changes
+    /// applied.
+    pub fn enum_size(&self, enum_name: &str) -> SimpleResult<usize> {
+        let mut size = match self.enums.get(enum_name) {
+            Some(values) => values.len(),
+            None         => h2data::enum_size(enum_name)?,
+        };
+
+        for (value, change) in self.changes_get(enum_name) {
+            match (change, self.enum_base(enum_name, value)?) {
+                (H2EnumChange::Named(_), None)   => size += 1,
+                (H2EnumChange::Removed, Some(_)) => size -= 1,
+                _                                => (),
+            }
+        }
+
+        Ok(size)
+    }
+
+    /// Render `value` the same way [`H2Enum`] does, but with any changes
+    /// applied.
+    pub fn render(&self, enum_name: &str, value: usize) -> SimpleResult<String> {
+        let output = match self.get(enum_name, value)? {
+            Some(o) => o,
+            None    => format!("Unknown_0x{:x}", value),
+        };
+
+        Ok(format!("{}::{}", enum_name, output))
+    }
+
+    /// Create an [`H2Enum`] for `enum_name`, which can be defined by the
+    /// project or by [`h2data`].
+    ///
+    /// Values from a project enum are only shown properly once the resolved
+    /// type goes through [`H2DataOverlay::rerender`] - use
+    /// [`crate::project::H2Project::peek`] to do both at once.
+    pub fn enum_type(&self, reader: IntegerReader, enum_name: &str) -> SimpleResult<H2Type> {
+        if !self.enum_exists(enum_name) {
+            bail!("No such enum: {}", enum_name);
+        }
+
+        H2Enum::new_unchecked(reader, enum_name)
+    }
+
+    /// Define an enum in the project, replacing any earlier one with the same
+    /// name. Returns the earlier one, if there was one.
+    pub fn enum_define(&mut self, enum_name: &str, values: BTreeMap<usize, String>) -> SimpleResult<Option<BTreeMap<usize, String>>> {
+        if enum_name == "" || enum_name.contains("::") {
+            bail!("Invalid enum name: '{}'", enum_name);
+        }
+
+        if values.values().any(|name| name == "") {
+            bail!("Enum values can't have an empty name");
+        }
+
+        Ok(self.enums.insert(enum_name.to_string(), values))
+    }
+
+    /// Remove an enum defined by the project, and return its values.
+    ///
+    /// Any changes made to it are kept, in case it's defined again.
+    pub fn enum_undefine(&mut self, enum_name: &str) -> SimpleResult<BTreeMap<usize, String>> {
+        self.enums.remove(enum_name).ok_or(
+            SimpleError::new(format!("No such project enum: {}", enum_name))
+        )
+    }
+
+    /// Get the change made to `value`, if there is one.
+    pub fn change_get(&self, enum_name: &str, value: usize) -> Option<&H2EnumChange> {
+        self.changes.get(enum_name).and_then(|changes| changes.get(&value))
+    }
+
+    /// Get every change made to `enum_name`, sorted by value.
+    pub fn changes_get(&self, enum_name: &str) -> Vec<(usize, &H2EnumChange)> {
+        match self.changes.get(enum_name) {
+            Some(changes) => changes.iter().map(|(value, change)| (*value, change)).collect(),
+            None          => vec![],
+        }
+    }
+
+    /// Give `value` a new name, or no name at all.
+    ///
+    /// Returns the change that was there before, which can be put back with
+    /// [`H2DataOverlay::change_set`]. If the new name is the same as the
+    /// enum's own name for the value, the change is dropped.
+    pub fn set(&mut self, enum_name: &str, value: usize, name: Option<&str>) -> SimpleResult<Option<H2EnumChange>> {
+        if name == Some("") {
+            bail!("Enum values can't have an empty name");
+        }
+
+        if !self.enum_exists(enum_name) {
+            bail!("No such enum: {}", enum_name);
+        }
+
+        let base = self.enum_base(enum_name, value)?;
+
+        let change = match (name, base) {
+            (Some(name), Some(base)) if name == base => None,
+            (Some(name), _)                          => Some(H2EnumChange::Named(name.to_string())),
+            (None,       Some(_))                    => Some(H2EnumChange::Removed),
+            (None,       None)                       => None,
+        };
+
+        Ok(self.change_set(enum_name, value, change))
+    }
+
+    /// Replace the change made to `value` (or remove it, with `None`), and
+    /// return the old one.
+    pub fn change_set(&mut self, enum_name: &str, value: usize, change: Option<H2EnumChange>) -> Option<H2EnumChange> {
+        let changes = self.changes.entry(enum_name.to_string()).or_default();
+
+        let old = match change {
+            Some(change) => changes.insert(value, change),
+            None         => changes.remove(&value),
+        };
+
+        if changes.is_empty() {
+            self.changes.remove(enum_name);
+        }
+
+        old
+    }
+
+    /// Update every value from `enum_name` in `resolved` (and its children)
+    /// to match the overlay.
+    pub fn rerender_enum(&self, resolved: &mut ResolvedType, enum_name: &str) {
+        if let Some((name, value)) = enum_value(resolved) {
+            if name == enum_name {
+                // If the enum is gone, display it the same way H2Enum does
+                let display = self.render(enum_name, value).unwrap_or_else(|_| {
+                    format!("{}::Unknown_0x{:x}", enum_name, value)
+                });
+
+                resolved.display = display.clone();
+                resolved.as_string = Some(display);
+            }
+        }
+
+        for child in resolved.children.iter_mut() {
+            self.rerender_enum(child, enum_name);
+        }
+    }
+
+    /// Update every enum value in `resolved` (and its children) that the
+    /// overlay changes.
+    pub fn rerender(&self, resolved: &mut ResolvedType) {
+        if let Some((name, value)) = enum_value(resolved) {
+            if self.enums.contains_key(&name) || self.changes.contains_key(&name) {
+                if let Ok(display) = self.render(&name, value) {
+                    resolved.display = display.clone();
+                    resolved.as_string = Some(display);
+                }
+            }
+        }
+
+        for child in resolved.children.iter_mut() {
+            self.rerender(child);
+        }
+    }
+
+    /// Get every variable, for binding types (see [`H2Type::bind`]).
+    pub fn variables(&self) -> &H2Variables {
+        &self.variables
+    }
+
+    pub fn variable_get(&self, name: &str) -> Option<u64> {
+        self.variables.get(name).copied()
+    }
+
+    /// Set a variable, or remove it with `None`, and return the old value.
+    pub fn variable_set(&mut self, name: &str, value: Option<u64>) -> SimpleResult<Option<u64>> {
+        if name == "" {
+            bail!("Variables can't have an empty name");
+        }
+
+        Ok(match value {
+            Some(value) => self.variables.insert(name.to_string(), value),
+            None        => self.variables.remove(name),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.enums.is_empty() && self.changes.is_empty() && self.variables.is_empty()
+    }
+}
+
+/// If `resolved` came from an [`H2Enum`], get the enum's name and the value.
+fn enum_value(resolved: &ResolvedType) -> Option<(String, usize)> {
+    let value = resolved.as_integer.and_then(|i| i.as_usize().ok())?;
+    let name = resolved.enum_name.clone()?;
+
+    Some((name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+
+    #[test]
+    fn test_changes() -> SimpleResult<()> {
+        let mut overlay = H2DataOverlay::new();
+        assert_eq!(Some("Classic".to_string()), overlay.get("TerrariaGameMode", 0)?);
+        assert_eq!("TerrariaGameMode::Unknown_0x20", overlay.render("TerrariaGameMode", 0x20)?);
+
+        // Add, rename, and remove
+        assert_eq!(None, overlay.set("TerrariaGameMode", 0x20, Some("Secret"))?);
+        assert_eq!(None, overlay.set("TerrariaGameMode", 0, Some("Normal"))?);
+        assert_eq!(None, overlay.set("TerrariaGameMode", 1, None)?);
+
+        assert_eq!("TerrariaGameMode::Secret",       overlay.render("TerrariaGameMode", 0x20)?);
+        assert_eq!("TerrariaGameMode::Normal",       overlay.render("TerrariaGameMode", 0)?);
+        assert_eq!("TerrariaGameMode::Unknown_0x1",  overlay.render("TerrariaGameMode", 1)?);
+        assert_eq!("TerrariaGameMode::HardCore",     overlay.render("TerrariaGameMode", 2)?);
+        assert_eq!(3, overlay.changes_get("TerrariaGameMode").len());
+        assert_eq!(h2data::enum_size("TerrariaGameMode")?, overlay.enum_size("TerrariaGameMode")?);
+
+        // Putting back the original name drops the change
+        assert_eq!(Some(H2EnumChange::Named("Normal".to_string())), overlay.set("TerrariaGameMode", 0, Some("Classic"))?);
+        assert_eq!(None, overlay.change_get("TerrariaGameMode", 0));
+
+        // And so does removing an added value
+        overlay.set("TerrariaGameMode", 0x20, None)?;
+        overlay.change_set("TerrariaGameMode", 1, None);
+        assert!(overlay.is_empty());
+
+        assert!(overlay.set("NotAnEnum", 0, Some("Hi")).is_err());
+        assert!(overlay.set("TerrariaGameMode", 0, Some("")).is_err());
+        assert!(overlay.get("NotAnEnum", 0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_project_enums() -> SimpleResult<()> {
+        let mut overlay = H2DataOverlay::new();
+
+        let mut values = BTreeMap::new();
+        values.insert(1, "One".to_string());
+        values.insert(2, "Two".to_string());
+        assert_eq!(None, overlay.enum_define("Numbers", values.clone())?);
+
+        assert!(overlay.enum_exists("Numbers"));
+        assert!(overlay.enum_is_local("Numbers"));
+        assert!(overlay.enum_names().contains(&"Numbers"));
+        assert!(overlay.enum_names().contains(&"TerrariaGameMode"));
+        assert_eq!(Some("Two".to_string()), overlay.get("Numbers", 2)?);
+        assert_eq!(2, overlay.enum_size("Numbers")?);
+
+        // Changes go on top
+        overlay.set("Numbers", 3, Some("Three"))?;
+        assert_eq!(3, overlay.enum_size("Numbers")?);
+
+        // A project enum replaces a shared one completely
+        let mut modes = BTreeMap::new();
+        modes.insert(0, "Easy".to_string());
+        overlay.enum_define("TerrariaGameMode", modes)?;
+        assert_eq!(Some("Easy".to_string()), overlay.get("TerrariaGameMode", 0)?);
+        assert_eq!(None, overlay.get("TerrariaGameMode", 1)?);
+        assert_eq!(1, overlay.enum_names().iter().filter(|name| **name == "TerrariaGameMode").count());
+
+        overlay.enum_undefine("TerrariaGameMode")?;
+        assert_eq!(Some("MediumCore".to_string()), overlay.get("TerrariaGameMode", 1)?);
+
+        assert_eq!(values, overlay.enum_undefine("Numbers")?);
+        assert!(!overlay.enum_exists("Numbers"));
+        assert!(overlay.enum_undefine("Numbers").is_err());
+        assert!(overlay.enum_define("", BTreeMap::new()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_variables() -> SimpleResult<()> {
+        let mut overlay = H2DataOverlay::new();
+        assert_eq!(None, overlay.variable_get("format_version"));
+
+        assert_eq!(None, overlay.variable_set("format_version", Some(230))?);
+        assert_eq!(Some(230), overlay.variable_get("format_version"));
+        assert!(!overlay.is_empty());
+
+        assert_eq!(Some(230), overlay.variable_set("format_version", Some(279))?);
+        assert_eq!(Some(279), overlay.variable_set("format_version", None)?);
+        assert_eq!(None, overlay.variable_get("format_version"));
+        assert!(overlay.is_empty());
+
+        assert!(overlay.variable_set("", Some(1)).is_err());
+
+        Ok(())
+    }
+}
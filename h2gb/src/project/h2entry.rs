@@ -4,16 +4,68 @@
 //! quite ready for detailed comments just yet. :)
 
 use serde::{Serialize, Deserialize};
+use simple_error::{bail, SimpleResult};
 use std::fmt;
 use std::ops::Range;
 
 use bumpy_vector::AutoBumpyEntry;
 use h2datatype::{H2Type, ResolvedType};
 
+use crate::project::{H2Creator, H2Id, H2Provenance};
+
+/// The name of an entry's original interpretation - see
+/// [`H2Entry::interpretation_name`].
+pub const DEFAULT_INTERPRETATION: &str = "default";
+
+/// Another way of looking at the bytes of an entry - see
+/// [`H2Entry::interpretations`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct H2Interpretation {
+    name: String,
+    resolved_type: ResolvedType,
+    origin: Option<H2Type>,
+}
+
+impl H2Interpretation {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn resolved(&self) -> &ResolvedType {
+        &self.resolved_type
+    }
+
+    pub fn origin(&self) -> &Option<H2Type> {
+        &self.origin
+    }
+
+    pub fn split_up(self) -> (ResolvedType, Option<H2Type>) {
+        (self.resolved_type, self.origin)
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct H2Entry {
     resolved_type: ResolvedType,
     origin: Option<H2Type>,
+
+    #[serde(default)]
+    id: H2Id,
+
+    #[serde(default)]
+    provenance: H2Provenance,
+
+    // The name of the interpretation that's being shown (None is the
+    // default), and the others that can be shown instead
+    #[serde(default)]
+    interpretation: Option<String>,
+    #[serde(default)]
+    interpretations: Vec<H2Interpretation>,
+
+    // How far the entry's fields are shown as entries of their own - see
+    // H2Entry::children()
+    #[serde(default)]
+    nesting: H2Nesting,
 }
 
 impl fmt::Display for H2Entry {
@@ -30,13 +82,22 @@ impl AutoBumpyEntry for H2Entry {
 }
 
 impl H2Entry {
-    pub fn new(resolved_type: ResolvedType, origin: Option<H2Type>) -> Self {
+    pub fn new(resolved_type: ResolvedType, origin: Option<H2Type>, id: H2Id, provenance: H2Provenance) -> Self {
         Self {
             resolved_type: resolved_type,
             origin: origin,
+            id: id,
+            provenance: provenance,
+            interpretation: None,
+            interpretations: vec![],
+            nesting: H2Nesting::flat(),
         }
     }
 
+    pub fn id(&self) -> H2Id {
+        self.id
+    }
+
     pub fn resolved(&self) -> &ResolvedType {
         &self.resolved_type
     }
@@ -45,9 +106,297 @@ impl H2Entry {
         &self.origin
     }
 
+    /// Who created the entry, and when.
+    pub fn provenance(&self) -> &H2Provenance {
+        &self.provenance
+    }
+
     pub fn split_up(self) -> (ResolvedType, Option<H2Type>) {
         (self.resolved_type, self.origin)
     }
+
+    /// How far the entry is shown as a tree - see [`H2Entry::children`].
+    pub fn nesting(&self) -> H2Nesting {
+        self.nesting
+    }
+
+    /// Is the entry shown as a tree (even if it has no fields to show)?
+    pub fn is_nested(&self) -> bool {
+        self.nesting.depth > 0
+    }
+
+    pub(crate) fn set_nesting(&mut self, nesting: H2Nesting) {
+        self.nesting = nesting;
+    }
+
+    /// The fields of a nested entry (like the members of a struct), as
+    /// entries of their own, so they can be expanded and collapsed in a
+    /// listing. Which fields are shown depends on the entry's
+    /// [`H2Nesting`]; entries that aren't nested, and types without fields,
+    /// don't have any.
+    ///
+    /// The children are made from the interpretation that's being shown, so
+    /// they're always up to date, and they go away along with the entry.
+    /// They're nested one level less than the entry. They don't have IDs,
+    /// origins, or interpretations of their own - they're only a way of
+    /// looking at the entry.
+    ///
+    /// How far an entry is nested is picked when it's created - see
+    /// [`crate::actions::ActionEntryCreate::new_nested`].
+    pub fn children(&self) -> Vec<H2Entry> {
+        if !self.nesting.expands(&self.resolved_type) {
+            return vec![];
+        }
+
+        self.resolved_type.children.iter().map(|child| {
+            let mut entry = H2Entry::new(child.clone(), None, H2Id::default(), self.provenance.clone());
+            entry.nesting = self.nesting.below();
+
+            entry
+        }).collect()
+    }
+
+    /// The name of the interpretation that [`H2Entry::resolved`] and
+    /// [`H2Entry::origin`] are showing - [`DEFAULT_INTERPRETATION`], unless
+    /// another one has been selected.
+    pub fn interpretation_name(&self) -> &str {
+        self.interpretation.as_deref().unwrap_or(DEFAULT_INTERPRETATION)
+    }
+
+    /// The other interpretations of the entry, which aren't being shown.
+    ///
+    /// Sometimes the same bytes legitimately mean two things - a header that
+    /// could be either of two versions, or ciphertext that can also be viewed
+    /// decrypted. Rather than putting each view in its own layer, an entry
+    /// can carry them all, and switch between them (see
+    /// [`crate::actions::ActionEntryInterpret`] and
+    /// [`crate::actions::ActionEntrySelectInterpretation`]). Each one has a
+    /// name, and they all start at the same offset and fit in the same space
+    /// as the entry.
+    pub fn interpretations(&self) -> &[H2Interpretation] {
+        &self.interpretations
+    }
+
+    pub fn interpretation_exists(&self, name: &str) -> bool {
+        self.interpretation_name() == name || self.interpretations.iter().any(|i| i.name == name)
+    }
+
+    /// Add another interpretation.
+    ///
+    /// # Errors
+    ///
+    /// * The name can't be empty or already used
+    /// * It has to start where the entry starts, and end before the entry
+    ///   (including its alignment) ends
+    pub(crate) fn interpretation_add(&mut self, name: &str, mut resolved_type: ResolvedType, origin: Option<H2Type>) -> SimpleResult<()> {
+        if name.is_empty() {
+            bail!("Interpretation must have a name");
+        }
+
+        if self.interpretation_exists(name) {
+            bail!("Entry already has an interpretation called {}", name);
+        }
+
+        let slot = &self.resolved_type.aligned_range;
+        if resolved_type.actual_range.start != slot.start || resolved_type.actual_range.end > slot.end {
+            bail!("Interpretation {} (0x{:x?}) doesn't fit in the entry (0x{:x?})", name, resolved_type.actual_range, slot);
+        }
+
+        // It takes up the same space, no matter how it's aligned
+        resolved_type.aligned_range = slot.clone();
+
+        self.interpretations.push(H2Interpretation {
+            name: name.to_string(),
+            resolved_type: resolved_type,
+            origin: origin,
+        });
+
+        Ok(())
+    }
+
+    /// Remove an interpretation that isn't being shown.
+    pub(crate) fn interpretation_remove(&mut self, name: &str) -> SimpleResult<H2Interpretation> {
+        match self.interpretations.iter().position(|i| i.name == name) {
+            Some(index) => Ok(self.interpretations.remove(index)),
+            None if self.interpretation_name() == name => bail!("Can't remove interpretation {}: it's being shown", name),
+            None => bail!("Entry has no interpretation called {}", name),
+        }
+    }
+
+    /// Show a different interpretation, and return the name of the one that
+    /// was being shown.
+    pub(crate) fn interpretation_select(&mut self, name: &str) -> SimpleResult<String> {
+        let previous = self.interpretation_name().to_string();
+        if previous == name {
+            return Ok(previous);
+        }
+
+        let selected = self.interpretation_remove(name)?;
+
+        self.interpretations.push(H2Interpretation {
+            name: previous.clone(),
+            resolved_type: std::mem::replace(&mut self.resolved_type, selected.resolved_type),
+            origin: std::mem::replace(&mut self.origin, selected.origin),
+        });
+        self.interpretation = match selected.name == DEFAULT_INTERPRETATION {
+            true  => None,
+            false => Some(selected.name),
+        };
+
+        Ok(previous)
+    }
+
+    /// Make a copy of the entry with every interpretation resolved again by
+    /// `resolve` - after the bytes under it change, say.
+    ///
+    /// The ID, and which interpretation is shown, stay the same;
+    /// interpretations that don't know their type are kept as they are.
+    pub(crate) fn resolve_again(&self, provenance: H2Provenance, resolve: impl Fn(&H2Type) -> SimpleResult<ResolvedType>) -> SimpleResult<H2Entry> {
+        let resolved = match &self.origin {
+            Some(origin) => resolve(origin)?,
+            None         => self.resolved_type.clone(),
+        };
+
+        let mut out = H2Entry::new(resolved, self.origin.clone(), self.id, provenance);
+        out.interpretation = self.interpretation.clone();
+        out.nesting = self.nesting;
+
+        for interpretation in &self.interpretations {
+            let resolved = match &interpretation.origin {
+                Some(origin) => resolve(origin)?,
+                None         => interpretation.resolved_type.clone(),
+            };
+
+            out.interpretation_add(&interpretation.name, resolved, interpretation.origin.clone())?;
+        }
+
+        Ok(out)
+    }
+
+    /// Every view of the entry - the one that's shown, then the other
+    /// interpretations - as `(origin, resolved)`, so they can all be changed
+    /// together.
+    pub(crate) fn views_mut(&mut self) -> Vec<(Option<&H2Type>, &mut ResolvedType)> {
+        let mut out = vec![(self.origin.as_ref(), &mut self.resolved_type)];
+        out.extend(self.interpretations.iter_mut().map(|i| (i.origin.as_ref(), &mut i.resolved_type)));
+
+        out
+    }
+}
+
+/// How far an entry's fields are shown as entries of their own - see
+/// [`H2Entry::children`].
+///
+/// Each level of fields is one level of depth: at depth 1, a struct's fields
+/// are shown, but not the fields of a struct inside it. Arrays can be kept
+/// compact, as one value, no matter the depth (an array is a type whose
+/// fields don't have names - see [`ResolvedType::field_name`]).
+///
+/// The default is flat - an entry is one value, with no children. A project
+/// can have a different default (see
+/// [`crate::project::H2Config::nesting`]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct H2Nesting {
+    /// How many levels of fields are shown (0 for none)
+    pub depth: usize,
+
+    /// Whether arrays are expanded into their elements
+    pub arrays: bool,
+}
+
+impl H2Nesting {
+    /// Don't show any fields.
+    pub fn flat() -> Self {
+        Self::default()
+    }
+
+    /// Show every field, all the way down.
+    pub fn full() -> Self {
+        Self {
+            depth: usize::MAX,
+            arrays: true,
+        }
+    }
+
+    /// Show `depth` levels of fields; arrays are only expanded if `arrays`
+    /// is set.
+    pub fn new(depth: usize, arrays: bool) -> Self {
+        Self {
+            depth: depth,
+            arrays: arrays,
+        }
+    }
+
+    /// Are the fields of `resolved` shown?
+    pub fn expands(&self, resolved: &ResolvedType) -> bool {
+        if self.depth == 0 || resolved.children.is_empty() {
+            return false;
+        }
+
+        self.arrays || resolved.children.iter().any(|child| child.field_name.is_some())
+    }
+
+    /// The nesting of a field, one level down.
+    fn below(&self) -> Self {
+        Self {
+            depth: self.depth - 1,
+            arrays: self.arrays,
+        }
+    }
+}
+
+/// Which entries to pick out of a buffer - see
+/// [`crate::project::H2Buffer::entries_matching`].
+///
+/// Every condition that's set has to match; an empty filter matches
+/// everything.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct H2EntryFilter {
+    /// Only entries in this layer
+    pub layer: Option<String>,
+
+    /// Only entries made by this creator
+    pub creator: Option<H2Creator>,
+
+    /// Only entries whose type has this name, like `H2Integer` (see
+    /// [`h2datatype::H2Types::type_name`]) - entries that don't know their
+    /// type never match
+    pub type_name: Option<String>,
+
+    /// Only entries that are entirely inside this range
+    pub range: Option<Range<usize>>,
+}
+
+impl H2EntryFilter {
+    pub fn matches(&self, layer: &str, entry: &H2Entry) -> bool {
+        if let Some(l) = &self.layer {
+            if l != layer {
+                return false;
+            }
+        }
+
+        if let Some(creator) = &self.creator {
+            if entry.provenance().creator() != creator {
+                return false;
+            }
+        }
+
+        if let Some(type_name) = &self.type_name {
+            match &entry.origin {
+                Some(origin) if origin.field.type_name() == type_name => (),
+                _                                                     => return false,
+            }
+        }
+
+        if let Some(range) = &self.range {
+            let entry_range = entry.range();
+            if entry_range.start < range.start || entry_range.end > range.end || (entry_range.is_empty() && entry_range.start >= range.end) {
+                return false;
+            }
+        }
+
+        true
+    }
 }
 
 #[cfg(test)]
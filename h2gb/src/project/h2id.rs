@@ -0,0 +1,39 @@
+//! Stable identifiers for buffers, layers, and entries.
+
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+/// A stable, unique identifier for a buffer, layer, or entry.
+///
+/// Names can change, and the same layer name can be used in more than one
+/// buffer, but an ID is assigned once (by [`crate::project::H2Project::id_allocate`])
+/// and never reused within a project. Actions remember the IDs of what they
+/// create, so undoing and redoing them gets the same IDs back.
+///
+/// IDs are handed out in order, so applying the same actions to the same
+/// project always assigns the same IDs - that's what lets a journal of
+/// actions be replayed safely.
+///
+/// The default ID (zero) means "not assigned yet".
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct H2Id(u64);
+
+impl fmt::Display for H2Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+impl H2Id {
+    pub(crate) fn new(id: u64) -> Self {
+        Self(id)
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+
+    pub fn is_assigned(&self) -> bool {
+        self.0 != 0
+    }
+}
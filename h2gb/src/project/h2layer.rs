@@ -17,14 +17,15 @@
 //! In other words: DON'T USE THESE DIRECTLY, unless you're writing actions.
 
 use std::ops::Range;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use serde::{Serialize, Deserialize};
 use simple_error::{SimpleResult, bail, SimpleError};
 
-use bumpy_vector::BumpyVector;
+use bumpy_vector::{AutoBumpyEntry, BumpyVector};
 use h2datatype::{H2Type, ResolvedType};
-use crate::project::H2Entry;
+use crate::project::{H2AnnotationKind, H2Creator, H2Entry, H2Id, H2Invalidated, H2LargeEntry, H2LayerMemoryUsage, H2LayerStats, H2Provenance, H2Relocation, H2RelocationReport};
+use crate::project::h2memory::entry_size;
 
 /// Hold information for a layer - basically, a bunch of entires in a
 /// [`bumpy_vector::BumpyVector`].
@@ -35,7 +36,22 @@ pub struct H2Layer {
     name: String,
 
     entries: BumpyVector<H2Entry>,
-    comments: HashMap<usize, String>,
+    comments: BTreeMap<usize, String>,
+
+    #[serde(default)]
+    bookmarks: BTreeMap<usize, String>,
+
+    #[serde(default)]
+    id: H2Id,
+
+    // Where each entry starts, by its ID
+    #[serde(default)]
+    entry_ids: BTreeMap<H2Id, usize>,
+
+    // Zero-length entries (like markers) don't fit in a BumpyVector, and
+    // several can sit at the same offset, so they're kept here instead
+    #[serde(default)]
+    points: BTreeMap<usize, Vec<H2Entry>>,
 }
 
 // impl fmt::Display for H2Layer {
@@ -51,11 +67,15 @@ pub struct H2Layer {
 // }
 
 impl H2Layer {
-    pub fn new(name: &str, size: usize) -> Self {
+    pub fn new(name: &str, id: H2Id, size: usize) -> Self {
         H2Layer {
             name: name.to_string(),
             entries: BumpyVector::new(size),
-            comments: HashMap::new(),
+            comments: BTreeMap::new(),
+            bookmarks: BTreeMap::new(),
+            id: id,
+            entry_ids: BTreeMap::new(),
+            points: BTreeMap::new(),
         }
     }
 
@@ -63,8 +83,40 @@ impl H2Layer {
         &self.name
     }
 
-    pub fn entry_create(&mut self, resolved_type: ResolvedType, origin: Option<H2Type>) -> SimpleResult<()> {
-        self.entries.insert_auto(H2Entry::new(resolved_type, origin))
+    pub fn id(&self) -> H2Id {
+        self.id
+    }
+
+    /// Create an entry, with an ID from [`crate::project::H2Project::id_allocate`].
+    ///
+    /// Zero-length entries are stored as points - see [`H2Layer::point_get`].
+    pub fn entry_create(&mut self, resolved_type: ResolvedType, origin: Option<H2Type>, id: H2Id, provenance: H2Provenance) -> SimpleResult<()> {
+        self.entry_insert(H2Entry::new(resolved_type, origin, id, provenance))
+    }
+
+    /// Put back an entry that was removed, exactly as it was (including its
+    /// interpretations - see [`H2Entry::interpretations`]).
+    pub fn entry_insert(&mut self, entry: H2Entry) -> SimpleResult<()> {
+        let id = entry.id();
+        if !id.is_assigned() || self.entry_ids.contains_key(&id) {
+            bail!("Invalid entry ID: {}", id);
+        }
+
+        let start = entry.range().start;
+
+        if entry.range().is_empty() {
+            // A point can go right at the end, but not past it
+            if start > self.entries.max_size() {
+                bail!("Tried to create point at illegal offset {}", start);
+            }
+
+            self.points.entry(start).or_insert_with(Vec::new).push(entry);
+        } else {
+            self.entries.insert_auto(entry)?;
+        }
+        self.entry_ids.insert(id, start);
+
+        Ok(())
     }
 
     pub fn entry_remove(&mut self, offset: usize) -> SimpleResult<Option<(ResolvedType, Option<H2Type>)>> {
@@ -73,6 +125,7 @@ impl H2Layer {
         }
 
         Ok(self.entries.remove(offset).map(|entry| {
+            self.entry_ids.remove(&entry.entry.id());
             entry.entry.split_up()
         }))
     }
@@ -82,7 +135,66 @@ impl H2Layer {
             bail!("Tried to remove entries at illegal range {:?}", range);
         }
 
-        Ok(self.entries.remove_range(range).into_iter().map(|entry| entry.entry.split_up()).collect())
+        let entries = self.entries.remove_range(range);
+        for entry in &entries {
+            self.entry_ids.remove(&entry.entry.id());
+        }
+
+        Ok(entries.into_iter().map(|entry| entry.entry.split_up()).collect())
+    }
+
+    /// Remove the entry with the given ID, wherever it is.
+    pub fn entry_remove_by_id(&mut self, id: H2Id) -> SimpleResult<H2Entry> {
+        let offset = match self.entry_ids.remove(&id) {
+            Some(offset) => offset,
+            None         => bail!("No entry with ID {} in layer {}", id, self.name),
+        };
+
+        if let Some(points) = self.points.get_mut(&offset) {
+            if let Some(index) = points.iter().position(|point| point.id() == id) {
+                let point = points.remove(index);
+                if points.is_empty() {
+                    self.points.remove(&offset);
+                }
+
+                return Ok(point);
+            }
+        }
+
+        match self.entries.remove(offset) {
+            Some(entry) => Ok(entry.entry),
+            None        => bail!("Entry with ID {} wasn't where we expected it", id),
+        }
+    }
+
+    pub fn entry_get_by_id(&self, id: H2Id) -> Option<&H2Entry> {
+        let offset = self.entry_ids.get(&id)?;
+
+        if let Some(point) = self.points.get(offset).and_then(|points| points.iter().find(|point| point.id() == id)) {
+            return Some(point);
+        }
+
+        self.entries.get(*offset).map(|entry| &entry.entry)
+    }
+
+    pub fn entry_get_mut_by_id(&mut self, id: H2Id) -> Option<&mut H2Entry> {
+        let offset = *self.entry_ids.get(&id)?;
+
+        if let Some(points) = self.points.get_mut(&offset) {
+            if let Some(index) = points.iter().position(|point| point.id() == id) {
+                return Some(&mut points[index]);
+            }
+        }
+
+        self.entries.get_mut(offset).map(|entry| &mut entry.entry)
+    }
+
+    pub fn entry_get_mut_by_id_or_err(&mut self, id: H2Id) -> SimpleResult<&mut H2Entry> {
+        let name = self.name.clone();
+
+        self.entry_get_mut_by_id(id).ok_or(
+            SimpleError::new(format!("No entry with ID {} in layer {}", id, name))
+        )
     }
 
     pub fn entry_get(&self, offset: usize) -> SimpleResult<Option<H2Entry>> {
@@ -114,23 +226,279 @@ impl H2Layer {
     }
 
     pub fn entries_get(&self, range: Range<usize>) -> SimpleResult<Vec<&H2Entry>> {
-        if range.is_empty() || range.end >= self.entries.max_size() {
+        if range.is_empty() || range.end > self.entries.max_size() {
             bail!("Tried to get entries at illegal range {:?}", range);
         }
 
         Ok(self.entries.get_range(range).into_iter().map(|entry| &entry.entry).collect())
     }
 
+    /// Call `f` on every entry in the layer, in order.
+    pub(crate) fn entries_update(&mut self, mut f: impl FnMut(&mut H2Entry)) {
+        let starts: Vec<usize> = self.entries.iter().map(|entry| entry.range.start).collect();
+
+        for start in starts {
+            if let Some(entry) = self.entries.get_mut(start) {
+                f(&mut entry.entry);
+            }
+        }
+
+        for point in self.points.values_mut().flatten() {
+            f(point);
+        }
+    }
+
+    /// Get the zero-length entries (points) at an offset.
+    ///
+    /// Points don't take up any bytes, so they're never returned by
+    /// [`H2Layer::entry_get`] or [`H2Layer::entries_get`], and never get in
+    /// the way of a regular entry. There can be any number of them at the same
+    /// offset, in the order they were created.
+    pub fn point_get(&self, offset: usize) -> SimpleResult<Vec<&H2Entry>> {
+        if offset > self.entries.max_size() {
+            bail!("Tried to get point at illegal offset {}", offset);
+        }
+
+        Ok(self.points.get(&offset).map(|points| points.iter().collect()).unwrap_or_default())
+    }
+
+    /// Get the points in the given range, as `(offset, entry)`, sorted by
+    /// offset.
+    pub fn points_get(&self, range: Range<usize>) -> SimpleResult<Vec<(usize, &H2Entry)>> {
+        if range.end > self.entries.max_size() + 1 {
+            bail!("Tried to get points at illegal range 0x{:x?} (max = 0x{:x?})", range, self.entries.max_size());
+        }
+
+        if range.is_empty() {
+            return Ok(vec![]);
+        }
+
+        Ok(self.points.range(range).flat_map(|(offset, points)| {
+            points.iter().map(move |point| (*offset, point))
+        }).collect())
+    }
+
+    /// Get every entry in the layer, including points, sorted by offset.
+    pub fn entries_all(&self) -> Vec<&H2Entry> {
+        let entries = self.entries.iter().map(|entry| &entry.entry);
+        let points = self.points.values().flatten();
+
+        let mut out: Vec<&H2Entry> = entries.chain(points).collect();
+        out.sort_by_key(|entry| entry.range().start);
+
+        out
+    }
+
+    /// Get every entry (and point) made by `creator`, sorted by offset.
+    pub fn entries_by_creator(&self, creator: &H2Creator) -> Vec<&H2Entry> {
+        self.entries_all().into_iter().filter(|entry| entry.provenance().creator() == creator).collect()
+    }
+
+    /// Remove every point at an offset.
+    ///
+    /// Regular entries are left alone - likewise, [`H2Layer::entry_remove`]
+    /// doesn't remove points. To remove a single point, use
+    /// [`H2Layer::entry_remove_by_id`].
+    pub fn point_remove(&mut self, offset: usize) -> SimpleResult<Vec<(ResolvedType, Option<H2Type>)>> {
+        if offset > self.entries.max_size() {
+            bail!("Tried to remove point at illegal offset {}", offset);
+        }
+
+        let points = self.points.remove(&offset).unwrap_or_default();
+        for point in &points {
+            self.entry_ids.remove(&point.id());
+        }
+
+        Ok(points.into_iter().map(|point| point.split_up()).collect())
+    }
+
     // pub fn entries(&self) -> &BumpyVector<H2Entry> {
     //     &self.entries
     // }
 
+    /// Approximately how much memory the layer is using.
+    pub fn memory_usage(&self) -> H2LayerMemoryUsage {
+        let entries: Vec<&H2Entry> = self.entries.iter().map(|entry| &entry.entry).chain(self.points.values().flatten()).collect();
+
+        let annotations = self.comments.values().chain(self.bookmarks.values()).map(|s| {
+            std::mem::size_of::<usize>() + std::mem::size_of::<String>() + s.len()
+        }).sum();
+
+        H2LayerMemoryUsage {
+            name: self.name.clone(),
+            entry_count: entries.len(),
+            entries: entries.iter().map(|entry| entry_size(entry)).sum(),
+            annotations: annotations,
+        }
+    }
+
+    /// Summarize the layer - see [`H2LayerStats`].
+    ///
+    /// `limit` is how many of the most common comments and largest entries
+    /// to include.
+    pub fn stats(&self, limit: usize) -> H2LayerStats {
+        let entries: Vec<_> = self.entries.iter().collect();
+
+        let mut types: BTreeMap<String, usize> = BTreeMap::new();
+        let mut untyped = 0;
+        for entry in entries.iter().map(|entry| &entry.entry).chain(self.points.values().flatten()) {
+            match entry.origin() {
+                Some(origin) => *types.entry(origin.field.type_name().to_string()).or_insert(0) += 1,
+                None         => untyped += 1,
+            }
+        }
+
+        let mut comments: BTreeMap<&String, usize> = BTreeMap::new();
+        for comment in self.comments.values() {
+            *comments.entry(comment).or_insert(0) += 1;
+        }
+        let mut common_comments: Vec<(String, usize)> = comments.into_iter().map(|(comment, count)| (comment.clone(), count)).collect();
+        common_comments.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        common_comments.truncate(limit);
+
+        let mut largest: Vec<H2LargeEntry> = entries.iter().map(|entry| H2LargeEntry {
+            offset: entry.range.start,
+            size: entry.range.len(),
+            type_name: entry.entry.origin().as_ref().map(|origin| origin.field.type_name().to_string()),
+            display: entry.entry.resolved().display.clone(),
+        }).collect();
+        largest.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.offset.cmp(&b.offset)));
+        largest.truncate(limit);
+
+        H2LayerStats {
+            name: self.name.clone(),
+            entry_count: entries.len(),
+            point_count: self.points.values().map(|points| points.len()).sum(),
+            types: types,
+            untyped: untyped,
+            covered_bytes: entries.iter().map(|entry| entry.range.len()).sum(),
+            total_bytes: self.entries.max_size(),
+            comment_count: self.comments.len(),
+            bookmark_count: self.bookmarks.len(),
+            common_comments: common_comments,
+            largest: largest,
+        }
+    }
+
     pub fn len(&self) -> usize {
         self.entries.len()
     }
 
     pub fn is_populated(&self) -> bool {
-        self.len() > 0
+        self.len() > 0 || !self.points.is_empty()
+    }
+
+    /// Check whether everything in the layer would still fit if the buffer
+    /// were `size` bytes long.
+    pub(crate) fn can_resize(&self, size: usize) -> bool {
+        let entries_fit = self.entries.iter().all(|entry| entry.range.end <= size);
+
+        entries_fit
+            && self.comments.range(size..).next().is_none()
+            && self.bookmarks.range(size..).next().is_none()
+            && self.points.range((size + 1)..).next().is_none()
+    }
+
+    /// Change the size of the layer, when the buffer's data is replaced.
+    ///
+    /// Anything past the new end has to be removed first.
+    pub(crate) fn resize(&mut self, size: usize) -> SimpleResult<()> {
+        if !self.can_resize(size) {
+            bail!("Can't resize layer {} to {} bytes: it has annotations past the end", self.name, size);
+        }
+
+        let mut entries = BumpyVector::new(size);
+        for entry in self.entries.remove_range(0..self.entries.max_size()) {
+            entries.insert(entry)?;
+        }
+        self.entries = entries;
+
+        Ok(())
+    }
+
+    /// Work out what [`H2Layer::relocate`] would do, without doing it.
+    pub(crate) fn relocation_check(&self, relocation: &H2Relocation) -> H2RelocationReport {
+        let mut report = H2RelocationReport::default();
+
+        let mut check = |kind: H2AnnotationKind, offset: usize, fits: Option<usize>, display: &str| {
+            match fits {
+                Some(new) if new != offset => report.moved += 1,
+                Some(_)                    => (),
+                None                       => report.invalidated.push(H2Invalidated {
+                    layer: self.name.clone(),
+                    offset: offset,
+                    kind: kind,
+                    display: display.to_string(),
+                }),
+            }
+        };
+
+        for entry in &self.entries {
+            check(H2AnnotationKind::Entry, entry.range.start, relocation.relocate_range(entry.range.clone()).map(|r| r.start), &entry.entry.resolved().display);
+        }
+
+        for (offset, points) in &self.points {
+            for point in points {
+                check(H2AnnotationKind::Point, *offset, relocation.relocate_offset(*offset), &point.resolved().display);
+            }
+        }
+
+        for (offset, comment) in self.comments.range(relocation.offset..) {
+            check(H2AnnotationKind::Comment, *offset, relocation.relocate_offset(*offset), comment);
+        }
+
+        for (offset, name) in self.bookmarks.range(relocation.offset..) {
+            check(H2AnnotationKind::Bookmark, *offset, relocation.relocate_offset(*offset), name);
+        }
+
+        report
+    }
+
+    /// Move everything to where it belongs after part of the buffer changes
+    /// size - see [`H2Relocation`].
+    ///
+    /// Nothing can overlap the changed bytes (see [`H2Layer::relocation_check`]).
+    pub(crate) fn relocate(&mut self, relocation: &H2Relocation) -> SimpleResult<()> {
+        if let Some(invalidated) = self.relocation_check(relocation).invalidated.first() {
+            bail!("Can't relocate layer {}: {} is in the way", self.name, invalidated);
+        }
+
+        let size = self.entries.max_size() - relocation.old_length + relocation.new_length;
+
+        let mut entries = BumpyVector::new(size);
+        for mut entry in self.entries.remove_range(0..self.entries.max_size()).into_iter().map(|entry| entry.entry) {
+            for (_, resolved) in entry.views_mut() {
+                relocation.relocate_resolved(resolved);
+            }
+            entries.insert_auto(entry)?;
+        }
+        self.entries = entries;
+
+        let mut points: BTreeMap<usize, Vec<H2Entry>> = BTreeMap::new();
+        for (offset, mut list) in std::mem::take(&mut self.points) {
+            for point in list.iter_mut() {
+                for (_, resolved) in point.views_mut() {
+                    relocation.relocate_resolved(resolved);
+                }
+            }
+
+            // Checked above
+            points.insert(relocation.relocate_offset(offset).unwrap_or(offset), list);
+        }
+        self.points = points;
+
+        self.comments = std::mem::take(&mut self.comments).into_iter().map(|(offset, comment)| {
+            (relocation.relocate_offset(offset).unwrap_or(offset), comment)
+        }).collect();
+
+        self.bookmarks = std::mem::take(&mut self.bookmarks).into_iter().map(|(offset, name)| {
+            (relocation.relocate_offset(offset).unwrap_or(offset), name)
+        }).collect();
+
+        // The IDs all point at the old offsets
+        self.entry_ids = self.entries_all().into_iter().map(|entry| (entry.id(), entry.range().start)).collect();
+
+        Ok(())
     }
 
     pub fn comment_get(&self, offset: usize) -> SimpleResult<Option<&String>> {
@@ -142,20 +510,21 @@ impl H2Layer {
     }
 
     pub fn comments_get(&self, range: Range<usize>) -> SimpleResult<Vec<&String>> {
+        Ok(self.comments_get_with_offsets(range)?.into_iter().map(|(_, comment)| comment).collect())
+    }
+
+    /// Get the comments in the given range, as `(offset, comment)`, sorted by
+    /// offset.
+    pub fn comments_get_with_offsets(&self, range: Range<usize>) -> SimpleResult<Vec<(usize, &String)>> {
         if range.end > self.entries.max_size() {
             bail!("Tried to get comment at illegal range 0x{:x?} (max = 0x{:x?})", range, self.entries.max_size());
         }
 
-        let mut out = Vec::new();
-
-        for offset in range {
-            match self.comment_get(offset)? {
-                Some(comment) => out.push(comment),
-                None => (),
-            }
+        if range.is_empty() {
+            return Ok(vec![]);
         }
 
-        Ok(out)
+        Ok(self.comments.range(range).map(|(offset, comment)| (*offset, comment)).collect())
     }
 
     pub fn comment_set(&mut self, offset: usize, comment: Option<String>) -> SimpleResult<Option<String>> {
@@ -168,4 +537,37 @@ impl H2Layer {
             None => Ok(self.comments.remove(&offset)),
         }
     }
+
+    pub fn bookmark_get(&self, offset: usize) -> SimpleResult<Option<&String>> {
+        if offset >= self.entries.max_size() {
+            bail!("Tried to get bookmark at illegal offset {}", offset);
+        }
+
+        Ok(self.bookmarks.get(&offset))
+    }
+
+    /// Get the bookmarks in the given range, as `(offset, name)`, sorted by
+    /// offset.
+    pub fn bookmarks_get(&self, range: Range<usize>) -> SimpleResult<Vec<(usize, &String)>> {
+        if range.end > self.entries.max_size() {
+            bail!("Tried to get bookmarks at illegal range 0x{:x?} (max = 0x{:x?})", range, self.entries.max_size());
+        }
+
+        if range.is_empty() {
+            return Ok(vec![]);
+        }
+
+        Ok(self.bookmarks.range(range).map(|(offset, name)| (*offset, name)).collect())
+    }
+
+    pub fn bookmark_set(&mut self, offset: usize, name: Option<String>) -> SimpleResult<Option<String>> {
+        if offset >= self.entries.max_size() {
+            bail!("Tried to put bookmark at illegal offset {}", offset);
+        }
+
+        match name {
+            Some(name) => Ok(self.bookmarks.insert(offset, name)),
+            None => Ok(self.bookmarks.remove(&offset)),
+        }
+    }
 }
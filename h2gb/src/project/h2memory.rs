@@ -0,0 +1,125 @@
+//! Approximate memory usage for a project, broken down by what's using it.
+//!
+//! None of these numbers are exact - they count the data we store (bytes,
+//! strings, entries, and so on) plus the size of the structs holding them, but
+//! not allocator or hash table overhead. They're meant to answer "what's
+//! taking up all the memory?", not to be added up to the byte.
+
+use std::mem;
+
+use redo::Record;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError};
+
+use h2datatype::{H2Type, ResolvedType};
+
+use crate::actions::Action;
+use crate::project::H2Entry;
+
+/// The approximate size of a resolved type, including all of its children.
+pub(crate) fn resolved_type_size(resolved: &ResolvedType) -> usize {
+    mem::size_of::<ResolvedType>()
+        + resolved.field_name.as_ref().map(|s| s.len()).unwrap_or(0)
+        + resolved.display.len()
+        + resolved.as_string.as_ref().map(|s| s.len()).unwrap_or(0)
+        + resolved.related.len() * mem::size_of::<(u64, H2Type)>()
+        + resolved.children.iter().map(|child| resolved_type_size(child)).sum::<usize>()
+        + resolved.target.as_ref().map(|target| resolved_type_size(target)).unwrap_or(0)
+}
+
+/// The approximate size of an entry.
+pub(crate) fn entry_size(entry: &H2Entry) -> usize {
+    // The ResolvedType is counted separately, since it's the big part
+    mem::size_of::<H2Entry>() - mem::size_of::<ResolvedType>() + resolved_type_size(entry.resolved())
+}
+
+/// Memory used by a single layer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct H2LayerMemoryUsage {
+    pub name: String,
+
+    /// The number of entries in the layer
+    pub entry_count: usize,
+
+    /// Bytes used by the entries
+    pub entries: usize,
+
+    /// Bytes used by comments and bookmarks
+    pub annotations: usize,
+}
+
+impl H2LayerMemoryUsage {
+    pub fn total(&self) -> usize {
+        self.entries + self.annotations
+    }
+}
+
+/// Memory used by a single buffer, and its layers.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct H2BufferMemoryUsage {
+    pub name: String,
+
+    /// Bytes used by the buffer's data
+    pub data: usize,
+
+    /// Each layer, sorted by name
+    pub layers: Vec<H2LayerMemoryUsage>,
+}
+
+impl H2BufferMemoryUsage {
+    pub fn total(&self) -> usize {
+        self.data + self.layers.iter().map(|l| l.total()).sum::<usize>()
+    }
+}
+
+/// Memory used by a project, and optionally its undo history.
+///
+/// Create one with [`crate::project::H2Project::memory_usage`] (just the
+/// project), or [`H2MemoryUsage::from_record`] (the project plus its undo
+/// history).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct H2MemoryUsage {
+    /// Each buffer, sorted by name
+    pub buffers: Vec<H2BufferMemoryUsage>,
+
+    /// Bytes used by the undo/redo history, if known
+    pub history: Option<usize>,
+
+    /// Bytes used by loaded data (enums, bitmasks, etc) - see
+    /// [`h2data::memory_usage`]. This is shared by every project.
+    pub data: usize,
+}
+
+impl H2MemoryUsage {
+    pub(crate) fn new(buffers: Vec<H2BufferMemoryUsage>) -> Self {
+        Self {
+            buffers: buffers,
+            history: None,
+            data: h2data::memory_usage(),
+        }
+    }
+
+    /// Get the memory usage for the project in `record`, plus its history.
+    ///
+    /// The history is approximated by how much bigger the record is than the
+    /// project on its own, once they're both serialized.
+    pub fn from_record(record: &Record<Action>) -> SimpleResult<Self> {
+        let record_size = serde_json::to_string(record).map_err(|e| {
+            SimpleError::new(format!("Couldn't serialize the record: {}", e))
+        })?.len();
+
+        let project_size = serde_json::to_string(record.target()).map_err(|e| {
+            SimpleError::new(format!("Couldn't serialize the project: {}", e))
+        })?.len();
+
+        let mut usage = record.target().memory_usage();
+        usage.history = Some(record_size.saturating_sub(project_size));
+
+        Ok(usage)
+    }
+
+    /// Everything, added together.
+    pub fn total(&self) -> usize {
+        self.buffers.iter().map(|b| b.total()).sum::<usize>() + self.history.unwrap_or(0) + self.data
+    }
+}
@@ -5,10 +5,39 @@
 
 use serde::{Serialize, Deserialize};
 use simple_error::{bail, SimpleResult, SimpleError};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::ops::{Deref, DerefMut, Range};
+use std::sync::Arc;
 
-use crate::project::H2Buffer;
+use h2datatype::{H2DataReference, H2Type, H2Types, ResolvedType};
+
+use crate::render::{PixelLayout, PixelPreview, SampleLayout, SamplePreview};
+use crate::project::{H2Buffer, H2Config, H2DataOverlay, H2Entry, H2EnumChange, H2Id, H2Layer, H2Matcher, H2MemoryUsage, H2ReportOptions, H2SearchField, H2SearchMatch, H2SearchQuery, H2Symbol, H2SymbolScope, H2SymbolTable, H2Window};
+
+/// A buffer from [`H2Project::buffer_get_mut`], which can be read or
+/// changed.
+///
+/// Buffers can be shared with snapshots, so this only copies the buffer the
+/// first time it's borrowed mutably (and only if it's still shared). Reading
+/// through it - say, to validate something before changing anything - never
+/// copies.
+#[derive(Debug)]
+pub struct H2BufferMut<'a>(&'a mut Arc<H2Buffer>);
+
+impl Deref for H2BufferMut<'_> {
+    type Target = H2Buffer;
+
+    fn deref(&self) -> &H2Buffer {
+        &self.0
+    }
+}
+
+impl DerefMut for H2BufferMut<'_> {
+    fn deref_mut(&mut self) -> &mut H2Buffer {
+        Arc::make_mut(self.0)
+    }
+}
 
 // H2Project is the very core, and the root of undo. All actions will be taken
 // via this object.
@@ -18,8 +47,29 @@ pub struct H2Project {
     pub version: String,
 
     // Buffers that exist, indexed by their name; layers are stored in their
-    // respective buffer
-    buffers: HashMap<String, H2Buffer>,
+    // respective buffer. They're shared with any snapshots, and copied the
+    // first time they're changed after one is taken (see `snapshot()`).
+    // This (and the other maps in a project) is ordered, so a project is
+    // always saved the same way
+    buffers: BTreeMap<String, Arc<H2Buffer>>,
+
+    // The last ID handed out, and a lookup from buffer IDs to names
+    #[serde(default)]
+    last_id: u64,
+    #[serde(default)]
+    buffer_ids: BTreeMap<H2Id, String>,
+
+    // Project-specific enums, and changes to the shared ones
+    #[serde(default)]
+    data: H2DataOverlay,
+
+    // Defaults for building types, and display policies
+    #[serde(default)]
+    config: H2Config,
+
+    // Names for places in the buffers
+    #[serde(default)]
+    symbols: H2SymbolTable,
 }
 
 impl H2Project {
@@ -28,15 +78,38 @@ impl H2Project {
             name: String::from(name),
             version: String::from(version),
 
-            buffers: HashMap::new(),
+            buffers: BTreeMap::new(),
+
+            last_id: 0,
+            buffer_ids: BTreeMap::new(),
+
+            data: H2DataOverlay::new(),
+
+            config: H2Config::default(),
+
+            symbols: H2SymbolTable::new(),
         }
     }
 
+    /// Get a new, never-before-used ID for a buffer, layer, or entry.
+    ///
+    /// See [`H2Id`] for details.
+    pub fn id_allocate(&mut self) -> H2Id {
+        self.last_id += 1;
+
+        H2Id::new(self.last_id)
+    }
+
     // fn multi_key(buffer: &str, layer: &str) -> (String, String) {
     //     (buffer.to_string(), layer.to_string())
     // }
 
-    pub fn buffers(&self) -> &HashMap<String, H2Buffer> {
+    /// Every buffer, by name.
+    ///
+    /// Buffers are kept in an [`Arc`] so they can be shared with snapshots
+    /// (see [`H2Project::snapshot`]); it derefs to the [`H2Buffer`], and
+    /// [`Arc::ptr_eq`] tells whether two projects still share one.
+    pub fn buffers(&self) -> &BTreeMap<String, Arc<H2Buffer>> {
         return &self.buffers;
     }
 
@@ -44,7 +117,11 @@ impl H2Project {
         self.buffers.contains_key(buffer)
     }
 
-    pub fn buffer_insert(&mut self, name: &str, buffer: H2Buffer) -> SimpleResult<()> {
+    /// Insert a buffer, and return its ID.
+    ///
+    /// If the buffer doesn't have an ID yet, it's assigned a new one; if it
+    /// does (say, because it's being put back by redo), it keeps it.
+    pub fn buffer_insert(&mut self, name: &str, mut buffer: H2Buffer) -> SimpleResult<H2Id> {
         // Sanity check
         if name == "" {
             bail!("Buffer must have a name");
@@ -54,11 +131,21 @@ impl H2Project {
             bail!("Buffer already exists: {}", name);
         }
 
+        if self.buffer_ids.contains_key(&buffer.id()) {
+            bail!("Buffer ID is already in use: {}", buffer.id());
+        }
+
+        if !buffer.id().is_assigned() {
+            buffer.set_id(self.id_allocate());
+        }
+        let id = buffer.id();
+
         // Go
         // TODO: Check and insert at the same time
-        self.buffers.insert(name.to_string(), buffer);
+        self.buffers.insert(name.to_string(), Arc::new(buffer));
+        self.buffer_ids.insert(id, name.to_string());
 
-        Ok(())
+        Ok(id)
     }
 
     // Note: In the future, we should check for references to this buffer to
@@ -78,13 +165,16 @@ impl H2Project {
 
         // Go
         match self.buffers.remove(buffer) {
-            Some(b) => Ok(b),
+            Some(b) => {
+                self.buffer_ids.remove(&b.id());
+                Ok(Arc::try_unwrap(b).unwrap_or_else(|shared| (*shared).clone()))
+            },
             None => bail!("Buffer not found"),
         }
     }
 
     pub fn buffer_get(&self, buffer: &str) -> Option<&H2Buffer> {
-        self.buffers.get(buffer)
+        self.buffers.get(buffer).map(|b| b.as_ref())
     }
 
     pub fn buffer_get_or_err(&self, buffer: &str) -> SimpleResult<&H2Buffer> {
@@ -93,16 +183,484 @@ impl H2Project {
         )
     }
 
-    pub fn buffer_get_mut(&mut self, buffer: &str) -> Option<&mut H2Buffer> {
-        self.buffers.get_mut(buffer)
+    /// Get a buffer that can be changed.
+    ///
+    /// A buffer that's shared with a snapshot is only copied when it's
+    /// actually changed - see [`H2BufferMut`].
+    pub fn buffer_get_mut(&mut self, buffer: &str) -> Option<H2BufferMut> {
+        self.buffers.get_mut(buffer).map(H2BufferMut)
     }
 
-    pub fn buffer_get_mut_or_err(&mut self, buffer: &str) -> SimpleResult<&mut H2Buffer> {
+    pub fn buffer_get_mut_or_err(&mut self, buffer: &str) -> SimpleResult<H2BufferMut> {
         self.buffer_get_mut(buffer).ok_or(
             SimpleError::new(format!("Could not find buffer {}", buffer))
         )
     }
 
+    /// Get the ID of the buffer with the given name.
+    pub fn buffer_id(&self, buffer: &str) -> Option<H2Id> {
+        self.buffer_get(buffer).map(|b| b.id())
+    }
+
+    /// Get the current name of the buffer with the given ID.
+    pub fn buffer_name(&self, id: H2Id) -> Option<&str> {
+        self.buffer_ids.get(&id).map(|name| &name[..])
+    }
+
+    pub fn buffer_get_by_id(&self, id: H2Id) -> Option<&H2Buffer> {
+        self.buffer_ids.get(&id).and_then(|name| self.buffers.get(name)).map(|b| b.as_ref())
+    }
+
+    pub fn buffer_get_by_id_or_err(&self, id: H2Id) -> SimpleResult<&H2Buffer> {
+        self.buffer_get_by_id(id).ok_or(
+            SimpleError::new(format!("Could not find buffer with ID {}", id))
+        )
+    }
+
+    pub fn buffer_get_mut_by_id(&mut self, id: H2Id) -> Option<H2BufferMut> {
+        match self.buffer_ids.get(&id) {
+            Some(name) => self.buffers.get_mut(name).map(H2BufferMut),
+            None       => None,
+        }
+    }
+
+    pub fn buffer_get_mut_by_id_or_err(&mut self, id: H2Id) -> SimpleResult<H2BufferMut> {
+        self.buffer_get_mut_by_id(id).ok_or(
+            SimpleError::new(format!("Could not find buffer with ID {}", id))
+        )
+    }
+
+    /// Get the entries covering `offset` in every layer of `buffer`.
+    ///
+    /// Each entry comes with the layer it's in (which has the layer's name and
+    /// ID), sorted by layer name. Layers with nothing at that offset are left
+    /// out.
+    pub fn get_entries_at(&self, buffer: &str, offset: usize) -> SimpleResult<Vec<(&H2Layer, &H2Entry)>> {
+        let buffer = self.buffer_get_or_err(buffer)?;
+
+        if offset >= buffer.len() {
+            bail!("Offset {} is outside of buffer {}", offset, buffer.name());
+        }
+
+        let mut out = Vec::new();
+        for layer_name in buffer.layer_names() {
+            let layer = buffer.layer_get_or_err(layer_name)?;
+
+            for entry in layer.entries_get(offset..(offset + 1))? {
+                out.push((layer, entry));
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// The project's own data, layered over [`h2data`] - see
+    /// [`H2DataOverlay`].
+    pub fn data(&self) -> &H2DataOverlay {
+        &self.data
+    }
+
+    /// The project's configuration - see [`H2Config`].
+    pub fn config(&self) -> &H2Config {
+        &self.config
+    }
+
+    /// Replace the project's configuration, and return the old one.
+    pub fn config_set(&mut self, config: H2Config) -> H2Config {
+        std::mem::replace(&mut self.config, config)
+    }
+
+    /// Resolve `abstract_type` at `offset` in `buffer`, using the project's
+    /// data and configuration.
+    ///
+    /// This is [`H2Buffer::peek`], except that switches are bound to the
+    /// project's variables, enum values are displayed with the project's enums
+    /// and changes (see [`H2DataOverlay`]), and long displays are cut off
+    /// (see [`H2Config`]).
+    pub fn peek(&self, buffer: &str, abstract_type: &H2Type, offset: usize) -> SimpleResult<ResolvedType> {
+        let abstract_type = abstract_type.bind(self.data.variables())?;
+        let mut resolved = self.buffer_get_or_err(buffer)?.peek(&abstract_type, offset)?;
+        self.data.rerender(&mut resolved);
+        self.config.truncate(&mut resolved);
+
+        Ok(resolved)
+    }
+
+    /// Set a project variable (or remove it, with `None`), and return the
+    /// old value.
+    ///
+    /// Variables only affect types as they're resolved, so entries that
+    /// already exist aren't changed.
+    pub fn variable_set(&mut self, name: &str, value: Option<u64>) -> SimpleResult<Option<u64>> {
+        self.data.variable_set(name, value)
+    }
+
+    /// Define an enum in the project, and update every entry that displays
+    /// it.
+    ///
+    /// Returns the enum's old values, if the project already defined it.
+    pub fn enum_define(&mut self, enum_name: &str, values: BTreeMap<usize, String>) -> SimpleResult<Option<BTreeMap<usize, String>>> {
+        let old_values = self.data.enum_define(enum_name, values)?;
+        self.enum_rerender(enum_name);
+
+        Ok(old_values)
+    }
+
+    /// Remove an enum defined by the project, and update every entry that
+    /// displays it.
+    pub fn enum_undefine(&mut self, enum_name: &str) -> SimpleResult<BTreeMap<usize, String>> {
+        let values = self.data.enum_undefine(enum_name)?;
+        self.enum_rerender(enum_name);
+
+        Ok(values)
+    }
+
+    /// Give `value` in `enum_name` a new name (or remove its name), and
+    /// update every entry that displays it.
+    ///
+    /// Returns the change that was there before, for
+    /// [`H2Project::enum_member_restore`].
+    pub fn enum_member_set(&mut self, enum_name: &str, value: usize, name: Option<&str>) -> SimpleResult<Option<H2EnumChange>> {
+        let old_change = self.data.set(enum_name, value, name)?;
+        self.enum_rerender(enum_name);
+
+        Ok(old_change)
+    }
+
+    /// Put back a change returned by [`H2Project::enum_member_set`], and
+    /// update every entry that displays it.
+    pub fn enum_member_restore(&mut self, enum_name: &str, value: usize, change: Option<H2EnumChange>) -> SimpleResult<()> {
+        if !self.data.enum_exists(enum_name) {
+            bail!("No such enum: {}", enum_name);
+        }
+
+        self.data.change_set(enum_name, value, change);
+        self.enum_rerender(enum_name);
+
+        Ok(())
+    }
+
+    /// Update the displays of entries that use `enum_name`, and return how
+    /// many were updated (counting each interpretation of an entry
+    /// separately - see [`H2Entry::interpretations`]).
+    ///
+    /// Only entries whose type references the enum (see
+    /// [`H2Type::data_references`]) are updated - entries that don't know
+    /// their type are always checked.
+    fn enum_rerender(&mut self, enum_name: &str) -> usize {
+        let data = &self.data;
+        let reference = H2DataReference::Enum(enum_name.to_string());
+
+        // Entries made from the same type share its definition, so each
+        // type only has to be checked once
+        let mut uses_enum: HashMap<*const H2Types, bool> = HashMap::new();
+        let mut affected = |origin: Option<&H2Type>| match origin {
+            Some(origin) => *uses_enum.entry(Arc::as_ptr(&origin.field)).or_insert_with(|| {
+                origin.data_references().contains(&reference)
+            }),
+            None => true,
+        };
+        let mut updated = 0;
+
+        for buffer in self.buffers.values_mut() {
+            // Look before changing anything, so a buffer without any affected
+            // entries stays shared with snapshots
+            let needs_update = buffer.layers().any(|layer| layer.entries_all().into_iter().any(|entry| {
+                affected(entry.origin().as_ref()) || entry.interpretations().iter().any(|i| affected(i.origin().as_ref()))
+            }));
+
+            if !needs_update {
+                continue;
+            }
+
+            for layer in Arc::make_mut(buffer).layers_mut() {
+                layer.entries_update(|entry| {
+                    // Every interpretation of the entry can use the enum
+                    for (origin, resolved) in entry.views_mut() {
+                        if affected(origin) {
+                            data.rerender_enum(resolved, enum_name);
+                            updated += 1;
+                        }
+                    }
+                });
+            }
+        }
+
+        updated
+    }
+
+    pub fn symbols(&self) -> &H2SymbolTable {
+        &self.symbols
+    }
+
+    /// Add a symbol, replacing the one with the same name and scope.
+    ///
+    /// Returns the symbol that was replaced, if any.
+    pub fn symbol_set(&mut self, symbol: H2Symbol) -> SimpleResult<Option<H2Symbol>> {
+        let buffer = self.buffer_get_by_id_or_err(symbol.buffer)?;
+        if symbol.offset >= buffer.len() {
+            bail!("Symbol {} @ 0x{:x} is past the end of buffer {}", symbol.name, symbol.offset, buffer.name());
+        }
+
+        Ok(self.symbols.set(symbol))
+    }
+
+    /// Remove a symbol by name and scope (`buffer` is only used for local
+    /// symbols).
+    pub fn symbol_remove(&mut self, name: &str, scope: H2SymbolScope, buffer: H2Id) -> SimpleResult<H2Symbol> {
+        self.symbols.remove(name, scope, buffer).ok_or(
+            SimpleError::new(format!("No {} symbol named {}", scope, name))
+        )
+    }
+
+    /// Find where a name points, as `(buffer, offset)`, as seen from inside
+    /// `from_buffer` (see [`H2SymbolTable::lookup`]).
+    ///
+    /// Symbols that point into a buffer that no longer exists aren't found.
+    pub fn symbol_lookup(&self, name: &str, from_buffer: Option<&str>) -> SimpleResult<Option<(&str, usize)>> {
+        let from = match from_buffer {
+            Some(buffer) => Some(self.buffer_get_or_err(buffer)?.id()),
+            None         => None,
+        };
+
+        Ok(self.symbols.lookup(name, from).and_then(|symbol| {
+            self.buffer_name(symbol.buffer).map(|buffer| (buffer, symbol.offset))
+        }))
+    }
+
+    /// Describe an offset using the closest symbol at or before it - for
+    /// example, `player_name` or `player_name+0x4` - for displaying
+    /// pointers and addresses.
+    pub fn symbolize(&self, buffer: &str, offset: usize) -> SimpleResult<Option<String>> {
+        Ok(self.symbolize_in(self.buffer_get_or_err(buffer)?, offset))
+    }
+
+    fn symbolize_in(&self, buffer: &H2Buffer, offset: usize) -> Option<String> {
+        self.symbols.nearest(buffer.id(), offset).map(|symbol| {
+            match offset - symbol.offset {
+                0     => symbol.name.clone(),
+                delta => format!("{}+0x{:x}", symbol.name, delta),
+            }
+        })
+    }
+
+    /// Get the text to show for `resolved`, a value in `buffer`.
+    ///
+    /// That's usually just its display. A pointer (see
+    /// [`ResolvedType::is_pointer`]) to an address inside `buffer` - counting
+    /// from its base address - also gets the symbol it points at, like
+    /// `0x00401016 (main+0x16)` (see [`H2Project::symbolize`]). Pointers to
+    /// anywhere else are shown as-is.
+    pub fn render_value(&self, buffer: &str, resolved: &ResolvedType) -> SimpleResult<String> {
+        Ok(self.render_value_in(self.buffer_get_or_err(buffer)?, resolved))
+    }
+
+    fn render_value_in(&self, buffer: &H2Buffer, resolved: &ResolvedType) -> String {
+        let symbol = match resolved.is_pointer {
+            true => resolved.as_integer
+                .and_then(|address| address.to_usize().ok())
+                .and_then(|address| address.checked_sub(buffer.base_address))
+                .filter(|offset| *offset < buffer.len())
+                .and_then(|offset| self.symbolize_in(buffer, offset)),
+            false => None,
+        };
+
+        match symbol {
+            Some(symbol) => format!("{} ({})", resolved.display, symbol),
+            None         => resolved.display.clone(),
+        }
+    }
+
+    /// Approximately how much memory the project is using, by buffer and
+    /// layer.
+    ///
+    /// This doesn't include the undo history, since the project doesn't know
+    /// about it - see [`H2MemoryUsage::from_record`] for that.
+    pub fn memory_usage(&self) -> H2MemoryUsage {
+        H2MemoryUsage::new(self.buffers.values().map(|buffer| buffer.memory_usage()).collect())
+    }
+
+    /// Get everything needed to display part of a buffer - the bytes, plus
+    /// the entries, comments, bookmarks, and coverage from each of `layers`.
+    ///
+    /// See [`H2Window`] for details.
+    pub fn render_window(&self, buffer: &str, layers: &[&str], range: Range<usize>) -> SimpleResult<H2Window<'_>> {
+        H2Window::new(self.buffer_get_or_err(buffer)?, layers, range)
+    }
+
+    /// Write a text listing of the project - each buffer, then each layer's
+    /// entries (with their comments), in order. Nested entries have their
+    /// fields listed under them, indented (see [`H2Entry::children`]), and
+    /// pointers are shown with what they point at (see
+    /// [`H2Project::render_value`]).
+    ///
+    /// `options` picks what goes in and how much of it; see
+    /// [`H2ReportOptions`].
+    ///
+    /// # Errors
+    ///
+    /// * Every buffer named in `options` has to exist
+    pub fn report(&self, options: &H2ReportOptions) -> SimpleResult<String> {
+        let buffers: Vec<(&str, &H2Buffer)> = match &options.buffers {
+            Some(names) => names.iter().map(|name| Ok((&name[..], self.buffer_get_or_err(name)?))).collect::<SimpleResult<_>>()?,
+            None        => self.buffers.iter().map(|(name, buffer)| (&name[..], buffer.as_ref())).collect(),
+        };
+
+        let mut out = String::new();
+        for (name, buffer) in buffers {
+            out.push_str(&format!("Buffer: {} (base 0x{:x} / 0x{:x} bytes long)\n", name, buffer.base_address, buffer.len()));
+            for transformation in buffer.transformations() {
+                out.push_str(&format!(" Transformation: {}\n", transformation));
+            }
+
+            let entries = buffer.entries_matching(&options.filter);
+            let limit = options.max_lines.unwrap_or(entries.len());
+
+            let mut last_layer: Option<H2Id> = None;
+            for (layer, entry) in entries.iter().take(limit) {
+                if last_layer != Some(layer.id()) {
+                    out.push_str(&format!(" Layer: {}\n", layer.name()));
+                    last_layer = Some(layer.id());
+                }
+
+                let range = (entry.resolved().actual_range.start as usize)..(entry.resolved().actual_range.end as usize);
+                out.push_str(&format!("  0x{:08x} - 0x{:08x}", range.start + buffer.base_address, range.end + buffer.base_address));
+
+                if options.bytes > 0 {
+                    let bytes: Vec<String> = buffer.byte_range(range.clone())?.iter().take(options.bytes).map(|b| format!("{:02x}", b)).collect();
+                    out.push_str(&format!("  {}", bytes.join(" ")));
+                }
+
+                // Only name the interpretation if there's more than one
+                match entry.interpretations().is_empty() {
+                    true  => out.push_str(&format!("  {}", self.render_value_in(buffer, entry.resolved()))),
+                    false => out.push_str(&format!("  ({}) {}", entry.interpretation_name(), self.render_value_in(buffer, entry.resolved()))),
+                }
+
+                let comments = layer.comments_get(range)?;
+                if !comments.is_empty() {
+                    let comments: Vec<&str> = comments.iter().map(|c| &c[..]).collect();
+                    out.push_str(&format!(" ; {}", comments.join(" / ")));
+                }
+                out.push('\n');
+
+                for interpretation in entry.interpretations() {
+                    out.push_str(&format!("   or ({}) {}\n", interpretation.name(), self.render_value_in(buffer, interpretation.resolved())));
+                }
+
+                self.report_children(&mut out, buffer, entry, 1);
+            }
+
+            if entries.len() > limit {
+                out.push_str(&format!(" ... {} more entries\n", entries.len() - limit));
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// List the children of a nested entry (and theirs) in a report, each
+    /// level indented a bit more than the last.
+    fn report_children(&self, out: &mut String, buffer: &H2Buffer, entry: &H2Entry, depth: usize) {
+        for child in entry.children() {
+            let range = &child.resolved().actual_range;
+            out.push_str(&format!("  {}0x{:08x} - 0x{:08x}", "  ".repeat(depth), range.start as usize + buffer.base_address, range.end as usize + buffer.base_address));
+
+            match &child.resolved().field_name {
+                Some(name) => out.push_str(&format!("  {}: {}\n", name, self.render_value_in(buffer, child.resolved()))),
+                None       => out.push_str(&format!("  {}\n", self.render_value_in(buffer, child.resolved()))),
+            }
+
+            self.report_children(out, buffer, &child, depth + 1);
+        }
+    }
+
+    /// Find entries by their text - what they display as, their comments, or
+    /// their names (see [`H2SearchField`]).
+    ///
+    /// Matches are in buffer order (like [`H2Project::report`]), then by
+    /// layer name and offset, and for each entry in the order of
+    /// [`H2SearchField`]. Only the interpretation that's shown is searched.
+    ///
+    /// # Errors
+    ///
+    /// * Every buffer named in `query` has to exist
+    /// * A regex pattern has to be valid
+    pub fn search(&self, query: &H2SearchQuery) -> SimpleResult<Vec<H2SearchMatch>> {
+        let matcher = H2Matcher::new(&query.pattern)?;
+
+        let buffers: Vec<(&str, &H2Buffer)> = match &query.buffers {
+            Some(names) => names.iter().map(|name| Ok((&name[..], self.buffer_get_or_err(name)?))).collect::<SimpleResult<_>>()?,
+            None        => self.buffers.iter().map(|(name, buffer)| (&name[..], buffer.as_ref())).collect(),
+        };
+
+        let mut out = vec![];
+        for (name, buffer) in buffers {
+            for (layer, entry) in buffer.entries_matching(&query.filter) {
+                let range = (entry.resolved().actual_range.start as usize)..(entry.resolved().actual_range.end as usize);
+
+                let mut texts: Vec<(H2SearchField, &str)> = vec![];
+                if query.searches(H2SearchField::Display) {
+                    texts.push((H2SearchField::Display, &entry.resolved().display));
+                }
+
+                if query.searches(H2SearchField::Comment) {
+                    texts.extend(layer.comments_get(range.clone())?.into_iter().map(|comment| (H2SearchField::Comment, &comment[..])));
+                }
+
+                if query.searches(H2SearchField::Name) {
+                    if let Some(field_name) = &entry.resolved().field_name {
+                        texts.push((H2SearchField::Name, field_name));
+                    }
+                    texts.extend(layer.bookmarks_get(range.clone())?.into_iter().map(|(_, bookmark)| (H2SearchField::Name, &bookmark[..])));
+                }
+
+                out.extend(texts.into_iter().filter(|(_, text)| matcher.matches(text)).map(|(field, text)| H2SearchMatch {
+                    buffer: name.to_string(),
+                    layer: layer.name().to_string(),
+                    range: range.clone(),
+                    field: field,
+                    text: text.to_string(),
+                }));
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Decode an entry's bytes as pixels, for previewing an image.
+    ///
+    /// The entry can be any type (a blob, an array of
+    /// [`h2datatype::simple::Rgb`], ...); only its bytes are used, so `layout`
+    /// says how to interpret them. See [`PixelPreview`] for details.
+    pub fn render_pixels(&self, buffer: &str, layer: &str, offset: usize, layout: &PixelLayout) -> SimpleResult<PixelPreview> {
+        let buffer = self.buffer_get_or_err(buffer)?;
+        let range = buffer.layer_get_or_err(layer)?.entry_get_or_err(offset)?.resolved().actual_range.clone();
+
+        PixelPreview::decode(buffer.byte_range((range.start as usize)..(range.end as usize))?, layout)
+    }
+
+    /// Decode an entry's bytes as PCM audio samples.
+    ///
+    /// Like [`H2Project::render_pixels`], only the entry's bytes are used, so
+    /// `layout` says how to interpret them. See [`SamplePreview`] for details.
+    pub fn render_samples(&self, buffer: &str, layer: &str, offset: usize, layout: &SampleLayout) -> SimpleResult<SamplePreview> {
+        let buffer = self.buffer_get_or_err(buffer)?;
+        let range = buffer.layer_get_or_err(layer)?.entry_get_or_err(offset)?.resolved().actual_range.clone();
+
+        SamplePreview::decode(buffer.byte_range((range.start as usize)..(range.end as usize))?, layout)
+    }
+
+    /// Take a read-only copy of the project, as it is right now.
+    ///
+    /// This is cheap: buffers are shared between the project and its
+    /// snapshots, and a buffer is only copied the first time it's changed
+    /// afterwards. The snapshot can be handed to another thread - say, for a
+    /// long export or render - while actions keep changing the project.
+    pub fn snapshot(&self) -> Arc<H2Project> {
+        Arc::new(self.clone())
+    }
+
     // Guarantees either all or none are inserted
     // pub fn buffer_insert_multiple(&mut self, mut buffers: HashMap<String, H2Buffer>) -> SimpleResult<()> {
     //     // Validate first
@@ -179,13 +737,20 @@ impl H2Project {
     // }
 }
 
+/// A short summary - the project, then one line per buffer. See
+/// [`H2Project::report`] for everything in it.
 impl fmt::Display for H2Project {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Name: {}, version: {}", self.name, self.version)?;
 
-        for (_name, buffer) in self.buffers() {
-            writeln!(f, "{}", buffer)?;
-            writeln!(f, "")?;
+        for (name, buffer) in self.buffers() {
+            writeln!(f, " Buffer: {} (base 0x{:x} / 0x{:x} bytes long): {} layer(s), {} entries",
+                name,
+                buffer.base_address,
+                buffer.len(),
+                buffer.layers().count(),
+                buffer.entries_all().len(),
+            )?;
         }
 
         Ok(())
@@ -199,6 +764,14 @@ mod tests {
     use simple_error::SimpleResult;
     use pretty_assertions::assert_eq;
 
+    use generic_number::{IntegerReader, Endian, DefaultFormatter, HexFormatter};
+    use h2datatype::composite::{H2Array, H2Struct};
+    use h2datatype::simple::{H2Enum, H2Pointer, Rgb};
+    use h2datatype::simple::numeric::H2Integer;
+
+    use crate::project::{H2EntryFilter, H2Nesting, H2Provenance, H2SearchPattern};
+    use crate::render::{PixelFormat, SampleFormat};
+
     #[test]
     fn test_buffer_insert() -> SimpleResult<()> {
         let mut project = H2Project::new("name", "1.0");
@@ -300,6 +873,469 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_buffer_ids() -> SimpleResult<()> {
+        let mut project = H2Project::new("name", "1.0");
+
+        let id1 = project.buffer_insert("buffer1", H2Buffer::new("name", b"ABCD".to_vec(), 0x100)?)?;
+        let id2 = project.buffer_insert("buffer2", H2Buffer::new("name", b"EFGH".to_vec(), 0x100)?)?;
+        assert_ne!(id1, id2);
+        assert!(id1.is_assigned());
+
+        assert_eq!(Some(id1), project.buffer_id("buffer1"));
+        assert_eq!(Some("buffer2"), project.buffer_name(id2));
+        assert_eq!(b"EFGH".to_vec(), project.buffer_get_by_id_or_err(id2)?.data);
+
+        // Removing and re-inserting keeps the same ID
+        let buffer = project.buffer_remove("buffer1")?;
+        assert_eq!(None, project.buffer_name(id1));
+        assert!(project.buffer_get_by_id(id1).is_none());
+        assert_eq!(id1, project.buffer_insert("buffer1", buffer)?);
+
+        // IDs are never reused
+        let buffer = project.buffer_remove("buffer2")?;
+        let id3 = project.buffer_insert("buffer3", H2Buffer::new("name", b"IJKL".to_vec(), 0x100)?)?;
+        assert_ne!(id2, id3);
+
+        // Can't insert the same ID twice
+        let mut duplicate = H2Buffer::new("name", b"MNOP".to_vec(), 0x100)?;
+        duplicate.set_id(id3);
+        assert!(project.buffer_insert("duplicate", duplicate).is_err());
+        assert!(project.buffer_insert("buffer2", buffer).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_buffer_order() -> SimpleResult<()> {
+        let mut project = H2Project::new("name", "1.0");
+
+        // Buffers always come out sorted by name, regardless of when they
+        // were added
+        project.buffer_insert("c", H2Buffer::new("c", b"C".to_vec(), 0)?)?;
+        project.buffer_insert("a", H2Buffer::new("a", b"A".to_vec(), 0)?)?;
+        project.buffer_insert("b", H2Buffer::new("b", b"B".to_vec(), 0)?)?;
+
+        let names: Vec<&str> = project.buffers().keys().map(|name| &name[..]).collect();
+        assert_eq!(vec!["a", "b", "c"], names);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_pixels() -> SimpleResult<()> {
+        let mut project = H2Project::new("name", "1.0");
+        project.buffer_insert("buffer", H2Buffer::new("buffer", b"\xffhdr\xff\x00\x00\x00\xff\x00\x00\x00\xff\xff\xff\xff".to_vec(), 0)?)?;
+
+        let id = project.id_allocate();
+        project.buffer_get_mut_or_err("buffer")?.layer_add("layer", id)?;
+
+        // Four RGB pixels after a 4-byte header
+        let t = H2Array::new(4, Rgb::new(false))?;
+        let resolved = project.buffer_get_or_err("buffer")?.peek(&t, 4)?;
+        let id = project.id_allocate();
+        project.buffer_get_mut_or_err("buffer")?.layer_get_mut_or_err("layer")?.entry_create(resolved, Some(t), id, H2Provenance::default())?;
+
+        // Any offset in the entry works
+        let preview = project.render_pixels("buffer", "layer", 6, &PixelLayout::new(2, 2, PixelFormat::Rgb888))?;
+        assert_eq!(vec![
+            b"\xff\x00\x00\xff\x00\xff\x00\xff".to_vec(),
+            b"\x00\x00\xff\xff\xff\xff\xff\xff".to_vec(),
+        ], preview.rows);
+
+        // Too big for the entry, or no entry
+        assert!(project.render_pixels("buffer", "layer", 4, &PixelLayout::new(2, 3, PixelFormat::Rgb888)).is_err());
+        assert!(project.render_pixels("buffer", "layer", 0, &PixelLayout::new(1, 1, PixelFormat::Gray8)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_samples() -> SimpleResult<()> {
+        let mut project = H2Project::new("name", "1.0");
+        project.buffer_insert("buffer", H2Buffer::new("buffer", b"data\x00\x40\x00\xc0\x00\x20\x00\xe0".to_vec(), 0)?)?;
+
+        let id = project.id_allocate();
+        project.buffer_get_mut_or_err("buffer")?.layer_add("layer", id)?;
+
+        // Four 16-bit samples after a 4-byte header
+        let t = H2Array::new(4, H2Integer::new(IntegerReader::I16(Endian::Little), DefaultFormatter::new_integer()))?;
+        let resolved = project.buffer_get_or_err("buffer")?.peek(&t, 4)?;
+        let id = project.id_allocate();
+        project.buffer_get_mut_or_err("buffer")?.layer_get_mut_or_err("layer")?.entry_create(resolved, Some(t), id, H2Provenance::default())?;
+
+        let preview = project.render_samples("buffer", "layer", 4, &SampleLayout::new(SampleFormat::I16(Endian::Little), 2, 44100))?;
+        assert_eq!(vec![vec![0.5, 0.25], vec![-0.5, -0.25]], preview.channels);
+        assert_eq!(44100, preview.rate);
+
+        // No entry
+        assert!(project.render_samples("buffer", "layer", 0, &SampleLayout::new(SampleFormat::U8, 1, 8000)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_entries_at() -> SimpleResult<()> {
+        let mut project = H2Project::new("name", "1.0");
+        project.buffer_insert("buffer", H2Buffer::new("buffer", b"\x00\x01\x02\x03\x04\x05\x06\x07".to_vec(), 0)?)?;
+
+        // Three layers: two with an entry over offset 2, and one without
+        for layer in vec!["c", "a", "b"] {
+            let id = project.id_allocate();
+            project.buffer_get_mut_or_err("buffer")?.layer_add(layer, id)?;
+        }
+
+        let u32 = H2Integer::new(IntegerReader::U32(Endian::Big), DefaultFormatter::new_integer());
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+
+        let resolved = project.buffer_get_or_err("buffer")?.peek(&u32, 0)?;
+        let id = project.id_allocate();
+        project.buffer_get_mut_or_err("buffer")?.layer_get_mut_or_err("c")?.entry_create(resolved, None, id, H2Provenance::default())?;
+
+        let resolved = project.buffer_get_or_err("buffer")?.peek(&u8, 2)?;
+        let id = project.id_allocate();
+        project.buffer_get_mut_or_err("buffer")?.layer_get_mut_or_err("a")?.entry_create(resolved, None, id, H2Provenance::default())?;
+
+        let entries = project.get_entries_at("buffer", 2)?;
+        assert_eq!(2, entries.len());
+        assert_eq!("a", entries[0].0.name());
+        assert_eq!(2..3, entries[0].1.resolved().actual_range);
+        assert_eq!("c", entries[1].0.name());
+        assert_eq!(project.buffer_get_or_err("buffer")?.layer_id("c"), Some(entries[1].0.id()));
+        assert_eq!(0..4, entries[1].1.resolved().actual_range);
+
+        // Only the big one covers offset 3, and nothing covers 4
+        assert_eq!(1, project.get_entries_at("buffer", 3)?.len());
+        assert_eq!(0, project.get_entries_at("buffer", 4)?.len());
+
+        // Bad buffer or offset
+        assert!(project.get_entries_at("nobuffer", 0).is_err());
+        assert!(project.get_entries_at("buffer", 8).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search() -> SimpleResult<()> {
+        let mut project = H2Project::new("name", "1.0");
+        project.buffer_insert("buffer", H2Buffer::new("buffer", b"\x00\x01\x02\x03\x04\x05\x06\x07".to_vec(), 0)?)?;
+        project.buffer_insert("other", H2Buffer::new("other", b"\x12\x34".to_vec(), 0)?)?;
+
+        let u16 = H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer());
+        for (buffer, offset) in [("buffer", 0), ("buffer", 4), ("other", 0)] {
+            let id = project.id_allocate();
+            if project.buffer_get_or_err(buffer)?.layer_get("layer").is_none() {
+                project.buffer_get_mut_or_err(buffer)?.layer_add("layer", id)?;
+            }
+
+            let resolved = project.buffer_get_or_err(buffer)?.peek(&u16, offset)?;
+            let id = project.id_allocate();
+            project.buffer_get_mut_or_err(buffer)?.layer_get_mut_or_err("layer")?.entry_create(resolved, None, id, H2Provenance::default())?;
+        }
+
+        let mut buffer = project.buffer_get_mut_or_err("buffer")?;
+        let layer = buffer.layer_get_mut_or_err("layer")?;
+        layer.comment_set(1, Some("Checksum".to_string()))?;
+        layer.bookmark_set(4, Some("header checksum".to_string()))?;
+        project.buffer_get_mut_or_err("other")?.layer_get_mut_or_err("layer")?.comment_set(0, Some("not the checksum".to_string()))?;
+
+        let found = |query: &H2SearchQuery| {
+            project.search(query).map(|matches| matches.into_iter().map(|m| (m.buffer, m.range, m.field, m.text)).collect::<Vec<_>>())
+        };
+
+        // Exact substrings are case sensitive
+        assert_eq!(vec![
+            ("buffer".to_string(), 4..6, H2SearchField::Name,    "header checksum".to_string()),
+            ("other".to_string(),  0..2, H2SearchField::Comment, "not the checksum".to_string()),
+        ], found(&H2SearchQuery::new(H2SearchPattern::Substring("checksum".to_string())))?);
+
+        let mut query = H2SearchQuery::new(H2SearchPattern::SubstringIgnoreCase("checksum".to_string()));
+        assert_eq!(3, found(&query)?.len());
+
+        // Narrowed down by field, buffer, and filter
+        query.fields = vec![H2SearchField::Comment];
+        assert_eq!(2, found(&query)?.len());
+        query.buffers = Some(vec!["buffer".to_string()]);
+        assert_eq!(vec![("buffer".to_string(), 0..2, H2SearchField::Comment, "Checksum".to_string())], found(&query)?);
+        query.filter = H2EntryFilter { range: Some(2..8), ..Default::default() };
+        assert_eq!(0, found(&query)?.len());
+
+        // Displays, with a regex
+        let mut query = H2SearchQuery::new(H2SearchPattern::Regex("^(1|46[0-9]+)$".to_string()));
+        query.fields = vec![H2SearchField::Display];
+        assert_eq!(vec![
+            ("buffer".to_string(), 0..2, H2SearchField::Display, "1".to_string()),
+            ("other".to_string(),  0..2, H2SearchField::Display, "4660".to_string()),
+        ], found(&query)?);
+
+        // Errors
+        assert!(project.search(&H2SearchQuery::new(H2SearchPattern::Regex("(".to_string()))).is_err());
+        let mut query = H2SearchQuery::new(H2SearchPattern::Substring("x".to_string()));
+        query.buffers = Some(vec!["nobuffer".to_string()]);
+        assert!(project.search(&query).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_report() -> SimpleResult<()> {
+        let mut project = H2Project::new("name", "1.0");
+        project.buffer_insert("buffer", H2Buffer::new("buffer", b"\x00\x01\x02\x03\x04\x05\x06\x07".to_vec(), 0x100)?)?;
+        project.buffer_insert("other", H2Buffer::new("other", b"\xff".to_vec(), 0)?)?;
+
+        for layer in vec!["b", "a"] {
+            let id = project.id_allocate();
+            project.buffer_get_mut_or_err("buffer")?.layer_add(layer, id)?;
+        }
+
+        let u16 = H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer());
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+        for (layer, datatype, offset) in [("b", &u8, 7), ("a", &u16, 0), ("a", &u8, 4), ("b", &u16, 2)] {
+            let resolved = project.buffer_get_or_err("buffer")?.peek(datatype, offset)?;
+            let id = project.id_allocate();
+            project.buffer_get_mut_or_err("buffer")?.layer_get_mut_or_err(layer)?.entry_create(resolved, None, id, H2Provenance::default())?;
+        }
+        project.buffer_get_mut_or_err("buffer")?.layer_get_mut_or_err("a")?.comment_set(1, Some("first".to_string()))?;
+
+        let resolved = project.buffer_get_or_err("buffer")?.peek(&u8, 2)?;
+        project.buffer_get_mut_or_err("buffer")?.layer_get_mut_or_err("b")?.entry_get_mut_or_err(2)?.interpretation_add("byte", resolved, Some(u8.clone()))?;
+
+        // The short version
+        assert_eq!(vec![
+            "Name: name, version: 1.0",
+            " Buffer: buffer (base 0x100 / 0x8 bytes long): 2 layer(s), 4 entries",
+            " Buffer: other (base 0x0 / 0x1 bytes long): 0 layer(s), 0 entries",
+        ], project.to_string().lines().collect::<Vec<_>>());
+
+        // Everything
+        assert_eq!(vec![
+            "Buffer: buffer (base 0x100 / 0x8 bytes long)",
+            " Layer: a",
+            "  0x00000100 - 0x00000102  1 ; first",
+            "  0x00000104 - 0x00000105  4",
+            " Layer: b",
+            "  0x00000102 - 0x00000104  (default) 515",
+            "   or (byte) 2",
+            "  0x00000107 - 0x00000108  7",
+            "Buffer: other (base 0x0 / 0x1 bytes long)",
+        ], project.report(&H2ReportOptions::default())?.lines().collect::<Vec<_>>());
+
+        // Just part of one layer in one buffer, with bytes and a limit
+        let options = H2ReportOptions {
+            buffers: Some(vec!["buffer".to_string()]),
+            filter: H2EntryFilter {
+                layer: Some("a".to_string()),
+                range: Some(0..8),
+                ..Default::default()
+            },
+            max_lines: Some(1),
+            bytes: 1,
+        };
+        assert_eq!(vec![
+            "Buffer: buffer (base 0x100 / 0x8 bytes long)",
+            " Layer: a",
+            "  0x00000100 - 0x00000102  00  1 ; first",
+            " ... 1 more entries",
+        ], project.report(&options)?.lines().collect::<Vec<_>>());
+
+        // Bad buffer
+        let options = H2ReportOptions {
+            buffers: Some(vec!["nobuffer".to_string()]),
+            ..Default::default()
+        };
+        assert!(project.report(&options).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_nested() -> SimpleResult<()> {
+        let mut project = H2Project::new("name", "1.0");
+        project.buffer_insert("buffer", H2Buffer::new("buffer", b"\x01\x02\x03".to_vec(), 0x100)?)?;
+        let id = project.id_allocate();
+        project.buffer_get_mut_or_err("buffer")?.layer_add("layer", id)?;
+
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+        let t = H2Struct::new(vec![
+            ("a".to_string(), u8.clone()),
+            ("b".to_string(), H2Array::new(2, u8.clone())?),
+        ])?;
+
+        let resolved = project.buffer_get_or_err("buffer")?.peek(&t, 0)?;
+        let mut entry = H2Entry::new(resolved, Some(t), project.id_allocate(), H2Provenance::default());
+        entry.set_nesting(H2Nesting::full());
+        project.buffer_get_mut_or_err("buffer")?.layer_get_mut_or_err("layer")?.entry_insert(entry)?;
+
+        assert_eq!(vec![
+            "Buffer: buffer (base 0x100 / 0x3 bytes long)",
+            " Layer: layer",
+            "  0x00000100 - 0x00000103  { a: 1, b: [ 2, 3 ] }",
+            "    0x00000100 - 0x00000101  a: 1",
+            "    0x00000101 - 0x00000103  b: [ 2, 3 ]",
+            "      0x00000101 - 0x00000102  2",
+            "      0x00000102 - 0x00000103  3",
+        ], project.report(&H2ReportOptions::default())?.lines().collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_pointers() -> SimpleResult<()> {
+        let mut project = H2Project::new("name", "1.0");
+
+        //               main+0x6      main          past the end  before main   before the base
+        let data = b"\x16\x10\x00\x00\x10\x10\x00\x00\x00\x20\x00\x00\x04\x10\x00\x00\x05\x00\x00\x00AAAA".to_vec();
+        project.buffer_insert("buffer", H2Buffer::new("buffer", data, 0x1000)?)?;
+        let id = project.id_allocate();
+        project.buffer_get_mut_or_err("buffer")?.layer_add("layer", id)?;
+
+        let buffer_id = project.buffer_get_or_err("buffer")?.id();
+        project.symbol_set(H2Symbol::new("main", H2SymbolScope::Global, buffer_id, 0x10, H2Provenance::default()))?;
+
+        let pointer = H2Pointer::new(IntegerReader::U32(Endian::Little), HexFormatter::pretty_integer());
+        let pointers = H2Struct::new(vec![
+            ("a".to_string(), pointer.clone()),
+            ("b".to_string(), pointer.clone()),
+        ])?;
+
+        // Two pointers in a nested struct, then three on their own
+        let resolved = project.peek("buffer", &pointers, 0)?;
+        let mut entry = H2Entry::new(resolved, Some(pointers), project.id_allocate(), H2Provenance::default());
+        entry.set_nesting(H2Nesting::full());
+        project.buffer_get_mut_or_err("buffer")?.layer_get_mut_or_err("layer")?.entry_insert(entry)?;
+
+        for offset in vec![8, 12, 16] {
+            let resolved = project.peek("buffer", &pointer, offset)?;
+            let id = project.id_allocate();
+            project.buffer_get_mut_or_err("buffer")?.layer_get_mut_or_err("layer")?.entry_create(resolved, Some(pointer.clone()), id, H2Provenance::default())?;
+        }
+
+        assert_eq!(vec![
+            "Buffer: buffer (base 0x1000 / 0x18 bytes long)",
+            " Layer: layer",
+            "  0x00001000 - 0x00001008  { a: 0x00001016, b: 0x00001010 }",
+            "    0x00001000 - 0x00001004  a: 0x00001016 (main+0x6)",
+            "    0x00001004 - 0x00001008  b: 0x00001010 (main)",
+            "  0x00001008 - 0x0000100c  0x00002000",
+            "  0x0000100c - 0x00001010  0x00001004",
+            "  0x00001010 - 0x00001014  0x00000005",
+        ], project.report(&H2ReportOptions::default())?.lines().collect::<Vec<_>>());
+
+        // Plain integers aren't looked up
+        let resolved = project.peek("buffer", &H2Integer::new(IntegerReader::U32(Endian::Little), HexFormatter::pretty_integer()), 0)?;
+        assert_eq!("0x00001016", project.render_value("buffer", &resolved)?);
+        assert!(project.render_value("nobuffer", &resolved).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_enum_rerender() -> SimpleResult<()> {
+        let mut project = H2Project::new("name", "1.0");
+        project.buffer_insert("buffer", H2Buffer::new("buffer", b"\x01\x00\x00\x00\x02\x00\x00\x00\x03\x04".to_vec(), 0)?)?;
+        let id = project.id_allocate();
+        project.buffer_get_mut_or_err("buffer")?.layer_add("default", id)?;
+
+        // Two entries made from a type that uses the enum, one from a type
+        // that doesn't, and one that doesn't know its type
+        let item = H2Struct::new(vec![
+            ("id".to_string(), H2Enum::new(IntegerReader::U32(Endian::Little), "TerrariaItem")?),
+        ])?;
+        let u8 = H2Integer::new(IntegerReader::U8, DefaultFormatter::new_integer());
+
+        for (t, offset, has_origin) in vec![(&item, 0, true), (&item, 4, true), (&u8, 8, true), (&u8, 9, false)] {
+            let resolved = project.peek("buffer", t, offset)?;
+            let origin = if has_origin { Some(t.clone()) } else { None };
+            let id = project.id_allocate();
+            project.buffer_get_mut_or_err("buffer")?.layer_get_mut_or_err("default")?.entry_create(resolved, origin, id, H2Provenance::default())?;
+        }
+
+        project.enum_member_set("TerrariaItem", 1, Some("Renamed"))?;
+        let layer = project.buffer_get_or_err("buffer")?.layer_get("default").unwrap();
+        assert_eq!("TerrariaItem::Renamed", layer.entry_get(0)?.unwrap().resolved().children[0].display);
+        assert_ne!("TerrariaItem::Renamed", layer.entry_get(4)?.unwrap().resolved().children[0].display);
+        assert_eq!("3", layer.entry_get(8)?.unwrap().resolved().display);
+
+        // The u8 with a type is skipped
+        assert_eq!(3, project.enum_rerender("TerrariaItem"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_usage() -> SimpleResult<()> {
+        let mut project = H2Project::new("name", "1.0");
+        project.buffer_insert("buffer2", H2Buffer::new("buffer2", vec![0; 1000], 0)?)?;
+        project.buffer_insert("buffer1", H2Buffer::new("buffer1", vec![0; 100], 0)?)?;
+
+        let id = project.id_allocate();
+        project.buffer_get_mut_or_err("buffer1")?.layer_add("layer", id)?;
+
+        let empty = project.memory_usage();
+        assert_eq!(2, empty.buffers.len());
+        assert_eq!("buffer1", empty.buffers[0].name);
+        assert_eq!(100, empty.buffers[0].data);
+        assert_eq!(1000, empty.buffers[1].data);
+        assert_eq!(0, empty.buffers[0].layers[0].entry_count);
+        assert_eq!(0, empty.buffers[0].layers[0].total());
+        assert_eq!(None, empty.history);
+
+        // Add a couple entries and a comment
+        let t = H2Integer::new(IntegerReader::U32(Endian::Big), DefaultFormatter::new_integer());
+        for offset in vec![0, 4] {
+            let resolved = project.buffer_get_or_err("buffer1")?.peek(&t, offset)?;
+            let id = project.id_allocate();
+            project.buffer_get_mut_or_err("buffer1")?.layer_get_mut_or_err("layer")?.entry_create(resolved, Some(t.clone()), id, H2Provenance::default())?;
+        }
+        project.buffer_get_mut_or_err("buffer1")?.layer_get_mut_or_err("layer")?.comment_set(0, Some("Hello".to_string()))?;
+
+        let usage = project.memory_usage();
+        let layer = &usage.buffers[0].layers[0];
+        assert_eq!("layer", layer.name);
+        assert_eq!(2, layer.entry_count);
+        assert!(layer.entries > 0);
+        assert!(layer.annotations > 5);
+        assert!(usage.total() > empty.total());
+        assert_eq!(empty.buffers[1], usage.buffers[1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot() -> SimpleResult<()> {
+        let mut project = H2Project::new("name", "1.0");
+        project.buffer_insert("buffer1", H2Buffer::new("buffer1", b"ABCD".to_vec(), 0)?)?;
+        project.buffer_insert("buffer2", H2Buffer::new("buffer2", b"EFGH".to_vec(), 0)?)?;
+
+        let snapshot = project.snapshot();
+
+        // Nothing is copied until something changes - even reading through
+        // a mutable buffer doesn't count
+        assert_eq!(4, project.buffer_get_mut_or_err("buffer1")?.len());
+        assert!(Arc::ptr_eq(&project.buffers["buffer1"], &snapshot.buffers["buffer1"]));
+
+        let id = project.id_allocate();
+        project.buffer_get_mut_or_err("buffer1")?.layer_add("layer", id)?;
+        project.buffer_get_mut_or_err("buffer1")?.edit(b"X".to_vec(), 0)?;
+
+        // Only the changed buffer was copied, and the snapshot didn't see it
+        assert!(!Arc::ptr_eq(&project.buffers["buffer1"], &snapshot.buffers["buffer1"]));
+        assert!(Arc::ptr_eq(&project.buffers["buffer2"], &snapshot.buffers["buffer2"]));
+        assert_eq!(b"XBCD".to_vec(), project.buffer_get_or_err("buffer1")?.data);
+        assert_eq!(b"ABCD".to_vec(), snapshot.buffer_get_or_err("buffer1")?.data);
+        assert!(!snapshot.buffer_get_or_err("buffer1")?.layer_exists("layer"));
+
+        // It can be read from another thread while the project keeps changing
+        let reader = {
+            let snapshot = snapshot.clone();
+            std::thread::spawn(move || snapshot.buffer_get("buffer2").map(|b| b.data.clone()))
+        };
+        project.buffer_get_mut_or_err("buffer2")?.edit(b"Y".to_vec(), 0)?;
+        assert_eq!(Some(b"EFGH".to_vec()), reader.join().unwrap());
+
+        Ok(())
+    }
+
     #[test]
     fn test_buffer_remove_no_such_buffer() -> SimpleResult<()> {
         let mut project = H2Project::new("name", "1.0");
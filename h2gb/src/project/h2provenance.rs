@@ -0,0 +1,200 @@
+//! Where an entry came from.
+//!
+//! Every entry remembers who created it - an analyzer, a script, or the user
+//! - and when. That's what lets a front-end tell generated annotations from
+//! hand-made ones, and lets an analyzer's output be found (and cleared)
+//! without touching anything else.
+//!
+//! Entries also remember any transformations that were applied in place to
+//! part of them (see [`crate::actions::ActionEntryTransform`]), so the
+//! original bytes can be worked out again when writing back.
+
+use serde::{Serialize, Deserialize};
+use simple_error::{bail, SimpleError, SimpleResult};
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use h2transformation::Transformation;
+
+/// Who created an entry.
+///
+/// Creators display (and parse) as `user`, `analyzer:<name>`, or
+/// `script:<name>`, so they can be exported and typed in.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum H2Creator {
+    /// Made by hand - this is also what entries from before provenance was
+    /// tracked get
+    #[default]
+    User,
+
+    /// Made by an analyzer, by name (see [`crate::analyzer::analyzers`])
+    Analyzer(String),
+
+    /// Made by a script, by name
+    Script(String),
+}
+
+impl H2Creator {
+    pub fn analyzer(name: &str) -> Self {
+        Self::Analyzer(name.to_string())
+    }
+
+    pub fn script(name: &str) -> Self {
+        Self::Script(name.to_string())
+    }
+}
+
+impl fmt::Display for H2Creator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::User           => write!(f, "user"),
+            Self::Analyzer(name) => write!(f, "analyzer:{}", name),
+            Self::Script(name)   => write!(f, "script:{}", name),
+        }
+    }
+}
+
+impl FromStr for H2Creator {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> SimpleResult<Self> {
+        match s.split_once(':') {
+            None if s == "user"                          => Ok(Self::User),
+            Some(("analyzer", name)) if !name.is_empty() => Ok(Self::Analyzer(name.to_string())),
+            Some(("script", name)) if !name.is_empty()   => Ok(Self::Script(name.to_string())),
+            _                                            => bail!("Unknown creator: {} (expected user, analyzer:<name>, or script:<name>)", s),
+        }
+    }
+}
+
+/// A length-preserving transformation that was applied in place to part of
+/// an entry.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct H2RangeTransformation {
+    /// The bytes that were transformed, relative to the start of the entry
+    /// (so it stays right if the entry moves)
+    pub range: Range<usize>,
+
+    pub transformation: Transformation,
+}
+
+/// Who created an entry, and when.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct H2Provenance {
+    creator: H2Creator,
+
+    // Seconds since the UNIX epoch; zero means we don't know
+    created: u64,
+
+    // In-place transformations, in the order they were applied
+    #[serde(default)]
+    transformations: Vec<H2RangeTransformation>,
+}
+
+impl H2Provenance {
+    /// Provenance for something `creator` is making right now.
+    pub fn new(creator: H2Creator) -> Self {
+        Self {
+            creator: creator,
+            created: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            transformations: vec![],
+        }
+    }
+
+    /// Provenance with a specific timestamp (in seconds since the UNIX epoch).
+    pub fn new_at(creator: H2Creator, created: u64) -> Self {
+        Self {
+            creator: creator,
+            created: created,
+            transformations: vec![],
+        }
+    }
+
+    pub fn creator(&self) -> &H2Creator {
+        &self.creator
+    }
+
+    /// When the entry was created, in seconds since the UNIX epoch, if known.
+    pub fn created(&self) -> Option<u64> {
+        match self.created {
+            0 => None,
+            t => Some(t),
+        }
+    }
+
+    /// Was this made by hand?
+    pub fn is_user(&self) -> bool {
+        self.creator == H2Creator::User
+    }
+
+    /// The transformations applied in place to parts of the entry, oldest
+    /// first.
+    pub fn transformations(&self) -> &[H2RangeTransformation] {
+        &self.transformations
+    }
+
+    /// The same provenance, with one more in-place transformation.
+    pub fn with_transformation(mut self, range: Range<usize>, transformation: Transformation) -> Self {
+        self.transformations.push(H2RangeTransformation {
+            range: range,
+            transformation: transformation,
+        });
+
+        self
+    }
+
+    /// Undo the in-place transformations on an entry's bytes (starting at
+    /// the start of the entry), newest first, to get back what was
+    /// originally there.
+    pub fn untransform(&self, data: &[u8]) -> SimpleResult<Vec<u8>> {
+        let mut data = data.to_vec();
+
+        for t in self.transformations.iter().rev() {
+            if t.range.end > data.len() {
+                bail!("Transformed range 0x{:x?} is past the end of the entry", t.range);
+            }
+
+            let original = t.transformation.untransform(&data[t.range.clone()].to_vec())?;
+            if original.len() != t.range.len() {
+                bail!("Untransforming 0x{:x?} with {} changed its length", t.range, t.transformation);
+            }
+
+            data.splice(t.range.clone(), original);
+        }
+
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_creator_strings() -> SimpleResult<()> {
+        for creator in vec![H2Creator::User, H2Creator::analyzer("bson"), H2Creator::script("my:script")] {
+            assert_eq!(creator, creator.to_string().parse()?);
+        }
+
+        assert_eq!("analyzer:dex", H2Creator::analyzer("dex").to_string());
+        assert!("analyzer:".parse::<H2Creator>().is_err());
+        assert!("robot".parse::<H2Creator>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_provenance() {
+        let provenance = H2Provenance::new(H2Creator::analyzer("bson"));
+        assert!(provenance.created().is_some());
+        assert!(!provenance.is_user());
+
+        // Old entries don't know when they were made
+        assert_eq!(None, H2Provenance::default().created());
+        assert!(H2Provenance::default().is_user());
+    }
+}
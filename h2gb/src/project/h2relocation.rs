@@ -0,0 +1,191 @@
+//! Move annotations around when part of a buffer changes size.
+//!
+//! Most edits overwrite bytes in place, so nothing needs to move. But a
+//! variable-length value - a length-prefixed string, say - can grow or shrink
+//! when it's edited, and everything after it shifts along. An
+//! [`H2Relocation`] describes that change, and is used to work out where each
+//! entry, point, comment, and bookmark ends up.
+//!
+//! Anything after the change just moves. Anything before it stays put.
+//! Anything that overlaps the changed bytes can't be moved sensibly, and is
+//! reported as invalidated (see [`H2RelocationReport`]) - relocating a buffer
+//! with invalidated annotations is an error, so nothing is ever silently
+//! lost.
+//!
+//! Only the bytes that were edited are recomputed. A length prefix that's
+//! part of the edited value (like an `LPString`'s) is written with the new
+//! length, but lengths and offsets stored elsewhere - a header's file size, or
+//! a pointer to something that moved - aren't updated.
+
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::ops::Range;
+
+use h2datatype::ResolvedType;
+
+/// `old_length` bytes at `offset` are being replaced by `new_length` bytes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct H2Relocation {
+    pub offset: usize,
+    pub old_length: usize,
+    pub new_length: usize,
+}
+
+impl H2Relocation {
+    pub fn new(offset: usize, old_length: usize, new_length: usize) -> Self {
+        Self {
+            offset: offset,
+            old_length: old_length,
+            new_length: new_length,
+        }
+    }
+
+    /// The relocation that puts things back.
+    pub fn inverse(&self) -> Self {
+        Self::new(self.offset, self.new_length, self.old_length)
+    }
+
+    /// Does anything actually move?
+    pub fn is_noop(&self) -> bool {
+        self.old_length == self.new_length
+    }
+
+    /// The bytes that are being replaced.
+    pub fn old_range(&self) -> Range<usize> {
+        self.offset..(self.offset + self.old_length)
+    }
+
+    /// Where a range ends up, or [`None`] if it overlaps the changed bytes.
+    pub fn relocate_range(&self, range: Range<usize>) -> Option<Range<usize>> {
+        let old_end = self.offset + self.old_length;
+
+        if range.end <= self.offset {
+            Some(range)
+        } else if range.start >= old_end {
+            Some((range.start - old_end + self.offset + self.new_length)..(range.end - old_end + self.offset + self.new_length))
+        } else {
+            None
+        }
+    }
+
+    /// Where a single offset - a point, comment, or bookmark - ends up, or
+    /// [`None`] if it's inside the changed bytes.
+    ///
+    /// The first changed byte counts as "before", so a comment on an edited
+    /// value stays with it.
+    pub fn relocate_offset(&self, offset: usize) -> Option<usize> {
+        let old_end = self.offset + self.old_length;
+
+        if offset <= self.offset {
+            Some(offset)
+        } else if offset >= old_end {
+            Some(offset - old_end + self.offset + self.new_length)
+        } else {
+            None
+        }
+    }
+
+    /// Move a resolved value, and all of its children.
+    ///
+    /// The value has to be entirely before or after the changed bytes (see
+    /// [`Self::relocate_range`]); anything else is left alone.
+    pub fn relocate_resolved(&self, resolved: &mut ResolvedType) {
+        let convert = |range: &Range<u64>| (range.start as usize)..(range.end as usize);
+
+        if let Some(range) = self.relocate_range(convert(&resolved.actual_range)) {
+            resolved.actual_range = (range.start as u64)..(range.end as u64);
+        }
+
+        if let Some(range) = self.relocate_range(convert(&resolved.aligned_range)) {
+            resolved.aligned_range = (range.start as u64)..(range.end as u64);
+        }
+
+        for child in resolved.children.iter_mut() {
+            self.relocate_resolved(child);
+        }
+    }
+}
+
+/// What kind of annotation was invalidated.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum H2AnnotationKind {
+    Entry,
+    Point,
+    Comment,
+    Bookmark,
+}
+
+impl fmt::Display for H2AnnotationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Entry    => write!(f, "entry"),
+            Self::Point    => write!(f, "point"),
+            Self::Comment  => write!(f, "comment"),
+            Self::Bookmark => write!(f, "bookmark"),
+        }
+    }
+}
+
+/// An annotation that a relocation can't move.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct H2Invalidated {
+    pub layer: String,
+    pub offset: usize,
+    pub kind: H2AnnotationKind,
+
+    /// The entry's display, the comment, or the bookmark's name
+    pub display: String,
+}
+
+impl fmt::Display for H2Invalidated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} @ 0x{:x} in layer '{}' ({})", self.kind, self.offset, self.layer, self.display)
+    }
+}
+
+/// What a relocation would do to a buffer's annotations - see
+/// [`crate::project::H2Buffer::relocation_check`].
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct H2RelocationReport {
+    /// How many annotations would move
+    pub moved: usize,
+
+    /// The annotations that would be invalidated
+    pub invalidated: Vec<H2Invalidated>,
+}
+
+impl H2RelocationReport {
+    /// Can the relocation go ahead?
+    pub fn is_clean(&self) -> bool {
+        self.invalidated.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_relocate() {
+        // 2 bytes at 4 become 5 bytes
+        let r = H2Relocation::new(4, 2, 5);
+
+        assert_eq!(Some(0..4),   r.relocate_range(0..4));
+        assert_eq!(Some(9..11),  r.relocate_range(6..8));
+        assert_eq!(None,         r.relocate_range(3..5));
+        assert_eq!(None,         r.relocate_range(4..6));
+        assert_eq!(None,         r.relocate_range(5..7));
+
+        assert_eq!(Some(4),  r.relocate_offset(4));
+        assert_eq!(None,     r.relocate_offset(5));
+        assert_eq!(Some(9),  r.relocate_offset(6));
+
+        // And back again
+        let inverse = r.inverse();
+        assert_eq!(Some(6..8), inverse.relocate_range(9..11));
+        assert_eq!(Some(6),    inverse.relocate_offset(9));
+        assert!(!r.is_noop());
+    }
+}
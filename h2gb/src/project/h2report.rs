@@ -0,0 +1,34 @@
+//! Options for a text report of a project - see
+//! [`crate::project::H2Project::report`].
+//!
+//! Printing a project (with [`std::fmt::Display`]) only gives a short summary,
+//! since a big file can have hundreds of thousands of entries. A report is
+//! the full listing - every entry, with its comments - but it can be cut down
+//! to the buffers, layers, and ranges that matter, and capped at a number of
+//! lines.
+
+use serde::{Serialize, Deserialize};
+
+use crate::project::H2EntryFilter;
+
+/// What to include in a report - see
+/// [`crate::project::H2Project::report`].
+///
+/// The default includes every entry in every buffer, with no limit.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct H2ReportOptions {
+    /// Only these buffers, in this order (every buffer, sorted by name, if
+    /// `None`)
+    pub buffers: Option<Vec<String>>,
+
+    /// Only entries that match this filter - this is how to pick a layer or
+    /// a range
+    pub filter: H2EntryFilter,
+
+    /// The most entries to list for each buffer; the rest are counted, but
+    /// not listed
+    pub max_lines: Option<usize>,
+
+    /// How many bytes of each entry to show in hex (0 for none)
+    pub bytes: usize,
+}
@@ -0,0 +1,118 @@
+//! Search entries by text - see [`crate::project::H2Project::search`].
+//!
+//! This is for finding things that have already been annotated ("where did I
+//! mark the checksum?"), so it looks at the text around entries - what they
+//! display as, their comments, and their names - rather than at the bytes.
+
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError};
+
+use std::ops::Range;
+
+use crate::project::H2EntryFilter;
+
+/// Which text about an entry to search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum H2SearchField {
+    /// What the entry displays as (the interpretation that's shown)
+    Display,
+
+    /// Comments inside the entry
+    Comment,
+
+    /// The entry's field name, and bookmarks inside the entry
+    Name,
+}
+
+/// How to match the text.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum H2SearchPattern {
+    /// The text contains this string exactly
+    Substring(String),
+
+    /// The text contains this string, ignoring case
+    SubstringIgnoreCase(String),
+
+    /// The text matches this regular expression somewhere (see the [`regex`]
+    /// crate for the syntax; `(?i)` ignores case)
+    Regex(String),
+}
+
+/// What to search for - see [`crate::project::H2Project::search`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct H2SearchQuery {
+    pub pattern: H2SearchPattern,
+
+    /// Which text to look at (every field, if empty)
+    pub fields: Vec<H2SearchField>,
+
+    /// Only these buffers, in this order (every buffer, sorted by name, if
+    /// `None`)
+    pub buffers: Option<Vec<String>>,
+
+    /// Only entries that match this filter
+    pub filter: H2EntryFilter,
+}
+
+impl H2SearchQuery {
+    /// Search every field of every entry.
+    pub fn new(pattern: H2SearchPattern) -> Self {
+        Self {
+            pattern: pattern,
+            fields: vec![],
+            buffers: None,
+            filter: H2EntryFilter::default(),
+        }
+    }
+
+    pub fn searches(&self, field: H2SearchField) -> bool {
+        self.fields.is_empty() || self.fields.contains(&field)
+    }
+}
+
+/// One piece of text that matched a search.
+///
+/// An entry can match more than once - say, in its display and in a comment
+/// - so there's one of these for each.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct H2SearchMatch {
+    pub buffer: String,
+    pub layer: String,
+
+    /// Where the entry is, relative to the buffer
+    pub range: Range<usize>,
+
+    pub field: H2SearchField,
+
+    /// The whole text that matched
+    pub text: String,
+}
+
+/// A compiled [`H2SearchPattern`], so a regex is only parsed once per
+/// search.
+pub(crate) enum H2Matcher {
+    Substring(String),
+    SubstringIgnoreCase(String),
+    Regex(Regex),
+}
+
+impl H2Matcher {
+    pub(crate) fn new(pattern: &H2SearchPattern) -> SimpleResult<Self> {
+        Ok(match pattern {
+            H2SearchPattern::Substring(s)           => Self::Substring(s.clone()),
+            H2SearchPattern::SubstringIgnoreCase(s) => Self::SubstringIgnoreCase(s.to_lowercase()),
+            H2SearchPattern::Regex(r)               => Self::Regex(Regex::new(r).map_err(|e| {
+                SimpleError::new(format!("Bad search pattern {}: {}", r, e))
+            })?),
+        })
+    }
+
+    pub(crate) fn matches(&self, text: &str) -> bool {
+        match self {
+            Self::Substring(s)           => text.contains(&s[..]),
+            Self::SubstringIgnoreCase(s) => text.to_lowercase().contains(&s[..]),
+            Self::Regex(r)               => r.is_match(text),
+        }
+    }
+}
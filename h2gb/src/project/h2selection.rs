@@ -0,0 +1,318 @@
+//! A selection - the bytes a user highlighted - and what can be done with it.
+//!
+//! Front-ends and scripts both need to act on "the selected bytes": turn them
+//! into entries, pull them out into a new buffer, or just copy them. An
+//! [`H2Selection`] is a buffer plus a set of ranges, and its operations all
+//! treat those ranges the same way, so every front-end gets the same results.
+//!
+//! Like the [`crate::import`] functions, nothing here changes the project;
+//! operations that would change something return actions instead. The one
+//! exception is [`H2Selection::export`], which writes the selected bytes to a
+//! file - the everyday "carve this blob out" operation.
+
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+use simple_error::{bail, SimpleResult, SimpleError};
+
+use h2datatype::H2Type;
+use h2datatype::simple::H2Blob;
+use h2transformation::Transformation;
+
+use crate::actions::{Action, ActionBufferCreateFromBytes, ActionEntryCreate};
+use crate::project::{H2Entry, H2Project};
+
+/// Something to do to the selected bytes before they're used.
+#[derive(Debug, Clone)]
+pub enum H2SelectionTransform {
+    /// Run the bytes through a transformation (eg, hex-decode them)
+    Transform(Transformation),
+
+    /// Run the bytes backwards through a transformation (eg, hex-encode them)
+    Untransform(Transformation),
+}
+
+/// One or more ranges of a single buffer.
+///
+/// The ranges are always kept sorted, and ranges that overlap or touch are
+/// combined, so two selections of the same bytes are always equal.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct H2Selection {
+    buffer: String,
+    ranges: Vec<Range<usize>>,
+}
+
+impl H2Selection {
+    /// Create a selection. Empty ranges are ignored.
+    pub fn new(buffer: &str, ranges: Vec<Range<usize>>) -> Self {
+        let mut selection = Self {
+            buffer: buffer.to_string(),
+            ranges: vec![],
+        };
+
+        for range in ranges {
+            selection.add(range);
+        }
+
+        selection
+    }
+
+    /// Select the bytes an entry covers (including its alignment padding).
+    pub fn from_entry(buffer: &str, entry: &H2Entry) -> Self {
+        let range = &entry.resolved().aligned_range;
+
+        Self::new(buffer, vec![(range.start as usize)..(range.end as usize)])
+    }
+
+    pub fn buffer(&self) -> &str {
+        &self.buffer
+    }
+
+    /// The selected ranges, sorted and non-overlapping.
+    pub fn ranges(&self) -> &[Range<usize>] {
+        &self.ranges
+    }
+
+    /// Add a range, merging it with anything it overlaps or touches.
+    pub fn add(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+
+        let mut merged = range;
+        let mut ranges: Vec<Range<usize>> = vec![];
+        for existing in self.ranges.drain(..) {
+            if existing.end < merged.start || existing.start > merged.end {
+                ranges.push(existing);
+            } else {
+                merged = existing.start.min(merged.start)..existing.end.max(merged.end);
+            }
+        }
+
+        ranges.push(merged);
+        ranges.sort_by_key(|r| r.start);
+        self.ranges = ranges;
+    }
+
+    /// The total number of selected bytes.
+    pub fn len(&self) -> usize {
+        self.ranges.iter().map(|r| r.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.ranges.iter().any(|r| r.contains(&offset))
+    }
+
+    /// Make sure the selection can be used with the project.
+    fn validate(&self, project: &H2Project) -> SimpleResult<()> {
+        let buffer = project.buffer_get_or_err(&self.buffer)?;
+
+        if self.is_empty() {
+            bail!("The selection in buffer {} is empty", self.buffer);
+        }
+
+        if let Some(last) = self.ranges.last() {
+            if last.end > buffer.len() {
+                bail!("The selection 0x{:x?} goes past the end of buffer {} (0x{:x} bytes)", last, self.buffer, buffer.len());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the selected bytes, with the ranges joined together in order.
+    pub fn bytes(&self, project: &H2Project) -> SimpleResult<Vec<u8>> {
+        self.validate(project)?;
+        let buffer = project.buffer_get_or_err(&self.buffer)?;
+
+        let mut out = Vec::with_capacity(self.len());
+        for range in &self.ranges {
+            out.extend_from_slice(buffer.byte_range(range.clone())?);
+        }
+
+        Ok(out)
+    }
+
+    /// Get the selected bytes (see [`H2Selection::bytes`]) after running
+    /// them through a transformation.
+    pub fn transform(&self, project: &H2Project, transformation: &Transformation) -> SimpleResult<Vec<u8>> {
+        transformation.transform(&self.bytes(project)?)
+    }
+
+    /// Get the selected bytes after running them backwards through a
+    /// transformation.
+    pub fn untransform(&self, project: &H2Project, transformation: &Transformation) -> SimpleResult<Vec<u8>> {
+        transformation.untransform(&self.bytes(project)?)
+    }
+
+    /// Get the selected bytes, transformed (or untransformed) if requested.
+    pub fn export_bytes(&self, project: &H2Project, transform: Option<&H2SelectionTransform>) -> SimpleResult<Vec<u8>> {
+        match transform {
+            Some(H2SelectionTransform::Transform(t))   => self.transform(project, t),
+            Some(H2SelectionTransform::Untransform(t)) => self.untransform(project, t),
+            None                                       => self.bytes(project),
+        }
+    }
+
+    /// Write the selected bytes to a file, transformed (or untransformed) if
+    /// requested, and return how many bytes were written.
+    ///
+    /// To carve out a single entry, use [`H2Selection::from_entry`].
+    pub fn export(&self, project: &H2Project, path: impl AsRef<Path>, transform: Option<&H2SelectionTransform>) -> SimpleResult<usize> {
+        let path = path.as_ref();
+        let data = self.export_bytes(project, transform)?;
+
+        fs::write(path, &data).map_err(|e| {
+            SimpleError::new(format!("Couldn't write the selection to {}: {}", path.display(), e))
+        })?;
+
+        Ok(data.len())
+    }
+
+    /// Get an action that creates a new buffer from the selected bytes,
+    /// transformed (or untransformed) if requested.
+    pub fn to_buffer(&self, project: &H2Project, name: &str, transform: Option<&H2SelectionTransform>) -> SimpleResult<Action> {
+        Ok(ActionBufferCreateFromBytes::new(name, &self.export_bytes(project, transform)?, 0))
+    }
+
+    /// Get actions that create an entry at the start of each range.
+    ///
+    /// With a datatype, each entry is that type, which has to fit in its
+    /// range. Without one, each range becomes a blob of its length.
+    ///
+    /// Nothing is checked against the layer's existing entries here - that
+    /// happens when the actions are applied.
+    pub fn entries(&self, project: &H2Project, layer: &str, datatype: Option<&H2Type>) -> SimpleResult<Vec<Action>> {
+        self.validate(project)?;
+        project.buffer_get_or_err(&self.buffer)?.layer_get_or_err(layer)?;
+
+        self.ranges.iter().map(|range| {
+            let datatype = match datatype {
+                Some(t) => t.clone(),
+                None    => H2Blob::new(range.len() as u64)?,
+            };
+
+            let resolved = project.peek(&self.buffer, &datatype, range.start)?;
+            if resolved.aligned_range.end as usize > range.end {
+                bail!("{} at 0x{:x} doesn't fit in the selected range 0x{:x?}", datatype.describe(), range.start, range);
+            }
+
+            Ok(ActionEntryCreate::new(&self.buffer, layer, resolved, Some(datatype)))
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use redo::Record;
+
+    use generic_number::{DefaultFormatter, Endian, IntegerReader};
+    use h2datatype::simple::numeric::H2Integer;
+    use h2transformation::TransformHex;
+
+    use crate::actions::ActionLayerCreate;
+
+    #[test]
+    fn test_ranges() {
+        let mut selection = H2Selection::new("buffer", vec![8..10, 0..2, 5..5]);
+        assert_eq!(&[0..2, 8..10], selection.ranges());
+        assert_eq!(4, selection.len());
+
+        // Touching and overlapping ranges are merged
+        selection.add(2..4);
+        selection.add(7..9);
+        assert_eq!(&[0..4, 7..10], selection.ranges());
+
+        selection.add(3..8);
+        assert_eq!(&[0..10], selection.ranges());
+        assert!(selection.contains(9));
+        assert!(!selection.contains(10));
+
+        assert_eq!(H2Selection::new("buffer", vec![0..5, 5..10]), selection);
+    }
+
+    #[test]
+    fn test_operations() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"00112233414243", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        let selection = H2Selection::new("buffer", vec![2..4, 8..14]);
+        assert_eq!(b"11414243".to_vec(), selection.bytes(record.target())?);
+
+        let transformation = TransformHex::new();
+        assert_eq!(b"\x11ABC".to_vec(), selection.transform(record.target(), &transformation)?);
+
+        let transform = H2SelectionTransform::Transform(transformation);
+        record.apply(selection.to_buffer(record.target(), "carved", Some(&transform))?)?;
+        assert_eq!(b"\x11ABC".to_vec(), record.target().buffer_get_or_err("carved")?.data);
+
+        // Blobs by default, or a type in each range
+        for action in selection.entries(record.target(), "layer", None)? {
+            record.apply(action)?;
+        }
+        let layer = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?;
+        assert_eq!(2..4, layer.entry_get_or_err(2)?.resolved().actual_range);
+        assert_eq!(8..14, layer.entry_get_or_err(8)?.resolved().actual_range);
+
+        let u16 = H2Integer::new(IntegerReader::U16(Endian::Big), DefaultFormatter::new_integer());
+        let actions = H2Selection::new("buffer", vec![0..2, 4..6]).entries(record.target(), "layer", Some(&u16))?;
+        assert_eq!(2, actions.len());
+
+        // Doesn't fit
+        let u32 = H2Integer::new(IntegerReader::U32(Endian::Big), DefaultFormatter::new_integer());
+        assert!(H2Selection::new("buffer", vec![0..2]).entries(record.target(), "layer", Some(&u32)).is_err());
+
+        // Bad selections
+        assert!(H2Selection::new("buffer", vec![10..20]).bytes(record.target()).is_err());
+        assert!(H2Selection::new("buffer", vec![]).bytes(record.target()).is_err());
+        assert!(H2Selection::new("nobuffer", vec![0..1]).bytes(record.target()).is_err());
+        assert!(selection.entries(record.target(), "nolayer", None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_export() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+        record.apply(ActionBufferCreateFromBytes::new("buffer", b"\x00\x01ABCD", 0))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        // Carve out an entry
+        for action in H2Selection::new("buffer", vec![2..6]).entries(record.target(), "layer", None)? {
+            record.apply(action)?;
+        }
+        let entry = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?.entry_get_or_err(3)?;
+        let selection = H2Selection::from_entry("buffer", &entry);
+
+        let path = std::env::temp_dir().join(format!("h2gb-selection-{}-export", std::process::id()));
+        assert_eq!(4, selection.export(record.target(), &path, None)?);
+        assert_eq!(b"ABCD".to_vec(), fs::read(&path).unwrap());
+
+        // Untransform it on the way out
+        let transform = H2SelectionTransform::Untransform(TransformHex::new());
+        assert_eq!(8, selection.export(record.target(), &path, Some(&transform))?);
+        assert_eq!(b"41424344".to_vec(), fs::read(&path).unwrap());
+        assert_eq!(b"41424344".to_vec(), selection.export_bytes(record.target(), Some(&transform))?);
+        fs::remove_file(&path).unwrap();
+
+        // Somewhere that can't be written
+        let path = std::env::temp_dir().join("h2gb-no-such-directory").join("file");
+        assert!(selection.export(record.target(), &path, None).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+//! A summary of what's in a layer - how many of each type, how much of the
+//! buffer is covered, and so on.
+//!
+//! This is meant for dashboards ("how far along is this project?") and for
+//! testing analyzers ("does this file still produce 12 strings and 40
+//! integers?"). Everything is sorted, and serializes cleanly, so two
+//! summaries of the same layer always compare (and export) the same way.
+
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+
+/// One of the biggest entries in a layer - see [`H2LayerStats::largest`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct H2LargeEntry {
+    pub offset: usize,
+
+    /// The size, including alignment padding
+    pub size: usize,
+
+    /// The type's name (see [`h2datatype::H2Types::type_name`]), if the entry
+    /// knows its type
+    pub type_name: Option<String>,
+
+    pub display: String,
+}
+
+/// A summary of a single layer.
+///
+/// Created by [`crate::project::H2Layer::stats`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct H2LayerStats {
+    pub name: String,
+
+    /// The number of entries, not including points
+    pub entry_count: usize,
+
+    /// The number of zero-length entries
+    pub point_count: usize,
+
+    /// The number of entries (including points) of each type, by type name
+    pub types: BTreeMap<String, usize>,
+
+    /// The number of entries (including points) that don't know their type
+    pub untyped: usize,
+
+    /// Bytes covered by an entry, including alignment padding
+    pub covered_bytes: usize,
+
+    /// The size of the buffer the layer belongs to
+    pub total_bytes: usize,
+
+    pub comment_count: usize,
+    pub bookmark_count: usize,
+
+    /// The most common comments, as `(comment, count)`, most common first
+    /// (ties are sorted by comment)
+    pub common_comments: Vec<(String, usize)>,
+
+    /// The biggest entries, biggest first (ties are sorted by offset)
+    pub largest: Vec<H2LargeEntry>,
+}
+
+impl H2LayerStats {
+    /// The fraction of the buffer that's covered, from `0.0` to `1.0`.
+    pub fn coverage(&self) -> f64 {
+        match self.total_bytes {
+            0 => 0.0,
+            _ => self.covered_bytes as f64 / self.total_bytes as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+
+    use redo::Record;
+
+    use generic_number::{IntegerReader, Endian, HexFormatter};
+    use h2datatype::simple::H2Marker;
+    use h2datatype::simple::numeric::H2Integer;
+
+    use crate::actions::*;
+    use crate::project::H2Project;
+
+    #[test]
+    fn test_stats() -> SimpleResult<()> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &[0; 16], 0x1000))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        // Two u32s, a u8, a marker, and an entry with no type
+        let u32 = H2Integer::new(IntegerReader::U32(Endian::Big), HexFormatter::pretty_integer());
+        let u8 = H2Integer::new(IntegerReader::U8, HexFormatter::pretty_integer());
+        for (offset, t) in [(0, &u32), (8, &u32), (4, &u8)] {
+            let resolved = record.target().peek("buffer", t, offset)?;
+            record.apply(ActionEntryCreate::new("buffer", "layer", resolved, Some(t.clone())))?;
+        }
+
+        let marker = H2Marker::new("here");
+        let resolved = record.target().peek("buffer", &marker, 12)?;
+        record.apply(ActionEntryCreate::new("buffer", "layer", resolved, Some(marker)))?;
+
+        let resolved = record.target().peek("buffer", &u8, 14)?;
+        record.apply(ActionEntryCreate::new("buffer", "layer", resolved, None))?;
+
+        for (offset, comment) in [(0, "b"), (4, "a"), (8, "b"), (12, "c")] {
+            record.apply(ActionEntrySetComment::new("buffer", "layer", offset, Some(comment.to_string())))?;
+        }
+
+        let stats = record.target().buffer_get_or_err("buffer")?.layer_get_or_err("layer")?.stats(2);
+
+        assert_eq!("layer", stats.name);
+        assert_eq!(4, stats.entry_count);
+        assert_eq!(1, stats.point_count);
+        assert_eq!(vec![("H2Integer", 3), ("H2Marker", 1)], stats.types.iter().map(|(k, v)| (k.as_str(), *v)).collect::<Vec<_>>());
+        assert_eq!(1, stats.untyped);
+        assert_eq!(10, stats.covered_bytes);
+        assert_eq!(16, stats.total_bytes);
+        assert_eq!(0.625, stats.coverage());
+        assert_eq!(4, stats.comment_count);
+        assert_eq!(0, stats.bookmark_count);
+
+        // "b" is the most common, then "a" wins the tie with "c"
+        assert_eq!(vec![("b".to_string(), 2), ("a".to_string(), 1)], stats.common_comments);
+
+        assert_eq!(2, stats.largest.len());
+        assert_eq!(0, stats.largest[0].offset);
+        assert_eq!(8, stats.largest[1].offset);
+        assert_eq!(4, stats.largest[1].size);
+        assert_eq!(Some("H2Integer".to_string()), stats.largest[1].type_name);
+        assert_eq!("0x00000000", stats.largest[1].display);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+//! Names for places in a project's buffers.
+//!
+//! A symbol gives a name to an offset in a buffer - `player_name`, `main`,
+//! `.text` - so things can be found (and displayed) by name instead of by
+//! address. Symbols come from analyzers (an ELF's symbol table, say) or from
+//! the user, and belong to the project rather than a single layer, since a
+//! name means the same thing no matter which layer is looking at it.
+//!
+//! Symbols are scoped (see [`H2SymbolScope`]): a global symbol can be found
+//! from anywhere, while a local one can only be found from inside its own
+//! buffer. That way, two buffers can each have their own `main` without
+//! fighting over it.
+//!
+//! Symbols point at buffers by ID, so renaming a buffer doesn't break them.
+//! They aren't moved when data changes size (see
+//! [`crate::project::H2Relocation`]), since a symbol is usually where the
+//! data *should* be.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+
+use crate::project::{H2Id, H2Provenance};
+
+/// Where a symbol can be seen from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum H2SymbolScope {
+    /// Visible from anywhere in the project
+    Global,
+
+    /// Only visible from inside the buffer it points into
+    Local,
+}
+
+impl fmt::Display for H2SymbolScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Global => write!(f, "global"),
+            Self::Local  => write!(f, "local"),
+        }
+    }
+}
+
+/// A name for an offset in a buffer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct H2Symbol {
+    pub name: String,
+    pub scope: H2SymbolScope,
+
+    /// The buffer the symbol points into (which, for a local symbol, is also
+    /// the only buffer that can see it)
+    pub buffer: H2Id,
+    pub offset: usize,
+
+    pub provenance: H2Provenance,
+}
+
+impl H2Symbol {
+    pub fn new(name: &str, scope: H2SymbolScope, buffer: H2Id, offset: usize, provenance: H2Provenance) -> Self {
+        Self {
+            name: name.to_string(),
+            scope: scope,
+            buffer: buffer,
+            offset: offset,
+            provenance: provenance,
+        }
+    }
+
+    /// Does this symbol have the same name and scope as `name` / `scope`, in
+    /// `buffer`?
+    ///
+    /// A name can be used once globally, and once locally in each buffer.
+    fn is(&self, name: &str, scope: H2SymbolScope, buffer: H2Id) -> bool {
+        self.name == name && self.scope == scope && (scope == H2SymbolScope::Global || self.buffer == buffer)
+    }
+}
+
+/// All of the symbols in a project.
+///
+/// Don't change this directly - use [`crate::actions::ActionSymbolSet`].
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct H2SymbolTable {
+    // By name - each name can have one global symbol, plus one local symbol
+    // per buffer
+    symbols: BTreeMap<String, Vec<H2Symbol>>,
+}
+
+impl H2SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a symbol, replacing the one with the same name and scope (if any).
+    ///
+    /// Returns the symbol that was replaced.
+    pub fn set(&mut self, symbol: H2Symbol) -> Option<H2Symbol> {
+        let old = self.remove(&symbol.name, symbol.scope, symbol.buffer);
+        self.symbols.entry(symbol.name.clone()).or_default().push(symbol);
+
+        old
+    }
+
+    /// Remove a symbol by name and scope. `buffer` is only used for local
+    /// symbols.
+    pub fn remove(&mut self, name: &str, scope: H2SymbolScope, buffer: H2Id) -> Option<H2Symbol> {
+        let symbols = self.symbols.get_mut(name)?;
+        let index = symbols.iter().position(|symbol| symbol.is(name, scope, buffer))?;
+        let symbol = symbols.remove(index);
+
+        if symbols.is_empty() {
+            self.symbols.remove(name);
+        }
+
+        Some(symbol)
+    }
+
+    /// Get a symbol by name and scope. `buffer` is only used for local
+    /// symbols.
+    pub fn get(&self, name: &str, scope: H2SymbolScope, buffer: H2Id) -> Option<&H2Symbol> {
+        self.symbols.get(name)?.iter().find(|symbol| symbol.is(name, scope, buffer))
+    }
+
+    /// Find the symbol a name refers to, as seen from inside `from` (or from
+    /// outside of any buffer, if it's [`None`]).
+    ///
+    /// Local symbols win over global ones, just like a local variable.
+    pub fn lookup(&self, name: &str, from: Option<H2Id>) -> Option<&H2Symbol> {
+        let local = from.and_then(|from| self.get(name, H2SymbolScope::Local, from));
+
+        local.or_else(|| self.get(name, H2SymbolScope::Global, H2Id::default()))
+    }
+
+    /// Get every symbol at exactly `offset` in `buffer`, sorted by name.
+    pub fn at(&self, buffer: H2Id, offset: usize) -> Vec<&H2Symbol> {
+        self.iter().filter(|symbol| symbol.buffer == buffer && symbol.offset == offset).collect()
+    }
+
+    /// Get the closest symbol at or before `offset` in `buffer` - that's the
+    /// one to describe an address with (as `name+0x10`, say).
+    ///
+    /// If several symbols are equally close, the first by name is used.
+    pub fn nearest(&self, buffer: H2Id, offset: usize) -> Option<&H2Symbol> {
+        self.iter()
+            .filter(|symbol| symbol.buffer == buffer && symbol.offset <= offset)
+            .fold(None, |best: Option<&H2Symbol>, symbol| match best {
+                Some(best) if best.offset >= symbol.offset => Some(best),
+                _                                          => Some(symbol),
+            })
+    }
+
+    /// Every symbol, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item=&H2Symbol> {
+        self.symbols.values().flatten()
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.values().map(|symbols| symbols.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn symbol(name: &str, scope: H2SymbolScope, buffer: u64, offset: usize) -> H2Symbol {
+        H2Symbol::new(name, scope, H2Id::new(buffer), offset, H2Provenance::default())
+    }
+
+    #[test]
+    fn test_symbol_table() {
+        let mut table = H2SymbolTable::new();
+        assert!(table.is_empty());
+
+        // A global main, and a local main in buffer 2
+        assert_eq!(None, table.set(symbol("main", H2SymbolScope::Global, 1, 0x10)));
+        assert_eq!(None, table.set(symbol("main", H2SymbolScope::Local,  2, 0x20)));
+        assert_eq!(None, table.set(symbol("data", H2SymbolScope::Global, 1, 0x40)));
+        assert_eq!(3, table.len());
+
+        // Locals win, but only from their own buffer
+        assert_eq!(0x20, table.lookup("main", Some(H2Id::new(2))).unwrap().offset);
+        assert_eq!(0x10, table.lookup("main", Some(H2Id::new(1))).unwrap().offset);
+        assert_eq!(0x10, table.lookup("main", None).unwrap().offset);
+        assert_eq!(None, table.lookup("nope", None));
+
+        // Replacing gives back the old one
+        let old = table.set(symbol("main", H2SymbolScope::Global, 1, 0x18));
+        assert_eq!(Some(0x10), old.map(|s| s.offset));
+        assert_eq!(3, table.len());
+
+        // Finding symbols by address
+        assert_eq!(vec!["main"], table.at(H2Id::new(1), 0x18).iter().map(|s| s.name.as_str()).collect::<Vec<_>>());
+        assert_eq!(0, table.at(H2Id::new(1), 0x10).len());
+        assert_eq!("main", table.nearest(H2Id::new(1), 0x30).unwrap().name);
+        assert_eq!("data", table.nearest(H2Id::new(1), 0x40).unwrap().name);
+        assert_eq!(None, table.nearest(H2Id::new(1), 0x17));
+        assert_eq!(None, table.nearest(H2Id::new(3), 0x100));
+
+        // Removing only removes the one with the right scope
+        assert_eq!(None, table.remove("main", H2SymbolScope::Local, H2Id::new(1)));
+        assert!(table.remove("main", H2SymbolScope::Local, H2Id::new(2)).is_some());
+        assert_eq!(0x18, table.lookup("main", Some(H2Id::new(2))).unwrap().offset);
+        assert_eq!(2, table.len());
+    }
+}
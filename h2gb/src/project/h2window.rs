@@ -0,0 +1,215 @@
+//! Everything needed to draw a range of a buffer, collected in one call.
+//!
+//! A GUI (or anything else that displays a buffer) needs the same things
+//! every time it draws: the bytes, the entries, the comments, the bookmarks,
+//! and which bytes are covered by an entry. Rather than asking the project for
+//! each of those separately (and scanning the layers once per question), an
+//! [`H2Window`] gathers them all at once.
+//!
+//! Windows borrow from the project, so they're cheap to create but can't
+//! outlive it - create a fresh one each time something is drawn.
+
+use std::ops::Range;
+
+use simple_error::{bail, SimpleResult};
+
+use crate::project::{H2Buffer, H2Entry};
+
+/// Everything in a single layer that overlaps an [`H2Window`].
+#[derive(Debug)]
+pub struct H2WindowLayer<'a> {
+    /// The name of the layer
+    pub name: &'a str,
+
+    /// Entries that overlap the window, sorted by offset.
+    ///
+    /// Entries that start before the window or end after it are included, so
+    /// the caller can draw partial entries at the edges.
+    pub entries: Vec<&'a H2Entry>,
+
+    /// Comments within the window, as `(offset, comment)`, sorted by offset
+    pub comments: Vec<(usize, &'a String)>,
+
+    /// Bookmarks within the window, as `(offset, name)`, sorted by offset
+    pub bookmarks: Vec<(usize, &'a String)>,
+
+    /// For each byte in the window, whether it's part of an entry (including
+    /// alignment padding)
+    pub coverage: Vec<bool>,
+}
+
+impl<'a> H2WindowLayer<'a> {
+    /// The number of bytes in the window that are part of an entry.
+    pub fn covered_bytes(&self) -> usize {
+        self.coverage.iter().filter(|c| **c).count()
+    }
+}
+
+/// A range of a buffer, with everything from the requested layers.
+///
+/// Created by [`crate::project::H2Project::render_window`].
+#[derive(Debug)]
+pub struct H2Window<'a> {
+    /// The name of the buffer
+    pub buffer: &'a str,
+
+    /// The base address of the buffer, for display purposes
+    pub base_address: usize,
+
+    /// The range within the buffer (not including the base address)
+    pub range: Range<usize>,
+
+    /// The bytes in the range
+    pub data: &'a [u8],
+
+    /// The requested layers, in the order they were requested
+    pub layers: Vec<H2WindowLayer<'a>>,
+}
+
+impl<'a> H2Window<'a> {
+    pub fn new(buffer: &'a H2Buffer, layers: &[&str], range: Range<usize>) -> SimpleResult<Self> {
+        if range.is_empty() {
+            bail!("Can't render an empty window: {:?}", range);
+        }
+
+        let data = buffer.byte_range(range.clone())?;
+
+        let layers = layers.iter().map(|layer_name| {
+            let layer = buffer.layer_get_or_err(layer_name)?;
+
+            // Only scan the layer once, and work out coverage from the entries
+            // we found
+            let entries = layer.entries_get(range.clone())?;
+
+            let mut coverage = vec![false; range.len()];
+            for entry in &entries {
+                let aligned = &entry.resolved().aligned_range;
+                let start = std::cmp::max(aligned.start as usize, range.start);
+                let end = std::cmp::min(aligned.end as usize, range.end);
+
+                for covered in &mut coverage[(start - range.start)..(end - range.start)] {
+                    *covered = true;
+                }
+            }
+
+            Ok(H2WindowLayer {
+                name: layer.name(),
+                entries: entries,
+                comments: layer.comments_get_with_offsets(range.clone())?,
+                bookmarks: layer.bookmarks_get(range.clone())?,
+                coverage: coverage,
+            })
+        }).collect::<SimpleResult<Vec<H2WindowLayer>>>()?;
+
+        Ok(Self {
+            buffer: buffer.name(),
+            base_address: buffer.base_address,
+            range: range,
+            data: data,
+            layers: layers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+
+    use redo::Record;
+
+    use generic_number::{IntegerReader, Endian, HexFormatter};
+    use h2datatype::simple::numeric::H2Integer;
+
+    use crate::actions::*;
+    use crate::import::ImportedBookmark;
+    use crate::project::H2Project;
+
+    fn build_project() -> SimpleResult<Record<Action>> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f".to_vec(), 0x1000))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer1"))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer2"))?;
+
+        // A 4-byte entry at 2 and 8 on the first layer
+        let t = H2Integer::new(IntegerReader::U32(Endian::Big), HexFormatter::pretty_integer());
+        for offset in vec![2, 8] {
+            let resolved = record.target().buffer_get_or_err("buffer")?.peek(&t, offset)?;
+            record.apply(ActionEntryCreate::new("buffer", "layer1", resolved, Some(t.clone())))?;
+        }
+
+        record.apply(ActionLayerImportBookmarks::new("buffer", "layer1", vec![
+            ImportedBookmark::new(2,  Some("first"),  Some("First comment")),
+            ImportedBookmark::new(9,  None,           Some("Second comment")),
+            ImportedBookmark::new(14, Some("last"),   None),
+        ]))?;
+
+        Ok(record)
+    }
+
+    #[test]
+    fn test_render_window() -> SimpleResult<()> {
+        let record = build_project()?;
+        let window = record.target().render_window("buffer", &["layer1", "layer2"], 4..10)?;
+
+        assert_eq!("buffer", window.buffer);
+        assert_eq!(0x1000, window.base_address);
+        assert_eq!(4..10, window.range);
+        assert_eq!(b"\x04\x05\x06\x07\x08\x09", window.data);
+        assert_eq!(2, window.layers.len());
+
+        // Both entries overlap the window, even though neither is entirely in it
+        let layer = &window.layers[0];
+        assert_eq!("layer1", layer.name);
+        assert_eq!(2, layer.entries.len());
+        assert_eq!("0x02030405", layer.entries[0].resolved().display);
+        assert_eq!(8..12, layer.entries[1].resolved().actual_range);
+
+        // The bookmark at 2 and 14 are outside the window, but the comment at
+        // 9 isn't
+        assert_eq!(vec![(9, &"Second comment".to_string())], layer.comments);
+        assert_eq!(0, layer.bookmarks.len());
+
+        assert_eq!(vec![true, true, false, false, true, true], layer.coverage);
+        assert_eq!(4, layer.covered_bytes());
+
+        // The second layer is empty
+        let layer = &window.layers[1];
+        assert_eq!("layer2", layer.name);
+        assert_eq!(0, layer.entries.len());
+        assert_eq!(0, layer.covered_bytes());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_full_buffer() -> SimpleResult<()> {
+        let record = build_project()?;
+        let window = record.target().render_window("buffer", &["layer1"], 0..16)?;
+
+        assert_eq!(16, window.data.len());
+        assert_eq!(vec![(2, &"first".to_string()), (14, &"last".to_string())], window.layers[0].bookmarks);
+        assert_eq!(2, window.layers[0].comments.len());
+        assert_eq!(8, window.layers[0].covered_bytes());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_window_errors() -> SimpleResult<()> {
+        let record = build_project()?;
+
+        assert!(record.target().render_window("nobuffer", &["layer1"], 0..4).is_err());
+        assert!(record.target().render_window("buffer", &["nolayer"], 0..4).is_err());
+        assert!(record.target().render_window("buffer", &["layer1"], 0..17).is_err());
+        assert!(record.target().render_window("buffer", &["layer1"], 4..4).is_err());
+
+        // No layers is fine, we just get the bytes
+        assert_eq!(0, record.target().render_window("buffer", &[], 0..4)?.layers.len());
+
+        Ok(())
+    }
+}
@@ -1,5 +1,5 @@
 mod h2project;
-pub use h2project::H2Project;
+pub use h2project::{H2Project, H2BufferMut};
 
 mod h2buffer;
 pub use h2buffer::H2Buffer;
@@ -8,4 +8,41 @@ mod h2layer;
 pub use h2layer::H2Layer;
 
 mod h2entry;
-pub use h2entry::H2Entry;
+pub use h2entry::{H2Entry, H2EntryFilter, H2Interpretation, H2Nesting, DEFAULT_INTERPRETATION};
+
+mod h2window;
+pub use h2window::{H2Window, H2WindowLayer};
+
+mod h2id;
+pub use h2id::H2Id;
+
+mod h2memory;
+pub use h2memory::{H2MemoryUsage, H2BufferMemoryUsage, H2LayerMemoryUsage};
+
+mod h2stats;
+pub use h2stats::{H2LayerStats, H2LargeEntry};
+
+mod h2report;
+pub use h2report::H2ReportOptions;
+
+mod h2search;
+pub use h2search::{H2SearchField, H2SearchMatch, H2SearchPattern, H2SearchQuery};
+pub(crate) use h2search::H2Matcher;
+
+mod h2symbols;
+pub use h2symbols::{H2Symbol, H2SymbolScope, H2SymbolTable};
+
+mod h2data_overlay;
+pub use h2data_overlay::{H2DataOverlay, H2EnumChange};
+
+mod h2config;
+pub use h2config::H2Config;
+
+mod h2selection;
+pub use h2selection::{H2Selection, H2SelectionTransform};
+
+mod h2relocation;
+pub use h2relocation::{H2AnnotationKind, H2Invalidated, H2Relocation, H2RelocationReport};
+
+mod h2provenance;
+pub use h2provenance::{H2Creator, H2Provenance, H2RangeTransformation};
@@ -0,0 +1,223 @@
+use simple_error::{bail, SimpleResult};
+
+use crate::project::H2Window;
+
+/// Render an [`H2Window`] as a classic hexdump.
+///
+/// Each line has the address, the bytes in hex, and an ASCII gutter:
+///
+/// ```text
+/// 00001002 <header>:
+/// 00001000  00 01[02 03 04 05]06 07  08[09 0a 0b 0c]0d 0e 0f  |................| ; First comment
+/// ```
+///
+/// Entries from every layer in the window are marked in the hex column: `[`
+/// is the first byte of an entry, `]` follows the last byte, and `|` is used
+/// where one entry ends and the next begins. Comments are added to the end
+/// of the line they fall on, and bookmarks are shown as labels above the line
+/// they fall on.
+#[derive(Debug, Clone, Copy)]
+pub struct HexdumpFormatter {
+    width: usize,
+    group: usize,
+    ascii: bool,
+}
+
+impl HexdumpFormatter {
+    /// Create a new hexdump formatter.
+    ///
+    /// `width` is the number of bytes per line, and an extra space is added
+    /// every `group` bytes. `width` must be a multiple of `group`. If `ascii`
+    /// is set, the ASCII gutter is displayed.
+    pub fn new(width: usize, group: usize, ascii: bool) -> SimpleResult<Self> {
+        if width == 0 || group == 0 {
+            bail!("Width and group must both be at least 1");
+        }
+
+        if width % group != 0 {
+            bail!("Width ({}) must be a multiple of the group size ({})", width, group);
+        }
+
+        Ok(Self {
+            width: width,
+            group: group,
+            ascii: ascii,
+        })
+    }
+
+    /// The same layout as `hexdump -C`: 16 bytes per line, in groups of 8,
+    /// with the ASCII gutter.
+    pub fn pretty() -> Self {
+        Self {
+            width: 16,
+            group: 8,
+            ascii: true,
+        }
+    }
+
+    /// Render the window, one line per `width` bytes (plus any bookmarks).
+    pub fn render(&self, window: &H2Window) -> SimpleResult<String> {
+        let start = window.range.start;
+        let length = window.data.len();
+
+        // Work out where entries start and end, relative to the window, from
+        // every layer
+        let mut starts = vec![false; length];
+        let mut ends = vec![false; length + 1];
+        for layer in &window.layers {
+            for entry in &layer.entries {
+                let range = &entry.resolved().actual_range;
+                let (entry_start, entry_end) = (range.start as usize, range.end as usize);
+
+                if entry_start >= start && entry_start < start + length {
+                    starts[entry_start - start] = true;
+                }
+
+                if entry_end > start && entry_end <= start + length {
+                    ends[entry_end - start] = true;
+                }
+            }
+        }
+
+        let mut out = String::new();
+
+        for line_start in (0..length).step_by(self.width) {
+            let line_end = std::cmp::min(line_start + self.width, length);
+            let line_range = (start + line_start)..(start + line_end);
+
+            // Bookmarks go above the line
+            for layer in &window.layers {
+                for (offset, name) in &layer.bookmarks {
+                    if line_range.contains(offset) {
+                        out.push_str(&format!("{:08x} <{}>:\n", window.base_address + offset, name));
+                    }
+                }
+            }
+
+            out.push_str(&format!("{:08x} ", window.base_address + start + line_start));
+
+            for i in 0..self.width {
+                if i > 0 && i % self.group == 0 {
+                    out.push(' ');
+                }
+
+                let index = line_start + i;
+                if index >= length {
+                    out.push_str("   ");
+                    continue;
+                }
+
+                out.push(match (ends[index] && i > 0, starts[index]) {
+                    (true,  true)  => '|',
+                    (false, true)  => '[',
+                    (true,  false) => ']',
+                    (false, false) => ' ',
+                });
+                out.push_str(&format!("{:02x}", window.data[index]));
+            }
+
+            // Close off an entry that ends at the end of the line
+            out.push(if ends[line_end] { ']' } else { ' ' });
+
+            if self.ascii {
+                let ascii: String = window.data[line_start..line_end].iter().map(|b| {
+                    match b {
+                        0x20..=0x7e => *b as char,
+                        _           => '.',
+                    }
+                }).collect();
+
+                out.push_str(&format!(" |{}|", ascii));
+            }
+
+            // Comments go at the end of the line
+            let comments: Vec<&str> = window.layers.iter().flat_map(|layer| {
+                layer.comments.iter().filter(|(offset, _)| line_range.contains(offset)).map(|(_, comment)| &comment[..])
+            }).collect();
+
+            if comments.len() > 0 {
+                out.push_str(&format!(" ; {}", comments.join(" / ")));
+            }
+
+            out.push('\n');
+        }
+
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+
+    use redo::Record;
+
+    use generic_number::{IntegerReader, Endian, DefaultFormatter};
+    use h2datatype::simple::numeric::H2Integer;
+
+    use crate::actions::*;
+    use crate::import::ImportedBookmark;
+    use crate::project::H2Project;
+
+    fn build_project() -> SimpleResult<Record<Action>> {
+        let mut record: Record<Action> = Record::new(
+            H2Project::new("name", "1.0")
+        );
+
+        record.apply(ActionBufferCreateFromBytes::new("buffer", &b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0fABCDEFGHIJKLMNOPQRSTUV".to_vec(), 0x1000))?;
+        record.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        // Entries at 2..6, 9..13, 13..17 (which crosses a line), and 28..32
+        // (which ends a line)
+        let t = H2Integer::new(IntegerReader::U32(Endian::Big), DefaultFormatter::new_integer());
+        for offset in vec![2, 9, 13, 28] {
+            let resolved = record.target().buffer_get_or_err("buffer")?.peek(&t, offset)?;
+            record.apply(ActionEntryCreate::new("buffer", "layer", resolved, Some(t.clone())))?;
+        }
+
+        record.apply(ActionLayerImportBookmarks::new("buffer", "layer", vec![
+            ImportedBookmark::new(2,  Some("header"), Some("First comment")),
+            ImportedBookmark::new(5,  None,           Some("Second comment")),
+            ImportedBookmark::new(20, None,           Some("Third comment")),
+        ]))?;
+
+        Ok(record)
+    }
+
+    #[test]
+    fn test_hexdump() -> SimpleResult<()> {
+        let record = build_project()?;
+        let window = record.target().render_window("buffer", &["layer"], 0..38)?;
+
+        assert_eq!(
+            "00001002 <header>:\n\
+             00001000  00 01[02 03 04 05]06 07  08[09 0a 0b 0c|0d 0e 0f  |................| ; First comment / Second comment\n\
+             00001010  41]42 43 44 45 46 47 48  49 4a 4b 4c[4d 4e 4f 50] |ABCDEFGHIJKLMNOP| ; Third comment\n\
+             00001020  51 52 53 54 55 56                                 |QRSTUV|\n",
+            HexdumpFormatter::pretty().render(&window)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hexdump_options() -> SimpleResult<()> {
+        let record = build_project()?;
+
+        // Part of the buffer, in a narrower format, with no layers
+        let window = record.target().render_window("buffer", &[], 16..24)?;
+        assert_eq!(
+            "00001010  41 42  43 44 \n\
+             00001014  45 46  47 48 \n",
+            HexdumpFormatter::new(4, 2, false)?.render(&window)?
+        );
+
+        assert!(HexdumpFormatter::new(0, 1, true).is_err());
+        assert!(HexdumpFormatter::new(16, 0, true).is_err());
+        assert!(HexdumpFormatter::new(16, 5, true).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+//! Turn parts of a project into text.
+//!
+//! Renderers take an [`crate::project::H2Window`] - the bytes, entries,
+//! comments, and bookmarks for a range of a buffer - and produce something a
+//! human can read. They don't know anything about terminals or GUIs; they
+//! just build strings.
+//!
+//! So far, we have:
+//!
+//! * A classic hexdump, with entry boundaries and comments marked
+//!   ([`HexdumpFormatter`])
+//! * Pixel data decoded to RGBA rows, for previewing images
+//!   ([`PixelPreview`]) - these are bytes rather than strings, but still
+//!   don't depend on any particular image library (with the `image` feature,
+//!   they can be converted to an `image::RgbaImage`)
+//! * PCM audio decoded to normalized samples, for playing or graphing
+//!   ([`SamplePreview`])
+//!
+//! # Example
+//!
+//! ```
+//! use libh2gb::project::H2Project;
+//! use libh2gb::actions::*;
+//! use libh2gb::render::HexdumpFormatter;
+//! use redo::Record;
+//!
+//! let mut record: Record<Action> = Record::new(H2Project::new("name", "1.0"));
+//! record.apply(ActionBufferCreateFromBytes::new("buffer", b"Hello, world!", 0x1000)).unwrap();
+//!
+//! let window = record.target().render_window("buffer", &[], 0..13).unwrap();
+//! let dump = HexdumpFormatter::pretty().render(&window).unwrap();
+//!
+//! assert_eq!(
+//!   "00001000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21           |Hello, world!|\n",
+//!   dump
+//! );
+//! ```
+
+mod hexdump;
+pub use hexdump::*;
+
+mod pixels;
+pub use pixels::*;
+
+mod samples;
+pub use samples::*;
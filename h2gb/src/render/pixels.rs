@@ -0,0 +1,271 @@
+use serde::{Serialize, Deserialize};
+use simple_error::{bail, SimpleResult};
+
+use generic_number::Endian;
+
+/// How a single pixel is stored.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// One byte of brightness
+    Gray8,
+
+    /// Red, green, blue - one byte each (an array of
+    /// [`h2datatype::simple::Rgb`] looks like this)
+    Rgb888,
+
+    /// Blue, green, red - one byte each (BMP files use this)
+    Bgr888,
+
+    /// Red, green, blue, alpha - one byte each
+    Rgba8888,
+
+    /// Blue, green, red, alpha - one byte each
+    Bgra8888,
+
+    /// Alpha, red, green, blue - one byte each
+    Argb8888,
+
+    /// Red, green, and blue packed into 5, 6, and 5 bits of a 16-bit value
+    Rgb565(Endian),
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            Self::Gray8     => 1,
+            Self::Rgb565(_) => 2,
+            Self::Rgb888    => 3,
+            Self::Bgr888    => 3,
+            Self::Rgba8888  => 4,
+            Self::Bgra8888  => 4,
+            Self::Argb8888  => 4,
+        }
+    }
+
+    /// Convert one pixel (exactly [`PixelFormat::bytes_per_pixel`] bytes) to
+    /// RGBA.
+    fn to_rgba(self, p: &[u8]) -> [u8; 4] {
+        match self {
+            Self::Gray8    => [p[0], p[0], p[0], 0xff],
+            Self::Rgb888   => [p[0], p[1], p[2], 0xff],
+            Self::Bgr888   => [p[2], p[1], p[0], 0xff],
+            Self::Rgba8888 => [p[0], p[1], p[2], p[3]],
+            Self::Bgra8888 => [p[2], p[1], p[0], p[3]],
+            Self::Argb8888 => [p[1], p[2], p[3], p[0]],
+            Self::Rgb565(endian) => {
+                let value = match endian {
+                    Endian::Big    => u16::from_be_bytes([p[0], p[1]]),
+                    Endian::Little => u16::from_le_bytes([p[0], p[1]]),
+                };
+
+                // Scale each channel up to 8 bits, so full intensity is 0xff
+                let red   = ((value >> 11) & 0x1f) as u32;
+                let green = ((value >> 5)  & 0x3f) as u32;
+                let blue  = (value         & 0x1f) as u32;
+
+                [(red * 255 / 31) as u8, (green * 255 / 63) as u8, (blue * 255 / 31) as u8, 0xff]
+            },
+        }
+    }
+}
+
+/// How pixel data is laid out - the metadata that turns a pile of bytes into
+/// an image.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelLayout {
+    pub width: usize,
+    pub height: usize,
+    pub format: PixelFormat,
+
+    /// The number of bytes from the start of one row to the start of the
+    /// next, if rows are padded (BMP pads them to 4 bytes, for example)
+    pub stride: Option<usize>,
+
+    /// Is the bottom row stored first?
+    pub bottom_up: bool,
+}
+
+impl PixelLayout {
+    pub fn new(width: usize, height: usize, format: PixelFormat) -> Self {
+        Self {
+            width: width,
+            height: height,
+            format: format,
+            stride: None,
+            bottom_up: false,
+        }
+    }
+
+    /// The same layout, with padded rows.
+    pub fn with_stride(self, stride: usize) -> Self {
+        Self {
+            stride: Some(stride),
+            ..self
+        }
+    }
+
+    /// The same layout, stored bottom row first.
+    pub fn with_bottom_up(self) -> Self {
+        Self {
+            bottom_up: true,
+            ..self
+        }
+    }
+
+    /// The number of bytes of pixels in a row, or `None` if it's too big to
+    /// count.
+    fn pixels_size(&self) -> Option<usize> {
+        self.width.checked_mul(self.format.bytes_per_pixel())
+    }
+
+    /// The number of bytes from the start of one row to the start of the
+    /// next, or `None` if it's too big to count.
+    pub fn row_size(&self) -> Option<usize> {
+        match self.stride {
+            Some(stride) => Some(stride),
+            None         => self.pixels_size(),
+        }
+    }
+
+    /// The number of bytes needed for the whole image (the last row doesn't
+    /// need its padding), or `None` if it's too big to count.
+    pub fn size(&self) -> Option<usize> {
+        match self.height {
+            0 => Some(0),
+            h => (h - 1).checked_mul(self.row_size()?)?.checked_add(self.pixels_size()?),
+        }
+    }
+}
+
+/// Decoded pixels, ready to be drawn.
+///
+/// Every pixel is four bytes - red, green, blue, alpha - so a front-end can
+/// hand the rows to whatever it draws with without knowing anything about
+/// the original format. Rows are always top to bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PixelPreview {
+    pub width: usize,
+    pub height: usize,
+
+    /// `height` rows of `width * 4` bytes each
+    pub rows: Vec<Vec<u8>>,
+}
+
+impl PixelPreview {
+    /// Decode `data` as described by `layout`.
+    ///
+    /// Anything past the end of the image is ignored, but there has to be
+    /// enough data for all of it.
+    pub fn decode(data: &[u8], layout: &PixelLayout) -> SimpleResult<Self> {
+        let bpp = layout.format.bytes_per_pixel();
+
+        if layout.width == 0 || layout.height == 0 {
+            bail!("Can't preview an empty image ({}x{})", layout.width, layout.height);
+        }
+
+        let (pixels_size, row_size, size) = match (layout.pixels_size(), layout.row_size(), layout.size()) {
+            (Some(pixels_size), Some(row_size), Some(size)) => (pixels_size, row_size, size),
+            _ => bail!("A {}x{} {:?} image is too big", layout.width, layout.height, layout.format),
+        };
+
+        if row_size < pixels_size {
+            bail!("Stride ({}) is too small for {} pixels of {} bytes", row_size, layout.width, bpp);
+        }
+
+        if data.len() < size {
+            bail!("A {}x{} {:?} image needs {} bytes, but there are only {}", layout.width, layout.height, layout.format, size, data.len());
+        }
+
+        let mut rows: Vec<Vec<u8>> = (0..layout.height).map(|row| {
+            let start = row * row_size;
+
+            data[start..(start + pixels_size)].chunks(bpp).flat_map(|pixel| layout.format.to_rgba(pixel)).collect()
+        }).collect();
+
+        if layout.bottom_up {
+            rows.reverse();
+        }
+
+        Ok(Self {
+            width: layout.width,
+            height: layout.height,
+            rows: rows,
+        })
+    }
+
+    /// All of the rows, one after the other.
+    pub fn to_rgba(&self) -> Vec<u8> {
+        self.rows.concat()
+    }
+
+    /// Convert to an [`image::RgbaImage`] (only with the `image` feature).
+    #[cfg(feature = "image")]
+    pub fn to_image(&self) -> Option<image::RgbaImage> {
+        image::RgbaImage::from_raw(self.width as u32, self.height as u32, self.to_rgba())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_decode() -> SimpleResult<()> {
+        // 2x2, with a byte of padding on each row
+        let data = b"\xff\x00\x00\x00\xff\x00\xee\x00\x00\xff\x10\x20\x30\xee";
+        let layout = PixelLayout::new(2, 2, PixelFormat::Rgb888).with_stride(7);
+        assert_eq!(Some(13), layout.size());
+
+        let preview = PixelPreview::decode(data, &layout)?;
+        assert_eq!(vec![
+            b"\xff\x00\x00\xff\x00\xff\x00\xff".to_vec(),
+            b"\x00\x00\xff\xff\x10\x20\x30\xff".to_vec(),
+        ], preview.rows);
+
+        // Upside down
+        let preview = PixelPreview::decode(data, &layout.with_bottom_up())?;
+        assert_eq!(b"\x00\x00\xff\xff\x10\x20\x30\xff".to_vec(), preview.rows[0]);
+        assert_eq!(16, preview.to_rgba().len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_formats() -> SimpleResult<()> {
+        let tests: Vec<(PixelFormat, &[u8], [u8; 4])> = vec![
+            (PixelFormat::Gray8,                  b"\x80",             [0x80, 0x80, 0x80, 0xff]),
+            (PixelFormat::Bgr888,                 b"\x01\x02\x03",     [0x03, 0x02, 0x01, 0xff]),
+            (PixelFormat::Rgba8888,               b"\x01\x02\x03\x04", [0x01, 0x02, 0x03, 0x04]),
+            (PixelFormat::Bgra8888,               b"\x01\x02\x03\x04", [0x03, 0x02, 0x01, 0x04]),
+            (PixelFormat::Argb8888,               b"\x01\x02\x03\x04", [0x02, 0x03, 0x04, 0x01]),
+            (PixelFormat::Rgb565(Endian::Big),    b"\xf8\x00",         [0xff, 0x00, 0x00, 0xff]),
+            (PixelFormat::Rgb565(Endian::Little), b"\xe0\x07",         [0x00, 0xff, 0x00, 0xff]),
+            (PixelFormat::Rgb565(Endian::Big),    b"\x00\x1f",         [0x00, 0x00, 0xff, 0xff]),
+        ];
+
+        for (format, data, expected) in tests {
+            assert_eq!(vec![expected.to_vec()], PixelPreview::decode(data, &PixelLayout::new(1, 1, format))?.rows);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_errors() -> SimpleResult<()> {
+        let data = [0; 12];
+
+        assert!(PixelPreview::decode(&data, &PixelLayout::new(0, 1, PixelFormat::Gray8)).is_err());
+        assert!(PixelPreview::decode(&data, &PixelLayout::new(2, 2, PixelFormat::Rgb888).with_stride(5)).is_err());
+        assert!(PixelPreview::decode(&data, &PixelLayout::new(2, 2, PixelFormat::Rgba8888)).is_err());
+        assert!(PixelPreview::decode(&data, &PixelLayout::new(2, 2, PixelFormat::Rgb888)).is_ok());
+
+        // Sizes that overflow
+        assert_eq!(None, PixelLayout::new(usize::MAX, 1, PixelFormat::Rgba8888).size());
+        assert!(PixelPreview::decode(&data, &PixelLayout::new(usize::MAX, 1, PixelFormat::Rgba8888)).is_err());
+        assert!(PixelPreview::decode(&data, &PixelLayout::new(1, usize::MAX, PixelFormat::Gray8).with_stride(usize::MAX)).is_err());
+
+        Ok(())
+    }
+}
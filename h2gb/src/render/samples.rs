@@ -0,0 +1,213 @@
+use serde::{Serialize, Deserialize};
+use simple_error::{bail, SimpleResult};
+
+use generic_number::Endian;
+
+/// How a single PCM sample is stored.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    /// Unsigned 8-bit, centered on `0x80` (WAV files use this)
+    U8,
+
+    /// Signed 8-bit
+    I8,
+
+    /// Signed 16-bit
+    I16(Endian),
+
+    /// Signed 24-bit, packed into 3 bytes
+    I24(Endian),
+
+    /// Signed 32-bit
+    I32(Endian),
+
+    /// 32-bit float, already from `-1.0` to `1.0`
+    F32(Endian),
+}
+
+impl SampleFormat {
+    pub fn bits(&self) -> usize {
+        match self {
+            Self::U8     => 8,
+            Self::I8     => 8,
+            Self::I16(_) => 16,
+            Self::I24(_) => 24,
+            Self::I32(_) => 32,
+            Self::F32(_) => 32,
+        }
+    }
+
+    pub fn bytes_per_sample(&self) -> usize {
+        self.bits() / 8
+    }
+
+    /// Convert one sample (exactly [`SampleFormat::bytes_per_sample`] bytes)
+    /// to a float from `-1.0` to `1.0`.
+    fn normalize(self, s: &[u8]) -> f32 {
+        // Read the sample as a big-endian signed integer in the top bits of
+        // an i32, so everything but floats can be scaled the same way
+        let big_endian = |s: &[u8], endian: Endian| -> i32 {
+            let mut bytes = [0u8; 4];
+            for (i, b) in s.iter().enumerate() {
+                bytes[i] = match endian {
+                    Endian::Big    => *b,
+                    Endian::Little => s[s.len() - 1 - i],
+                };
+            }
+
+            i32::from_be_bytes(bytes)
+        };
+
+        let value = match self {
+            Self::U8             => ((s[0] as i32) - 0x80) << 24,
+            Self::I8             => (s[0] as i8 as i32) << 24,
+            Self::I16(endian)    => big_endian(s, endian),
+            Self::I24(endian)    => big_endian(s, endian),
+            Self::I32(endian)    => big_endian(s, endian),
+            Self::F32(endian)    => return match endian {
+                Endian::Big    => f32::from_be_bytes([s[0], s[1], s[2], s[3]]),
+                Endian::Little => f32::from_le_bytes([s[0], s[1], s[2], s[3]]),
+            },
+        };
+
+        (value as f64 / 2147483648.0) as f32
+    }
+}
+
+/// How PCM sample data is laid out.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleLayout {
+    pub format: SampleFormat,
+
+    /// The number of channels; samples for each channel are interleaved
+    pub channels: usize,
+
+    /// Samples per second (per channel)
+    pub rate: usize,
+}
+
+impl SampleLayout {
+    pub fn new(format: SampleFormat, channels: usize, rate: usize) -> Self {
+        Self {
+            format: format,
+            channels: channels,
+            rate: rate,
+        }
+    }
+
+    /// The size of one sample for every channel.
+    pub fn frame_size(&self) -> usize {
+        self.format.bytes_per_sample() * self.channels
+    }
+}
+
+/// Decoded PCM samples, ready to be played or graphed.
+///
+/// Samples are normalized to floats from `-1.0` to `1.0`, whatever their
+/// original format was, and split up by channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplePreview {
+    pub rate: usize,
+
+    /// One list of samples per channel, all the same length
+    pub channels: Vec<Vec<f32>>,
+}
+
+impl SamplePreview {
+    /// Decode `data` as described by `layout`.
+    ///
+    /// A partial frame at the end is ignored.
+    pub fn decode(data: &[u8], layout: &SampleLayout) -> SimpleResult<Self> {
+        if layout.channels == 0 || layout.rate == 0 {
+            bail!("Sample layout needs at least one channel and a non-zero rate");
+        }
+
+        if data.len() < layout.frame_size() {
+            bail!("Need at least {} bytes for one sample on each of {} channel(s), but there are only {}", layout.frame_size(), layout.channels, data.len());
+        }
+
+        let mut channels: Vec<Vec<f32>> = vec![Vec::with_capacity(data.len() / layout.frame_size()); layout.channels];
+        for frame in data.chunks_exact(layout.frame_size()) {
+            for (channel, sample) in frame.chunks_exact(layout.format.bytes_per_sample()).enumerate() {
+                channels[channel].push(layout.format.normalize(sample));
+            }
+        }
+
+        Ok(Self {
+            rate: layout.rate,
+            channels: channels,
+        })
+    }
+
+    /// The number of samples in each channel.
+    pub fn len(&self) -> usize {
+        self.channels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// How long the samples take to play, in seconds.
+    pub fn duration(&self) -> f64 {
+        self.len() as f64 / self.rate as f64
+    }
+
+    /// All of the channels, interleaved again (the way most audio APIs want
+    /// them).
+    pub fn interleaved(&self) -> Vec<f32> {
+        (0..self.len()).flat_map(|i| self.channels.iter().map(move |channel| channel[i])).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_decode() -> SimpleResult<()> {
+        // Stereo 16-bit little endian, with half a frame left over
+        let data = b"\x00\x40\x00\xc0\xff\x7f\x00\x80\x01";
+        let preview = SamplePreview::decode(data, &SampleLayout::new(SampleFormat::I16(Endian::Little), 2, 4))?;
+
+        assert_eq!(vec![vec![0.5, 32767.0 / 32768.0], vec![-0.5, -1.0]], preview.channels);
+        assert_eq!(2, preview.len());
+        assert_eq!(0.5, preview.duration());
+        assert_eq!(vec![0.5, -0.5, 32767.0 / 32768.0, -1.0], preview.interleaved());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_formats() -> SimpleResult<()> {
+        let tests: Vec<(SampleFormat, &[u8], f32)> = vec![
+            (SampleFormat::U8,                  b"\x80",             0.0),
+            (SampleFormat::U8,                  b"\x00",             -1.0),
+            (SampleFormat::U8,                  b"\xc0",             0.5),
+            (SampleFormat::I8,                  b"\xc0",             -0.5),
+            (SampleFormat::I16(Endian::Big),    b"\x40\x00",         0.5),
+            (SampleFormat::I24(Endian::Big),    b"\xc0\x00\x00",     -0.5),
+            (SampleFormat::I24(Endian::Little), b"\x00\x00\x40",     0.5),
+            (SampleFormat::I32(Endian::Little), b"\x00\x00\x00\x80", -1.0),
+            (SampleFormat::F32(Endian::Big),    b"\x3f\x00\x00\x00", 0.5),
+            (SampleFormat::F32(Endian::Little), b"\x00\x00\x00\xbf", -0.5),
+        ];
+
+        for (format, data, expected) in tests {
+            assert_eq!(vec![vec![expected]], SamplePreview::decode(data, &SampleLayout::new(format, 1, 8000))?.channels);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_errors() -> SimpleResult<()> {
+        assert!(SamplePreview::decode(b"\x00\x00", &SampleLayout::new(SampleFormat::U8, 0, 8000)).is_err());
+        assert!(SamplePreview::decode(b"\x00\x00", &SampleLayout::new(SampleFormat::U8, 1, 0)).is_err());
+        assert!(SamplePreview::decode(b"\x00\x00", &SampleLayout::new(SampleFormat::I16(Endian::Big), 2, 8000)).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use redo::Record;
+use serde::{Serialize, Deserialize};
+use simple_error::{SimpleResult, SimpleError, bail};
+
+use crate::actions::Action;
+use crate::project::H2Project;
+
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const SNAPSHOT_TEMP_FILE: &str = "snapshot.json.tmp";
+
+/// A single line in the journal.
+#[derive(Serialize, Deserialize, Debug)]
+enum JournalEntry {
+    Apply(Action),
+    Undo,
+    Redo,
+}
+
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    generation: u64,
+    record: &'a Record<Action>,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    generation: u64,
+    record: Record<Action>,
+}
+
+fn journal_path(directory: &Path, generation: u64) -> PathBuf {
+    directory.join(format!("journal-{}.jsonl", generation))
+}
+
+fn io_error(what: &str, path: &Path, e: std::io::Error) -> SimpleError {
+    SimpleError::new(format!("Couldn't {} {}: {}", what, path.display(), e))
+}
+
+/// A project being worked on, with autosave.
+///
+/// Changes should go through [`H2Session::apply`], [`H2Session::undo`], and
+/// [`H2Session::redo`] rather than through the record directly - otherwise
+/// they won't be journaled, and can't be recovered.
+pub struct H2Session {
+    record: Record<Action>,
+    directory: PathBuf,
+
+    generation: u64,
+    journal: File,
+
+    interval: Duration,
+    last_snapshot: Instant,
+}
+
+impl H2Session {
+    /// Start a new session for `project`, autosaving to `directory`.
+    ///
+    /// A snapshot is taken whenever at least `interval` has passed since the
+    /// last one (checked after each change). The directory is created if it
+    /// doesn't exist. If it already contains a session, this fails rather than
+    /// overwrite it - see [`H2Session::needs_recovery`].
+    pub fn new(project: H2Project, directory: &Path, interval: Duration) -> SimpleResult<Self> {
+        if Self::needs_recovery(directory) {
+            bail!("There's already a session in {}, it needs to be recovered or discarded first", directory.display());
+        }
+
+        fs::create_dir_all(directory).map_err(|e| io_error("create", directory, e))?;
+
+        let record = Record::new(project);
+        Self::write_snapshot(directory, 0, &record)?;
+
+        Ok(Self {
+            record: record,
+            directory: directory.to_path_buf(),
+            generation: 0,
+            journal: Self::open_journal(directory, 0)?,
+            interval: interval,
+            last_snapshot: Instant::now(),
+        })
+    }
+
+    /// Is there a session in `directory` that didn't close cleanly?
+    pub fn needs_recovery(directory: &Path) -> bool {
+        directory.join(SNAPSHOT_FILE).exists()
+    }
+
+    /// Recover a session that didn't close cleanly.
+    ///
+    /// Loads the last snapshot from `directory`, and replays everything in the
+    /// journal on top of it. A journaled change that fails when it's replayed
+    /// also failed originally (it was written before it was attempted), so it's
+    /// skipped. A partially written last line (from crashing mid-write) is
+    /// ignored.
+    ///
+    /// The recovered state is snapshotted right away, and the session carries
+    /// on from there.
+    pub fn recover(directory: &Path, interval: Duration) -> SimpleResult<Self> {
+        let snapshot_path = directory.join(SNAPSHOT_FILE);
+        let snapshot = fs::read_to_string(&snapshot_path).map_err(|e| io_error("read", &snapshot_path, e))?;
+        let snapshot: Snapshot = serde_json::from_str(&snapshot).map_err(|e| {
+            SimpleError::new(format!("Couldn't parse snapshot {}: {}", snapshot_path.display(), e))
+        })?;
+
+        let mut record = snapshot.record;
+
+        // The journal might not exist, if we crashed right after a snapshot
+        let journal = journal_path(directory, snapshot.generation);
+        if journal.exists() {
+            let file = File::open(&journal).map_err(|e| io_error("open", &journal, e))?;
+
+            for line in BufReader::new(file).lines() {
+                let line = line.map_err(|e| io_error("read", &journal, e))?;
+
+                let entry: JournalEntry = match serde_json::from_str(&line) {
+                    Ok(entry) => entry,
+                    Err(_)    => break,
+                };
+
+                // Errors are expected here, see above
+                let _ = match entry {
+                    JournalEntry::Apply(action) => record.apply(action),
+                    JournalEntry::Undo          => record.undo(),
+                    JournalEntry::Redo          => record.redo(),
+                };
+            }
+        }
+
+        let mut session = Self {
+            record: record,
+            directory: directory.to_path_buf(),
+            generation: snapshot.generation,
+            journal: Self::open_journal(directory, snapshot.generation)?,
+            interval: interval,
+            last_snapshot: Instant::now(),
+        };
+        session.snapshot()?;
+
+        Ok(session)
+    }
+
+    pub fn project(&self) -> &H2Project {
+        self.record.target()
+    }
+
+    pub fn record(&self) -> &Record<Action> {
+        &self.record
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Journal and apply an action.
+    pub fn apply(&mut self, action: Action) -> SimpleResult<()> {
+        self.journal(JournalEntry::Apply(action))
+    }
+
+    /// Journal and undo the last action.
+    pub fn undo(&mut self) -> SimpleResult<()> {
+        self.journal(JournalEntry::Undo)
+    }
+
+    /// Journal and redo the last undone action.
+    pub fn redo(&mut self) -> SimpleResult<()> {
+        self.journal(JournalEntry::Redo)
+    }
+
+    /// Take a snapshot now, and start a fresh journal.
+    pub fn snapshot(&mut self) -> SimpleResult<()> {
+        let generation = self.generation + 1;
+
+        // Once the new snapshot is in place, the old journal is never read
+        // again - so it's safe to crash anywhere in here
+        Self::write_snapshot(&self.directory, generation, &self.record)?;
+        self.journal = Self::open_journal(&self.directory, generation)?;
+
+        let old_journal = journal_path(&self.directory, self.generation);
+        if old_journal.exists() {
+            fs::remove_file(&old_journal).map_err(|e| io_error("remove", &old_journal, e))?;
+        }
+
+        self.generation = generation;
+        self.last_snapshot = Instant::now();
+
+        Ok(())
+    }
+
+    /// End the session cleanly, removing the autosave files.
+    ///
+    /// Returns the record, so the project (and its history) can be carried on
+    /// with or saved elsewhere.
+    pub fn close(self) -> SimpleResult<Record<Action>> {
+        let journal = journal_path(&self.directory, self.generation);
+        fs::remove_file(&journal).map_err(|e| io_error("remove", &journal, e))?;
+
+        let snapshot = self.directory.join(SNAPSHOT_FILE);
+        fs::remove_file(&snapshot).map_err(|e| io_error("remove", &snapshot, e))?;
+
+        Ok(self.record)
+    }
+
+    fn journal(&mut self, entry: JournalEntry) -> SimpleResult<()> {
+        // Write it down before doing it
+        let line = serde_json::to_string(&entry).map_err(|e| {
+            SimpleError::new(format!("Couldn't serialize journal entry: {}", e))
+        })?;
+
+        let path = journal_path(&self.directory, self.generation);
+        self.journal.write_all(format!("{}\n", line).as_bytes()).map_err(|e| io_error("write", &path, e))?;
+        self.journal.sync_data().map_err(|e| io_error("sync", &path, e))?;
+
+        let result = match entry {
+            JournalEntry::Apply(action) => self.record.apply(action),
+            JournalEntry::Undo          => self.record.undo(),
+            JournalEntry::Redo          => self.record.redo(),
+        };
+
+        if self.last_snapshot.elapsed() >= self.interval {
+            self.snapshot()?;
+        }
+
+        result
+    }
+
+    fn open_journal(directory: &Path, generation: u64) -> SimpleResult<File> {
+        let path = journal_path(directory, generation);
+
+        OpenOptions::new().create(true).append(true).open(&path).map_err(|e| io_error("open", &path, e))
+    }
+
+    fn write_snapshot(directory: &Path, generation: u64, record: &Record<Action>) -> SimpleResult<()> {
+        let snapshot = serde_json::to_string(&SnapshotRef {
+            generation: generation,
+            record: record,
+        }).map_err(|e| {
+            SimpleError::new(format!("Couldn't serialize snapshot: {}", e))
+        })?;
+
+        // Write to a temporary file then rename it, so there's always a
+        // complete snapshot on disk
+        let temp = directory.join(SNAPSHOT_TEMP_FILE);
+        let mut file = File::create(&temp).map_err(|e| io_error("create", &temp, e))?;
+        file.write_all(snapshot.as_bytes()).map_err(|e| io_error("write", &temp, e))?;
+        file.sync_all().map_err(|e| io_error("sync", &temp, e))?;
+
+        let path = directory.join(SNAPSHOT_FILE);
+        fs::rename(&temp, &path).map_err(|e| io_error("rename", &path, e))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use simple_error::SimpleResult;
+
+    use crate::actions::*;
+
+    fn test_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("h2gb-session-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&directory);
+
+        directory
+    }
+
+    fn comment(session: &H2Session, offset: usize) -> Option<String> {
+        session.project().buffer_get("buffer").unwrap().layer_get("layer").unwrap().comment_get(offset).unwrap().cloned()
+    }
+
+    #[test]
+    fn test_recover() -> SimpleResult<()> {
+        let directory = test_directory("recover");
+
+        // Never snapshot on its own
+        let interval = Duration::from_secs(3600);
+
+        let mut session = H2Session::new(H2Project::new("name", "1.0"), &directory, interval)?;
+        session.apply(ActionBufferCreateEmpty::new("buffer", 16, 0))?;
+        session.apply(ActionLayerCreate::new("buffer", "layer"))?;
+        session.snapshot()?;
+
+        session.apply(ActionEntrySetComment::new("buffer", "layer", 4, Some("Hello".to_string())))?;
+        session.apply(ActionEntrySetComment::new("buffer", "layer", 8, Some("Undone".to_string())))?;
+        session.undo()?;
+
+        // This one fails, and should still fail on replay
+        assert!(session.apply(ActionLayerCreate::new("buffer", "layer")).is_err());
+
+        // "Crash"
+        drop(session);
+        assert!(H2Session::needs_recovery(&directory));
+        assert!(H2Session::new(H2Project::new("name", "1.0"), &directory, interval).is_err());
+
+        let mut session = H2Session::recover(&directory, interval)?;
+        assert_eq!(Some("Hello".to_string()), comment(&session, 4));
+        assert_eq!(None, comment(&session, 8));
+
+        // The undo history survived too
+        session.redo()?;
+        assert_eq!(Some("Undone".to_string()), comment(&session, 8));
+
+        session.close()?;
+        assert!(!H2Session::needs_recovery(&directory));
+        fs::remove_dir_all(&directory).map_err(|e| SimpleError::new(e.to_string()))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_autosave() -> SimpleResult<()> {
+        let directory = test_directory("autosave");
+
+        // Snapshot after every change
+        let mut session = H2Session::new(H2Project::new("name", "1.0"), &directory, Duration::from_secs(0))?;
+        session.apply(ActionBufferCreateEmpty::new("buffer", 16, 0))?;
+        session.apply(ActionLayerCreate::new("buffer", "layer"))?;
+
+        // Only the current journal is kept, and it's empty
+        assert!(!journal_path(&directory, 0).exists());
+        assert_eq!(0, fs::metadata(journal_path(&directory, 2)).map_err(|e| SimpleError::new(e.to_string()))?.len());
+
+        drop(session);
+        let session = H2Session::recover(&directory, Duration::from_secs(0))?;
+        assert!(session.project().buffer_get("buffer").unwrap().layer_get("layer").is_some());
+
+        session.close()?;
+        fs::remove_dir_all(&directory).map_err(|e| SimpleError::new(e.to_string()))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated_journal() -> SimpleResult<()> {
+        let directory = test_directory("truncated");
+        let interval = Duration::from_secs(3600);
+
+        let mut session = H2Session::new(H2Project::new("name", "1.0"), &directory, interval)?;
+        session.apply(ActionBufferCreateEmpty::new("buffer", 16, 0))?;
+        drop(session);
+
+        // Simulate crashing partway through writing a line
+        let mut journal = OpenOptions::new().append(true).open(journal_path(&directory, 0)).map_err(|e| SimpleError::new(e.to_string()))?;
+        journal.write_all(b"{\"Apply\":{\"LayerCr").map_err(|e| SimpleError::new(e.to_string()))?;
+
+        let session = H2Session::recover(&directory, interval)?;
+        assert!(session.project().buffer_exists("buffer"));
+
+        session.close()?;
+        fs::remove_dir_all(&directory).map_err(|e| SimpleError::new(e.to_string()))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+//! Autosave and crash recovery for a working session.
+//!
+//! An [`H2Session`] wraps the undo/redo record for a project, and keeps two
+//! files in an autosave directory:
+//!
+//! * A snapshot of the whole record (the project, plus its undo history),
+//!   written every so often
+//! * A journal of every action, undo, and redo since that snapshot, written
+//!   *before* the change is made
+//!
+//! If the session ends cleanly ([`H2Session::close`]), both files are removed.
+//! If it doesn't, [`H2Session::recover`] loads the last snapshot and replays
+//! the journal onto it, which gets back to where things were when it crashed.
+//!
+//! Each snapshot has a generation number, and the journal is named after the
+//! generation it applies to. That way, a crash partway through taking a
+//! snapshot never replays a journal onto the wrong snapshot.
+
+mod h2session;
+pub use h2session::H2Session;
@@ -12,6 +12,11 @@
 //! Check out the definition of the [`Transformation`] enum for full details on
 //! everything it can do!
 //!
+//! To apply several transformations in a row - and check that edits can be
+//! written back through all of them - use a [`TransformPipeline`]. Pipelines
+//! can also be parsed from a short spec, like `base64:url,zlib` (see
+//! [`TransformPipeline::parse`]), for the command line and scripts.
+//!
 //! # Usage
 //!
 //! The public API is pretty straight forward. Here's an example that transforms
@@ -52,6 +57,11 @@ pub use transformer_trait::TransformerTrait;
 mod helpers;
 pub use helpers::*;
 
+mod pipeline;
+pub use pipeline::*;
+
+mod spec;
+
 /// Which transformation to perform.
 ///
 /// In general, don't create this enum directly - use the initializer methods
@@ -124,6 +134,70 @@ pub enum Transformation {
     /// ```
     XorByConstant(TransformXorByConstant),
 
+    /// Reverse the order of the bits in each byte, so the most significant
+    /// bit becomes the least significant. Some hardware dumps are stored this
+    /// way.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use h2transformation::TransformBitReverse;
+    ///
+    /// // Input: "\x01\x12\xf0"
+    /// let i: Vec<u8> = b"\x01\x12\xf0".to_vec();
+    ///
+    /// // Output: "\x80\x48\x0f"
+    /// let o = TransformBitReverse::new().transform(&i);
+    /// assert_eq!(Ok(b"\x80\x48\x0f".to_vec()), o);
+    /// ```
+    ///
+    /// # Restrictions / errors
+    ///
+    /// n/a
+    BitReverse(TransformBitReverse),
+
+    /// Swap the high and low nibbles (4-bit halves) of each byte.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use h2transformation::TransformNibbleSwap;
+    ///
+    /// // Input: "\x12\x34\xab"
+    /// let i: Vec<u8> = b"\x12\x34\xab".to_vec();
+    ///
+    /// // Output: "\x21\x43\xba"
+    /// let o = TransformNibbleSwap::new().transform(&i);
+    /// assert_eq!(Ok(b"\x21\x43\xba".to_vec()), o);
+    /// ```
+    ///
+    /// # Restrictions / errors
+    ///
+    /// n/a
+    NibbleSwap(TransformNibbleSwap),
+
+    /// Translate each byte through a 256-byte table - for converting from
+    /// EBCDIC, or undoing a simple substitution.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use h2transformation::TransformTranslate;
+    ///
+    /// // Input: "Hello" in EBCDIC
+    /// let i: Vec<u8> = b"\xc8\x85\x93\x93\x96".to_vec();
+    ///
+    /// // Output: "Hello"
+    /// let o = TransformTranslate::from_ebcdic().transform(&i);
+    /// assert_eq!(Ok(b"Hello".to_vec()), o);
+    /// ```
+    ///
+    /// # Restrictions / errors
+    ///
+    /// The table must be exactly 256 bytes. It's only two-way if no two bytes
+    /// translate to the same value.
+    Translate(TransformTranslate),
+
     /// Base64 decode the buffer.
     ///
     /// [`TransformBase64`] has a number of constructors to configure the
@@ -355,6 +429,33 @@ pub enum Transformation {
     /// assert_eq!(b"Salsa20 Demo".to_vec(), result);
     /// ```
     FromStreamCipher(TransformStreamCipher),
+
+    /// XOR with the keystream from a seeded pseudo-random number generator,
+    /// such as an LCG or xorshift. Lots of games "encrypt" their files this
+    /// way.
+    ///
+    /// The generator, seed, and how many bytes of each output are used are
+    /// all set in [`TransformPrngStream`].
+    ///
+    /// ```
+    /// use h2transformation::*;
+    ///
+    /// // srand(1), then XOR each byte with the low byte of rand()
+    /// let transformation = TransformPrngStream::new(
+    ///     PrngType::msvc_rand(), // Microsoft's rand()
+    ///     1,                     // The seed
+    ///     PrngWidth::EightBit,   // Use one byte from each rand()
+    /// ).unwrap();
+    ///
+    /// let result = transformation.transform(&b"\x61\x46\xd2\xe8".to_vec()).unwrap();
+    /// assert_eq!(b"Hell".to_vec(), result);
+    /// ```
+    ///
+    /// # Restrictions / errors
+    ///
+    /// The seed has to fit in the generator, and xorshift seeds can't be
+    /// zero.
+    FromPrngStream(TransformPrngStream),
 }
 
 impl fmt::Display for Transformation {
@@ -368,16 +469,21 @@ impl Transformation {
         match self { // TODO: I think I can simplify this by moving the *
             Self::Null(s)             => Box::new(*s),
             Self::XorByConstant(s)    => Box::new(*s),
+            Self::BitReverse(s)       => Box::new(*s),
+            Self::NibbleSwap(s)       => Box::new(*s),
+            Self::Translate(s)        => Box::new(*s),
             Self::FromBase64(s)       => Box::new(*s),
             Self::FromBase32(s)       => Box::new(*s),
             Self::FromDeflated(s)     => Box::new(*s),
             Self::FromHex(s)          => Box::new(*s),
             Self::FromBlockCipher(s)  => Box::new(*s),
             Self::FromStreamCipher(s) => Box::new(*s),
+            Self::FromPrngStream(s)   => Box::new(*s),
         }
     }
 
     /// Transform a buffer into another buffer, without changing the original.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(self, buffer), fields(transformation = %self, length = buffer.len())))]
     pub fn transform(&self, buffer: &Vec<u8>) -> SimpleResult<Vec<u8>> {
         // We can never handle 0-length buffers
         if buffer.len() == 0 {
@@ -390,6 +496,7 @@ impl Transformation {
     /// Transform a buffer backwards, if possible. The length of the result will
     /// match the length of the original buffer, but the data may be normalized.
     /// The original buffer is not changed.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(self, buffer), fields(transformation = %self, length = buffer.len())))]
     pub fn untransform(&self, buffer: &Vec<u8>) -> SimpleResult<Vec<u8>> {
         // We can never handle 0-length buffers
         if buffer.len() == 0 {
@@ -426,17 +533,27 @@ impl Transformation {
     ///
     /// This is VERY expensive, as it attempts to transform using every
     /// potential variant.
+    ///
+    /// The order is always the same for the same buffer: transformers are
+    /// tried in a fixed order (simplest first), and each transformer lists
+    /// its variants from most to least common. That way, the first result is
+    /// a reasonable guess, and a UI (or a test) won't see the list shuffle
+    /// around between runs.
     pub fn detect(buffer: &Vec<u8>) -> Vec<Transformation> {
         let mut out: Vec<Transformation> = Vec::new();
 
         out.extend(TransformNull::detect(buffer));
         out.extend(TransformHex::detect(buffer));
         out.extend(TransformXorByConstant::detect(buffer));
+        out.extend(TransformBitReverse::detect(buffer));
+        out.extend(TransformNibbleSwap::detect(buffer));
+        out.extend(TransformTranslate::detect(buffer));
         out.extend(TransformBase64::detect(buffer));
         out.extend(TransformBase32::detect(buffer));
         out.extend(TransformDeflate::detect(buffer));
         out.extend(TransformBlockCipher::detect(buffer));
         out.extend(TransformStreamCipher::detect(buffer));
+        out.extend(TransformPrngStream::detect(buffer));
 
         out
     }
@@ -0,0 +1,276 @@
+use simple_error::{SimpleResult, SimpleError};
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+use crate::Transformation;
+
+/// How well a single stage survives a transform followed by an untransform.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RoundTrip {
+    /// Untransforming gives back exactly the original data
+    Exact,
+
+    /// Untransforming gives back different data that transforms to the same
+    /// thing - hex strings come back lowercase, for example
+    Normalized,
+
+    /// The transformation can't be undone at all (decompression, say)
+    OneWay,
+
+    /// Untransforming either failed, or gave back data that doesn't transform
+    /// to the same thing
+    Lost(String),
+}
+
+impl RoundTrip {
+    /// Is it safe to write changes back through this stage?
+    pub fn is_faithful(&self) -> bool {
+        matches!(self, Self::Exact | Self::Normalized)
+    }
+}
+
+impl fmt::Display for RoundTrip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact        => write!(f, "exact"),
+            Self::Normalized   => write!(f, "normalized"),
+            Self::OneWay       => write!(f, "one-way"),
+            Self::Lost(reason) => write!(f, "lost: {}", reason),
+        }
+    }
+}
+
+/// The result of checking one stage of a [`TransformPipeline`].
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct StageReport {
+    /// Which stage this is, starting at 0
+    pub index: usize,
+    pub transformation: Transformation,
+    pub round_trip: RoundTrip,
+}
+
+/// The result of [`TransformPipeline::verify`].
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PipelineReport {
+    /// One report per stage, in the order they're applied
+    pub stages: Vec<StageReport>,
+
+    /// Whether untransforming the final output through every stage gets back
+    /// to (a normalized version of) the input
+    pub round_trip: RoundTrip,
+}
+
+impl PipelineReport {
+    /// Can edits to the output be written back to the input?
+    pub fn is_faithful(&self) -> bool {
+        self.round_trip.is_faithful() && self.stages.iter().all(|s| s.round_trip.is_faithful())
+    }
+
+    /// The first stage that doesn't survive a round trip, if any.
+    pub fn first_loss(&self) -> Option<&StageReport> {
+        self.stages.iter().find(|s| !s.round_trip.is_faithful())
+    }
+}
+
+/// A list of transformations, applied one after the other - the same as a
+/// buffer that's been transformed a few times.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TransformPipeline {
+    transformations: Vec<Transformation>,
+}
+
+impl From<Vec<Transformation>> for TransformPipeline {
+    fn from(transformations: Vec<Transformation>) -> Self {
+        Self {
+            transformations: transformations,
+        }
+    }
+}
+
+impl TransformPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The same pipeline, with another stage on the end.
+    pub fn then(mut self, transformation: Transformation) -> Self {
+        self.transformations.push(transformation);
+        self
+    }
+
+    pub fn transformations(&self) -> &Vec<Transformation> {
+        &self.transformations
+    }
+
+    pub fn len(&self) -> usize {
+        self.transformations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transformations.is_empty()
+    }
+
+    /// Apply every stage, in order.
+    pub fn transform(&self, buffer: &Vec<u8>) -> SimpleResult<Vec<u8>> {
+        let mut buffer = buffer.clone();
+
+        for (index, transformation) in self.transformations.iter().enumerate() {
+            buffer = transformation.transform(&buffer).map_err(|e| {
+                SimpleError::new(format!("Stage {} ({}) failed to transform: {}", index, transformation, e))
+            })?;
+        }
+
+        Ok(buffer)
+    }
+
+    /// Undo every stage, last first.
+    pub fn untransform(&self, buffer: &Vec<u8>) -> SimpleResult<Vec<u8>> {
+        let mut buffer = buffer.clone();
+
+        for (index, transformation) in self.transformations.iter().enumerate().rev() {
+            buffer = transformation.untransform(&buffer).map_err(|e| {
+                SimpleError::new(format!("Stage {} ({}) failed to untransform: {}", index, transformation, e))
+            })?;
+        }
+
+        Ok(buffer)
+    }
+
+    /// Check one stage: `input` goes in, `output` comes out.
+    fn check(transformation: &Transformation, input: &Vec<u8>, output: &Vec<u8>) -> RoundTrip {
+        if !transformation.is_two_way() {
+            return RoundTrip::OneWay;
+        }
+
+        let back = match transformation.untransform(output) {
+            Ok(back) => back,
+            Err(e)   => return RoundTrip::Lost(format!("untransform failed: {}", e)),
+        };
+
+        if &back == input {
+            return RoundTrip::Exact;
+        }
+
+        if back.len() != input.len() {
+            return RoundTrip::Lost(format!("untransform changed the length from {} to {} bytes", input.len(), back.len()));
+        }
+
+        match transformation.transform(&back) {
+            Ok(again) if &again == output => RoundTrip::Normalized,
+            Ok(_)                         => RoundTrip::Lost("untransformed data transforms to something different".to_string()),
+            Err(e)                        => RoundTrip::Lost(format!("untransformed data can't be transformed again: {}", e)),
+        }
+    }
+
+    /// Check that `buffer` survives being transformed then untransformed, and
+    /// find where it doesn't.
+    ///
+    /// Each stage is checked on its own, then the whole pipeline is checked
+    /// end to end. Data is allowed to come back normalized (different, but
+    /// transforming to the same thing), since that doesn't change what's
+    /// being edited. This should pass before changes are written back through
+    /// a stack of transformations.
+    ///
+    /// An error means the pipeline can't transform `buffer` in the first
+    /// place.
+    pub fn verify(&self, buffer: &Vec<u8>) -> SimpleResult<PipelineReport> {
+        let mut stages: Vec<StageReport> = vec![];
+        let mut input = buffer.clone();
+
+        for (index, transformation) in self.transformations.iter().enumerate() {
+            let output = transformation.transform(&input).map_err(|e| {
+                SimpleError::new(format!("Stage {} ({}) failed to transform: {}", index, transformation, e))
+            })?;
+
+            stages.push(StageReport {
+                index: index,
+                transformation: *transformation,
+                round_trip: Self::check(transformation, &input, &output),
+            });
+
+            input = output;
+        }
+
+        // Only bother with the whole thing if every stage can be undone
+        let round_trip = match stages.iter().find(|s| !s.round_trip.is_faithful()) {
+            Some(s) => RoundTrip::Lost(format!("stage {} is {}", s.index, s.round_trip)),
+            None    => match self.untransform(&input) {
+                Ok(back) if &back == buffer => RoundTrip::Exact,
+                Ok(back) => match self.transform(&back) {
+                    Ok(again) if again == input => RoundTrip::Normalized,
+                    Ok(_)                       => RoundTrip::Lost("untransformed data transforms to something different".to_string()),
+                    Err(e)                      => RoundTrip::Lost(e.to_string()),
+                },
+                Err(e) => RoundTrip::Lost(e.to_string()),
+            },
+        };
+
+        Ok(PipelineReport {
+            stages: stages,
+            round_trip: round_trip,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    use crate::{TransformBase64, TransformHex, TransformTranslate, TransformXorByConstant, XorSettings};
+
+    #[test]
+    fn test_pipeline() -> SimpleResult<()> {
+        // Base64 of "41424344" (hex), then XOR
+        let pipeline = TransformPipeline::new()
+            .then(TransformBase64::standard())
+            .then(TransformHex::new())
+            .then(TransformXorByConstant::new(XorSettings::EightBit(0x20)));
+
+        let output = pipeline.transform(&b"NDE0MjQzNDQ=".to_vec())?;
+        assert_eq!(b"abcd".to_vec(), output);
+        assert_eq!(b"NDE0MjQzNDQ=".to_vec(), pipeline.untransform(&output)?);
+
+        let report = pipeline.verify(&b"NDE0MjQzNDQ=".to_vec())?;
+        assert!(report.is_faithful());
+        assert_eq!(None, report.first_loss());
+        assert_eq!(vec![RoundTrip::Exact, RoundTrip::Exact, RoundTrip::Exact], report.stages.iter().map(|s| s.round_trip.clone()).collect::<Vec<_>>());
+        assert_eq!(RoundTrip::Exact, report.round_trip);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_normalized() -> SimpleResult<()> {
+        // Uppercase hex comes back lowercase, but that's fine
+        let pipeline = TransformPipeline::from(vec![TransformHex::new()]);
+        let report = pipeline.verify(&b"4A4b".to_vec())?;
+
+        assert!(report.is_faithful());
+        assert_eq!(RoundTrip::Normalized, report.stages[0].round_trip);
+        assert_eq!(RoundTrip::Normalized, report.round_trip);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_lost() -> SimpleResult<()> {
+        // Uppercasing can't be undone, so the second stage loses fidelity
+        let table: Vec<u8> = (0..=255u8).map(|b| b.to_ascii_uppercase()).collect();
+        let pipeline = TransformPipeline::new()
+            .then(TransformHex::new())
+            .then(TransformTranslate::new(&table)?);
+
+        let report = pipeline.verify(&b"6869".to_vec())?;
+        assert!(!report.is_faithful());
+        assert_eq!(1, report.first_loss().unwrap().index);
+        assert_eq!(RoundTrip::Exact, report.stages[0].round_trip);
+        assert_eq!(RoundTrip::OneWay, report.stages[1].round_trip);
+        assert!(!report.round_trip.is_faithful());
+
+        // If it can't transform at all, that's an error
+        assert!(pipeline.verify(&b"zz".to_vec()).is_err());
+
+        Ok(())
+    }
+}
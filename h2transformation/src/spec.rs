@@ -0,0 +1,377 @@
+//! Parse transformations from short text specs, like
+//! `base64:url,zlib,aes-128-cbc:key=...:iv=...`.
+//!
+//! This is for the command line and scripts, where building a
+//! [`Transformation`] in Rust isn't an option.
+
+use simple_error::{SimpleResult, SimpleError, bail};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use generic_number::Integer;
+
+use crate::*;
+
+/// The options of a single stage - `name:flag:key=value:...`.
+struct StageSpec<'a> {
+    name: &'a str,
+    flags: Vec<&'a str>,
+    values: HashMap<&'a str, &'a str>,
+}
+
+impl<'a> StageSpec<'a> {
+    fn parse(spec: &'a str) -> SimpleResult<Self> {
+        let mut parts = spec.split(':').map(|part| part.trim());
+
+        let name = match parts.next() {
+            Some(name) if !name.is_empty() => name,
+            _                              => bail!("Transformation is missing a name"),
+        };
+
+        let mut flags = vec![];
+        let mut values = HashMap::new();
+        for part in parts {
+            match part.split_once('=') {
+                Some((key, value)) => {
+                    if values.insert(key, value).is_some() {
+                        bail!("Option {} is set more than once", key);
+                    }
+                },
+                None if part.is_empty() => bail!("Empty option"),
+                None                    => flags.push(part),
+            }
+        }
+
+        Ok(Self {
+            name: name,
+            flags: flags,
+            values: values,
+        })
+    }
+
+    /// Take a flag out, if it's there.
+    fn flag(&mut self, flag: &str) -> bool {
+        match self.flags.iter().position(|f| f.eq_ignore_ascii_case(flag)) {
+            Some(index) => {
+                self.flags.remove(index);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Take a `key=value` option out, if it's there.
+    fn value(&mut self, key: &str) -> Option<&'a str> {
+        self.values.remove(key)
+    }
+
+    /// Take a `key=value` option out, and fail if it isn't there.
+    fn required(&mut self, key: &str) -> SimpleResult<&'a str> {
+        self.value(key).ok_or(
+            SimpleError::new(format!("{} needs a {}=... option", self.name, key))
+        )
+    }
+
+    /// Take the single flag that's left, like the `ff` in `xor:ff`.
+    fn argument(&mut self) -> SimpleResult<&'a str> {
+        match self.flags.len() {
+            1 => Ok(self.flags.remove(0)),
+            0 => bail!("{} needs a value, like {}:...", self.name, self.name),
+            _ => bail!("{} takes one value, not {}", self.name, self.flags.len()),
+        }
+    }
+
+    /// Make sure every option was used.
+    fn finish(self, transformation: Transformation) -> SimpleResult<Transformation> {
+        if let Some(flag) = self.flags.first() {
+            bail!("Unknown option for {}: {}", self.name, flag);
+        }
+
+        let mut keys: Vec<&&str> = self.values.keys().collect();
+        keys.sort();
+        if let Some(key) = keys.first() {
+            bail!("Unknown option for {}: {}=", self.name, key);
+        }
+
+        Ok(transformation)
+    }
+}
+
+/// Decode a hex string (with or without `0x`), like a key or IV.
+fn parse_hex(name: &str, value: &str) -> SimpleResult<Vec<u8>> {
+    let value = value.strip_prefix("0x").unwrap_or(value);
+
+    hex::decode(value).map_err(|e| {
+        SimpleError::new(format!("{} must be hex: {}", name, e))
+    })
+}
+
+/// Parse a number - see [`Integer::parse`] for the formats.
+fn parse_number(name: &str, value: &str) -> SimpleResult<u64> {
+    Integer::parse(value).and_then(|i| i.to_u64()).map_err(|e| {
+        SimpleError::new(format!("{} must be a number: {}", name, e))
+    })
+}
+
+fn parse_block_cipher(stage: &mut StageSpec) -> SimpleResult<Transformation> {
+    // aes-128-cbc, aes-cbc, des-ecb, ...
+    let parts: Vec<&str> = stage.name.split('-').collect();
+    let (cipher, bits, mode) = match &parts[..] {
+        [cipher, mode]       => (*cipher, None, *mode),
+        [cipher, bits, mode] => (*cipher, Some(*bits), *mode),
+        _                    => bail!("Unknown transformation: {}", stage.name),
+    };
+
+    let cipher = match &cipher.to_lowercase()[..] {
+        "aes" => BlockCipherType::AES,
+        "des" => BlockCipherType::DES,
+        _     => bail!("Unknown block cipher: {}", cipher),
+    };
+
+    let mode = match &mode.to_lowercase()[..] {
+        "ecb" => BlockCipherMode::ECB,
+        "cbc" => BlockCipherMode::CBC,
+        "cfb" => BlockCipherMode::CFB,
+        _     => bail!("Unknown block cipher mode: {}", mode),
+    };
+
+    let padding = match stage.value("padding").map(|p| p.to_lowercase()).as_deref() {
+        None | Some("pkcs7") => BlockCipherPadding::Pkcs7,
+        Some("none")         => BlockCipherPadding::NoPadding,
+        Some("zero")         => BlockCipherPadding::ZeroPadding,
+        Some(padding)        => bail!("Unknown padding: {} (try pkcs7, none, or zero)", padding),
+    };
+
+    let key = parse_hex("key", stage.required("key")?)?;
+    let iv = stage.value("iv").map(|iv| parse_hex("iv", iv)).transpose()?;
+
+    if let Some(bits) = bits {
+        if parse_number("key size", bits)? != key.len() as u64 * 8 {
+            bail!("{} needs a {}-bit key, but the key is {} bits", stage.name, bits, key.len() * 8);
+        }
+    }
+
+    TransformBlockCipher::new(cipher, mode, padding, key, iv)
+}
+
+fn parse_prng(stage: &mut StageSpec) -> SimpleResult<Transformation> {
+    let prng = match &stage.name.to_lowercase()[..] {
+        "msvc-rand"   => PrngType::msvc_rand(),
+        "ansi-c-rand" => PrngType::ansi_c_rand(),
+        "xorshift32"  => PrngType::Xorshift32,
+        "xorshift64"  => PrngType::Xorshift64,
+        "lcg32"       => PrngType::Lcg32 {
+            multiplier: parse_number("multiplier", stage.required("multiplier")?)? as u32,
+            increment:  parse_number("increment",  stage.required("increment")?)? as u32,
+            shift:      parse_number("shift",      stage.value("shift").unwrap_or("0"))? as u8,
+            bits:       parse_number("bits",       stage.value("bits").unwrap_or("32"))? as u8,
+        },
+        _ => bail!("Unknown transformation: {}", stage.name),
+    };
+
+    let width = match stage.value("width").unwrap_or("8") {
+        "8"  => PrngWidth::EightBit,
+        "16" => PrngWidth::SixteenBit,
+        "32" => PrngWidth::ThirtyTwoBit,
+        "64" => PrngWidth::SixtyFourBit,
+        w    => bail!("Unknown width: {} (try 8, 16, 32, or 64)", w),
+    };
+
+    TransformPrngStream::new(prng, parse_number("seed", stage.required("seed")?)?, width)
+}
+
+impl Transformation {
+    /// Parse a single transformation from a spec - see
+    /// [`TransformPipeline::parse`] for the format.
+    pub fn parse(spec: &str) -> SimpleResult<Self> {
+        let mut stage = StageSpec::parse(spec)?;
+
+        let transformation = match &stage.name.to_lowercase()[..] {
+            "null"        => TransformNull::new(),
+            "hex"         => TransformHex::new(),
+            "bit-reverse" => TransformBitReverse::new(),
+            "nibble-swap" => TransformNibbleSwap::new(),
+            "ebcdic"      => TransformTranslate::from_ebcdic(),
+            "translate"   => TransformTranslate::new(&parse_hex("table", stage.required("table")?)?)?,
+
+            "base64" => {
+                let (no_padding, permissive, url) = (stage.flag("nopad"), stage.flag("permissive"), stage.flag("url"));
+                TransformBase64::new(no_padding, permissive, url)
+            },
+            "base32" => {
+                let (no_padding, permissive, crockford) = (stage.flag("nopad"), stage.flag("permissive"), stage.flag("crockford"));
+                TransformBase32::new(no_padding, permissive, crockford)
+            },
+
+            "deflate" | "inflate" => TransformDeflate::without_header(),
+            "zlib"                => TransformDeflate::with_header(),
+            "gunzip" | "gzip"     => bail!("gzip isn't supported yet - try zlib (deflate with a zlib header) or deflate (no header)"),
+
+            "xor" => {
+                let value = stage.argument()?;
+                let digits = value.strip_prefix("0x").unwrap_or(value);
+                let number = u64::from_str_radix(digits, 16).map_err(|e| {
+                    SimpleError::new(format!("xor value must be hex: {}", e))
+                })?;
+
+                // The width comes from the number of digits
+                TransformXorByConstant::new(match digits.len() {
+                    2  => XorSettings::EightBit(number as u8),
+                    4  => XorSettings::SixteenBit(number as u16),
+                    8  => XorSettings::ThirtyTwoBit(number as u32),
+                    16 => XorSettings::SixtyFourBit(number),
+                    _  => bail!("xor value must be 2, 4, 8, or 16 hex digits (8, 16, 32, or 64 bits), not {}", digits.len()),
+                })
+            },
+
+            "salsa20" | "chacha" | "arc4" | "rc4" => {
+                let cipher = match &stage.name.to_lowercase()[..] {
+                    "salsa20" => StreamCipherType::Salsa20,
+                    "chacha"  => StreamCipherType::ChaCha,
+                    _         => StreamCipherType::Arc4,
+                };
+
+                let key = parse_hex("key", stage.required("key")?)?;
+                let iv = stage.value("iv").map(|iv| parse_hex("iv", iv)).transpose()?;
+
+                TransformStreamCipher::new(cipher, key, iv)?
+            },
+
+            "msvc-rand" | "ansi-c-rand" | "xorshift32" | "xorshift64" | "lcg32" => parse_prng(&mut stage)?,
+
+            name if name.starts_with("aes-") || name.starts_with("des-") => parse_block_cipher(&mut stage)?,
+
+            _ => bail!("Unknown transformation: {}", stage.name),
+        };
+
+        stage.finish(transformation)
+    }
+}
+
+impl TransformPipeline {
+    /// Parse a pipeline from a compact spec, like
+    /// `base64:url,zlib,aes-128-cbc:key=<hex>:iv=<hex>`.
+    ///
+    /// Stages are separated by commas, and applied in order. Each stage is a
+    /// name, then options separated by colons - flags (`url`) or values
+    /// (`key=...`). Keys, IVs, and tables are hex; other numbers can be
+    /// decimal or hex (`0x`). Names and flags ignore case.
+    ///
+    /// | Stage | Options |
+    /// |---|---|
+    /// | `null`, `hex`, `bit-reverse`, `nibble-swap`, `ebcdic` | - |
+    /// | `translate` | `table=` (256 bytes) |
+    /// | `base64` | `url`, `nopad`, `permissive` |
+    /// | `base32` | `crockford`, `nopad`, `permissive` |
+    /// | `deflate` (or `inflate`), `zlib` | - |
+    /// | `xor:<hex>` | the width is from the number of digits (`xor:ff00` is 16-bit) |
+    /// | `aes-<bits>-<mode>`, `aes-<mode>`, `des-<mode>` | `key=`, `iv=`, `padding=` (`pkcs7`, `none`, or `zero`) |
+    /// | `salsa20`, `chacha`, `arc4` (or `rc4`) | `key=`, `iv=` |
+    /// | `msvc-rand`, `ansi-c-rand`, `xorshift32`, `xorshift64` | `seed=`, `width=` (8, 16, 32, or 64 bits; 8 by default) |
+    /// | `lcg32` | `multiplier=`, `increment=`, `shift=`, `bits=`, plus the above |
+    ///
+    /// Errors say which stage was wrong, and why - an unknown name, a
+    /// missing or unknown option, or settings the transformation itself
+    /// refuses (like a key that's the wrong size).
+    pub fn parse(spec: &str) -> SimpleResult<Self> {
+        if spec.trim().is_empty() {
+            bail!("Empty transformation spec");
+        }
+
+        let transformations = spec.split(',').enumerate().map(|(index, stage)| {
+            Transformation::parse(stage).map_err(|e| {
+                SimpleError::new(format!("Stage {} ({}): {}", index, stage.trim(), e))
+            })
+        }).collect::<SimpleResult<Vec<Transformation>>>()?;
+
+        Ok(Self::from(transformations))
+    }
+}
+
+impl FromStr for TransformPipeline {
+    type Err = SimpleError;
+
+    fn from_str(spec: &str) -> SimpleResult<Self> {
+        Self::parse(spec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse() -> SimpleResult<()> {
+        assert_eq!(TransformPipeline::new()
+            .then(TransformBase64::url())
+            .then(TransformDeflate::with_header())
+            .then(TransformBlockCipher::new(
+                BlockCipherType::AES,
+                BlockCipherMode::CBC,
+                BlockCipherPadding::Pkcs7,
+                b"AAAAAAAAAAAAAAAA".to_vec(),
+                Some(b"BBBBBBBBBBBBBBBB".to_vec()),
+            )?),
+            TransformPipeline::parse("base64:url,zlib,aes-128-cbc:key=41414141414141414141414141414141:iv=0x42424242424242424242424242424242")?,
+        );
+
+        let tests = vec![
+            ("hex",                             TransformHex::new()),
+            ("NULL",                            TransformNull::new()),
+            ("base64",                          TransformBase64::standard()),
+            ("base64:url:nopad",                TransformBase64::url_no_padding()),
+            (" base32 : crockford ",            TransformBase32::crockford()),
+            ("deflate",                         TransformDeflate::without_header()),
+            ("xor:ff",                          TransformXorByConstant::new(XorSettings::EightBit(0xff))),
+            ("xor:0x0100",                      TransformXorByConstant::new(XorSettings::SixteenBit(0x0100))),
+            ("ebcdic",                          TransformTranslate::from_ebcdic()),
+            ("des-ecb:key=4141414141414141:padding=none", TransformBlockCipher::new(BlockCipherType::DES, BlockCipherMode::ECB, BlockCipherPadding::NoPadding, b"AAAAAAAA".to_vec(), None)?),
+            ("rc4:key=41414141414141414141414141414141", TransformStreamCipher::new(StreamCipherType::Arc4, b"AAAAAAAAAAAAAAAA".to_vec(), None)?),
+            ("msvc-rand:seed=1",                TransformPrngStream::new(PrngType::msvc_rand(), 1, PrngWidth::EightBit)?),
+            ("xorshift32:seed=0x10:width=32",   TransformPrngStream::new(PrngType::Xorshift32, 16, PrngWidth::ThirtyTwoBit)?),
+            ("xorshift64:seed=0x1_0000_0000",   TransformPrngStream::new(PrngType::Xorshift64, 0x1_0000_0000, PrngWidth::EightBit)?),
+        ];
+
+        for (spec, expected) in tests {
+            assert_eq!(expected, Transformation::parse(spec)?, "{}", spec);
+        }
+
+        // It works end to end
+        let pipeline: TransformPipeline = "hex,msvc-rand:seed=1".parse()?;
+        assert_eq!(b"Hell".to_vec(), pipeline.transform(&b"6146d2e8".to_vec())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() -> SimpleResult<()> {
+        let tests = vec![
+            ("",                            "Empty transformation spec"),
+            ("hex,,hex",                    "Stage 1 (): Transformation is missing a name"),
+            ("rot13",                       "Stage 0 (rot13): Unknown transformation: rot13"),
+            ("hex:url",                     "Stage 0 (hex:url): Unknown option for hex: url"),
+            ("base64:url:url",              "Stage 0 (base64:url:url): Unknown option for base64: url"),
+            ("hex:a=1:a=2",                 "Stage 0 (hex:a=1:a=2): Option a is set more than once"),
+            ("xor",                         "Stage 0 (xor): xor needs a value, like xor:..."),
+            ("xor:fff",                     "Stage 0 (xor:fff): xor value must be 2, 4, 8, or 16 hex digits (8, 16, 32, or 64 bits), not 3"),
+            ("aes-128-cbc",                 "Stage 0 (aes-128-cbc): aes-128-cbc needs a key=... option"),
+            ("aes-256-cbc:key=4141",        "Stage 0 (aes-256-cbc:key=4141): aes-256-cbc needs a 256-bit key, but the key is 16 bits"),
+            ("aes-xyz:key=4141",            "Stage 0 (aes-xyz:key=4141): Unknown block cipher mode: xyz"),
+            ("msvc-rand:seed=1:width=7",    "Stage 0 (msvc-rand:seed=1:width=7): Unknown width: 7 (try 8, 16, 32, or 64)"),
+            ("msvc-rand:seed=x",            "Stage 0 (msvc-rand:seed=x): seed must be a number: Couldn't parse \"x\" as an integer: invalid digit found in string"),
+        ];
+
+        for (spec, expected) in tests {
+            assert_eq!(expected, TransformPipeline::parse(spec).unwrap_err().as_str(), "{}", spec);
+        }
+
+        assert!(TransformPipeline::parse("aes-cbc:key=zz").unwrap_err().as_str().starts_with("Stage 0 (aes-cbc:key=zz): key must be hex: "));
+
+        // Errors from the transformation itself come through too
+        assert!(TransformPipeline::parse("aes-cbc:key=4141").is_err());
+        assert!(TransformPipeline::parse("gunzip").is_err());
+
+        Ok(())
+    }
+}
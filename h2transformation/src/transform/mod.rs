@@ -11,6 +11,15 @@ mod transform_xor_by_constant;
 pub use transform_xor_by_constant::TransformXorByConstant;
 pub use transform_xor_by_constant::XorSettings;
 
+mod transform_bit_reverse;
+pub use transform_bit_reverse::TransformBitReverse;
+
+mod transform_nibble_swap;
+pub use transform_nibble_swap::TransformNibbleSwap;
+
+mod transform_translate;
+pub use transform_translate::TransformTranslate;
+
 mod transform_deflate;
 pub use transform_deflate::TransformDeflate;
 
@@ -22,3 +31,6 @@ pub use transform_block_cipher::{TransformBlockCipher, BlockCipherPadding, Block
 
 mod transform_stream_cipher;
 pub use transform_stream_cipher::{TransformStreamCipher, StreamCipherType};
+
+mod transform_prng_stream;
+pub use transform_prng_stream::{TransformPrngStream, PrngType, PrngWidth};
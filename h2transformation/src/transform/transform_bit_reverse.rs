@@ -0,0 +1,75 @@
+use simple_error::SimpleResult;
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+use crate::{Transformation, TransformerTrait};
+
+#[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Serialize, Deserialize)]
+pub struct TransformBitReverse {
+}
+
+impl TransformBitReverse {
+    pub fn new() -> Transformation {
+        Transformation::BitReverse(TransformBitReverse {})
+    }
+}
+
+impl fmt::Display for TransformBitReverse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl TransformerTrait for TransformBitReverse {
+    fn transform(&self, buffer: &Vec<u8>) -> SimpleResult<Vec<u8>> {
+        Ok(buffer.iter().map(|b| b.reverse_bits()).collect())
+    }
+
+    fn untransform(&self, buffer: &Vec<u8>) -> SimpleResult<Vec<u8>> {
+        // Reversing the bits again puts them back
+        self.transform(buffer)
+    }
+
+    fn can_transform(&self, _buffer: &Vec<u8>) -> bool {
+        true
+    }
+
+    fn is_two_way(&self) -> bool {
+        true
+    }
+
+    fn detect(_buffer: &Vec<u8>) -> Vec<Transformation> where Self: Sized {
+        // Every buffer can be bit-reversed, so there's nothing to detect
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_bit_reverse() -> SimpleResult<()> {
+        assert_eq!(true, TransformBitReverse::new().is_two_way());
+
+        let tests: Vec<(Vec<u8>, SimpleResult<Vec<u8>>)> = vec![
+            (vec![0x00],             Ok(vec![0x00])),
+            (vec![0x01],             Ok(vec![0x80])),
+            (vec![0x0f, 0xf0],       Ok(vec![0xf0, 0x0f])),
+            (vec![0x12, 0x34, 0xff], Ok(vec![0x48, 0x2c, 0xff])),
+        ];
+
+        for (test, expected) in tests {
+            assert!(TransformBitReverse::new().can_transform(&test));
+
+            let result = TransformBitReverse::new().transform(&test);
+            assert_eq!(expected, result);
+
+            let result = TransformBitReverse::new().untransform(&result?);
+            assert_eq!(Ok(test), result);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use simple_error::SimpleResult;
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+use crate::{Transformation, TransformerTrait};
+
+#[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Serialize, Deserialize)]
+pub struct TransformNibbleSwap {
+}
+
+impl TransformNibbleSwap {
+    pub fn new() -> Transformation {
+        Transformation::NibbleSwap(TransformNibbleSwap {})
+    }
+}
+
+impl fmt::Display for TransformNibbleSwap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl TransformerTrait for TransformNibbleSwap {
+    fn transform(&self, buffer: &Vec<u8>) -> SimpleResult<Vec<u8>> {
+        Ok(buffer.iter().map(|b| b.rotate_left(4)).collect())
+    }
+
+    fn untransform(&self, buffer: &Vec<u8>) -> SimpleResult<Vec<u8>> {
+        // Swapping the nibbles again puts them back
+        self.transform(buffer)
+    }
+
+    fn can_transform(&self, _buffer: &Vec<u8>) -> bool {
+        true
+    }
+
+    fn is_two_way(&self) -> bool {
+        true
+    }
+
+    fn detect(_buffer: &Vec<u8>) -> Vec<Transformation> where Self: Sized {
+        // Every buffer can be nibble-swapped, so there's nothing to detect
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_nibble_swap() -> SimpleResult<()> {
+        assert_eq!(true, TransformNibbleSwap::new().is_two_way());
+
+        let tests: Vec<(Vec<u8>, SimpleResult<Vec<u8>>)> = vec![
+            (vec![0x00],             Ok(vec![0x00])),
+            (vec![0x01],             Ok(vec![0x10])),
+            (vec![0x0f, 0xf0],       Ok(vec![0xf0, 0x0f])),
+            (vec![0x12, 0x34, 0xab], Ok(vec![0x21, 0x43, 0xba])),
+        ];
+
+        for (test, expected) in tests {
+            assert!(TransformNibbleSwap::new().can_transform(&test));
+
+            let result = TransformNibbleSwap::new().transform(&test);
+            assert_eq!(expected, result);
+
+            let result = TransformNibbleSwap::new().untransform(&result?);
+            assert_eq!(Ok(test), result);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+use simple_error::{SimpleResult, bail};
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+use crate::{Transformation, TransformerTrait};
+
+/// Which pseudo-random number generator makes the keystream?
+#[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Serialize, Deserialize)]
+pub enum PrngType {
+    /// A 32-bit linear congruential generator:
+    /// `state = state * multiplier + increment` (mod 2^32), and each output
+    /// is the `bits` bits of the state starting at bit `shift`.
+    Lcg32 { multiplier: u32, increment: u32, shift: u8, bits: u8 },
+
+    /// Marsaglia's 32-bit xorshift (shifts of 13, 17, 5); the seed can't be
+    /// zero.
+    Xorshift32,
+
+    /// Marsaglia's 64-bit xorshift (shifts of 13, 7, 17); the seed can't be
+    /// zero.
+    Xorshift64,
+}
+
+impl PrngType {
+    /// The LCG from Microsoft's C runtime `rand()` (15-bit outputs).
+    pub fn msvc_rand() -> Self {
+        Self::Lcg32 { multiplier: 214013, increment: 2531011, shift: 16, bits: 15 }
+    }
+
+    /// The LCG from the example `rand()` in the ANSI C standard (15-bit
+    /// outputs).
+    pub fn ansi_c_rand() -> Self {
+        Self::Lcg32 { multiplier: 1103515245, increment: 12345, shift: 16, bits: 15 }
+    }
+
+    /// The size of the generator's state, in bits - outputs are never
+    /// bigger than this.
+    fn state_bits(self) -> usize {
+        match self {
+            Self::Lcg32 { .. } => 32,
+            Self::Xorshift32   => 32,
+            Self::Xorshift64   => 64,
+        }
+    }
+}
+
+/// How much of each output from the generator is used as keystream. If the
+/// outputs are smaller than this, the extra bits are zero.
+#[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Serialize, Deserialize)]
+pub enum PrngWidth {
+    /// The low byte of each output
+    EightBit,
+
+    /// The low two bytes of each output, least significant first
+    SixteenBit,
+
+    /// The low four bytes of each output, least significant first
+    ThirtyTwoBit,
+
+    /// All eight bytes of each output, least significant first (only for
+    /// 64-bit generators)
+    SixtyFourBit,
+}
+
+impl PrngWidth {
+    fn bytes(self) -> usize {
+        match self {
+            Self::EightBit     => 1,
+            Self::SixteenBit   => 2,
+            Self::ThirtyTwoBit => 4,
+            Self::SixtyFourBit => 8,
+        }
+    }
+}
+
+/// Configures a keystream from a seeded pseudo-random number generator.
+///
+/// The generator is stepped before each output (the way `rand()` works), and
+/// the keystream is XORed with the data, so it works the same way in both
+/// directions.
+#[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Serialize, Deserialize)]
+pub struct TransformPrngStream {
+    prng: PrngType,
+    seed: u64,
+    width: PrngWidth,
+}
+
+impl fmt::Display for TransformPrngStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl TransformPrngStream {
+    /// Create a new instance of [`TransformPrngStream`].
+    pub fn new(prng: PrngType, seed: u64, width: PrngWidth) -> SimpleResult<Transformation> {
+        let result = TransformPrngStream {
+            prng: prng,
+            seed: seed,
+            width: width,
+        };
+
+        result.validate_settings()?;
+
+        Ok(Transformation::FromPrngStream(result))
+    }
+
+    fn validate_settings(&self) -> SimpleResult<()> {
+        match self.prng {
+            PrngType::Lcg32 { shift, bits, .. } if bits == 0 || shift as usize + bits as usize > 32 => bail!("LCG output (bits {}..{}) doesn't fit in 32 bits", shift, shift as usize + bits as usize),
+            PrngType::Lcg32 { .. } | PrngType::Xorshift32 if self.seed > u32::MAX as u64 => bail!("Seed 0x{:x} doesn't fit in a 32-bit generator", self.seed),
+            PrngType::Xorshift32 | PrngType::Xorshift64 if self.seed == 0 => bail!("Xorshift can't have a seed of zero"),
+            _ => (),
+        };
+
+        if self.width.bytes() * 8 > self.prng.state_bits() {
+            bail!("{:?} only has {} bits of state, which isn't enough for {:?}", self.prng, self.prng.state_bits(), self.width);
+        }
+
+        Ok(())
+    }
+
+    /// Generate `length` bytes of keystream.
+    fn keystream(self, length: usize) -> Vec<u8> {
+        let mut state = self.seed;
+        let mut keystream: Vec<u8> = Vec::with_capacity(length + 8);
+
+        while keystream.len() < length {
+            let output: u64 = match self.prng {
+                PrngType::Lcg32 { multiplier, increment, shift, bits } => {
+                    state = (state as u32).wrapping_mul(multiplier).wrapping_add(increment) as u64;
+                    (state >> shift) & ((1 << bits) - 1)
+                },
+                PrngType::Xorshift32 => {
+                    let mut x = state as u32;
+                    x ^= x << 13;
+                    x ^= x >> 17;
+                    x ^= x << 5;
+                    state = x as u64;
+                    state
+                },
+                PrngType::Xorshift64 => {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state
+                },
+            };
+
+            keystream.extend_from_slice(&output.to_le_bytes()[..self.width.bytes()]);
+        }
+
+        keystream.truncate(length);
+        keystream
+    }
+}
+
+impl TransformerTrait for TransformPrngStream {
+    fn transform(&self, buffer: &Vec<u8>) -> SimpleResult<Vec<u8>> {
+        self.validate_settings()?;
+
+        Ok(buffer.iter().zip(self.keystream(buffer.len())).map(|(b, k)| b ^ k).collect())
+    }
+
+    fn untransform(&self, buffer: &Vec<u8>) -> SimpleResult<Vec<u8>> {
+        // Untransform is identical to transform
+        self.transform(buffer)
+    }
+
+    fn can_transform(&self, _buffer: &Vec<u8>) -> bool {
+        self.validate_settings().is_ok()
+    }
+
+    fn is_two_way(&self) -> bool {
+        true
+    }
+
+    fn detect(_buffer: &Vec<u8>) -> Vec<Transformation> where Self: Sized {
+        // Without the seed, there's nothing to try
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_lcg() -> SimpleResult<()> {
+        // MSVC: srand(1); rand() returns 41, 18467, 6334, 26500
+        let t = TransformPrngStream::new(PrngType::msvc_rand(), 1, PrngWidth::SixteenBit)?;
+        assert_eq!(true, t.is_two_way());
+        assert_eq!(vec![41, 0, 0x23, 0x48, 0xbe, 0x18, 0x84, 0x67], t.transform(&vec![0; 8])?);
+
+        // Only the low byte of each output
+        let t = TransformPrngStream::new(PrngType::msvc_rand(), 1, PrngWidth::EightBit)?;
+        assert_eq!(vec![41, 0x23, 0xbe, 0x84], t.transform(&vec![0; 4])?);
+
+        // Round trip, with a partial output at the end
+        let t = TransformPrngStream::new(PrngType::ansi_c_rand(), 0x1234, PrngWidth::SixteenBit)?;
+        let encrypted = t.transform(&b"Hello, world!".to_vec())?;
+        assert_ne!(b"Hello, world!".to_vec(), encrypted);
+        assert_eq!(b"Hello, world!".to_vec(), t.untransform(&encrypted)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_xorshift() -> SimpleResult<()> {
+        // The first 32-bit output from a seed of 1 is 0x00042021
+        let t = TransformPrngStream::new(PrngType::Xorshift32, 1, PrngWidth::ThirtyTwoBit)?;
+        assert_eq!(vec![0x21, 0x20, 0x04, 0x00], t.transform(&vec![0; 4])?);
+
+        // The first 64-bit output from a seed of 1 is 0x40822041
+        let t = TransformPrngStream::new(PrngType::Xorshift64, 1, PrngWidth::SixtyFourBit)?;
+        assert_eq!(vec![0x41, 0x20, 0x82, 0x40, 0x00, 0x00, 0x00, 0x00], t.transform(&vec![0; 8])?);
+
+        let t = TransformPrngStream::new(PrngType::Xorshift64, 0xdeadbeef, PrngWidth::EightBit)?;
+        let encrypted = t.transform(&b"Some game data".to_vec())?;
+        assert_eq!(b"Some game data".to_vec(), t.untransform(&encrypted)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_settings() -> SimpleResult<()> {
+        // Zero seeds get stuck at zero
+        assert!(TransformPrngStream::new(PrngType::Xorshift32, 0, PrngWidth::EightBit).is_err());
+        assert!(TransformPrngStream::new(PrngType::Xorshift64, 0, PrngWidth::EightBit).is_err());
+
+        // Seeds and widths that are too big
+        assert!(TransformPrngStream::new(PrngType::Xorshift32, 0x100000000, PrngWidth::EightBit).is_err());
+        assert!(TransformPrngStream::new(PrngType::Xorshift32, 1, PrngWidth::SixtyFourBit).is_err());
+        assert!(TransformPrngStream::new(PrngType::msvc_rand(), 1, PrngWidth::SixtyFourBit).is_err());
+        assert!(TransformPrngStream::new(PrngType::Lcg32 { multiplier: 1, increment: 1, shift: 16, bits: 17 }, 1, PrngWidth::EightBit).is_err());
+        assert!(TransformPrngStream::new(PrngType::Lcg32 { multiplier: 1, increment: 1, shift: 0, bits: 0 }, 1, PrngWidth::EightBit).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+use simple_error::{SimpleResult, bail};
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+use crate::{Transformation, TransformerTrait};
+
+/// EBCDIC (code page 037) to Latin-1, which is ASCII for everything
+/// printable.
+const EBCDIC_037: [u8; 256] = [
+    0x00, 0x01, 0x02, 0x03, 0x9c, 0x09, 0x86, 0x7f, 0x97, 0x8d, 0x8e, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+    0x10, 0x11, 0x12, 0x13, 0x9d, 0x85, 0x08, 0x87, 0x18, 0x19, 0x92, 0x8f, 0x1c, 0x1d, 0x1e, 0x1f,
+    0x80, 0x81, 0x82, 0x83, 0x84, 0x0a, 0x17, 0x1b, 0x88, 0x89, 0x8a, 0x8b, 0x8c, 0x05, 0x06, 0x07,
+    0x90, 0x91, 0x16, 0x93, 0x94, 0x95, 0x96, 0x04, 0x98, 0x99, 0x9a, 0x9b, 0x14, 0x15, 0x9e, 0x1a,
+    0x20, 0xa0, 0xe2, 0xe4, 0xe0, 0xe1, 0xe3, 0xe5, 0xe7, 0xf1, 0xa2, 0x2e, 0x3c, 0x28, 0x2b, 0x7c,
+    0x26, 0xe9, 0xea, 0xeb, 0xe8, 0xed, 0xee, 0xef, 0xec, 0xdf, 0x21, 0x24, 0x2a, 0x29, 0x3b, 0xac,
+    0x2d, 0x2f, 0xc2, 0xc4, 0xc0, 0xc1, 0xc3, 0xc5, 0xc7, 0xd1, 0xa6, 0x2c, 0x25, 0x5f, 0x3e, 0x3f,
+    0xf8, 0xc9, 0xca, 0xcb, 0xc8, 0xcd, 0xce, 0xcf, 0xcc, 0x60, 0x3a, 0x23, 0x40, 0x27, 0x3d, 0x22,
+    0xd8, 0x61, 0x62, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0xab, 0xbb, 0xf0, 0xfd, 0xfe, 0xb1,
+    0xb0, 0x6a, 0x6b, 0x6c, 0x6d, 0x6e, 0x6f, 0x70, 0x71, 0x72, 0xaa, 0xba, 0xe6, 0xb8, 0xc6, 0xa4,
+    0xb5, 0x7e, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0xa1, 0xbf, 0xd0, 0xdd, 0xde, 0xae,
+    0x5e, 0xa3, 0xa5, 0xb7, 0xa9, 0xa7, 0xb6, 0xbc, 0xbd, 0xbe, 0x5b, 0x5d, 0xaf, 0xa8, 0xb4, 0xd7,
+    0x7b, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0xad, 0xf4, 0xf6, 0xf2, 0xf3, 0xf5,
+    0x7d, 0x4a, 0x4b, 0x4c, 0x4d, 0x4e, 0x4f, 0x50, 0x51, 0x52, 0xb9, 0xfb, 0xfc, 0xf9, 0xfa, 0xff,
+    0x5c, 0xf7, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0xb2, 0xd4, 0xd6, 0xd2, 0xd3, 0xd5,
+    0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0xb3, 0xdb, 0xdc, 0xd9, 0xda, 0x9f,
+];
+
+/// Serde only handles arrays up to 32 elements, so the table is stored as a
+/// list of bytes.
+mod table {
+    use serde::{Serialize, Deserialize, Serializer, Deserializer};
+    use serde::de::Error;
+    use std::convert::TryInto;
+
+    pub fn serialize<S: Serializer>(table: &[u8; 256], serializer: S) -> Result<S::Ok, S::Error> {
+        table[..].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 256], D::Error> {
+        let table = Vec::<u8>::deserialize(deserializer)?;
+        let length = table.len();
+
+        table.try_into().map_err(|_| D::Error::custom(format!("Translation table must be 256 bytes, not {}", length)))
+    }
+}
+
+/// Configures a byte-for-byte translation.
+///
+/// Each byte `b` becomes `table[b]`. If no two bytes translate to the same
+/// thing, the table can be inverted to go back; otherwise, it's a one-way
+/// transformation.
+#[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Serialize, Deserialize)]
+pub struct TransformTranslate {
+    #[serde(with = "table")]
+    table: [u8; 256],
+}
+
+impl fmt::Display for TransformTranslate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TransformTranslate {{ table: {} }}", hex::encode(&self.table[..]))
+    }
+}
+
+impl TransformTranslate {
+    /// Create a new instance of [`TransformTranslate`] from a 256-byte table.
+    pub fn new(table: &[u8]) -> SimpleResult<Transformation> {
+        if table.len() != 256 {
+            bail!("Translation table must be 256 bytes, not {}", table.len());
+        }
+
+        let mut a = [0; 256];
+        a.copy_from_slice(table);
+
+        Ok(Transformation::Translate(TransformTranslate {
+            table: a,
+        }))
+    }
+
+    /// Convert EBCDIC (code page 037, the usual US one) to ASCII / Latin-1.
+    pub fn from_ebcdic() -> Transformation {
+        Transformation::Translate(TransformTranslate {
+            table: EBCDIC_037,
+        })
+    }
+
+    /// The table that undoes this one, if there is one.
+    fn inverse(&self) -> Option<[u8; 256]> {
+        let mut inverse: [Option<u8>; 256] = [None; 256];
+
+        for (from, to) in self.table.iter().enumerate() {
+            // Two bytes translate to the same thing, so we can't go back
+            if inverse[*to as usize].is_some() {
+                return None;
+            }
+
+            inverse[*to as usize] = Some(from as u8);
+        }
+
+        // Since there are 256 entries and no duplicates, every one is filled
+        let mut out = [0; 256];
+        for (i, b) in inverse.iter().enumerate() {
+            out[i] = (*b)?;
+        }
+
+        Some(out)
+    }
+}
+
+impl TransformerTrait for TransformTranslate {
+    fn transform(&self, buffer: &Vec<u8>) -> SimpleResult<Vec<u8>> {
+        Ok(buffer.iter().map(|b| self.table[*b as usize]).collect())
+    }
+
+    fn untransform(&self, buffer: &Vec<u8>) -> SimpleResult<Vec<u8>> {
+        let inverse = match self.inverse() {
+            Some(i) => i,
+            None    => bail!("Translation table maps more than one byte to the same value, so it can't be undone"),
+        };
+
+        Ok(buffer.iter().map(|b| inverse[*b as usize]).collect())
+    }
+
+    fn can_transform(&self, _buffer: &Vec<u8>) -> bool {
+        true
+    }
+
+    fn is_two_way(&self) -> bool {
+        self.inverse().is_some()
+    }
+
+    fn detect(_buffer: &Vec<u8>) -> Vec<Transformation> where Self: Sized {
+        // Every table works on every buffer, so there's nothing to detect
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_ebcdic() -> SimpleResult<()> {
+        let t = TransformTranslate::from_ebcdic();
+        assert_eq!(true, t.is_two_way());
+
+        // "Hello, World 123" in EBCDIC
+        let ebcdic = b"\xc8\x85\x93\x93\x96\x6b\x40\xe6\x96\x99\x93\x84\x40\xf1\xf2\xf3".to_vec();
+        assert_eq!(b"Hello, World 123".to_vec(), t.transform(&ebcdic)?);
+        assert_eq!(ebcdic, t.untransform(&b"Hello, World 123".to_vec())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_table() -> SimpleResult<()> {
+        // A simple substitution: rotate every byte by one
+        let table: Vec<u8> = (0..=255u8).map(|b| b.wrapping_add(1)).collect();
+        let t = TransformTranslate::new(&table)?;
+        assert_eq!(true, t.is_two_way());
+        assert_eq!(vec![0x01, 0x42, 0x00], t.transform(&vec![0x00, 0x41, 0xff])?);
+        assert_eq!(vec![0x00, 0x41, 0xff], t.untransform(&vec![0x01, 0x42, 0x00])?);
+
+        // Uppercasing loses information, so it can't be undone
+        let table: Vec<u8> = (0..=255u8).map(|b| b.to_ascii_uppercase()).collect();
+        let t = TransformTranslate::new(&table)?;
+        assert_eq!(false, t.is_two_way());
+        assert_eq!(b"HELLO".to_vec(), t.transform(&b"HeLlo".to_vec())?);
+        assert!(t.untransform(&b"HELLO".to_vec()).is_err());
+
+        // Tables have to be exactly 256 bytes
+        assert!(TransformTranslate::new(&[0; 255]).is_err());
+        assert!(TransformTranslate::new(&[0; 257]).is_err());
+
+        Ok(())
+    }
+}
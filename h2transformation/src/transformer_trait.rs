@@ -27,5 +27,10 @@ pub trait TransformerTrait: fmt::Display {
     /// exact content.
     fn is_two_way(&self) -> bool;
 
+    /// Get the variants of this transformer that work on `buffer`.
+    ///
+    /// Variants must come out in a fixed order, most common first - don't
+    /// build the list from anything with an arbitrary iteration order (like a
+    /// `HashMap`). See [`Transformation::detect`].
     fn detect(buffer: &Vec<u8>) -> Vec<Transformation> where Self: Sized;
 }